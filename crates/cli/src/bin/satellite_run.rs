@@ -5,7 +5,7 @@
 ///
 /// A minimal executable focused solely on running JavaScript/TypeScript files.
 /// Designed for container instances where only execution capability is needed.
-use andromeda::{CliError, CliResult};
+use andromeda::{CliError, CliResult, permissions::PermissionFlags};
 use andromeda_core::RuntimeFile;
 use clap::Parser as ClapParser;
 
@@ -22,6 +22,9 @@ struct Cli {
     #[arg(short = 's', long)]
     no_strict: bool,
 
+    #[command(flatten)]
+    permissions: PermissionFlags,
+
     /// The files to run
     #[arg(required = true)]
     paths: Vec<String>,
@@ -43,8 +46,13 @@ fn main() -> CliResult<()> {
         .map(|path| RuntimeFile::Local { path })
         .collect();
 
-    andromeda::run::run(cli.verbose, cli.no_strict, runtime_files)
-        .map_err(|e| CliError::TestExecution(format!("{e}")))?;
+    andromeda::run::run(
+        cli.verbose,
+        cli.no_strict,
+        cli.permissions.into_options(),
+        runtime_files,
+    )
+    .map_err(|e| CliError::TestExecution(format!("{e}")))?;
 
     Ok(())
 }
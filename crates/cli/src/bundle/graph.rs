@@ -0,0 +1,179 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use std::{
+    collections::{HashMap, VecDeque},
+    error::Error,
+    path::{Path, PathBuf},
+};
+
+use andromeda_core::{ImportType, ModuleCache, ModuleResolver, wrap_commonjs};
+use oxc_allocator::Allocator;
+use oxc_codegen::{Codegen, CodegenOptions};
+use oxc_parser::Parser;
+use oxc_semantic::SemanticBuilder;
+use oxc_sourcemap::SourceMap;
+use oxc_span::SourceType;
+use oxc_transformer::{TransformOptions, Transformer};
+
+use super::module::BundleModule;
+
+/// The modules of a bundle, in the order they were loaded from the entry
+pub(super) struct BundleGraph {
+    pub modules: Vec<BundleModule>,
+    /// The module each request of a module resolves to
+    pub resolved: Vec<Vec<usize>>,
+    /// The source map of each module compiled from TypeScript or JSX, from its
+    /// code to its original source
+    pub source_maps: Vec<Option<SourceMap>>,
+}
+
+/// Load the module at `entry` and every module it imports, resolving local,
+/// import-mapped, package and remote specifiers the way the runtime does
+pub(super) fn load_graph(
+    entry: &str,
+    resolver: &ModuleResolver,
+    cache: &ModuleCache,
+) -> Result<BundleGraph, Box<dyn Error>> {
+    let mut graph = BundleGraph {
+        modules: Vec::new(),
+        resolved: Vec::new(),
+        source_maps: Vec::new(),
+    };
+    let mut indices = HashMap::from([((entry.to_string(), ImportType::JavaScript), 0)]);
+    let mut queue = VecDeque::from([(entry.to_string(), ImportType::JavaScript)]);
+
+    while let Some((specifier, import_type)) = queue.pop_front() {
+        let (code, source_map) = load_module(&specifier, import_type, resolver, cache)?;
+        let module = BundleModule::analyze(specifier.clone(), &code, &module_stem(&specifier))?;
+
+        let mut resolved = Vec::with_capacity(module.requests.len());
+        for request in &module.requests {
+            let target = resolver
+                .resolve(&request.specifier, &specifier)
+                .map_err(|path| format!("Module not found: {path} imported by {specifier}"))?;
+            let import_type = ImportType::from_attribute(&target, request.import_type.as_deref())?;
+            let next = indices.len();
+            let index = *indices
+                .entry((target.clone(), import_type))
+                .or_insert_with(|| {
+                    queue.push_back((target, import_type));
+                    next
+                });
+            resolved.push(index);
+        }
+
+        graph.modules.push(module);
+        graph.resolved.push(resolved);
+        graph.source_maps.push(source_map);
+    }
+
+    Ok(graph)
+}
+
+/// An identifier made of the file name of a module, for the names the bundle
+/// declares for it
+pub(super) fn module_stem(specifier: &str) -> String {
+    let path = module_path(specifier);
+    let stem = Path::new(&path)
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .unwrap_or("module");
+    let mut name: String = stem
+        .chars()
+        .map(|c| {
+            if c.is_alphanumeric() || c == '_' || c == '$' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    if name.is_empty() || name.starts_with(|c: char| c.is_ascii_digit()) {
+        name.insert(0, '_');
+    }
+    name
+}
+
+/// The JavaScript code of the module at `specifier` imported as `import_type`:
+/// CommonJS modules are wrapped into ES modules, and TypeScript and JSX are
+/// compiled to JavaScript along with a source map
+fn load_module(
+    specifier: &str,
+    import_type: ImportType,
+    resolver: &ModuleResolver,
+    cache: &ModuleCache,
+) -> Result<(String, Option<SourceMap>), Box<dyn Error>> {
+    let is_remote = specifier.starts_with("http://") || specifier.starts_with("https://");
+    let (content, content_type) = if is_remote {
+        let module = cache.load_remote(specifier)?;
+        (module.content, module.content_type)
+    } else {
+        let content = std::fs::read(specifier)
+            .map_err(|e| format!("Failed to read module {specifier}: {e}"))?;
+        (content, None)
+    };
+
+    import_type.check(specifier, content_type.as_deref())?;
+    let source_text = import_type.module_source(specifier, &content)?;
+    if import_type != ImportType::JavaScript {
+        return Ok((source_text, None));
+    }
+
+    if !is_remote && resolver.npm.is_commonjs(Path::new(specifier)) {
+        return Ok((wrap_commonjs(&resolver.npm, specifier, &source_text), None));
+    }
+    Ok(compile(specifier, source_text)?)
+}
+
+/// Compile a TypeScript or JSX module to JavaScript, with the source map from
+/// the JavaScript to the module
+fn compile(specifier: &str, source_text: String) -> Result<(String, Option<SourceMap>), String> {
+    let path = module_path(specifier);
+    // Extensionless URLs are JavaScript
+    let Ok(source_type) = SourceType::from_path(&path) else {
+        return Ok((source_text, None));
+    };
+    if !source_type.is_typescript() && !source_type.is_jsx() {
+        return Ok((source_text, None));
+    }
+
+    let allocator = Allocator::default();
+    let ret = Parser::new(&allocator, &source_text, source_type.with_module(true)).parse();
+    if let Some(error) = ret.errors.first() {
+        return Err(format!("Failed to parse {specifier}: {error}"));
+    }
+    let mut program = ret.program;
+
+    let scoping = SemanticBuilder::new()
+        .build(&program)
+        .semantic
+        .into_scoping();
+    let ret = Transformer::new(&allocator, Path::new(&path), &TransformOptions::default())
+        .build_with_scoping(scoping, &mut program);
+    if let Some(error) = ret.errors.first() {
+        return Err(format!("Failed to transform {specifier}: {error}"));
+    }
+
+    let ret = Codegen::new()
+        .with_options(CodegenOptions {
+            source_map_path: Some(PathBuf::from(&path)),
+            ..CodegenOptions::default()
+        })
+        .build(&program);
+    Ok((ret.code, ret.map))
+}
+
+/// The path of a module, or its URL without the query and fragment
+fn module_path(specifier: &str) -> String {
+    if specifier.contains("://") {
+        specifier
+            .split(['?', '#'])
+            .next()
+            .unwrap_or(specifier)
+            .to_string()
+    } else {
+        specifier.to_string()
+    }
+}
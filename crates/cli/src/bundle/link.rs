@@ -0,0 +1,803 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+
+use crate::config::{BundleConfig, BundleFormat};
+
+use super::graph::{BundleGraph, module_stem};
+use super::module::{Edit, ImportName};
+use super::source_map::Mapping;
+
+/// A file of the bundle
+pub(super) struct Chunk {
+    pub file_name: String,
+    pub code: String,
+    /// Where the code of the modules was copied to, in order
+    pub mappings: Vec<Mapping>,
+}
+
+/// What an import or export refers to, once the imports and re-exports are
+/// followed to the module declaring it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Binding {
+    /// A top-level symbol of a module
+    Symbol(usize, usize),
+    /// The namespace object of a module
+    Namespace(usize),
+}
+
+/// A chunk being linked
+#[derive(Default)]
+struct ChunkPlan {
+    file_name: String,
+    /// The module whose exports the chunk exports
+    entry: Option<usize>,
+    modules: Vec<usize>,
+    /// The names imported from each other chunk
+    imports: BTreeMap<usize, BTreeSet<String>>,
+    /// The names other chunks import from this one
+    shared: BTreeSet<String>,
+}
+
+/// Globals referenced by the code the bundler adds
+const RESERVED_NAMES: &[&str] = &["Object", "Promise", "Symbol", "globalThis"];
+
+/// Link the modules of `graph` into chunks, the first one being the entry
+/// chunk named `file_name`
+pub(super) fn link(
+    graph: &BundleGraph,
+    config: &BundleConfig,
+    file_name: &str,
+) -> Result<Vec<Chunk>, String> {
+    let mut linker = Linker {
+        graph,
+        config,
+        active: vec![false; graph.modules.len()],
+        included: graph
+            .modules
+            .iter()
+            .map(|module| vec![false; module.statements.len()])
+            .collect(),
+        order: Vec::new(),
+        dynamic_entries: Vec::new(),
+        namespaces: BTreeSet::new(),
+        names: HashMap::new(),
+        chunk_of: vec![None; graph.modules.len()],
+        entry_chunks: HashMap::new(),
+        chunks: Vec::new(),
+    };
+    linker.mark()?;
+    linker.split(file_name);
+    linker.assign_names();
+    linker.render()
+}
+
+struct Linker<'a> {
+    graph: &'a BundleGraph,
+    config: &'a BundleConfig,
+    /// The modules that are loaded, statically or by an included dynamic import
+    active: Vec<bool>,
+    /// The statements of each module that are kept
+    included: Vec<Vec<bool>>,
+    /// The active modules in evaluation order
+    order: Vec<usize>,
+    /// The modules imported by the included dynamic imports
+    dynamic_entries: Vec<usize>,
+    /// The modules whose namespace object is used
+    namespaces: BTreeSet<usize>,
+    /// The top-level names of the bindings in the bundle
+    names: HashMap<Binding, String>,
+    chunk_of: Vec<Option<usize>>,
+    /// The chunk exporting the exports of each entry module
+    entry_chunks: HashMap<usize, usize>,
+    chunks: Vec<ChunkPlan>,
+}
+
+impl Linker<'_> {
+    /// Tree-shake the bundle: keep the statements with side effects of the
+    /// loaded modules, and the declarations of the bindings they use and that
+    /// the entry exports
+    fn mark(&mut self) -> Result<(), String> {
+        let graph = self.graph;
+        let mut queue = Vec::new();
+        let mut marked = HashSet::new();
+        self.activate(0, &mut queue)?;
+        queue.extend(self.export_names(0)?.into_values());
+
+        // Dynamically imported modules are loaded after the static ones
+        let mut activated_entries = 0;
+        loop {
+            while let Some(binding) = queue.pop() {
+                if !marked.insert(binding) {
+                    continue;
+                }
+                match binding {
+                    Binding::Symbol(module, symbol) => {
+                        if let Some(statement) = graph.modules[module].symbols[symbol].statement {
+                            self.include(module, statement, &mut queue)?;
+                        }
+                    }
+                    Binding::Namespace(module) => {
+                        self.namespaces.insert(module);
+                        queue.extend(self.export_names(module)?.into_values());
+                    }
+                }
+            }
+
+            let Some(&entry) = self.dynamic_entries.get(activated_entries) else {
+                break;
+            };
+            activated_entries += 1;
+            self.activate(entry, &mut queue)?;
+            queue.extend(self.export_names(entry)?.into_values());
+        }
+        Ok(())
+    }
+
+    /// Load a module after its static dependencies, keeping its statements
+    /// with side effects
+    fn activate(&mut self, module: usize, queue: &mut Vec<Binding>) -> Result<(), String> {
+        if self.active[module] {
+            return Ok(());
+        }
+        self.active[module] = true;
+        let graph = self.graph;
+        for &request in &graph.modules[module].static_requests {
+            self.activate(graph.resolved[module][request], queue)?;
+        }
+        self.order.push(module);
+
+        for (index, statement) in graph.modules[module].statements.iter().enumerate() {
+            if statement.has_side_effects || !self.config.tree_shaking {
+                self.include(module, index, queue)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn include(
+        &mut self,
+        module: usize,
+        statement: usize,
+        queue: &mut Vec<Binding>,
+    ) -> Result<(), String> {
+        if self.included[module][statement] {
+            return Ok(());
+        }
+        self.included[module][statement] = true;
+
+        let graph = self.graph;
+        let statement = &graph.modules[module].statements[statement];
+        for &symbol in &statement.symbols {
+            queue.push(self.resolve_symbol(module, symbol)?);
+        }
+        for edit in &statement.edits {
+            if let Edit::DynamicImport(_, request) = edit {
+                let target = graph.resolved[module][*request];
+                if !self.dynamic_entries.contains(&target) {
+                    self.dynamic_entries.push(target);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Assign the active modules to chunks. With code splitting, the modules
+    /// loaded by the entry go in its chunk, and the others are grouped by the
+    /// set of dynamic imports loading them.
+    fn split(&mut self, file_name: &str) {
+        self.chunks.push(ChunkPlan {
+            file_name: file_name.to_string(),
+            entry: Some(0),
+            ..ChunkPlan::default()
+        });
+        self.entry_chunks.insert(0, 0);
+
+        let splitting = self.config.splitting && self.config.format == BundleFormat::Esm;
+        if splitting {
+            let main = self.reachable(0);
+            let mut loaded_by = vec![Vec::new(); self.graph.modules.len()];
+            for (index, &entry) in self.dynamic_entries.iter().enumerate() {
+                for (module, reached) in self.reachable(entry).into_iter().enumerate() {
+                    if reached {
+                        loaded_by[module].push(index);
+                    }
+                }
+            }
+
+            // The chunks of the dynamic imports come first, to be named after them
+            let mut chunk_keys = HashMap::new();
+            for (index, &entry) in self.dynamic_entries.clone().iter().enumerate() {
+                if main[entry] {
+                    continue;
+                }
+                let stem = module_stem(&self.graph.modules[entry].specifier);
+                let chunk = self.add_chunk(&stem, Some(entry));
+                chunk_keys.insert(vec![index], chunk);
+                self.entry_chunks.insert(entry, chunk);
+            }
+
+            for &module in &self.order.clone() {
+                let chunk = if main[module] {
+                    0
+                } else {
+                    match chunk_keys.get(&loaded_by[module]) {
+                        Some(&chunk) => chunk,
+                        None => {
+                            let chunk = self.add_chunk("shared", None);
+                            chunk_keys.insert(loaded_by[module].clone(), chunk);
+                            chunk
+                        }
+                    }
+                };
+                self.chunk_of[module] = Some(chunk);
+            }
+        } else {
+            for &module in &self.order {
+                self.chunk_of[module] = Some(0);
+            }
+        }
+
+        for &module in &self.order {
+            if let Some(chunk) = self.chunk_of[module] {
+                self.chunks[chunk].modules.push(module);
+            }
+        }
+    }
+
+    fn add_chunk(&mut self, stem: &str, entry: Option<usize>) -> usize {
+        let mut file_name = format!("{stem}.chunk.js");
+        let mut count = 1;
+        while self.chunks.iter().any(|chunk| chunk.file_name == file_name) {
+            count += 1;
+            file_name = format!("{stem}-{count}.chunk.js");
+        }
+        self.chunks.push(ChunkPlan {
+            file_name,
+            entry,
+            ..ChunkPlan::default()
+        });
+        self.chunks.len() - 1
+    }
+
+    /// The modules `entry` loads statically, including itself
+    fn reachable(&self, entry: usize) -> Vec<bool> {
+        let mut reached = vec![false; self.graph.modules.len()];
+        let mut stack = vec![entry];
+        while let Some(module) = stack.pop() {
+            if std::mem::replace(&mut reached[module], true) {
+                continue;
+            }
+            for &request in &self.graph.modules[module].static_requests {
+                stack.push(self.graph.resolved[module][request]);
+            }
+        }
+        reached
+    }
+
+    /// Give every kept top-level binding a name that is unique in the bundle,
+    /// and that neither shadows nor is shadowed by the names of other modules
+    fn assign_names(&mut self) {
+        let graph = self.graph;
+        let mut used: HashSet<String> =
+            RESERVED_NAMES.iter().map(|name| name.to_string()).collect();
+        for module in &graph.modules {
+            used.extend(module.globals.iter().cloned());
+        }
+
+        // Dynamic imports of modules without their own chunk resolve to their namespace
+        for &entry in &self.dynamic_entries {
+            if entry == 0 || !self.entry_chunks.contains_key(&entry) {
+                self.namespaces.insert(entry);
+            }
+        }
+
+        for &module in &self.order {
+            for (index, symbol) in graph.modules[module].symbols.iter().enumerate() {
+                if symbol.import.is_some()
+                    || !symbol
+                        .statement
+                        .is_some_and(|statement| self.included[module][statement])
+                {
+                    continue;
+                }
+                let name = self.unique_name(&symbol.name, Some(module), &mut used);
+                self.names.insert(Binding::Symbol(module, index), name);
+            }
+        }
+
+        for &module in &self.namespaces {
+            let stem = module_stem(&graph.modules[module].specifier);
+            let name = self.unique_name(&format!("{stem}_ns"), None, &mut used);
+            self.names.insert(Binding::Namespace(module), name);
+        }
+    }
+
+    /// `name` for a binding of `module`, or `name$1`, `name$2`, ... when another
+    /// binding of the bundle uses it
+    fn unique_name(&self, name: &str, module: Option<usize>, used: &mut HashSet<String>) -> String {
+        let is_free =
+            |candidate: &str, module: Option<usize>| {
+                !used.contains(candidate)
+                    && self.graph.modules.iter().enumerate().all(|(index, other)| {
+                        Some(index) == module || !other.names.contains(candidate)
+                    })
+            };
+
+        let mut candidate = name.to_string();
+        let mut count = 0;
+        // The module's own nested bindings only shadow its original name
+        while !is_free(&candidate, module.filter(|_| count == 0)) {
+            count += 1;
+            candidate = format!("{name}${count}");
+        }
+        used.insert(candidate.clone());
+        candidate
+    }
+
+    fn render(&mut self) -> Result<Vec<Chunk>, String> {
+        let graph = self.graph;
+        let mut bodies = Vec::with_capacity(self.chunks.len());
+        for chunk in 0..self.chunks.len() {
+            let mut body = String::new();
+            let mut mappings = Vec::new();
+            for module in self.chunks[chunk].modules.clone() {
+                for statement in 0..graph.modules[module].statements.len() {
+                    if self.included[module][statement] {
+                        self.render_statement(module, statement, chunk, &mut body, &mut mappings)?;
+                    }
+                }
+                if self.namespaces.contains(&module) {
+                    let object = self.namespace_object(module, chunk)?;
+                    let name = &self.names[&Binding::Namespace(module)];
+                    body.push_str(&format!("const {name} = {object};\n"));
+                }
+            }
+            bodies.push((body, mappings));
+        }
+
+        if self.config.format == BundleFormat::Iife {
+            let exports = match &self.config.global_name {
+                Some(_) => format!("return {};\n", self.namespace_object(0, 0)?),
+                None => String::new(),
+            };
+            let (body, mut mappings) = bodies.swap_remove(0);
+            let prefix = match &self.config.global_name {
+                Some(global_name) => format!("{} = ", global_target(global_name)),
+                None => String::new(),
+            };
+            let header = format!("{prefix}(function () {{\n\"use strict\";\n");
+            shift_mappings(&mut mappings, header.len());
+            let code = format!("{header}{body}{exports}}})();\n");
+            return Ok(vec![Chunk {
+                file_name: self.chunks[0].file_name.clone(),
+                code,
+                mappings,
+            }]);
+        }
+
+        // The exports of the entry modules, then the names shared with other chunks
+        let mut exports: Vec<BTreeMap<String, String>> = vec![BTreeMap::new(); self.chunks.len()];
+        for chunk in 0..self.chunks.len() {
+            if let Some(entry) = self.chunks[chunk].entry {
+                for (name, binding) in self.export_names(entry)? {
+                    let local = self.reference(binding, chunk);
+                    exports[chunk].insert(name, local);
+                }
+            }
+        }
+        for (chunk, exports) in exports.iter_mut().enumerate() {
+            for local in &self.chunks[chunk].shared {
+                if exports.values().any(|exported| exported == local) {
+                    continue;
+                }
+                let mut name = local.clone();
+                while exports.contains_key(&name) {
+                    name.push('_');
+                }
+                exports.insert(name, local.clone());
+            }
+        }
+
+        let mut chunks = Vec::with_capacity(self.chunks.len());
+        for (chunk, (body, mut mappings)) in bodies.into_iter().enumerate() {
+            let mut code = String::new();
+            for dependency in self.chunk_dependencies(chunk) {
+                let file_name = &self.chunks[dependency].file_name;
+                let imports = self.chunks[chunk].imports.get(&dependency);
+                let specifiers: Vec<String> = imports
+                    .into_iter()
+                    .flatten()
+                    .map(|local| {
+                        let exported = exports[dependency]
+                            .iter()
+                            .find(|(_, exported)| *exported == local)
+                            .map(|(name, _)| name.as_str())
+                            .unwrap_or(local.as_str());
+                        import_specifier(exported, local)
+                    })
+                    .collect();
+                if specifiers.is_empty() {
+                    code.push_str(&format!("import \"./{file_name}\";\n"));
+                } else {
+                    code.push_str(&format!(
+                        "import {{ {} }} from \"./{file_name}\";\n",
+                        specifiers.join(", ")
+                    ));
+                }
+            }
+            shift_mappings(&mut mappings, code.len());
+            code.push_str(&body);
+            if !exports[chunk].is_empty() {
+                let specifiers: Vec<String> = exports[chunk]
+                    .iter()
+                    .map(|(name, local)| export_specifier(name, local))
+                    .collect();
+                code.push_str(&format!("export {{ {} }};\n", specifiers.join(", ")));
+            }
+            chunks.push(Chunk {
+                file_name: self.chunks[chunk].file_name.clone(),
+                code,
+                mappings,
+            });
+        }
+        Ok(chunks)
+    }
+
+    /// The chunks a chunk imports, because it uses their bindings or its modules
+    /// import their modules, in evaluation order
+    fn chunk_dependencies(&self, chunk: usize) -> Vec<usize> {
+        let mut dependencies: BTreeSet<usize> =
+            self.chunks[chunk].imports.keys().copied().collect();
+        for &module in &self.chunks[chunk].modules {
+            for &request in &self.graph.modules[module].static_requests {
+                if let Some(other) = self.chunk_of[self.graph.resolved[module][request]]
+                    && other != chunk
+                {
+                    dependencies.insert(other);
+                }
+            }
+        }
+        let mut dependencies: Vec<usize> = dependencies.into_iter().collect();
+        dependencies.sort_by_key(|&dependency| {
+            self.chunks[dependency]
+                .modules
+                .first()
+                .and_then(|first| self.order.iter().position(|module| module == first))
+        });
+        dependencies
+    }
+
+    /// Render a statement of a module into `output`, recording where its code
+    /// came from in `mappings`
+    fn render_statement(
+        &mut self,
+        module: usize,
+        index: usize,
+        chunk: usize,
+        output: &mut String,
+        mappings: &mut Vec<Mapping>,
+    ) -> Result<(), String> {
+        let graph = self.graph;
+        let bundle_module = &graph.modules[module];
+        let statement = &bundle_module.statements[index];
+        let code = &bundle_module.code;
+
+        let mut edits: Vec<&Edit> = statement.edits.iter().collect();
+        edits.sort_by_key(|edit| edit.span().start);
+
+        let mut position = statement.span.start;
+        for edit in edits {
+            let span = edit.span();
+            if span.start < position {
+                continue;
+            }
+            copy_code(output, mappings, module, code, position, span.start);
+            mappings.push(Mapping {
+                generated: output.len(),
+                module,
+                offset: span.start,
+            });
+            match edit {
+                Edit::Replace(_, text) => output.push_str(text),
+                Edit::Symbol(_, symbol, shorthand) => {
+                    let binding = self.resolve_symbol(module, *symbol)?;
+                    let name = self.reference(binding, chunk);
+                    let key = &code[span.start as usize..span.end as usize];
+                    if *shorthand && key != name {
+                        output.push_str(&format!("{key}: {name}"));
+                    } else {
+                        output.push_str(&name);
+                    }
+                }
+                Edit::DeclareDefault(_, symbol) => {
+                    let name = self.reference(Binding::Symbol(module, *symbol), chunk);
+                    output.push_str(&format!("const {name} = "));
+                }
+                Edit::DynamicImport(_, request) => {
+                    let import = self.dynamic_import(graph.resolved[module][*request], chunk);
+                    output.push_str(&import);
+                }
+            }
+            position = span.end;
+        }
+        copy_code(output, mappings, module, code, position, statement.span.end);
+        if statement.needs_semicolon {
+            output.push(';');
+        }
+        output.push('\n');
+        Ok(())
+    }
+
+    /// The name of a binding in `chunk`, imported from the chunk declaring it
+    fn reference(&mut self, binding: Binding, chunk: usize) -> String {
+        let (module, name) = match binding {
+            Binding::Symbol(module, symbol) => (
+                module,
+                self.names
+                    .get(&binding)
+                    .cloned()
+                    .unwrap_or_else(|| self.graph.modules[module].symbols[symbol].name.clone()),
+            ),
+            Binding::Namespace(module) => (module, self.names[&binding].clone()),
+        };
+        if let Some(other) = self.chunk_of[module]
+            && other != chunk
+        {
+            self.chunks[chunk]
+                .imports
+                .entry(other)
+                .or_default()
+                .insert(name.clone());
+            self.chunks[other].shared.insert(name.clone());
+        }
+        name
+    }
+
+    /// A dynamic import of `target`, which loads its chunk, or resolves to its
+    /// namespace object when it is bundled in the same chunk
+    fn dynamic_import(&mut self, target: usize, chunk: usize) -> String {
+        match self.entry_chunks.get(&target) {
+            Some(&entry_chunk) if entry_chunk != chunk => {
+                format!("import(\"./{}\")", self.chunks[entry_chunk].file_name)
+            }
+            _ => {
+                let namespace = self.reference(Binding::Namespace(target), chunk);
+                format!("Promise.resolve().then(() => {namespace})")
+            }
+        }
+    }
+
+    /// A frozen object with a getter for each export of the module, as the
+    /// bindings may change
+    fn namespace_object(&mut self, module: usize, chunk: usize) -> Result<String, String> {
+        let mut properties = vec![
+            "__proto__: null".to_string(),
+            "[Symbol.toStringTag]: \"Module\"".to_string(),
+        ];
+        for (name, binding) in self.export_names(module)? {
+            let local = self.reference(binding, chunk);
+            properties.push(format!(
+                "get {}() {{ return {local}; }}",
+                json_string(&name)
+            ));
+        }
+        Ok(format!("Object.freeze({{ {} }})", properties.join(", ")))
+    }
+
+    fn resolve_symbol(&self, module: usize, symbol: usize) -> Result<Binding, String> {
+        self.follow_symbol(module, symbol, &mut Vec::new())
+    }
+
+    fn follow_symbol(
+        &self,
+        module: usize,
+        symbol: usize,
+        visited: &mut Vec<(usize, String)>,
+    ) -> Result<Binding, String> {
+        match &self.graph.modules[module].symbols[symbol].import {
+            None => Ok(Binding::Symbol(module, symbol)),
+            Some((request, imported)) => {
+                self.follow_import(self.graph.resolved[module][*request], imported, visited)
+            }
+        }
+    }
+
+    fn follow_import(
+        &self,
+        target: usize,
+        imported: &ImportName,
+        visited: &mut Vec<(usize, String)>,
+    ) -> Result<Binding, String> {
+        match imported {
+            ImportName::Namespace => Ok(Binding::Namespace(target)),
+            ImportName::Named(name) => {
+                self.follow_export(target, name, visited)?.ok_or_else(|| {
+                    format!(
+                        "\"{name}\" is not exported by {}",
+                        self.graph.modules[target].specifier
+                    )
+                })
+            }
+        }
+    }
+
+    /// The binding `module` exports as `name`, if any
+    fn follow_export(
+        &self,
+        module: usize,
+        name: &str,
+        visited: &mut Vec<(usize, String)>,
+    ) -> Result<Option<Binding>, String> {
+        if visited
+            .iter()
+            .any(|(other, other_name)| *other == module && other_name == name)
+        {
+            return Ok(None);
+        }
+        visited.push((module, name.to_string()));
+
+        let bundle_module = &self.graph.modules[module];
+        if let Some((_, symbol)) = bundle_module
+            .exports
+            .iter()
+            .find(|(exported, _)| exported == name)
+        {
+            return self.follow_symbol(module, *symbol, visited).map(Some);
+        }
+        if let Some((_, request, imported)) = bundle_module
+            .reexports
+            .iter()
+            .find(|(exported, _, _)| exported == name)
+        {
+            let target = self.graph.resolved[module][*request];
+            return self.follow_import(target, imported, visited).map(Some);
+        }
+        if name != "default" {
+            for &request in &bundle_module.star_exports {
+                let target = self.graph.resolved[module][request];
+                if let Some(binding) = self.follow_export(target, name, visited)? {
+                    return Ok(Some(binding));
+                }
+            }
+        }
+        Ok(None)
+    }
+
+    /// Every export of `module`, including the ones of `export * from`
+    fn export_names(&self, module: usize) -> Result<BTreeMap<String, Binding>, String> {
+        let mut names = BTreeSet::new();
+        self.collect_export_names(module, false, &mut names, &mut HashSet::new());
+
+        let mut exports = BTreeMap::new();
+        for name in names {
+            if let Some(binding) = self.follow_export(module, &name, &mut Vec::new())? {
+                exports.insert(name, binding);
+            }
+        }
+        Ok(exports)
+    }
+
+    fn collect_export_names(
+        &self,
+        module: usize,
+        is_star: bool,
+        names: &mut BTreeSet<String>,
+        visited: &mut HashSet<usize>,
+    ) {
+        if !visited.insert(module) {
+            return;
+        }
+        let bundle_module = &self.graph.modules[module];
+        let exported = bundle_module
+            .exports
+            .iter()
+            .map(|(name, _)| name)
+            .chain(bundle_module.reexports.iter().map(|(name, _, _)| name));
+        for name in exported {
+            // `export * from` leaves out the default export
+            if !is_star || name != "default" {
+                names.insert(name.clone());
+            }
+        }
+        for &request in &bundle_module.star_exports {
+            let target = self.graph.resolved[module][request];
+            self.collect_export_names(target, true, names, visited);
+        }
+    }
+}
+
+/// `local` or `local as exported` in an export list
+fn export_specifier(exported: &str, local: &str) -> String {
+    if exported == local {
+        local.to_string()
+    } else {
+        format!("{local} as {}", module_export_name(exported))
+    }
+}
+
+/// `local` or `exported as local` in an import list
+fn import_specifier(exported: &str, local: &str) -> String {
+    if exported == local {
+        local.to_string()
+    } else {
+        format!("{} as {local}", module_export_name(exported))
+    }
+}
+
+/// An exported name, quoted when it is not an identifier
+fn module_export_name(name: &str) -> String {
+    if is_identifier_name(name) {
+        name.to_string()
+    } else {
+        json_string(name)
+    }
+}
+
+/// The assignment target of `global_name`, creating the objects of a dotted name
+fn global_target(global_name: &str) -> String {
+    let mut parts = global_name.split('.');
+    let first = parts.next().unwrap_or(global_name);
+    let rest: Vec<&str> = parts.collect();
+    if rest.is_empty() {
+        return format!("var {first}");
+    }
+    let mut target = format!("globalThis.{first}");
+    for part in &rest[..rest.len() - 1] {
+        target = format!("({target} ??= {{}}).{part}");
+    }
+    format!("({target} ??= {{}}).{}", rest[rest.len() - 1])
+}
+
+fn is_identifier_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_alphabetic() || c == '_' || c == '$')
+        && chars.all(|c| c.is_alphanumeric() || c == '_' || c == '$')
+}
+
+fn json_string(value: &str) -> String {
+    serde_json::to_string(value).unwrap_or_else(|_| format!("\"{value}\""))
+}
+
+/// Copy the code of a module between `start` and `end` into `output`, mapping
+/// its first position and the start of each of its lines
+fn copy_code(
+    output: &mut String,
+    mappings: &mut Vec<Mapping>,
+    module: usize,
+    code: &str,
+    start: u32,
+    end: u32,
+) {
+    if start == end {
+        return;
+    }
+    let segment = &code[start as usize..end as usize];
+    let generated = output.len();
+    mappings.push(Mapping {
+        generated,
+        module,
+        offset: start,
+    });
+    for (index, _) in segment.match_indices('\n') {
+        if index + 1 < segment.len() {
+            mappings.push(Mapping {
+                generated: generated + index + 1,
+                module,
+                offset: start + index as u32 + 1,
+            });
+        }
+    }
+    output.push_str(segment);
+}
+
+/// Move the mappings of a body placed after `offset` bytes of other code
+fn shift_mappings(mappings: &mut [Mapping], offset: usize) {
+    for mapping in mappings {
+        mapping.generated += offset;
+    }
+}
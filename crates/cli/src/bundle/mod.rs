@@ -0,0 +1,215 @@
+// This Source Code Form is subject to the terms of the Mozilla Public License, v. 2.0.
+// If a copy of the MPL was not distributed with this file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+mod graph;
+mod link;
+mod module;
+mod source_map;
+
+use crate::cache::module_cache;
+use crate::config::{AndromedaConfig, BundleConfig, BundleFormat, ConfigManager, SourceMapMode};
+use crate::run::build_import_map;
+use andromeda_core::{CacheSetting, JsrResolver, ModuleCache, ModuleResolver, commonjs_files};
+use oxc_allocator::Allocator;
+use oxc_mangler::MangleOptions;
+use oxc_minifier::{CompressOptions, Minifier, MinifierOptions};
+use oxc_parser::Parser;
+use oxc_semantic::SemanticBuilder;
+use oxc_sourcemap::SourceMap;
+use oxc_span::SourceType;
+use oxc_transformer::{TransformOptions, Transformer};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use graph::load_graph;
+use link::link;
+use source_map::{chunk_source_map, compose};
+
+/// Bundles a JavaScript or TypeScript file and the modules it imports into
+/// `output`, following the `bundle` section of the configuration. With code
+/// splitting, the dynamically imported modules are written as chunks next to it.
+/// `sourcemap` overrides how the configuration writes source maps.
+pub fn bundle(
+    input: &str,
+    output: &str,
+    sourcemap: Option<SourceMapMode>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let config = ConfigManager::load_or_default(None);
+    let sourcemap = sourcemap.unwrap_or(config.bundle.sourcemap);
+    let (resolver, cache) = module_resolver(&config)?;
+
+    let entry = std::path::absolute(input)?.to_string_lossy().to_string();
+    let graph = load_graph(&entry, &resolver, &cache)?;
+
+    let output_path = Path::new(output);
+    let file_name = output_path
+        .file_name()
+        .and_then(|file_name| file_name.to_str())
+        .ok_or_else(|| format!("Invalid output file {output}"))?;
+    let output_dir = output_path.parent().unwrap_or(Path::new("."));
+
+    for chunk in link(&graph, &config.bundle, file_name)? {
+        let path = output_dir.join(&chunk.file_name);
+        let linked_map = (sourcemap != SourceMapMode::None)
+            .then(|| chunk_source_map(&graph, &chunk, output_dir));
+        let (mut code, source_map) =
+            finish_chunk(&chunk.code, &config.bundle, &path, linked_map.as_ref())?;
+
+        if let Some(source_map) = source_map {
+            let url = match sourcemap {
+                SourceMapMode::Inline => source_map.to_data_url(),
+                _ => {
+                    let map_name = format!("{}.map", chunk.file_name);
+                    std::fs::write(output_dir.join(&map_name), source_map.to_json_string())?;
+                    map_name
+                }
+            };
+            if !code.ends_with('\n') {
+                code.push('\n');
+            }
+            code.push_str(&format!("//# sourceMappingURL={url}\n"));
+        }
+        std::fs::write(path, code)?;
+    }
+
+    Ok(())
+}
+
+/// The files of the modules `input` imports, directly or not, along with itself:
+/// the local files by absolute path, with the CommonJS files their `require()`
+/// calls run, and the remote modules by URL
+pub fn module_files(input: &str) -> Result<Vec<(String, Vec<u8>)>, Box<dyn std::error::Error>> {
+    let config = ConfigManager::load_or_default(None);
+    let (resolver, cache) = module_resolver(&config)?;
+
+    let entry = std::path::absolute(input)?.to_string_lossy().to_string();
+    let graph = load_graph(&entry, &resolver, &cache)?;
+
+    let mut seen = HashSet::new();
+    let mut files = Vec::new();
+    for module in &graph.modules {
+        let specifier = &module.specifier;
+        if !seen.insert(specifier.clone()) {
+            continue;
+        }
+        if specifier.starts_with("http://") || specifier.starts_with("https://") {
+            files.push((specifier.clone(), cache.load_remote(specifier)?.content));
+            continue;
+        }
+
+        let content = std::fs::read(specifier)
+            .map_err(|e| format!("Failed to read module {specifier}: {e}"))?;
+        let path = Path::new(specifier);
+        if resolver.npm.is_commonjs(path) {
+            let source = String::from_utf8_lossy(&content);
+            for file in commonjs_files(&resolver.npm, specifier, &source) {
+                let file = file.to_string_lossy().to_string();
+                if seen.insert(file.clone()) {
+                    let content = std::fs::read(&file)
+                        .map_err(|e| format!("Failed to read module {file}: {e}"))?;
+                    files.push((file, content));
+                }
+            }
+        }
+        files.push((specifier.clone(), content));
+    }
+    Ok(files)
+}
+
+/// The resolver the modules are bundled with, following the import map and the
+/// cache settings of the configuration
+fn module_resolver(
+    config: &AndromedaConfig,
+) -> Result<(ModuleResolver, Arc<ModuleCache>), Box<dyn std::error::Error>> {
+    let import_map = build_import_map(config, None)?;
+    let cache = Arc::new(module_cache(
+        config,
+        import_map.as_ref(),
+        None,
+        CacheSetting::Use,
+    )?);
+    let mut resolver = ModuleResolver::new(
+        std::env::current_dir().unwrap_or_else(|_| PathBuf::from(".")),
+        import_map,
+    );
+    resolver.jsr = JsrResolver::new(JsrResolver::default_registry_url(), cache.clone());
+    Ok((resolver, cache))
+}
+
+/// Compiles the code of a chunk down to the configured target, then minifies
+/// and mangles it as configured. With the source map of the linked code, the
+/// source map of the finished code to the original sources is returned too.
+fn finish_chunk(
+    code: &str,
+    config: &BundleConfig,
+    path: &Path,
+    linked_map: Option<&SourceMap>,
+) -> Result<(String, Option<SourceMap>), Box<dyn std::error::Error>> {
+    let allocator = Allocator::default();
+    let source_type = match config.format {
+        BundleFormat::Esm => SourceType::mjs(),
+        BundleFormat::Iife => SourceType::cjs(),
+    };
+
+    let ret = Parser::new(&allocator, code, source_type).parse();
+
+    if !ret.errors.is_empty() {
+        eprintln!("Parser errors:");
+        for error in &ret.errors {
+            eprintln!("  {error}");
+        }
+        return Err(format!("Failed to parse the bundled {}", path.display()).into());
+    }
+
+    let mut program = ret.program;
+
+    let transform_options = TransformOptions::from_target(&config.target)
+        .map_err(|e| format!("Invalid bundle target {}: {e}", config.target))?;
+    let scoping = SemanticBuilder::new()
+        .build(&program)
+        .semantic
+        .into_scoping();
+    let transformer_ret = Transformer::new(&allocator, path, &transform_options)
+        .build_with_scoping(scoping, &mut program);
+
+    if !transformer_ret.errors.is_empty() {
+        eprintln!("Transform errors:");
+        for error in &transformer_ret.errors {
+            eprintln!("  {error}");
+        }
+        return Err(format!("Failed to transform the bundle to {}", config.target).into());
+    }
+
+    let scoping = if config.minify || config.mangle {
+        let options = MinifierOptions {
+            mangle: config.mangle.then(MangleOptions::default),
+            compress: config.minify.then(CompressOptions::default),
+        };
+        Minifier::new(options)
+            .minify(&allocator, &mut program)
+            .scoping
+    } else {
+        None
+    };
+
+    let comments = if config.minify {
+        oxc_codegen::CommentOptions::disabled()
+    } else {
+        oxc_codegen::CommentOptions::default()
+    };
+    let ret = oxc_codegen::Codegen::new()
+        .with_options(oxc_codegen::CodegenOptions {
+            minify: config.minify,
+            comments,
+            source_map_path: linked_map.map(|_| path.to_path_buf()),
+            ..oxc_codegen::CodegenOptions::default()
+        })
+        .with_scoping(scoping)
+        .build(&program);
+
+    let source_map = linked_map
+        .zip(ret.map.as_ref())
+        .map(|(linked_map, finished_map)| compose(finished_map, linked_map));
+    Ok((ret.code, source_map))
+}
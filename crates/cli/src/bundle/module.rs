@@ -0,0 +1,576 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use std::collections::{HashMap, HashSet};
+
+use oxc_allocator::Allocator;
+use oxc_ast::ast::{self, Expression, Statement};
+use oxc_ast_visit::{Visit, walk};
+use oxc_parser::Parser;
+use oxc_semantic::SemanticBuilder;
+use oxc_span::{GetSpan, SourceType, Span};
+
+/// A name imported from another module
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(super) enum ImportName {
+    Named(String),
+    /// The namespace object, as in `import * as ns` and `export * as ns from`
+    Namespace,
+}
+
+/// A module imported by another one, with the value of its `type` import attribute
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub(super) struct Request {
+    pub specifier: String,
+    pub import_type: Option<String>,
+}
+
+/// A top-level binding of a module
+#[derive(Debug)]
+pub(super) struct Symbol {
+    pub name: String,
+    /// The request and imported name of an import binding
+    pub import: Option<(usize, ImportName)>,
+    /// The statement declaring the binding
+    pub statement: Option<usize>,
+}
+
+/// A change to the code of a statement when it is bundled
+#[derive(Debug)]
+pub(super) enum Edit {
+    /// Replace the span with the text
+    Replace(Span, String),
+    /// Replace the span with the final name of a symbol, as a `key: name`
+    /// property when it is a shorthand property
+    Symbol(Span, usize, bool),
+    /// Replace `export default` with `const <name> =` for a symbol
+    DeclareDefault(Span, usize),
+    /// Replace a dynamic `import()` of a request with the loading of its chunk
+    DynamicImport(Span, usize),
+}
+
+impl Edit {
+    pub fn span(&self) -> Span {
+        match self {
+            Edit::Replace(span, _)
+            | Edit::Symbol(span, _, _)
+            | Edit::DeclareDefault(span, _)
+            | Edit::DynamicImport(span, _) => *span,
+        }
+    }
+}
+
+/// A top-level statement kept in the bundle, which are all of them but the
+/// imports and re-exports
+#[derive(Debug)]
+pub(super) struct ModuleStatement {
+    pub span: Span,
+    pub edits: Vec<Edit>,
+    /// The symbols the statement declares or references
+    pub symbols: Vec<usize>,
+    pub has_side_effects: bool,
+    /// Whether a `;` has to be added after the statement, when a default export
+    /// of an expression becomes a declaration
+    pub needs_semicolon: bool,
+}
+
+/// A JavaScript module analyzed for bundling
+#[derive(Debug)]
+pub(super) struct BundleModule {
+    /// Resolved path or URL of the module
+    pub specifier: String,
+    pub code: String,
+    pub requests: Vec<Request>,
+    /// The requests of the imports and re-exports, in source order
+    pub static_requests: Vec<usize>,
+    pub symbols: Vec<Symbol>,
+    /// The exported names of local and import bindings
+    pub exports: Vec<(String, usize)>,
+    /// `export { name as exported } from` and `export * as exported from`
+    pub reexports: Vec<(String, usize, ImportName)>,
+    /// The requests of `export * from`
+    pub star_exports: Vec<usize>,
+    pub statements: Vec<ModuleStatement>,
+    /// The names bound in the nested scopes of the module
+    pub names: HashSet<String>,
+    /// The global variables the module references
+    pub globals: HashSet<String>,
+}
+
+impl BundleModule {
+    /// Analyze the JavaScript `code` of the module at `specifier`. A default
+    /// export of an expression is declared as a binding named after `stem`.
+    pub fn analyze(specifier: String, code: &str, stem: &str) -> Result<Self, String> {
+        let allocator = Allocator::default();
+        let ret = Parser::new(&allocator, code, SourceType::mjs()).parse();
+        if let Some(error) = ret.errors.first() {
+            return Err(format!("Failed to parse {specifier}: {error}"));
+        }
+        let program = ret.program;
+        let semantic = SemanticBuilder::new().build(&program).semantic;
+        let scoping = semantic.scoping();
+        let root_scope = scoping.root_scope_id();
+
+        let mut names = HashSet::new();
+        let mut symbols = Vec::new();
+        let mut symbol_indices = HashMap::new();
+        let mut root_names = HashMap::new();
+        let mut occurrences = Vec::new();
+        for symbol_id in scoping.symbol_ids() {
+            let name = scoping.symbol_name(symbol_id);
+            if scoping.symbol_scope_id(symbol_id) != root_scope {
+                names.insert(name.to_string());
+                continue;
+            }
+            let index = symbols.len();
+            symbol_indices.insert(symbol_id, index);
+            root_names.insert(name.to_string(), index);
+            symbols.push(Symbol {
+                name: name.to_string(),
+                import: None,
+                statement: None,
+            });
+            occurrences.push((scoping.symbol_span(symbol_id), index, true));
+            for &reference_id in scoping.get_resolved_reference_ids(symbol_id) {
+                let reference = scoping.get_reference(reference_id);
+                occurrences.push((semantic.reference_span(reference), index, false));
+            }
+        }
+
+        let mut globals = HashSet::new();
+        for reference_ids in scoping.root_unresolved_references_ids() {
+            for reference_id in reference_ids {
+                let reference = scoping.get_reference(reference_id);
+                globals.insert(semantic.reference_name(reference).to_string());
+            }
+        }
+
+        let mut default_name = format!("{stem}_default");
+        while names.contains(&default_name) || root_names.contains_key(&default_name) {
+            default_name.insert(0, '_');
+        }
+
+        let mut module = Self {
+            specifier,
+            code: code.to_string(),
+            requests: Vec::new(),
+            static_requests: Vec::new(),
+            symbols,
+            exports: Vec::new(),
+            reexports: Vec::new(),
+            star_exports: Vec::new(),
+            statements: Vec::new(),
+            names,
+            globals,
+        };
+        // Statements whose declarations are all exported under their names
+        let mut exported_statements = Vec::new();
+
+        for statement in &program.body {
+            match statement {
+                Statement::ImportDeclaration(decl) => {
+                    let request = module.request(&decl.source.value, decl.with_clause.as_deref());
+                    module.static_requests.push(request);
+                    for specifier in decl.specifiers.iter().flatten() {
+                        let (local, imported) = match specifier {
+                            ast::ImportDeclarationSpecifier::ImportSpecifier(specifier) => (
+                                &specifier.local,
+                                ImportName::Named(specifier.imported.name().to_string()),
+                            ),
+                            ast::ImportDeclarationSpecifier::ImportDefaultSpecifier(specifier) => {
+                                (&specifier.local, ImportName::Named("default".to_string()))
+                            }
+                            ast::ImportDeclarationSpecifier::ImportNamespaceSpecifier(
+                                specifier,
+                            ) => (&specifier.local, ImportName::Namespace),
+                        };
+                        if let Some(&index) = symbol_indices.get(&local.symbol_id()) {
+                            module.symbols[index].import = Some((request, imported));
+                        }
+                    }
+                }
+                Statement::ExportAllDeclaration(decl) => {
+                    let request = module.request(&decl.source.value, decl.with_clause.as_deref());
+                    module.static_requests.push(request);
+                    match &decl.exported {
+                        Some(exported) => module.reexports.push((
+                            exported.name().to_string(),
+                            request,
+                            ImportName::Namespace,
+                        )),
+                        None => module.star_exports.push(request),
+                    }
+                }
+                Statement::ExportNamedDeclaration(decl) => {
+                    if let Some(source) = &decl.source {
+                        let request = module.request(&source.value, decl.with_clause.as_deref());
+                        module.static_requests.push(request);
+                        for specifier in &decl.specifiers {
+                            module.reexports.push((
+                                specifier.exported.name().to_string(),
+                                request,
+                                ImportName::Named(specifier.local.name().to_string()),
+                            ));
+                        }
+                    } else if let Some(declaration) = &decl.declaration {
+                        exported_statements.push(module.statements.len());
+                        module.statements.push(ModuleStatement {
+                            span: decl.span,
+                            edits: vec![Edit::Replace(
+                                Span::new(decl.span.start, declaration.span().start),
+                                String::new(),
+                            )],
+                            symbols: Vec::new(),
+                            has_side_effects: !is_pure_declaration(declaration),
+                            needs_semicolon: false,
+                        });
+                    } else {
+                        for specifier in &decl.specifiers {
+                            if let Some(&index) = root_names.get(specifier.local.name().as_str()) {
+                                module
+                                    .exports
+                                    .push((specifier.exported.name().to_string(), index));
+                            }
+                        }
+                    }
+                }
+                Statement::ExportDefaultDeclaration(decl) => {
+                    let (declaration, has_side_effects) = match &decl.declaration {
+                        ast::ExportDefaultDeclarationKind::FunctionDeclaration(function) => {
+                            (function.id.as_ref().map(|id| id.name.as_str()), false)
+                        }
+                        ast::ExportDefaultDeclarationKind::ClassDeclaration(class) => (
+                            class.id.as_ref().map(|id| id.name.as_str()),
+                            !is_pure_class(class),
+                        ),
+                        ast::ExportDefaultDeclarationKind::TSInterfaceDeclaration(_) => continue,
+                        kind => (None, !kind.as_expression().is_some_and(is_pure_expression)),
+                    };
+                    let prefix = Span::new(decl.span.start, decl.declaration.span().start);
+                    let mut statement = ModuleStatement {
+                        span: decl.span,
+                        edits: Vec::new(),
+                        symbols: Vec::new(),
+                        has_side_effects,
+                        needs_semicolon: false,
+                    };
+                    match declaration.and_then(|name| root_names.get(name)) {
+                        // `export default function name() {}` declares `name`
+                        Some(&index) => {
+                            statement.edits.push(Edit::Replace(prefix, String::new()));
+                            module.exports.push(("default".to_string(), index));
+                        }
+                        None => {
+                            let index = module.symbols.len();
+                            module.symbols.push(Symbol {
+                                name: default_name.clone(),
+                                import: None,
+                                statement: Some(module.statements.len()),
+                            });
+                            statement.edits.push(Edit::DeclareDefault(prefix, index));
+                            statement.symbols.push(index);
+                            statement.needs_semicolon =
+                                !code[..decl.span.end as usize].trim_end().ends_with(';');
+                            module.exports.push(("default".to_string(), index));
+                        }
+                    }
+                    module.statements.push(statement);
+                }
+                statement => module.statements.push(ModuleStatement {
+                    span: statement.span(),
+                    edits: Vec::new(),
+                    symbols: Vec::new(),
+                    has_side_effects: !is_pure_statement(statement),
+                    needs_semicolon: false,
+                }),
+            }
+        }
+
+        let mut collector = ExpressionCollector::default();
+        collector.visit_program(&program);
+
+        for (span, specifier, import_type) in collector.dynamic_imports {
+            let request = module.request_with_type(specifier, import_type);
+            if let Some(statement) = module.statement_at(span) {
+                module.statements[statement]
+                    .edits
+                    .push(Edit::DynamicImport(span, request));
+            }
+        }
+
+        for (span, index, is_declaration) in occurrences {
+            let Some(statement) = module.statement_at(span) else {
+                continue;
+            };
+            if is_declaration && module.symbols[index].statement.is_none() {
+                module.symbols[index].statement = Some(statement);
+            }
+            let statement = &mut module.statements[statement];
+            // Identifiers in the options of a rewritten `import()`
+            if statement.edits.iter().any(|edit| {
+                matches!(edit, Edit::DynamicImport(..))
+                    && edit.span().start <= span.start
+                    && span.end <= edit.span().end
+            }) {
+                continue;
+            }
+            let shorthand = collector.shorthands.contains(&span.start);
+            statement.edits.push(Edit::Symbol(span, index, shorthand));
+            if !statement.symbols.contains(&index) {
+                statement.symbols.push(index);
+            }
+        }
+
+        for statement in exported_statements {
+            for (index, symbol) in module.symbols.iter().enumerate() {
+                if symbol.statement == Some(statement) && symbol.import.is_none() {
+                    module.exports.push((symbol.name.clone(), index));
+                }
+            }
+        }
+
+        Ok(module)
+    }
+
+    /// The index of the top-level statement containing `span`
+    fn statement_at(&self, span: Span) -> Option<usize> {
+        let index = self
+            .statements
+            .partition_point(|statement| statement.span.end <= span.start);
+        self.statements
+            .get(index)
+            .filter(|statement| {
+                statement.span.start <= span.start && span.end <= statement.span.end
+            })
+            .map(|_| index)
+    }
+
+    fn request(&mut self, specifier: &str, with_clause: Option<&ast::WithClause>) -> usize {
+        let import_type = with_clause.and_then(|with_clause| {
+            with_clause
+                .with_entries
+                .iter()
+                .find(|attribute| attribute.key.as_atom().as_str() == "type")
+                .map(|attribute| attribute.value.value.to_string())
+        });
+        self.request_with_type(specifier.to_string(), import_type)
+    }
+
+    fn request_with_type(&mut self, specifier: String, import_type: Option<String>) -> usize {
+        let request = Request {
+            specifier,
+            import_type,
+        };
+        match self.requests.iter().position(|other| *other == request) {
+            Some(index) => index,
+            None => {
+                self.requests.push(request);
+                self.requests.len() - 1
+            }
+        }
+    }
+}
+
+/// Collects the dynamic imports of string literals, and the shorthand properties
+/// whose names may change
+#[derive(Default)]
+struct ExpressionCollector {
+    dynamic_imports: Vec<(Span, String, Option<String>)>,
+    shorthands: HashSet<u32>,
+}
+
+impl<'a> Visit<'a> for ExpressionCollector {
+    fn visit_import_expression(&mut self, expr: &ast::ImportExpression<'a>) {
+        if let Expression::StringLiteral(source) = &expr.source {
+            let import_type = expr.options.as_ref().and_then(import_type_option);
+            self.dynamic_imports
+                .push((expr.span, source.value.to_string(), import_type));
+        }
+        walk::walk_import_expression(self, expr);
+    }
+
+    fn visit_object_property(&mut self, property: &ast::ObjectProperty<'a>) {
+        if property.shorthand {
+            self.shorthands.insert(property.key.span().start);
+        }
+        walk::walk_object_property(self, property);
+    }
+
+    fn visit_binding_property(&mut self, property: &ast::BindingProperty<'a>) {
+        if property.shorthand {
+            self.shorthands.insert(property.key.span().start);
+        }
+        walk::walk_binding_property(self, property);
+    }
+
+    fn visit_assignment_target_property_identifier(
+        &mut self,
+        property: &ast::AssignmentTargetPropertyIdentifier<'a>,
+    ) {
+        self.shorthands.insert(property.binding.span.start);
+        walk::walk_assignment_target_property_identifier(self, property);
+    }
+}
+
+/// The `type` in the `{ with: { type } }` options of a dynamic import
+fn import_type_option(options: &Expression) -> Option<String> {
+    let Expression::ObjectExpression(options) = options else {
+        return None;
+    };
+    let with = options
+        .properties
+        .iter()
+        .find_map(|property| match property {
+            ast::ObjectPropertyKind::ObjectProperty(property)
+                if property
+                    .key
+                    .static_name()
+                    .is_some_and(|name| name == "with") =>
+            {
+                Some(&property.value)
+            }
+            _ => None,
+        })?;
+    let Expression::ObjectExpression(with) = with else {
+        return None;
+    };
+    with.properties.iter().find_map(|property| match property {
+        ast::ObjectPropertyKind::ObjectProperty(property)
+            if property
+                .key
+                .static_name()
+                .is_some_and(|name| name == "type") =>
+        {
+            match &property.value {
+                Expression::StringLiteral(value) => Some(value.value.to_string()),
+                _ => None,
+            }
+        }
+        _ => None,
+    })
+}
+
+/// Whether removing the statement, when nothing uses what it declares, cannot
+/// change the behaviour of the program
+fn is_pure_statement(statement: &Statement) -> bool {
+    match statement {
+        Statement::EmptyStatement(_) | Statement::FunctionDeclaration(_) => true,
+        Statement::ClassDeclaration(class) => is_pure_class(class),
+        Statement::VariableDeclaration(decl) => is_pure_variables(decl),
+        _ => false,
+    }
+}
+
+fn is_pure_declaration(declaration: &ast::Declaration) -> bool {
+    match declaration {
+        ast::Declaration::FunctionDeclaration(_) => true,
+        ast::Declaration::ClassDeclaration(class) => is_pure_class(class),
+        ast::Declaration::VariableDeclaration(decl) => is_pure_variables(decl),
+        _ => false,
+    }
+}
+
+fn is_pure_variables(decl: &ast::VariableDeclaration) -> bool {
+    !matches!(
+        decl.kind,
+        ast::VariableDeclarationKind::Using | ast::VariableDeclarationKind::AwaitUsing
+    ) && decl.declarations.iter().all(|declarator| {
+        // Destructuring may call getters and iterators
+        matches!(
+            declarator.id.kind,
+            ast::BindingPatternKind::BindingIdentifier(_)
+        ) && declarator.init.as_ref().is_none_or(is_pure_expression)
+    })
+}
+
+fn is_pure_class(class: &ast::Class) -> bool {
+    class.decorators.is_empty()
+        && class.super_class.as_ref().is_none_or(is_pure_expression)
+        && class.body.body.iter().all(|element| {
+            let is_pure_key = |computed: bool, key: &ast::PropertyKey| {
+                !computed || key.as_expression().is_some_and(is_pure_expression)
+            };
+            match element {
+                ast::ClassElement::StaticBlock(_) => false,
+                ast::ClassElement::MethodDefinition(method) => {
+                    method.decorators.is_empty() && is_pure_key(method.computed, &method.key)
+                }
+                ast::ClassElement::PropertyDefinition(property) => {
+                    property.decorators.is_empty()
+                        && is_pure_key(property.computed, &property.key)
+                        && (!property.r#static
+                            || property.value.as_ref().is_none_or(is_pure_expression))
+                }
+                ast::ClassElement::AccessorProperty(property) => {
+                    property.decorators.is_empty()
+                        && is_pure_key(property.computed, &property.key)
+                        && (!property.r#static
+                            || property.value.as_ref().is_none_or(is_pure_expression))
+                }
+                ast::ClassElement::TSIndexSignature(_) => true,
+            }
+        })
+}
+
+/// Whether evaluating the expression has no side effects, including the calls
+/// annotated with `/* @__PURE__ */`
+fn is_pure_expression(expr: &Expression) -> bool {
+    let is_pure_arguments = |arguments: &[ast::Argument]| {
+        arguments
+            .iter()
+            .all(|argument| argument.as_expression().is_some_and(is_pure_expression))
+    };
+    match expr {
+        Expression::BooleanLiteral(_)
+        | Expression::NullLiteral(_)
+        | Expression::NumericLiteral(_)
+        | Expression::BigIntLiteral(_)
+        | Expression::StringLiteral(_)
+        | Expression::RegExpLiteral(_)
+        | Expression::Identifier(_)
+        | Expression::FunctionExpression(_)
+        | Expression::ArrowFunctionExpression(_)
+        | Expression::MetaProperty(_) => true,
+        Expression::TemplateLiteral(template) => {
+            template.expressions.iter().all(is_pure_expression)
+        }
+        Expression::ClassExpression(class) => is_pure_class(class),
+        Expression::ArrayExpression(array) => array.elements.iter().all(|element| match element {
+            ast::ArrayExpressionElement::SpreadElement(_) => false,
+            ast::ArrayExpressionElement::Elision(_) => true,
+            element => element.as_expression().is_some_and(is_pure_expression),
+        }),
+        Expression::ObjectExpression(object) => {
+            object.properties.iter().all(|property| match property {
+                ast::ObjectPropertyKind::ObjectProperty(property) => {
+                    (!property.computed
+                        || property.key.as_expression().is_some_and(is_pure_expression))
+                        && is_pure_expression(&property.value)
+                }
+                ast::ObjectPropertyKind::SpreadProperty(_) => false,
+            })
+        }
+        Expression::UnaryExpression(unary) => {
+            unary.operator != ast::UnaryOperator::Delete && is_pure_expression(&unary.argument)
+        }
+        Expression::BinaryExpression(binary) => {
+            is_pure_expression(&binary.left) && is_pure_expression(&binary.right)
+        }
+        Expression::LogicalExpression(logical) => {
+            is_pure_expression(&logical.left) && is_pure_expression(&logical.right)
+        }
+        Expression::ConditionalExpression(conditional) => {
+            is_pure_expression(&conditional.test)
+                && is_pure_expression(&conditional.consequent)
+                && is_pure_expression(&conditional.alternate)
+        }
+        Expression::ParenthesizedExpression(expr) => is_pure_expression(&expr.expression),
+        Expression::SequenceExpression(sequence) => {
+            sequence.expressions.iter().all(is_pure_expression)
+        }
+        Expression::CallExpression(call) => call.pure && is_pure_arguments(&call.arguments),
+        Expression::NewExpression(new) => new.pure && is_pure_arguments(&new.arguments),
+        _ => false,
+    }
+}
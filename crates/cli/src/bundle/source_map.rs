@@ -0,0 +1,168 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use std::{
+    collections::HashMap,
+    path::{Component, Path, PathBuf},
+};
+
+use oxc_sourcemap::{SourceMap, SourceMapBuilder};
+
+use super::graph::BundleGraph;
+use super::link::Chunk;
+
+/// A position of the code of a chunk copied from a module
+#[derive(Debug, Clone, Copy)]
+pub(super) struct Mapping {
+    /// Byte offset in the chunk
+    pub generated: usize,
+    pub module: usize,
+    /// Byte offset in the code of the module
+    pub offset: u32,
+}
+
+/// Zero-based line and column of byte offsets in a text
+struct LineIndex<'a> {
+    text: &'a str,
+    line_starts: Vec<usize>,
+}
+
+impl<'a> LineIndex<'a> {
+    fn new(text: &'a str) -> Self {
+        let line_starts = std::iter::once(0)
+            .chain(text.match_indices('\n').map(|(index, _)| index + 1))
+            .collect();
+        Self { text, line_starts }
+    }
+
+    fn line_column(&self, offset: usize) -> (u32, u32) {
+        let line = self.line_starts.partition_point(|&start| start <= offset) - 1;
+        let line_start = self.line_starts[line];
+        let column = self.text[line_start..offset.min(self.text.len())]
+            .chars()
+            .count();
+        (line as u32, column as u32)
+    }
+}
+
+/// The source map from the code of a chunk to the original sources of its
+/// modules, with the sources relative to `output_dir`
+pub(super) fn chunk_source_map(graph: &BundleGraph, chunk: &Chunk, output_dir: &Path) -> SourceMap {
+    let output_dir = std::path::absolute(output_dir).unwrap_or_else(|_| output_dir.to_path_buf());
+    let generated = LineIndex::new(&chunk.code);
+    let mut modules: HashMap<usize, LineIndex> = HashMap::new();
+    let mut lookup_tables = HashMap::new();
+    let mut sources: HashMap<String, u32> = HashMap::new();
+    let mut builder = SourceMapBuilder::default();
+
+    for mapping in &chunk.mappings {
+        let module = &graph.modules[mapping.module];
+        let (dst_line, dst_col) = generated.line_column(mapping.generated);
+        let (line, column) = modules
+            .entry(mapping.module)
+            .or_insert_with(|| LineIndex::new(&module.code))
+            .line_column(mapping.offset as usize);
+
+        // Compiled modules map through the source map of their compilation
+        let (source, content, src_line, src_col) = match &graph.source_maps[mapping.module] {
+            Some(source_map) => {
+                let lookup_table = lookup_tables
+                    .entry(mapping.module)
+                    .or_insert_with(|| source_map.generate_lookup_table());
+                let Some(token) = source_map.lookup_token(lookup_table, line, column) else {
+                    continue;
+                };
+                let Some(source_id) = token.get_source_id() else {
+                    continue;
+                };
+                (
+                    source_map
+                        .get_source(source_id)
+                        .unwrap_or(&module.specifier),
+                    source_map.get_source_content(source_id).unwrap_or(""),
+                    token.get_src_line(),
+                    token.get_src_col(),
+                )
+            }
+            None => (
+                module.specifier.as_str(),
+                module.code.as_str(),
+                line,
+                column,
+            ),
+        };
+
+        let source_id = *sources.entry(source.to_string()).or_insert_with(|| {
+            builder.add_source_and_content(&relative_source(source, &output_dir), content)
+        });
+        builder.add_token(dst_line, dst_col, src_line, src_col, Some(source_id), None);
+    }
+
+    builder.into_sourcemap()
+}
+
+/// Compose the source map of the finished code of a chunk, which maps to its
+/// linked code, with the map of the linked code to the original sources
+pub(super) fn compose(finished: &SourceMap, linked: &SourceMap) -> SourceMap {
+    let lookup_table = linked.generate_lookup_table();
+    let mut sources: HashMap<u32, u32> = HashMap::new();
+    let mut names: HashMap<u32, u32> = HashMap::new();
+    let mut builder = SourceMapBuilder::default();
+
+    for token in finished.get_tokens() {
+        let Some(original) =
+            linked.lookup_token(&lookup_table, token.get_src_line(), token.get_src_col())
+        else {
+            continue;
+        };
+        let Some(linked_source) = original.get_source_id() else {
+            continue;
+        };
+        let source_id = *sources.entry(linked_source).or_insert_with(|| {
+            builder.add_source_and_content(
+                linked.get_source(linked_source).unwrap_or_default(),
+                linked.get_source_content(linked_source).unwrap_or_default(),
+            )
+        });
+        let name_id = token.get_name_id().and_then(|name_id| {
+            let name = finished.get_name(name_id)?;
+            Some(
+                *names
+                    .entry(name_id)
+                    .or_insert_with(|| builder.add_name(name)),
+            )
+        });
+        builder.add_token(
+            token.get_dst_line(),
+            token.get_dst_col(),
+            original.get_src_line(),
+            original.get_src_col(),
+            Some(source_id),
+            name_id,
+        );
+    }
+
+    builder.into_sourcemap()
+}
+
+/// A source as written in a source map next to the bundle: URLs are kept, and
+/// paths are made relative to `output_dir`
+fn relative_source(source: &str, output_dir: &Path) -> String {
+    if source.contains("://") {
+        return source.to_string();
+    }
+    let path = std::path::absolute(source).unwrap_or_else(|_| PathBuf::from(source));
+    let path: Vec<Component> = path.components().collect();
+    let base: Vec<Component> = output_dir.components().collect();
+    let common = path.iter().zip(&base).take_while(|(a, b)| a == b).count();
+
+    let mut relative = PathBuf::new();
+    for _ in common..base.len() {
+        relative.push("..");
+    }
+    for component in &path[common..] {
+        relative.push(component);
+    }
+    relative.to_string_lossy().replace('\\', "/")
+}
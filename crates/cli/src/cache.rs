@@ -0,0 +1,167 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use crate::config::{AndromedaConfig, ConfigManager};
+use crate::error::{AndromedaError, Result};
+use crate::run::build_import_map;
+use andromeda_core::{
+    CacheSetting, DependencyGraph, ImportMap, JsrResolver, LOCKFILE_NAME, Lockfile, ModuleCache,
+    ModuleResolver, TranspileCache, walk_module_graph,
+};
+use clap::Args;
+use console::Style;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// Flags controlling how remote modules are cached.
+#[derive(Debug, Clone, Copy, Default, Args)]
+pub struct CacheFlags {
+    /// Download the remote modules again instead of using the cache
+    #[arg(long, conflicts_with = "cached_only")]
+    pub reload: bool,
+
+    /// Only use cached remote modules, failing on the ones that are not cached
+    #[arg(long)]
+    pub cached_only: bool,
+}
+
+impl CacheFlags {
+    pub fn setting(self) -> CacheSetting {
+        if self.reload {
+            CacheSetting::Reload
+        } else if self.cached_only {
+            CacheSetting::Only
+        } else {
+            CacheSetting::Use
+        }
+    }
+}
+
+/// Create the cache of the remote modules, verified against the `integrity` of the
+/// configuration and the lockfile. The lockfile lives next to the configuration
+/// file, and is only used without one when it already exists in `start_dir`.
+#[allow(clippy::result_large_err)]
+pub fn module_cache(
+    config: &AndromedaConfig,
+    import_map: Option<&ImportMap>,
+    start_dir: Option<&Path>,
+    setting: CacheSetting,
+) -> Result<ModuleCache> {
+    let lockfile_path = match ConfigManager::find_config_file(start_dir) {
+        Some((config_path, _)) => config_path.parent().map(|dir| dir.join(LOCKFILE_NAME)),
+        None => Some(start_dir.unwrap_or(Path::new(".")).join(LOCKFILE_NAME))
+            .filter(|path| path.exists()),
+    };
+    let lockfile = match lockfile_path {
+        Some(lockfile_path) => Some(Lockfile::load(&lockfile_path).map_err(|e| {
+            AndromedaError::config_error(
+                format!("Failed to load lockfile: {e}"),
+                Some(lockfile_path.clone()),
+                None::<std::io::Error>,
+            )
+        })?),
+        None => None,
+    };
+
+    let mut integrity = config.integrity.clone();
+    if let Some(import_map) = import_map {
+        integrity.extend(import_map.integrity.clone());
+    }
+
+    let cache = ModuleCache::new(ModuleCache::default_dir(), setting).with_integrity(integrity);
+    Ok(match lockfile {
+        Some(lockfile) => cache.with_lockfile(lockfile),
+        None => cache,
+    })
+}
+
+/// Download the remote modules imported by the modules at `paths` and their
+/// dependencies into the cache, for running them offline
+#[allow(clippy::result_large_err)]
+#[hotpath::measure]
+pub fn cache_modules(paths: &[PathBuf], flags: CacheFlags) -> Result<()> {
+    let config = ConfigManager::load_or_default(None);
+    let import_map = build_import_map(&config, None)?;
+    let cache = Arc::new(module_cache(
+        &config,
+        import_map.as_ref(),
+        None,
+        flags.setting(),
+    )?);
+    let mut resolver = ModuleResolver::new(
+        std::env::current_dir().unwrap_or_else(|_| PathBuf::from(".")),
+        import_map,
+    );
+    resolver.jsr = JsrResolver::new(JsrResolver::default_registry_url(), cache.clone());
+
+    let entries: Vec<String> = paths
+        .iter()
+        .map(|path| path.to_string_lossy().to_string())
+        .collect();
+    let mut downloaded = 0;
+    let modules = walk_module_graph(
+        &entries,
+        &resolver,
+        &mut DependencyGraph::new(),
+        |specifier| {
+            if specifier.starts_with("http://") || specifier.starts_with("https://") {
+                let download = match flags.setting() {
+                    CacheSetting::Use => !cache.is_cached(specifier),
+                    CacheSetting::Reload => true,
+                    CacheSetting::Only => false,
+                };
+                if download {
+                    let download = Style::new().green().bold().apply_to("Download");
+                    println!("{download} {specifier}");
+                    downloaded += 1;
+                }
+                cache.load(specifier)
+            } else {
+                std::fs::read_to_string(specifier).map_err(|e| andromeda_core::ModuleError::Io {
+                    message: format!("Failed to read module {specifier}: {e}"),
+                })
+            }
+        },
+    )
+    .map_err(|e| {
+        AndromedaError::runtime_error(
+            format!("Failed to cache modules: {e}"),
+            None,
+            None,
+            None,
+            None,
+        )
+    })?;
+
+    let remote = modules
+        .iter()
+        .filter(|module| module.specifier.contains("://"))
+        .count();
+    let done = Style::new().green().bold().apply_to("✅");
+    println!(
+        "{done} {remote} remote module(s) cached in {}, {downloaded} downloaded",
+        cache.dir().display()
+    );
+    Ok(())
+}
+
+/// Remove the transpiled modules, which are transpiled again on the next run
+#[allow(clippy::result_large_err)]
+pub fn clean_cache() -> Result<()> {
+    let transpile_cache = TranspileCache::new(TranspileCache::default_dir());
+    let removed = transpile_cache.clean().map_err(|e| {
+        AndromedaError::permission_denied(
+            format!("removing {}", transpile_cache.dir().display()),
+            Some(transpile_cache.dir().to_path_buf()),
+            e,
+        )
+    })?;
+
+    let done = Style::new().green().bold().apply_to("✅");
+    println!(
+        "{done} {removed} transpiled module(s) removed from {}",
+        transpile_cache.dir().display()
+    );
+    Ok(())
+}
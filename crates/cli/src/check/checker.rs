@@ -0,0 +1,1946 @@
+// This Source Code Form is subject to the terms of the Mozilla Public License, v. 2.0.
+// If a copy of the MPL was not distributed with this file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::Path;
+use std::rc::Rc;
+
+use andromeda_core::ModuleResolver;
+use miette::{NamedSource, SourceSpan};
+use oxc_allocator::Allocator;
+use oxc_ast::ast::*;
+use oxc_ast_visit::{Visit, walk};
+use oxc_parser::Parser;
+use oxc_semantic::{ScopeFlags, Scoping, SymbolFlags, SymbolId};
+use oxc_span::{GetSpan, SourceType, Span};
+
+use super::TypeCheckError;
+use super::types::{Mismatch, ObjectType, Param, Property, Signature, Type, TypeEnv, TypeName};
+use crate::config::AndromedaConfig;
+
+/// The type declarations bundled with Andromeda, describing its globals
+const GLOBAL_DECLARATIONS: &[&str] = &[
+    include_str!("../../../../types/global.d.ts"),
+    include_str!("../../../../types/internals.d.ts"),
+];
+
+/// Globals of the language and the web platform that the bundled declarations
+/// rely on without declaring them
+const KNOWN_GLOBALS: &[&str] = &[
+    // ECMAScript
+    "globalThis",
+    "undefined",
+    "NaN",
+    "Infinity",
+    "Object",
+    "Function",
+    "Array",
+    "Number",
+    "Boolean",
+    "String",
+    "Symbol",
+    "BigInt",
+    "Date",
+    "Promise",
+    "RegExp",
+    "Error",
+    "AggregateError",
+    "EvalError",
+    "RangeError",
+    "ReferenceError",
+    "SyntaxError",
+    "TypeError",
+    "URIError",
+    "JSON",
+    "Math",
+    "Intl",
+    "Reflect",
+    "Proxy",
+    "Map",
+    "Set",
+    "WeakMap",
+    "WeakSet",
+    "WeakRef",
+    "FinalizationRegistry",
+    "ArrayBuffer",
+    "SharedArrayBuffer",
+    "Atomics",
+    "DataView",
+    "Int8Array",
+    "Uint8Array",
+    "Uint8ClampedArray",
+    "Int16Array",
+    "Uint16Array",
+    "Int32Array",
+    "Uint32Array",
+    "Float16Array",
+    "Float32Array",
+    "Float64Array",
+    "BigInt64Array",
+    "BigUint64Array",
+    "Iterator",
+    "Generator",
+    "AsyncGenerator",
+    "parseInt",
+    "parseFloat",
+    "isNaN",
+    "isFinite",
+    "decodeURI",
+    "decodeURIComponent",
+    "encodeURI",
+    "encodeURIComponent",
+    "escape",
+    "unescape",
+    "eval",
+    // TypeScript utility types
+    "Record",
+    "Partial",
+    "Required",
+    "Readonly",
+    "Pick",
+    "Omit",
+    "Exclude",
+    "Extract",
+    "NonNullable",
+    "ReturnType",
+    "Parameters",
+    "ConstructorParameters",
+    "InstanceType",
+    "Awaited",
+    "PromiseLike",
+    "ArrayLike",
+    "ReadonlyArray",
+    "Iterable",
+    "IterableIterator",
+    "IteratorResult",
+    "AsyncIterable",
+    "AsyncIterableIterator",
+    "PropertyKey",
+    "ArrayBufferLike",
+    "ArrayBufferView",
+    "TemplateStringsArray",
+    "ThisType",
+    "ThisParameterType",
+    "OmitThisParameter",
+    "NoInfer",
+    "Uppercase",
+    "Lowercase",
+    "Capitalize",
+    "Uncapitalize",
+    // Web platform
+    "console",
+    "setTimeout",
+    "clearTimeout",
+    "setInterval",
+    "clearInterval",
+    "queueMicrotask",
+    "fetch",
+    "Request",
+    "Response",
+    "Headers",
+    "FormData",
+    "URL",
+    "URLSearchParams",
+    "URLPattern",
+    "Blob",
+    "File",
+    "FileReader",
+    "AbortController",
+    "AbortSignal",
+    "Event",
+    "EventTarget",
+    "CustomEvent",
+    "ErrorEvent",
+    "MessageEvent",
+    "MessageChannel",
+    "MessagePort",
+    "BroadcastChannel",
+    "ReadableStream",
+    "WritableStream",
+    "TransformStream",
+    "ReadableStreamDefaultReader",
+    "WritableStreamDefaultWriter",
+    "ByteLengthQueuingStrategy",
+    "CountQueuingStrategy",
+    "CompressionStream",
+    "DecompressionStream",
+    "TextEncoderStream",
+    "TextDecoderStream",
+    "crypto",
+    "Crypto",
+    "CryptoKey",
+    "SubtleCrypto",
+    "performance",
+    "Performance",
+    "navigator",
+    "localStorage",
+    "sessionStorage",
+    "Storage",
+    "caches",
+    "CacheStorage",
+    "Cache",
+    "DOMException",
+    "WebSocket",
+    "atob",
+    "btoa",
+    "reportError",
+    "self",
+    "window",
+    "location",
+    "alert",
+    // CommonJS
+    "require",
+    "module",
+    "exports",
+    "__filename",
+    "__dirname",
+];
+
+/// Members every object has through `Object.prototype`
+const OBJECT_MEMBERS: &[&str] = &[
+    "constructor",
+    "hasOwnProperty",
+    "isPrototypeOf",
+    "propertyIsEnumerable",
+    "toLocaleString",
+    "toString",
+    "valueOf",
+];
+
+/// The values and types declared by the bundled type declarations
+#[derive(Debug, Default)]
+struct Globals {
+    values: HashMap<String, Type>,
+    types: HashMap<String, Type>,
+}
+
+/// The values and types a module exports, by exported name
+#[derive(Debug, Default)]
+struct ModuleExports {
+    values: HashMap<String, Type>,
+    types: HashMap<String, Type>,
+    /// Whether the module was found and parsed, so that missing exports are
+    /// known to be missing
+    loaded: bool,
+}
+
+/// A binding created by an import declaration
+struct Import {
+    local: String,
+    symbol: Option<SymbolId>,
+    value: Type,
+    ty: Option<Type>,
+}
+
+/// The declarations of a checked project: the bundled globals and the modules
+/// imported by the checked files, resolved like the runtime resolves them
+pub struct Project {
+    env: TypeEnv,
+    globals: Globals,
+    modules: HashMap<String, Rc<ModuleExports>>,
+    resolver: ModuleResolver,
+}
+
+impl Project {
+    pub fn new(config: Option<&AndromedaConfig>) -> Self {
+        let import_map =
+            config.and_then(|config| crate::run::build_import_map(config, None).ok().flatten());
+        let base_path = std::env::current_dir().unwrap_or_default();
+        let mut project = Self {
+            env: TypeEnv::default(),
+            globals: Globals::default(),
+            modules: HashMap::new(),
+            resolver: ModuleResolver::new(base_path, import_map),
+        };
+        project.load_globals();
+        project
+    }
+
+    /// Whether `name` is a value or type available in every module
+    pub fn is_global(&self, name: &str) -> bool {
+        self.globals.values.contains_key(name)
+            || self.globals.types.contains_key(name)
+            || KNOWN_GLOBALS.contains(&name)
+    }
+
+    fn load_globals(&mut self) {
+        let allocator = Allocator::default();
+        let programs: Vec<Program> = GLOBAL_DECLARATIONS
+            .iter()
+            .map(|source| {
+                Parser::new(&allocator, source, SourceType::d_ts())
+                    .parse()
+                    .program
+            })
+            .collect();
+        // The declaration files refer to each other's types
+        for program in &programs {
+            declare_types(&mut self.globals.types, "global", "", &program.body);
+        }
+        let empty = HashMap::new();
+        let scope = Scope::new(&self.globals.types, &empty);
+        for program in &programs {
+            define_types(&mut self.env, &scope, "global", "", &program.body);
+        }
+        let checker = Checker::new(&self.env, &self.globals.types, &empty, &empty, None);
+        for program in &programs {
+            for (name, ty) in checker.declared_values(&program.body) {
+                merge_value(&mut self.globals.values, name, ty);
+            }
+        }
+    }
+
+    /// Type check a parsed module, whose semantic analysis produced `scoping`
+    pub fn check(
+        &mut self,
+        path: &Path,
+        source_text: &str,
+        program: &Program,
+        scoping: &Scoping,
+    ) -> Vec<TypeCheckError> {
+        let key = module_key(path);
+        let mut names = HashMap::new();
+        declare_types(&mut names, &key, "", &program.body);
+        let imports = self.imports(Path::new(&key), &program.body);
+        for import in &imports {
+            if let Some(ty) = &import.ty {
+                names.insert(import.local.clone(), ty.clone());
+            }
+        }
+        let scope = Scope::new(&names, &self.globals.types);
+        define_types(&mut self.env, &scope, &key, "", &program.body);
+
+        let source = NamedSource::new(path.display().to_string(), source_text.to_string());
+        let mut checker = Checker::new(
+            &self.env,
+            &names,
+            &self.globals.types,
+            &self.globals.values,
+            Some(scoping),
+        );
+        checker.source = Some((source, source_text));
+        for import in imports {
+            if let Some(symbol) = import.symbol {
+                checker.bindings.insert(symbol, import.value);
+            }
+        }
+        checker.declare_functions(&program.body);
+        checker.visit_program(program);
+        checker.errors
+    }
+
+    /// The bindings of the import declarations among `statements`
+    fn imports(&mut self, referrer: &Path, statements: &[Statement]) -> Vec<Import> {
+        let mut imports = Vec::new();
+        for statement in statements {
+            let Statement::ImportDeclaration(import) = statement else {
+                continue;
+            };
+            let Some(specifiers) = &import.specifiers else {
+                continue;
+            };
+            let module = self.module_exports(&import.source.value, referrer);
+            for specifier in specifiers {
+                let (local, value, ty) = match specifier {
+                    ImportDeclarationSpecifier::ImportSpecifier(specifier) => {
+                        let imported = specifier.imported.name();
+                        (
+                            &specifier.local,
+                            module.values.get(imported.as_str()).cloned(),
+                            module.types.get(imported.as_str()).cloned(),
+                        )
+                    }
+                    ImportDeclarationSpecifier::ImportDefaultSpecifier(specifier) => (
+                        &specifier.local,
+                        module.values.get("default").cloned(),
+                        None,
+                    ),
+                    ImportDeclarationSpecifier::ImportNamespaceSpecifier(specifier) => {
+                        (&specifier.local, Some(namespace_object(&module)), None)
+                    }
+                };
+                imports.push(Import {
+                    local: local.name.to_string(),
+                    symbol: local.symbol_id.get(),
+                    value: value.unwrap_or(Type::Any),
+                    ty,
+                });
+            }
+        }
+        imports
+    }
+
+    /// The exports of the module `specifier` imported by `referrer`, which
+    /// are unknown for remote modules and modules that aren't TypeScript
+    fn module_exports(&mut self, specifier: &str, referrer: &Path) -> Rc<ModuleExports> {
+        if specifier.contains("://") || specifier.starts_with("jsr:") {
+            return Rc::default();
+        }
+        let Ok(resolved) = self
+            .resolver
+            .resolve(specifier, &referrer.to_string_lossy())
+        else {
+            return Rc::default();
+        };
+        if let Some(exports) = self.modules.get(&resolved) {
+            return exports.clone();
+        }
+        // Modules importing each other see each other's exports as unknown
+        self.modules.insert(resolved.clone(), Rc::default());
+
+        let path = Path::new(&resolved);
+        let exports = match SourceType::from_path(path) {
+            Ok(source_type) if source_type.is_typescript() => std::fs::read_to_string(path)
+                .map(|source| self.collect_exports(path, &source, source_type))
+                .unwrap_or_default(),
+            _ => ModuleExports::default(),
+        };
+        let exports = Rc::new(exports);
+        self.modules.insert(resolved, exports.clone());
+        exports
+    }
+
+    fn collect_exports(
+        &mut self,
+        path: &Path,
+        source: &str,
+        source_type: SourceType,
+    ) -> ModuleExports {
+        let allocator = Allocator::default();
+        let ret = Parser::new(&allocator, source, source_type).parse();
+        if ret.panicked {
+            return ModuleExports::default();
+        }
+        let statements = &ret.program.body;
+        let key = module_key(path);
+
+        let mut names = HashMap::new();
+        declare_types(&mut names, &key, "", statements);
+        let mut values = HashMap::new();
+        for import in self.imports(path, statements) {
+            if let Some(ty) = import.ty {
+                names.insert(import.local.clone(), ty);
+            }
+            values.insert(import.local, import.value);
+        }
+        let scope = Scope::new(&names, &self.globals.types);
+        define_types(&mut self.env, &scope, &key, "", statements);
+
+        let checker = Checker::new(
+            &self.env,
+            &names,
+            &self.globals.types,
+            &self.globals.values,
+            None,
+        );
+        values.extend(checker.declared_values(statements));
+        let default = statements.iter().find_map(|statement| match statement {
+            Statement::ExportDefaultDeclaration(export) => Some(match &export.declaration {
+                ExportDefaultDeclarationKind::FunctionDeclaration(function) => {
+                    checker.function_type(function)
+                }
+                kind => kind
+                    .as_expression()
+                    .map_or(Type::Any, |expression| checker.type_of(expression)),
+            }),
+            _ => None,
+        });
+
+        let mut exports = ModuleExports {
+            loaded: true,
+            ..ModuleExports::default()
+        };
+        if let Some(default) = default {
+            exports.values.insert("default".to_string(), default);
+        }
+        for statement in statements {
+            match statement {
+                Statement::ExportNamedDeclaration(export) => {
+                    for name in export
+                        .declaration
+                        .as_ref()
+                        .map(declaration_names)
+                        .unwrap_or_default()
+                    {
+                        copy_export(&mut exports, &values, &names, &name, &name);
+                    }
+                    let module = export
+                        .source
+                        .as_ref()
+                        .map(|source| self.module_exports(&source.value, path));
+                    for specifier in &export.specifiers {
+                        let local = specifier.local.name();
+                        let exported = specifier.exported.name();
+                        match &module {
+                            Some(module) => copy_export(
+                                &mut exports,
+                                &module.values,
+                                &module.types,
+                                &local,
+                                &exported,
+                            ),
+                            None => copy_export(&mut exports, &values, &names, &local, &exported),
+                        }
+                    }
+                }
+                Statement::ExportAllDeclaration(export) => {
+                    let module = self.module_exports(&export.source.value, path);
+                    match &export.exported {
+                        Some(exported) => {
+                            exports
+                                .values
+                                .insert(exported.name().to_string(), namespace_object(&module));
+                        }
+                        None if module.loaded => {
+                            for (name, ty) in &module.values {
+                                if name != "default" {
+                                    exports.values.insert(name.clone(), ty.clone());
+                                }
+                            }
+                            exports.types.extend(module.types.clone());
+                        }
+                        // The names the module exports are unknown
+                        None => exports.loaded = false,
+                    }
+                }
+                _ => {}
+            }
+        }
+        exports
+    }
+}
+
+fn copy_export(
+    exports: &mut ModuleExports,
+    values: &HashMap<String, Type>,
+    types: &HashMap<String, Type>,
+    local: &str,
+    exported: &str,
+) {
+    if let Some(value) = values.get(local) {
+        exports.values.insert(exported.to_string(), value.clone());
+    }
+    if let Some(ty) = types.get(local) {
+        exports.types.insert(exported.to_string(), ty.clone());
+    }
+}
+
+/// The object an `import * as` or `export * as` of a module binds
+fn namespace_object(module: &ModuleExports) -> Type {
+    if !module.loaded {
+        return Type::Any;
+    }
+    let properties = module
+        .values
+        .iter()
+        .map(|(name, ty)| {
+            let property = Property {
+                ty: ty.clone(),
+                optional: false,
+                readonly: true,
+            };
+            (name.clone(), property)
+        })
+        .collect();
+    Type::object(properties, false)
+}
+
+/// The id types declared by the module at `path` are prefixed with
+fn module_key(path: &Path) -> String {
+    std::fs::canonicalize(path)
+        .unwrap_or_else(|_| path.to_path_buf())
+        .to_string_lossy()
+        .to_string()
+}
+
+/// Record the type of a value declared more than once, such as an overloaded
+/// function or a namespace declared in several files
+fn merge_value(values: &mut HashMap<String, Type>, name: String, ty: Type) {
+    let ty = match (values.remove(&name), ty) {
+        (None, ty) => ty,
+        (Some(Type::Object(existing)), Type::Object(added)) => {
+            let mut merged = (*existing).clone();
+            for (name, property) in &added.properties {
+                merged
+                    .properties
+                    .entry(name.clone())
+                    .or_insert_with(|| property.clone());
+            }
+            Type::Object(Rc::new(merged))
+        }
+        // Overloads aren't modelled
+        (Some(_), _) => Type::Any,
+    };
+    values.insert(name, ty);
+}
+
+/// The declaration of a statement, exported or not
+fn statement_declaration<'b, 'a>(statement: &'b Statement<'a>) -> Option<&'b Declaration<'a>> {
+    match statement {
+        Statement::ExportNamedDeclaration(export) => export.declaration.as_ref(),
+        statement => statement.as_declaration(),
+    }
+}
+
+/// The name and statements of a namespace, `None` for ambient modules
+fn namespace_body<'b, 'a>(
+    module: &'b TSModuleDeclaration<'a>,
+) -> Option<(String, &'b [Statement<'a>])> {
+    let TSModuleDeclarationName::Identifier(id) = &module.id else {
+        return None;
+    };
+    match module.body.as_ref()? {
+        TSModuleDeclarationBody::TSModuleBlock(block) => {
+            Some((id.name.to_string(), &block.body[..]))
+        }
+        TSModuleDeclarationBody::TSModuleDeclaration(nested) => {
+            let (name, body) = namespace_body(nested)?;
+            Some((format!("{}.{name}", id.name), body))
+        }
+    }
+}
+
+/// The names of the values and types a declaration binds
+fn declaration_names(declaration: &Declaration) -> Vec<String> {
+    match declaration {
+        Declaration::VariableDeclaration(variable) => variable
+            .declarations
+            .iter()
+            .filter_map(|declarator| declarator.id.get_binding_identifier())
+            .map(|id| id.name.to_string())
+            .collect(),
+        Declaration::FunctionDeclaration(function) => {
+            function.id.iter().map(|id| id.name.to_string()).collect()
+        }
+        Declaration::ClassDeclaration(class) => {
+            class.id.iter().map(|id| id.name.to_string()).collect()
+        }
+        Declaration::TSInterfaceDeclaration(interface) => vec![interface.id.name.to_string()],
+        Declaration::TSTypeAliasDeclaration(alias) => vec![alias.id.name.to_string()],
+        Declaration::TSEnumDeclaration(declaration) => vec![declaration.id.name.to_string()],
+        Declaration::TSModuleDeclaration(module) => namespace_body(module)
+            .map(|(name, _)| vec![name])
+            .unwrap_or_default(),
+        _ => Vec::new(),
+    }
+}
+
+/// Give an id to the types declared among `statements`, so that declarations
+/// can refer to each other in any order
+fn declare_types(
+    names: &mut HashMap<String, Type>,
+    key: &str,
+    prefix: &str,
+    statements: &[Statement],
+) {
+    for declaration in statements.iter().filter_map(statement_declaration) {
+        let name = match declaration {
+            Declaration::TSInterfaceDeclaration(interface) => interface.id.name.as_str(),
+            Declaration::TSTypeAliasDeclaration(alias) => alias.id.name.as_str(),
+            Declaration::ClassDeclaration(class) => match &class.id {
+                Some(id) => id.name.as_str(),
+                None => continue,
+            },
+            // Enums aren't modelled
+            Declaration::TSEnumDeclaration(declaration) => {
+                names.insert(format!("{prefix}{}", declaration.id.name), Type::Any);
+                continue;
+            }
+            Declaration::TSModuleDeclaration(module) => {
+                if let Some((name, body)) = namespace_body(module) {
+                    declare_types(names, key, &format!("{prefix}{name}."), body);
+                }
+                continue;
+            }
+            _ => continue,
+        };
+        let name = format!("{prefix}{name}");
+        let ty = Type::Named(Rc::new(TypeName {
+            id: format!("{key}#{name}"),
+            name: name.clone(),
+        }));
+        names.insert(name, ty);
+    }
+}
+
+/// Define the types declared among `statements` in the environment
+fn define_types(
+    env: &mut TypeEnv,
+    scope: &Scope,
+    key: &str,
+    prefix: &str,
+    statements: &[Statement],
+) {
+    for declaration in statements.iter().filter_map(statement_declaration) {
+        let (name, ty) = match declaration {
+            Declaration::TSInterfaceDeclaration(interface) => {
+                let scope = scope.with_params(interface.type_parameters.as_deref());
+                let mut object = scope.members(&interface.body.body);
+                for heritage in &interface.extends {
+                    let base = match &heritage.expression {
+                        Expression::Identifier(base) => scope.lookup(&base.name),
+                        _ => None,
+                    };
+                    match base.map(|base| env.resolve(&base)) {
+                        Some(Type::Object(base)) => {
+                            object.open |= base.open;
+                            for (name, property) in &base.properties {
+                                object
+                                    .properties
+                                    .entry(name.clone())
+                                    .or_insert_with(|| property.clone());
+                            }
+                        }
+                        _ => object.open = true,
+                    }
+                }
+                (interface.id.name.as_str(), Type::Object(Rc::new(object)))
+            }
+            Declaration::TSTypeAliasDeclaration(alias) => {
+                let scope = scope.with_params(alias.type_parameters.as_deref());
+                (
+                    alias.id.name.as_str(),
+                    scope.convert(&alias.type_annotation),
+                )
+            }
+            Declaration::ClassDeclaration(class) => match &class.id {
+                Some(id) => (id.name.as_str(), scope.class_instance(class)),
+                None => continue,
+            },
+            Declaration::TSModuleDeclaration(module) => {
+                if let Some((name, body)) = namespace_body(module) {
+                    // Types of a namespace refer to each other unqualified
+                    let prefix = format!("{prefix}{name}.");
+                    let mut names = scope.names.clone();
+                    for (qualified, ty) in scope.names {
+                        if let Some(name) = qualified.strip_prefix(&prefix) {
+                            names.insert(name.to_string(), ty.clone());
+                        }
+                    }
+                    let scope = Scope {
+                        names: &names,
+                        ..scope.clone()
+                    };
+                    define_types(env, &scope, key, &prefix, body);
+                }
+                continue;
+            }
+            _ => continue,
+        };
+        env.insert(format!("{key}#{prefix}{name}"), ty);
+    }
+}
+
+/// Converts type annotations to [Type]s, resolving type names declared by the
+/// module and then by the globals
+#[derive(Clone)]
+struct Scope<'s> {
+    names: &'s HashMap<String, Type>,
+    globals: &'s HashMap<String, Type>,
+    /// Type parameters in scope, which aren't modelled
+    params: Vec<String>,
+}
+
+impl<'s> Scope<'s> {
+    fn new(names: &'s HashMap<String, Type>, globals: &'s HashMap<String, Type>) -> Self {
+        Self {
+            names,
+            globals,
+            params: Vec::new(),
+        }
+    }
+
+    fn with_params(&self, params: Option<&TSTypeParameterDeclaration>) -> Self {
+        let mut scope = self.clone();
+        if let Some(params) = params {
+            scope.params.extend(
+                params
+                    .params
+                    .iter()
+                    .map(|param| param.name.name.to_string()),
+            );
+        }
+        scope
+    }
+
+    fn lookup(&self, name: &str) -> Option<Type> {
+        if self.params.iter().any(|param| param == name) {
+            return Some(Type::Any);
+        }
+        self.names
+            .get(name)
+            .or_else(|| self.globals.get(name))
+            .cloned()
+    }
+
+    fn convert(&self, ty: &TSType) -> Type {
+        match ty {
+            TSType::TSAnyKeyword(_) => Type::Any,
+            TSType::TSUnknownKeyword(_) => Type::Unknown,
+            TSType::TSNeverKeyword(_) => Type::Never,
+            TSType::TSVoidKeyword(_) => Type::Void,
+            TSType::TSUndefinedKeyword(_) => Type::Undefined,
+            TSType::TSNullKeyword(_) => Type::Null,
+            TSType::TSBooleanKeyword(_) => Type::Boolean,
+            TSType::TSNumberKeyword(_) => Type::Number,
+            TSType::TSStringKeyword(_) => Type::String,
+            TSType::TSBigIntKeyword(_) => Type::BigInt,
+            TSType::TSSymbolKeyword(_) => Type::Symbol,
+            TSType::TSLiteralType(literal) => match &literal.literal {
+                TSLiteral::BooleanLiteral(literal) => Type::BooleanLiteral(literal.value),
+                TSLiteral::NumericLiteral(literal) => Type::NumberLiteral(literal.value),
+                TSLiteral::StringLiteral(literal) => Type::StringLiteral(literal.value.to_string()),
+                _ => Type::Any,
+            },
+            TSType::TSArrayType(array) => Type::Array(Box::new(self.convert(&array.element_type))),
+            TSType::TSTupleType(tuple) => tuple
+                .element_types
+                .iter()
+                .map(|element| element.as_ts_type().map(|ty| self.convert(ty)))
+                .collect::<Option<Vec<_>>>()
+                .map_or(Type::Any, Type::Tuple),
+            TSType::TSUnionType(union) => {
+                Type::union(union.types.iter().map(|ty| self.convert(ty)))
+            }
+            TSType::TSParenthesizedType(ty) => self.convert(&ty.type_annotation),
+            TSType::TSFunctionType(function) => self.signature(
+                function.type_parameters.as_deref(),
+                &function.params,
+                Some(&function.return_type.type_annotation),
+            ),
+            TSType::TSTypeLiteral(literal) => Type::Object(Rc::new(self.members(&literal.members))),
+            TSType::TSTypeReference(reference) => {
+                let Some(name) = type_name(&reference.type_name) else {
+                    return Type::Any;
+                };
+                match (name.as_str(), &reference.type_arguments) {
+                    ("Array" | "ReadonlyArray", Some(arguments)) if arguments.params.len() == 1 => {
+                        Type::Array(Box::new(self.convert(&arguments.params[0])))
+                    }
+                    (name, _) => self.lookup(name).unwrap_or(Type::Any),
+                }
+            }
+            _ => Type::Any,
+        }
+    }
+
+    /// The type of a binding pattern, from its annotation
+    fn pattern(&self, pattern: &BindingPattern) -> Type {
+        let annotation = match &pattern.kind {
+            BindingPatternKind::AssignmentPattern(assignment)
+                if pattern.type_annotation.is_none() =>
+            {
+                &assignment.left.type_annotation
+            }
+            _ => &pattern.type_annotation,
+        };
+        annotation.as_ref().map_or(Type::Any, |annotation| {
+            self.convert(&annotation.type_annotation)
+        })
+    }
+
+    fn params(&self, params: &FormalParameters) -> (Vec<Param>, Option<Type>) {
+        let items = params
+            .items
+            .iter()
+            .enumerate()
+            .map(|(idx, param)| Param {
+                name: param
+                    .pattern
+                    .get_binding_identifier()
+                    .map_or_else(|| format!("arg{idx}"), |id| id.name.to_string()),
+                ty: self.pattern(&param.pattern),
+                optional: param.pattern.optional
+                    || matches!(param.pattern.kind, BindingPatternKind::AssignmentPattern(_)),
+            })
+            .collect();
+        let rest = params
+            .rest
+            .as_ref()
+            .map(|rest| match self.pattern(&rest.argument) {
+                Type::Array(element) => *element,
+                _ => Type::Any,
+            });
+        (items, rest)
+    }
+
+    fn signature(
+        &self,
+        type_params: Option<&TSTypeParameterDeclaration>,
+        params: &FormalParameters,
+        ret: Option<&TSType>,
+    ) -> Type {
+        let scope = self.with_params(type_params);
+        let (params, rest) = scope.params(params);
+        let ret = ret.map_or(Type::Any, |ret| scope.convert(ret));
+        Type::function(params, rest, ret)
+    }
+
+    fn members(&self, members: &[TSSignature]) -> ObjectType {
+        let mut object = ObjectType::default();
+        for member in members {
+            let (name, property) = match member {
+                TSSignature::TSPropertySignature(signature) => {
+                    let property = Property {
+                        ty: signature
+                            .type_annotation
+                            .as_ref()
+                            .map_or(Type::Any, |annotation| {
+                                self.convert(&annotation.type_annotation)
+                            }),
+                        optional: signature.optional,
+                        readonly: signature.readonly,
+                    };
+                    (signature.key.static_name(), property)
+                }
+                TSSignature::TSMethodSignature(signature) => {
+                    let ret = signature
+                        .return_type
+                        .as_ref()
+                        .map(|annotation| &annotation.type_annotation);
+                    let ty = match signature.kind {
+                        TSMethodSignatureKind::Method => self.signature(
+                            signature.type_parameters.as_deref(),
+                            &signature.params,
+                            ret,
+                        ),
+                        TSMethodSignatureKind::Get => {
+                            ret.map_or(Type::Any, |ret| self.convert(ret))
+                        }
+                        TSMethodSignatureKind::Set => Type::Any,
+                    };
+                    let property = Property {
+                        ty,
+                        optional: signature.optional,
+                        readonly: false,
+                    };
+                    (signature.key.static_name(), property)
+                }
+                // Index, call and construct signatures
+                _ => {
+                    object.open = true;
+                    continue;
+                }
+            };
+            match name {
+                Some(name) => add_member(&mut object, name.to_string(), property),
+                None => object.open = true,
+            }
+        }
+        object
+    }
+
+    /// The type of the instances of a class
+    fn class_instance(&self, class: &Class) -> Type {
+        let scope = self.with_params(class.type_parameters.as_deref());
+        let mut object = ObjectType {
+            open: class.super_class.is_some(),
+            ..ObjectType::default()
+        };
+        for element in &class.body.body {
+            let (key, property) = match element {
+                ClassElement::PropertyDefinition(definition) if !definition.r#static => {
+                    let property = Property {
+                        ty: definition
+                            .type_annotation
+                            .as_ref()
+                            .map_or(Type::Any, |annotation| {
+                                scope.convert(&annotation.type_annotation)
+                            }),
+                        optional: definition.optional,
+                        readonly: definition.readonly,
+                    };
+                    (&definition.key, property)
+                }
+                ClassElement::MethodDefinition(method) if !method.r#static => {
+                    let function = &method.value;
+                    let ret = function
+                        .return_type
+                        .as_ref()
+                        .map(|annotation| &annotation.type_annotation);
+                    let ty = match method.kind {
+                        MethodDefinitionKind::Constructor => {
+                            // Parameter properties
+                            for param in &function.params.items {
+                                if (param.accessibility.is_some() || param.readonly)
+                                    && let Some(id) = param.pattern.get_binding_identifier()
+                                {
+                                    let property = Property {
+                                        ty: scope.pattern(&param.pattern),
+                                        optional: param.pattern.optional,
+                                        readonly: param.readonly,
+                                    };
+                                    add_member(&mut object, id.name.to_string(), property);
+                                }
+                            }
+                            continue;
+                        }
+                        MethodDefinitionKind::Method => scope.signature(
+                            function.type_parameters.as_deref(),
+                            &function.params,
+                            ret,
+                        ),
+                        MethodDefinitionKind::Get => {
+                            ret.map_or(Type::Any, |ret| scope.convert(ret))
+                        }
+                        MethodDefinitionKind::Set => Type::Any,
+                    };
+                    let property = Property {
+                        ty,
+                        optional: method.optional,
+                        readonly: false,
+                    };
+                    (&method.key, property)
+                }
+                ClassElement::AccessorProperty(_) | ClassElement::TSIndexSignature(_) => {
+                    object.open = true;
+                    continue;
+                }
+                _ => continue,
+            };
+            if matches!(key, PropertyKey::PrivateIdentifier(_)) {
+                continue;
+            }
+            match key.static_name() {
+                Some(name) => add_member(&mut object, name.to_string(), property),
+                None => object.open = true,
+            }
+        }
+        Type::Object(Rc::new(object))
+    }
+}
+
+/// Add a member to an object type, where overloaded methods and accessor pairs
+/// declare the same member more than once
+fn add_member(object: &mut ObjectType, name: String, property: Property) {
+    match object.properties.get_mut(&name) {
+        Some(existing) if matches!(existing.ty, Type::Function(_)) => existing.ty = Type::Any,
+        Some(_) => {}
+        None => {
+            object.properties.insert(name, property);
+        }
+    }
+}
+
+fn type_name(name: &TSTypeName) -> Option<String> {
+    match name {
+        TSTypeName::IdentifierReference(id) => Some(id.name.to_string()),
+        TSTypeName::QualifiedName(name) => {
+            Some(format!("{}.{}", type_name(&name.left)?, name.right.name))
+        }
+        _ => None,
+    }
+}
+
+fn error_span(span: Span) -> SourceSpan {
+    SourceSpan::new((span.start as usize).into(), span.size() as usize)
+}
+
+/// Where an expression checked against a type appears
+#[derive(Clone, Copy)]
+enum Context {
+    Assignment,
+    Return,
+}
+
+/// Infers the types of expressions and checks them against the types they are
+/// assigned to, passed as or returned as.
+///
+/// Control flow narrowing isn't modelled, so values of union types are only
+/// checked where they are assigned, not where they are used.
+struct Checker<'c> {
+    env: &'c TypeEnv,
+    /// Types visible in the module by name
+    names: &'c HashMap<String, Type>,
+    global_types: &'c HashMap<String, Type>,
+    global_values: &'c HashMap<String, Type>,
+    scoping: Option<&'c Scoping>,
+    /// Declared types of the bindings of the module
+    bindings: HashMap<SymbolId, Type>,
+    /// Type parameters in scope
+    type_params: Vec<String>,
+    /// Declared return types of the enclosing functions
+    returns: Vec<Option<Type>>,
+    /// The checked source and its text, when reporting errors
+    source: Option<(NamedSource<String>, &'c str)>,
+    errors: Vec<TypeCheckError>,
+}
+
+impl<'c> Checker<'c> {
+    fn new(
+        env: &'c TypeEnv,
+        names: &'c HashMap<String, Type>,
+        global_types: &'c HashMap<String, Type>,
+        global_values: &'c HashMap<String, Type>,
+        scoping: Option<&'c Scoping>,
+    ) -> Self {
+        Self {
+            env,
+            names,
+            global_types,
+            global_values,
+            scoping,
+            bindings: HashMap::new(),
+            type_params: Vec::new(),
+            returns: Vec::new(),
+            source: None,
+            errors: Vec::new(),
+        }
+    }
+
+    fn scope(&self) -> Scope<'c> {
+        Scope {
+            names: self.names,
+            globals: self.global_types,
+            params: self.type_params.clone(),
+        }
+    }
+
+    fn convert(&self, ty: &TSType) -> Type {
+        self.scope().convert(ty)
+    }
+
+    fn function_type(&self, function: &Function) -> Type {
+        self.scope().signature(
+            function.type_parameters.as_deref(),
+            &function.params,
+            function
+                .return_type
+                .as_ref()
+                .map(|annotation| &annotation.type_annotation),
+        )
+    }
+
+    /// The types of the values declared among `statements`, by name
+    fn declared_values(&self, statements: &[Statement]) -> HashMap<String, Type> {
+        let mut values = HashMap::new();
+        for declaration in statements.iter().filter_map(statement_declaration) {
+            match declaration {
+                Declaration::VariableDeclaration(variable) => {
+                    for declarator in &variable.declarations {
+                        let Some(id) = declarator.id.get_binding_identifier() else {
+                            continue;
+                        };
+                        let ty = match (&declarator.id.type_annotation, &declarator.init) {
+                            (Some(annotation), _) => self.convert(&annotation.type_annotation),
+                            (None, Some(init))
+                                if variable.kind == VariableDeclarationKind::Const =>
+                            {
+                                self.type_of(init)
+                            }
+                            (None, Some(init)) => self.type_of(init).widen(),
+                            (None, None) => Type::Any,
+                        };
+                        merge_value(&mut values, id.name.to_string(), ty);
+                    }
+                }
+                Declaration::FunctionDeclaration(function) => {
+                    if let Some(id) = &function.id {
+                        merge_value(
+                            &mut values,
+                            id.name.to_string(),
+                            self.function_type(function),
+                        );
+                    }
+                }
+                Declaration::ClassDeclaration(class) => {
+                    if let Some(id) = &class.id {
+                        values.insert(id.name.to_string(), Type::Any);
+                    }
+                }
+                Declaration::TSEnumDeclaration(declaration) => {
+                    values.insert(declaration.id.name.to_string(), Type::Any);
+                }
+                Declaration::TSModuleDeclaration(module) => {
+                    let Some((name, body)) = namespace_body(module) else {
+                        continue;
+                    };
+                    if name.contains('.') {
+                        values.insert(name, Type::Any);
+                        continue;
+                    }
+                    // Members of a namespace refer to its types unqualified
+                    let prefix = format!("{name}.");
+                    let mut names = self.names.clone();
+                    for (qualified, ty) in self.names {
+                        if let Some(name) = qualified.strip_prefix(&prefix) {
+                            names.insert(name.to_string(), ty.clone());
+                        }
+                    }
+                    let checker = Checker::new(
+                        self.env,
+                        &names,
+                        self.global_types,
+                        self.global_values,
+                        None,
+                    );
+                    let properties = checker
+                        .declared_values(body)
+                        .into_iter()
+                        .map(|(name, ty)| {
+                            let property = Property {
+                                ty,
+                                optional: false,
+                                readonly: false,
+                            };
+                            (name, property)
+                        })
+                        .collect();
+                    merge_value(&mut values, name, Type::object(properties, false));
+                }
+                _ => {}
+            }
+        }
+        values
+    }
+
+    /// Bind the functions declared at the top level of the module, which can
+    /// be called before their declaration
+    fn declare_functions(&mut self, statements: &[Statement]) {
+        for declaration in statements.iter().filter_map(statement_declaration) {
+            if let Declaration::FunctionDeclaration(function) = declaration
+                && let Some(symbol) = function.id.as_ref().and_then(|id| id.symbol_id.get())
+            {
+                let ty = self.function_type(function);
+                match self.bindings.get(&symbol) {
+                    // Overloads aren't modelled
+                    Some(_) => self.bindings.insert(symbol, Type::Any),
+                    None => self.bindings.insert(symbol, ty),
+                };
+            }
+        }
+    }
+
+    /// The symbol an identifier refers to, if declared in the module
+    fn symbol(&self, id: &IdentifierReference) -> Option<SymbolId> {
+        let scoping = self.scoping?;
+        scoping.get_reference(id.reference_id.get()?).symbol_id()
+    }
+
+    /// The declared type of the binding an identifier refers to
+    fn declared_type(&self, id: &IdentifierReference) -> Option<Type> {
+        match self.symbol(id) {
+            Some(symbol) => self.bindings.get(&symbol).cloned(),
+            None if self.scoping.is_some() => self.global_values.get(id.name.as_str()).cloned(),
+            None => None,
+        }
+    }
+
+    fn identifier_type(&self, id: &IdentifierReference) -> Type {
+        if self.symbol(id).is_none() {
+            match id.name.as_str() {
+                "undefined" => return Type::Undefined,
+                "NaN" | "Infinity" => return Type::Number,
+                _ => {}
+            }
+        }
+        self.narrowable(self.declared_type(id).unwrap_or(Type::Any))
+    }
+
+    /// The type of a read of a variable or property declared with `ty`. Reads
+    /// of union and unknown types may be narrowed by control flow, which isn't
+    /// tracked, so they aren't checked.
+    fn narrowable(&self, ty: Type) -> Type {
+        match self.env.resolve(&ty) {
+            Type::Union(_) | Type::Unknown => Type::Any,
+            _ => ty,
+        }
+    }
+
+    /// The member `name` of a value of type `ty`, if known
+    fn property(&self, ty: &Type, name: &str) -> Option<Property> {
+        match self.env.resolve(ty) {
+            Type::Object(object) => object.properties.get(name).cloned(),
+            Type::Array(_) | Type::Tuple(_) | Type::String | Type::StringLiteral(_)
+                if name == "length" =>
+            {
+                Some(Property {
+                    ty: Type::Number,
+                    optional: false,
+                    readonly: false,
+                })
+            }
+            _ => None,
+        }
+    }
+
+    /// The type of the instances created by `new callee()`
+    fn instance_type(&self, callee: &Expression) -> Type {
+        let Expression::Identifier(id) = callee else {
+            return Type::Any;
+        };
+        let class = match (self.scoping, self.symbol(id)) {
+            (Some(scoping), Some(symbol))
+                if scoping
+                    .symbol_flags(symbol)
+                    .intersects(SymbolFlags::Class | SymbolFlags::Import) =>
+            {
+                self.names.get(id.name.as_str())
+            }
+            (Some(_), Some(_)) => None,
+            (Some(_), None) => self.global_types.get(id.name.as_str()),
+            (None, _) => None,
+        };
+        class.cloned().unwrap_or(Type::Any)
+    }
+
+    fn type_of(&self, expression: &Expression) -> Type {
+        match expression {
+            Expression::BooleanLiteral(literal) => Type::BooleanLiteral(literal.value),
+            Expression::NumericLiteral(literal) => Type::NumberLiteral(literal.value),
+            Expression::StringLiteral(literal) => Type::StringLiteral(literal.value.to_string()),
+            Expression::TemplateLiteral(_) => Type::String,
+            Expression::NullLiteral(_) => Type::Null,
+            Expression::BigIntLiteral(_) => Type::BigInt,
+            Expression::Identifier(id) => self.identifier_type(id),
+            Expression::ArrayExpression(array) => {
+                let elements: Option<Vec<Type>> = array
+                    .elements
+                    .iter()
+                    .map(|element| element.as_expression().map(|element| self.type_of(element)))
+                    .collect();
+                match elements {
+                    Some(elements) if !elements.is_empty() => {
+                        Type::Array(Box::new(Type::union(elements.into_iter().map(Type::widen))))
+                    }
+                    _ => Type::Array(Box::new(Type::Any)),
+                }
+            }
+            Expression::ObjectExpression(object) => self.object_type(object),
+            Expression::ArrowFunctionExpression(arrow) => self.scope().signature(
+                arrow.type_parameters.as_deref(),
+                &arrow.params,
+                arrow
+                    .return_type
+                    .as_ref()
+                    .map(|annotation| &annotation.type_annotation),
+            ),
+            Expression::FunctionExpression(function) => self.function_type(function),
+            Expression::ParenthesizedExpression(expression) => self.type_of(&expression.expression),
+            Expression::TSAsExpression(expression) => self.convert(&expression.type_annotation),
+            Expression::TSTypeAssertion(expression) => self.convert(&expression.type_annotation),
+            Expression::TSSatisfiesExpression(expression) => self.type_of(&expression.expression),
+            Expression::TSNonNullExpression(expression) => {
+                match self.env.resolve(&self.type_of(&expression.expression)) {
+                    Type::Union(members) => Type::union(
+                        members
+                            .into_iter()
+                            .filter(|member| !matches!(member, Type::Null | Type::Undefined)),
+                    ),
+                    ty => ty,
+                }
+            }
+            Expression::ConditionalExpression(expression) => Type::union([
+                self.type_of(&expression.consequent),
+                self.type_of(&expression.alternate),
+            ]),
+            Expression::SequenceExpression(expression) => expression
+                .expressions
+                .last()
+                .map_or(Type::Any, |expression| self.type_of(expression)),
+            Expression::AssignmentExpression(expression)
+                if expression.operator == AssignmentOperator::Assign =>
+            {
+                self.type_of(&expression.right)
+            }
+            Expression::UnaryExpression(expression) => match expression.operator {
+                UnaryOperator::Typeof => Type::String,
+                UnaryOperator::LogicalNot | UnaryOperator::Delete => Type::Boolean,
+                UnaryOperator::Void => Type::Undefined,
+                UnaryOperator::UnaryNegation => match self.type_of(&expression.argument) {
+                    Type::NumberLiteral(value) => Type::NumberLiteral(-value),
+                    Type::BigInt => Type::BigInt,
+                    _ => Type::Number,
+                },
+                UnaryOperator::UnaryPlus => Type::Number,
+                UnaryOperator::BitwiseNot => Type::Any,
+            },
+            Expression::BinaryExpression(expression) => match expression.operator {
+                BinaryOperator::Equality
+                | BinaryOperator::Inequality
+                | BinaryOperator::StrictEquality
+                | BinaryOperator::StrictInequality
+                | BinaryOperator::LessThan
+                | BinaryOperator::LessEqualThan
+                | BinaryOperator::GreaterThan
+                | BinaryOperator::GreaterEqualThan
+                | BinaryOperator::In
+                | BinaryOperator::Instanceof => Type::Boolean,
+                BinaryOperator::Addition => {
+                    let left = self.env.resolve(&self.type_of(&expression.left));
+                    let right = self.env.resolve(&self.type_of(&expression.right));
+                    let is_string = |ty: &Type| matches!(ty, Type::String | Type::StringLiteral(_));
+                    let is_number = |ty: &Type| matches!(ty, Type::Number | Type::NumberLiteral(_));
+                    if is_string(&left) || is_string(&right) {
+                        Type::String
+                    } else if is_number(&left) && is_number(&right) {
+                        Type::Number
+                    } else {
+                        Type::Any
+                    }
+                }
+                // Other operators may also apply to bigints
+                _ => Type::Any,
+            },
+            Expression::CallExpression(call) => match self.env.resolve(&self.type_of(&call.callee))
+            {
+                Type::Function(signature) => signature.ret.clone(),
+                _ => Type::Any,
+            },
+            Expression::NewExpression(expression) => self.instance_type(&expression.callee),
+            Expression::StaticMemberExpression(member) => {
+                match self.property(&self.type_of(&member.object), &member.property.name) {
+                    Some(property) if property.optional => {
+                        self.narrowable(Type::union([property.ty, Type::Undefined]))
+                    }
+                    Some(property) => self.narrowable(property.ty),
+                    None => Type::Any,
+                }
+            }
+            _ => Type::Any,
+        }
+    }
+
+    fn object_type(&self, object: &ObjectExpression) -> Type {
+        let mut properties = BTreeMap::new();
+        let mut open = false;
+        for property in &object.properties {
+            let ObjectPropertyKind::ObjectProperty(property) = property else {
+                open = true;
+                continue;
+            };
+            let Some(name) = property.key.static_name() else {
+                open = true;
+                continue;
+            };
+            let ty = match property.kind {
+                PropertyKind::Init => self.type_of(&property.value).widen(),
+                PropertyKind::Get | PropertyKind::Set => Type::Any,
+            };
+            let property = Property {
+                ty,
+                optional: false,
+                readonly: false,
+            };
+            properties.insert(name.to_string(), property);
+        }
+        Type::object(properties, open)
+    }
+
+    fn report(&mut self, error: impl FnOnce(NamedSource<String>) -> TypeCheckError) {
+        if let Some((source, _)) = &self.source {
+            self.errors.push(error(source.clone()));
+        }
+    }
+
+    /// The source text of a node
+    fn text(&self, span: Span) -> String {
+        self.source
+            .as_ref()
+            .map(|(_, text)| span.source_text(text).to_string())
+            .unwrap_or_default()
+    }
+
+    /// Bind the annotated parameters of a function
+    fn bind_params(&mut self, params: &FormalParameters) {
+        let scope = self.scope();
+        for param in &params.items {
+            if let BindingPatternKind::BindingIdentifier(id) = &param.pattern.kind
+                && let Some(symbol) = id.symbol_id.get()
+                && param.pattern.type_annotation.is_some()
+            {
+                let mut ty = scope.pattern(&param.pattern);
+                if param.pattern.optional {
+                    ty = Type::union([ty, Type::Undefined]);
+                }
+                self.bindings.insert(symbol, ty);
+            }
+        }
+        if let Some(rest) = &params.rest
+            && let BindingPatternKind::BindingIdentifier(id) = &rest.argument.kind
+            && let Some(symbol) = id.symbol_id.get()
+        {
+            self.bindings.insert(symbol, scope.pattern(&rest.argument));
+        }
+    }
+
+    /// Type the unannotated parameters of a callback by the signature it is
+    /// passed or assigned as
+    fn bind_contextual_params(&mut self, params: &FormalParameters, signature: &Signature) {
+        for (idx, param) in params.items.iter().enumerate() {
+            if param.pattern.type_annotation.is_none()
+                && let BindingPatternKind::BindingIdentifier(id) = &param.pattern.kind
+                && let Some(symbol) = id.symbol_id.get()
+                && let Some(ty) = signature.param_type(idx)
+            {
+                self.bindings.insert(symbol, ty.clone());
+            }
+        }
+    }
+
+    fn push_type_params(&mut self, params: Option<&TSTypeParameterDeclaration>) -> usize {
+        let len = self.type_params.len();
+        if let Some(params) = params {
+            self.type_params.extend(
+                params
+                    .params
+                    .iter()
+                    .map(|param| param.name.name.to_string()),
+            );
+        }
+        len
+    }
+
+    /// Check an expression against the type it is assigned to
+    fn check_expression(&mut self, expression: &Expression, target: &Type, context: Context) {
+        let resolved = self.env.resolve(target);
+        match (expression, &resolved) {
+            (_, Type::Any | Type::Unknown) => {}
+            (Expression::ParenthesizedExpression(expression), _) => {
+                self.check_expression(&expression.expression, target, context);
+            }
+            (Expression::ConditionalExpression(expression), _) => {
+                self.check_expression(&expression.consequent, target, context);
+                self.check_expression(&expression.alternate, target, context);
+            }
+            (Expression::ObjectExpression(object), Type::Object(expected)) => {
+                self.check_object(object, target, expected);
+            }
+            (Expression::ArrayExpression(array), Type::Array(element)) => {
+                for expression in array.elements.iter().filter_map(|e| e.as_expression()) {
+                    self.check_expression(expression, element, context);
+                }
+            }
+            _ => {
+                match (expression, &resolved) {
+                    (Expression::ArrowFunctionExpression(arrow), Type::Function(signature)) => {
+                        self.bind_contextual_params(&arrow.params, signature);
+                    }
+                    (Expression::FunctionExpression(function), Type::Function(signature)) => {
+                        self.bind_contextual_params(&function.params, signature);
+                    }
+                    _ => {}
+                }
+                let actual = self.type_of(expression);
+                let span = error_span(expression.span());
+                let expected = target.to_string();
+                match self.env.assignable(&actual, target) {
+                    Ok(()) => {}
+                    Err(Mismatch::MissingProperty(property)) => {
+                        let actual = actual.to_string();
+                        self.report(|source_code| TypeCheckError::MissingProperty {
+                            property,
+                            actual,
+                            expected,
+                            span,
+                            source_code,
+                        });
+                    }
+                    Err(Mismatch::Incompatible) => {
+                        let actual = actual.to_string();
+                        self.report(|source_code| match context {
+                            Context::Assignment => TypeCheckError::TypeMismatch {
+                                expected,
+                                actual,
+                                span,
+                                source_code,
+                            },
+                            Context::Return => TypeCheckError::ReturnTypeMismatch {
+                                expected,
+                                actual,
+                                span,
+                                source_code,
+                            },
+                        });
+                    }
+                }
+            }
+        }
+    }
+
+    /// Check the properties of an object literal against an object type,
+    /// reporting each wrong, excess or missing property
+    fn check_object(&mut self, object: &ObjectExpression, target: &Type, expected: &ObjectType) {
+        let mut seen = HashSet::new();
+        let mut spread = false;
+        for property in &object.properties {
+            let ObjectPropertyKind::ObjectProperty(property) = property else {
+                spread = true;
+                continue;
+            };
+            let Some(name) = property.key.static_name() else {
+                spread = true;
+                continue;
+            };
+            seen.insert(name.to_string());
+            match expected.properties.get(&*name) {
+                Some(expected) if property.kind == PropertyKind::Init => {
+                    self.check_expression(&property.value, &expected.ty, Context::Assignment);
+                }
+                Some(_) => {}
+                None if !expected.open => {
+                    let property_name = name.to_string();
+                    let object_type = target.to_string();
+                    let span = error_span(property.key.span());
+                    self.report(|source_code| TypeCheckError::PropertyNotFound {
+                        property: property_name,
+                        object_type,
+                        span,
+                        source_code,
+                    });
+                }
+                None => {}
+            }
+        }
+        if spread {
+            return;
+        }
+        let missing = expected
+            .properties
+            .iter()
+            .find(|(name, property)| !property.optional && !seen.contains(*name));
+        if let Some((property, _)) = missing {
+            let property = property.clone();
+            let actual = self.object_type(object).to_string();
+            let expected = target.to_string();
+            let span = error_span(object.span);
+            self.report(|source_code| TypeCheckError::MissingProperty {
+                property,
+                actual,
+                expected,
+                span,
+                source_code,
+            });
+        }
+    }
+}
+
+impl<'a> Visit<'a> for Checker<'_> {
+    fn visit_variable_declarator(&mut self, declarator: &VariableDeclarator<'a>) {
+        let annotation = declarator
+            .id
+            .type_annotation
+            .as_ref()
+            .map(|annotation| self.convert(&annotation.type_annotation));
+        if let (Some(target), Some(init)) = (&annotation, &declarator.init) {
+            self.check_expression(init, target, Context::Assignment);
+        }
+        if let BindingPatternKind::BindingIdentifier(id) = &declarator.id.kind
+            && let Some(symbol) = id.symbol_id.get()
+        {
+            let ty = match (annotation, &declarator.init) {
+                (Some(ty), _) => ty,
+                (None, Some(init)) if declarator.kind == VariableDeclarationKind::Const => {
+                    self.type_of(init)
+                }
+                (None, Some(init)) => self.type_of(init).widen(),
+                (None, None) => Type::Any,
+            };
+            self.bindings.insert(symbol, ty);
+        }
+        walk::walk_variable_declarator(self, declarator);
+    }
+
+    fn visit_property_definition(&mut self, definition: &PropertyDefinition<'a>) {
+        if let (Some(annotation), Some(value)) = (&definition.type_annotation, &definition.value) {
+            let target = self.convert(&annotation.type_annotation);
+            self.check_expression(value, &target, Context::Assignment);
+        }
+        walk::walk_property_definition(self, definition);
+    }
+
+    fn visit_assignment_expression(&mut self, assignment: &AssignmentExpression<'a>) {
+        if assignment.operator == AssignmentOperator::Assign {
+            match &assignment.left {
+                AssignmentTarget::AssignmentTargetIdentifier(id) => {
+                    if let Some(target) = self.declared_type(id) {
+                        self.check_expression(&assignment.right, &target, Context::Assignment);
+                    }
+                }
+                AssignmentTarget::StaticMemberExpression(member) => {
+                    let object = self.type_of(&member.object);
+                    match self.property(&object, &member.property.name) {
+                        Some(property) if property.readonly => {
+                            let property = member.property.name.to_string();
+                            let span = error_span(member.property.span);
+                            self.report(|source_code| TypeCheckError::ReadonlyAssignment {
+                                property,
+                                span,
+                                source_code,
+                            });
+                        }
+                        Some(property) => {
+                            self.check_expression(
+                                &assignment.right,
+                                &property.ty,
+                                Context::Assignment,
+                            );
+                        }
+                        None => {}
+                    }
+                }
+                _ => {}
+            }
+        }
+        walk::walk_assignment_expression(self, assignment);
+    }
+
+    fn visit_call_expression(&mut self, call: &CallExpression<'a>) {
+        if let Type::Function(signature) = self.env.resolve(&self.type_of(&call.callee))
+            && !call
+                .arguments
+                .iter()
+                .any(|argument| matches!(argument, Argument::SpreadElement(_)))
+        {
+            let count = call.arguments.len();
+            let expected_args = if count < signature.min_args() {
+                Some(signature.min_args())
+            } else {
+                signature.max_args().filter(|max_args| count > *max_args)
+            };
+            if let Some(expected_args) = expected_args {
+                let function_name = self.text(call.callee.span());
+                let span = error_span(call.span);
+                self.report(|source_code| TypeCheckError::ArgumentMismatch {
+                    function_name,
+                    expected_args,
+                    actual_args: count,
+                    span,
+                    source_code,
+                });
+            }
+            for (idx, argument) in call.arguments.iter().enumerate() {
+                if let (Some(argument), Some(ty)) =
+                    (argument.as_expression(), signature.param_type(idx))
+                {
+                    self.check_expression(argument, ty, Context::Assignment);
+                }
+            }
+        }
+        walk::walk_call_expression(self, call);
+    }
+
+    fn visit_static_member_expression(&mut self, member: &StaticMemberExpression<'a>) {
+        let object = self.type_of(&member.object);
+        let name = member.property.name.as_str();
+        if let Type::Object(object_type) = self.env.resolve(&object)
+            && !object_type.open
+            && !object_type.properties.contains_key(name)
+            && !OBJECT_MEMBERS.contains(&name)
+        {
+            let property = name.to_string();
+            let object_type = object.to_string();
+            let span = error_span(member.property.span);
+            self.report(|source_code| TypeCheckError::PropertyNotFound {
+                property,
+                object_type,
+                span,
+                source_code,
+            });
+        }
+        walk::walk_static_member_expression(self, member);
+    }
+
+    fn visit_function(&mut self, function: &Function<'a>, flags: ScopeFlags) {
+        let type_params = self.push_type_params(function.type_parameters.as_deref());
+        if let Some(symbol) = function.id.as_ref().and_then(|id| id.symbol_id.get())
+            && !self.bindings.contains_key(&symbol)
+        {
+            let ty = self.function_type(function);
+            self.bindings.insert(symbol, ty);
+        }
+        self.bind_params(&function.params);
+        // The return types of async functions and generators wrap the values
+        // they return
+        let ret = match &function.return_type {
+            Some(annotation) if !function.r#async && !function.generator => {
+                Some(self.convert(&annotation.type_annotation))
+            }
+            _ => None,
+        };
+        self.returns.push(ret);
+        walk::walk_function(self, function, flags);
+        self.returns.pop();
+        self.type_params.truncate(type_params);
+    }
+
+    fn visit_arrow_function_expression(&mut self, arrow: &ArrowFunctionExpression<'a>) {
+        let type_params = self.push_type_params(arrow.type_parameters.as_deref());
+        self.bind_params(&arrow.params);
+        let ret = match &arrow.return_type {
+            Some(annotation) if !arrow.r#async => Some(self.convert(&annotation.type_annotation)),
+            _ => None,
+        };
+        if arrow.expression
+            && let Some(ret) = &ret
+            && let Some(Statement::ExpressionStatement(statement)) = arrow.body.statements.first()
+        {
+            self.check_expression(&statement.expression, ret, Context::Return);
+        }
+        self.returns.push(ret);
+        walk::walk_arrow_function_expression(self, arrow);
+        self.returns.pop();
+        self.type_params.truncate(type_params);
+    }
+
+    fn visit_return_statement(&mut self, statement: &ReturnStatement<'a>) {
+        if let Some(Some(expected)) = self.returns.last().cloned() {
+            match &statement.argument {
+                Some(argument) => self.check_expression(argument, &expected, Context::Return),
+                None if self.env.assignable(&Type::Undefined, &expected).is_err() => {
+                    let expected = expected.to_string();
+                    let span = error_span(statement.span);
+                    self.report(|source_code| TypeCheckError::ReturnTypeMismatch {
+                        expected,
+                        actual: "undefined".to_string(),
+                        span,
+                        source_code,
+                    });
+                }
+                None => {}
+            }
+        }
+        walk::walk_return_statement(self, statement);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use oxc_semantic::SemanticBuilder;
+
+    use super::*;
+
+    fn check(source: &str) -> Vec<TypeCheckError> {
+        let allocator = Allocator::default();
+        let ret = Parser::new(&allocator, source, SourceType::ts()).parse();
+        assert!(ret.errors.is_empty(), "{:?}", ret.errors);
+        let semantic = SemanticBuilder::new().build(&ret.program).semantic;
+        let mut project = Project::new(None);
+        project.check(
+            Path::new("test.ts"),
+            source,
+            &ret.program,
+            semantic.scoping(),
+        )
+    }
+
+    #[test]
+    fn narrowed_unknown_is_not_an_error() {
+        let errors = check(
+            r#"function f(v: unknown) { if (typeof v === "string") { const s: string = v; } }"#,
+        );
+        assert!(errors.is_empty(), "{errors:?}");
+    }
+
+    #[test]
+    fn narrowed_union_is_not_an_error() {
+        let errors = check(
+            r#"function f(v: string | number) { if (typeof v === "string") { const s: string = v; } }"#,
+        );
+        assert!(errors.is_empty(), "{errors:?}");
+    }
+
+    #[test]
+    fn literal_mismatch() {
+        let errors = check("const s: string = 1;");
+        assert!(
+            matches!(
+                errors.as_slice(),
+                [TypeCheckError::TypeMismatch { expected, .. }] if expected == "string"
+            ),
+            "{errors:?}"
+        );
+    }
+
+    #[test]
+    fn return_mismatch() {
+        let errors = check("function f(): number { return \"a\"; }");
+        assert!(
+            matches!(
+                errors.as_slice(),
+                [TypeCheckError::ReturnTypeMismatch { .. }]
+            ),
+            "{errors:?}"
+        );
+    }
+
+    #[test]
+    fn unknown_property() {
+        let errors = check(
+            "interface Point { x: number; y: number }\nconst p: Point = { x: 1, y: 2 };\np.z;",
+        );
+        assert!(
+            matches!(
+                errors.as_slice(),
+                [TypeCheckError::PropertyNotFound { property, .. }] if property == "z"
+            ),
+            "{errors:?}"
+        );
+    }
+
+    #[test]
+    fn missing_property() {
+        let errors = check("interface Point { x: number; y: number }\nconst p: Point = { x: 1 };");
+        assert!(
+            matches!(
+                errors.as_slice(),
+                [TypeCheckError::MissingProperty { property, .. }] if property == "y"
+            ),
+            "{errors:?}"
+        );
+    }
+
+    #[test]
+    fn call_arity() {
+        let errors = check("function add(a: number, b: number) { return a + b; }\nadd(1);");
+        assert!(
+            matches!(
+                errors.as_slice(),
+                [TypeCheckError::ArgumentMismatch {
+                    expected_args: 2,
+                    actual_args: 1,
+                    ..
+                }]
+            ),
+            "{errors:?}"
+        );
+    }
+
+    #[test]
+    fn optional_params() {
+        let errors = check("function greet(name?: string) { return name; }\ngreet();");
+        assert!(errors.is_empty(), "{errors:?}");
+    }
+
+    #[test]
+    fn narrowed_optional_property_is_not_an_error() {
+        let errors = check(
+            "interface User { name?: string }\nfunction f(u: User): string { if (u.name) { return u.name; } return \"\"; }",
+        );
+        assert!(errors.is_empty(), "{errors:?}");
+    }
+
+    #[test]
+    fn narrowed_union_property_is_not_an_error() {
+        let errors = check(
+            r#"interface Box { value: string | number }
+function f(b: Box) { if (typeof b.value === "string") { const s: string = b.value; } }"#,
+        );
+        assert!(errors.is_empty(), "{errors:?}");
+    }
+
+    #[test]
+    fn property_mismatch() {
+        let errors = check(
+            "interface User { name: string }\nfunction f(u: User) { const n: number = u.name; }",
+        );
+        assert!(
+            matches!(
+                errors.as_slice(),
+                [TypeCheckError::TypeMismatch { expected, actual, .. }]
+                    if expected == "number" && actual == "string"
+            ),
+            "{errors:?}"
+        );
+    }
+}
@@ -15,6 +15,11 @@ use oxc_span::SourceType;
 use std::fs;
 use std::path::{Path, PathBuf};
 
+mod checker;
+mod types;
+
+use checker::Project;
+
 /// Type checking error types with rich diagnostic information
 #[allow(dead_code)]
 #[derive(Diagnostic, Debug, Clone)]
@@ -66,8 +71,26 @@ pub enum TypeCheckError {
         #[source_code]
         source_code: NamedSource<String>,
     },
+    /// Required property missing from a value
+    #[diagnostic(
+        code(andromeda::check::missing_property),
+        help(
+            "🔍 Add the missing property to the value.\n💡 Mark the property as optional (?) if it isn't always present.\n📖 Check that the value has the shape of the expected type."
+        ),
+        url("https://www.typescriptlang.org/docs/handbook/2/objects.html#optional-properties")
+    )]
+    MissingProperty {
+        property: String,
+        actual: String,
+        expected: String,
+        #[label(
+            "Property '{property}' is missing in type '{actual}' but required in type '{expected}'"
+        )]
+        span: SourceSpan,
+        #[source_code]
+        source_code: NamedSource<String>,
+    },
     /// Function call with incorrect arguments
-    #[allow(dead_code)]
     #[diagnostic(
         code(andromeda::check::argument_mismatch),
         help(
@@ -87,7 +110,6 @@ pub enum TypeCheckError {
         source_code: NamedSource<String>,
     },
     /// Return type mismatch
-    #[allow(dead_code)]
     #[diagnostic(
         code(andromeda::check::return_type_mismatch),
         help(
@@ -217,6 +239,17 @@ impl std::fmt::Display for TypeCheckError {
                     "Property '{property}' does not exist on type '{object_type}'"
                 )
             }
+            TypeCheckError::MissingProperty {
+                property,
+                actual,
+                expected,
+                ..
+            } => {
+                write!(
+                    f,
+                    "Property '{property}' is missing in type '{actual}' but required in type '{expected}'"
+                )
+            }
             TypeCheckError::ArgumentMismatch {
                 function_name,
                 expected_args,
@@ -280,7 +313,19 @@ pub fn check_file_with_config(
 pub fn check_file_content_with_config(
     path: &PathBuf,
     content: &str,
-    _config_override: Option<AndromedaConfig>,
+    config_override: Option<AndromedaConfig>,
+) -> Result<Vec<TypeCheckError>> {
+    let mut project = Project::new(config_override.as_ref());
+    check_file_content(&mut project, path, content)
+}
+
+/// Type check file content against the declarations of `project`, which
+/// caches the modules imported by the files checked with it
+#[allow(clippy::result_large_err)]
+fn check_file_content(
+    project: &mut Project,
+    path: &Path,
+    content: &str,
 ) -> Result<Vec<TypeCheckError>> {
     let allocator = Allocator::default();
     let source_type = SourceType::from_path(path).unwrap_or_default();
@@ -374,6 +419,9 @@ pub fn check_file_content_with_config(
         for reference_id in reference_id_list {
             let reference = scoping.get_reference(reference_id);
             let name = _semantic.reference_name(reference);
+            if project.is_global(name) {
+                continue;
+            }
             let ref_span = _semantic.reference_span(reference);
 
             let span = SourceSpan::new((ref_span.start as usize).into(), ref_span.size() as usize);
@@ -406,6 +454,10 @@ pub fn check_file_content_with_config(
         }
     }
 
+    if ret.errors.is_empty() {
+        type_errors.extend(project.check(path, content, program, scoping));
+    }
+
     Ok(type_errors)
 }
 
@@ -629,9 +681,13 @@ pub fn check_files_with_config(
 
     let mut total_errors = 0;
     let mut files_with_errors = 0;
+    let mut project = Project::new(config_override.as_ref());
 
     for path in &files_to_check {
-        match check_file_with_config(path, config_override.clone()) {
+        let result = fs::read_to_string(path)
+            .map_err(|e| AndromedaError::file_read_error(path.clone(), e))
+            .and_then(|content| check_file_content(&mut project, path, &content));
+        match result {
             Ok(type_errors) => {
                 if !type_errors.is_empty() {
                     files_with_errors += 1;
@@ -0,0 +1,393 @@
+// This Source Code Form is subject to the terms of the Mozilla Public License, v. 2.0.
+// If a copy of the MPL was not distributed with this file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::rc::Rc;
+
+/// How deep assignability recurses into nested and recursive types before
+/// assuming they are compatible
+const MAX_DEPTH: usize = 24;
+
+/// A simplified model of a TypeScript type.
+///
+/// Types the checker doesn't model, like generics, intersections or mapped
+/// types, are [Type::Any] so that they never produce false positives.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Type {
+    Any,
+    Unknown,
+    Never,
+    Void,
+    Undefined,
+    Null,
+    Boolean,
+    Number,
+    String,
+    BigInt,
+    Symbol,
+    BooleanLiteral(bool),
+    NumberLiteral(f64),
+    StringLiteral(String),
+    Array(Box<Type>),
+    Tuple(Vec<Type>),
+    Object(Rc<ObjectType>),
+    Function(Rc<Signature>),
+    Union(Vec<Type>),
+    /// A declared interface, type alias or class, by its id in the [TypeEnv]
+    Named(Rc<TypeName>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TypeName {
+    /// Unique id of the declaration, such as `global#Navigator` or
+    /// `/src/user.ts#User`
+    pub id: String,
+    /// The name the type is displayed with
+    pub name: String,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ObjectType {
+    pub properties: BTreeMap<String, Property>,
+    /// Whether values of the type may have properties that aren't listed,
+    /// because of index signatures, unresolved base types or spreads
+    pub open: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Property {
+    pub ty: Type,
+    pub optional: bool,
+    pub readonly: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Signature {
+    pub params: Vec<Param>,
+    /// Element type of the rest parameter
+    pub rest: Option<Type>,
+    pub ret: Type,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Param {
+    pub name: String,
+    pub ty: Type,
+    pub optional: bool,
+}
+
+/// Why a type isn't assignable to another
+#[derive(Debug, Clone, PartialEq)]
+pub enum Mismatch {
+    Incompatible,
+    /// A required property of the target type is missing
+    MissingProperty(String),
+}
+
+impl Type {
+    /// The union of `members`, flattened and without duplicates
+    pub fn union(members: impl IntoIterator<Item = Type>) -> Type {
+        let mut flat: Vec<Type> = Vec::new();
+        for member in members {
+            let nested = match member {
+                Type::Union(nested) => nested,
+                Type::Never => continue,
+                member => vec![member],
+            };
+            for member in nested {
+                if member == Type::Any {
+                    return Type::Any;
+                }
+                if !flat.contains(&member) {
+                    flat.push(member);
+                }
+            }
+        }
+        match flat.len() {
+            0 => Type::Never,
+            1 => flat.pop().unwrap(),
+            _ => Type::Union(flat),
+        }
+    }
+
+    /// The type of a mutable binding initialized with a value of this type:
+    /// literal types widen to their primitive, and `null` or `undefined`
+    /// leave the binding untyped
+    pub fn widen(self) -> Type {
+        match self {
+            Type::BooleanLiteral(_) => Type::Boolean,
+            Type::NumberLiteral(_) => Type::Number,
+            Type::StringLiteral(_) => Type::String,
+            Type::Null | Type::Undefined => Type::Any,
+            Type::Union(members) => Type::union(members.into_iter().map(Type::widen)),
+            ty => ty,
+        }
+    }
+
+    pub fn object(properties: BTreeMap<String, Property>, open: bool) -> Type {
+        Type::Object(Rc::new(ObjectType { properties, open }))
+    }
+
+    pub fn function(params: Vec<Param>, rest: Option<Type>, ret: Type) -> Type {
+        Type::Function(Rc::new(Signature { params, rest, ret }))
+    }
+}
+
+impl Signature {
+    /// Number of arguments a call needs at least
+    pub fn min_args(&self) -> usize {
+        self.params.iter().filter(|param| !param.optional).count()
+    }
+
+    /// Number of arguments a call accepts at most, if limited
+    pub fn max_args(&self) -> Option<usize> {
+        self.rest.is_none().then_some(self.params.len())
+    }
+
+    /// The type of the argument at `idx`
+    pub fn param_type(&self, idx: usize) -> Option<&Type> {
+        self.params
+            .get(idx)
+            .map(|param| &param.ty)
+            .or(self.rest.as_ref())
+    }
+}
+
+/// The declared types of a project, by id
+#[derive(Debug, Default)]
+pub struct TypeEnv {
+    types: HashMap<String, Type>,
+}
+
+impl TypeEnv {
+    /// Declare the type `id`, merging the members of interfaces declared more
+    /// than once
+    pub fn insert(&mut self, id: String, ty: Type) {
+        let ty = match (self.types.remove(&id), ty) {
+            (Some(Type::Object(existing)), Type::Object(added)) => {
+                let mut merged = (*existing).clone();
+                merged.open |= added.open;
+                for (name, property) in &added.properties {
+                    merged
+                        .properties
+                        .entry(name.clone())
+                        .or_insert_with(|| property.clone());
+                }
+                Type::Object(Rc::new(merged))
+            }
+            (_, ty) => ty,
+        };
+        self.types.insert(id, ty);
+    }
+
+    /// The structure of a type, following named types
+    pub fn resolve(&self, ty: &Type) -> Type {
+        let mut ty = ty.clone();
+        for _ in 0..MAX_DEPTH {
+            match ty {
+                Type::Named(name) => ty = self.types.get(&name.id).cloned().unwrap_or(Type::Any),
+                ty => return ty,
+            }
+        }
+        Type::Any
+    }
+
+    /// Whether a value of type `source` can be assigned to `target`
+    pub fn assignable(&self, source: &Type, target: &Type) -> Result<(), Mismatch> {
+        self.assignable_at(source, target, 0)
+    }
+
+    fn assignable_at(&self, source: &Type, target: &Type, depth: usize) -> Result<(), Mismatch> {
+        if depth > MAX_DEPTH {
+            return Ok(());
+        }
+        let source = self.resolve(source);
+        let target = self.resolve(target);
+        if matches!(target, Type::Any | Type::Unknown) || matches!(source, Type::Any | Type::Never)
+        {
+            return Ok(());
+        }
+
+        if let Type::Union(members) = &source {
+            return members
+                .iter()
+                .try_for_each(|member| self.assignable_at(member, &target, depth + 1))
+                .map_err(|_| Mismatch::Incompatible);
+        }
+        if let Type::Union(members) = &target {
+            // `boolean` is `true | false`
+            let booleans = source == Type::Boolean
+                && members.contains(&Type::BooleanLiteral(true))
+                && members.contains(&Type::BooleanLiteral(false));
+            let assignable = booleans
+                || members
+                    .iter()
+                    .any(|member| self.assignable_at(&source, member, depth + 1).is_ok());
+            return if assignable {
+                Ok(())
+            } else {
+                Err(Mismatch::Incompatible)
+            };
+        }
+
+        let assignable = match (&source, &target) {
+            (_, Type::Object(target)) => return self.object_assignable(&source, target, depth),
+            (Type::Undefined, Type::Void)
+            | (Type::BooleanLiteral(_), Type::Boolean)
+            | (Type::NumberLiteral(_), Type::Number)
+            | (Type::StringLiteral(_), Type::String) => true,
+            (Type::Array(source), Type::Array(target)) => {
+                self.assignable_at(source, target, depth + 1).is_ok()
+            }
+            (Type::Tuple(source), Type::Array(target)) => source
+                .iter()
+                .all(|element| self.assignable_at(element, target, depth + 1).is_ok()),
+            (Type::Tuple(source), Type::Tuple(target)) => {
+                source.len() == target.len()
+                    && source.iter().zip(target).all(|(source, target)| {
+                        self.assignable_at(source, target, depth + 1).is_ok()
+                    })
+            }
+            (Type::Function(source), Type::Function(target)) => {
+                self.signature_assignable(source, target, depth)
+            }
+            (source, target) => source == target,
+        };
+        if assignable {
+            Ok(())
+        } else {
+            Err(Mismatch::Incompatible)
+        }
+    }
+
+    fn object_assignable(
+        &self,
+        source: &Type,
+        target: &ObjectType,
+        depth: usize,
+    ) -> Result<(), Mismatch> {
+        let (properties, open) = match source {
+            Type::Object(source) => (Some(&source.properties), source.open),
+            Type::Function(_) => (None, false),
+            Type::Undefined | Type::Null | Type::Void => return Err(Mismatch::Incompatible),
+            // Primitives, arrays and tuples have the members of their wrapper
+            // objects, which aren't modelled
+            _ => return Ok(()),
+        };
+        for (name, property) in &target.properties {
+            match properties.and_then(|properties| properties.get(name)) {
+                Some(source) => {
+                    if self
+                        .assignable_at(&source.ty, &property.ty, depth + 1)
+                        .is_err()
+                    {
+                        return Err(Mismatch::Incompatible);
+                    }
+                }
+                None if property.optional || open => {}
+                None => return Err(Mismatch::MissingProperty(name.clone())),
+            }
+        }
+        Ok(())
+    }
+
+    fn signature_assignable(&self, source: &Signature, target: &Signature, depth: usize) -> bool {
+        // A function may ignore arguments, but not require more than it gets
+        if target
+            .max_args()
+            .is_some_and(|max_args| source.min_args() > max_args)
+        {
+            return false;
+        }
+        // Parameters are compared bivariantly, as TypeScript does for methods
+        let params = source.params.iter().enumerate().all(|(idx, param)| {
+            target.param_type(idx).is_none_or(|target| {
+                self.assignable_at(&param.ty, target, depth + 1).is_ok()
+                    || self.assignable_at(target, &param.ty, depth + 1).is_ok()
+            })
+        });
+        params
+            && (target.ret == Type::Void
+                || self
+                    .assignable_at(&source.ret, &target.ret, depth + 1)
+                    .is_ok())
+    }
+}
+
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Type::Any => write!(f, "any"),
+            Type::Unknown => write!(f, "unknown"),
+            Type::Never => write!(f, "never"),
+            Type::Void => write!(f, "void"),
+            Type::Undefined => write!(f, "undefined"),
+            Type::Null => write!(f, "null"),
+            Type::Boolean => write!(f, "boolean"),
+            Type::Number => write!(f, "number"),
+            Type::String => write!(f, "string"),
+            Type::BigInt => write!(f, "bigint"),
+            Type::Symbol => write!(f, "symbol"),
+            Type::BooleanLiteral(value) => write!(f, "{value}"),
+            Type::NumberLiteral(value) => write!(f, "{value}"),
+            Type::StringLiteral(value) => write!(f, "{value:?}"),
+            Type::Array(element) => match **element {
+                Type::Union(_) | Type::Function(_) => write!(f, "({element})[]"),
+                _ => write!(f, "{element}[]"),
+            },
+            Type::Tuple(elements) => {
+                write!(f, "[")?;
+                for (idx, element) in elements.iter().enumerate() {
+                    if idx > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{element}")?;
+                }
+                write!(f, "]")
+            }
+            Type::Object(object) => {
+                if object.properties.is_empty() {
+                    return write!(f, "{{}}");
+                }
+                write!(f, "{{ ")?;
+                for (name, property) in &object.properties {
+                    let readonly = if property.readonly { "readonly " } else { "" };
+                    let optional = if property.optional { "?" } else { "" };
+                    write!(f, "{readonly}{name}{optional}: {}; ", property.ty)?;
+                }
+                write!(f, "}}")
+            }
+            Type::Function(signature) => {
+                write!(f, "(")?;
+                for (idx, param) in signature.params.iter().enumerate() {
+                    if idx > 0 {
+                        write!(f, ", ")?;
+                    }
+                    let optional = if param.optional { "?" } else { "" };
+                    write!(f, "{}{optional}: {}", param.name, param.ty)?;
+                }
+                if let Some(rest) = &signature.rest {
+                    if !signature.params.is_empty() {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "...args: {}", Type::Array(Box::new(rest.clone())))?;
+                }
+                write!(f, ") => {}", signature.ret)
+            }
+            Type::Union(members) => {
+                for (idx, member) in members.iter().enumerate() {
+                    if idx > 0 {
+                        write!(f, " | ")?;
+                    }
+                    match member {
+                        Type::Function(_) => write!(f, "({member})")?,
+                        _ => write!(f, "{member}")?,
+                    }
+                }
+                Ok(())
+            }
+            Type::Named(name) => write!(f, "{}", name.name),
+        }
+    }
+}
@@ -0,0 +1,396 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+mod target;
+
+use crate::bundle::module_files;
+use crate::error::{AndromedaError, Result, read_file_with_context};
+use andromeda_core::{EmbeddedFs, PermissionsOptions};
+use libsui::{Elf, Macho, PortableExecutable};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+pub use target::CompileTarget;
+
+pub static ANDROMEDA_JS_CODE_SECTION: &str = "ANDROMEDABINCODE";
+pub static ANDROMEDA_CONFIG_SECTION: &str = "ANDROMEDACONFIG";
+/// Section of the modules and assets embedded in compiled binaries
+pub static ANDROMEDA_FS_SECTION: &str = "ANDROMEDAFS";
+
+/// Configuration embedded in compiled binaries
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct EmbeddedConfig {
+    pub verbose: bool,
+    pub no_strict: bool,
+    /// Path of the compiled entry point relative to the embedded files, which
+    /// are rooted at the directory of the binary
+    #[serde(default)]
+    pub entry: Option<String>,
+    /// Permissions granted to the program, every permission when unset
+    #[serde(default)]
+    pub permissions: Option<PermissionsOptions>,
+    /// Arguments passed to the program before the ones it is run with
+    #[serde(default)]
+    pub args: Vec<String>,
+}
+
+/// Options of `andromeda compile`
+#[derive(Debug, Clone, Default)]
+pub struct CompileOptions {
+    pub verbose: bool,
+    pub no_strict: bool,
+    /// Globs of the asset files to embed
+    pub include: Vec<String>,
+    /// Platform to compile for, the host by default
+    pub target: Option<CompileTarget>,
+    /// Directory of the prebuilt Andromeda binaries of the other platforms
+    pub runtimes_dir: Option<PathBuf>,
+    /// Icon of the executable, for Windows targets
+    pub icon: Option<PathBuf>,
+    /// Permissions granted to the program, every permission when unset
+    pub permissions: Option<PermissionsOptions>,
+    /// Arguments passed to the program before the ones it is run with
+    pub args: Vec<String>,
+}
+
+/// Compile `input_file` into a single-file executable at `result_name`,
+/// embedding every module it imports and the assets matching the `include`
+/// globs
+#[allow(clippy::result_large_err)]
+#[hotpath::measure]
+pub fn compile(result_name: &Path, input_file: &Path, options: &CompileOptions) -> Result<()> {
+    // Validate input file exists and is readable
+    if !input_file.exists() {
+        return Err(AndromedaError::file_not_found(
+            input_file.to_path_buf(),
+            std::io::Error::new(std::io::ErrorKind::NotFound, "Input file not found"),
+        ));
+    }
+
+    // Validate we can read the input file
+    let js_content = read_file_with_context(input_file)?;
+
+    // Validate JS content is not empty
+    if js_content.trim().is_empty() {
+        return Err(AndromedaError::invalid_argument(
+            "input_file".to_string(),
+            "non-empty JavaScript/TypeScript file".to_string(),
+            "empty file".to_string(),
+        ));
+    }
+
+    let target = match options.target.or_else(CompileTarget::host) {
+        Some(target) => target,
+        None => {
+            return Err(AndromedaError::unsupported_platform(format!(
+                "{}-{}",
+                std::env::consts::ARCH,
+                std::env::consts::OS
+            )));
+        }
+    };
+    let exe = target.base_binary(options.runtimes_dir.as_deref())?;
+
+    let icon = match &options.icon {
+        Some(_) if target.os != "windows" => {
+            eprintln!(
+                "⚠️  Icons are only set on Windows executables, ignoring --icon for {}",
+                target.triple
+            );
+            None
+        }
+        Some(icon) => Some(
+            std::fs::read(icon).map_err(|e| AndromedaError::file_read_error(icon.clone(), e))?,
+        ),
+        None => None,
+    };
+
+    let js = js_content.into_bytes();
+    let (fs, entry) = embed_files(input_file, result_name, &options.include)?;
+
+    // Create embedded config
+    let config = EmbeddedConfig {
+        verbose: options.verbose,
+        no_strict: options.no_strict,
+        entry: Some(entry),
+        permissions: options.permissions.clone(),
+        args: options.args.clone(),
+    };
+    let config_json = serde_json::to_vec(&config).map_err(|e| {
+        AndromedaError::config_error(
+            "Failed to serialize embedded config".to_string(),
+            None,
+            Some(Box::new(e)),
+        )
+    })?;
+
+    // Validate output directory exists or can be created
+    if let Some(parent) = result_name.parent()
+        && !parent.exists()
+    {
+        std::fs::create_dir_all(parent).map_err(|e| {
+            AndromedaError::permission_denied(
+                format!("creating output directory {}", parent.display()),
+                Some(parent.to_path_buf()),
+                e,
+            )
+        })?;
+    }
+
+    let sections = [
+        (ANDROMEDA_JS_CODE_SECTION, js),
+        (ANDROMEDA_FS_SECTION, fs.to_bytes()),
+        (ANDROMEDA_CONFIG_SECTION, config_json),
+    ];
+    let os = target.os;
+    let binary = match os {
+        "macos" => {
+            // Each section is written in its own pass
+            let mut binary = exe;
+            for (name, data) in sections {
+                let mut out = Vec::new();
+                Macho::from(binary)
+                    .map_err(|e| {
+                        executable_error(
+                            "Failed to parse macOS executable".to_string(),
+                            input_file,
+                            result_name,
+                            e,
+                        )
+                    })?
+                    .write_section(name, data)
+                    .map_err(|e| {
+                        executable_error(
+                            format!("Failed to write {name} section to macOS executable"),
+                            input_file,
+                            result_name,
+                            e,
+                        )
+                    })?
+                    .build_and_sign(&mut out)
+                    .map_err(|e| {
+                        executable_error(
+                            format!("Failed to build and sign macOS executable ({name})"),
+                            input_file,
+                            result_name,
+                            e,
+                        )
+                    })?;
+                binary = out;
+            }
+            binary
+        }
+        "linux" => {
+            // libsui's Elf doesn't support multiple appends in sequence, so
+            // each section is appended in its own pass
+            let mut binary = exe;
+            for (name, data) in sections {
+                let mut out = Vec::new();
+                Elf::new(&binary)
+                    .append(name, &data, &mut out)
+                    .map_err(|e| {
+                        executable_error(
+                            format!("Failed to append {name} section to Linux executable"),
+                            input_file,
+                            result_name,
+                            e,
+                        )
+                    })?;
+                binary = out;
+            }
+            binary
+        }
+        "windows" => {
+            let mut executable = PortableExecutable::from(&exe).map_err(|e| {
+                executable_error(
+                    "Failed to parse Windows executable".to_string(),
+                    input_file,
+                    result_name,
+                    e,
+                )
+            })?;
+            for (name, data) in sections {
+                executable = executable.write_resource(name, data).map_err(|e| {
+                    executable_error(
+                        format!("Failed to write {name} resource to Windows executable"),
+                        input_file,
+                        result_name,
+                        e,
+                    )
+                })?;
+            }
+            if let Some(icon) = &icon {
+                executable = executable.set_icon(icon).map_err(|e| {
+                    executable_error(
+                        "Failed to set the icon of the Windows executable".to_string(),
+                        input_file,
+                        result_name,
+                        e,
+                    )
+                })?;
+            }
+            let mut out = Vec::new();
+            executable.build(&mut out).map_err(|e| {
+                executable_error(
+                    "Failed to build Windows executable".to_string(),
+                    input_file,
+                    result_name,
+                    e,
+                )
+            })?;
+            out
+        }
+        _ => {
+            return Err(AndromedaError::unsupported_platform(os.to_string()));
+        }
+    };
+
+    std::fs::write(result_name, binary).map_err(|e| {
+        AndromedaError::permission_denied(
+            format!("creating output file {}", result_name.display()),
+            Some(result_name.to_path_buf()),
+            e,
+        )
+    })?;
+
+    // Make the binary executable on Unix-like systems
+    #[cfg(any(target_os = "linux", target_os = "macos"))]
+    {
+        use std::fs::{metadata, set_permissions};
+        use std::os::unix::fs::PermissionsExt;
+
+        if matches!(os, "macos" | "linux") {
+            let metadata = metadata(result_name).map_err(|e| {
+                AndromedaError::permission_denied(
+                    format!("reading permissions for {}", result_name.display()),
+                    Some(result_name.to_path_buf()),
+                    e,
+                )
+            })?;
+            let mut perms = metadata.permissions();
+            perms.set_mode(0o755); // rwxr-xr-x permissions
+            set_permissions(result_name, perms).map_err(|e| {
+                AndromedaError::permission_denied(
+                    format!(
+                        "setting executable permissions for {}",
+                        result_name.display()
+                    ),
+                    Some(result_name.to_path_buf()),
+                    e,
+                )
+            })?;
+        }
+    }
+
+    Ok(())
+}
+
+/// The compile error of a failure to write the sections of the executable
+fn executable_error(
+    reason: String,
+    input_file: &Path,
+    result_name: &Path,
+    source: impl std::error::Error + Send + Sync + 'static,
+) -> AndromedaError {
+    AndromedaError::compile_error(
+        reason,
+        input_file.to_path_buf(),
+        result_name.to_path_buf(),
+        Some(source),
+    )
+}
+
+/// Embed the module graph of `input_file` and the files matching the `include`
+/// globs, returning them with the path of the entry among them.
+///
+/// The files are rooted at the closest directory containing the current
+/// directory and all of them, which becomes the directory of the binary.
+#[allow(clippy::result_large_err)]
+fn embed_files(
+    input_file: &Path,
+    result_name: &Path,
+    include: &[String],
+) -> Result<(EmbeddedFs, String)> {
+    let collect_error = |reason: String| {
+        AndromedaError::compile_error(
+            reason,
+            input_file.to_path_buf(),
+            result_name.to_path_buf(),
+            None::<std::io::Error>,
+        )
+    };
+    let cwd = std::env::current_dir()
+        .map_err(|e| collect_error(format!("Failed to get the current directory: {e}")))?;
+    let entry = std::path::absolute(input_file)
+        .map_err(|e| collect_error(format!("Failed to resolve the input file: {e}")))?;
+
+    let mut files = module_files(&entry.to_string_lossy())
+        .map_err(|e| collect_error(format!("Failed to collect the modules: {e}")))?;
+
+    // The package.json files deciding how the modules of packages resolve, then
+    // the assets
+    let mut extra_files = Vec::new();
+    for (path, _) in &files {
+        let path = Path::new(path);
+        if !path.is_absolute() || !path.starts_with(&cwd) {
+            continue;
+        }
+        for dir in path.ancestors().skip(1) {
+            let package_json = dir.join("package.json");
+            if package_json.is_file() {
+                extra_files.push(package_json);
+            }
+            if dir == cwd {
+                break;
+            }
+        }
+    }
+
+    for pattern in include {
+        let pattern = cwd.join(pattern).to_string_lossy().to_string();
+        let paths = glob::glob(&pattern)
+            .map_err(|e| collect_error(format!("Invalid include pattern {pattern}: {e}")))?;
+        extra_files.extend(paths.flatten().filter(|path| path.is_file()));
+    }
+
+    for path in extra_files {
+        let path_string = path.to_string_lossy().to_string();
+        if files.iter().any(|(file, _)| *file == path_string) {
+            continue;
+        }
+        let content =
+            std::fs::read(&path).map_err(|e| AndromedaError::file_read_error(path.clone(), e))?;
+        files.push((path_string, content));
+    }
+
+    let root = files
+        .iter()
+        .filter(|(path, _)| !path.contains("://"))
+        .filter_map(|(path, _)| Path::new(path).parent())
+        .fold(cwd.clone(), |root, dir| common_ancestor(&root, dir));
+
+    let mut fs = EmbeddedFs::new(cwd.strip_prefix(&root).unwrap_or(Path::new("")));
+    for (path, content) in files {
+        if path.contains("://") {
+            fs.insert(&path, content);
+        } else if let Ok(relative) = Path::new(&path).strip_prefix(&root) {
+            fs.insert(&relative.to_string_lossy(), content);
+        }
+    }
+    let entry = entry
+        .strip_prefix(&root)
+        .unwrap_or(&entry)
+        .to_string_lossy()
+        .replace('\\', "/");
+    Ok((fs, entry))
+}
+
+/// The deepest directory containing both `a` and `b`
+fn common_ancestor(a: &Path, b: &Path) -> PathBuf {
+    a.components()
+        .zip(b.components())
+        .take_while(|(a, b)| a == b)
+        .map(|(component, _)| component)
+        .collect()
+}
@@ -0,0 +1,147 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use crate::error::{AndromedaError, Result};
+use andromeda_core::ModuleCache;
+use std::path::{Path, PathBuf};
+
+const CURRENT_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// A platform single-file executables can be compiled for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CompileTarget {
+    /// Rust target triple
+    pub triple: &'static str,
+    /// Operating system, as in `std::env::consts::OS`
+    pub os: &'static str,
+    /// Name of the release asset of the Andromeda binary
+    pub asset_name: &'static str,
+}
+
+/// The platforms Andromeda binaries are released for
+const TARGETS: &[CompileTarget] = &[
+    CompileTarget {
+        triple: "x86_64-unknown-linux-gnu",
+        os: "linux",
+        asset_name: "andromeda-linux-amd64",
+    },
+    CompileTarget {
+        triple: "aarch64-unknown-linux-gnu",
+        os: "linux",
+        asset_name: "andromeda-linux-arm64",
+    },
+    CompileTarget {
+        triple: "x86_64-apple-darwin",
+        os: "macos",
+        asset_name: "andromeda-macos-amd64",
+    },
+    CompileTarget {
+        triple: "aarch64-apple-darwin",
+        os: "macos",
+        asset_name: "andromeda-macos-arm64",
+    },
+    CompileTarget {
+        triple: "x86_64-pc-windows-msvc",
+        os: "windows",
+        asset_name: "andromeda-windows-amd64.exe",
+    },
+    CompileTarget {
+        triple: "aarch64-pc-windows-msvc",
+        os: "windows",
+        asset_name: "andromeda-windows-arm64.exe",
+    },
+];
+
+impl CompileTarget {
+    /// The target of a Rust target triple
+    #[allow(clippy::result_large_err)]
+    pub fn from_triple(triple: &str) -> Result<Self> {
+        TARGETS
+            .iter()
+            .find(|target| target.triple == triple)
+            .copied()
+            .ok_or_else(|| {
+                let triples: Vec<&str> = TARGETS.iter().map(|target| target.triple).collect();
+                AndromedaError::invalid_argument(
+                    "target".to_string(),
+                    format!("one of {}", triples.join(", ")),
+                    triple.to_string(),
+                )
+            })
+    }
+
+    /// The platform Andromeda is running on
+    pub fn host() -> Option<Self> {
+        TARGETS
+            .iter()
+            .find(|target| {
+                target.os == std::env::consts::OS
+                    && target.triple.starts_with(std::env::consts::ARCH)
+            })
+            .copied()
+    }
+
+    /// The file name of an executable named `name` on this platform
+    pub fn executable_name(&self, name: &str) -> String {
+        if self.os == "windows" && !name.ends_with(".exe") {
+            format!("{name}.exe")
+        } else {
+            name.to_string()
+        }
+    }
+
+    /// The Andromeda binary the program is embedded into: the running one for
+    /// the host, or else the prebuilt binary for the target from
+    /// `runtimes_dir` or the cache directory of this version.
+    ///
+    /// Prebuilt binaries are never downloaded: the executable must come from
+    /// the same Andromeda version, since an older one can't read the sections
+    /// and embedded configuration written here.
+    #[allow(clippy::result_large_err)]
+    pub fn base_binary(&self, runtimes_dir: Option<&Path>) -> Result<Vec<u8>> {
+        if Self::host() == Some(*self) {
+            let exe_path = std::env::current_exe().map_err(|e| {
+                AndromedaError::config_error(
+                    "Failed to get current executable path".to_string(),
+                    None,
+                    Some(Box::new(e)),
+                )
+            })?;
+            return std::fs::read(&exe_path)
+                .map_err(|e| AndromedaError::file_read_error(exe_path, e));
+        }
+
+        if let Some(runtimes_dir) = runtimes_dir {
+            let path = runtimes_dir.join(self.asset_name);
+            if path.is_file() {
+                return std::fs::read(&path).map_err(|e| AndromedaError::file_read_error(path, e));
+            }
+        }
+
+        let cached = runtimes_cache_dir()
+            .join(CURRENT_VERSION)
+            .join(self.asset_name);
+        if cached.is_file() {
+            return std::fs::read(&cached).map_err(|e| AndromedaError::file_read_error(cached, e));
+        }
+
+        Err(AndromedaError::config_error(
+            format!(
+                "No Andromeda {CURRENT_VERSION} binary for {}: build `{}` from this version and \
+                 put it in the `compile.runtimes_dir` directory or in {}",
+                self.triple,
+                self.asset_name,
+                cached.parent().unwrap_or(&cached).display()
+            ),
+            None,
+            None::<std::io::Error>,
+        ))
+    }
+}
+
+/// The directory prebuilt Andromeda binaries are looked up in by version, next
+/// to the module cache
+fn runtimes_cache_dir() -> PathBuf {
+    ModuleCache::default_dir().with_file_name("runtimes")
+}
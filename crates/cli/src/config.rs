@@ -3,6 +3,7 @@
 // file, You can obtain one at https://mozilla.org/MPL/2.0/.
 
 use crate::error::{AndromedaError, Result};
+use andromeda_core::{NetDescriptor, PermissionsOptions};
 use serde::{Deserialize, Serialize};
 use std::path::{Path, PathBuf};
 
@@ -16,6 +17,12 @@ pub struct AndromedaConfig {
     pub format: FormatConfig,
     /// Linting configuration
     pub lint: LintConfig,
+    /// Bundler configuration
+    pub bundle: BundleConfig,
+    /// Single-file executable configuration
+    pub compile: CompileConfig,
+    /// Permissions granted to the programs run by Andromeda
+    pub permissions: PermissionsOptions,
     /// Task definitions
     #[serde(skip_serializing_if = "std::collections::HashMap::is_empty")]
     pub tasks: std::collections::HashMap<String, TaskDefinition>,
@@ -63,6 +70,9 @@ pub struct RuntimeConfig {
     pub exclude: Vec<String>,
     /// Runtime timeout in milliseconds
     pub timeout: Option<u64>,
+    /// Cache the transpiled code of the TypeScript modules between runs
+    /// instead of letting Nova parse them directly
+    pub transpile_cache: bool,
 }
 
 /// Code formatting configuration
@@ -109,6 +119,64 @@ pub struct LintConfig {
     pub exclude: Vec<String>,
 }
 
+/// Bundler configuration
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct BundleConfig {
+    /// Module format of the bundle
+    pub format: BundleFormat,
+    /// Compress the bundled code and remove its whitespace
+    pub minify: bool,
+    /// Shorten the names of the local variables
+    pub mangle: bool,
+    /// Syntax to compile the bundle down to, such as `es2020` or `chrome100`
+    pub target: String,
+    /// Split dynamically imported modules into separate chunks (ESM only)
+    pub splitting: bool,
+    /// Remove the unused exports and side-effect free statements
+    pub tree_shaking: bool,
+    /// Global variable the exports of the entry are assigned to (IIFE only)
+    pub global_name: Option<String>,
+    /// Source maps written for the bundle
+    pub sourcemap: SourceMapMode,
+}
+
+/// Module format of the bundle
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum BundleFormat {
+    /// An ES module, keeping the exports of the entry
+    #[default]
+    Esm,
+    /// An immediately invoked function expression, for classic scripts
+    Iife,
+}
+
+/// How the source maps of a bundle are written
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum SourceMapMode {
+    /// No source maps
+    #[default]
+    None,
+    /// A `.map` file next to each chunk
+    External,
+    /// A data URL at the end of each chunk
+    Inline,
+}
+
+/// Single-file executable configuration
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
+#[serde(default)]
+pub struct CompileConfig {
+    /// Globs of the asset files embedded next to the modules, relative to the
+    /// current directory
+    pub include: Vec<String>,
+    /// Directory of the prebuilt Andromeda binaries used as the base of the
+    /// executables compiled for other platforms, named like the release assets
+    pub runtimes_dir: Option<String>,
+}
+
 /// Task definition configuration
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 #[serde(untagged)]
@@ -202,6 +270,21 @@ impl Default for LintConfig {
     }
 }
 
+impl Default for BundleConfig {
+    fn default() -> Self {
+        Self {
+            format: BundleFormat::Esm,
+            minify: true,
+            mangle: true,
+            target: "esnext".to_string(),
+            splitting: true,
+            tree_shaking: true,
+            global_name: None,
+            sourcemap: SourceMapMode::None,
+        }
+    }
+}
+
 /// Configuration file formats
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub enum ConfigFormat {
@@ -442,6 +525,33 @@ impl ConfigManager {
             ));
         }
 
+        // Validate bundle configuration
+        if let Some(global_name) = &config.bundle.global_name
+            && !global_name.split('.').all(|part| {
+                part.chars().next().is_some_and(|c| !c.is_ascii_digit())
+                    && part
+                        .chars()
+                        .all(|c| c.is_alphanumeric() || c == '_' || c == '$')
+            })
+        {
+            return Err(AndromedaError::config_error(
+                format!("Invalid bundle global name: {global_name}"),
+                None,
+                None::<std::io::Error>,
+            ));
+        }
+
+        // Validate permissions configuration
+        for host in config.permissions.allow_net.iter().flatten() {
+            if NetDescriptor::parse(host).is_none() {
+                return Err(AndromedaError::config_error(
+                    format!("Invalid network permission entry: {host}"),
+                    None,
+                    None::<std::io::Error>,
+                ));
+            }
+        }
+
         // Validate import map configuration
         for config_file in &config.import_map_files {
             let path = Path::new(config_file);
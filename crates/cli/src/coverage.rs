@@ -0,0 +1,160 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use crate::config::AndromedaConfig;
+use crate::error::{AndromedaError, Result};
+use crate::helper::should_include_file;
+use andromeda_core::{Coverage, FileCoverage, to_lcov};
+use console::Style;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Create a coverage collector for the local modules matching the `runtime.include`
+/// and `runtime.exclude` globs of the configuration
+pub fn coverage_collector(config: &AndromedaConfig) -> Coverage {
+    let include = config.runtime.include.clone();
+    let exclude = config.runtime.exclude.clone();
+    Coverage::new(move |path| {
+        if path.starts_with("http://") || path.starts_with("https://") {
+            return false;
+        }
+        let relative = relative_path(path);
+        should_include_file(&relative, &include, &exclude).unwrap_or(false)
+    })
+}
+
+/// Absolute path of a module, without `.` components
+fn absolute_path(path: &str) -> PathBuf {
+    std::path::absolute(path)
+        .unwrap_or_else(|_| PathBuf::from(path))
+        .components()
+        .collect()
+}
+
+/// Path of a module relative to the current directory when it's inside of it
+fn relative_path(path: &str) -> PathBuf {
+    let absolute = absolute_path(path);
+    match std::env::current_dir() {
+        Ok(cwd) => absolute
+            .strip_prefix(&cwd)
+            .map(Path::to_path_buf)
+            .unwrap_or(absolute),
+        Err(_) => absolute,
+    }
+}
+
+/// Write `lcov.info` into the coverage directory and print a summary per file
+#[allow(clippy::result_large_err)]
+pub fn write_coverage_report(dir: &Path, files: &[FileCoverage]) -> Result<()> {
+    let mut files: Vec<FileCoverage> = files
+        .iter()
+        .cloned()
+        .map(|mut file| {
+            file.path = absolute_path(&file.path).to_string_lossy().to_string();
+            file
+        })
+        .collect();
+    files.sort_by(|a, b| a.path.cmp(&b.path));
+
+    fs::create_dir_all(dir).map_err(|e| {
+        AndromedaError::config_error(
+            format!("Failed to create coverage directory {}: {e}", dir.display()),
+            Some(dir.to_path_buf()),
+            Some(e),
+        )
+    })?;
+    let lcov_path = dir.join("lcov.info");
+    fs::write(&lcov_path, to_lcov(&files)).map_err(|e| {
+        AndromedaError::config_error(
+            format!(
+                "Failed to write coverage report {}: {e}",
+                lcov_path.display()
+            ),
+            Some(lcov_path.clone()),
+            Some(e),
+        )
+    })?;
+
+    print_summary(&files);
+    let done = Style::new().green().bold().apply_to("✅");
+    println!("{done} Coverage report written to {}", lcov_path.display());
+    Ok(())
+}
+
+fn print_summary(files: &[FileCoverage]) {
+    let rows: Vec<(String, [(usize, usize); 3])> = files
+        .iter()
+        .map(|file| {
+            (
+                relative_path(&file.path).display().to_string(),
+                [
+                    (file.lines_hit(), file.lines.len()),
+                    (file.branches_hit(), file.branches.len()),
+                    (file.functions_hit(), file.functions.len()),
+                ],
+            )
+        })
+        .collect();
+    let mut total = [(0, 0); 3];
+    for (_, counts) in &rows {
+        for (sum, (hit, found)) in total.iter_mut().zip(counts) {
+            sum.0 += hit;
+            sum.1 += found;
+        }
+    }
+
+    let width = rows
+        .iter()
+        .map(|(name, _)| name.len())
+        .chain(std::iter::once("All files".len()))
+        .max()
+        .unwrap_or_default();
+    let dim = Style::new().dim();
+
+    println!();
+    println!(
+        "{}",
+        Style::new().bold().apply_to(format!(
+            "{:<width$} | {:>18} | {:>18} | {:>18}",
+            "File", "Lines", "Branches", "Functions"
+        ))
+    );
+    println!("{}", dim.apply_to("─".repeat(width + 63)));
+    for (name, counts) in &rows {
+        print_row(name, counts, width, false);
+    }
+    println!("{}", dim.apply_to("─".repeat(width + 63)));
+    print_row("All files", &total, width, true);
+    println!();
+}
+
+fn print_row(name: &str, counts: &[(usize, usize); 3], width: usize, bold: bool) {
+    let cells: Vec<String> = counts
+        .iter()
+        .map(|(hit, found)| {
+            // Nothing to cover counts as fully covered
+            let percent = if *found == 0 {
+                100.0
+            } else {
+                *hit as f64 * 100.0 / *found as f64
+            };
+            let style = if percent >= 80.0 {
+                Style::new().green()
+            } else if percent >= 50.0 {
+                Style::new().yellow()
+            } else {
+                Style::new().red()
+            };
+            let cell = format!("{percent:.2}% ({hit}/{found})");
+            style.apply_to(format!("{cell:>18}")).to_string()
+        })
+        .collect();
+    let name = format!("{name:<width$}");
+    let name = if bold {
+        Style::new().bold().apply_to(name).to_string()
+    } else {
+        name
+    };
+    println!("{name} | {} | {} | {}", cells[0], cells[1], cells[2]);
+}
@@ -133,7 +133,7 @@ pub fn apply_include_exclude_filters(
 
 /// Check if a file should be included based on include/exclude patterns
 #[allow(clippy::result_large_err)]
-fn should_include_file(
+pub fn should_include_file(
     file_path: &Path,
     include_patterns: &[String],
     exclude_patterns: &[String],
@@ -221,3 +221,34 @@ pub fn find_formattable_files_for_lint(
 ) -> Result<Vec<PathBuf>> {
     find_formattable_files_with_filters(paths, &lint_config.include, &lint_config.exclude)
 }
+
+/// Find test modules: `*_test.*` and `*.test.*` JavaScript/TypeScript files in the given
+/// directories, along with any script file given explicitly
+#[allow(clippy::result_large_err)]
+pub fn find_test_files(paths: &[PathBuf]) -> Result<Vec<PathBuf>> {
+    let explicit_files: Vec<&PathBuf> = paths.iter().filter(|path| path.is_file()).collect();
+    let files = find_formattable_files(paths)?;
+
+    Ok(files
+        .into_iter()
+        .filter(|file| {
+            is_script_file(file) && (explicit_files.contains(&file) || is_test_file(file))
+        })
+        .collect())
+}
+
+/// Checks if a file is a JavaScript or TypeScript module
+fn is_script_file(path: &Path) -> bool {
+    is_formattable_file(path)
+        && !matches!(
+            path.extension().and_then(|ext| ext.to_str()),
+            Some("json" | "jsonc")
+        )
+}
+
+/// Checks if a file name follows the `name_test.ext` or `name.test.ext` convention
+fn is_test_file(path: &Path) -> bool {
+    path.file_stem()
+        .and_then(|stem| stem.to_str())
+        .is_some_and(|stem| stem.ends_with("_test") || stem.ends_with(".test") || stem == "test")
+}
@@ -1,14 +1,18 @@
 use std::fmt;
 
 pub mod bundle;
+pub mod cache;
 pub mod check;
 pub mod compile;
 pub mod config;
+pub mod coverage;
 pub mod error;
 pub mod format;
 pub mod helper;
 pub mod lint;
+pub mod permissions;
 pub mod run;
+pub mod watch;
 
 #[derive(Debug)]
 pub enum CliError {
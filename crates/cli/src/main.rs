@@ -2,7 +2,7 @@
 // License, v. 2.0. If a copy of the MPL was not distributed with this
 // file, You can obtain one at https://mozilla.org/MPL/2.0/.
 
-use andromeda_core::RuntimeFile;
+use andromeda_core::{EmbeddedFs, PermissionsOptions, RuntimeFile};
 use clap::{CommandFactory, Parser as ClapParser, Subcommand};
 use clap_complete::{Shell, generate};
 use console::Style;
@@ -12,30 +12,44 @@ use std::path::PathBuf;
 
 mod bundle;
 use bundle::bundle;
+mod cache;
+use cache::{CacheFlags, cache_modules, clean_cache};
 mod compile;
-use compile::{ANDROMEDA_CONFIG_SECTION, ANDROMEDA_JS_CODE_SECTION, EmbeddedConfig, compile};
+use compile::{
+    ANDROMEDA_CONFIG_SECTION, ANDROMEDA_FS_SECTION, ANDROMEDA_JS_CODE_SECTION, CompileOptions,
+    CompileTarget, EmbeddedConfig, compile,
+};
 mod repl;
 use repl::run_repl_with_config;
 mod run;
 mod styles;
-use run::run;
+use run::{RunOptions, run_watch, run_with_options};
 mod error;
 use error::{Result, init_error_reporting, print_error};
 mod format;
 use format::{FormatResult, format_file};
 mod helper;
-use helper::{find_formattable_files_for_format, find_formattable_files_for_lint};
+use helper::{
+    find_formattable_files, find_formattable_files_for_format, find_formattable_files_for_lint,
+};
 mod lint;
 use lint::lint_file_with_config;
+mod permissions;
+use permissions::PermissionFlags;
 mod check;
 use check::check_files_with_config;
 mod config;
+mod coverage;
 mod lsp;
 mod task;
+mod test;
 mod upgrade;
-use config::{AndromedaConfig, ConfigFormat, ConfigManager};
+mod watch;
+use config::{AndromedaConfig, ConfigFormat, ConfigManager, SourceMapMode};
 use lsp::run_lsp_server;
 use task::run_task;
+use test::{TestOptions, TestReporterKind, run_tests};
+use watch::WatchContext;
 
 /// A JavaScript runtime
 #[derive(Debug, ClapParser)]
@@ -62,6 +76,26 @@ enum Command {
         #[arg(short, long)]
         no_strict: bool,
 
+        #[command(flatten)]
+        permissions: PermissionFlags,
+
+        #[command(flatten)]
+        cache: CacheFlags,
+
+        /// Collect code coverage into this directory, `coverage` by default
+        #[arg(
+            long,
+            value_name = "DIR",
+            num_args = 0..=1,
+            require_equals = true,
+            default_missing_value = "coverage"
+        )]
+        coverage: Option<PathBuf>,
+
+        /// Restart whenever one of the loaded modules changes
+        #[arg(long)]
+        watch: bool,
+
         /// The file to run
         #[arg(required = true)]
         path: String,
@@ -77,9 +111,9 @@ enum Command {
         #[arg(required = true)]
         path: PathBuf,
 
-        // The output binary location
-        #[arg(required = true)]
-        out: PathBuf,
+        // The output binary location, named after `--name` or the input file
+        // by default
+        out: Option<PathBuf>,
 
         /// Enable verbose output in the compiled binary
         #[arg(short, long)]
@@ -88,6 +122,31 @@ enum Command {
         /// Disable strict mode in the compiled binary
         #[arg(short = 's', long)]
         no_strict: bool,
+
+        /// Embed the files matching a glob, readable by the compiled binary
+        /// (repeatable, added to the `compile.include` configuration)
+        #[arg(long = "include", value_name = "GLOB")]
+        include: Vec<String>,
+
+        /// Compile for another platform, such as `aarch64-apple-darwin`
+        #[arg(long, value_name = "TRIPLE")]
+        target: Option<String>,
+
+        /// Name of the executable when no output is given (`.exe` is added for Windows)
+        #[arg(long)]
+        name: Option<String>,
+
+        /// Icon of the executable, an `.ico` file (Windows targets only)
+        #[arg(long, value_name = "PATH")]
+        icon: Option<PathBuf>,
+
+        /// Permissions granted to the compiled program (all of them by default)
+        #[command(flatten)]
+        permissions: PermissionFlags,
+
+        /// Arguments passed to the compiled program before the ones it is run with
+        #[arg(last = true)]
+        args: Vec<String>,
     },
 
     /// Start an interactive REPL (Read-Eval-Print Loop)
@@ -130,7 +189,7 @@ enum Command {
         #[arg(long)]
         dry_run: bool,
     },
-    /// Bundle and minify a JavaScript/TypeScript file
+    /// Bundle a JavaScript/TypeScript file and the modules it imports
     Bundle {
         /// The input file to bundle
         #[arg(required = true)]
@@ -139,6 +198,10 @@ enum Command {
         /// The output file to write the bundled code
         #[arg(required = true)]
         output: PathBuf,
+
+        /// Write source maps, overriding the `bundle.sourcemap` configuration
+        #[arg(long, value_enum)]
+        sourcemap: Option<SourceMapMode>,
     },
 
     /// Lint JavaScript/TypeScript files
@@ -146,6 +209,10 @@ enum Command {
         /// The file(s) or directory(ies) to lint
         #[arg(required = false)]
         paths: Vec<PathBuf>,
+
+        /// Lint again whenever one of the files changes
+        #[arg(long)]
+        watch: bool,
     },
 
     /// Type-check TypeScript files
@@ -153,6 +220,56 @@ enum Command {
         /// The file(s) or directory(ies) to type-check
         #[arg(required = false)]
         paths: Vec<PathBuf>,
+
+        /// Type-check again whenever one of the files changes
+        #[arg(long)]
+        watch: bool,
+    },
+
+    /// Run the tests registered with `Andromeda.test()` in test modules
+    Test {
+        /// The test file(s) or directory(ies), searched for `*_test.ts` and `*.test.ts` modules
+        #[arg(required = false)]
+        paths: Vec<PathBuf>,
+
+        /// Only run tests whose name contains this string, or matches it when written as /regex/
+        #[arg(long)]
+        filter: Option<String>,
+
+        /// Fail tests that take longer than this many milliseconds
+        #[arg(long, value_name = "MS")]
+        timeout: Option<u64>,
+
+        /// The format of the test results
+        #[arg(long, value_enum, default_value = "pretty")]
+        reporter: TestReporterKind,
+
+        #[arg(short, long)]
+        no_strict: bool,
+
+        #[command(flatten)]
+        permissions: PermissionFlags,
+
+        #[command(flatten)]
+        cache: CacheFlags,
+
+        /// Run the tests again whenever one of the loaded modules changes
+        #[arg(long)]
+        watch: bool,
+    },
+
+    /// Download the remote modules imported by the given modules into the cache
+    #[command(subcommand_negates_reqs = true, args_conflicts_with_subcommands = true)]
+    Cache {
+        #[command(subcommand)]
+        action: Option<CacheAction>,
+
+        /// The module(s) whose module graph to cache
+        #[arg(required = true)]
+        paths: Vec<PathBuf>,
+
+        #[command(flatten)]
+        cache: CacheFlags,
     },
 
     /// Start Language Server Protocol (LSP) server
@@ -171,6 +288,12 @@ enum Command {
     },
 }
 
+#[derive(Debug, Subcommand)]
+enum CacheAction {
+    /// Remove the transpiled modules from the cache
+    Clean,
+}
+
 #[derive(Debug, Subcommand)]
 enum ConfigAction {
     /// Initialize a new config file
@@ -232,30 +355,51 @@ fn main() {
 fn run_main() -> Result<()> {
     // Check if this is currently a single-file executable
     if let Ok(Some(js)) = find_section(ANDROMEDA_JS_CODE_SECTION) {
-        // Try to load embedded config, fall back to defaults if not found
-        let (verbose, no_strict) = match find_section(ANDROMEDA_CONFIG_SECTION) {
+        // Try to load embedded config, fall back to defaults if not found, or
+        // if it is corrupted or from an older binary format
+        let config = match find_section(ANDROMEDA_CONFIG_SECTION) {
             Ok(Some(config_bytes)) => {
-                match serde_json::from_slice::<EmbeddedConfig>(config_bytes) {
-                    Ok(config) => (config.verbose, config.no_strict),
-                    Err(_) => {
-                        // If config is corrupted or in old format, use defaults
-                        (false, false)
-                    }
-                }
-            }
-            _ => {
-                // No config section found (old binary format), use defaults
-                (false, false)
+                serde_json::from_slice::<EmbeddedConfig>(config_bytes).unwrap_or_default()
             }
+            _ => EmbeddedConfig::default(),
         };
-
-        return run(
-            verbose,
-            no_strict,
-            vec![RuntimeFile::Embedded {
-                path: String::from("internal"),
-                content: js,
-            }],
+        // The embedded files, including the entry point, are located next to
+        // the binary, so that `import.meta` points at the directory it is run from
+        let root = std::env::current_exe()
+            .ok()
+            .and_then(|exe| exe.parent().map(std::path::Path::to_path_buf));
+        if let Some(root) = &root
+            && let Ok(Some(fs)) = find_section(ANDROMEDA_FS_SECTION)
+            && let Some(fs) = EmbeddedFs::from_bytes(fs)
+        {
+            fs.install(root.clone());
+        }
+        let path = root
+            .map(|root| root.join(config.entry.as_deref().unwrap_or("main.js")))
+            .map(|path| path.to_string_lossy().to_string())
+            .unwrap_or_else(|| String::from("internal"));
+
+        // The embedded arguments come before the ones the binary is run with
+        let args = (!config.args.is_empty()).then(|| {
+            config
+                .args
+                .into_iter()
+                .chain(std::env::args().skip(1))
+                .collect()
+        });
+        return run_with_options(
+            RunOptions {
+                verbose: config.verbose,
+                no_strict: config.no_strict,
+                // Compiled binaries are trusted by whoever built them, unless
+                // they were compiled with permission flags
+                permissions: config
+                    .permissions
+                    .unwrap_or_else(PermissionsOptions::allow_all),
+                args,
+                ..Default::default()
+            },
+            vec![RuntimeFile::Embedded { path, content: js }],
         );
     }
 
@@ -282,6 +426,10 @@ fn run_main() -> Result<()> {
             command: Command::Run {
                 verbose: false,
                 no_strict: false,
+                permissions: PermissionFlags::default(),
+                cache: CacheFlags::default(),
+                coverage: None,
+                watch: false,
                 path,
                 args,
             },
@@ -308,19 +456,68 @@ fn run_main() -> Result<()> {
             Command::Run {
                 verbose,
                 no_strict,
+                permissions,
+                cache,
+                coverage,
+                watch,
                 path,
                 args: _,
             } => {
+                let options = RunOptions {
+                    verbose,
+                    no_strict,
+                    permissions: permissions.into_options()?,
+                    coverage_dir: coverage,
+                    cache: cache.setting(),
+                };
                 let runtime_file = RuntimeFile::Local { path };
-                run(verbose, no_strict, vec![runtime_file])
+                if watch {
+                    run_watch(options, vec![runtime_file])
+                } else {
+                    run_with_options(options, vec![runtime_file])
+                }
             }
             Command::Compile {
                 path,
                 out,
                 verbose,
                 no_strict,
+                include,
+                target,
+                name,
+                icon,
+                permissions,
+                args,
             } => {
-                compile(out.as_path(), path.as_path(), verbose, no_strict).map_err(|e| {
+                let config = ConfigManager::load_or_default(None);
+                let target = target
+                    .as_deref()
+                    .map(CompileTarget::from_triple)
+                    .transpose()?;
+                let out = out.unwrap_or_else(|| {
+                    let name = name.unwrap_or_else(|| {
+                        path.file_stem()
+                            .map(|stem| stem.to_string_lossy().to_string())
+                            .unwrap_or_else(|| "main".to_string())
+                    });
+                    match target.or_else(CompileTarget::host) {
+                        Some(target) => PathBuf::from(target.executable_name(&name)),
+                        None => PathBuf::from(name),
+                    }
+                });
+                let permissions = permissions.into_options()?;
+                let options = CompileOptions {
+                    verbose,
+                    no_strict,
+                    include: config.compile.include.into_iter().chain(include).collect(),
+                    target,
+                    runtimes_dir: config.compile.runtimes_dir.map(PathBuf::from),
+                    icon,
+                    permissions: (permissions != PermissionsOptions::default())
+                        .then_some(permissions),
+                    args,
+                };
+                compile(out.as_path(), path.as_path(), &options).map_err(|e| {
                     error::AndromedaError::compile_error(
                         format!("Compilation failed: {e}"),
                         path.clone(),
@@ -430,54 +627,83 @@ fn run_main() -> Result<()> {
                     None,
                 )
             }),
-            Command::Bundle { input, output } => {
-                bundle(input.to_str().unwrap(), output.to_str().unwrap()).map_err(|e| {
-                    error::AndromedaError::runtime_error(
-                        format!("Bundle failed: {e}"),
-                        None,
-                        None,
-                        None,
-                        None,
-                    )
-                })?;
-                println!("✅ Successfully bundled and minified to {output:?}");
+            Command::Bundle {
+                input,
+                output,
+                sourcemap,
+            } => {
+                bundle(input.to_str().unwrap(), output.to_str().unwrap(), sourcemap).map_err(
+                    |e| {
+                        error::AndromedaError::runtime_error(
+                            format!("Bundle failed: {e}"),
+                            None,
+                            None,
+                            None,
+                            None,
+                        )
+                    },
+                )?;
+                println!("✅ Successfully bundled to {output:?}");
                 Ok(())
             }
-            Command::Lint { paths } => {
-                // Load configuration
-                let config = ConfigManager::load_or_default(None);
-
-                let files_to_lint = find_formattable_files_for_lint(&paths, &config.lint)?;
-                if files_to_lint.is_empty() {
-                    println!("No lintable files found.");
-                    return Ok(());
-                }
-                println!("Found {} file(s) to lint:", files_to_lint.len());
-                let mut had_issues = false;
-                for path in &files_to_lint {
-                    if let Err(e) = lint_file_with_config(path, Some(config.clone())) {
-                        print_error(e);
-                        had_issues = true;
-                    }
-                }
-                if had_issues {
-                    Err(error::AndromedaError::runtime_error(
-                        "Linting completed with errors".to_string(),
-                        None,
-                        None,
-                        None,
-                        None,
-                    ))
+            Command::Lint { paths, watch } => {
+                if watch {
+                    watch::watch(|context| lint_paths(&paths, Some(context)))
                 } else {
-                    Ok(())
+                    lint_paths(&paths, None)
                 }
             }
-            Command::Check { paths } => {
-                // Load configuration
-                let config = ConfigManager::load_or_default(None);
+            Command::Check { paths, watch } => {
+                if watch {
+                    watch::watch(|context| {
+                        for path in find_formattable_files(&paths)? {
+                            context.watch_file(&path);
+                        }
+                        // Load configuration
+                        let config = ConfigManager::load_or_default(None);
+
+                        check_files_with_config(&paths, Some(config))
+                    })
+                } else {
+                    // Load configuration
+                    let config = ConfigManager::load_or_default(None);
 
-                check_files_with_config(&paths, Some(config))
+                    check_files_with_config(&paths, Some(config))
+                }
             }
+            Command::Test {
+                paths,
+                filter,
+                timeout,
+                reporter,
+                no_strict,
+                permissions,
+                cache,
+                watch,
+            } => {
+                let options = TestOptions {
+                    filter,
+                    timeout,
+                    reporter,
+                    no_strict,
+                    permissions: permissions.into_options()?,
+                    cache: cache.setting(),
+                };
+                if watch {
+                    watch::watch(|context| run_tests(&paths, &options, Some(context)))
+                } else {
+                    run_tests(&paths, &options, None)
+                }
+            }
+            Command::Cache {
+                action: Some(CacheAction::Clean),
+                ..
+            } => clean_cache(),
+            Command::Cache {
+                action: None,
+                paths,
+                cache,
+            } => cache_modules(&paths, cache),
             Command::Lsp => {
                 run_lsp_server().map_err(|e| {
                     error::AndromedaError::runtime_error(
@@ -504,6 +730,43 @@ fn run_main() -> Result<()> {
     }
 }
 
+/// Lint the files found in `paths`, watching them when `watch` is given
+#[allow(clippy::result_large_err)]
+fn lint_paths(paths: &[PathBuf], watch: Option<&WatchContext>) -> Result<()> {
+    // Load configuration
+    let config = ConfigManager::load_or_default(None);
+
+    let files_to_lint = find_formattable_files_for_lint(paths, &config.lint)?;
+    if let Some(watch) = watch {
+        for path in &files_to_lint {
+            watch.watch_file(path);
+        }
+    }
+    if files_to_lint.is_empty() {
+        println!("No lintable files found.");
+        return Ok(());
+    }
+    println!("Found {} file(s) to lint:", files_to_lint.len());
+    let mut had_issues = false;
+    for path in &files_to_lint {
+        if let Err(e) = lint_file_with_config(path, Some(config.clone())) {
+            print_error(e);
+            had_issues = true;
+        }
+    }
+    if had_issues {
+        Err(error::AndromedaError::runtime_error(
+            "Linting completed with errors".to_string(),
+            None,
+            None,
+            None,
+            None,
+        ))
+    } else {
+        Ok(())
+    }
+}
+
 fn generate_completions(shell: Option<Shell>) {
     let mut cmd = Cli::command();
     let bin_name = "andromeda";
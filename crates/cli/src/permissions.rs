@@ -0,0 +1,74 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use andromeda_core::{NetDescriptor, PermissionsOptions};
+use clap::Args;
+
+use crate::error::{AndromedaError, Result};
+
+/// Permission flags accepted by the commands that execute code.
+///
+/// Every `--allow-*` flag can be given without a value to grant access to
+/// everything, or with a comma separated allowlist, e.g. `--allow-read=./data,/tmp`.
+#[derive(Debug, Clone, Default, Args)]
+pub struct PermissionFlags {
+    /// Allow all permissions
+    #[arg(short = 'A', long)]
+    pub allow_all: bool,
+
+    /// Allow file system read access, optionally restricted to the given paths
+    #[arg(long, num_args = 0.., value_delimiter = ',', require_equals = true, value_name = "PATH")]
+    pub allow_read: Option<Vec<String>>,
+
+    /// Allow file system write access, optionally restricted to the given paths
+    #[arg(long, num_args = 0.., value_delimiter = ',', require_equals = true, value_name = "PATH")]
+    pub allow_write: Option<Vec<String>>,
+
+    /// Allow network access, optionally restricted to the given hosts (`host` or `host:port`)
+    #[arg(long, num_args = 0.., value_delimiter = ',', require_equals = true, value_name = "HOST")]
+    pub allow_net: Option<Vec<String>>,
+
+    /// Allow environment access, optionally restricted to the given variables
+    #[arg(long, num_args = 0.., value_delimiter = ',', require_equals = true, value_name = "VAR")]
+    pub allow_env: Option<Vec<String>>,
+
+    /// Allow loading dynamic libraries, optionally restricted to the given paths
+    #[arg(long, num_args = 0.., value_delimiter = ',', require_equals = true, value_name = "PATH")]
+    pub allow_ffi: Option<Vec<String>>,
+
+    /// Allow spawning subprocesses, optionally restricted to the given programs
+    #[arg(long, num_args = 0.., value_delimiter = ',', require_equals = true, value_name = "PROGRAM")]
+    pub allow_run: Option<Vec<String>>,
+
+    /// Prompt on the terminal for permissions that were not granted instead of denying them
+    #[arg(long)]
+    pub prompt: bool,
+}
+
+impl PermissionFlags {
+    /// Convert the flags into the options understood by the runtime, rejecting
+    /// allowlist entries that can't be parsed.
+    pub fn into_options(self) -> Result<PermissionsOptions> {
+        for host in self.allow_net.iter().flatten() {
+            if NetDescriptor::parse(host).is_none() {
+                return Err(AndromedaError::config_error(
+                    format!("Invalid network permission entry: {host}"),
+                    None,
+                    None::<std::io::Error>,
+                ));
+            }
+        }
+
+        Ok(PermissionsOptions {
+            allow_all: self.allow_all,
+            allow_read: self.allow_read,
+            allow_write: self.allow_write,
+            allow_net: self.allow_net,
+            allow_env: self.allow_env,
+            allow_ffi: self.allow_ffi,
+            allow_run: self.allow_run,
+            prompt: self.prompt,
+        })
+    }
+}
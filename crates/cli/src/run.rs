@@ -2,14 +2,39 @@
 // License, v. 2.0. If a copy of the MPL was not distributed with this
 // file, You can obtain one at https://mozilla.org/MPL/2.0/.
 
+use crate::cache::module_cache;
 use crate::config::{AndromedaConfig, ConfigManager};
+use crate::coverage::{coverage_collector, write_coverage_report};
 use crate::error::{Result, read_file_with_context};
+use crate::watch::{WatchContext, watch};
 use andromeda_core::{
-    AndromedaError, ErrorReporter, HostData, ImportMap, Runtime, RuntimeConfig, RuntimeFile,
+    AndromedaError, CacheSetting, ErrorReporter, HostData, ImportMap, MacroTask, Permissions,
+    PermissionsOptions, Runtime, RuntimeConfig, RuntimeFile, TranspileCache,
 };
 use andromeda_runtime::{
     recommended_builtins, recommended_eventloop_handler, recommended_extensions,
 };
+use nova_vm::{
+    ecmascript::types::{InternalMethods, IntoValue, Object, PropertyKey},
+    engine::context::Bindable,
+};
+use std::path::PathBuf;
+use std::rc::Rc;
+use std::sync::Arc;
+
+/// Options of `andromeda run`
+#[derive(Debug, Clone, Default)]
+pub struct RunOptions {
+    pub verbose: bool,
+    pub no_strict: bool,
+    pub permissions: PermissionsOptions,
+    /// Write the coverage of the modules that ran into this directory
+    pub coverage_dir: Option<PathBuf>,
+    /// How the remote modules are cached
+    pub cache: CacheSetting,
+    /// Arguments of the program, instead of the ones of the process
+    pub args: Option<Vec<String>>,
+}
 
 /// Run a single Andromeda file
 ///
@@ -17,17 +42,44 @@ use andromeda_runtime::{
 /// the CLI interface only passes a single file.
 #[allow(clippy::result_large_err)]
 #[hotpath::measure]
-pub fn run(verbose: bool, no_strict: bool, files: Vec<RuntimeFile>) -> Result<()> {
-    create_runtime_files(verbose, no_strict, files, None)
+pub fn run(
+    verbose: bool,
+    no_strict: bool,
+    permissions: PermissionsOptions,
+    files: Vec<RuntimeFile>,
+) -> Result<()> {
+    run_with_options(
+        RunOptions {
+            verbose,
+            no_strict,
+            permissions,
+            ..Default::default()
+        },
+        files,
+    )
+}
+
+/// Run a single Andromeda file with the given [RunOptions]
+#[allow(clippy::result_large_err)]
+#[hotpath::measure]
+pub fn run_with_options(options: RunOptions, files: Vec<RuntimeFile>) -> Result<()> {
+    create_runtime_files(options, files, None, None)
+}
+
+/// Run a single Andromeda file, restarting it whenever one of its modules changes
+#[allow(clippy::result_large_err)]
+#[hotpath::measure]
+pub fn run_watch(options: RunOptions, files: Vec<RuntimeFile>) -> Result<()> {
+    watch(|context| create_runtime_files(options.clone(), files.clone(), None, Some(context)))
 }
 
 #[allow(clippy::result_large_err)]
 #[hotpath::measure]
 pub fn create_runtime_files(
-    verbose: bool,
-    no_strict: bool,
+    options: RunOptions,
     files: Vec<RuntimeFile>,
     config_override: Option<AndromedaConfig>,
+    watch: Option<&WatchContext>,
 ) -> Result<()> {
     // Load configuration
     let config = config_override.unwrap_or_else(|| {
@@ -43,8 +95,10 @@ pub fn create_runtime_files(
     });
 
     // Apply CLI overrides to config
-    let effective_verbose = verbose || config.runtime.verbose;
-    let effective_no_strict = no_strict || config.runtime.no_strict;
+    let effective_verbose = options.verbose || config.runtime.verbose;
+    let effective_no_strict = options.no_strict || config.runtime.no_strict;
+    let mut effective_permissions = config.permissions.clone();
+    effective_permissions.merge(options.permissions);
 
     // Validate that we have files to run
     if files.is_empty() {
@@ -67,11 +121,15 @@ pub fn create_runtime_files(
         }
     });
     let import_map = build_import_map(&config, start_dir)?;
+    let module_cache = module_cache(&config, import_map.as_ref(), start_dir, options.cache)?;
 
     // Pre-validate all local files exist before starting the runtime
     for file in &filtered_files {
         if let RuntimeFile::Local { path } = file {
             let file_path = std::path::Path::new(path);
+            if let Some(watch) = watch {
+                watch.watch_file(file_path);
+            }
             if !file_path.exists() {
                 return Err(crate::error::AndromedaError::file_not_found(
                     file_path.to_path_buf(),
@@ -85,7 +143,17 @@ pub fn create_runtime_files(
     }
 
     let (macro_task_tx, macro_task_rx) = std::sync::mpsc::channel();
-    let host_data = HostData::new(macro_task_tx);
+    let host_data = HostData::with_permissions(
+        macro_task_tx,
+        Permissions::from_options(&effective_permissions),
+    );
+
+    if let Some(watch) = watch {
+        let macro_task_tx = host_data.macro_task_tx();
+        watch.on_change(move || {
+            let _ = macro_task_tx.send(MacroTask::Terminate);
+        });
+    }
 
     // Store file information before moving files into runtime
     let first_file_info = filtered_files.first().and_then(|file| {
@@ -96,6 +164,15 @@ pub fn create_runtime_files(
         }
     });
 
+    let coverage = options
+        .coverage_dir
+        .as_ref()
+        .map(|_| Rc::new(coverage_collector(&config)));
+
+    // Coverage instruments the sources of the modules instead
+    let transpile_cache = (config.runtime.transpile_cache && coverage.is_none())
+        .then(|| Arc::new(TranspileCache::new(TranspileCache::default_dir())));
+
     let runtime = Runtime::new(
         RuntimeConfig {
             no_strict: effective_no_strict,
@@ -106,16 +183,50 @@ pub fn create_runtime_files(
             eventloop_handler: recommended_eventloop_handler,
             macro_task_rx,
             import_map,
+            coverage: coverage.clone(),
+            module_graph: watch.map(WatchContext::module_graph),
+            module_cache: Some(Arc::new(module_cache)),
+            args: options.args.clone(),
+            transpile_cache: transpile_cache.clone(),
         },
         host_data,
     );
 
-    let mut runtime_output = runtime.run();
+    let mut runtime_output = match runtime.run() {
+        Ok(runtime_output) => runtime_output,
+        Err(error) => {
+            ErrorReporter::print_error(&error);
+            // Keep watching for a fix
+            if watch.is_some() {
+                return Ok(());
+            }
+            std::process::exit(1);
+        }
+    };
+    if watch.is_some() {
+        // Stop the timers, crons and servers left behind before restarting
+        runtime_output.host_data().abort_all_macro_tasks();
+    }
+
+    // Report coverage before any error, which exits the process
+    if let (Some(coverage), Some(coverage_dir)) = (&coverage, &options.coverage_dir) {
+        let files = coverage.collect(&mut runtime_output.agent, &runtime_output.realm_root);
+        write_coverage_report(coverage_dir, &files)?;
+    }
 
     match runtime_output.result {
         Ok(result) => {
             if effective_verbose {
                 println!("✅ Execution completed successfully: {result:?}");
+                if let Some(transpile_cache) = &transpile_cache {
+                    let stats = transpile_cache.stats();
+                    println!(
+                        "📦 Transpile cache: {} hit(s), {} miss(es), {:.0}% hit ratio",
+                        stats.transpile_cache_hits,
+                        stats.transpile_cache_misses,
+                        stats.transpile_cache_hit_ratio() * 100.0
+                    );
+                }
             }
             Ok(())
         }
@@ -132,6 +243,24 @@ pub fn create_runtime_files(
                             .expect("String is not valid UTF-8")
                             .to_string()
                     });
+            // The stack of thrown errors locates the error in the modules
+            let stack =
+                runtime_output
+                    .agent
+                    .run_in_realm(&runtime_output.realm_root, |agent, mut gc| {
+                        let object = Object::try_from(error.value()).ok()?;
+                        let key = PropertyKey::from_static_str(agent, "stack", gc.nogc()).unbind();
+                        let stack = object
+                            .unbind()
+                            .internal_get(agent, key, object.into_value().unbind(), gc.reborrow())
+                            .unbind()
+                            .ok()?;
+                        if stack.is_undefined() {
+                            return None;
+                        }
+                        let stack = stack.to_string(agent, gc).ok()?;
+                        stack.as_str(agent).map(|stack| stack.to_string())
+                    });
 
             // Try to get the first file from our runtime files to show source context
             let (file_path, source_content) = if let Some(path) = first_file_info {
@@ -169,10 +298,19 @@ pub fn create_runtime_files(
             } else {
                 AndromedaError::runtime_error(error_message.clone())
             };
+            let enhanced_error = match stack {
+                Some(stack) => enhanced_error.with_stack_trace(stack),
+                None => enhanced_error,
+            };
 
             // Print the enhanced error using our error reporting system
             ErrorReporter::print_error(&enhanced_error);
 
+            // Keep watching for a fix
+            if watch.is_some() {
+                return Ok(());
+            }
+
             // Exit directly instead of returning another error to avoid double printing
             std::process::exit(1);
         }
@@ -232,7 +370,7 @@ fn apply_file_filters(
 
 /// Build import map from configuration
 #[allow(clippy::result_large_err)]
-fn build_import_map(
+pub(crate) fn build_import_map(
     config: &AndromedaConfig,
     start_dir: Option<&std::path::Path>,
 ) -> Result<Option<ImportMap>> {
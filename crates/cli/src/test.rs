@@ -0,0 +1,737 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use crate::cache::module_cache;
+use crate::config::ConfigManager;
+use crate::error::{AndromedaError, Result};
+use crate::helper::find_test_files;
+use crate::run::build_import_map;
+use crate::watch::WatchContext;
+use andromeda_core::{
+    CacheSetting, HostData, ImportMap, MacroTask, ModuleCache, Permissions, PermissionsOptions,
+    Runtime, RuntimeConfig,
+};
+use andromeda_runtime::{
+    TestContext, TestEvent, TestExt, TestOutcome, TestRunOptions, recommended_builtins,
+    recommended_eventloop_handler, recommended_extensions,
+};
+use console::Style;
+use std::cell::RefCell;
+use std::fmt::Write as _;
+use std::io::Write as _;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Output format of `andromeda test`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum TestReporterKind {
+    /// Human readable output
+    #[default]
+    Pretty,
+    /// Test Anything Protocol, version 14
+    Tap,
+    /// JUnit XML
+    Junit,
+}
+
+/// Options of `andromeda test`
+#[derive(Debug, Clone)]
+pub struct TestOptions {
+    /// Only run the tests whose name contains this string, or matches it when written as `/regex/`
+    pub filter: Option<String>,
+    /// Default timeout of every test, in milliseconds
+    pub timeout: Option<u64>,
+    pub reporter: TestReporterKind,
+    pub no_strict: bool,
+    pub permissions: PermissionsOptions,
+    /// How the remote modules are cached
+    pub cache: CacheSetting,
+}
+
+/// A failed test, step or test module
+struct TestFailure {
+    origin: String,
+    /// Names of the failed test and its ancestors, empty when the module itself failed
+    path: Vec<String>,
+    error: String,
+}
+
+impl TestFailure {
+    fn name(&self) -> String {
+        if self.path.is_empty() {
+            self.origin.clone()
+        } else {
+            self.path.join(" ... ")
+        }
+    }
+}
+
+#[derive(Default)]
+struct TestSummary {
+    passed: usize,
+    failed: usize,
+    ignored: usize,
+    passed_steps: usize,
+    failed_steps: usize,
+    ignored_steps: usize,
+    filtered: usize,
+    only: bool,
+    failures: Vec<TestFailure>,
+}
+
+impl TestSummary {
+    fn has_failed(&self) -> bool {
+        !self.failures.is_empty() || self.only
+    }
+}
+
+trait TestReporter {
+    /// A test module starts running
+    fn report_module(&mut self, _origin: &str) {}
+    fn report_event(&mut self, origin: &str, event: &TestEvent);
+    fn report_summary(&mut self, summary: &TestSummary, elapsed: Duration);
+}
+
+/// Tracks the results of every test module as the harness reports them
+struct TestRun {
+    reporter: Box<dyn TestReporter>,
+    summary: TestSummary,
+    /// Names of the running test and its running steps
+    names: Vec<String>,
+    ended: bool,
+}
+
+impl TestRun {
+    fn handle(&mut self, origin: &str, event: TestEvent) {
+        match &event {
+            TestEvent::Plan { filtered, only, .. } => {
+                self.summary.filtered += filtered;
+                self.summary.only |= only;
+            }
+            TestEvent::Wait { name, depth } => {
+                self.names.truncate(*depth);
+                self.names.push(name.clone());
+            }
+            TestEvent::Result {
+                depth,
+                outcome,
+                error,
+                ..
+            } => {
+                let summary = &mut self.summary;
+                let (passed, failed, ignored) = if *depth == 0 {
+                    (
+                        &mut summary.passed,
+                        &mut summary.failed,
+                        &mut summary.ignored,
+                    )
+                } else {
+                    (
+                        &mut summary.passed_steps,
+                        &mut summary.failed_steps,
+                        &mut summary.ignored_steps,
+                    )
+                };
+                match outcome {
+                    TestOutcome::Ok => *passed += 1,
+                    TestOutcome::Ignored => *ignored += 1,
+                    TestOutcome::Failed => {
+                        *failed += 1;
+                        summary.failures.push(TestFailure {
+                            origin: origin.to_string(),
+                            path: self.names[..=*depth].to_vec(),
+                            error: error.clone().unwrap_or_default(),
+                        });
+                    }
+                }
+                self.names.truncate(*depth);
+            }
+            TestEvent::Error { error } => {
+                self.summary.failures.push(TestFailure {
+                    origin: origin.to_string(),
+                    path: vec![],
+                    error: error.clone(),
+                });
+            }
+            TestEvent::End => self.ended = true,
+        }
+        self.reporter.report_event(origin, &event);
+    }
+}
+
+/// Run the tests registered by the test modules found in `paths`, watching
+/// every module they load when `watch` is given
+#[allow(clippy::result_large_err)]
+#[hotpath::measure]
+pub fn run_tests(
+    paths: &[PathBuf],
+    options: &TestOptions,
+    watch: Option<&WatchContext>,
+) -> Result<()> {
+    let files = find_test_files(paths)?;
+    if files.is_empty() {
+        let warning = Style::new().yellow().bold().apply_to("⚠️");
+        let msg = Style::new().yellow().apply_to("No test modules found.");
+        println!("{warning} {msg}");
+        return Ok(());
+    }
+
+    let config = ConfigManager::load_or_default(None);
+    let mut permissions = config.permissions.clone();
+    permissions.merge(options.permissions.clone());
+    let options = TestOptions {
+        no_strict: options.no_strict || config.runtime.no_strict,
+        permissions,
+        ..options.clone()
+    };
+    let import_map = build_import_map(&config, None)?;
+    let module_cache = Arc::new(module_cache(
+        &config,
+        import_map.as_ref(),
+        None,
+        options.cache,
+    )?);
+
+    let reporter: Box<dyn TestReporter> = match options.reporter {
+        TestReporterKind::Pretty => Box::new(PrettyReporter::default()),
+        TestReporterKind::Tap => Box::new(TapReporter::default()),
+        TestReporterKind::Junit => Box::new(JunitReporter::default()),
+    };
+    let run = Rc::new(RefCell::new(TestRun {
+        reporter,
+        summary: TestSummary::default(),
+        names: vec![],
+        ended: false,
+    }));
+
+    let start = Instant::now();
+    for file in &files {
+        // The run restarts anyway
+        if watch.is_some_and(WatchContext::changed) {
+            break;
+        }
+        run_test_module(
+            file,
+            &options,
+            import_map.clone(),
+            module_cache.clone(),
+            run.clone(),
+            watch,
+        );
+    }
+
+    let mut run = run.borrow_mut();
+    let TestRun {
+        reporter, summary, ..
+    } = &mut *run;
+    reporter.report_summary(summary, start.elapsed());
+
+    if summary.has_failed() {
+        Err(AndromedaError::runtime_error(
+            "Test failed".to_string(),
+            None,
+            None,
+            None,
+            None,
+        ))
+    } else {
+        Ok(())
+    }
+}
+
+/// Run a single test module in its own runtime
+fn run_test_module(
+    file: &Path,
+    options: &TestOptions,
+    import_map: Option<ImportMap>,
+    module_cache: Arc<ModuleCache>,
+    run: Rc<RefCell<TestRun>>,
+    watch: Option<&WatchContext>,
+) {
+    let origin = file.display().to_string();
+    {
+        let mut run = run.borrow_mut();
+        run.ended = false;
+        run.names.clear();
+        run.reporter.report_module(&origin);
+    }
+
+    let (macro_task_tx, macro_task_rx) = std::sync::mpsc::channel();
+    let host_data = HostData::with_permissions(
+        macro_task_tx,
+        Permissions::from_options(&options.permissions),
+    );
+    host_data.storage.borrow_mut().insert(TestContext {
+        options: TestRunOptions {
+            main: std::path::absolute(file)
+                .unwrap_or_else(|_| file.to_path_buf())
+                .to_string_lossy()
+                .to_string(),
+            filter: options.filter.clone(),
+            timeout: options.timeout,
+        },
+        reporter: Box::new({
+            let run = run.clone();
+            let origin = origin.clone();
+            move |event| run.borrow_mut().handle(&origin, event)
+        }),
+    });
+
+    if let Some(watch) = watch {
+        watch.watch_file(file);
+        let macro_task_tx = host_data.macro_task_tx();
+        watch.on_change(move || {
+            let _ = macro_task_tx.send(MacroTask::Terminate);
+        });
+    }
+
+    let mut builtins = recommended_builtins();
+    builtins.push(TestExt::harness());
+
+    let runtime = Runtime::new(
+        RuntimeConfig {
+            no_strict: options.no_strict,
+            files: vec![],
+            verbose: false,
+            extensions: recommended_extensions(),
+            builtins,
+            eventloop_handler: recommended_eventloop_handler,
+            macro_task_rx,
+            import_map,
+            coverage: None,
+            module_graph: watch.map(WatchContext::module_graph),
+            module_cache: Some(module_cache),
+            args: None,
+            transpile_cache: None,
+        },
+        host_data,
+    );
+    let mut runtime_output = match runtime.run() {
+        Ok(runtime_output) => runtime_output,
+        Err(error) => {
+            run.borrow_mut().handle(
+                &origin,
+                TestEvent::Error {
+                    error: error.to_string(),
+                },
+            );
+            return;
+        }
+    };
+    if watch.is_some() {
+        // Stop the timers, crons and servers left behind by the tests
+        runtime_output.host_data().abort_all_macro_tasks();
+        if watch.is_some_and(WatchContext::changed) {
+            return;
+        }
+    }
+
+    let mut run = run.borrow_mut();
+    if let Err(error) = runtime_output.result {
+        let message = runtime_output
+            .agent
+            .run_in_realm(&runtime_output.realm_root, |agent, gc| {
+                error
+                    .value()
+                    .string_repr(agent, gc)
+                    .as_str(agent)
+                    .expect("String is not valid UTF-8")
+                    .to_string()
+            });
+        run.handle(&origin, TestEvent::Error { error: message });
+    } else if !run.ended {
+        run.handle(
+            &origin,
+            TestEvent::Error {
+                error: "The event loop finished while a test was still pending, a promise never resolved"
+                    .to_string(),
+            },
+        );
+    }
+}
+
+fn format_duration(milliseconds: f64) -> String {
+    if milliseconds >= 1000.0 {
+        format!("{:.1}s", milliseconds / 1000.0)
+    } else {
+        format!("{}ms", milliseconds.round() as u64)
+    }
+}
+
+/// Human readable reporter, printing each test as it runs
+#[derive(Default)]
+struct PrettyReporter {
+    /// Whether a `name ...` line is waiting for its result
+    line_open: bool,
+    /// Depth of the test or step the open line belongs to
+    open_depth: usize,
+}
+
+impl PrettyReporter {
+    fn close_line(&mut self) {
+        if self.line_open {
+            println!();
+            self.line_open = false;
+        }
+    }
+}
+
+impl TestReporter for PrettyReporter {
+    fn report_event(&mut self, origin: &str, event: &TestEvent) {
+        match event {
+            TestEvent::Plan { total, .. } => {
+                let tests = if *total == 1 { "test" } else { "tests" };
+                let header = Style::new()
+                    .dim()
+                    .apply_to(format!("running {total} {tests} from"));
+                println!("{header} {}", Style::new().cyan().apply_to(origin));
+            }
+            TestEvent::Wait { name, depth } => {
+                self.close_line();
+                print!("{}{name} ...", "  ".repeat(*depth));
+                let _ = std::io::stdout().flush();
+                self.line_open = true;
+                self.open_depth = *depth;
+            }
+            TestEvent::Result {
+                name,
+                depth,
+                outcome,
+                duration,
+                ..
+            } => {
+                if !(self.line_open && self.open_depth == *depth) {
+                    self.close_line();
+                    print!("{}{name} ...", "  ".repeat(*depth));
+                }
+                let status = match outcome {
+                    TestOutcome::Ok => Style::new().green().apply_to("ok"),
+                    TestOutcome::Failed => Style::new().red().bold().apply_to("FAILED"),
+                    TestOutcome::Ignored => Style::new().yellow().apply_to("ignored"),
+                };
+                let duration = Style::new()
+                    .dim()
+                    .apply_to(format!("({})", format_duration(*duration)));
+                println!(" {status} {duration}");
+                self.line_open = false;
+            }
+            TestEvent::Error { error } => {
+                self.close_line();
+                let label = Style::new().red().bold().apply_to("error:");
+                println!("{label} {origin}: {error}");
+            }
+            TestEvent::End => self.close_line(),
+        }
+    }
+
+    fn report_summary(&mut self, summary: &TestSummary, elapsed: Duration) {
+        self.close_line();
+
+        if !summary.failures.is_empty() {
+            println!();
+            println!(
+                "{}",
+                Style::new().red().bold().reverse().apply_to(" ERRORS ")
+            );
+            for failure in &summary.failures {
+                println!();
+                let arrow = Style::new().dim().apply_to("=>");
+                println!("{} {arrow} {}", failure.name(), failure.origin);
+                let label = Style::new().red().bold().apply_to("error:");
+                println!("{label} {}", failure.error);
+            }
+
+            println!();
+            println!(
+                "{}",
+                Style::new().red().bold().reverse().apply_to(" FAILURES ")
+            );
+            println!();
+            for failure in &summary.failures {
+                let arrow = Style::new().dim().apply_to("=>");
+                println!("{} {arrow} {}", failure.name(), failure.origin);
+            }
+        }
+
+        println!();
+        let status = if summary.has_failed() {
+            Style::new().red().bold().apply_to("FAILED")
+        } else {
+            Style::new().green().bold().apply_to("ok")
+        };
+        let steps = |count: usize| match count {
+            0 => String::new(),
+            1 => " (1 step)".to_string(),
+            count => format!(" ({count} steps)"),
+        };
+        let elapsed = Style::new().dim().apply_to(format!(
+            "({})",
+            format_duration(elapsed.as_secs_f64() * 1000.0)
+        ));
+        println!(
+            "{status} | {} passed{} | {} failed{} | {} ignored{} | {} filtered out {elapsed}",
+            summary.passed,
+            steps(summary.passed_steps),
+            summary.failed,
+            steps(summary.failed_steps),
+            summary.ignored,
+            steps(summary.ignored_steps),
+            summary.filtered,
+        );
+
+        if summary.only {
+            let label = Style::new().red().bold().apply_to("error:");
+            println!("{label} Test failed because the \"only\" option was used");
+        }
+    }
+}
+
+/// Test Anything Protocol reporter, with steps reported as subtests
+/// https://testanything.org/tap-version-14-specification.html
+#[derive(Default)]
+struct TapReporter {
+    started: bool,
+    /// Number of results reported at each depth of the running test
+    counts: Vec<usize>,
+    /// Names of the running test and its running steps
+    names: Vec<String>,
+}
+
+impl TapReporter {
+    fn indent(depth: usize) -> String {
+        "    ".repeat(depth)
+    }
+
+    fn start(&mut self) {
+        if !self.started {
+            println!("TAP version 14");
+            self.started = true;
+            self.counts.push(0);
+        }
+    }
+
+    fn print_diagnostics(depth: usize, error: &str) {
+        let indent = Self::indent(depth);
+        println!("{indent}  ---");
+        println!(
+            "{indent}  message: {}",
+            serde_json::to_string(error).unwrap_or_default()
+        );
+        println!("{indent}  severity: fail");
+        println!("{indent}  ...");
+    }
+}
+
+impl TestReporter for TapReporter {
+    fn report_module(&mut self, origin: &str) {
+        self.start();
+        println!("# {origin}");
+    }
+
+    fn report_event(&mut self, origin: &str, event: &TestEvent) {
+        match event {
+            TestEvent::Plan { .. } | TestEvent::End => {}
+            TestEvent::Wait { name, depth } => {
+                // The first step of a test opens a subtest for it
+                if *depth > 0 && self.counts.len() == *depth {
+                    println!(
+                        "{}# Subtest: {}",
+                        Self::indent(*depth),
+                        self.names[*depth - 1]
+                    );
+                    self.counts.push(0);
+                }
+                self.names.truncate(*depth);
+                self.names.push(name.clone());
+            }
+            TestEvent::Result {
+                name,
+                depth,
+                outcome,
+                error,
+                ..
+            } => {
+                if self.counts.len() > depth + 1 {
+                    println!("{}1..{}", Self::indent(depth + 1), self.counts[depth + 1]);
+                    self.counts.truncate(depth + 1);
+                }
+                self.counts[*depth] += 1;
+                let indent = Self::indent(*depth);
+                let number = self.counts[*depth];
+                match outcome {
+                    TestOutcome::Ok => println!("{indent}ok {number} - {name}"),
+                    TestOutcome::Ignored => println!("{indent}ok {number} - {name} # SKIP"),
+                    TestOutcome::Failed => {
+                        println!("{indent}not ok {number} - {name}");
+                        Self::print_diagnostics(*depth, error.as_deref().unwrap_or_default());
+                    }
+                }
+                self.names.truncate(*depth);
+            }
+            TestEvent::Error { error } => {
+                self.counts.truncate(1);
+                self.counts[0] += 1;
+                println!("not ok {} - {origin}", self.counts[0]);
+                Self::print_diagnostics(0, error);
+            }
+        }
+    }
+
+    fn report_summary(&mut self, summary: &TestSummary, _elapsed: Duration) {
+        self.start();
+        self.counts.truncate(1);
+        println!("1..{}", self.counts[0]);
+        if summary.only {
+            println!("# Test failed because the \"only\" option was used");
+        }
+    }
+}
+
+struct JunitTestCase {
+    name: String,
+    duration: f64,
+    outcome: TestOutcome,
+    error: Option<String>,
+}
+
+struct JunitTestSuite {
+    name: String,
+    cases: Vec<JunitTestCase>,
+}
+
+/// JUnit XML reporter, printing the report once every module ran
+#[derive(Default)]
+struct JunitReporter {
+    suites: Vec<JunitTestSuite>,
+    /// Names of the running test and its running steps
+    names: Vec<String>,
+}
+
+fn escape_xml(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+impl TestReporter for JunitReporter {
+    fn report_module(&mut self, origin: &str) {
+        self.suites.push(JunitTestSuite {
+            name: origin.to_string(),
+            cases: vec![],
+        });
+        self.names.clear();
+    }
+
+    fn report_event(&mut self, origin: &str, event: &TestEvent) {
+        let Some(suite) = self.suites.last_mut() else {
+            return;
+        };
+        match event {
+            TestEvent::Plan { .. } | TestEvent::End => {}
+            TestEvent::Wait { name, depth } => {
+                self.names.truncate(*depth);
+                self.names.push(name.clone());
+            }
+            TestEvent::Result {
+                depth,
+                outcome,
+                duration,
+                error,
+                ..
+            } => {
+                suite.cases.push(JunitTestCase {
+                    name: self.names[..=*depth].join(" > "),
+                    duration: *duration,
+                    outcome: *outcome,
+                    error: error.clone(),
+                });
+                self.names.truncate(*depth);
+            }
+            TestEvent::Error { error } => {
+                suite.cases.push(JunitTestCase {
+                    name: origin.to_string(),
+                    duration: 0.0,
+                    outcome: TestOutcome::Failed,
+                    error: Some(error.clone()),
+                });
+            }
+        }
+    }
+
+    fn report_summary(&mut self, _summary: &TestSummary, elapsed: Duration) {
+        let count = |suite: &JunitTestSuite, outcome: TestOutcome| {
+            suite
+                .cases
+                .iter()
+                .filter(|case| case.outcome == outcome)
+                .count()
+        };
+        let tests: usize = self.suites.iter().map(|suite| suite.cases.len()).sum();
+        let failures: usize = self
+            .suites
+            .iter()
+            .map(|suite| count(suite, TestOutcome::Failed))
+            .sum();
+
+        let mut xml = String::new();
+        let _ = writeln!(xml, r#"<?xml version="1.0" encoding="UTF-8"?>"#);
+        let _ = writeln!(
+            xml,
+            r#"<testsuites name="andromeda test" tests="{tests}" failures="{failures}" errors="0" time="{:.3}">"#,
+            elapsed.as_secs_f64()
+        );
+        for suite in &self.suites {
+            let time: f64 = suite.cases.iter().map(|case| case.duration).sum::<f64>() / 1000.0;
+            let _ = writeln!(
+                xml,
+                r#"  <testsuite name="{}" tests="{}" failures="{}" skipped="{}" errors="0" time="{time:.3}">"#,
+                escape_xml(&suite.name),
+                suite.cases.len(),
+                count(suite, TestOutcome::Failed),
+                count(suite, TestOutcome::Ignored),
+            );
+            for case in &suite.cases {
+                let _ = write!(
+                    xml,
+                    r#"    <testcase name="{}" classname="{}" time="{:.3}""#,
+                    escape_xml(&case.name),
+                    escape_xml(&suite.name),
+                    case.duration / 1000.0
+                );
+                match case.outcome {
+                    TestOutcome::Ok => {
+                        let _ = writeln!(xml, "/>");
+                    }
+                    TestOutcome::Ignored => {
+                        let _ = writeln!(xml, ">\n      <skipped/>\n    </testcase>");
+                    }
+                    TestOutcome::Failed => {
+                        let error = case.error.as_deref().unwrap_or_default();
+                        let message = error.lines().next().unwrap_or_default();
+                        let _ = writeln!(
+                            xml,
+                            ">\n      <failure message=\"{}\">{}</failure>\n    </testcase>",
+                            escape_xml(message),
+                            escape_xml(error)
+                        );
+                    }
+                }
+            }
+            let _ = writeln!(xml, "  </testsuite>");
+        }
+        let _ = writeln!(xml, "</testsuites>");
+        print!("{xml}");
+    }
+}
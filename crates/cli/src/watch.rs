@@ -0,0 +1,163 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use crate::error::{Result, print_error};
+use andromeda_core::DependencyGraph;
+use console::{Style, Term};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+/// Delay between two checks of the watched files
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Changes made within this delay of the first one only restart once
+const DEBOUNCE: Duration = Duration::from_millis(200);
+
+type StopHandler = Box<dyn FnOnce() + Send>;
+
+/// Files watched during one run of a `--watch` command
+#[derive(Clone, Default)]
+pub struct WatchContext {
+    module_graph: Arc<Mutex<DependencyGraph>>,
+    on_change: Arc<Mutex<Option<StopHandler>>>,
+    changed: Arc<AtomicBool>,
+}
+
+impl WatchContext {
+    /// Module graph to hand to the runtime, every module it loads gets watched
+    pub fn module_graph(&self) -> Arc<Mutex<DependencyGraph>> {
+        self.module_graph.clone()
+    }
+
+    /// Watch a file that is not loaded by a runtime, e.g. a linted file
+    pub fn watch_file(&self, path: &Path) {
+        self.module_graph
+            .lock()
+            .unwrap()
+            .add_module(&path.to_string_lossy());
+    }
+
+    /// Call `stop` once a watched file changed, to interrupt a run that would
+    /// otherwise never finish, such as a server
+    pub fn on_change(&self, stop: impl FnOnce() + Send + 'static) {
+        *self.on_change.lock().unwrap() = Some(Box::new(stop));
+    }
+
+    /// Whether a watched file changed, after which the run can stop early
+    pub fn changed(&self) -> bool {
+        self.changed.load(Ordering::Acquire)
+    }
+
+    /// The local files of the module graph
+    fn files(&self) -> Vec<PathBuf> {
+        self.module_graph
+            .lock()
+            .unwrap()
+            .modules()
+            .into_iter()
+            .filter(|module| !module.contains("://"))
+            .map(|module| std::path::absolute(&module).unwrap_or_else(|_| PathBuf::from(module)))
+            .filter(|path| path.is_file())
+            .collect()
+    }
+}
+
+/// Run `job`, then run it again every time one of the files it watched changes.
+/// Errors are reported without stopping the watcher, only interrupting the
+/// process stops it.
+#[allow(clippy::result_large_err)]
+pub fn watch<F>(mut job: F) -> Result<()>
+where
+    F: FnMut(&WatchContext) -> Result<()>,
+{
+    let mut changed: Option<PathBuf> = None;
+    loop {
+        print_banner(changed.as_deref());
+
+        let context = WatchContext::default();
+        let done = Arc::new(AtomicBool::new(false));
+        let (change_tx, change_rx) = mpsc::channel();
+        let watcher = thread::spawn({
+            let context = context.clone();
+            let done = done.clone();
+            move || watch_files(&context, &done, change_tx)
+        });
+
+        if let Err(error) = job(&context) {
+            print_error(error);
+        }
+
+        let change = match change_rx.try_recv() {
+            Ok(change) => Some(change),
+            Err(_) => {
+                let label = Style::new().cyan().bold().apply_to("Watcher");
+                println!("{label} Process finished. Restarting on file change...");
+                change_rx.recv().ok()
+            }
+        };
+        done.store(true, Ordering::Release);
+        let _ = watcher.join();
+
+        match change {
+            Some(change) => changed = Some(change),
+            // The watcher thread stopped without seeing a change
+            None => return Ok(()),
+        }
+    }
+}
+
+fn print_banner(changed: Option<&Path>) {
+    let _ = Term::stdout().clear_screen();
+    let label = Style::new().cyan().bold().apply_to("Watcher");
+    match changed {
+        Some(path) => {
+            let path = Style::new().dim().apply_to(relative_path(path).display());
+            println!("{label} File change detected: {path}. Restarting!");
+        }
+        None => println!("{label} Process started."),
+    }
+}
+
+/// Path relative to the current directory when it's inside of it
+fn relative_path(path: &Path) -> PathBuf {
+    std::env::current_dir()
+        .ok()
+        .and_then(|cwd| path.strip_prefix(cwd).ok().map(Path::to_path_buf))
+        .unwrap_or_else(|| path.to_path_buf())
+}
+
+/// Poll the modification time of the watched files until one of them changes,
+/// then stop the run and send the changed file
+fn watch_files(context: &WatchContext, done: &AtomicBool, change_tx: Sender<PathBuf>) {
+    let mut modified: HashMap<PathBuf, Option<SystemTime>> = HashMap::new();
+    while !done.load(Ordering::Acquire) {
+        thread::sleep(POLL_INTERVAL);
+        let changed = context.files().into_iter().find(|path| {
+            let time = path
+                .metadata()
+                .and_then(|metadata| metadata.modified())
+                .ok();
+            // A file seen for the first time did not change
+            modified
+                .insert(path.clone(), time)
+                .is_some_and(|previous| previous != time)
+        });
+
+        if let Some(changed) = changed {
+            // Editors often write a file in several steps
+            thread::sleep(DEBOUNCE);
+            context.changed.store(true, Ordering::Release);
+            if let Some(stop) = context.on_change.lock().unwrap().take() {
+                stop();
+            }
+            let _ = change_tx.send(changed);
+            return;
+        }
+    }
+}
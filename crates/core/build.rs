@@ -0,0 +1,31 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! Exposes the git revision of the `nova_vm` workspace dependency as
+//! `NOVA_REVISION`, which keys the transpile cache.
+
+use std::path::Path;
+
+fn main() {
+    let manifest = Path::new(env!("CARGO_MANIFEST_DIR")).join("../../Cargo.toml");
+    println!("cargo:rerun-if-changed={}", manifest.display());
+
+    // Outside the workspace, such as a published crate, the package version
+    // in the cache key still tells releases apart
+    let revision = std::fs::read_to_string(&manifest)
+        .ok()
+        .and_then(|manifest| manifest.parse::<toml::Table>().ok())
+        .and_then(|manifest| {
+            let nova = manifest
+                .get("workspace")?
+                .get("dependencies")?
+                .get("nova_vm")?;
+            nova.get("rev")
+                .or_else(|| nova.get("version"))?
+                .as_str()
+                .map(str::to_string)
+        })
+        .unwrap_or_else(|| "unknown".to_string());
+    println!("cargo:rustc-env=NOVA_REVISION={revision}");
+}
@@ -0,0 +1,620 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use std::{
+    cell::RefCell,
+    collections::{BTreeMap, HashMap, HashSet},
+    fmt::Write,
+};
+
+use nova_vm::{
+    ecmascript::{
+        execution::agent::{GcAgent, RealmRoot},
+        scripts_and_modules::script::{parse_script, script_evaluation},
+        types,
+    },
+    engine::context::Bindable,
+};
+use oxc_allocator::Allocator;
+use oxc_ast::ast::{
+    ArrowFunctionExpression, ConditionalExpression, Declaration, DoWhileStatement, ForInStatement,
+    ForOfStatement, ForStatement, Function, FunctionBody, IfStatement, LabeledStatement,
+    LogicalExpression, Statement, SwitchStatement, TSModuleDeclaration, WhileStatement,
+    WithStatement,
+};
+use oxc_ast_visit::{Visit, walk};
+use oxc_parser::Parser;
+use oxc_span::{GetSpan, SourceType, Span};
+use oxc_syntax::scope::ScopeFlags;
+use serde::Deserialize;
+
+/// Global object the instrumented modules store their counters in.
+const COUNTERS_GLOBAL: &str = "__andromeda_coverage__";
+
+/// Collects line, branch and function coverage of the modules a [crate::Runtime] loads.
+///
+/// Modules accepted by the filter are instrumented with hit counters before being parsed
+/// by Nova, and the counters are read back with [Coverage::collect] once the runtime ran.
+pub struct Coverage {
+    filter: Box<dyn Fn(&str) -> bool>,
+    files: RefCell<Vec<InstrumentedFile>>,
+}
+
+/// Layout of the counters of an instrumented module.
+struct InstrumentedFile {
+    path: String,
+    /// Line of each statement counter.
+    statements: Vec<usize>,
+    functions: Vec<FunctionInfo>,
+    branches: Vec<BranchInfo>,
+}
+
+struct FunctionInfo {
+    name: String,
+    line: usize,
+}
+
+struct BranchInfo {
+    line: usize,
+    kind: BranchKind,
+}
+
+enum BranchKind {
+    /// One counter per path, like the consequent and alternate of an `if`.
+    Paths(Vec<usize>),
+    /// The counter of the whole logical expression and of its right operand.
+    ShortCircuit { evaluated: usize, right: usize },
+}
+
+#[derive(Deserialize, Default)]
+struct Counters {
+    s: Vec<u64>,
+    f: Vec<u64>,
+    b: Vec<u64>,
+}
+
+/// Coverage of a single module.
+#[derive(Debug, Clone)]
+pub struct FileCoverage {
+    pub path: String,
+    /// Hits of every line holding a statement, by line number.
+    pub lines: BTreeMap<usize, u64>,
+    pub functions: Vec<FunctionCoverage>,
+    pub branches: Vec<BranchCoverage>,
+}
+
+#[derive(Debug, Clone)]
+pub struct FunctionCoverage {
+    pub name: String,
+    pub line: usize,
+    pub hits: u64,
+}
+
+#[derive(Debug, Clone)]
+pub struct BranchCoverage {
+    pub line: usize,
+    pub block: usize,
+    pub branch: usize,
+    /// `None` when the code holding the branch never ran.
+    pub taken: Option<u64>,
+}
+
+impl FileCoverage {
+    pub fn lines_hit(&self) -> usize {
+        self.lines.values().filter(|hits| **hits > 0).count()
+    }
+
+    pub fn functions_hit(&self) -> usize {
+        self.functions.iter().filter(|f| f.hits > 0).count()
+    }
+
+    pub fn branches_hit(&self) -> usize {
+        self.branches
+            .iter()
+            .filter(|b| b.taken.is_some_and(|taken| taken > 0))
+            .count()
+    }
+
+    /// Write the coverage of this module as an lcov record.
+    pub fn write_lcov(&self, out: &mut String) {
+        let _ = writeln!(out, "TN:");
+        let _ = writeln!(out, "SF:{}", self.path);
+        for function in &self.functions {
+            let _ = writeln!(out, "FN:{},{}", function.line, function.name);
+        }
+        for function in &self.functions {
+            let _ = writeln!(out, "FNDA:{},{}", function.hits, function.name);
+        }
+        let _ = writeln!(out, "FNF:{}", self.functions.len());
+        let _ = writeln!(out, "FNH:{}", self.functions_hit());
+        for branch in &self.branches {
+            let taken = branch
+                .taken
+                .map_or_else(|| "-".to_string(), |taken| taken.to_string());
+            let _ = writeln!(
+                out,
+                "BRDA:{},{},{},{taken}",
+                branch.line, branch.block, branch.branch
+            );
+        }
+        let _ = writeln!(out, "BRF:{}", self.branches.len());
+        let _ = writeln!(out, "BRH:{}", self.branches_hit());
+        for (line, hits) in &self.lines {
+            let _ = writeln!(out, "DA:{line},{hits}");
+        }
+        let _ = writeln!(out, "LF:{}", self.lines.len());
+        let _ = writeln!(out, "LH:{}", self.lines_hit());
+        let _ = writeln!(out, "end_of_record");
+    }
+}
+
+/// Format a coverage report in the lcov tracefile format.
+pub fn to_lcov(files: &[FileCoverage]) -> String {
+    let mut out = String::new();
+    for file in files {
+        file.write_lcov(&mut out);
+    }
+    out
+}
+
+impl Coverage {
+    /// Create a collector instrumenting the modules whose path the filter accepts.
+    pub fn new(filter: impl Fn(&str) -> bool + 'static) -> Self {
+        Self {
+            filter: Box::new(filter),
+            files: RefCell::default(),
+        }
+    }
+
+    /// Instrument the source of a module with hit counters, if the filter accepts its path.
+    /// Sources that fail to parse are returned untouched so Nova reports their errors.
+    pub fn instrument(&self, path: &str, source: String) -> String {
+        if !(self.filter)(path) {
+            return source;
+        }
+
+        let allocator = Allocator::default();
+        let source_type = SourceType::from_path(path)
+            .unwrap_or_default()
+            .with_module(true);
+        let ret = Parser::new(&allocator, &source, source_type).parse();
+        if ret.panicked || !ret.errors.is_empty() {
+            return source;
+        }
+
+        let id = self.files.borrow().len();
+        let mut instrumenter = Instrumenter::new(id, &source);
+        instrumenter.visit_program(&ret.program);
+        let Instrumenter {
+            mut insertions,
+            statements,
+            functions,
+            branches,
+            branch_counters,
+            ..
+        } = instrumenter;
+
+        insertions.sort_by_key(|insertion| (insertion.offset, insertion.order));
+        let mut output = String::with_capacity(source.len() + insertions.len() * 32);
+        let mut last = 0;
+        for insertion in &insertions {
+            let offset = insertion.offset as usize;
+            output.push_str(&source[last..offset]);
+            output.push_str(&insertion.text);
+            last = offset;
+        }
+        output.push_str(&source[last..]);
+
+        // A hoisted function, so counters also work in code running before the
+        // module body does, like functions called through an import cycle
+        let _ = write!(
+            output,
+            "\nvar __andromeda_cov_data_{id};\
+             function __andromeda_cov_{id}() {{ return __andromeda_cov_data_{id} ??= \
+             ((globalThis.{COUNTERS_GLOBAL} ??= {{}})[{id}] ??= \
+             {{ s: new Array({}).fill(0), f: new Array({}).fill(0), b: new Array({branch_counters}).fill(0) }}); }}\n",
+            statements.len(),
+            functions.len(),
+        );
+
+        self.files.borrow_mut().push(InstrumentedFile {
+            path: path.to_string(),
+            statements,
+            functions,
+            branches,
+        });
+        output
+    }
+
+    /// Read the counters of the instrumented modules from the realm they ran in.
+    pub fn collect(&self, agent: &mut GcAgent, realm_root: &RealmRoot) -> Vec<FileCoverage> {
+        let json = agent.run_in_realm(realm_root, |agent, mut gc| {
+            let realm = agent.current_realm(gc.nogc());
+            let source_text = types::String::from_string(
+                agent,
+                format!("JSON.stringify(globalThis.{COUNTERS_GLOBAL} ?? {{}})"),
+                gc.nogc(),
+            );
+            let script = parse_script(agent, source_text, realm, true, None, gc.nogc()).ok()?;
+            let value = script_evaluation(agent, script.unbind(), gc.reborrow())
+                .unbind()
+                .ok()?;
+            value
+                .string_repr(agent, gc)
+                .as_str(agent)
+                .map(|json| json.to_string())
+        });
+        let mut counters: HashMap<String, Counters> = json
+            .and_then(|json| serde_json::from_str(&json).ok())
+            .unwrap_or_default();
+
+        self.files
+            .borrow()
+            .iter()
+            .enumerate()
+            .map(|(id, file)| file.coverage(counters.remove(&id.to_string()).unwrap_or_default()))
+            .collect()
+    }
+}
+
+impl InstrumentedFile {
+    fn coverage(&self, counters: Counters) -> FileCoverage {
+        let count = |counters: &[u64], index: usize| counters.get(index).copied().unwrap_or(0);
+
+        let mut lines = BTreeMap::new();
+        for (index, line) in self.statements.iter().enumerate() {
+            let hits = lines.entry(*line).or_insert(0);
+            *hits = (*hits).max(count(&counters.s, index));
+        }
+
+        let functions = self
+            .functions
+            .iter()
+            .enumerate()
+            .map(|(index, function)| FunctionCoverage {
+                name: function.name.clone(),
+                line: function.line,
+                hits: count(&counters.f, index),
+            })
+            .collect();
+
+        let mut branches = Vec::new();
+        for (block, branch) in self.branches.iter().enumerate() {
+            let taken: Vec<u64> = match &branch.kind {
+                BranchKind::Paths(paths) => paths
+                    .iter()
+                    .map(|counter| count(&counters.b, *counter))
+                    .collect(),
+                BranchKind::ShortCircuit { evaluated, right } => {
+                    let evaluated = count(&counters.b, *evaluated);
+                    let right = count(&counters.b, *right);
+                    vec![evaluated.saturating_sub(right), right]
+                }
+            };
+            let reached = taken.iter().any(|taken| *taken > 0);
+            for (index, taken) in taken.into_iter().enumerate() {
+                branches.push(BranchCoverage {
+                    line: branch.line,
+                    block,
+                    branch: index,
+                    taken: reached.then_some(taken),
+                });
+            }
+        }
+
+        FileCoverage {
+            path: self.path.clone(),
+            lines,
+            functions,
+            branches,
+        }
+    }
+}
+
+/// Text inserted in the source of a module.
+struct Insertion {
+    offset: u32,
+    /// Orders insertions at the same offset so the code they wrap stays well nested:
+    /// closing text first, innermost first, then opening text, outermost first.
+    /// Closing text is keyed by twice the length of what it closes, so the `else`
+    /// added to an `if` can sort between its body and an enclosing block.
+    order: (u8, i64),
+    text: String,
+}
+
+const CLOSE: u8 = 0;
+const OPEN_BLOCK: u8 = 1;
+const STATEMENT: u8 = 2;
+const OPEN_EXPRESSION: u8 = 3;
+
+/// Walks a module, recording where to insert counters.
+struct Instrumenter {
+    id: usize,
+    line_starts: Vec<u32>,
+    insertions: Vec<Insertion>,
+    statements: Vec<usize>,
+    functions: Vec<FunctionInfo>,
+    branches: Vec<BranchInfo>,
+    branch_counters: usize,
+    /// Statements that can't be preceded by a counter, like the body of a label.
+    uncounted: HashSet<u32>,
+    /// Name of the function whose body is visited next.
+    function_name: Option<String>,
+    function_names: HashSet<String>,
+}
+
+impl Instrumenter {
+    fn new(id: usize, source: &str) -> Self {
+        let line_starts = std::iter::once(0)
+            .chain(
+                source
+                    .match_indices('\n')
+                    .map(|(index, _)| index as u32 + 1),
+            )
+            .collect();
+        Self {
+            id,
+            line_starts,
+            insertions: vec![],
+            statements: vec![],
+            functions: vec![],
+            branches: vec![],
+            branch_counters: 0,
+            uncounted: HashSet::new(),
+            function_name: None,
+            function_names: HashSet::new(),
+        }
+    }
+
+    fn line(&self, offset: u32) -> usize {
+        self.line_starts.partition_point(|start| *start <= offset)
+    }
+
+    fn counter(&self, kind: char, index: usize) -> String {
+        format!("__andromeda_cov_{}().{kind}[{index}]++", self.id)
+    }
+
+    fn insert(&mut self, offset: u32, order: (u8, i64), text: String) {
+        self.insertions.push(Insertion {
+            offset,
+            order,
+            text,
+        });
+    }
+
+    fn new_branch_counter(&mut self) -> usize {
+        self.branch_counters += 1;
+        self.branch_counters - 1
+    }
+
+    /// Wrap an expression in a sequence incrementing a counter first.
+    fn wrap_expression(&mut self, span: Span, counter: String) {
+        let len = i64::from(span.size());
+        self.insert(span.start, (OPEN_EXPRESSION, -len), format!("({counter}, "));
+        self.insert(span.end, (CLOSE, 2 * len), ")".to_string());
+    }
+
+    /// Make sure the body of a statement is a block, so a counter can be inserted
+    /// before it, optionally starting the block with `prefix`.
+    fn wrap_body(&mut self, body: &Statement<'_>, prefix: Option<String>) {
+        let prefix = prefix
+            .map(|counter| format!("{counter};"))
+            .unwrap_or_default();
+        if let Statement::BlockStatement(block) = body {
+            if !prefix.is_empty() {
+                self.insert(block.span.start + 1, (STATEMENT, 0), prefix);
+            }
+        } else {
+            let len = i64::from(body.span().size());
+            self.insert(body.span().start, (OPEN_BLOCK, -len), format!("{{{prefix}"));
+            self.insert(body.span().end, (CLOSE, 2 * len), "}".to_string());
+        }
+    }
+
+    fn is_counted(statement: &Statement<'_>) -> bool {
+        match statement {
+            Statement::ExpressionStatement(_)
+            | Statement::IfStatement(_)
+            | Statement::ForStatement(_)
+            | Statement::ForInStatement(_)
+            | Statement::ForOfStatement(_)
+            | Statement::WhileStatement(_)
+            | Statement::DoWhileStatement(_)
+            | Statement::ReturnStatement(_)
+            | Statement::ThrowStatement(_)
+            | Statement::TryStatement(_)
+            | Statement::SwitchStatement(_)
+            | Statement::BreakStatement(_)
+            | Statement::ContinueStatement(_)
+            | Statement::ExportDefaultDeclaration(_) => true,
+            Statement::VariableDeclaration(declaration) => !declaration.declare,
+            Statement::ClassDeclaration(class) => !class.declare,
+            Statement::ExportNamedDeclaration(export) => matches!(
+                &export.declaration,
+                Some(Declaration::VariableDeclaration(declaration)) if !declaration.declare
+            ),
+            _ => false,
+        }
+    }
+
+    /// Count calls of the function whose body starts at `offset`.
+    fn count_function(&mut self, line_offset: u32) -> String {
+        let index = self.functions.len();
+        let name = match self.function_name.take() {
+            Some(name) if self.function_names.insert(name.clone()) => name,
+            Some(name) => format!("{name}_{index}"),
+            None => format!("(anonymous_{index})"),
+        };
+        self.functions.push(FunctionInfo {
+            name,
+            line: self.line(line_offset),
+        });
+        self.counter('f', index)
+    }
+}
+
+impl<'a> Visit<'a> for Instrumenter {
+    fn visit_statement(&mut self, it: &Statement<'a>) {
+        let span = it.span();
+        if Self::is_counted(it) && !self.uncounted.contains(&span.start) {
+            let index = self.statements.len();
+            self.statements.push(self.line(span.start));
+            let counter = self.counter('s', index);
+            self.insert(span.start, (STATEMENT, 0), format!("{counter};"));
+        }
+        walk::walk_statement(self, it);
+    }
+
+    fn visit_labeled_statement(&mut self, it: &LabeledStatement<'a>) {
+        // `label: counter; loop` would detach the label from the loop
+        self.uncounted.insert(it.body.span().start);
+        walk::walk_labeled_statement(self, it);
+    }
+
+    fn visit_function(&mut self, it: &Function<'a>, flags: ScopeFlags) {
+        if it.body.is_some() {
+            self.function_name = it.id.as_ref().map(|id| id.name.to_string());
+        }
+        walk::walk_function(self, it, flags);
+        self.function_name = None;
+    }
+
+    fn visit_function_body(&mut self, it: &FunctionBody<'a>) {
+        // Counters go after the directives, which must stay first
+        let offset = it
+            .directives
+            .last()
+            .map_or(it.span.start + 1, |directive| directive.span.end);
+        let counter = self.count_function(it.span.start);
+        self.insert(offset, (STATEMENT, 0), format!("{counter};"));
+        walk::walk_function_body(self, it);
+    }
+
+    fn visit_arrow_function_expression(&mut self, it: &ArrowFunctionExpression<'a>) {
+        self.function_name = None;
+        if it.expression {
+            // The body of `() => expression` holds a single expression statement
+            // that can't be preceded by a counter
+            if let Some(Statement::ExpressionStatement(body)) = it.body.statements.first() {
+                let counter = self.count_function(it.span.start);
+                self.wrap_expression(body.expression.span(), counter);
+                self.visit_formal_parameters(&it.params);
+                self.visit_expression(&body.expression);
+            }
+            return;
+        }
+        walk::walk_arrow_function_expression(self, it);
+    }
+
+    fn visit_if_statement(&mut self, it: &IfStatement<'a>) {
+        let consequent = self.new_branch_counter();
+        let alternate = self.new_branch_counter();
+        self.branches.push(BranchInfo {
+            line: self.line(it.span.start),
+            kind: BranchKind::Paths(vec![consequent, alternate]),
+        });
+
+        let counter = self.counter('b', consequent);
+        self.wrap_body(&it.consequent, Some(counter));
+        let counter = self.counter('b', alternate);
+        match &it.alternate {
+            Some(statement) => self.wrap_body(statement, Some(counter)),
+            None => {
+                // Added before the block wrapping this `if`, if it is the body of another one
+                let len = i64::from(it.span.size());
+                self.insert(
+                    it.span.end,
+                    (CLOSE, 2 * len - 1),
+                    format!(" else {{ {counter}; }}"),
+                );
+            }
+        }
+        walk::walk_if_statement(self, it);
+    }
+
+    fn visit_conditional_expression(&mut self, it: &ConditionalExpression<'a>) {
+        let consequent = self.new_branch_counter();
+        let alternate = self.new_branch_counter();
+        self.branches.push(BranchInfo {
+            line: self.line(it.span.start),
+            kind: BranchKind::Paths(vec![consequent, alternate]),
+        });
+
+        let counter = self.counter('b', consequent);
+        self.wrap_expression(it.consequent.span(), counter);
+        let counter = self.counter('b', alternate);
+        self.wrap_expression(it.alternate.span(), counter);
+        walk::walk_conditional_expression(self, it);
+    }
+
+    fn visit_logical_expression(&mut self, it: &LogicalExpression<'a>) {
+        let evaluated = self.new_branch_counter();
+        let right = self.new_branch_counter();
+        self.branches.push(BranchInfo {
+            line: self.line(it.span.start),
+            kind: BranchKind::ShortCircuit { evaluated, right },
+        });
+
+        let counter = self.counter('b', evaluated);
+        self.wrap_expression(it.left.span(), counter);
+        let counter = self.counter('b', right);
+        self.wrap_expression(it.right.span(), counter);
+        walk::walk_logical_expression(self, it);
+    }
+
+    fn visit_switch_statement(&mut self, it: &SwitchStatement<'a>) {
+        let mut paths = Vec::with_capacity(it.cases.len());
+        for case in &it.cases {
+            let path = self.new_branch_counter();
+            paths.push(path);
+            let counter = format!("{};", self.counter('b', path));
+            match case.consequent.first() {
+                Some(statement) => self.insert(statement.span().start, (OPEN_BLOCK, 0), counter),
+                None => self.insert(case.span.end, (STATEMENT, 0), counter),
+            }
+        }
+        self.branches.push(BranchInfo {
+            line: self.line(it.span.start),
+            kind: BranchKind::Paths(paths),
+        });
+        walk::walk_switch_statement(self, it);
+    }
+
+    fn visit_for_statement(&mut self, it: &ForStatement<'a>) {
+        self.wrap_body(&it.body, None);
+        walk::walk_for_statement(self, it);
+    }
+
+    fn visit_for_in_statement(&mut self, it: &ForInStatement<'a>) {
+        self.wrap_body(&it.body, None);
+        walk::walk_for_in_statement(self, it);
+    }
+
+    fn visit_for_of_statement(&mut self, it: &ForOfStatement<'a>) {
+        self.wrap_body(&it.body, None);
+        walk::walk_for_of_statement(self, it);
+    }
+
+    fn visit_while_statement(&mut self, it: &WhileStatement<'a>) {
+        self.wrap_body(&it.body, None);
+        walk::walk_while_statement(self, it);
+    }
+
+    fn visit_do_while_statement(&mut self, it: &DoWhileStatement<'a>) {
+        self.wrap_body(&it.body, None);
+        walk::walk_do_while_statement(self, it);
+    }
+
+    fn visit_with_statement(&mut self, it: &WithStatement<'a>) {
+        self.wrap_body(&it.body, None);
+        walk::walk_with_statement(self, it);
+    }
+
+    fn visit_ts_module_declaration(&mut self, it: &TSModuleDeclaration<'a>) {
+        // Ambient declarations hold no code
+        if !it.declare {
+            walk::walk_ts_module_declaration(self, it);
+        }
+    }
+}
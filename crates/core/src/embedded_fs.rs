@@ -0,0 +1,211 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use std::{
+    collections::BTreeMap,
+    io,
+    path::{Component, Path, PathBuf},
+    sync::OnceLock,
+};
+
+/// The files embedded in the running single-file executable, if any
+static EMBEDDED_FS: OnceLock<EmbeddedFs> = OnceLock::new();
+
+/// A read-only file system embedded in a single-file executable, holding the
+/// modules of the program and its assets.
+///
+/// Files are stored by their path relative to a root directory, which is the
+/// directory of the executable at runtime, and remote modules by their URL.
+/// Once installed, the module loader and the file system APIs read embedded
+/// files in place of the ones on the disk.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct EmbeddedFs {
+    /// The directory relative paths are resolved against, relative to the root
+    cwd: String,
+    files: BTreeMap<String, Vec<u8>>,
+    root: PathBuf,
+}
+
+impl EmbeddedFs {
+    /// An empty file system whose relative paths resolve against `cwd`, a
+    /// directory relative to its root
+    pub fn new(cwd: &Path) -> Self {
+        Self {
+            cwd: normalize_key(cwd).unwrap_or_default(),
+            ..Self::default()
+        }
+    }
+
+    /// Add a file at a `path` relative to the root, or a remote module by URL
+    pub fn insert(&mut self, path: &str, content: Vec<u8>) {
+        let key = if path.contains("://") {
+            Some(path.to_string())
+        } else {
+            normalize_key(Path::new(path))
+        };
+        if let Some(key) = key {
+            self.files.insert(key, content);
+        }
+    }
+
+    /// Number of embedded files
+    pub fn len(&self) -> usize {
+        self.files.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.files.is_empty()
+    }
+
+    /// Serialize the file system to embed it in an executable
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let size = self
+            .files
+            .iter()
+            .map(|(key, content)| 12 + key.len() + content.len())
+            .sum::<usize>();
+        let mut bytes = Vec::with_capacity(8 + self.cwd.len() + size);
+        write_chunk(&mut bytes, self.cwd.as_bytes());
+        bytes.extend_from_slice(&(self.files.len() as u32).to_le_bytes());
+        for (key, content) in &self.files {
+            write_chunk(&mut bytes, key.as_bytes());
+            bytes.extend_from_slice(&(content.len() as u64).to_le_bytes());
+            bytes.extend_from_slice(content);
+        }
+        bytes
+    }
+
+    /// Deserialize a file system embedded in an executable
+    pub fn from_bytes(mut bytes: &[u8]) -> Option<Self> {
+        let cwd = String::from_utf8(read_chunk(&mut bytes)?.to_vec()).ok()?;
+        let count = u32::from_le_bytes(take(&mut bytes, 4)?.try_into().ok()?);
+        let mut files = BTreeMap::new();
+        for _ in 0..count {
+            let key = String::from_utf8(read_chunk(&mut bytes)?.to_vec()).ok()?;
+            let len = u64::from_le_bytes(take(&mut bytes, 8)?.try_into().ok()?);
+            let content = take(&mut bytes, usize::try_from(len).ok()?)?;
+            files.insert(key, content.to_vec());
+        }
+        Some(Self {
+            cwd,
+            files,
+            root: PathBuf::new(),
+        })
+    }
+
+    /// Make the files readable by the runtime, with `root` as their directory
+    pub fn install(mut self, root: PathBuf) {
+        self.root = root;
+        let _ = EMBEDDED_FS.set(self);
+    }
+
+    /// The content of the embedded file at `path`, which is either relative to
+    /// the current directory of the program or absolute, or of a remote module
+    pub fn get(path: impl AsRef<Path>) -> Option<&'static [u8]> {
+        let fs = EMBEDDED_FS.get()?;
+        fs.files.get(&fs.key(path.as_ref())?).map(Vec::as_slice)
+    }
+
+    /// Whether `path` is an embedded directory, containing embedded files
+    fn is_embedded_dir(path: &Path) -> bool {
+        let Some(fs) = EMBEDDED_FS.get() else {
+            return false;
+        };
+        let Some(key) = fs.key(path) else {
+            return false;
+        };
+        if key.is_empty() {
+            return !fs.files.is_empty();
+        }
+        let prefix = format!("{key}/");
+        fs.files
+            .range(prefix.clone()..)
+            .next()
+            .is_some_and(|(file, _)| file.starts_with(&prefix))
+    }
+
+    /// Read a file, from the embedded files first
+    pub fn read(path: impl AsRef<Path>) -> io::Result<Vec<u8>> {
+        match Self::get(path.as_ref()) {
+            Some(content) => Ok(content.to_vec()),
+            None => std::fs::read(path),
+        }
+    }
+
+    /// Read a text file, from the embedded files first
+    pub fn read_to_string(path: impl AsRef<Path>) -> io::Result<String> {
+        match Self::get(path.as_ref()) {
+            Some(content) => String::from_utf8(content.to_vec())
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e)),
+            None => std::fs::read_to_string(path),
+        }
+    }
+
+    /// Whether `path` is an embedded file or a file on the disk
+    pub fn is_file(path: impl AsRef<Path>) -> bool {
+        let path = path.as_ref();
+        Self::get(path).is_some() || path.is_file()
+    }
+
+    /// Whether `path` is an embedded directory or a directory on the disk
+    pub fn is_dir(path: impl AsRef<Path>) -> bool {
+        let path = path.as_ref();
+        Self::is_embedded_dir(path) || path.is_dir()
+    }
+
+    /// Whether `path` is embedded or exists on the disk
+    pub fn exists(path: impl AsRef<Path>) -> bool {
+        let path = path.as_ref();
+        Self::get(path).is_some() || Self::is_embedded_dir(path) || path.exists()
+    }
+
+    /// The key of an embedded path or URL
+    fn key(&self, path: &Path) -> Option<String> {
+        let path_str = path.to_string_lossy();
+        if path_str.contains("://") {
+            return Some(path_str.to_string());
+        }
+        if path.is_absolute() {
+            normalize_key(path.strip_prefix(&self.root).ok()?)
+        } else {
+            normalize_key(&Path::new(&self.cwd).join(path))
+        }
+    }
+}
+
+/// A relative path with its `.` and `..` components resolved and `/`
+/// separators, or `None` if it leaves its base directory
+fn normalize_key(path: &Path) -> Option<String> {
+    let mut parts: Vec<String> = Vec::new();
+    for component in path.components() {
+        match component {
+            Component::Normal(part) => parts.push(part.to_string_lossy().to_string()),
+            Component::ParentDir => {
+                parts.pop()?;
+            }
+            Component::CurDir => {}
+            Component::RootDir | Component::Prefix(_) => return None,
+        }
+    }
+    Some(parts.join("/"))
+}
+
+fn write_chunk(bytes: &mut Vec<u8>, chunk: &[u8]) {
+    bytes.extend_from_slice(&(chunk.len() as u32).to_le_bytes());
+    bytes.extend_from_slice(chunk);
+}
+
+fn read_chunk<'a>(bytes: &mut &'a [u8]) -> Option<&'a [u8]> {
+    let len = u32::from_le_bytes(take(bytes, 4)?.try_into().ok()?);
+    take(bytes, len as usize)
+}
+
+fn take<'a>(bytes: &mut &'a [u8], len: usize) -> Option<&'a [u8]> {
+    if bytes.len() < len {
+        return None;
+    }
+    let (chunk, rest) = bytes.split_at(len);
+    *bytes = rest;
+    Some(chunk)
+}
@@ -8,6 +8,8 @@ use oxc_diagnostics::OxcDiagnostic;
 use oxc_miette::{Diagnostic, NamedSource, SourceSpan};
 use std::fmt;
 
+use crate::{ModuleSourceMap, line_column, line_column_offset, remap_stack_trace};
+
 /// Comprehensive error type for Andromeda runtime operations
 #[derive(Diagnostic, Debug, Clone)]
 pub enum AndromedaError {
@@ -317,16 +319,34 @@ impl AndromedaError {
         source_path: impl Into<String>,
         location: SourceSpan,
     ) -> Self {
+        let source_code = source_code.into();
+        let source_path = source_path.into();
+        // Point at the original source of code that has a source map embedding it
+        let (source_path, source_code, location) = original_location(
+            &source_path,
+            &source_code,
+            location,
+        )
+        .unwrap_or((source_path, source_code, location));
         Self::RuntimeError {
             message: message.into(),
             location: Some(location),
-            source_code: Some(NamedSource::new(source_path.into(), source_code.into())),
+            source_code: Some(NamedSource::new(source_path, source_code)),
             stack_trace: None,
             variable_context: Vec::new(),
             related_locations: Vec::new(),
         }
     }
 
+    /// Attach the stack trace of a JavaScript error to a runtime error, with its
+    /// locations mapped to the original sources of the modules with source maps
+    pub fn with_stack_trace(mut self, stack: impl Into<String>) -> Self {
+        if let Self::RuntimeError { stack_trace, .. } = &mut self {
+            *stack_trace = Some(remap_stack_trace(&stack.into()));
+        }
+        self
+    }
+
     /// Create a new extension error
     pub fn extension_error(extension_name: impl Into<String>, message: impl Into<String>) -> Self {
         Self::ExtensionError {
@@ -473,6 +493,26 @@ impl fmt::Display for AndromedaError {
 
 impl std::error::Error for AndromedaError {}
 
+/// The path, source and span in the original source of a `location` in the
+/// module at `source_path`, when its source map embeds the original source
+fn original_location(
+    source_path: &str,
+    source_code: &str,
+    location: SourceSpan,
+) -> Option<(String, String, SourceSpan)> {
+    let source_map = ModuleSourceMap::from_module(source_path, source_code)?;
+    let (line, column) = line_column(source_code, location.offset());
+    let position = source_map.original_position(line, column)?;
+    let content = position.content?;
+    let offset = line_column_offset(&content, position.line, position.column);
+    let length = location.len().min(content.len() - offset);
+    Some((
+        position.source,
+        content,
+        SourceSpan::new(offset.into(), length),
+    ))
+}
+
 /// Result type alias for Andromeda operations with boxed errors to reduce stack size
 pub type AndromedaResult<T> = Result<T, Box<AndromedaError>>;
 
@@ -507,6 +547,14 @@ impl ErrorReporter {
         );
         eprintln!("{}", "─".repeat(50).red());
         eprintln!("{:?}", oxc_miette::Report::new(error.clone()));
+        if let AndromedaError::RuntimeError {
+            stack_trace: Some(stack),
+            ..
+        } = error
+            && !stack.is_empty()
+        {
+            eprintln!("{}", stack.dimmed());
+        }
     }
 
     /// Print multiple errors with enhanced formatting
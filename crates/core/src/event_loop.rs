@@ -11,4 +11,6 @@ pub enum MacroTask<UserMacroTask> {
     ResolvePromise(Global<Value<'static>>),
     /// User-defined macro task.
     User(UserMacroTask),
+    /// Stop the event loop, e.g. to restart the runtime when a watched file changed.
+    Terminate,
 }
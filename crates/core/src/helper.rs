@@ -6,6 +6,16 @@ use oxc_diagnostics::OxcDiagnostic;
 
 use crate::{AndromedaError, ErrorReporter};
 
+/// Read a line from standard input without its line terminator.
+/// Returns `None` once the end of the input is reached.
+pub fn read_line() -> std::io::Result<Option<String>> {
+    let mut input = String::new();
+    if std::io::stdin().read_line(&mut input)? == 0 {
+        return Ok(None);
+    }
+    Ok(Some(input.trim_end().to_string()))
+}
+
 /// Initialize enhanced error reporting system
 pub fn init_error_system() {
     ErrorReporter::init();
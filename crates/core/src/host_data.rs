@@ -8,15 +8,16 @@ use std::{
     future::Future,
     sync::{
         Arc,
-        atomic::{AtomicU32, Ordering},
+        atomic::{AtomicBool, AtomicU32, Ordering},
         mpsc::Sender,
     },
 };
 
 use anymap::AnyMap;
+use nova_vm::{ecmascript::types::Object, engine::Global};
 use tokio::task::JoinHandle;
 
-use crate::{AndromedaError, AndromedaResult, MacroTask, TaskId};
+use crate::{AndromedaError, AndromedaResult, MacroTask, Permissions, TaskId};
 
 pub type OpsStorage = AnyMap;
 
@@ -34,17 +35,52 @@ pub struct HostData<UserMacroTask> {
     pub tasks: RefCell<HashMap<TaskId, JoinHandle<()>>>,
     /// Counter of accumulative created async tasks. Used for ID generation.
     pub task_count: Arc<AtomicU32>,
+    /// Permissions consulted by the built-in functions before accessing the host.
+    pub permissions: RefCell<Permissions>,
+    /// Prototype of the errors thrown by denied permission checks, registered by the
+    /// `Andromeda` namespace once it is loaded.
+    pub permission_denied_prototype: RefCell<Option<Global<Object<'static>>>>,
+    /// Set to stop the event loop early, e.g. when a worker is terminated by its parent.
+    /// Shared so it can be set from another thread.
+    pub terminated: Arc<AtomicBool>,
 }
 
 impl<UserMacroTask> HostData<UserMacroTask> {
+    /// Create the host data with every permission granted.
     pub fn new(macro_task_tx: Sender<MacroTask<UserMacroTask>>) -> Self {
+        Self::with_permissions(macro_task_tx, Permissions::allow_all())
+    }
+
+    /// Create the host data with the given [Permissions].
+    pub fn with_permissions(
+        macro_task_tx: Sender<MacroTask<UserMacroTask>>,
+        permissions: Permissions,
+    ) -> Self {
         Self {
             storage: RefCell::new(AnyMap::new()),
             macro_task_tx,
             macro_task_count: Arc::new(AtomicU32::new(0)),
             tasks: RefCell::default(),
             task_count: Arc::default(),
+            permissions: RefCell::new(permissions),
+            permission_denied_prototype: RefCell::default(),
+            terminated: Arc::default(),
+        }
+    }
+
+    /// Whether the event loop was asked to stop.
+    pub fn is_terminated(&self) -> bool {
+        self.terminated.load(Ordering::Acquire)
+    }
+
+    /// Abort every pending macro task, such as timers, crons and sockets, and drop
+    /// the resources held by the extensions so the runtime can be thrown away.
+    pub fn abort_all_macro_tasks(&self) {
+        for (_, task) in self.tasks.borrow_mut().drain() {
+            task.abort();
         }
+        self.macro_task_count.store(0, Ordering::Release);
+        self.storage.borrow_mut().clear();
     }
 
     /// Get an owned senderto the macro tasks event loop.
@@ -2,24 +2,32 @@
 // License, v. 2.0. If a copy of the MPL was not distributed with this
 // file, You can obtain one at https://mozilla.org/MPL/2.0/.
 
+mod coverage;
+mod embedded_fs;
 mod error;
 mod event_loop;
 mod extension;
 mod helper;
 mod host_data;
 mod module;
+mod permissions;
 mod resource_table;
 mod runtime;
+mod source_map;
 mod sync_resource_table;
 mod task;
 
+pub use coverage::*;
+pub use embedded_fs::*;
 pub use error::*;
 pub use event_loop::*;
 pub use extension::*;
 pub use helper::*;
 pub use host_data::*;
 pub use module::*;
+pub use permissions::*;
 pub use resource_table::*;
 pub use runtime::*;
+pub use source_map::*;
 pub use sync_resource_table::*;
 pub use task::*;
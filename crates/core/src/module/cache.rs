@@ -0,0 +1,407 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+use ring::digest;
+use serde::{Deserialize, Serialize};
+
+use super::{ModuleError, ModuleResult};
+
+/// Name of the lockfile written next to the configuration file
+pub const LOCKFILE_NAME: &str = "andromeda.lock";
+
+const LOCKFILE_VERSION: &str = "1";
+
+/// How the remote module cache is used
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum CacheSetting {
+    /// Use the cached modules, downloading the missing ones
+    #[default]
+    Use,
+    /// Download every remote module again
+    Reload,
+    /// Never download, failing on modules that are not cached
+    Only,
+}
+
+/// Lockfile recording the SHA-256 hash of every remote module, so that a
+/// module changing on the server is noticed
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Lockfile {
+    pub version: String,
+    /// Hex SHA-256 hash of the content of every remote module by URL
+    #[serde(default)]
+    pub remote: BTreeMap<String, String>,
+    /// Version resolved for every package requirement, such as `jsr:@std/path@^1`
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub specifiers: BTreeMap<String, String>,
+    #[serde(skip)]
+    path: PathBuf,
+}
+
+impl Lockfile {
+    /// Read the lockfile at `path`, or start an empty one when it doesn't exist
+    pub fn load(path: impl Into<PathBuf>) -> ModuleResult<Self> {
+        let path = path.into();
+        if !path.exists() {
+            return Ok(Self {
+                version: LOCKFILE_VERSION.to_string(),
+                remote: BTreeMap::new(),
+                specifiers: BTreeMap::new(),
+                path,
+            });
+        }
+
+        let content = std::fs::read_to_string(&path).map_err(|e| ModuleError::Io {
+            message: format!("Failed to read lockfile {}: {e}", path.display()),
+        })?;
+        let mut lockfile: Lockfile =
+            serde_json::from_str(&content).map_err(|e| ModuleError::ParseError {
+                path: path.to_string_lossy().to_string(),
+                message: format!("Invalid lockfile: {e}"),
+            })?;
+        lockfile.path = path;
+        Ok(lockfile)
+    }
+
+    /// Path of the lockfile
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Check `hash` against the one recorded for `url`, recording it when there is none
+    fn check(&mut self, url: &str, hash: &str) -> ModuleResult<()> {
+        match self.remote.get(url) {
+            Some(locked) if locked == hash => Ok(()),
+            Some(locked) => Err(ModuleError::IntegrityError {
+                specifier: url.to_string(),
+                message: format!(
+                    "the lockfile {} expects sha256 {locked} but the module has sha256 {hash}",
+                    self.path.display()
+                ),
+            }),
+            None => {
+                self.remote.insert(url.to_string(), hash.to_string());
+                self.save()
+            }
+        }
+    }
+
+    /// Record the version `requirement` resolved to
+    fn lock_version(&mut self, requirement: &str, version: &str) -> ModuleResult<()> {
+        if self.specifiers.get(requirement).map(String::as_str) == Some(version) {
+            return Ok(());
+        }
+        self.specifiers
+            .insert(requirement.to_string(), version.to_string());
+        self.save()
+    }
+
+    fn save(&self) -> ModuleResult<()> {
+        let content = serde_json::to_string_pretty(self).unwrap();
+        std::fs::write(&self.path, format!("{content}\n")).map_err(|e| ModuleError::Io {
+            message: format!("Failed to write lockfile {}: {e}", self.path.display()),
+        })
+    }
+}
+
+/// A remote module and the content type it was served with
+#[derive(Debug, Clone)]
+pub struct RemoteModule {
+    pub content: Vec<u8>,
+    /// `Content-Type` header of the response, if any
+    pub content_type: Option<String>,
+}
+
+/// Content-addressed disk cache of remote modules.
+///
+/// Module contents are stored in `blobs/<sha256>` and `urls/<sha256 of the URL>`
+/// points each URL to its content, so that identical modules are stored once.
+/// Every module is checked against the `integrity` metadata of the import map
+/// and the lockfile, if any.
+pub struct ModuleCache {
+    dir: PathBuf,
+    setting: CacheSetting,
+    integrity: HashMap<String, String>,
+    lockfile: Option<Mutex<Lockfile>>,
+    client: ureq::Agent,
+}
+
+impl ModuleCache {
+    pub fn new(dir: impl Into<PathBuf>, setting: CacheSetting) -> Self {
+        Self {
+            dir: dir.into(),
+            setting,
+            integrity: HashMap::new(),
+            lockfile: None,
+            client: ureq::Agent::new_with_defaults(),
+        }
+    }
+
+    /// The directory of the cache: `$ANDROMEDA_CACHE_DIR`, or `andromeda/deps` in
+    /// the user cache directory
+    pub fn default_dir() -> PathBuf {
+        if let Some(dir) = std::env::var_os("ANDROMEDA_CACHE_DIR") {
+            return PathBuf::from(dir);
+        }
+        let cache_dir = std::env::var_os("XDG_CACHE_HOME")
+            .map(PathBuf::from)
+            .or_else(|| std::env::var_os("LOCALAPPDATA").map(PathBuf::from))
+            .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".cache")))
+            .unwrap_or_else(std::env::temp_dir);
+        cache_dir.join("andromeda").join("deps")
+    }
+
+    /// Verify the modules against Subresource Integrity metadata, by URL
+    pub fn with_integrity(mut self, integrity: HashMap<String, String>) -> Self {
+        self.integrity = integrity;
+        self
+    }
+
+    /// Verify the modules against a lockfile, recording the new ones into it
+    pub fn with_lockfile(mut self, lockfile: Lockfile) -> Self {
+        self.lockfile = Some(Mutex::new(lockfile));
+        self
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    pub fn setting(&self) -> CacheSetting {
+        self.setting
+    }
+
+    /// Whether the module at `url` is in the cache
+    pub fn is_cached(&self, url: &str) -> bool {
+        self.url_path(url).exists()
+    }
+
+    /// Load the remote module at `url`, from the cache unless reloading
+    pub fn load(&self, url: &str) -> ModuleResult<String> {
+        let module = self.load_remote(url)?;
+        String::from_utf8(module.content).map_err(|e| ModuleError::RuntimeError {
+            path: url.to_string(),
+            message: format!("Module is not valid UTF-8: {e}"),
+        })
+    }
+
+    /// Load the content of the remote module at `url` with its content type,
+    /// from the cache unless reloading
+    pub fn load_remote(&self, url: &str) -> ModuleResult<RemoteModule> {
+        let cached = match self.setting {
+            CacheSetting::Reload => None,
+            CacheSetting::Use | CacheSetting::Only => self.read_cached(url),
+        };
+        let (module, fetched) = match cached {
+            Some(module) => (module, false),
+            None if self.setting == CacheSetting::Only => {
+                return Err(ModuleError::NotFound {
+                    specifier: format!(
+                        "{url} is not cached, run `andromeda cache` or remove --cached-only"
+                    ),
+                });
+            }
+            None => (self.fetch(url)?, true),
+        };
+
+        let hash = sha256_hex(&module.content);
+        self.verify(url, &module.content, &hash)?;
+        if fetched {
+            self.write_cached(url, &module, &hash)?;
+        }
+        Ok(module)
+    }
+
+    /// Load registry metadata such as the versions of a package, which is not
+    /// verified as it changes when packages are published. `refresh` downloads
+    /// it again, unless only cached modules are used.
+    pub fn load_metadata(&self, url: &str, refresh: bool) -> ModuleResult<String> {
+        let cached = match self.setting {
+            CacheSetting::Use if !refresh => self.read_cached(url),
+            CacheSetting::Use | CacheSetting::Reload => None,
+            CacheSetting::Only => self.read_cached(url),
+        };
+        let content = match cached {
+            Some(module) => module.content,
+            None if self.setting == CacheSetting::Only => {
+                return Err(ModuleError::NotFound {
+                    specifier: format!(
+                        "{url} is not cached, run `andromeda cache` or remove --cached-only"
+                    ),
+                });
+            }
+            None => {
+                let module = self.fetch(url)?;
+                self.write_cached(url, &module, &sha256_hex(&module.content))?;
+                module.content
+            }
+        };
+
+        String::from_utf8(content).map_err(|e| ModuleError::RuntimeError {
+            path: url.to_string(),
+            message: format!("Metadata is not valid UTF-8: {e}"),
+        })
+    }
+
+    /// Version of a package `requirement` such as `jsr:@std/path@^1` recorded
+    /// in the lockfile
+    pub fn locked_version(&self, requirement: &str) -> Option<String> {
+        let lockfile = self.lockfile.as_ref()?.lock().unwrap();
+        lockfile.specifiers.get(requirement).cloned()
+    }
+
+    /// Record the version a package `requirement` resolved to in the lockfile
+    pub fn lock_version(&self, requirement: &str, version: &str) -> ModuleResult<()> {
+        match &self.lockfile {
+            Some(lockfile) => lockfile.lock().unwrap().lock_version(requirement, version),
+            None => Ok(()),
+        }
+    }
+
+    fn fetch(&self, url: &str) -> ModuleResult<RemoteModule> {
+        // Local mirrors of registries are read directly
+        if let Some(path) = url::Url::parse(url)
+            .ok()
+            .filter(|url| url.scheme() == "file")
+            .and_then(|url| url.to_file_path().ok())
+        {
+            let content = std::fs::read(&path).map_err(|e| ModuleError::NotFound {
+                specifier: format!("{url}: {e}"),
+            })?;
+            return Ok(RemoteModule {
+                content,
+                content_type: None,
+            });
+        }
+        let mut response = self
+            .client
+            .get(url)
+            .call()
+            .map_err(|e| ModuleError::NotFound {
+                specifier: format!("{url}: {e}"),
+            })?;
+        let content_type = response
+            .headers()
+            .get("content-type")
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_string);
+        let content = response
+            .body_mut()
+            .read_to_vec()
+            .map_err(|e| ModuleError::RuntimeError {
+                path: url.to_string(),
+                message: e.to_string(),
+            })?;
+        Ok(RemoteModule {
+            content,
+            content_type,
+        })
+    }
+
+    fn verify(&self, url: &str, content: &[u8], hash: &str) -> ModuleResult<()> {
+        if let Some(metadata) = self.integrity.get(url) {
+            check_integrity(url, metadata, content)?;
+        }
+        if let Some(lockfile) = &self.lockfile {
+            lockfile.lock().unwrap().check(url, hash)?;
+        }
+        Ok(())
+    }
+
+    fn url_path(&self, url: &str) -> PathBuf {
+        self.dir.join("urls").join(sha256_hex(url.as_bytes()))
+    }
+
+    fn blob_path(&self, hash: &str) -> PathBuf {
+        self.dir.join("blobs").join(hash)
+    }
+
+    fn read_cached(&self, url: &str) -> Option<RemoteModule> {
+        let entry = std::fs::read_to_string(self.url_path(url)).ok()?;
+        let entry: CacheEntry = serde_json::from_str(&entry).ok()?;
+        let content = std::fs::read(self.blob_path(&entry.hash)).ok()?;
+        // A corrupted blob is downloaded again
+        (sha256_hex(&content) == entry.hash).then_some(RemoteModule {
+            content,
+            content_type: entry.content_type,
+        })
+    }
+
+    fn write_cached(&self, url: &str, module: &RemoteModule, hash: &str) -> ModuleResult<()> {
+        let io_error = |e: std::io::Error| ModuleError::Io {
+            message: format!("Failed to write {url} to the cache: {e}"),
+        };
+
+        let blob_path = self.blob_path(hash);
+        if !blob_path.exists() {
+            std::fs::create_dir_all(self.dir.join("blobs")).map_err(io_error)?;
+            std::fs::write(&blob_path, &module.content).map_err(io_error)?;
+        }
+        let entry = serde_json::to_string(&CacheEntry {
+            url: url.to_string(),
+            hash: hash.to_string(),
+            content_type: module.content_type.clone(),
+        })
+        .unwrap();
+        std::fs::create_dir_all(self.dir.join("urls")).map_err(io_error)?;
+        std::fs::write(self.url_path(url), entry).map_err(io_error)
+    }
+}
+
+/// Entry of the cache pointing a URL to the hash of its content
+#[derive(Serialize, Deserialize)]
+struct CacheEntry {
+    url: String,
+    hash: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    content_type: Option<String>,
+}
+
+pub(crate) fn sha256_hex(content: &[u8]) -> String {
+    digest::digest(&digest::SHA256, content)
+        .as_ref()
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect()
+}
+
+/// Check `content` against Subresource Integrity metadata such as `sha384-<base64>`,
+/// which matches when any of its hashes does
+fn check_integrity(url: &str, metadata: &str, content: &[u8]) -> ModuleResult<()> {
+    let mut supported = false;
+    for hash in metadata.split_whitespace() {
+        let Some((algorithm, expected)) = hash.split_once('-') else {
+            continue;
+        };
+        let algorithm = match algorithm {
+            "sha256" => &digest::SHA256,
+            "sha384" => &digest::SHA384,
+            "sha512" => &digest::SHA512,
+            _ => continue,
+        };
+        supported = true;
+        // Options such as `sha384-<base64>?foo` are ignored
+        let expected = expected.split('?').next().unwrap_or_default();
+        let actual =
+            base64_simd::STANDARD.encode_to_string(digest::digest(algorithm, content).as_ref());
+        if actual == expected {
+            return Ok(());
+        }
+    }
+
+    Err(ModuleError::IntegrityError {
+        specifier: url.to_string(),
+        message: if supported {
+            format!("the content does not match the integrity metadata {metadata}")
+        } else {
+            format!("unsupported integrity metadata {metadata}")
+        },
+    })
+}
@@ -0,0 +1,405 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use std::{
+    collections::{BTreeSet, HashSet, VecDeque},
+    path::{Path, PathBuf},
+};
+
+use oxc_allocator::Allocator;
+use oxc_ast::ast;
+use oxc_ast_visit::{Visit, walk};
+use oxc_parser::Parser;
+use oxc_span::SourceType;
+
+use super::NpmResolver;
+use crate::EmbeddedFs;
+
+/// Defines `globalThis.__andromeda_commonjs`, the registry of the CommonJS
+/// modules shared by every wrapped module. `require()` runs the modules it
+/// defines synchronously and caches them by filename, so that a module required
+/// during its own evaluation returns its partially filled `module.exports`.
+const COMMONJS_RUNTIME: &str = r#"globalThis.__andromeda_commonjs ??= (() => {
+  const records = new Map();
+  const cache = Object.create(null);
+  function notFound(specifier, referrer) {
+    const error = new Error(`Cannot find module '${specifier}' from ${referrer}`);
+    error.code = "MODULE_NOT_FOUND";
+    return error;
+  }
+  function load(filename) {
+    if (Object.hasOwn(cache, filename)) return cache[filename].exports;
+    const record = records.get(filename);
+    if (!record) throw notFound(filename, filename);
+    if (record.namespace) return record.namespace;
+    const module = { id: filename, filename, path: record.dirname, exports: {}, loaded: false };
+    const resolve = (specifier) => {
+      if (Object.hasOwn(record.requires, specifier)) return record.requires[specifier];
+      throw notFound(specifier, filename);
+    };
+    const require = (specifier) => load(resolve(String(specifier)));
+    require.resolve = resolve;
+    require.cache = cache;
+    module.require = require;
+    cache[filename] = module;
+    try {
+      record.factory.call(module.exports, module.exports, require, module, filename, record.dirname);
+    } catch (error) {
+      delete cache[filename];
+      throw error;
+    }
+    module.loaded = true;
+    return module.exports;
+  }
+  return {
+    define(filename, dirname, requires, factory) {
+      if (!records.has(filename)) records.set(filename, { dirname, requires, factory });
+    },
+    defineNamespace(filename, namespace) {
+      if (!records.has(filename)) records.set(filename, { namespace });
+    },
+    require: load,
+  };
+})();"#;
+
+/// Wrap the CommonJS module at `path` into an ES module whose default export is
+/// `module.exports`, and whose named exports are the ones found by
+/// [`commonjs_exports`].
+///
+/// The CommonJS modules and JSON files it requires, directly or not, are
+/// bundled into it so that `require()` can run them synchronously, with the
+/// usual caching and cycle semantics. The ES modules they require are imported
+/// ahead of it and `require()` returns their namespace. The source stays on its
+/// original lines.
+pub fn wrap_commonjs(resolver: &NpmResolver, path: &str, source: &str) -> String {
+    let mut imports = String::new();
+    // The wrapped module comes first, to stay on its original lines
+    let mut defines = Vec::new();
+    let mut json_defines = Vec::new();
+    let mut seen = HashSet::from([absolute(Path::new(path))]);
+    let mut queue = VecDeque::from([(absolute(Path::new(path)), source.to_string())]);
+
+    while let Some((filename, source)) = queue.pop_front() {
+        let mut requires = Vec::new();
+        for specifier in commonjs_requires(&source) {
+            // Requires that can't be resolved only fail when they run, as they
+            // are often optional
+            let Ok(resolved) = resolver.resolve_require(&specifier, &filename) else {
+                continue;
+            };
+            let resolved = absolute(&resolved);
+            requires.push(format!(
+                "{}: {}",
+                json_string(&specifier),
+                json_string(&resolved.to_string_lossy())
+            ));
+            if !seen.insert(resolved.clone()) {
+                continue;
+            }
+
+            let is_json = resolved.extension().is_some_and(|ext| ext == "json");
+            if resolver.is_commonjs(&resolved) || is_json {
+                let Ok(source) = EmbeddedFs::read_to_string(&resolved) else {
+                    continue;
+                };
+                if is_json {
+                    json_defines.push(define_module(
+                        &resolved,
+                        &[],
+                        &format!("module.exports = {};", source.trim()),
+                    ));
+                } else {
+                    queue.push_back((resolved, source));
+                }
+            } else {
+                let binding = format!("__andromeda_require_{}", seen.len());
+                let filename = json_string(&resolved.to_string_lossy());
+                imports.push_str(&format!(
+                    "import * as {binding} from {filename};\
+                     __andromeda_commonjs.defineNamespace({filename}, {binding});"
+                ));
+            }
+        }
+        defines.push(define_module(&filename, &requires, &source));
+    }
+
+    defines.extend(json_defines);
+    let filename = json_string(&absolute(Path::new(path)).to_string_lossy());
+    let mut wrapped = format!(
+        "{}{imports}{}\n\
+         const __andromeda_exports = __andromeda_commonjs.require({filename});\n\
+         export default __andromeda_exports;\n",
+        COMMONJS_RUNTIME.replace('\n', " "),
+        defines.join("\n"),
+    );
+    for (index, name) in commonjs_exports_of(resolver, path, source)
+        .iter()
+        .enumerate()
+    {
+        wrapped.push_str(&format!(
+            "const __andromeda_export_{index} = __andromeda_exports[{}];\n\
+             export {{ __andromeda_export_{index} as {name} }};\n",
+            json_string(name)
+        ));
+    }
+    wrapped
+}
+
+/// The CommonJS and JSON files that `wrap_commonjs` bundles into the wrapper
+/// of the CommonJS module at `path`, for `require()` to run them
+pub fn commonjs_files(resolver: &NpmResolver, path: &str, source: &str) -> Vec<PathBuf> {
+    let mut files = Vec::new();
+    let mut seen = HashSet::from([absolute(Path::new(path))]);
+    let mut queue = VecDeque::from([(absolute(Path::new(path)), source.to_string())]);
+
+    while let Some((filename, source)) = queue.pop_front() {
+        for specifier in commonjs_requires(&source) {
+            let Ok(resolved) = resolver.resolve_require(&specifier, &filename) else {
+                continue;
+            };
+            let resolved = absolute(&resolved);
+            if !seen.insert(resolved.clone()) {
+                continue;
+            }
+            // The ES modules it requires are imported by the wrapper instead
+            let is_json = resolved.extension().is_some_and(|ext| ext == "json");
+            if !resolver.is_commonjs(&resolved) && !is_json {
+                continue;
+            }
+            let Ok(source) = EmbeddedFs::read_to_string(&resolved) else {
+                continue;
+            };
+            if !is_json {
+                queue.push_back((resolved.clone(), source));
+            }
+            files.push(resolved);
+        }
+    }
+    files
+}
+
+/// Define the module `filename` in the CommonJS registry, with the resolved
+/// paths of its `require()` specifiers
+fn define_module(filename: &Path, requires: &[String], source: &str) -> String {
+    let dirname = filename.parent().unwrap_or(Path::new("/"));
+    // A shebang is only valid at the start of the file
+    let source = match source.strip_prefix("#!") {
+        Some(rest) => format!("//{rest}"),
+        None => source.to_string(),
+    };
+    format!(
+        "__andromeda_commonjs.define({}, {}, {{ {} }}, \
+         function (exports, require, module, __filename, __dirname) {{{source}\n}});",
+        json_string(&filename.to_string_lossy()),
+        json_string(&dirname.to_string_lossy()),
+        requires.join(", "),
+    )
+}
+
+/// The named exports of the CommonJS module at `path`, including the ones it
+/// re-exports from the modules it requires. Names that can't be exported by
+/// the wrapping ES module, such as `default`, are left out.
+fn commonjs_exports_of(resolver: &NpmResolver, path: &str, source: &str) -> BTreeSet<String> {
+    let mut names = BTreeSet::new();
+    let mut seen = HashSet::new();
+    let mut queue = VecDeque::from([(PathBuf::from(path), source.to_string())]);
+
+    while let Some((path, source)) = queue.pop_front() {
+        if !seen.insert(absolute(&path)) {
+            continue;
+        }
+        let exports = commonjs_exports(&source);
+        names.extend(exports.names);
+        for specifier in exports.reexports {
+            if let Ok(resolved) = resolver.resolve_require(&specifier, &path)
+                && resolver.is_commonjs(&resolved)
+                && let Ok(source) = EmbeddedFs::read_to_string(&resolved)
+            {
+                queue.push_back((resolved, source));
+            }
+        }
+    }
+
+    names.retain(|name| name != "default" && is_identifier_name(name));
+    names
+}
+
+/// Exports of a CommonJS module found by static analysis
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct CommonJsExports {
+    /// Names assigned on `exports` or `module.exports`
+    pub names: Vec<String>,
+    /// Specifiers of the modules whose exports are re-exported, as in
+    /// `module.exports = require("./other")`
+    pub reexports: Vec<String>,
+}
+
+/// Detect the named exports of a CommonJS module: the properties assigned to or
+/// defined on `exports` and `module.exports`, and the keys of an object literal
+/// assigned to `module.exports`
+pub fn commonjs_exports(source: &str) -> CommonJsExports {
+    let allocator = Allocator::default();
+    let ret = Parser::new(&allocator, source, SourceType::cjs()).parse();
+    let mut collector = ExportCollector::default();
+    collector.visit_program(&ret.program);
+    collector.exports
+}
+
+/// The string literal specifiers passed to `require()` in a CommonJS module
+pub fn commonjs_requires(source: &str) -> Vec<String> {
+    let allocator = Allocator::default();
+    let ret = Parser::new(&allocator, source, SourceType::cjs()).parse();
+    let mut collector = RequireCollector::default();
+    collector.visit_program(&ret.program);
+    collector.specifiers
+}
+
+#[derive(Default)]
+struct RequireCollector {
+    specifiers: Vec<String>,
+}
+
+impl<'a> Visit<'a> for RequireCollector {
+    fn visit_call_expression(&mut self, call: &ast::CallExpression<'a>) {
+        if let Some(specifier) = require_specifier(call)
+            && !self.specifiers.iter().any(|s| s == specifier)
+        {
+            self.specifiers.push(specifier.to_string());
+        }
+        walk::walk_call_expression(self, call);
+    }
+}
+
+#[derive(Default)]
+struct ExportCollector {
+    exports: CommonJsExports,
+}
+
+impl ExportCollector {
+    fn add_name(&mut self, name: &str) {
+        if !self.exports.names.iter().any(|n| n == name) {
+            self.exports.names.push(name.to_string());
+        }
+    }
+
+    fn add_reexport(&mut self, expr: &ast::Expression) {
+        if let ast::Expression::CallExpression(call) = expr
+            && let Some(specifier) = require_specifier(call)
+        {
+            self.exports.reexports.push(specifier.to_string());
+        }
+    }
+}
+
+impl<'a> Visit<'a> for ExportCollector {
+    fn visit_assignment_expression(&mut self, expr: &ast::AssignmentExpression<'a>) {
+        match &expr.left {
+            // exports.name = ... and module.exports.name = ...
+            ast::AssignmentTarget::StaticMemberExpression(member)
+                if is_exports_object(&member.object) =>
+            {
+                self.add_name(member.property.name.as_str());
+            }
+            // exports["name"] = ...
+            ast::AssignmentTarget::ComputedMemberExpression(member)
+                if is_exports_object(&member.object) =>
+            {
+                if let ast::Expression::StringLiteral(literal) = &member.expression {
+                    self.add_name(literal.value.as_str());
+                }
+            }
+            // module.exports = ...
+            ast::AssignmentTarget::StaticMemberExpression(member)
+                if is_module_exports(&member.object, &member.property) =>
+            {
+                match &expr.right {
+                    ast::Expression::ObjectExpression(object) => {
+                        for property in &object.properties {
+                            match property {
+                                ast::ObjectPropertyKind::ObjectProperty(property) => {
+                                    match &property.key {
+                                        ast::PropertyKey::StaticIdentifier(id) => {
+                                            self.add_name(id.name.as_str());
+                                        }
+                                        ast::PropertyKey::StringLiteral(literal) => {
+                                            self.add_name(literal.value.as_str());
+                                        }
+                                        _ => {}
+                                    }
+                                }
+                                ast::ObjectPropertyKind::SpreadProperty(spread) => {
+                                    self.add_reexport(&spread.argument);
+                                }
+                            }
+                        }
+                    }
+                    right => self.add_reexport(right),
+                }
+            }
+            _ => {}
+        }
+        walk::walk_assignment_expression(self, expr);
+    }
+
+    fn visit_call_expression(&mut self, call: &ast::CallExpression<'a>) {
+        // Object.defineProperty(exports, "name", ...)
+        if let ast::Expression::StaticMemberExpression(callee) = &call.callee
+            && let ast::Expression::Identifier(object) = &callee.object
+            && object.name == "Object"
+            && callee.property.name == "defineProperty"
+            && let [target, ast::Argument::StringLiteral(name), ..] = call.arguments.as_slice()
+            && let Some(target) = target.as_expression()
+            && is_exports_object(target)
+        {
+            self.add_name(name.value.as_str());
+        }
+        walk::walk_call_expression(self, call);
+    }
+}
+
+/// The specifier of a `require("specifier")` call
+fn require_specifier<'a>(call: &'a ast::CallExpression) -> Option<&'a str> {
+    if let ast::Expression::Identifier(callee) = &call.callee
+        && callee.name == "require"
+        && let [ast::Argument::StringLiteral(literal)] = call.arguments.as_slice()
+    {
+        Some(literal.value.as_str())
+    } else {
+        None
+    }
+}
+
+/// Whether `expr` is `exports` or `module.exports`
+fn is_exports_object(expr: &ast::Expression) -> bool {
+    match expr {
+        ast::Expression::Identifier(id) => id.name == "exports",
+        ast::Expression::StaticMemberExpression(member) => {
+            is_module_exports(&member.object, &member.property)
+        }
+        _ => false,
+    }
+}
+
+/// Whether `object.property` is `module.exports`
+fn is_module_exports(object: &ast::Expression, property: &ast::IdentifierName) -> bool {
+    matches!(object, ast::Expression::Identifier(id) if id.name == "module")
+        && property.name == "exports"
+}
+
+/// Whether `name` can be written as is after `export { binding as ... }`
+fn is_identifier_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_alphabetic() || c == '_' || c == '$')
+        && chars.all(|c| c.is_alphanumeric() || c == '_' || c == '$')
+}
+
+fn absolute(path: &Path) -> PathBuf {
+    std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf())
+}
+
+fn json_string(value: &str) -> String {
+    serde_json::to_string(value).unwrap()
+}
@@ -0,0 +1,148 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use std::{
+    collections::{HashSet, VecDeque},
+    path::Path,
+};
+
+use oxc_allocator::Allocator;
+use oxc_ast::ast;
+use oxc_ast_visit::{Visit, walk};
+use oxc_parser::Parser;
+use oxc_span::SourceType;
+
+use super::{DependencyGraph, ModuleError, ModuleResolver, ModuleResult, commonjs_requires};
+
+/// A module found while walking a module graph
+#[derive(Debug, Clone)]
+pub struct GraphModule {
+    /// Resolved path or URL of the module
+    pub specifier: String,
+    pub source: String,
+}
+
+/// The specifiers a module imports: its static imports and re-exports, and its
+/// dynamic imports of string literals. Type-only imports are left out, as they
+/// are never loaded.
+pub fn module_imports(specifier: &str, source: &str) -> Vec<String> {
+    let path = module_path(specifier);
+    let source_type = SourceType::from_path(&path)
+        .unwrap_or_else(|_| SourceType::mjs())
+        .with_module(true);
+
+    let allocator = Allocator::default();
+    let ret = Parser::new(&allocator, source, source_type).parse();
+    let mut collector = ImportCollector::default();
+    collector.visit_program(&ret.program);
+    collector.specifiers
+}
+
+/// Load `entries` and every module they import, calling `load` once per module.
+/// The import edges are added to `graph` and the modules are returned in the
+/// order they were loaded.
+pub fn walk_module_graph(
+    entries: &[String],
+    resolver: &ModuleResolver,
+    graph: &mut DependencyGraph,
+    mut load: impl FnMut(&str) -> ModuleResult<String>,
+) -> ModuleResult<Vec<GraphModule>> {
+    let mut seen: HashSet<String> = entries.iter().cloned().collect();
+    let mut queue: VecDeque<String> = entries.iter().cloned().collect();
+    let mut modules = Vec::new();
+
+    while let Some(specifier) = queue.pop_front() {
+        graph.add_module(&specifier);
+        let source = load(&specifier)?;
+
+        // JSON, text and bytes modules don't import anything
+        if is_script(&specifier) {
+            for import in module_imports(&specifier, &source) {
+                let resolved = resolver.resolve(&import, &specifier).map_err(|resolved| {
+                    ModuleError::NotFound {
+                        specifier: format!("{resolved} imported by {specifier}"),
+                    }
+                })?;
+                graph.add_dependency(&specifier, &resolved);
+                if seen.insert(resolved.clone()) {
+                    queue.push_back(resolved);
+                }
+            }
+        }
+
+        // The modules required by CommonJS modules, which may be optional
+        if !specifier.contains("://") && resolver.npm.is_commonjs(Path::new(&specifier)) {
+            for require in commonjs_requires(&source) {
+                let Ok(resolved) = resolver
+                    .npm
+                    .resolve_require(&require, Path::new(&specifier))
+                else {
+                    continue;
+                };
+                let resolved = resolved.to_string_lossy().to_string();
+                graph.add_dependency(&specifier, &resolved);
+                if seen.insert(resolved.clone()) {
+                    queue.push_back(resolved);
+                }
+            }
+        }
+
+        modules.push(GraphModule { specifier, source });
+    }
+
+    Ok(modules)
+}
+
+/// The path of a module, or of its URL
+fn module_path(specifier: &str) -> String {
+    if specifier.contains("://") {
+        url::Url::parse(specifier)
+            .map(|url| url.path().to_string())
+            .unwrap_or_default()
+    } else {
+        specifier.to_string()
+    }
+}
+
+/// Whether the module at `specifier` is JavaScript or TypeScript, which is
+/// assumed for extensionless URLs
+fn is_script(specifier: &str) -> bool {
+    let path = module_path(specifier);
+    Path::new(&path).extension().is_none() || SourceType::from_path(&path).is_ok()
+}
+
+#[derive(Default)]
+struct ImportCollector {
+    specifiers: Vec<String>,
+}
+
+impl<'a> Visit<'a> for ImportCollector {
+    fn visit_import_declaration(&mut self, decl: &ast::ImportDeclaration<'a>) {
+        if !decl.import_kind.is_type() {
+            self.specifiers.push(decl.source.value.to_string());
+        }
+    }
+
+    fn visit_export_all_declaration(&mut self, decl: &ast::ExportAllDeclaration<'a>) {
+        if !decl.export_kind.is_type() {
+            self.specifiers.push(decl.source.value.to_string());
+        }
+    }
+
+    fn visit_export_named_declaration(&mut self, decl: &ast::ExportNamedDeclaration<'a>) {
+        if let Some(source) = &decl.source
+            && !decl.export_kind.is_type()
+        {
+            self.specifiers.push(source.value.to_string());
+        }
+        walk::walk_export_named_declaration(self, decl);
+    }
+
+    fn visit_import_expression(&mut self, expr: &ast::ImportExpression<'a>) {
+        if let ast::Expression::StringLiteral(literal) = &expr.source {
+            self.specifiers.push(literal.value.to_string());
+        }
+        walk::walk_import_expression(self, expr);
+    }
+}
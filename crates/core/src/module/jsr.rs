@@ -0,0 +1,267 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::{Arc, Mutex},
+};
+
+use semver::Version;
+use serde::Deserialize;
+use url::Url;
+
+use super::{
+    CacheSetting, ModuleCache, ModuleError, ModuleLoader, ModuleResult, PackageSpecifier, satisfies,
+};
+
+/// The public JSR registry
+pub const DEFAULT_JSR_URL: &str = "https://jsr.io/";
+
+/// `meta.json` of a package, listing its versions
+#[derive(Debug, Deserialize)]
+struct PackageMeta {
+    #[serde(default)]
+    versions: HashMap<String, VersionInfo>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct VersionInfo {
+    yanked: bool,
+}
+
+/// `<version>_meta.json` of a package, mapping its exports to its files
+#[derive(Debug, Deserialize)]
+struct VersionMeta {
+    #[serde(default)]
+    exports: BTreeMap<String, String>,
+}
+
+/// Resolves `jsr:@scope/name@range/export` specifiers into the URLs of the
+/// modules of a JSR registry.
+///
+/// Version ranges are resolved from the `meta.json` of the package, and the
+/// exports from the `<version>_meta.json` of the version. Both are kept in the
+/// module cache, and the resolved versions are recorded in its lockfile.
+#[derive(Clone)]
+pub struct JsrResolver {
+    registry_url: Url,
+    cache: Arc<ModuleCache>,
+    /// Versions resolved during this run, by requirement
+    versions: Arc<Mutex<HashMap<String, String>>>,
+}
+
+impl std::fmt::Debug for JsrResolver {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("JsrResolver")
+            .field("registry_url", &self.registry_url.as_str())
+            .finish_non_exhaustive()
+    }
+}
+
+impl JsrResolver {
+    pub fn new(registry_url: Url, cache: Arc<ModuleCache>) -> Self {
+        Self {
+            registry_url,
+            cache,
+            versions: Arc::default(),
+        }
+    }
+
+    /// The registry URL: `$JSR_URL`, or the public JSR registry. `file://` URLs
+    /// point to a local mirror.
+    pub fn default_registry_url() -> Url {
+        std::env::var("JSR_URL")
+            .ok()
+            .and_then(|url| {
+                // Without a trailing slash joining would drop the last segment
+                let url = if url.ends_with('/') {
+                    url
+                } else {
+                    format!("{url}/")
+                };
+                Url::parse(&url).ok()
+            })
+            .unwrap_or_else(|| Url::parse(DEFAULT_JSR_URL).unwrap())
+    }
+
+    pub fn registry_url(&self) -> &Url {
+        &self.registry_url
+    }
+
+    /// Whether `url` is the URL of a module of the registry
+    pub fn is_registry_url(&self, url: &str) -> bool {
+        url.starts_with(self.registry_url.as_str())
+    }
+
+    /// Resolve a `jsr:` specifier into the URL of a module of the registry
+    pub fn resolve(&self, specifier: &str) -> ModuleResult<String> {
+        let package = PackageSpecifier::parse(specifier)
+            .filter(|package| package.name.starts_with('@'))
+            .ok_or_else(|| ModuleError::InvalidSpecifier {
+                specifier: format!("{specifier} (JSR packages are named @scope/name)"),
+            })?;
+        let version = self.resolve_version(&package.name, package.range.as_deref())?;
+
+        let meta_url = self.package_url(&package.name, &format!("{version}_meta.json"))?;
+        let meta = self.cache.load_metadata(meta_url.as_str(), false)?;
+        let meta: VersionMeta =
+            serde_json::from_str(&meta).map_err(|e| ModuleError::ParseError {
+                path: meta_url.to_string(),
+                message: format!("Invalid version metadata: {e}"),
+            })?;
+        let path = meta
+            .exports
+            .get(&package.subpath)
+            .ok_or_else(|| ModuleError::NotFound {
+                specifier: format!(
+                    "{specifier} ({} is not exported by {}@{version})",
+                    package.subpath, package.name
+                ),
+            })?;
+
+        let path = path.trim_start_matches("./").trim_start_matches('/');
+        let url = self.package_url(&package.name, &format!("{version}/{path}"))?;
+        // The modules of a local mirror are loaded as files
+        match url.to_file_path() {
+            Ok(path) if url.scheme() == "file" => Ok(path.to_string_lossy().to_string()),
+            _ => Ok(url.to_string()),
+        }
+    }
+
+    /// The highest version of package `name` that isn't yanked and satisfies
+    /// `range`, or the one recorded in the lockfile
+    fn resolve_version(&self, name: &str, range: Option<&str>) -> ModuleResult<String> {
+        let requirement = format!("jsr:{name}@{}", range.unwrap_or("*"));
+        if let Some(version) = self.versions.lock().unwrap().get(&requirement) {
+            return Ok(version.clone());
+        }
+        if let Some(version) = self.cache.locked_version(&requirement) {
+            self.versions
+                .lock()
+                .unwrap()
+                .insert(requirement, version.clone());
+            return Ok(version);
+        }
+
+        let meta_url = self.package_url(name, "meta.json")?;
+        let mut version = self.find_version(&meta_url, range, false)?;
+        // The cached metadata may predate the version
+        if version.is_none() && self.cache.setting() == CacheSetting::Use {
+            version = self.find_version(&meta_url, range, true)?;
+        }
+        let version = version.ok_or_else(|| ModuleError::NotFound {
+            specifier: format!("{requirement} (no matching version in {meta_url})"),
+        })?;
+
+        self.cache.lock_version(&requirement, &version)?;
+        self.versions
+            .lock()
+            .unwrap()
+            .insert(requirement, version.clone());
+        Ok(version)
+    }
+
+    fn find_version(
+        &self,
+        meta_url: &Url,
+        range: Option<&str>,
+        refresh: bool,
+    ) -> ModuleResult<Option<String>> {
+        let meta = self.cache.load_metadata(meta_url.as_str(), refresh)?;
+        let meta: PackageMeta =
+            serde_json::from_str(&meta).map_err(|e| ModuleError::ParseError {
+                path: meta_url.to_string(),
+                message: format!("Invalid package metadata: {e}"),
+            })?;
+
+        Ok(meta
+            .versions
+            .into_iter()
+            .filter(|(version, info)| {
+                !info.yanked && range.is_none_or(|range| satisfies(version, range))
+            })
+            .filter_map(|(version, _)| Version::parse(&version).ok())
+            // Pre-releases are only used when asked for
+            .filter(|version| range.is_some() || version.pre.is_empty())
+            .max()
+            .map(|version| version.to_string()))
+    }
+
+    fn package_url(&self, name: &str, path: &str) -> ModuleResult<Url> {
+        self.registry_url
+            .join(&format!("{name}/{path}"))
+            .map_err(|e| ModuleError::ResolutionError {
+                message: format!("Invalid JSR URL for {name}/{path}: {e}"),
+            })
+    }
+}
+
+/// Module loader for `jsr:` specifiers, loading the modules of the registry
+/// through the module cache
+pub struct JsrModuleLoader {
+    resolver: JsrResolver,
+}
+
+impl JsrModuleLoader {
+    pub fn new(resolver: JsrResolver) -> Self {
+        Self { resolver }
+    }
+}
+
+impl Default for JsrModuleLoader {
+    fn default() -> Self {
+        let cache = ModuleCache::new(ModuleCache::default_dir(), CacheSetting::Use);
+        Self::new(JsrResolver::new(
+            JsrResolver::default_registry_url(),
+            Arc::new(cache),
+        ))
+    }
+}
+
+#[hotpath::measure_all]
+impl ModuleLoader for JsrModuleLoader {
+    fn load_module(&self, specifier: &str) -> ModuleResult<String> {
+        let url = if specifier.starts_with("jsr:") {
+            self.resolver.resolve(specifier)?
+        } else if self.resolver.is_registry_url(specifier) {
+            specifier.to_string()
+        } else {
+            return Err(ModuleError::NotFound {
+                specifier: specifier.to_string(),
+            });
+        };
+        self.resolver.cache.load(&url)
+    }
+
+    fn resolve_specifier(&self, specifier: &str, base: Option<&str>) -> ModuleResult<String> {
+        if specifier.starts_with("jsr:") {
+            return self.resolver.resolve(specifier);
+        }
+        // Modules of the registry import each other relatively
+        match base {
+            Some(base) if self.resolver.is_registry_url(base) => {
+                let base = Url::parse(base).map_err(|e| ModuleError::ResolutionError {
+                    message: format!("Invalid base URL {base}: {e}"),
+                })?;
+                base.join(specifier)
+                    .map(|url| url.to_string())
+                    .map_err(|e| ModuleError::ResolutionError {
+                        message: format!("Failed to resolve {specifier} relative to {base}: {e}"),
+                    })
+            }
+            _ => Err(ModuleError::ResolutionError {
+                message: format!("JSR loader only resolves jsr: specifiers, got: {specifier}"),
+            }),
+        }
+    }
+
+    fn module_exists(&self, specifier: &str) -> bool {
+        self.resolve_specifier(specifier, None).is_ok()
+    }
+
+    fn supported_extensions(&self) -> Vec<&'static str> {
+        vec!["ts", "tsx", "js", "mjs", "json"]
+    }
+}
@@ -3,7 +3,7 @@
 // file, You can obtain one at https://mozilla.org/MPL/2.0/.
 
 use std::{
-    collections::{HashMap, HashSet, VecDeque},
+    collections::{BTreeSet, HashMap, HashSet, VecDeque},
     path::{Path, PathBuf},
     sync::{Arc, Mutex},
     time::SystemTime,
@@ -15,6 +15,24 @@ use oxc_parser::{Parser, ParserReturn};
 use oxc_span::SourceType;
 use serde::{Deserialize, Serialize};
 
+mod cache;
+mod commonjs;
+mod graph;
+mod jsr;
+mod npm;
+mod resolver;
+mod synthetic;
+mod transpile;
+
+pub use cache::*;
+pub use commonjs::*;
+pub use graph::*;
+pub use jsr::*;
+pub use npm::*;
+pub use resolver::*;
+pub use synthetic::*;
+pub use transpile::*;
+
 /// Error type for module-related operations
 #[derive(Debug, thiserror::Error)]
 pub enum ModuleError {
@@ -47,6 +65,12 @@ pub enum ModuleError {
 
     #[error("IO error: {message}")]
     Io { message: String },
+
+    #[error("Integrity check failed for {specifier}: {message}")]
+    IntegrityError { specifier: String, message: String },
+
+    #[error("Invalid import type for {specifier}: {message}")]
+    ImportTypeError { specifier: String, message: String },
 }
 
 /// Result type for module operations
@@ -122,7 +146,11 @@ pub struct ModuleRecord {
     pub error: Option<String>,
     /// SHA-256 hash of the source code for integrity verification
     pub source_hash: Option<String>,
-    /// Cached compiled bytecode for faster loading
+    /// Cached JavaScript the module's TypeScript was transpiled to
+    pub transpiled_code: Option<String>,
+    /// Always `None`: Nova can't serialize bytecode, so modules are cached as
+    /// transpiled code instead
+    #[deprecated(note = "Nova has no bytecode to cache, use `transpiled_code`")]
     pub compiled_bytecode: Option<Vec<u8>>,
     /// Last modification time for cache invalidation
     pub last_modified: Option<SystemTime>,
@@ -131,12 +159,7 @@ pub struct ModuleRecord {
 impl ModuleRecord {
     /// Calculate and store the SHA-256 hash of the source code
     pub fn calculate_source_hash(&mut self) {
-        use std::collections::hash_map::DefaultHasher;
-        use std::hash::{Hash, Hasher};
-
-        let mut hasher = DefaultHasher::new();
-        self.source.hash(&mut hasher);
-        self.source_hash = Some(format!("{:x}", hasher.finish()));
+        self.source_hash = Some(cache::sha256_hex(self.source.as_bytes()));
     }
 
     /// Check if the module needs recompilation based on hash
@@ -147,12 +170,18 @@ impl ModuleRecord {
         }
     }
 
-    /// Check if cached bytecode is valid
+    /// Check if the cached transpiled code is valid
+    pub fn has_valid_transpiled_code(&self) -> bool {
+        self.transpiled_code.is_some() && self.source_hash.is_some()
+    }
+
+    #[deprecated(note = "use `has_valid_transpiled_code`")]
     pub fn has_valid_bytecode(&self) -> bool {
-        self.compiled_bytecode.is_some() && self.source_hash.is_some()
+        self.has_valid_transpiled_code()
     }
 
     /// Create a new ModuleRecord with default caching fields
+    #[allow(deprecated)]
     pub fn new(id: String, specifier: String, source: String, module_type: ModuleType) -> Self {
         let mut record = Self {
             id,
@@ -166,6 +195,7 @@ impl ModuleRecord {
             module_type,
             error: None,
             source_hash: None,
+            transpiled_code: None,
             compiled_bytecode: None,
             last_modified: Some(SystemTime::now()),
         };
@@ -178,6 +208,9 @@ impl ModuleRecord {
 #[derive(Debug, Clone, PartialEq)]
 pub enum ModuleType {
     JavaScript,
+    /// CommonJS module, run through `require()` and imported as an ES module
+    /// whose default export is `module.exports`
+    CommonJs,
     TypeScript,
     Json,
     Wasm,
@@ -188,7 +221,8 @@ impl ModuleType {
     /// Determine module type from file extension
     pub fn from_extension(ext: &str) -> Self {
         match ext.to_lowercase().as_str() {
-            "js" | "mjs" | "cjs" => ModuleType::JavaScript,
+            "js" | "mjs" => ModuleType::JavaScript,
+            "cjs" => ModuleType::CommonJs,
             "ts" | "tsx" | "mts" | "cts" => ModuleType::TypeScript,
             "json" => ModuleType::Json,
             "wasm" => ModuleType::Wasm,
@@ -364,6 +398,7 @@ impl ModuleLoader for FileSystemModuleLoader {
 pub struct HttpModuleLoader {
     client: ureq::Agent,
     cache: Arc<Mutex<HashMap<String, String>>>,
+    disk_cache: Option<Arc<ModuleCache>>,
 }
 
 impl HttpModuleLoader {
@@ -371,6 +406,15 @@ impl HttpModuleLoader {
         Self {
             client: ureq::Agent::new_with_defaults(),
             cache: Arc::new(Mutex::new(HashMap::new())),
+            disk_cache: None,
+        }
+    }
+
+    /// Load the modules through a disk cache, which also verifies their integrity
+    pub fn with_disk_cache(disk_cache: Arc<ModuleCache>) -> Self {
+        Self {
+            disk_cache: Some(disk_cache),
+            ..Self::new()
         }
     }
 
@@ -399,6 +443,13 @@ impl ModuleLoader for HttpModuleLoader {
             }
         }
 
+        if let Some(disk_cache) = &self.disk_cache {
+            let content = disk_cache.load(specifier)?;
+            let mut cache = self.cache.lock().unwrap();
+            cache.insert(specifier.to_string(), content.clone());
+            return Ok(content);
+        }
+
         // Fetch from network
         let mut response =
             self.client
@@ -484,7 +535,9 @@ impl CompositeModuleLoader {
 
     pub fn default_loaders() -> Self {
         let mut composite = Self::new();
+        composite.add_loader(Box::new(NpmModuleLoader::default()));
         composite.add_loader(Box::new(FileSystemModuleLoader::new(".")));
+        composite.add_loader(Box::new(JsrModuleLoader::default()));
         composite.add_loader(Box::new(HttpModuleLoader::new()));
         composite
     }
@@ -760,6 +813,24 @@ impl DependencyGraph {
         }
     }
 
+    /// Add a module without dependencies, e.g. an entry point
+    pub fn add_module(&mut self, module: &str) {
+        if !self.graph.contains_key(module) {
+            self.graph.insert(module.to_string(), HashSet::new());
+            self.invalidate_cache();
+        }
+    }
+
+    /// All modules of the graph, sorted
+    pub fn modules(&self) -> Vec<String> {
+        let modules: BTreeSet<&String> = self
+            .graph
+            .iter()
+            .flat_map(|(node, deps)| std::iter::once(node).chain(deps))
+            .collect();
+        modules.into_iter().cloned().collect()
+    }
+
     /// Invalidate caches when graph structure changes
     fn invalidate_cache(&mut self) {
         self.scc_cache = None;
@@ -1080,13 +1151,17 @@ pub struct ModuleSystem {
 }
 
 /// Statistics for module system performance monitoring
-#[derive(Debug, Default)]
+#[derive(Debug, Clone, Default)]
 pub struct ModuleSystemStats {
     pub modules_loaded: u64,
     pub cache_hits: u64,
     pub cache_misses: u64,
     pub cycles_detected: u64,
     pub resolution_time_ms: u64,
+    /// Modules whose transpiled code was read from the transpile cache
+    pub transpile_cache_hits: u64,
+    /// Modules transpiled because the transpile cache didn't have them
+    pub transpile_cache_misses: u64,
 }
 
 impl ModuleSystemStats {
@@ -1097,6 +1172,15 @@ impl ModuleSystemStats {
             self.cache_hits as f64 / (self.cache_hits + self.cache_misses) as f64
         }
     }
+
+    pub fn transpile_cache_hit_ratio(&self) -> f64 {
+        let total = self.transpile_cache_hits + self.transpile_cache_misses;
+        if total == 0 {
+            0.0
+        } else {
+            self.transpile_cache_hits as f64 / total as f64
+        }
+    }
 }
 
 #[hotpath::measure_all]
@@ -1199,6 +1283,13 @@ impl ModuleSystem {
         // Load dependencies
         let dependencies = module.imports.clone();
         for import in dependencies {
+            // CommonJS modules can require the modules requiring them, which
+            // get their partially filled exports
+            if module.module_type == ModuleType::CommonJs
+                && loading_stack.contains(&import.specifier)
+            {
+                continue;
+            }
             let dep_id = self.load_module_recursive(&import.specifier, loading_stack)?;
             module.dependencies.push(dep_id.clone());
             self.dependency_graph.add_dependency(&module.id, &dep_id);
@@ -1231,6 +1322,29 @@ impl ModuleSystem {
                 // Parse JavaScript/TypeScript for imports and exports
                 self.parse_js_ts_module(module)?;
             }
+            ModuleType::CommonJs => {
+                // Required modules are the imports, `module.exports` is the
+                // default export
+                module.imports = commonjs_requires(&module.source)
+                    .into_iter()
+                    .map(|specifier| ModuleImport {
+                        specifier,
+                        imports: None,
+                        default_import: None,
+                        namespace_import: None,
+                    })
+                    .collect();
+                module.exports = std::iter::once(None)
+                    .chain(commonjs_exports(&module.source).names.into_iter().map(Some))
+                    .map(|name| ModuleExport {
+                        name,
+                        is_reexport: false,
+                        source_module: None,
+                        source_name: None,
+                    })
+                    .collect();
+                module.is_es_module = false;
+            }
             _ => {
                 return Err(ModuleError::ParseError {
                     path: module.specifier.clone(),
@@ -0,0 +1,731 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use std::{
+    collections::HashMap,
+    io::Read,
+    path::{Component, Path, PathBuf},
+};
+
+use semver::{Version, VersionReq};
+use serde::Deserialize;
+use serde_json::Value;
+
+use super::{ModuleCache, ModuleError, ModuleLoader, ModuleResult, wrap_commonjs};
+use crate::EmbeddedFs;
+
+/// Conditions of `exports` and `imports` matched when a module is imported
+pub const IMPORT_CONDITIONS: &[&str] = &["andromeda", "import", "module", "default"];
+
+/// Conditions of `exports` and `imports` matched when a module is required
+pub const REQUIRE_CONDITIONS: &[&str] = &["andromeda", "require", "default"];
+
+/// Extensions tried, in order, for extensionless files of packages
+const PACKAGE_EXTENSIONS: &[&str] = &["js", "mjs", "cjs", "json", "ts"];
+
+/// A parsed package specifier of `npm:` and `jsr:` imports, such as
+/// `npm:@scope/name@^1.2/sub/path`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PackageSpecifier {
+    pub name: String,
+    /// Version range, any version when missing
+    pub range: Option<String>,
+    /// Subpath of the package such as `./sub/path`, `.` for the package itself
+    pub subpath: String,
+}
+
+impl PackageSpecifier {
+    /// Parse a specifier with or without its `npm:` or `jsr:` prefix
+    pub fn parse(specifier: &str) -> Option<Self> {
+        let specifier = specifier
+            .strip_prefix("npm:")
+            .or_else(|| specifier.strip_prefix("jsr:"))
+            .unwrap_or(specifier);
+        let specifier = specifier.strip_prefix('/').unwrap_or(specifier);
+
+        // The name of a scoped package contains a slash
+        let name_start = if specifier.starts_with('@') {
+            specifier.find('/')? + 1
+        } else {
+            0
+        };
+        let name_end = specifier[name_start..]
+            .find(['@', '/'])
+            .map_or(specifier.len(), |index| name_start + index);
+        let name = &specifier[..name_end];
+        if name.is_empty() || name.ends_with('/') {
+            return None;
+        }
+
+        let rest = &specifier[name_end..];
+        let (range, subpath) = match rest.strip_prefix('@') {
+            Some(rest) => match rest.split_once('/') {
+                Some((range, subpath)) => (Some(range), Some(subpath)),
+                None => (Some(rest), None),
+            },
+            None => (None, rest.strip_prefix('/')),
+        };
+
+        Some(Self {
+            name: name.to_string(),
+            range: range.filter(|range| !range.is_empty()).map(str::to_string),
+            subpath: match subpath {
+                Some(subpath) if !subpath.is_empty() => format!("./{subpath}"),
+                _ => ".".to_string(),
+            },
+        })
+    }
+}
+
+/// The fields of a `package.json` used to resolve modules
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+struct PackageJson {
+    version: Option<String>,
+    #[serde(rename = "type")]
+    module_type: Option<String>,
+    main: Option<String>,
+    exports: Option<Value>,
+    imports: Option<Value>,
+    dependencies: HashMap<String, String>,
+    peer_dependencies: HashMap<String, String>,
+    optional_dependencies: HashMap<String, String>,
+}
+
+impl PackageJson {
+    fn load(dir: &Path) -> Option<Self> {
+        let content = EmbeddedFs::read_to_string(dir.join("package.json")).ok()?;
+        serde_json::from_str(&content).ok()
+    }
+
+    /// Version range of a dependency of the package
+    fn dependency_range(&self, name: &str) -> Option<&str> {
+        self.dependencies
+            .get(name)
+            .or_else(|| self.peer_dependencies.get(name))
+            .or_else(|| self.optional_dependencies.get(name))
+            .map(String::as_str)
+    }
+}
+
+/// Resolves npm packages from `node_modules` directories and from a local
+/// registry directory.
+///
+/// The registry directory holds one directory per package, containing the
+/// tarballs of its versions as `<version>.tgz` or their extracted contents as
+/// `<version>/`. Tarballs are extracted next to themselves the first time they
+/// are used, so a plain directory of packages works as a registry stand-in.
+#[derive(Debug, Clone)]
+pub struct NpmResolver {
+    registry_dir: PathBuf,
+}
+
+impl NpmResolver {
+    pub fn new(registry_dir: impl Into<PathBuf>) -> Self {
+        let registry_dir = registry_dir.into();
+        Self {
+            registry_dir: std::path::absolute(&registry_dir).unwrap_or(registry_dir),
+        }
+    }
+
+    /// The registry directory: `$ANDROMEDA_NPM_CACHE`, or `npm` in the remote
+    /// module cache directory
+    pub fn default_registry_dir() -> PathBuf {
+        match std::env::var_os("ANDROMEDA_NPM_CACHE") {
+            Some(dir) => PathBuf::from(dir),
+            None => ModuleCache::default_dir().join("npm"),
+        }
+    }
+
+    pub fn registry_dir(&self) -> &Path {
+        &self.registry_dir
+    }
+
+    /// Resolve an `npm:` specifier imported by `referrer` into the path of a file
+    pub fn resolve_npm(
+        &self,
+        specifier: &str,
+        referrer: &Path,
+        conditions: &[&str],
+    ) -> ModuleResult<PathBuf> {
+        let npm =
+            PackageSpecifier::parse(specifier).ok_or_else(|| ModuleError::InvalidSpecifier {
+                specifier: specifier.to_string(),
+            })?;
+        let package_dir = self.find_package(&npm.name, npm.range.as_deref(), referrer)?;
+        self.resolve_package_subpath(&package_dir, &npm.subpath, conditions)
+    }
+
+    /// Resolve a bare specifier such as `pkg/sub/path`, or a `#internal`
+    /// specifier of the `imports` of the package of `referrer`, the way Node does
+    pub fn resolve_package(
+        &self,
+        specifier: &str,
+        referrer: &Path,
+        conditions: &[&str],
+    ) -> ModuleResult<PathBuf> {
+        if specifier.starts_with('#') {
+            return self.resolve_imports(specifier, referrer, conditions);
+        }
+        let npm =
+            PackageSpecifier::parse(specifier).ok_or_else(|| ModuleError::InvalidSpecifier {
+                specifier: specifier.to_string(),
+            })?;
+
+        // Packages of the registry get their dependencies from it, at the range
+        // they depend on
+        let range = package_scope(referrer)
+            .filter(|(dir, _)| dir.starts_with(&self.registry_dir))
+            .and_then(|(_, package)| package.dependency_range(&npm.name).map(str::to_string));
+        let package_dir = match self.find_in_node_modules(&npm.name, None, referrer) {
+            Some(dir) => dir,
+            None if referrer.starts_with(&self.registry_dir) => {
+                self.find_in_registry(&npm.name, range.as_deref())?
+            }
+            None => {
+                return Err(ModuleError::NotFound {
+                    specifier: format!("{specifier} (no node_modules/{} found)", npm.name),
+                });
+            }
+        };
+        self.resolve_package_subpath(&package_dir, &npm.subpath, conditions)
+    }
+
+    /// Resolve the specifier of a `require()` call of a CommonJS module
+    pub fn resolve_require(&self, specifier: &str, referrer: &Path) -> ModuleResult<PathBuf> {
+        if specifier.starts_with("./") || specifier.starts_with("../") || specifier.starts_with('/')
+        {
+            let base = referrer.parent().unwrap_or(Path::new("."));
+            return resolve_node_path(&normalize_path(&base.join(specifier))).ok_or_else(|| {
+                ModuleError::NotFound {
+                    specifier: format!("{specifier} required by {}", referrer.display()),
+                }
+            });
+        }
+        if specifier.starts_with("npm:") {
+            return self.resolve_npm(specifier, referrer, REQUIRE_CONDITIONS);
+        }
+        self.resolve_package(specifier, referrer, REQUIRE_CONDITIONS)
+    }
+
+    /// Whether the file at `path` belongs to a package
+    pub fn is_package_file(&self, path: &Path) -> bool {
+        path.starts_with(&self.registry_dir)
+            || path
+                .components()
+                .any(|component| component.as_os_str() == "node_modules")
+    }
+
+    /// Whether the file at `path` is a CommonJS module: a `.cjs` file, or a `.js`
+    /// file of a package whose `type` isn't `module`
+    pub fn is_commonjs(&self, path: &Path) -> bool {
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("cjs") => true,
+            Some("js") if self.is_package_file(path) => package_scope(path)
+                .is_none_or(|(_, package)| package.module_type.as_deref() != Some("module")),
+            _ => false,
+        }
+    }
+
+    /// Directory of the package `name` satisfying `range`, from the `node_modules`
+    /// around `referrer` or else the registry
+    fn find_package(
+        &self,
+        name: &str,
+        range: Option<&str>,
+        referrer: &Path,
+    ) -> ModuleResult<PathBuf> {
+        match self.find_in_node_modules(name, range, referrer) {
+            Some(dir) => Ok(dir),
+            None => self.find_in_registry(name, range),
+        }
+    }
+
+    fn find_in_node_modules(
+        &self,
+        name: &str,
+        range: Option<&str>,
+        referrer: &Path,
+    ) -> Option<PathBuf> {
+        let referrer = std::path::absolute(referrer).unwrap_or_else(|_| referrer.to_path_buf());
+        referrer
+            .ancestors()
+            .skip(1)
+            .filter(|dir| dir.file_name().is_none_or(|name| name != "node_modules"))
+            .map(|dir| dir.join("node_modules").join(name))
+            .find(|dir| {
+                PackageJson::load(dir).is_some_and(|package| match range {
+                    Some(range) => package
+                        .version
+                        .as_deref()
+                        .is_some_and(|version| satisfies(version, range)),
+                    None => true,
+                })
+            })
+    }
+
+    /// Directory of the highest version of the package `name` in the registry
+    /// satisfying `range`, extracting its tarball when needed
+    fn find_in_registry(&self, name: &str, range: Option<&str>) -> ModuleResult<PathBuf> {
+        let package_dir = self.registry_dir.join(name);
+        let not_found = || ModuleError::NotFound {
+            specifier: format!(
+                "npm:{name}@{} (not in node_modules nor in the registry {})",
+                range.unwrap_or("*"),
+                self.registry_dir.display()
+            ),
+        };
+
+        let entries = std::fs::read_dir(&package_dir).map_err(|_| not_found())?;
+        let mut versions: Vec<(Version, PathBuf)> = entries
+            .filter_map(|entry| {
+                let path = entry.ok()?.path();
+                let file_name = path.file_name()?.to_str()?;
+                let version = if path.is_dir() {
+                    file_name
+                } else {
+                    file_name.strip_suffix(".tgz")?
+                };
+                if range.is_some_and(|range| !satisfies(version, range)) {
+                    return None;
+                }
+                Some((Version::parse(version).ok()?, path))
+            })
+            .collect();
+        versions.sort_by(|(a, _), (b, _)| a.cmp(b));
+        let (version, path) = versions.pop().ok_or_else(not_found)?;
+
+        let version_dir = package_dir.join(version.to_string());
+        if version_dir.is_dir() {
+            return Ok(version_dir);
+        }
+        // Extract next to the final directory and rename it, so that an
+        // interrupted extraction is never used
+        let partial_dir = package_dir.join(format!("{version}.partial-{}", std::process::id()));
+        extract_tarball(&path, &partial_dir)?;
+        if std::fs::rename(&partial_dir, &version_dir).is_err() {
+            // Extracted concurrently by another process
+            let _ = std::fs::remove_dir_all(&partial_dir);
+        }
+        Ok(version_dir)
+    }
+
+    /// Resolve `subpath` of the package in `dir` through its `exports`, or its
+    /// `main` and files when it has none
+    fn resolve_package_subpath(
+        &self,
+        dir: &Path,
+        subpath: &str,
+        conditions: &[&str],
+    ) -> ModuleResult<PathBuf> {
+        let package = PackageJson::load(dir).unwrap_or_default();
+        let not_found = |reason: &str| ModuleError::NotFound {
+            specifier: format!("{subpath} of package {} ({reason})", dir.display()),
+        };
+
+        if let Some(exports) = &package.exports {
+            let target = resolve_exports(exports, subpath, conditions)
+                .ok_or_else(|| not_found("not exported by its package.json \"exports\""))?;
+            let path = normalize_path(&dir.join(target));
+            return if EmbeddedFs::is_file(&path) {
+                Ok(path)
+            } else {
+                Err(not_found(&format!("{} does not exist", path.display())))
+            };
+        }
+
+        let path = if subpath == "." {
+            package
+                .main
+                .as_deref()
+                .and_then(|main| resolve_node_path(&normalize_path(&dir.join(main))))
+                .or_else(|| resolve_node_path(&dir.join("index")))
+        } else {
+            resolve_node_path(&normalize_path(&dir.join(subpath)))
+        };
+        path.ok_or_else(|| not_found("no such file"))
+    }
+
+    /// Resolve a `#internal` specifier through the `imports` of the package of
+    /// `referrer`
+    fn resolve_imports(
+        &self,
+        specifier: &str,
+        referrer: &Path,
+        conditions: &[&str],
+    ) -> ModuleResult<PathBuf> {
+        let not_found = || ModuleError::NotFound {
+            specifier: format!(
+                "{specifier} (not in the package.json \"imports\" of {})",
+                referrer.display()
+            ),
+        };
+        let (dir, package) = package_scope(referrer).ok_or_else(not_found)?;
+        let Some(Value::Object(imports)) = &package.imports else {
+            return Err(not_found());
+        };
+        let (target, pattern_match) = match_subpath(imports, specifier).ok_or_else(not_found)?;
+        let target =
+            resolve_target(target, pattern_match.as_deref(), conditions).ok_or_else(not_found)?;
+
+        if target.starts_with("./") {
+            let path = normalize_path(&dir.join(&target));
+            EmbeddedFs::is_file(&path)
+                .then_some(path)
+                .ok_or_else(not_found)
+        } else {
+            // Imports can map to other packages
+            self.resolve_package(&target, referrer, conditions)
+        }
+    }
+}
+
+/// Module loader for `npm:` specifiers and the files of npm packages, which
+/// wraps CommonJS modules into ES modules
+pub struct NpmModuleLoader {
+    resolver: NpmResolver,
+}
+
+impl NpmModuleLoader {
+    pub fn new(resolver: NpmResolver) -> Self {
+        Self { resolver }
+    }
+}
+
+impl Default for NpmModuleLoader {
+    fn default() -> Self {
+        Self::new(NpmResolver::new(NpmResolver::default_registry_dir()))
+    }
+}
+
+#[hotpath::measure_all]
+impl ModuleLoader for NpmModuleLoader {
+    fn load_module(&self, specifier: &str) -> ModuleResult<String> {
+        let path = Path::new(specifier);
+        if !self.resolver.is_package_file(path) {
+            return Err(ModuleError::NotFound {
+                specifier: specifier.to_string(),
+            });
+        }
+        let source = EmbeddedFs::read_to_string(path).map_err(|e| ModuleError::Io {
+            message: format!("Failed to read {specifier}: {e}"),
+        })?;
+        if self.resolver.is_commonjs(path) {
+            Ok(wrap_commonjs(&self.resolver, specifier, &source))
+        } else {
+            Ok(source)
+        }
+    }
+
+    fn resolve_specifier(&self, specifier: &str, base: Option<&str>) -> ModuleResult<String> {
+        let referrer = match base {
+            Some(base) if !base.contains("://") => PathBuf::from(base),
+            _ => std::env::current_dir()
+                .unwrap_or_else(|_| PathBuf::from("."))
+                .join("module.js"),
+        };
+        let path = if specifier.starts_with("npm:") {
+            self.resolver
+                .resolve_npm(specifier, &referrer, IMPORT_CONDITIONS)?
+        } else if specifier.starts_with("./")
+            || specifier.starts_with("../")
+            || specifier.starts_with('/')
+            || specifier.contains("://")
+        {
+            return Err(ModuleError::ResolutionError {
+                message: format!("npm loader only resolves packages, got: {specifier}"),
+            });
+        } else {
+            self.resolver
+                .resolve_package(specifier, &referrer, IMPORT_CONDITIONS)?
+        };
+        Ok(path.to_string_lossy().to_string())
+    }
+
+    fn module_exists(&self, specifier: &str) -> bool {
+        self.resolve_specifier(specifier, None).is_ok()
+    }
+
+    fn supported_extensions(&self) -> Vec<&'static str> {
+        vec!["js", "mjs", "cjs", "json"]
+    }
+}
+
+/// The directory and `package.json` of the package containing `path`
+fn package_scope(path: &Path) -> Option<(PathBuf, PackageJson)> {
+    path.ancestors()
+        .skip(1)
+        .take_while(|dir| dir.file_name().is_none_or(|name| name != "node_modules"))
+        .find_map(|dir| PackageJson::load(dir).map(|package| (dir.to_path_buf(), package)))
+}
+
+/// Resolve a file the way `require()` does: as is, with an extension, or as a
+/// directory through its `package.json` `main` or its index file
+pub fn resolve_node_path(path: &Path) -> Option<PathBuf> {
+    if EmbeddedFs::is_file(path) {
+        return Some(path.to_path_buf());
+    }
+    for ext in PACKAGE_EXTENSIONS {
+        let mut candidate = path.as_os_str().to_owned();
+        candidate.push(format!(".{ext}"));
+        let candidate = PathBuf::from(candidate);
+        if EmbeddedFs::is_file(&candidate) {
+            return Some(candidate);
+        }
+    }
+    if EmbeddedFs::is_dir(path) {
+        if let Some(main) = PackageJson::load(path).and_then(|package| package.main) {
+            let main = normalize_path(&path.join(main));
+            if EmbeddedFs::is_file(&main) {
+                return Some(main);
+            }
+            for ext in PACKAGE_EXTENSIONS {
+                let mut candidate = main.as_os_str().to_owned();
+                candidate.push(format!(".{ext}"));
+                let candidate = PathBuf::from(candidate);
+                if EmbeddedFs::is_file(&candidate) {
+                    return Some(candidate);
+                }
+            }
+        }
+        for ext in PACKAGE_EXTENSIONS {
+            let index = path.join(format!("index.{ext}"));
+            if EmbeddedFs::is_file(&index) {
+                return Some(index);
+            }
+        }
+    }
+    None
+}
+
+/// Remove the `.` and `..` components of a path, so that a module always
+/// resolves to the same path
+fn normalize_path(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir if normalized.file_name().is_some() => {
+                normalized.pop();
+            }
+            component => normalized.push(component),
+        }
+    }
+    normalized
+}
+
+/// The target of `subpath` in a package.json `exports` field
+fn resolve_exports(exports: &Value, subpath: &str, conditions: &[&str]) -> Option<String> {
+    let target = match exports {
+        Value::Object(map) if map.keys().any(|key| key.starts_with('.')) => {
+            let (target, pattern_match) = match_subpath(map, subpath)?;
+            resolve_target(target, pattern_match.as_deref(), conditions)?
+        }
+        // Only the package itself is exported
+        _ if subpath == "." => resolve_target(exports, None, conditions)?,
+        _ => return None,
+    };
+    // Exports can only point inside of the package
+    target.starts_with("./").then_some(target)
+}
+
+/// The entry of an `exports` or `imports` map matching `key`, either exactly or
+/// through the most specific `*` pattern, with the text matched by the `*`
+fn match_subpath<'a>(
+    map: &'a serde_json::Map<String, Value>,
+    key: &str,
+) -> Option<(&'a Value, Option<String>)> {
+    if let Some(target) = map.get(key)
+        && !key.contains('*')
+    {
+        return Some((target, None));
+    }
+
+    map.iter()
+        .filter_map(|(pattern, target)| {
+            let (prefix, suffix) = pattern.split_once('*')?;
+            (key.len() >= prefix.len() + suffix.len()
+                && key.starts_with(prefix)
+                && key.ends_with(suffix))
+            .then(|| {
+                (
+                    prefix.len(),
+                    pattern.len(),
+                    target,
+                    &key[prefix.len()..key.len() - suffix.len()],
+                )
+            })
+        })
+        .max_by_key(|(prefix_len, pattern_len, _, _)| (*prefix_len, *pattern_len))
+        .map(|(_, _, target, matched)| (target, Some(matched.to_string())))
+}
+
+/// Resolve the target of an `exports` or `imports` entry, picking the first
+/// matching condition of nested condition objects
+fn resolve_target(
+    target: &Value,
+    pattern_match: Option<&str>,
+    conditions: &[&str],
+) -> Option<String> {
+    match target {
+        Value::String(target) => Some(match pattern_match {
+            Some(matched) => target.replace('*', matched),
+            None => target.clone(),
+        }),
+        Value::Array(targets) => targets
+            .iter()
+            .find_map(|target| resolve_target(target, pattern_match, conditions)),
+        Value::Object(map) => conditions.iter().find_map(|condition| {
+            map.get(*condition)
+                .and_then(|target| resolve_target(target, pattern_match, conditions))
+        }),
+        _ => None,
+    }
+}
+
+/// Whether `version` satisfies the npm version range `range`
+pub(super) fn satisfies(version: &str, range: &str) -> bool {
+    let Ok(version) = Version::parse(version.trim_start_matches('v')) else {
+        return false;
+    };
+    range
+        .split("||")
+        .filter_map(version_req)
+        .any(|req| req.matches(&version))
+}
+
+/// Convert a set of npm comparators, e.g. `>=1.2 <2`, `1.x` or `1.0 - 2.0`, into
+/// a semver requirement
+fn version_req(range: &str) -> Option<VersionReq> {
+    let range = range.trim();
+    if range.is_empty() || range == "*" || range == "latest" || range.eq_ignore_ascii_case("x") {
+        return Some(VersionReq::STAR);
+    }
+    if let Some((from, to)) = range.split_once(" - ") {
+        return VersionReq::parse(&format!(">={}, <={}", from.trim(), to.trim())).ok();
+    }
+
+    let mut comparators = Vec::new();
+    let mut operator = String::new();
+    for token in range.split_whitespace() {
+        // Operators can be separated from their version, as in `>= 1.2`
+        if token.chars().all(|c| "<>=~^".contains(c)) {
+            operator.push_str(token);
+            continue;
+        }
+        let token = format!("{}{token}", std::mem::take(&mut operator));
+        let version_start = token
+            .find(|c: char| !"<>=~^v".contains(c))
+            .unwrap_or(token.len());
+        let (op, version) = token.split_at(version_start);
+        // `1.x` is written `1` and a bare version is exact, unlike in Cargo
+        let version = version
+            .split('.')
+            .take_while(|part| !matches!(*part, "x" | "X" | "*"))
+            .collect::<Vec<_>>()
+            .join(".");
+        if version.is_empty() {
+            comparators.push("*".to_string());
+            continue;
+        }
+        let op = match op.trim_end_matches('v') {
+            "" => "=",
+            op => op,
+        };
+        comparators.push(format!("{op}{version}"));
+    }
+    if comparators.iter().any(|comparator| comparator == "*") {
+        return Some(VersionReq::STAR);
+    }
+    VersionReq::parse(&comparators.join(", ")).ok()
+}
+
+/// Extract a gzipped npm tarball into `dest`, without its top-level directory
+fn extract_tarball(tarball: &Path, dest: &Path) -> ModuleResult<()> {
+    let io_error = |e: std::io::Error| ModuleError::Io {
+        message: format!("Failed to extract {}: {e}", tarball.display()),
+    };
+    let invalid = |message: &str| ModuleError::Io {
+        message: format!("Failed to extract {}: {message}", tarball.display()),
+    };
+
+    let file = std::fs::File::open(tarball).map_err(io_error)?;
+    let mut archive = Vec::new();
+    flate2::read::GzDecoder::new(file)
+        .read_to_end(&mut archive)
+        .map_err(io_error)?;
+
+    let mut offset = 0;
+    let mut long_name: Option<String> = None;
+    while offset + 512 <= archive.len() {
+        let header = &archive[offset..offset + 512];
+        if header.iter().all(|byte| *byte == 0) {
+            break;
+        }
+        let size = parse_octal(&header[124..136]).ok_or_else(|| invalid("invalid entry size"))?;
+        let data_start = offset + 512;
+        let data = archive
+            .get(data_start..data_start + size)
+            .ok_or_else(|| invalid("truncated archive"))?;
+        offset = data_start + size.div_ceil(512) * 512;
+
+        let name = long_name.take().unwrap_or_else(|| {
+            let name = tar_string(&header[0..100]);
+            match tar_string(&header[345..500]) {
+                prefix if prefix.is_empty() => name,
+                prefix => format!("{prefix}/{name}"),
+            }
+        });
+        match header[156] {
+            // Regular files
+            b'0' | 0 | b'7' => {
+                // Drop the top-level directory, usually `package/`, and refuse
+                // paths escaping the destination
+                let relative: PathBuf = Path::new(&name).components().skip(1).collect();
+                if relative.as_os_str().is_empty()
+                    || !relative
+                        .components()
+                        .all(|component| matches!(component, Component::Normal(_)))
+                {
+                    continue;
+                }
+                let path = dest.join(relative);
+                if let Some(parent) = path.parent() {
+                    std::fs::create_dir_all(parent).map_err(io_error)?;
+                }
+                std::fs::write(&path, data).map_err(io_error)?;
+            }
+            // GNU long name of the next entry
+            b'L' => long_name = Some(tar_string(data)),
+            // PAX header, which can hold the path of the next entry
+            b'x' => {
+                long_name = String::from_utf8_lossy(data).lines().find_map(|record| {
+                    record
+                        .split_once(" path=")
+                        .map(|(_, path)| path.to_string())
+                });
+            }
+            // Directories are created with their files, other entries are ignored
+            _ => {}
+        }
+    }
+    std::fs::create_dir_all(dest).map_err(io_error)
+}
+
+fn tar_string(bytes: &[u8]) -> String {
+    let end = bytes
+        .iter()
+        .position(|byte| *byte == 0)
+        .unwrap_or(bytes.len());
+    String::from_utf8_lossy(&bytes[..end]).to_string()
+}
+
+fn parse_octal(bytes: &[u8]) -> Option<usize> {
+    let value = tar_string(bytes);
+    let value = value.trim_matches(|c: char| c == ' ' || c == '\0');
+    if value.is_empty() {
+        return Some(0);
+    }
+    usize::from_str_radix(value, 8).ok()
+}
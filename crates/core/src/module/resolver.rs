@@ -0,0 +1,260 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use super::{
+    CacheSetting, IMPORT_CONDITIONS, ImportMap, JsrResolver, ModuleCache, ModuleError, NpmResolver,
+    resolve_node_path,
+};
+use crate::EmbeddedFs;
+
+/// Resolves module specifiers the way the runtime loads them, relative to a
+/// referrer path or URL and through an optional import map
+#[derive(Debug, Clone)]
+pub struct ModuleResolver {
+    /// Directory of the entry module, used for bare specifiers
+    pub base_path: PathBuf,
+    pub import_map: Option<ImportMap>,
+    /// Resolves `npm:` specifiers and the packages of `node_modules`
+    pub npm: NpmResolver,
+    /// Resolves `jsr:` specifiers into the URLs of the registry modules
+    pub jsr: JsrResolver,
+}
+
+impl ModuleResolver {
+    pub fn new(base_path: PathBuf, import_map: Option<ImportMap>) -> Self {
+        Self {
+            base_path,
+            import_map,
+            npm: NpmResolver::new(NpmResolver::default_registry_dir()),
+            jsr: JsrResolver::new(
+                JsrResolver::default_registry_url(),
+                Arc::new(ModuleCache::new(
+                    ModuleCache::default_dir(),
+                    CacheSetting::Use,
+                )),
+            ),
+        }
+    }
+
+    /// Resolve `specifier` imported by `referrer` into a URL or the path of an
+    /// existing file, or `Err` with the path that was not found
+    pub fn resolve(&self, specifier: &str, referrer: &str) -> Result<String, String> {
+        let resolved_specifier =
+            if referrer.starts_with("http://") || referrer.starts_with("https://") {
+                // Referrer is a URL, use URL-based resolution
+                self.resolve_url_specifier(specifier, referrer)
+            } else {
+                // Referrer is a file path, use file-based resolution
+                self.resolve_module_specifier(specifier, Path::new(referrer))
+            };
+
+        if resolved_specifier.starts_with("jsr:") {
+            return self.jsr.resolve(&resolved_specifier).map_err(package_error);
+        }
+
+        // For HTTP URLs, skip extension resolution; for file paths, try to resolve extensions
+        if resolved_specifier.starts_with("http://") || resolved_specifier.starts_with("https://") {
+            return Ok(resolved_specifier);
+        }
+
+        // npm packages are looked up around the referrer, or the base path for remote referrers
+        let referrer_path = if referrer.contains("://") {
+            self.base_path.join("module.js")
+        } else {
+            PathBuf::from(referrer)
+        };
+        if resolved_specifier.starts_with("npm:") {
+            return self
+                .npm
+                .resolve_npm(&resolved_specifier, &referrer_path, IMPORT_CONDITIONS)
+                .map(|path| path.to_string_lossy().to_string())
+                .map_err(package_error);
+        }
+
+        let path = PathBuf::from(&resolved_specifier);
+        match self.resolve_extensions(path.clone()) {
+            Some(resolved) if EmbeddedFs::is_file(&resolved) => {
+                Ok(resolved.to_string_lossy().to_string())
+            }
+            // Directories resolve to their index file
+            _ => match resolve_node_path(&path) {
+                Some(resolved) => Ok(resolved.to_string_lossy().to_string()),
+                None if is_bare_specifier(specifier) => self
+                    .npm
+                    .resolve_package(specifier, &referrer_path, IMPORT_CONDITIONS)
+                    .map(|path| path.to_string_lossy().to_string())
+                    .map_err(package_error),
+                None => Err(resolved_specifier),
+            },
+        }
+    }
+
+    /// Resolve a module specifier relative to a referrer path
+    pub fn resolve_module_specifier(&self, specifier: &str, referrer_path: &Path) -> String {
+        // Try import map resolution first for bare specifiers
+        if let Some(import_map) = &self.import_map
+            && !specifier.starts_with("./")
+            && !specifier.starts_with("../")
+            && !specifier.starts_with("/")
+            && !specifier.contains("://")
+        {
+            // This is a bare specifier, try import map resolution
+            let base_url = referrer_path.to_string_lossy();
+            if let Some(mapped_specifier) = import_map.resolve_specifier(specifier, Some(&base_url))
+            {
+                // Use the mapped specifier for resolution
+                return mapped_specifier;
+            }
+        }
+
+        // Handle HTTP URLs and npm and JSR packages directly
+        if specifier.starts_with("http://")
+            || specifier.starts_with("https://")
+            || specifier.starts_with("npm:")
+            || specifier.starts_with("jsr:")
+        {
+            specifier.to_string()
+        } else {
+            // Check if referrer is a URL (HTTP/HTTPS)
+            let referrer_str = referrer_path.to_string_lossy();
+            if referrer_str.starts_with("http://") || referrer_str.starts_with("https://") {
+                // Use URL joining for URL-to-URL resolution
+                self.resolve_url_specifier(specifier, &referrer_str)
+            } else {
+                // Use file path resolution for file-to-file resolution
+                self.resolve_path_specifier(specifier, referrer_path)
+                    .to_string_lossy()
+                    .to_string()
+            }
+        }
+    }
+
+    /// Resolve a path-based specifier (internal helper)
+    pub fn resolve_path_specifier(&self, specifier: &str, referrer_path: &Path) -> PathBuf {
+        if specifier.starts_with("http://") || specifier.starts_with("https://") {
+            // For HTTP URLs, return the URL as a path-like string
+            // We'll handle this specially in the loading logic
+            PathBuf::from(specifier)
+        } else if specifier.starts_with("./") || specifier.starts_with("../") {
+            // Relative import
+            let referrer_dir = referrer_path.parent().unwrap_or(&self.base_path);
+            referrer_dir.join(specifier)
+        } else if specifier.starts_with("/") {
+            // Absolute import
+            PathBuf::from(specifier)
+        } else {
+            // Relative to base path or bare specifier
+            self.base_path.join(specifier)
+        }
+    }
+
+    /// Resolve a URL-based specifier (internal helper for URL-to-URL resolution)
+    pub fn resolve_url_specifier(&self, specifier: &str, referrer_url: &str) -> String {
+        if specifier.starts_with("http://") || specifier.starts_with("https://") {
+            // Already a full URL
+            specifier.to_string()
+        } else {
+            // Use URL joining for relative imports
+            match url::Url::parse(referrer_url) {
+                Ok(base_url) => {
+                    match base_url.join(specifier) {
+                        Ok(resolved_url) => resolved_url.to_string(),
+                        Err(_) => {
+                            // If URL joining fails, fall back to simple concatenation
+                            // This shouldn't happen with valid URLs, but provides a fallback
+                            if let Some(stripped) = specifier.strip_prefix("./") {
+                                format!("{}/{}", referrer_url.trim_end_matches('/'), stripped)
+                            } else if let Some(stripped) = specifier.strip_prefix("../") {
+                                // Simple fallback for parent directory navigation
+                                let mut base = referrer_url.trim_end_matches('/');
+                                if let Some(last_slash) = base.rfind('/') {
+                                    base = &base[..last_slash];
+                                }
+                                format!("{base}/{stripped}")
+                            } else {
+                                format!("{}/{}", referrer_url.trim_end_matches('/'), specifier)
+                            }
+                        }
+                    }
+                }
+                Err(_) => {
+                    // If base URL parsing fails, use simple string concatenation as fallback
+                    if let Some(stripped) = specifier.strip_prefix("./") {
+                        format!("{}/{}", referrer_url.trim_end_matches('/'), stripped)
+                    } else if let Some(stripped) = specifier.strip_prefix("../") {
+                        // Simple fallback for parent directory navigation
+                        let mut base = referrer_url.trim_end_matches('/');
+                        if let Some(last_slash) = base.rfind('/') {
+                            base = &base[..last_slash];
+                        }
+                        format!("{base}/{stripped}")
+                    } else {
+                        format!("{}/{}", referrer_url.trim_end_matches('/'), specifier)
+                    }
+                }
+            }
+        }
+    }
+
+    /// Resolve module file with proper extension handling
+    pub fn resolve_extensions(&self, path: PathBuf) -> Option<PathBuf> {
+        let path_str = path.to_string_lossy();
+
+        // Handle HTTP URLs - they don't need file system extension resolution
+        if path_str.starts_with("http://") || path_str.starts_with("https://") {
+            return Some(path);
+        }
+
+        // First try the path as-is
+        if EmbeddedFs::exists(&path) {
+            return Some(path);
+        }
+
+        // Get the base path without extension for trying alternatives
+        let path_stem = path.with_extension("");
+
+        // Try different extensions in order of preference
+        for ext in &["ts", "js", "mjs", "json"] {
+            let candidate = path_stem.with_extension(ext);
+            if EmbeddedFs::exists(&candidate) {
+                return Some(candidate);
+            }
+        }
+
+        // If the original import had an extension but we didn't find it,
+        // try the path without extension (for cases like './math' -> './math.ts')
+        if path.extension().is_none() {
+            for ext in &["ts", "js", "mjs", "json"] {
+                let candidate = path.with_extension(ext);
+                if EmbeddedFs::exists(&candidate) {
+                    return Some(candidate);
+                }
+            }
+        }
+
+        None
+    }
+}
+
+/// Whether `specifier` names a package or a `#internal` import rather than a
+/// path or URL
+fn is_bare_specifier(specifier: &str) -> bool {
+    !specifier.starts_with("./")
+        && !specifier.starts_with("../")
+        && !specifier.starts_with('/')
+        && !specifier.contains(':')
+}
+
+/// What was not found when resolving an npm or JSR package
+fn package_error(error: ModuleError) -> String {
+    match error {
+        ModuleError::NotFound { specifier } => specifier,
+        error => error.to_string(),
+    }
+}
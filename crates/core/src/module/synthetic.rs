@@ -0,0 +1,124 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use super::{ModuleError, ModuleResult};
+
+/// How a module is imported, given by the `type` import attribute as in
+/// `import data from "./data.json" with { type: "json" }`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ImportType {
+    /// No `type` attribute, an ES module
+    JavaScript,
+    /// Synthetic module whose default export is the parsed JSON
+    Json,
+    /// Synthetic module whose default export is the content as a string
+    Text,
+    /// Synthetic module whose default export is the content as a `Uint8Array`
+    Bytes,
+}
+
+impl ImportType {
+    /// The import type of the value of a `type` attribute, if any
+    pub fn from_attribute(specifier: &str, value: Option<&str>) -> ModuleResult<Self> {
+        match value {
+            None => Ok(Self::JavaScript),
+            Some("json") => Ok(Self::Json),
+            Some("text") => Ok(Self::Text),
+            Some("bytes") => Ok(Self::Bytes),
+            Some(other) => Err(ModuleError::ImportTypeError {
+                specifier: specifier.to_string(),
+                message: format!("unsupported import type \"{other}\""),
+            }),
+        }
+    }
+
+    /// Value of the `type` attribute for this import type
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::JavaScript => "javascript",
+            Self::Json => "json",
+            Self::Text => "text",
+            Self::Bytes => "bytes",
+        }
+    }
+
+    /// Check that the module at `specifier`, served with `content_type` when it
+    /// is remote, can be imported with this type. JSON modules must be imported
+    /// with `type: "json"`, and remote JSON and JavaScript modules must be
+    /// served with a matching content type.
+    pub fn check(&self, specifier: &str, content_type: Option<&str>) -> ModuleResult<()> {
+        let is_remote = specifier.starts_with("http://") || specifier.starts_with("https://");
+        let essence = content_type
+            .and_then(|content_type| content_type.split(';').next())
+            .map(|essence| essence.trim().to_ascii_lowercase());
+        let error = |message: String| ModuleError::ImportTypeError {
+            specifier: specifier.to_string(),
+            message,
+        };
+
+        match self {
+            Self::JavaScript if !is_remote && specifier.ends_with(".json") => Err(error(
+                "JSON modules must be imported with { type: \"json\" }".to_string(),
+            )),
+            Self::JavaScript if is_remote => match essence.as_deref() {
+                Some(essence) if is_json_mime(essence) => Err(error(format!(
+                    "served as {essence}, JSON modules must be imported with {{ type: \"json\" }}"
+                ))),
+                _ => Ok(()),
+            },
+            Self::Json if is_remote => match essence.as_deref() {
+                Some(essence) if is_json_mime(essence) => Ok(()),
+                Some(essence) => Err(error(format!(
+                    "imported with type \"json\" but served as {essence}"
+                ))),
+                None => Err(error(
+                    "imported with type \"json\" but served without a content type".to_string(),
+                )),
+            },
+            _ => Ok(()),
+        }
+    }
+
+    /// Source of the ES module for `content`: the content itself for JavaScript,
+    /// or a synthetic module exporting it by default for the other types
+    pub fn module_source(&self, specifier: &str, content: &[u8]) -> ModuleResult<String> {
+        let text = || {
+            std::str::from_utf8(content).map_err(|e| ModuleError::ParseError {
+                path: specifier.to_string(),
+                message: format!("Module is not valid UTF-8: {e}"),
+            })
+        };
+        match self {
+            Self::JavaScript => Ok(text()?.to_string()),
+            Self::Json => {
+                let text = text()?;
+                // Validate here so that the error points at the JSON file
+                serde_json::from_str::<serde_json::Value>(text).map_err(|e| {
+                    ModuleError::ParseError {
+                        path: specifier.to_string(),
+                        message: format!("Invalid JSON: {e}"),
+                    }
+                })?;
+                Ok(format!("export default {};\n", text.trim()))
+            }
+            Self::Text => Ok(format!(
+                "export default {};\n",
+                serde_json::to_string(text()?).unwrap()
+            )),
+            Self::Bytes => {
+                let bytes: Vec<String> = content.iter().map(u8::to_string).collect();
+                Ok(format!(
+                    "export default new Uint8Array([{}]);\n",
+                    bytes.join(",")
+                ))
+            }
+        }
+    }
+}
+
+/// Whether `essence` is a JSON MIME type, such as `application/json` or
+/// `application/ld+json`
+fn is_json_mime(essence: &str) -> bool {
+    essence == "application/json" || essence == "text/json" || essence.ends_with("+json")
+}
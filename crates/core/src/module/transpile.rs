@@ -0,0 +1,207 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use std::{
+    io,
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+use oxc_allocator::Allocator;
+use oxc_codegen::{Codegen, CodegenOptions};
+use oxc_parser::Parser;
+use oxc_semantic::SemanticBuilder;
+use oxc_span::SourceType;
+use oxc_transformer::{TransformOptions, Transformer};
+
+use super::cache::sha256_hex;
+use super::{ModuleCache, ModuleRecord, ModuleSystemStats, ModuleType};
+use crate::register_generated_source;
+
+/// Revision of Nova the transpiled modules are evaluated by, invalidating the
+/// cache when it changes. Set by the build script from the `nova_vm`
+/// dependency of the workspace
+const NOVA_REVISION: &str = env!("NOVA_REVISION");
+
+/// On-disk cache of the JavaScript that TypeScript modules transpile to.
+///
+/// The cached artifact is the transpiled code with an inline source map to its
+/// original source, not bytecode: Nova can't serialize its bytecode, and
+/// JavaScript modules have nothing to transpile, so they are not cached.
+/// Artifacts are keyed by the source hash of the [ModuleRecord] and stored per
+/// Nova revision, so that editing a module or upgrading Nova transpiles it
+/// again.
+#[derive(Debug)]
+pub struct TranspileCache {
+    dir: PathBuf,
+    stats: Mutex<ModuleSystemStats>,
+}
+
+impl TranspileCache {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self {
+            dir: dir.into(),
+            stats: Mutex::default(),
+        }
+    }
+
+    /// The directory of the transpile cache, next to the module cache
+    pub fn default_dir() -> PathBuf {
+        ModuleCache::default_dir().with_file_name("transpiled")
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// The code to evaluate for the module at `specifier`: the cached
+    /// transpiled code of TypeScript modules, transpiling and caching it on a
+    /// miss, or else the source as it is
+    pub fn load(&self, specifier: &str, source: String) -> String {
+        let extension = Path::new(specifier.split(['?', '#']).next().unwrap_or(specifier))
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .unwrap_or_default();
+        let module_type = ModuleType::from_extension(extension);
+        if module_type != ModuleType::TypeScript {
+            return source;
+        }
+
+        let mut record = ModuleRecord::new(
+            specifier.to_string(),
+            specifier.to_string(),
+            source,
+            module_type,
+        );
+        let Some(path) = self.artifact_path(&record) else {
+            return record.source;
+        };
+        match std::fs::read_to_string(&path) {
+            Ok(code) => {
+                self.stats.lock().unwrap().transpile_cache_hits += 1;
+                record.transpiled_code = Some(code);
+            }
+            Err(_) => {
+                self.stats.lock().unwrap().transpile_cache_misses += 1;
+                if let Some(code) = transpile_module(specifier, &record.source) {
+                    // The cache only saves transpiling, so failing to write
+                    // it is fine
+                    let _ = path
+                        .parent()
+                        .map_or(Ok(()), std::fs::create_dir_all)
+                        .and_then(|()| std::fs::write(&path, &code));
+                    record.transpiled_code = Some(code);
+                }
+            }
+        }
+
+        match record.transpiled_code {
+            Some(code) => {
+                // Stack traces locate errors in the transpiled code, which is
+                // mapped back through its source map
+                register_generated_source(specifier, code.clone());
+                code
+            }
+            None => record.source,
+        }
+    }
+
+    /// Statistics of the modules loaded through the cache
+    pub fn stats(&self) -> ModuleSystemStats {
+        self.stats.lock().unwrap().clone()
+    }
+
+    /// Remove every cached module, returning how many were removed
+    pub fn clean(&self) -> io::Result<usize> {
+        if !self.dir.exists() {
+            return Ok(0);
+        }
+        let removed = count_files(&self.dir)?;
+        std::fs::remove_dir_all(&self.dir)?;
+        Ok(removed)
+    }
+
+    /// The path of the transpiled code of a module, which depends on its
+    /// specifier through its source map
+    fn artifact_path(&self, record: &ModuleRecord) -> Option<PathBuf> {
+        let source_hash = record.source_hash.as_deref()?;
+        let key = sha256_hex(
+            format!(
+                "{}\0{}\0{source_hash}",
+                env!("CARGO_PKG_VERSION"),
+                record.specifier
+            )
+            .as_bytes(),
+        );
+        Some(self.dir.join(NOVA_REVISION).join(format!("{key}.js")))
+    }
+}
+
+fn count_files(dir: &Path) -> io::Result<usize> {
+    let mut count = 0;
+    for entry in std::fs::read_dir(dir)? {
+        let entry = entry?;
+        if entry.file_type()?.is_dir() {
+            count += count_files(&entry.path())?;
+        } else {
+            count += 1;
+        }
+    }
+    Ok(count)
+}
+
+/// Transpile a TypeScript module to JavaScript with an inline source map, or
+/// `None` if it doesn't compile
+fn transpile_module(specifier: &str, source: &str) -> Option<String> {
+    let source_type = SourceType::ts().with_module(true);
+    let (code, map) = compile_typescript(source, source_type, Some(Path::new(specifier)), false)?;
+    let map = map?;
+    Some(format!(
+        "{code}\n//# sourceMappingURL={}\n",
+        map.to_data_url()
+    ))
+}
+
+/// Strip the types of a TypeScript source, with a source map of the generated
+/// code when `source_map_path` is given, or `None` if it doesn't compile
+pub(crate) fn compile_typescript(
+    source: &str,
+    source_type: SourceType,
+    source_map_path: Option<&Path>,
+    minify: bool,
+) -> Option<(String, Option<oxc_sourcemap::SourceMap>)> {
+    let allocator = Allocator::default();
+    let ret = Parser::new(&allocator, source, source_type).parse();
+    if ret.panicked || !ret.errors.is_empty() {
+        return None;
+    }
+    let mut program = ret.program;
+
+    let scoping = SemanticBuilder::new()
+        .build(&program)
+        .semantic
+        .into_scoping();
+    let ret = Transformer::new(
+        &allocator,
+        source_map_path.unwrap_or(Path::new("module.ts")),
+        &TransformOptions::default(),
+    )
+    .build_with_scoping(scoping, &mut program);
+    if !ret.errors.is_empty() {
+        return None;
+    }
+
+    let options = if minify {
+        CodegenOptions::minify()
+    } else {
+        CodegenOptions::default()
+    };
+    let ret = Codegen::new()
+        .with_options(CodegenOptions {
+            source_map_path: source_map_path.map(Path::to_path_buf),
+            ..options
+        })
+        .build(&program);
+    Some((ret.code, ret.map))
+}
@@ -0,0 +1,791 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use std::{
+    fmt,
+    path::{Component, Path, PathBuf},
+};
+
+use nova_vm::{
+    ecmascript::{
+        execution::{Agent, JsError, agent::ExceptionType},
+        types::{InternalMethods, Object},
+    },
+    engine::context::NoGcScope,
+};
+use serde::{Deserialize, Serialize};
+
+use owo_colors::OwoColorize;
+
+use crate::{HostData, read_line};
+
+/// Kind of host capability guarded by [Permissions].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PermissionKind {
+    /// Reading from the file system.
+    Read,
+    /// Writing to the file system.
+    Write,
+    /// Opening network connections or listening on sockets.
+    Net,
+    /// Reading or modifying environment variables.
+    Env,
+    /// Loading dynamic libraries through the FFI.
+    Ffi,
+    /// Spawning subprocesses.
+    Run,
+}
+
+impl PermissionKind {
+    /// Name of the permission as used by the `--allow-<name>` flags.
+    pub fn name(&self) -> &'static str {
+        match self {
+            PermissionKind::Read => "read",
+            PermissionKind::Write => "write",
+            PermissionKind::Net => "net",
+            PermissionKind::Env => "env",
+            PermissionKind::Ffi => "ffi",
+            PermissionKind::Run => "run",
+        }
+    }
+
+    /// Parse a permission name such as `"read"` or `"net"`.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "read" => Some(PermissionKind::Read),
+            "write" => Some(PermissionKind::Write),
+            "net" => Some(PermissionKind::Net),
+            "env" => Some(PermissionKind::Env),
+            "ffi" => Some(PermissionKind::Ffi),
+            "run" => Some(PermissionKind::Run),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for PermissionKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// Error returned when an operation is not allowed by the active [Permissions].
+#[derive(Debug, Clone, PartialEq, thiserror::Error)]
+#[error("Requires {kind} access to {target}, run again with the --allow-{kind} flag")]
+pub struct PermissionDenied {
+    /// Kind of permission that was required.
+    pub kind: PermissionKind,
+    /// Human readable description of what was accessed.
+    pub target: String,
+}
+
+/// Result type for permission checks
+pub type PermissionResult = Result<(), PermissionDenied>;
+
+/// A value that can be granted by a [UnaryPermission], e.g. a path or a host.
+pub trait PermissionDescriptor: Clone + fmt::Debug + fmt::Display + PartialEq {
+    /// Whether this granted descriptor also grants access to `requested`.
+    fn covers(&self, requested: &Self) -> bool;
+}
+
+/// File system path descriptor used by the read, write and ffi permissions.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct PathDescriptor(pub PathBuf);
+
+impl PathDescriptor {
+    /// Create a descriptor from a path, resolving it against the current directory.
+    pub fn new(path: impl AsRef<Path>) -> Self {
+        Self(normalize_path(path.as_ref()))
+    }
+}
+
+impl PermissionDescriptor for PathDescriptor {
+    fn covers(&self, requested: &Self) -> bool {
+        requested.0.starts_with(&self.0)
+    }
+}
+
+impl fmt::Display for PathDescriptor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "\"{}\"", self.0.display())
+    }
+}
+
+/// Network descriptor used by the net permission, e.g. `example.com` or `127.0.0.1:8080`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct NetDescriptor {
+    pub host: String,
+    /// `None` grants every port of the host.
+    pub port: Option<u16>,
+}
+
+impl NetDescriptor {
+    pub fn new(host: impl Into<String>, port: Option<u16>) -> Self {
+        Self {
+            host: host.into().to_lowercase(),
+            port,
+        }
+    }
+
+    /// Parse a `host`, `host:port` or `[ipv6]:port` string.
+    pub fn parse(value: &str) -> Option<Self> {
+        let value = value.trim();
+        if value.is_empty() {
+            return None;
+        }
+
+        if let Some(rest) = value.strip_prefix('[') {
+            let (host, rest) = rest.split_once(']')?;
+            let port = match rest.strip_prefix(':') {
+                Some(port) => Some(port.parse().ok()?),
+                None if rest.is_empty() => None,
+                None => return None,
+            };
+            return Some(Self::new(host, port));
+        }
+
+        // A bare IPv6 address contains several colons and no port
+        if value.matches(':').count() > 1 {
+            return Some(Self::new(value, None));
+        }
+
+        match value.rsplit_once(':') {
+            Some((host, port)) => Some(Self::new(host, Some(port.parse().ok()?))),
+            None => Some(Self::new(value, None)),
+        }
+    }
+
+    /// Build a descriptor from a URL, using the scheme default port when none is given.
+    pub fn from_url(url: &url::Url) -> Option<Self> {
+        let host = url.host_str()?;
+        let host = host.trim_start_matches('[').trim_end_matches(']');
+        Some(Self::new(host, url.port_or_known_default()))
+    }
+}
+
+impl PermissionDescriptor for NetDescriptor {
+    fn covers(&self, requested: &Self) -> bool {
+        self.host == requested.host && (self.port.is_none() || self.port == requested.port)
+    }
+}
+
+impl fmt::Display for NetDescriptor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (self.host.contains(':'), self.port) {
+            (true, Some(port)) => write!(f, "\"[{}]:{port}\"", self.host),
+            (false, Some(port)) => write!(f, "\"{}:{port}\"", self.host),
+            (_, None) => write!(f, "\"{}\"", self.host),
+        }
+    }
+}
+
+/// Environment variable descriptor used by the env permission.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct EnvDescriptor(pub String);
+
+impl EnvDescriptor {
+    pub fn new(key: impl Into<String>) -> Self {
+        let key = key.into();
+        // Environment variables are case insensitive on Windows
+        #[cfg(windows)]
+        let key = key.to_uppercase();
+        Self(key)
+    }
+}
+
+impl PermissionDescriptor for EnvDescriptor {
+    fn covers(&self, requested: &Self) -> bool {
+        self.0 == requested.0
+    }
+}
+
+impl fmt::Display for EnvDescriptor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "\"{}\"", self.0)
+    }
+}
+
+/// Program descriptor used by the run permission, either a command name or a path.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct RunDescriptor(pub String);
+
+impl RunDescriptor {
+    pub fn new(command: impl Into<String>) -> Self {
+        Self(command.into())
+    }
+}
+
+impl PermissionDescriptor for RunDescriptor {
+    fn covers(&self, requested: &Self) -> bool {
+        if self.0 == requested.0 {
+            return true;
+        }
+        // A bare command name like `git` also grants `git.exe`, but never a path
+        // to a program that happens to share its name
+        let is_bare = |command: &str| !command.contains(['/', '\\']);
+        is_bare(&self.0)
+            && is_bare(&requested.0)
+            && Path::new(&requested.0)
+                .file_stem()
+                .is_some_and(|stem| stem == self.0.as_str())
+    }
+}
+
+impl fmt::Display for RunDescriptor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "\"{}\"", self.0)
+    }
+}
+
+/// State of a permission as reported to JavaScript by `Andromeda.permissions`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PermissionState {
+    /// Access is allowed.
+    Granted,
+    /// Access is not allowed yet, the user will be asked on the next request.
+    Prompt,
+    /// Access is not allowed.
+    Denied,
+}
+
+impl PermissionState {
+    /// Name of the state as used by the web `PermissionStatus.state`.
+    pub fn name(&self) -> &'static str {
+        match self {
+            PermissionState::Granted => "granted",
+            PermissionState::Prompt => "prompt",
+            PermissionState::Denied => "denied",
+        }
+    }
+}
+
+impl fmt::Display for PermissionState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// Answer given by the user to a permission prompt.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PromptResponse {
+    /// Allow the requested target.
+    Allow,
+    /// Allow every target of the requested kind.
+    AllowAll,
+    /// Deny the requested target.
+    Deny,
+}
+
+/// Permission state of a single [PermissionKind].
+#[derive(Debug, Clone, PartialEq)]
+pub struct UnaryPermission<T: PermissionDescriptor> {
+    kind: PermissionKind,
+    /// Every target of this kind is granted.
+    granted_global: bool,
+    /// Targets explicitly granted.
+    granted_list: Vec<T>,
+    /// Every target of this kind was denied at a prompt.
+    denied_global: bool,
+    /// Targets denied at a prompt.
+    denied_list: Vec<T>,
+    /// Ask the user on the terminal when a target is neither granted nor denied.
+    prompt: bool,
+}
+
+impl<T: PermissionDescriptor> UnaryPermission<T> {
+    /// Create a permission from an allowlist. `None` grants nothing, an empty list grants
+    /// everything and a non-empty list only grants the listed targets.
+    pub fn new(kind: PermissionKind, allowlist: Option<Vec<T>>) -> Self {
+        let (granted_global, granted_list) = match allowlist {
+            None => (false, Vec::new()),
+            Some(list) => (list.is_empty(), list),
+        };
+        Self {
+            kind,
+            granted_global,
+            granted_list,
+            denied_global: false,
+            denied_list: Vec::new(),
+            prompt: false,
+        }
+    }
+
+    /// Create a permission granting every target.
+    pub fn allow_all(kind: PermissionKind) -> Self {
+        Self::new(kind, Some(Vec::new()))
+    }
+
+    /// Enable or disable prompting for targets that are not granted.
+    pub fn set_prompt(&mut self, prompt: bool) {
+        self.prompt = prompt;
+    }
+
+    /// Whether the given target is granted. `None` asks for every target of this kind.
+    pub fn is_granted(&self, descriptor: Option<&T>) -> bool {
+        if self.granted_global {
+            return true;
+        }
+        match descriptor {
+            Some(descriptor) => self
+                .granted_list
+                .iter()
+                .any(|granted| granted.covers(descriptor)),
+            None => false,
+        }
+    }
+
+    fn is_denied(&self, descriptor: Option<&T>) -> bool {
+        if self.denied_global {
+            return true;
+        }
+        match descriptor {
+            Some(descriptor) => self
+                .denied_list
+                .iter()
+                .any(|denied| denied.covers(descriptor)),
+            None => false,
+        }
+    }
+
+    /// Current state of the given target without prompting.
+    pub fn query(&self, descriptor: Option<&T>) -> PermissionState {
+        if self.is_granted(descriptor) {
+            PermissionState::Granted
+        } else if self.prompt && !self.is_denied(descriptor) {
+            PermissionState::Prompt
+        } else {
+            PermissionState::Denied
+        }
+    }
+
+    /// Request access to the given target, prompting the user if needed.
+    /// The answer is remembered for the rest of the session.
+    pub fn request(&mut self, descriptor: Option<&T>) -> PermissionState {
+        let state = self.query(descriptor);
+        if state != PermissionState::Prompt {
+            return state;
+        }
+
+        match prompt_for_permission(self.kind, &self.target(descriptor)) {
+            PromptResponse::Allow => {
+                match descriptor {
+                    Some(descriptor) => self.granted_list.push(descriptor.clone()),
+                    None => self.granted_global = true,
+                }
+                PermissionState::Granted
+            }
+            PromptResponse::AllowAll => {
+                self.granted_global = true;
+                PermissionState::Granted
+            }
+            PromptResponse::Deny => {
+                match descriptor {
+                    Some(descriptor) => self.denied_list.push(descriptor.clone()),
+                    None => self.denied_global = true,
+                }
+                PermissionState::Denied
+            }
+        }
+    }
+
+    /// Revoke access to the given target and every target it covers.
+    /// `None` revokes every target of this kind.
+    pub fn revoke(&mut self, descriptor: Option<&T>) -> PermissionState {
+        match descriptor {
+            Some(descriptor) => {
+                self.granted_list
+                    .retain(|granted| !descriptor.covers(granted) && !granted.covers(descriptor));
+            }
+            None => self.granted_list.clear(),
+        }
+        self.granted_global = false;
+        self.query(descriptor)
+    }
+
+    /// Check that the given target is granted, prompting the user if needed.
+    /// `None` asks for every target of this kind.
+    pub fn check(&mut self, descriptor: Option<&T>) -> PermissionResult {
+        match self.request(descriptor) {
+            PermissionState::Granted => Ok(()),
+            _ => Err(PermissionDenied {
+                kind: self.kind,
+                target: self.target(descriptor),
+            }),
+        }
+    }
+
+    fn target(&self, descriptor: Option<&T>) -> String {
+        match descriptor {
+            Some(descriptor) => descriptor.to_string(),
+            None => format!("all {} targets", self.kind),
+        }
+    }
+}
+
+/// Ask the user on the terminal whether access should be granted.
+/// Access is denied when standard input or standard error is not a terminal.
+fn prompt_for_permission(kind: PermissionKind, target: &str) -> PromptResponse {
+    use std::io::{IsTerminal, Write};
+
+    if !std::io::stdin().is_terminal() || !std::io::stderr().is_terminal() {
+        return PromptResponse::Deny;
+    }
+
+    let mut stderr = std::io::stderr();
+    let _ = writeln!(
+        stderr,
+        "{} Andromeda requests {kind} access to {target}.",
+        "⚠️ ".yellow().bold()
+    );
+    let _ = writeln!(
+        stderr,
+        "   {}",
+        format!("Run again with --allow-{kind} to bypass this prompt.").dimmed()
+    );
+
+    loop {
+        let _ = write!(
+            stderr,
+            "   Allow? [y/n/A] (y = yes, allow; n = no, deny; A = allow all {kind} permissions) > "
+        );
+        let _ = stderr.flush();
+
+        let answer = match read_line() {
+            Ok(Some(answer)) => answer,
+            // End of input or a broken terminal can't grant anything
+            _ => return PromptResponse::Deny,
+        };
+
+        let response = match answer.trim() {
+            "y" | "Y" => PromptResponse::Allow,
+            "A" => PromptResponse::AllowAll,
+            "n" | "N" => PromptResponse::Deny,
+            _ => continue,
+        };
+
+        let message = match response {
+            PromptResponse::Allow => format!("✅ Granted {kind} access to {target}."),
+            PromptResponse::AllowAll => format!("✅ Granted all {kind} access."),
+            PromptResponse::Deny => format!("❌ Denied {kind} access to {target}."),
+        };
+        let _ = writeln!(stderr, "   {message}");
+        return response;
+    }
+}
+
+/// Options used to build [Permissions], usually coming from CLI flags and the config file.
+///
+/// For every `allow_*` list, `None` means the permission is not granted, an empty list
+/// grants it for every target and a non-empty list only grants the listed targets.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PermissionsOptions {
+    /// Grant every permission.
+    pub allow_all: bool,
+    /// Paths that can be read.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub allow_read: Option<Vec<String>>,
+    /// Paths that can be written.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub allow_write: Option<Vec<String>>,
+    /// Hosts that can be reached or listened on.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub allow_net: Option<Vec<String>>,
+    /// Environment variables that can be accessed.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub allow_env: Option<Vec<String>>,
+    /// Dynamic libraries that can be loaded.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub allow_ffi: Option<Vec<String>>,
+    /// Programs that can be spawned.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub allow_run: Option<Vec<String>>,
+    /// Prompt on the terminal for permissions that are not granted instead of denying them.
+    pub prompt: bool,
+}
+
+impl PermissionsOptions {
+    /// Options granting every permission.
+    pub fn allow_all() -> Self {
+        Self {
+            allow_all: true,
+            ..Default::default()
+        }
+    }
+
+    /// Merge another set of options into this one, granting the union of both.
+    pub fn merge(&mut self, other: PermissionsOptions) {
+        fn merge_list(target: &mut Option<Vec<String>>, other: Option<Vec<String>>) {
+            match (target.as_mut(), other) {
+                (_, None) => {}
+                (None, Some(other)) => *target = Some(other),
+                // An empty list already grants everything
+                (Some(list), Some(_)) if list.is_empty() => {}
+                (Some(list), Some(other)) if other.is_empty() => list.clear(),
+                (Some(list), Some(other)) => {
+                    for item in other {
+                        if !list.contains(&item) {
+                            list.push(item);
+                        }
+                    }
+                }
+            }
+        }
+
+        self.allow_all |= other.allow_all;
+        self.prompt |= other.prompt;
+        merge_list(&mut self.allow_read, other.allow_read);
+        merge_list(&mut self.allow_write, other.allow_write);
+        merge_list(&mut self.allow_net, other.allow_net);
+        merge_list(&mut self.allow_env, other.allow_env);
+        merge_list(&mut self.allow_ffi, other.allow_ffi);
+        merge_list(&mut self.allow_run, other.allow_run);
+    }
+}
+
+/// Set of permissions consulted by the extension ops before touching the host.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Permissions {
+    pub read: UnaryPermission<PathDescriptor>,
+    pub write: UnaryPermission<PathDescriptor>,
+    pub net: UnaryPermission<NetDescriptor>,
+    pub env: UnaryPermission<EnvDescriptor>,
+    pub ffi: UnaryPermission<PathDescriptor>,
+    pub run: UnaryPermission<RunDescriptor>,
+}
+
+impl Default for Permissions {
+    fn default() -> Self {
+        Self::from_options(&PermissionsOptions::default())
+    }
+}
+
+impl Permissions {
+    /// Permissions granting access to everything.
+    pub fn allow_all() -> Self {
+        Self {
+            read: UnaryPermission::allow_all(PermissionKind::Read),
+            write: UnaryPermission::allow_all(PermissionKind::Write),
+            net: UnaryPermission::allow_all(PermissionKind::Net),
+            env: UnaryPermission::allow_all(PermissionKind::Env),
+            ffi: UnaryPermission::allow_all(PermissionKind::Ffi),
+            run: UnaryPermission::allow_all(PermissionKind::Run),
+        }
+    }
+
+    /// Build the permissions described by the given [PermissionsOptions].
+    /// Relative paths are resolved against the current working directory.
+    pub fn from_options(options: &PermissionsOptions) -> Self {
+        if options.allow_all {
+            return Self::allow_all();
+        }
+
+        let paths = |list: &Option<Vec<String>>| {
+            list.as_ref()
+                .map(|list| list.iter().map(PathDescriptor::new).collect())
+        };
+
+        let mut permissions = Self {
+            read: UnaryPermission::new(PermissionKind::Read, paths(&options.allow_read)),
+            write: UnaryPermission::new(PermissionKind::Write, paths(&options.allow_write)),
+            net: UnaryPermission::new(
+                PermissionKind::Net,
+                options.allow_net.as_ref().and_then(|list| {
+                    let hosts: Vec<_> = list
+                        .iter()
+                        .filter_map(|s| NetDescriptor::parse(s))
+                        .collect();
+                    // A list of only invalid entries grants nothing rather than everything
+                    (hosts.is_empty() == list.is_empty()).then_some(hosts)
+                }),
+            ),
+            env: UnaryPermission::new(
+                PermissionKind::Env,
+                options
+                    .allow_env
+                    .as_ref()
+                    .map(|list| list.iter().map(EnvDescriptor::new).collect()),
+            ),
+            ffi: UnaryPermission::new(PermissionKind::Ffi, paths(&options.allow_ffi)),
+            run: UnaryPermission::new(
+                PermissionKind::Run,
+                options
+                    .allow_run
+                    .as_ref()
+                    .map(|list| list.iter().map(RunDescriptor::new).collect()),
+            ),
+        };
+        permissions.set_prompt(options.prompt);
+        permissions
+    }
+
+    /// Enable or disable prompting for every permission kind.
+    pub fn set_prompt(&mut self, prompt: bool) {
+        self.read.set_prompt(prompt);
+        self.write.set_prompt(prompt);
+        self.net.set_prompt(prompt);
+        self.env.set_prompt(prompt);
+        self.ffi.set_prompt(prompt);
+        self.run.set_prompt(prompt);
+    }
+
+    /// Current state of a permission without prompting. `None` queries every target.
+    pub fn query(&self, kind: PermissionKind, target: Option<&str>) -> PermissionState {
+        match kind {
+            PermissionKind::Read => self.read.query(target.map(PathDescriptor::new).as_ref()),
+            PermissionKind::Write => self.write.query(target.map(PathDescriptor::new).as_ref()),
+            PermissionKind::Net => self.net.query(target.map(net_target).as_ref()),
+            PermissionKind::Env => self.env.query(target.map(EnvDescriptor::new).as_ref()),
+            PermissionKind::Ffi => self.ffi.query(target.map(PathDescriptor::new).as_ref()),
+            PermissionKind::Run => self.run.query(target.map(RunDescriptor::new).as_ref()),
+        }
+    }
+
+    /// Request a permission, prompting the user if needed. `None` requests every target.
+    pub fn request(&mut self, kind: PermissionKind, target: Option<&str>) -> PermissionState {
+        match kind {
+            PermissionKind::Read => self.read.request(target.map(PathDescriptor::new).as_ref()),
+            PermissionKind::Write => self.write.request(target.map(PathDescriptor::new).as_ref()),
+            PermissionKind::Net => self.net.request(target.map(net_target).as_ref()),
+            PermissionKind::Env => self.env.request(target.map(EnvDescriptor::new).as_ref()),
+            PermissionKind::Ffi => self.ffi.request(target.map(PathDescriptor::new).as_ref()),
+            PermissionKind::Run => self.run.request(target.map(RunDescriptor::new).as_ref()),
+        }
+    }
+
+    /// Revoke a permission. `None` revokes every target.
+    pub fn revoke(&mut self, kind: PermissionKind, target: Option<&str>) -> PermissionState {
+        match kind {
+            PermissionKind::Read => self.read.revoke(target.map(PathDescriptor::new).as_ref()),
+            PermissionKind::Write => self.write.revoke(target.map(PathDescriptor::new).as_ref()),
+            PermissionKind::Net => self.net.revoke(target.map(net_target).as_ref()),
+            PermissionKind::Env => self.env.revoke(target.map(EnvDescriptor::new).as_ref()),
+            PermissionKind::Ffi => self.ffi.revoke(target.map(PathDescriptor::new).as_ref()),
+            PermissionKind::Run => self.run.revoke(target.map(RunDescriptor::new).as_ref()),
+        }
+    }
+
+    /// Check read access to a file system path.
+    pub fn check_read(&mut self, path: &Path) -> PermissionResult {
+        self.read.check(Some(&PathDescriptor::new(path)))
+    }
+
+    /// Check write access to a file system path.
+    pub fn check_write(&mut self, path: &Path) -> PermissionResult {
+        self.write.check(Some(&PathDescriptor::new(path)))
+    }
+
+    /// Check network access to a host, optionally restricted to a port.
+    pub fn check_net(&mut self, host: &str, port: Option<u16>) -> PermissionResult {
+        let host = host.trim_start_matches('[').trim_end_matches(']');
+        self.net.check(Some(&NetDescriptor::new(host, port)))
+    }
+
+    /// Check network access to a `host:port` address.
+    pub fn check_net_addr(&mut self, addr: &str) -> PermissionResult {
+        match NetDescriptor::parse(addr) {
+            Some(descriptor) => self.net.check(Some(&descriptor)),
+            None => self.net.check(None),
+        }
+    }
+
+    /// Check network access to the host of a URL.
+    pub fn check_net_url(&mut self, url: &url::Url) -> PermissionResult {
+        match NetDescriptor::from_url(url) {
+            Some(descriptor) => self.net.check(Some(&descriptor)),
+            None => self.net.check(None),
+        }
+    }
+
+    /// Check access to a single environment variable.
+    pub fn check_env(&mut self, key: &str) -> PermissionResult {
+        self.env.check(Some(&EnvDescriptor::new(key)))
+    }
+
+    /// Check access to every environment variable, e.g. to list them.
+    pub fn check_env_all(&mut self) -> PermissionResult {
+        self.env.check(None)
+    }
+
+    /// Check that a program can be spawned.
+    pub fn check_run(&mut self, command: &str) -> PermissionResult {
+        self.run.check(Some(&RunDescriptor::new(command)))
+    }
+
+    /// Check that a dynamic library can be loaded.
+    pub fn check_ffi(&mut self, path: &Path) -> PermissionResult {
+        self.ffi.check(Some(&PathDescriptor::new(path)))
+    }
+}
+
+/// Parse a net target given by JavaScript, treating unparsable values as a bare host.
+fn net_target(target: &str) -> NetDescriptor {
+    NetDescriptor::parse(target).unwrap_or_else(|| NetDescriptor::new(target, None))
+}
+
+/// Resolve a path against the current directory and lexically remove `.` and `..`
+/// components so it can be compared against the granted paths.
+pub fn normalize_path(path: &Path) -> PathBuf {
+    let absolute = if path.is_absolute() {
+        path.to_path_buf()
+    } else {
+        std::env::current_dir()
+            .unwrap_or_else(|_| PathBuf::from("."))
+            .join(path)
+    };
+
+    let mut normalized = PathBuf::new();
+    for component in absolute.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            other => normalized.push(other.as_os_str()),
+        }
+    }
+    normalized
+}
+
+/// Run a permission check against the [Permissions] stored in the agent's [HostData].
+///
+/// Only a shared borrow of the agent is needed so it can be called while strings owned by
+/// the agent are still borrowed; use [PermissionDenied::into_js_error] to throw the error.
+pub fn check_permission<UserMacroTask: 'static>(
+    agent: &Agent,
+    check: impl FnOnce(&mut Permissions) -> PermissionResult,
+) -> PermissionResult {
+    let host_data: &HostData<UserMacroTask> = agent.get_host_data().downcast_ref().unwrap();
+    check(&mut host_data.permissions.borrow_mut())
+}
+
+impl PermissionDenied {
+    /// Throw this error into the agent as an `Andromeda.errors.PermissionDenied` error.
+    pub fn into_js_error<'gc, UserMacroTask: 'static>(
+        self,
+        agent: &mut Agent,
+        gc: NoGcScope<'gc, '_>,
+    ) -> JsError<'gc> {
+        let host_data: &HostData<UserMacroTask> = agent.get_host_data().downcast_ref().unwrap();
+        let Some(prototype) = host_data.permission_denied_prototype.take() else {
+            // Without the namespace the error can only be told apart by its message
+            return agent.throw_exception(
+                ExceptionType::Error,
+                format!("PermissionDenied: {self}"),
+                gc,
+            );
+        };
+
+        let error = agent.throw_exception(ExceptionType::Error, self.to_string(), gc);
+        if let Ok(object) = Object::try_from(error.value()) {
+            let prototype_object = prototype.get(agent, gc);
+            let _ = object.try_set_prototype_of(agent, Some(prototype_object), gc);
+        }
+        let host_data: &HostData<UserMacroTask> = agent.get_host_data().downcast_ref().unwrap();
+        host_data
+            .permission_denied_prototype
+            .replace(Some(prototype));
+        error
+    }
+}
@@ -7,14 +7,19 @@ use std::{
     borrow::BorrowMut,
     cell::RefCell,
     collections::VecDeque,
-    path::{Path, PathBuf},
+    path::PathBuf,
+    ptr::NonNull,
+    rc::Rc,
     str,
-    sync::{atomic::Ordering, mpsc::Receiver},
+    sync::{Arc, Mutex, atomic::Ordering, mpsc::Receiver},
 };
 
 use nova_vm::{
     ecmascript::{
-        builtins::promise_objects::promise_abstract_operations::promise_capability_records::PromiseCapability,
+        builtins::{
+            ArgumentsList, Behaviour, BuiltinFunctionArgs, create_builtin_function,
+            promise_objects::promise_abstract_operations::promise_capability_records::PromiseCapability,
+        },
         execution::{
             Agent, JsResult,
             agent::{ExceptionType, GcAgent, HostHooks, Job, Options, RealmRoot},
@@ -41,15 +46,25 @@ use nova_vm::{
 };
 
 use crate::{
-    AndromedaError, AndromedaResult, Extension, HostData, MacroTask, exit_with_parse_errors,
-    module::ImportMap,
+    AndromedaError, AndromedaResult, Coverage, DependencyGraph, EmbeddedFs, Extension, HostData,
+    ImportType, JsrResolver, MacroTask, ModuleCache, ModuleError, ModuleResolver, ModuleResult,
+    RemoteModule, TranspileCache, exit_with_parse_errors, module::ImportMap, wrap_commonjs,
 };
 
 pub struct RuntimeHostHooks<UserMacroTask> {
     pub(crate) promise_job_queue: RefCell<VecDeque<Job>>,
     pub(crate) host_data: HostData<UserMacroTask>,
-    pub(crate) base_path: PathBuf,
-    pub(crate) import_map: Option<ImportMap>,
+    pub(crate) resolver: ModuleResolver,
+    /// Instruments the loaded modules when collecting coverage.
+    pub(crate) coverage: Option<Rc<Coverage>>,
+    /// Records the loaded modules and their imports.
+    pub(crate) module_graph: Option<Arc<Mutex<DependencyGraph>>>,
+    /// Disk cache of the remote modules.
+    pub(crate) module_cache: Option<Arc<ModuleCache>>,
+    /// Disk cache of the compiled TypeScript modules.
+    pub(crate) transpile_cache: Option<Arc<TranspileCache>>,
+    /// URL of the entry module, for `import.meta.main`.
+    pub(crate) main_module: Option<String>,
 }
 
 impl<UserMacroTask> std::fmt::Debug for RuntimeHostHooks<UserMacroTask> {
@@ -63,8 +78,15 @@ impl<UserMacroTask> RuntimeHostHooks<UserMacroTask> {
         Self {
             promise_job_queue: RefCell::default(),
             host_data,
-            base_path: std::env::current_dir().unwrap_or_else(|_| PathBuf::from(".")),
-            import_map: None,
+            resolver: ModuleResolver::new(
+                std::env::current_dir().unwrap_or_else(|_| PathBuf::from(".")),
+                None,
+            ),
+            coverage: None,
+            module_graph: None,
+            module_cache: None,
+            transpile_cache: None,
+            main_module: None,
         }
     }
 
@@ -72,8 +94,12 @@ impl<UserMacroTask> RuntimeHostHooks<UserMacroTask> {
         Self {
             promise_job_queue: RefCell::default(),
             host_data,
-            base_path,
-            import_map: None,
+            resolver: ModuleResolver::new(base_path, None),
+            coverage: None,
+            module_graph: None,
+            module_cache: None,
+            transpile_cache: None,
+            main_module: None,
         }
     }
 
@@ -85,159 +111,90 @@ impl<UserMacroTask> RuntimeHostHooks<UserMacroTask> {
         Self {
             promise_job_queue: RefCell::default(),
             host_data,
-            base_path,
-            import_map: Some(import_map),
+            resolver: ModuleResolver::new(base_path, Some(import_map)),
+            coverage: None,
+            module_graph: None,
+            module_cache: None,
+            transpile_cache: None,
+            main_module: None,
         }
     }
 
-    pub fn pop_promise_job(&self) -> Option<Job> {
-        self.promise_job_queue.borrow_mut().pop_front()
-    }
-
-    pub fn any_pending_macro_tasks(&self) -> bool {
-        self.host_data.macro_task_count.load(Ordering::Acquire) > 0
+    pub fn host_data(&self) -> &HostData<UserMacroTask> {
+        &self.host_data
     }
 
-    /// Resolve a module specifier relative to a referrer path
-    fn resolve_module_specifier(&self, specifier: &str, referrer_path: &Path) -> String {
-        // Try import map resolution first for bare specifiers
-        if let Some(import_map) = &self.import_map
-            && !specifier.starts_with("./")
-            && !specifier.starts_with("../")
-            && !specifier.starts_with("/")
-            && !specifier.contains("://")
-        {
-            // This is a bare specifier, try import map resolution
-            let base_url = referrer_path.to_string_lossy();
-            if let Some(mapped_specifier) = import_map.resolve_specifier(specifier, Some(&base_url))
-            {
-                // Use the mapped specifier for resolution
-                return mapped_specifier;
+    /// Add a loaded module to the module graph, if one is recorded.
+    fn record_module(&self, referrer: Option<&str>, module: &str) {
+        if let Some(module_graph) = &self.module_graph {
+            let mut module_graph = module_graph.lock().unwrap();
+            match referrer {
+                Some(referrer) => module_graph.add_dependency(referrer, module),
+                None => module_graph.add_module(module),
             }
         }
+    }
 
-        // Handle HTTP URLs directly
-        if specifier.starts_with("http://") || specifier.starts_with("https://") {
-            specifier.to_string()
-        } else {
-            // Check if referrer is a URL (HTTP/HTTPS)
-            let referrer_str = referrer_path.to_string_lossy();
-            if referrer_str.starts_with("http://") || referrer_str.starts_with("https://") {
-                // Use URL joining for URL-to-URL resolution
-                self.resolve_url_specifier(specifier, &referrer_str)
+    /// Read the module at `specifier` from the disk cache, the network or the
+    /// file system, and turn it into the source of an ES module: a synthetic
+    /// module for the JSON, text and bytes import types, or the wrapped module
+    /// for CommonJS modules.
+    fn load_module_source(&self, specifier: &str, import_type: ImportType) -> ModuleResult<String> {
+        let (content, content_type) =
+            if specifier.starts_with("http://") || specifier.starts_with("https://") {
+                // HTTP import - embedded in compiled executables, or loaded
+                // from the module cache when there is one
+                if let Some(content) = EmbeddedFs::get(specifier) {
+                    (content.to_vec(), None)
+                } else {
+                    let module = match &self.module_cache {
+                        Some(module_cache) => module_cache.load_remote(specifier)?,
+                        None => fetch_remote_module(specifier)?,
+                    };
+                    (module.content, module.content_type)
+                }
             } else {
-                // Use file path resolution for file-to-file resolution
-                self.resolve_path_specifier(specifier, referrer_path)
-                    .to_string_lossy()
-                    .to_string()
-            }
-        }
-    }
+                let content = EmbeddedFs::read(specifier).map_err(|e| ModuleError::Io {
+                    message: format!("Failed to read module {specifier}: {e}"),
+                })?;
+                (content, None)
+            };
 
-    /// Resolve a path-based specifier (internal helper)
-    fn resolve_path_specifier(&self, specifier: &str, referrer_path: &Path) -> PathBuf {
-        if specifier.starts_with("http://") || specifier.starts_with("https://") {
-            // For HTTP URLs, return the URL as a path-like string
-            // We'll handle this specially in the loading logic
-            PathBuf::from(specifier)
-        } else if specifier.starts_with("./") || specifier.starts_with("../") {
-            // Relative import
-            let referrer_dir = referrer_path.parent().unwrap_or(&self.base_path);
-            referrer_dir.join(specifier)
-        } else if specifier.starts_with("/") {
-            // Absolute import
-            PathBuf::from(specifier)
-        } else {
-            // Relative to base path or bare specifier
-            self.base_path.join(specifier)
+        import_type.check(specifier, content_type.as_deref())?;
+        let source_text = import_type.module_source(specifier, &content)?;
+        if import_type != ImportType::JavaScript {
+            return Ok(source_text);
         }
-    }
 
-    /// Resolve a URL-based specifier (internal helper for URL-to-URL resolution)
-    fn resolve_url_specifier(&self, specifier: &str, referrer_url: &str) -> String {
-        if specifier.starts_with("http://") || specifier.starts_with("https://") {
-            // Already a full URL
-            specifier.to_string()
+        if self
+            .resolver
+            .npm
+            .is_commonjs(std::path::Path::new(specifier))
+        {
+            // CommonJS modules are wrapped into ES modules
+            Ok(wrap_commonjs(&self.resolver.npm, specifier, &source_text))
+        } else if let Some(coverage) = &self.coverage {
+            Ok(coverage.instrument(specifier, source_text))
         } else {
-            // Use URL joining for relative imports
-            match url::Url::parse(referrer_url) {
-                Ok(base_url) => {
-                    match base_url.join(specifier) {
-                        Ok(resolved_url) => resolved_url.to_string(),
-                        Err(_) => {
-                            // If URL joining fails, fall back to simple concatenation
-                            // This shouldn't happen with valid URLs, but provides a fallback
-                            if let Some(stripped) = specifier.strip_prefix("./") {
-                                format!("{}/{}", referrer_url.trim_end_matches('/'), stripped)
-                            } else if let Some(stripped) = specifier.strip_prefix("../") {
-                                // Simple fallback for parent directory navigation
-                                let mut base = referrer_url.trim_end_matches('/');
-                                if let Some(last_slash) = base.rfind('/') {
-                                    base = &base[..last_slash];
-                                }
-                                format!("{base}/{stripped}")
-                            } else {
-                                format!("{}/{}", referrer_url.trim_end_matches('/'), specifier)
-                            }
-                        }
-                    }
-                }
-                Err(_) => {
-                    // If base URL parsing fails, use simple string concatenation as fallback
-                    if let Some(stripped) = specifier.strip_prefix("./") {
-                        format!("{}/{}", referrer_url.trim_end_matches('/'), stripped)
-                    } else if let Some(stripped) = specifier.strip_prefix("../") {
-                        // Simple fallback for parent directory navigation
-                        let mut base = referrer_url.trim_end_matches('/');
-                        if let Some(last_slash) = base.rfind('/') {
-                            base = &base[..last_slash];
-                        }
-                        format!("{base}/{stripped}")
-                    } else {
-                        format!("{}/{}", referrer_url.trim_end_matches('/'), specifier)
-                    }
-                }
-            }
+            Ok(self.compiled_source(specifier, source_text))
         }
     }
 
-    /// Resolve module file with proper extension handling
-    fn resolve_extensions(&self, path: PathBuf) -> Option<PathBuf> {
-        let path_str = path.to_string_lossy();
-
-        // Handle HTTP URLs - they don't need file system extension resolution
-        if path_str.starts_with("http://") || path_str.starts_with("https://") {
-            return Some(path);
-        }
-
-        // First try the path as-is
-        if path.exists() {
-            return Some(path);
-        }
-
-        // Get the base path without extension for trying alternatives
-        let path_stem = path.with_extension("");
-
-        // Try different extensions in order of preference
-        for ext in &["ts", "js", "mjs", "json"] {
-            let candidate = path_stem.with_extension(ext);
-            if candidate.exists() {
-                return Some(candidate);
-            }
+    /// The code a module is evaluated as, transpiled through the transpile cache
+    /// when there is one
+    fn compiled_source(&self, specifier: &str, source_text: String) -> String {
+        match &self.transpile_cache {
+            Some(transpile_cache) => transpile_cache.load(specifier, source_text),
+            None => source_text,
         }
+    }
 
-        // If the original import had an extension but we didn't find it,
-        // try the path without extension (for cases like './math' -> './math.ts')
-        if path.extension().is_none() {
-            for ext in &["ts", "js", "mjs", "json"] {
-                let candidate = path.with_extension(ext);
-                if candidate.exists() {
-                    return Some(candidate);
-                }
-            }
-        }
+    pub fn pop_promise_job(&self) -> Option<Job> {
+        self.promise_job_queue.borrow_mut().pop_front()
+    }
 
-        None
+    pub fn any_pending_macro_tasks(&self) -> bool {
+        self.host_data.macro_task_count.load(Ordering::Acquire) > 0
     }
 }
 
@@ -275,139 +232,74 @@ impl<UserMacroTask: 'static> HostHooks for RuntimeHostHooks<UserMacroTask> {
         let specifier = module_request.specifier(agent);
         let specifier_str = specifier.to_string_lossy(agent);
 
-        // Extract referrer information properly from Nova VM
-        let referrer_info = referrer.host_defined(agent);
-        let referrer_str = if let Some(host_defined) = referrer_info {
-            // Try to get the referrer module path from host_defined
-            if let Some(path_rc) = host_defined.downcast_ref::<std::rc::Rc<String>>() {
-                path_rc.as_str().to_string()
-            } else if let Some(path_rc) = host_defined.downcast_ref::<std::rc::Rc<PathBuf>>() {
-                path_rc.to_string_lossy().to_string()
-            } else if let Some(string_val) = host_defined.downcast_ref::<String>() {
-                string_val.clone()
-            } else {
-                // Fallback to default base path
-                self.base_path.to_string_lossy().to_string()
+        // Extract referrer information properly from Nova VM, falling back to
+        // the runtime's base_path
+        let referrer_str = module_specifier(agent, referrer)
+            .unwrap_or_else(|| self.resolver.base_path.to_string_lossy().to_string());
+
+        // Resolve the module specifier using the proper referrer
+        let final_specifier = match self.resolver.resolve(&specifier_str, &referrer_str) {
+            Ok(final_specifier) => final_specifier,
+            Err(resolved_specifier) => {
+                // Module not found error
+                let error = agent.throw_exception(
+                    ExceptionType::TypeError,
+                    format!("Module not found: {resolved_specifier}"),
+                    gc,
+                );
+                finish_loading_imported_module(
+                    agent,
+                    referrer,
+                    module_request,
+                    payload,
+                    Err(error),
+                    gc,
+                );
+                return;
             }
-        } else {
-            // Use runtime's base_path as fallback
-            self.base_path.to_string_lossy().to_string()
         };
 
-        // Resolve the module specifier using the proper referrer
-        let resolved_specifier =
-            if referrer_str.starts_with("http://") || referrer_str.starts_with("https://") {
-                // Referrer is a URL, use URL-based resolution
-                self.resolve_url_specifier(&specifier_str, &referrer_str)
-            } else {
-                // Referrer is a file path, use file-based resolution
-                let referrer_path = PathBuf::from(&referrer_str);
-                self.resolve_module_specifier(&specifier_str, &referrer_path)
-            };
+        self.record_module(Some(&referrer_str), &final_specifier);
 
-        // For HTTP URLs, skip extension resolution; for file paths, try to resolve extensions
-        let final_specifier = if resolved_specifier.starts_with("http://")
-            || resolved_specifier.starts_with("https://")
-        {
-            resolved_specifier
-        } else {
-            let path_buf = PathBuf::from(&resolved_specifier);
-            match self.resolve_extensions(path_buf) {
-                Some(path) => path.to_string_lossy().to_string(),
-                None => {
-                    // Module not found error
-                    let error = agent.throw_exception(
-                        ExceptionType::TypeError,
-                        format!("Module not found: {resolved_specifier}"),
-                        gc,
-                    );
-                    finish_loading_imported_module(
-                        agent,
-                        referrer,
-                        module_request,
-                        payload,
-                        Err(error),
-                        gc,
-                    );
-                    return;
-                }
+        // The `type` import attribute selects JSON, text and bytes modules
+        let import_type = match ImportType::from_attribute(
+            &final_specifier,
+            import_type_attribute(agent, module_request).as_deref(),
+        ) {
+            Ok(import_type) => import_type,
+            Err(error) => {
+                let error = agent.throw_exception(ExceptionType::TypeError, error.to_string(), gc);
+                finish_loading_imported_module(
+                    agent,
+                    referrer,
+                    module_request,
+                    payload,
+                    Err(error),
+                    gc,
+                );
+                return;
             }
         };
 
-        // Check if this is a JSON module
-        let is_json = final_specifier.ends_with(".json");
-
         // Read the module source - handle both file system and HTTP URLs
-        let source_text =
-            if final_specifier.starts_with("http://") || final_specifier.starts_with("https://") {
-                // HTTP import - fetch from network
-                match ureq::get(&final_specifier).call() {
-                    Ok(mut response) => match response.body_mut().read_to_string() {
-                        Ok(content) => content,
-                        Err(error) => {
-                            let error = agent.throw_exception(
-                                ExceptionType::TypeError,
-                                format!("Failed to read HTTP module {final_specifier}: {error}"),
-                                gc,
-                            );
-                            finish_loading_imported_module(
-                                agent,
-                                referrer,
-                                module_request,
-                                payload,
-                                Err(error),
-                                gc,
-                            );
-                            return;
-                        }
-                    },
-                    Err(error) => {
-                        let error = agent.throw_exception(
-                            ExceptionType::TypeError,
-                            format!("Failed to fetch HTTP module {final_specifier}: {error}"),
-                            gc,
-                        );
-                        finish_loading_imported_module(
-                            agent,
-                            referrer,
-                            module_request,
-                            payload,
-                            Err(error),
-                            gc,
-                        );
-                        return;
-                    }
-                }
-            } else {
-                // File system import - read from local file
-                let file_path = PathBuf::from(&final_specifier);
-                match std::fs::read_to_string(&file_path) {
-                    Ok(content) => content,
-                    Err(error) => {
-                        let error = agent.throw_exception(
-                            ExceptionType::TypeError,
-                            format!("Failed to read module {}: {}", file_path.display(), error),
-                            gc,
-                        );
-                        finish_loading_imported_module(
-                            agent,
-                            referrer,
-                            module_request,
-                            payload,
-                            Err(error),
-                            gc,
-                        );
-                        return;
-                    }
-                }
-            };
-
-        // Handle JSON modules specially
-        let final_source = if is_json {
-            // For JSON modules, wrap the JSON in a JavaScript module that exports it as default
-            format!("export default {source_text};")
-        } else {
-            source_text
+        let final_source = match self.load_module_source(&final_specifier, import_type) {
+            Ok(source) => source,
+            Err(error) => {
+                let exception_type = match error {
+                    ModuleError::ParseError { .. } => ExceptionType::SyntaxError,
+                    _ => ExceptionType::TypeError,
+                };
+                let error = agent.throw_exception(exception_type, error.to_string(), gc);
+                finish_loading_imported_module(
+                    agent,
+                    referrer,
+                    module_request,
+                    payload,
+                    Err(error),
+                    gc,
+                );
+                return;
+            }
         };
 
         // Convert to Nova string
@@ -449,64 +341,56 @@ impl<UserMacroTask: 'static> HostHooks for RuntimeHostHooks<UserMacroTask> {
     fn get_import_meta_properties<'gc>(
         &self,
         agent: &mut Agent,
-        module_record: SourceTextModule,
+        module_record: SourceTextModule<'gc>,
         gc: NoGcScope<'gc, '_>,
     ) -> Vec<(PropertyKey<'gc>, Value<'gc>)> {
-        let mut properties = Vec::new();
-
-        // Create import.meta.url property
-        let url_key = PropertyKey::from_str(agent, "url", gc);
-
         // Get the module specifier from the module's host_defined data
-        // Convert SourceTextModule to Referrer to access host_defined
-        let referrer = Referrer::from(module_record);
-        let module_url = if let Some(host_defined) = referrer.host_defined(agent) {
-            // Try to get the module specifier from host_defined
-            if let Some(specifier_rc) = host_defined.downcast_ref::<std::rc::Rc<String>>() {
-                let specifier = specifier_rc.as_str();
-                // If it's already a full URL, use it as-is; otherwise make it a file:// URL
-                if specifier.starts_with("http://") || specifier.starts_with("https://") {
-                    specifier.to_string()
-                } else {
-                    format!(
-                        "file://{}",
-                        std::fs::canonicalize(specifier)
-                            .unwrap_or_else(|_| std::path::PathBuf::from(specifier))
-                            .to_string_lossy()
-                    )
-                }
-            } else if let Some(specifier_string) = host_defined.downcast_ref::<String>() {
-                // If it's already a full URL, use it as-is; otherwise make it a file:// URL
-                if specifier_string.starts_with("http://")
-                    || specifier_string.starts_with("https://")
-                {
-                    specifier_string.clone()
-                } else {
-                    format!(
-                        "file://{}",
-                        std::fs::canonicalize(specifier_string)
-                            .unwrap_or_else(|_| std::path::PathBuf::from(specifier_string))
-                            .to_string_lossy()
-                    )
-                }
-            } else {
-                // Fallback to base_path
-                format!(
-                    "file://{}",
-                    self.base_path.join("script.js").to_string_lossy()
-                )
-            }
-        } else {
-            // Fallback to base_path
-            format!(
-                "file://{}",
-                self.base_path.join("script.js").to_string_lossy()
-            )
+        let url = match module_specifier(agent, Referrer::from(module_record)) {
+            Some(specifier) => module_url(&specifier),
+            // Modules without a specifier are given the directory of the entry point
+            None => directory_url(&self.resolver.base_path),
         };
+        let is_main = self.main_module.as_deref() == Some(url.as_str());
+        let path = url::Url::parse(&url)
+            .ok()
+            .filter(|url| url.scheme() == "file")
+            .and_then(|url| url.to_file_path().ok());
+
+        let mut properties = vec![
+            (
+                PropertyKey::from_static_str(agent, "url", gc),
+                Value::from_string(agent, url, gc),
+            ),
+            (
+                PropertyKey::from_static_str(agent, "main", gc),
+                Value::Boolean(is_main),
+            ),
+        ];
+
+        // Only local modules have a file name and a directory
+        if let Some(path) = path {
+            let dirname = path.parent().unwrap_or(&path).to_string_lossy().to_string();
+            let filename = path.to_string_lossy().to_string();
+            properties.push((
+                PropertyKey::from_static_str(agent, "filename", gc),
+                Value::from_string(agent, filename, gc),
+            ));
+            properties.push((
+                PropertyKey::from_static_str(agent, "dirname", gc),
+                Value::from_string(agent, dirname, gc),
+            ));
+        }
 
-        let url_value = Value::from_string(agent, module_url, gc);
-
-        properties.push((url_key, url_value));
+        let resolve = create_builtin_function(
+            agent,
+            Behaviour::Regular(import_meta_resolve::<UserMacroTask>),
+            BuiltinFunctionArgs::new(1, "resolve"),
+            gc,
+        );
+        properties.push((
+            PropertyKey::from_static_str(agent, "resolve", gc),
+            resolve.into_value(),
+        ));
 
         properties
     }
@@ -522,6 +406,134 @@ impl<UserMacroTask: 'static> HostHooks for RuntimeHostHooks<UserMacroTask> {
     }
 }
 
+/// The specifier a module was loaded with, from its host defined data
+fn module_specifier(agent: &Agent, referrer: Referrer) -> Option<String> {
+    let host_defined = referrer.host_defined(agent)?;
+    if let Some(specifier) = host_defined.downcast_ref::<std::rc::Rc<String>>() {
+        Some(specifier.as_str().to_string())
+    } else if let Some(path) = host_defined.downcast_ref::<std::rc::Rc<PathBuf>>() {
+        Some(path.to_string_lossy().to_string())
+    } else {
+        host_defined.downcast_ref::<String>().cloned()
+    }
+}
+
+/// The URL of the module at `specifier`: remote URLs are kept as is and paths
+/// become absolute `file://` URLs
+pub fn module_url(specifier: &str) -> String {
+    if specifier.contains("://") || specifier.starts_with("npm:") || specifier.starts_with("jsr:") {
+        return specifier.to_string();
+    }
+    let path = std::fs::canonicalize(specifier)
+        .or_else(|_| std::path::absolute(specifier))
+        .unwrap_or_else(|_| PathBuf::from(specifier));
+    url::Url::from_file_path(&path)
+        .map(|url| url.to_string())
+        .unwrap_or_else(|_| format!("file://{}", path.to_string_lossy()))
+}
+
+/// The `file://` URL of a directory, ending with a slash
+fn directory_url(dir: &std::path::Path) -> String {
+    let dir = std::path::absolute(dir).unwrap_or_else(|_| dir.to_path_buf());
+    url::Url::from_directory_path(&dir)
+        .map(|url| url.to_string())
+        .unwrap_or_else(|_| format!("file://{}/", dir.to_string_lossy()))
+}
+
+/// `import.meta.resolve(specifier)`, resolving `specifier` relative to the
+/// module whose `import.meta` is `this`, through the import map, into a URL.
+/// The resolved module doesn't have to exist.
+fn import_meta_resolve<'gc, UserMacroTask: 'static>(
+    agent: &mut Agent,
+    this: Value,
+    args: ArgumentsList,
+    mut gc: GcScope<'gc, '_>,
+) -> JsResult<'gc, Value<'gc>> {
+    let specifier = args.get(0).to_string(agent, gc.reborrow()).unbind()?;
+    let specifier = specifier
+        .as_str(agent)
+        .expect("String is not valid UTF-8")
+        .to_string();
+
+    let Ok(import_meta) = Object::try_from(this) else {
+        return Err(agent
+            .throw_exception_with_static_message(
+                ExceptionType::TypeError,
+                "import.meta.resolve must be called on import.meta",
+                gc.into_nogc(),
+            )
+            .unbind());
+    };
+    let url_key = PropertyKey::from_static_str(agent, "url", gc.nogc()).unbind();
+    let referrer = import_meta
+        .unbind()
+        .internal_get(
+            agent,
+            url_key,
+            import_meta.into_value().unbind(),
+            gc.reborrow(),
+        )
+        .unbind()?
+        .to_string(agent, gc.reborrow())
+        .unbind()?;
+    let referrer = referrer
+        .as_str(agent)
+        .expect("String is not valid UTF-8")
+        .to_string();
+
+    let host_data: &HostData<UserMacroTask> = agent.get_host_data().downcast_ref().unwrap();
+    let Some(resolver) = host_data.storage.borrow().get::<ModuleResolver>().cloned() else {
+        return Err(agent
+            .throw_exception_with_static_message(
+                ExceptionType::TypeError,
+                "import.meta.resolve is not available in this runtime",
+                gc.into_nogc(),
+            )
+            .unbind());
+    };
+    // Local modules resolve relative to their path, remote ones to their URL
+    let referrer_path = url::Url::parse(&referrer)
+        .ok()
+        .filter(|url| url.scheme() == "file")
+        .and_then(|url| url.to_file_path().ok())
+        .unwrap_or_else(|| PathBuf::from(&referrer));
+    let resolved = module_url(&resolver.resolve_module_specifier(&specifier, &referrer_path));
+
+    Ok(Value::from_string(agent, resolved, gc.into_nogc()))
+}
+
+/// The value of the `type` import attribute of `module_request`, if any
+fn import_type_attribute(agent: &Agent, module_request: ModuleRequest) -> Option<String> {
+    module_request
+        .attributes(agent)
+        .iter()
+        .find(|attribute| attribute.key(agent).to_string_lossy(agent) == "type")
+        .map(|attribute| attribute.value(agent).to_string_lossy(agent).to_string())
+}
+
+/// Fetch a remote module from the network, with its content type
+fn fetch_remote_module(url: &str) -> ModuleResult<RemoteModule> {
+    let mut response = ureq::get(url).call().map_err(|e| ModuleError::NotFound {
+        specifier: format!("{url}: {e}"),
+    })?;
+    let content_type = response
+        .headers()
+        .get("content-type")
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_string);
+    let content = response
+        .body_mut()
+        .read_to_vec()
+        .map_err(|e| ModuleError::RuntimeError {
+            path: url.to_string(),
+            message: format!("Failed to read HTTP module: {e}"),
+        })?;
+    Ok(RemoteModule {
+        content,
+        content_type,
+    })
+}
+
 pub type EventLoopHandler<UserMacroTask> = fn(
     macro_task: UserMacroTask,
     agent: &mut GcAgent,
@@ -529,6 +541,7 @@ pub type EventLoopHandler<UserMacroTask> = fn(
     host_data: &HostData<UserMacroTask>,
 );
 
+#[derive(Clone)]
 pub enum RuntimeFile {
     Embedded {
         path: String,
@@ -607,13 +620,31 @@ pub struct RuntimeConfig<UserMacroTask: 'static> {
     pub macro_task_rx: Receiver<MacroTask<UserMacroTask>>,
     /// Import map for module resolution
     pub import_map: Option<ImportMap>,
+    /// Collect the coverage of the modules that are run
+    pub coverage: Option<Rc<Coverage>>,
+    /// Record the loaded modules into this graph, e.g. to watch them for changes
+    pub module_graph: Option<Arc<Mutex<DependencyGraph>>>,
+    /// Load the remote modules through this disk cache instead of always fetching them
+    pub module_cache: Option<Arc<ModuleCache>>,
+    /// Arguments `Andromeda.args` returns, instead of the ones of the process
+    pub args: Option<Vec<String>>,
+    /// Evaluate the TypeScript modules as their code compiled through this
+    /// cache. Unused when collecting coverage, which instruments the sources
+    pub transpile_cache: Option<Arc<TranspileCache>>,
 }
 
+/// The arguments of the program, stored in the host data when they are not
+/// the ones of the process
+#[derive(Debug, Clone)]
+pub struct ProgramArgs(pub Vec<String>);
+
 pub struct Runtime<UserMacroTask: 'static> {
     pub config: RuntimeConfig<UserMacroTask>,
     pub agent: GcAgent,
     pub realm_root: RealmRoot,
-    pub host_hooks: &'static RuntimeHostHooks<UserMacroTask>,
+    host_hooks: &'static RuntimeHostHooks<UserMacroTask>,
+    // Declared after the agent so it is dropped once the agent is gone
+    owned_host_hooks: OwnedHostHooks<UserMacroTask>,
 }
 
 #[hotpath::measure_all]
@@ -636,13 +667,38 @@ impl<UserMacroTask> Runtime<UserMacroTask> {
             })
             .unwrap_or_else(|| std::env::current_dir().unwrap_or_else(|_| PathBuf::from(".")));
 
-        let host_hooks = if let Some(import_map) = config.import_map.clone() {
+        let mut host_hooks = if let Some(import_map) = config.import_map.clone() {
             RuntimeHostHooks::with_import_map(host_data, base_path, import_map)
         } else {
             RuntimeHostHooks::with_base_path(host_data, base_path)
         };
+        host_hooks.coverage = config.coverage.clone();
+        host_hooks.module_graph = config.module_graph.clone();
+        host_hooks.module_cache = config.module_cache.clone();
+        host_hooks.transpile_cache = config.transpile_cache.clone();
+        if let Some(module_cache) = &config.module_cache {
+            host_hooks.resolver.jsr =
+                JsrResolver::new(JsrResolver::default_registry_url(), module_cache.clone());
+        }
+        host_hooks.main_module = config.files.first().map(|file| module_url(file.get_path()));
+        // Shared with `import.meta.resolve()`
+        host_hooks
+            .host_data
+            .storage
+            .borrow_mut()
+            .insert(host_hooks.resolver.clone());
+        if let Some(args) = config.args.take() {
+            host_hooks
+                .host_data
+                .storage
+                .borrow_mut()
+                .insert(ProgramArgs(args));
+        }
 
-        let host_hooks: &RuntimeHostHooks<UserMacroTask> = &*Box::leak(Box::new(host_hooks));
+        let owned_host_hooks = OwnedHostHooks(NonNull::from(Box::leak(Box::new(host_hooks))));
+        // SAFETY: The hooks outlive the agent, which is dropped before them
+        let host_hooks: &'static RuntimeHostHooks<UserMacroTask> =
+            unsafe { owned_host_hooks.0.as_ref() };
         let mut agent = GcAgent::new(
             Options {
                 no_block: false,
@@ -697,11 +753,12 @@ impl<UserMacroTask> Runtime<UserMacroTask> {
             agent,
             realm_root,
             host_hooks,
+            owned_host_hooks,
         }
     }
 
     /// Run the Runtime with the specified configuration.
-    pub fn run(mut self) -> RuntimeOutput {
+    pub fn run(mut self) -> AndromedaResult<RuntimeOutput<UserMacroTask>> {
         // Load the builtins js sources
         self.agent.run_in_realm(&self.realm_root, |agent, mut gc| {
             for builtin in &self.config.builtins {
@@ -733,34 +790,83 @@ impl<UserMacroTask> Runtime<UserMacroTask> {
                 }
             }
         });
+        let mut result = match self.run_files() {
+            Ok(result) => result,
+            Err(error) => {
+                // Stop what the files run before the failing one left behind
+                self.host_hooks.host_data.abort_all_macro_tasks();
+                return Err(error);
+            }
+        };
+
+        loop {
+            while let Some(job) = self.host_hooks.pop_promise_job() {
+                result = self.agent.run_in_realm(&self.realm_root, |agent, mut gc| {
+                    job.run(agent, gc.reborrow()).unbind().map(|_| Value::Null)
+                });
+            }
+
+            if self.host_hooks.host_data.is_terminated() {
+                break;
+            }
+
+            // Try to handle a macro task without blocking
+            // This handles the case where a task completed so fast that the counter
+            // was already decremented but the message is still in the channel
+            let has_macro_task = self.try_handle_macro_task();
+
+            // Only exit if there are no pending tasks AND no message was processed
+            if !has_macro_task && !self.host_hooks.any_pending_macro_tasks() {
+                break;
+            }
+
+            // If we saw pending tasks but got no message, block waiting for one
+            if !has_macro_task && self.host_hooks.any_pending_macro_tasks() {
+                self.handle_macro_task();
+            }
+        }
+
+        Ok(RuntimeOutput {
+            agent: self.agent,
+            realm_root: self.realm_root,
+            result,
+            host_hooks: self.owned_host_hooks,
+        })
+    }
+
+    /// Validate and run the files of the runtime, returning the result of the
+    /// last one.
+    fn run_files(&mut self) -> AndromedaResult<JsResult<'static, Value<'static>>> {
         let mut result = JsResult::Ok(Value::Null);
 
         // Validate all files before execution
         for file in &self.config.files {
-            if let Err(error) = file.validate() {
-                eprintln!(
-                    "🚨 File validation error for {}: {}",
-                    file.get_path(),
-                    error
-                );
-                std::process::exit(1);
-            }
+            file.validate()?;
         }
 
         // Fetch the runtime mod.ts file using a macro and add it to the paths
         for file in &self.config.files {
-            let file_content = match file.read() {
-                Ok(content) => content,
-                Err(error) => {
-                    eprintln!("🚨 Failed to read file {}: {}", file.get_path(), error);
-                    std::process::exit(1);
-                }
-            };
+            let file_content = file.read()?;
+
+            self.host_hooks.record_module(None, file.get_path());
 
             if file_content.trim().is_empty() {
                 eprintln!("⚠️  Warning: File {} is empty", file.get_path());
                 continue;
             }
+            let resolver = &self.host_hooks.resolver;
+            let file_content = if resolver
+                .npm
+                .is_commonjs(std::path::Path::new(file.get_path()))
+            {
+                // CommonJS entry points are wrapped into ES modules as well
+                wrap_commonjs(&resolver.npm, file.get_path(), &file_content)
+            } else if let Some(coverage) = &self.host_hooks.coverage {
+                coverage.instrument(file.get_path(), file_content)
+            } else {
+                self.host_hooks
+                    .compiled_source(file.get_path(), file_content)
+            };
             result = self.agent.run_in_realm(&self.realm_root, |agent, mut gc| {
                 let source_text = types::String::from_string(agent, file_content, gc.nogc());
                 let realm = agent.current_realm(gc.nogc());
@@ -773,50 +879,25 @@ impl<UserMacroTask> Runtime<UserMacroTask> {
                     gc.nogc(),
                 ) {
                     Ok(module) => module,
-                    Err(errors) => exit_with_parse_errors(
-                        errors,
-                        file.get_path(),
-                        source_text
-                            .as_str(agent)
-                            .expect("String is not valid UTF-8"),
-                    ),
+                    Err(errors) => {
+                        return Err(Box::new(AndromedaError::parse_error(
+                            errors,
+                            file.get_path(),
+                            source_text
+                                .as_str(agent)
+                                .expect("String is not valid UTF-8"),
+                        )));
+                    }
                 };
 
-                agent
+                Ok(agent
                     .run_parsed_module(module.unbind(), None, gc.reborrow())
                     .unbind()
-                    .map(|_| Value::Null)
-            });
+                    .map(|_| Value::Null))
+            })?;
         }
 
-        loop {
-            while let Some(job) = self.host_hooks.pop_promise_job() {
-                result = self.agent.run_in_realm(&self.realm_root, |agent, mut gc| {
-                    job.run(agent, gc.reborrow()).unbind().map(|_| Value::Null)
-                });
-            }
-
-            // Try to handle a macro task without blocking
-            // This handles the case where a task completed so fast that the counter
-            // was already decremented but the message is still in the channel
-            let has_macro_task = self.try_handle_macro_task();
-
-            // Only exit if there are no pending tasks AND no message was processed
-            if !has_macro_task && !self.host_hooks.any_pending_macro_tasks() {
-                break;
-            }
-
-            // If we saw pending tasks but got no message, block waiting for one
-            if !has_macro_task && self.host_hooks.any_pending_macro_tasks() {
-                self.handle_macro_task();
-            }
-        }
-
-        RuntimeOutput {
-            agent: self.agent,
-            realm_root: self.realm_root,
-            result,
-        }
+        Ok(result)
     }
 
     // Listen for pending macro tasks and resolve one by one
@@ -842,6 +923,12 @@ impl<UserMacroTask> Runtime<UserMacroTask> {
                     &self.host_hooks.host_data,
                 );
             }
+            Ok(MacroTask::Terminate) => {
+                self.host_hooks
+                    .host_data
+                    .terminated
+                    .store(true, Ordering::Release);
+            }
             _ => {}
         }
     }
@@ -871,13 +958,41 @@ impl<UserMacroTask> Runtime<UserMacroTask> {
                 );
                 true
             }
+            Ok(MacroTask::Terminate) => {
+                self.host_hooks
+                    .host_data
+                    .terminated
+                    .store(true, Ordering::Release);
+                true
+            }
             _ => false,
         }
     }
 }
 
-pub struct RuntimeOutput {
+pub struct RuntimeOutput<UserMacroTask: 'static> {
     pub agent: GcAgent,
     pub realm_root: RealmRoot,
     pub result: JsResult<'static, Value<'static>>,
+    // Declared after the agent so it is dropped once the agent is gone
+    host_hooks: OwnedHostHooks<UserMacroTask>,
+}
+
+impl<UserMacroTask> RuntimeOutput<UserMacroTask> {
+    pub fn host_data(&self) -> &HostData<UserMacroTask> {
+        // SAFETY: The hooks are only freed when the output is dropped
+        unsafe { self.host_hooks.0.as_ref().host_data() }
+    }
+}
+
+/// The [RuntimeHostHooks] handed to the agent of a [Runtime], which are freed
+/// when dropped so restarting a runtime does not leak them.
+struct OwnedHostHooks<UserMacroTask: 'static>(NonNull<RuntimeHostHooks<UserMacroTask>>);
+
+impl<UserMacroTask> Drop for OwnedHostHooks<UserMacroTask> {
+    fn drop(&mut self) {
+        // SAFETY: The pointer comes from `Box::leak` and the agent holding the
+        // hooks is always dropped first
+        drop(unsafe { Box::from_raw(self.0.as_ptr()) });
+    }
 }
@@ -0,0 +1,213 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::{Mutex, OnceLock},
+};
+
+use oxc_sourcemap::SourceMap;
+
+/// A position in the original source of generated code
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OriginalPosition {
+    /// Path or URL of the original source
+    pub source: String,
+    /// Zero-based line
+    pub line: u32,
+    /// Zero-based column
+    pub column: u32,
+    /// The original source, when the source map embeds it
+    pub content: Option<String>,
+}
+
+/// The code modules were evaluated as, by path, when it isn't their source
+static GENERATED_SOURCES: OnceLock<Mutex<HashMap<String, String>>> = OnceLock::new();
+
+/// Record that the module at `path` was evaluated as `source`, whose source
+/// map locates its errors in the original source
+pub fn register_generated_source(path: &str, source: String) {
+    GENERATED_SOURCES
+        .get_or_init(Default::default)
+        .lock()
+        .unwrap()
+        .insert(path.to_string(), source);
+}
+
+/// The code the module at `path` was evaluated as, from the registered code
+/// or else its file
+fn generated_source(path: &str) -> Option<String> {
+    let generated = GENERATED_SOURCES
+        .get()
+        .and_then(|sources| sources.lock().unwrap().get(path).cloned());
+    generated.or_else(|| std::fs::read_to_string(path).ok())
+}
+
+/// The source map of a module, found through its `sourceMappingURL` comment
+pub struct ModuleSourceMap {
+    map: SourceMap,
+    /// Directory the relative sources of the map are resolved against
+    base_dir: PathBuf,
+}
+
+impl ModuleSourceMap {
+    /// Load the source map of the module at `path`, either inline as a data URL
+    /// or from the file next to the module its `sourceMappingURL` names
+    pub fn from_module(path: &str, source_text: &str) -> Option<Self> {
+        let url = source_mapping_url(source_text)?;
+        let module_dir = Path::new(path).parent().unwrap_or(Path::new("."));
+
+        let (json, base_dir) = if let Some(data) = url.strip_prefix("data:") {
+            let (media_type, payload) = data.split_once(',')?;
+            let json = if media_type.ends_with(";base64") {
+                let bytes = base64_simd::STANDARD.decode_to_vec(payload).ok()?;
+                String::from_utf8(bytes).ok()?
+            } else {
+                payload.to_string()
+            };
+            (json, module_dir.to_path_buf())
+        } else {
+            let map_path = match url.strip_prefix("file://") {
+                Some(map_path) => PathBuf::from(map_path),
+                None => module_dir.join(url),
+            };
+            let json = std::fs::read_to_string(&map_path).ok()?;
+            let base_dir = map_path
+                .parent()
+                .map(Path::to_path_buf)
+                .unwrap_or_else(|| module_dir.to_path_buf());
+            (json, base_dir)
+        };
+
+        let map = SourceMap::from_json_string(&json).ok()?;
+        Some(Self { map, base_dir })
+    }
+
+    /// The original position of the zero-based `line` and `column` of the
+    /// generated code, if the map has a mapping for it
+    pub fn original_position(&self, line: u32, column: u32) -> Option<OriginalPosition> {
+        let lookup_table = self.map.generate_lookup_table();
+        let token = self.map.lookup_token(&lookup_table, line, column)?;
+        let source_id = token.get_source_id()?;
+        let source = self.map.get_source(source_id)?;
+        let source = if source.contains("://") || Path::new(source).is_absolute() {
+            source.to_string()
+        } else {
+            normalize(&self.base_dir.join(source))
+        };
+        Some(OriginalPosition {
+            source,
+            line: token.get_src_line(),
+            column: token.get_src_col(),
+            content: self
+                .map
+                .get_source_content(source_id)
+                .map(|content| content.to_string()),
+        })
+    }
+}
+
+/// The URL of the last `//# sourceMappingURL=` comment of a module
+pub fn source_mapping_url(source_text: &str) -> Option<&str> {
+    source_text.lines().rev().find_map(|line| {
+        let line = line.trim();
+        line.strip_prefix("//# sourceMappingURL=")
+            .or_else(|| line.strip_prefix("//@ sourceMappingURL="))
+            .map(str::trim)
+            .filter(|url| !url.is_empty())
+    })
+}
+
+/// Zero-based line and column of a byte `offset` in `text`
+pub fn line_column(text: &str, offset: usize) -> (u32, u32) {
+    let mut offset = offset.min(text.len());
+    while !text.is_char_boundary(offset) {
+        offset -= 1;
+    }
+    let before = &text[..offset];
+    let line = before.matches('\n').count();
+    let line_start = before.rfind('\n').map_or(0, |index| index + 1);
+    (line as u32, before[line_start..].chars().count() as u32)
+}
+
+/// Byte offset of a zero-based `line` and `column` in `text`, clamped to the line
+pub fn line_column_offset(text: &str, line: u32, column: u32) -> usize {
+    let mut line_start = 0;
+    for _ in 0..line {
+        match text[line_start..].find('\n') {
+            Some(index) => line_start += index + 1,
+            None => return text.len(),
+        }
+    }
+    let line_text = text[line_start..].split('\n').next().unwrap_or("");
+    line_start
+        + line_text
+            .char_indices()
+            .nth(column as usize)
+            .map_or(line_text.len(), |(index, _)| index)
+}
+
+/// Rewrite the `path:line:column` locations of a JavaScript stack trace to the
+/// original sources of the modules that have source maps
+pub fn remap_stack_trace(stack: &str) -> String {
+    let mut maps: HashMap<String, Option<ModuleSourceMap>> = HashMap::new();
+    stack
+        .lines()
+        .map(|frame| remap_frame(frame, &mut maps).unwrap_or_else(|| frame.to_string()))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn remap_frame(frame: &str, maps: &mut HashMap<String, Option<ModuleSourceMap>>) -> Option<String> {
+    // `at name (location)` or `at location`
+    let location_start = match frame.rfind('(') {
+        Some(index) if frame.ends_with(')') => index + 1,
+        _ => frame.find("at ")? + 3,
+    };
+    let location_end = if frame.ends_with(')') {
+        frame.len() - 1
+    } else {
+        frame.len()
+    };
+    let location = &frame[location_start..location_end];
+
+    let (rest, column) = location.rsplit_once(':')?;
+    let (path, line) = rest.rsplit_once(':')?;
+    let line: u32 = line.parse().ok()?;
+    let column: u32 = column.parse().ok()?;
+    let file_path = path.strip_prefix("file://").unwrap_or(path);
+
+    let map = maps.entry(file_path.to_string()).or_insert_with(|| {
+        let source_text = generated_source(file_path)?;
+        ModuleSourceMap::from_module(file_path, &source_text)
+    });
+    let position = map
+        .as_ref()?
+        .original_position(line.saturating_sub(1), column.saturating_sub(1))?;
+
+    Some(format!(
+        "{}{}:{}:{}{}",
+        &frame[..location_start],
+        position.source,
+        position.line + 1,
+        position.column + 1,
+        &frame[location_end..]
+    ))
+}
+
+/// `path` with its `.` and `..` components resolved
+fn normalize(path: &Path) -> String {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            std::path::Component::CurDir => {}
+            std::path::Component::ParentDir => {
+                normalized.pop();
+            }
+            component => normalized.push(component),
+        }
+    }
+    normalized.to_string_lossy().to_string()
+}
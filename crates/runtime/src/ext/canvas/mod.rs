@@ -10,7 +10,9 @@ pub mod renderer;
 pub mod state;
 pub mod text;
 pub mod text_metrics;
-use andromeda_core::{Extension, ExtensionOp, HostData, OpsStorage, ResourceTable, Rid};
+use andromeda_core::{
+    Extension, ExtensionOp, HostData, OpsStorage, ResourceTable, Rid, check_permission,
+};
 use std::ops::DerefMut;
 
 use crate::ext::canvas::context2d::{
@@ -904,6 +906,14 @@ impl CanvasExt {
         let binding = args.get(0).to_string(agent, gc.reborrow()).unbind()?;
         let path = binding.as_str(agent).expect("String is not valid UTF-8");
 
+        if let Err(error) = check_permission::<crate::RuntimeMacroTask>(agent, |p| {
+            p.check_read(std::path::Path::new(path))
+        }) {
+            return Err(error
+                .into_js_error::<crate::RuntimeMacroTask>(agent, gc.nogc())
+                .unbind());
+        }
+
         // Load and decode the image
         let image_result = Self::load_image_from_path(path);
 
@@ -1176,6 +1186,14 @@ impl CanvasExt {
             .expect("String is not valid UTF-8")
             .to_owned();
 
+        if let Err(error) = check_permission::<crate::RuntimeMacroTask>(agent, |p| {
+            p.check_write(std::path::Path::new(&path_owned))
+        }) {
+            return Err(error
+                .into_js_error::<crate::RuntimeMacroTask>(agent, gc.nogc())
+                .unbind());
+        }
+
         let host_data = agent
             .get_host_data()
             .downcast_ref::<HostData<crate::RuntimeMacroTask>>()
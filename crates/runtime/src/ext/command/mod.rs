@@ -0,0 +1,632 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use std::{collections::HashMap, process::Stdio, sync::Arc};
+
+use andromeda_core::{
+    Extension, ExtensionOp, HostData, MacroTask, OpsStorage, ResourceTable, Rid, check_permission,
+};
+use nova_vm::{
+    ecmascript::{
+        builtins::{
+            ArgumentsList,
+            promise_objects::promise_abstract_operations::promise_capability_records::PromiseCapability,
+        },
+        execution::{Agent, JsError, JsResult, agent::ExceptionType},
+        types::{IntoValue, Value},
+    },
+    engine::{
+        Global,
+        context::{Bindable, GcScope},
+    },
+};
+use serde::{Deserialize, Serialize};
+use tokio::{
+    io::{AsyncRead, AsyncReadExt, AsyncWriteExt},
+    process::{Child, ChildStderr, ChildStdin, ChildStdout},
+    sync::{Mutex as TokioMutex, mpsc, watch},
+};
+
+use crate::RuntimeMacroTask;
+
+/// Largest chunk read from the output of a child at once, in bytes.
+const MAX_CHUNK_SIZE: usize = 64 * 1024;
+
+/// How a standard stream of the child is connected.
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum StdioOption {
+    Piped,
+    #[default]
+    Inherit,
+    Null,
+}
+
+impl From<StdioOption> for Stdio {
+    fn from(option: StdioOption) -> Self {
+        match option {
+            StdioOption::Piped => Stdio::piped(),
+            StdioOption::Inherit => Stdio::inherit(),
+            StdioOption::Null => Stdio::null(),
+        }
+    }
+}
+
+/// Options passed from `Andromeda.Command` when spawning a child.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SpawnOptions {
+    cmd: String,
+    #[serde(default)]
+    args: Vec<String>,
+    cwd: Option<String>,
+    #[serde(default)]
+    env: HashMap<String, String>,
+    #[serde(default)]
+    clear_env: bool,
+    #[serde(default)]
+    stdin: StdioOption,
+    #[serde(default)]
+    stdout: StdioOption,
+    #[serde(default)]
+    stderr: StdioOption,
+}
+
+/// The exit status of a child, as seen by JavaScript.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ChildStatus {
+    success: bool,
+    code: i32,
+    signal: Option<String>,
+}
+
+impl From<std::process::ExitStatus> for ChildStatus {
+    fn from(status: std::process::ExitStatus) -> Self {
+        #[cfg(unix)]
+        let signal = {
+            use std::os::unix::process::ExitStatusExt;
+            status.signal()
+        };
+        #[cfg(not(unix))]
+        let signal: Option<i32> = None;
+
+        ChildStatus {
+            success: status.success(),
+            // A child terminated by a signal reports `128 + signal` like a shell does
+            code: status
+                .code()
+                .or(signal.map(|signal| 128 + signal))
+                .unwrap_or(1),
+            signal: signal.and_then(signal_name).map(str::to_string),
+        }
+    }
+}
+
+/// A spawned child process and the pipes connected to it.
+#[derive(Clone)]
+struct ChildResource {
+    stdin: Option<Arc<TokioMutex<Option<ChildStdin>>>>,
+    stdout: Option<Arc<TokioMutex<ChildStdout>>>,
+    stderr: Option<Arc<TokioMutex<ChildStderr>>>,
+    status: watch::Receiver<Option<ChildStatus>>,
+    kill_tx: mpsc::UnboundedSender<i32>,
+}
+
+struct CommandResources {
+    children: ResourceTable<ChildResource>,
+}
+
+/// Signals that can be sent to a child with `ChildProcess.kill()`.
+#[cfg(unix)]
+const SIGNALS: &[(&str, i32)] = &[
+    ("SIGHUP", libc::SIGHUP),
+    ("SIGINT", libc::SIGINT),
+    ("SIGQUIT", libc::SIGQUIT),
+    ("SIGILL", libc::SIGILL),
+    ("SIGTRAP", libc::SIGTRAP),
+    ("SIGABRT", libc::SIGABRT),
+    ("SIGBUS", libc::SIGBUS),
+    ("SIGFPE", libc::SIGFPE),
+    ("SIGKILL", libc::SIGKILL),
+    ("SIGUSR1", libc::SIGUSR1),
+    ("SIGSEGV", libc::SIGSEGV),
+    ("SIGUSR2", libc::SIGUSR2),
+    ("SIGPIPE", libc::SIGPIPE),
+    ("SIGALRM", libc::SIGALRM),
+    ("SIGTERM", libc::SIGTERM),
+    ("SIGCHLD", libc::SIGCHLD),
+    ("SIGCONT", libc::SIGCONT),
+    ("SIGSTOP", libc::SIGSTOP),
+    ("SIGTSTP", libc::SIGTSTP),
+    ("SIGTTIN", libc::SIGTTIN),
+    ("SIGTTOU", libc::SIGTTOU),
+    ("SIGWINCH", libc::SIGWINCH),
+];
+
+/// Windows can only terminate a child, so both names map to the same action.
+#[cfg(not(unix))]
+const SIGNALS: &[(&str, i32)] = &[("SIGKILL", 9), ("SIGTERM", 15)];
+
+fn signal_number(name: &str) -> Option<i32> {
+    SIGNALS
+        .iter()
+        .find(|(signal, _)| *signal == name)
+        .map(|(_, number)| *number)
+}
+
+fn signal_name(number: i32) -> Option<&'static str> {
+    SIGNALS
+        .iter()
+        .find(|(_, signal)| *signal == number)
+        .map(|(name, _)| *name)
+}
+
+#[cfg(unix)]
+fn send_signal(_child: &mut Child, pid: u32, signal: i32) {
+    // SAFETY: `kill` has no memory safety requirements and the child has not
+    // been reaped yet, so the pid still refers to it.
+    unsafe {
+        libc::kill(pid as libc::pid_t, signal);
+    }
+}
+
+#[cfg(not(unix))]
+fn send_signal(child: &mut Child, _pid: u32, _signal: i32) {
+    let _ = child.start_kill();
+}
+
+pub(crate) fn decode_hex(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+async fn read_chunk<R: AsyncRead + Unpin>(
+    reader: Arc<TokioMutex<R>>,
+    len: usize,
+) -> std::io::Result<Vec<u8>> {
+    let mut buffer = vec![0u8; len];
+    let n = reader.lock().await.read(&mut buffer).await?;
+    buffer.truncate(n);
+    Ok(buffer)
+}
+
+/// Command extension for Andromeda.
+/// This extension backs the `Andromeda.Command` subprocess API.
+#[derive(Default)]
+pub struct CommandExt;
+
+#[hotpath::measure_all]
+impl CommandExt {
+    pub fn new_extension() -> Extension {
+        Extension {
+            name: "command",
+            ops: vec![
+                ExtensionOp::new(
+                    "internal_command_spawn",
+                    Self::internal_command_spawn,
+                    1,
+                    false,
+                ),
+                ExtensionOp::new(
+                    "internal_command_read",
+                    Self::internal_command_read,
+                    3,
+                    false,
+                ),
+                ExtensionOp::new(
+                    "internal_command_write",
+                    Self::internal_command_write,
+                    2,
+                    false,
+                ),
+                ExtensionOp::new(
+                    "internal_command_close_stdin",
+                    Self::internal_command_close_stdin,
+                    1,
+                    false,
+                ),
+                ExtensionOp::new(
+                    "internal_command_wait",
+                    Self::internal_command_wait,
+                    1,
+                    false,
+                ),
+                ExtensionOp::new(
+                    "internal_command_kill",
+                    Self::internal_command_kill,
+                    2,
+                    false,
+                ),
+                ExtensionOp::new(
+                    "internal_command_close",
+                    Self::internal_command_close,
+                    1,
+                    false,
+                ),
+            ],
+            storage: Some(Box::new(|storage: &mut OpsStorage| {
+                storage.insert(CommandResources {
+                    children: ResourceTable::new(),
+                });
+            })),
+            files: vec![include_str!("./mod.ts")],
+        }
+    }
+
+    /// Spawn a child process from JSON encoded options, returning `{ rid, pid }` as JSON.
+    fn internal_command_spawn<'gc>(
+        agent: &mut Agent,
+        _this: Value,
+        args: ArgumentsList,
+        mut gc: GcScope<'gc, '_>,
+    ) -> JsResult<'gc, Value<'gc>> {
+        let options_binding = args.get(0).to_string(agent, gc.reborrow()).unbind()?;
+        let options_str = options_binding
+            .as_str(agent)
+            .expect("String is not valid UTF-8");
+        let options: SpawnOptions = match serde_json::from_str(options_str) {
+            Ok(options) => options,
+            Err(e) => {
+                return Err(agent
+                    .throw_exception(
+                        ExceptionType::TypeError,
+                        format!("Invalid command options: {e}"),
+                        gc.nogc(),
+                    )
+                    .unbind());
+            }
+        };
+
+        if let Err(error) =
+            check_permission::<RuntimeMacroTask>(agent, |p| p.check_run(&options.cmd))
+        {
+            return Err(error
+                .into_js_error::<RuntimeMacroTask>(agent, gc.nogc())
+                .unbind());
+        }
+
+        let mut command = tokio::process::Command::new(&options.cmd);
+        command
+            .args(&options.args)
+            .stdin(options.stdin)
+            .stdout(options.stdout)
+            .stderr(options.stderr);
+        if let Some(cwd) = &options.cwd {
+            command.current_dir(cwd);
+        }
+        if options.clear_env {
+            command.env_clear();
+        }
+        command.envs(&options.env);
+
+        let mut child = match command.spawn() {
+            Ok(child) => child,
+            Err(e) => {
+                return Err(agent
+                    .throw_exception(
+                        ExceptionType::Error,
+                        format!("Failed to spawn '{}': {e}", options.cmd),
+                        gc.nogc(),
+                    )
+                    .unbind());
+            }
+        };
+
+        let pid = child.id().unwrap_or_default();
+        let stdin = child
+            .stdin
+            .take()
+            .map(|s| Arc::new(TokioMutex::new(Some(s))));
+        let stdout = child.stdout.take().map(|s| Arc::new(TokioMutex::new(s)));
+        let stderr = child.stderr.take().map(|s| Arc::new(TokioMutex::new(s)));
+        let (status_tx, status_rx) = watch::channel(None);
+        let (kill_tx, mut kill_rx) = mpsc::unbounded_channel::<i32>();
+
+        let host_data = agent.get_host_data();
+        let host_data: &HostData<RuntimeMacroTask> = host_data.downcast_ref().unwrap();
+
+        // The child is awaited as a macro task so the event loop stays alive until it exits.
+        host_data.spawn_macro_task(async move {
+            let status = loop {
+                tokio::select! {
+                    status = child.wait() => break status,
+                    Some(signal) = kill_rx.recv() => send_signal(&mut child, pid, signal),
+                }
+            };
+            let status = match status {
+                Ok(status) => ChildStatus::from(status),
+                Err(_) => ChildStatus {
+                    success: false,
+                    code: 1,
+                    signal: None,
+                },
+            };
+            let _ = status_tx.send(Some(status));
+        });
+
+        let storage = host_data.storage.borrow();
+        let resources: &CommandResources = storage.get().unwrap();
+        let rid = resources.children.push(ChildResource {
+            stdin,
+            stdout,
+            stderr,
+            status: status_rx,
+            kill_tx,
+        });
+        drop(storage);
+
+        let result = serde_json::json!({ "rid": rid.index(), "pid": pid }).to_string();
+        Ok(Value::from_string(agent, result, gc.nogc()).unbind())
+    }
+
+    /// Read up to `len` bytes from the child's stdout or stderr.
+    /// Resolves with the bytes read, which are empty once the stream is exhausted.
+    fn internal_command_read<'gc>(
+        agent: &mut Agent,
+        _this: Value,
+        args: ArgumentsList,
+        mut gc: GcScope<'gc, '_>,
+    ) -> JsResult<'gc, Value<'gc>> {
+        let rid = Self::rid_arg(agent, &args, gc.reborrow())?;
+        let stream_binding = args.get(1).to_string(agent, gc.reborrow()).unbind()?;
+        let len = args
+            .get(2)
+            .to_number(agent, gc.reborrow())
+            .unbind()?
+            .into_f64(agent);
+        if !len.is_finite() || len < 1.0 {
+            let message = format!("Invalid read length: {len}");
+            return Err(agent
+                .throw_exception(ExceptionType::RangeError, message, gc.nogc())
+                .unbind());
+        }
+        let len = (len as usize).min(MAX_CHUNK_SIZE);
+        let stream = stream_binding
+            .as_str(agent)
+            .expect("String is not valid UTF-8")
+            .to_string();
+
+        let Some(child) = Self::child(agent, rid) else {
+            return Err(Self::bad_resource(agent, gc));
+        };
+
+        let promise_capability = PromiseCapability::new(agent, gc.nogc());
+        let root_value = Global::new(agent, promise_capability.promise().into_value().unbind());
+
+        let host_data = agent.get_host_data();
+        let host_data: &HostData<RuntimeMacroTask> = host_data.downcast_ref().unwrap();
+        let macro_task_tx = host_data.macro_task_tx();
+
+        let stdout = child.stdout.filter(|_| stream == "stdout");
+        let stderr = child.stderr.filter(|_| stream == "stderr");
+
+        host_data.spawn_macro_task(async move {
+            let result = match (stdout, stderr) {
+                (Some(stdout), _) => read_chunk(stdout, len).await,
+                (_, Some(stderr)) => read_chunk(stderr, len).await,
+                _ => Err(std::io::Error::other(format!("{stream} is not piped"))),
+            };
+            let task = match result {
+                Ok(bytes) => RuntimeMacroTask::ResolvePromiseWithBytes(root_value, bytes),
+                Err(e) => RuntimeMacroTask::RejectPromise(
+                    root_value,
+                    format!("Failed to read {stream}: {e}"),
+                ),
+            };
+            macro_task_tx.send(MacroTask::User(task)).unwrap();
+        });
+
+        Ok(Value::Promise(promise_capability.promise()).unbind())
+    }
+
+    /// Write hex encoded bytes to the child's stdin.
+    fn internal_command_write<'gc>(
+        agent: &mut Agent,
+        _this: Value,
+        args: ArgumentsList,
+        mut gc: GcScope<'gc, '_>,
+    ) -> JsResult<'gc, Value<'gc>> {
+        let rid = Self::rid_arg(agent, &args, gc.reborrow())?;
+        let data_binding = args.get(1).to_string(agent, gc.reborrow()).unbind()?;
+        let Some(data) = decode_hex(
+            data_binding
+                .as_str(agent)
+                .expect("String is not valid UTF-8"),
+        ) else {
+            return Err(agent
+                .throw_exception_with_static_message(
+                    ExceptionType::TypeError,
+                    "Invalid stdin data",
+                    gc.nogc(),
+                )
+                .unbind());
+        };
+
+        let Some(child) = Self::child(agent, rid) else {
+            return Err(Self::bad_resource(agent, gc));
+        };
+
+        let promise_capability = PromiseCapability::new(agent, gc.nogc());
+        let root_value = Global::new(agent, promise_capability.promise().into_value().unbind());
+
+        let host_data = agent.get_host_data();
+        let host_data: &HostData<RuntimeMacroTask> = host_data.downcast_ref().unwrap();
+        let macro_task_tx = host_data.macro_task_tx();
+
+        host_data.spawn_macro_task(async move {
+            let result = match child.stdin {
+                Some(stdin) => match stdin.lock().await.as_mut() {
+                    Some(stdin) => stdin.write_all(&data).await,
+                    None => Err(std::io::Error::other("stdin is closed")),
+                },
+                None => Err(std::io::Error::other("stdin is not piped")),
+            };
+            let task = match result {
+                Ok(()) => RuntimeMacroTask::ResolvePromiseWithString(root_value, String::new()),
+                Err(e) => RuntimeMacroTask::RejectPromise(
+                    root_value,
+                    format!("Failed to write stdin: {e}"),
+                ),
+            };
+            macro_task_tx.send(MacroTask::User(task)).unwrap();
+        });
+
+        Ok(Value::Promise(promise_capability.promise()).unbind())
+    }
+
+    /// Close the child's stdin so it observes end of input.
+    fn internal_command_close_stdin<'gc>(
+        agent: &mut Agent,
+        _this: Value,
+        args: ArgumentsList,
+        mut gc: GcScope<'gc, '_>,
+    ) -> JsResult<'gc, Value<'gc>> {
+        let rid = Self::rid_arg(agent, &args, gc.reborrow())?;
+        let Some(child) = Self::child(agent, rid) else {
+            return Err(Self::bad_resource(agent, gc));
+        };
+
+        if let Some(stdin) = child.stdin {
+            let host_data = agent.get_host_data();
+            let host_data: &HostData<RuntimeMacroTask> = host_data.downcast_ref().unwrap();
+            host_data.spawn_macro_task(async move {
+                if let Some(mut stdin) = stdin.lock().await.take() {
+                    let _ = stdin.shutdown().await;
+                }
+            });
+        }
+
+        Ok(Value::Undefined)
+    }
+
+    /// Wait for the child to exit, resolving with its JSON encoded status.
+    fn internal_command_wait<'gc>(
+        agent: &mut Agent,
+        _this: Value,
+        args: ArgumentsList,
+        mut gc: GcScope<'gc, '_>,
+    ) -> JsResult<'gc, Value<'gc>> {
+        let rid = Self::rid_arg(agent, &args, gc.reborrow())?;
+        let Some(child) = Self::child(agent, rid) else {
+            return Err(Self::bad_resource(agent, gc));
+        };
+
+        let promise_capability = PromiseCapability::new(agent, gc.nogc());
+        let root_value = Global::new(agent, promise_capability.promise().into_value().unbind());
+
+        let host_data = agent.get_host_data();
+        let host_data: &HostData<RuntimeMacroTask> = host_data.downcast_ref().unwrap();
+        let macro_task_tx = host_data.macro_task_tx();
+
+        let mut status_rx = child.status;
+        host_data.spawn_macro_task(async move {
+            let status = status_rx
+                .wait_for(Option::is_some)
+                .await
+                .ok()
+                .and_then(|status| status.clone());
+            let task = match status {
+                Some(status) => RuntimeMacroTask::ResolvePromiseWithString(
+                    root_value,
+                    serde_json::to_string(&status).unwrap(),
+                ),
+                None => RuntimeMacroTask::RejectPromise(
+                    root_value,
+                    "Child process status is unavailable".to_string(),
+                ),
+            };
+            macro_task_tx.send(MacroTask::User(task)).unwrap();
+        });
+
+        Ok(Value::Promise(promise_capability.promise()).unbind())
+    }
+
+    /// Send a signal to the child.
+    fn internal_command_kill<'gc>(
+        agent: &mut Agent,
+        _this: Value,
+        args: ArgumentsList,
+        mut gc: GcScope<'gc, '_>,
+    ) -> JsResult<'gc, Value<'gc>> {
+        let rid = Self::rid_arg(agent, &args, gc.reborrow())?;
+        let signal_binding = args.get(1).to_string(agent, gc.reborrow()).unbind()?;
+        let signal_str = signal_binding
+            .as_str(agent)
+            .expect("String is not valid UTF-8");
+
+        let Some(signal) = signal_number(signal_str) else {
+            let message = format!("Unsupported signal: {signal_str}");
+            return Err(agent
+                .throw_exception(ExceptionType::TypeError, message, gc.nogc())
+                .unbind());
+        };
+
+        let Some(child) = Self::child(agent, rid) else {
+            return Err(Self::bad_resource(agent, gc));
+        };
+
+        if child.status.borrow().is_some() || child.kill_tx.send(signal).is_err() {
+            return Err(agent
+                .throw_exception_with_static_message(
+                    ExceptionType::TypeError,
+                    "Child process has already terminated",
+                    gc.nogc(),
+                )
+                .unbind());
+        }
+
+        Ok(Value::Undefined)
+    }
+
+    /// Release the resources held for a child.
+    fn internal_command_close<'gc>(
+        agent: &mut Agent,
+        _this: Value,
+        args: ArgumentsList,
+        mut gc: GcScope<'gc, '_>,
+    ) -> JsResult<'gc, Value<'gc>> {
+        let rid = Self::rid_arg(agent, &args, gc.reborrow())?;
+
+        let host_data = agent.get_host_data();
+        let host_data: &HostData<RuntimeMacroTask> = host_data.downcast_ref().unwrap();
+        let storage = host_data.storage.borrow();
+        let resources: &CommandResources = storage.get().unwrap();
+        resources.children.remove(rid);
+
+        Ok(Value::Undefined)
+    }
+
+    fn rid_arg<'gc>(
+        agent: &mut Agent,
+        args: &ArgumentsList,
+        gc: GcScope<'gc, '_>,
+    ) -> JsResult<'gc, Rid> {
+        let rid = args.get(0).to_number(agent, gc).unbind()?.into_f64(agent);
+        Ok(Rid::from_index(rid as u32))
+    }
+
+    fn child(agent: &Agent, rid: Rid) -> Option<ChildResource> {
+        let host_data = agent.get_host_data();
+        let host_data: &HostData<RuntimeMacroTask> = host_data.downcast_ref().unwrap();
+        let storage = host_data.storage.borrow();
+        let resources: &CommandResources = storage.get().unwrap();
+        resources.children.get(rid)
+    }
+
+    fn bad_resource<'gc>(agent: &mut Agent, gc: GcScope<'gc, '_>) -> JsError<'gc> {
+        agent
+            .throw_exception_with_static_message(
+                ExceptionType::Error,
+                "Child process resource not found",
+                gc.into_nogc(),
+            )
+            .unbind()
+    }
+}
@@ -0,0 +1,242 @@
+// deno-lint-ignore-file no-explicit-any
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+type CommandStdio = "piped" | "inherit" | "null";
+
+interface CommandOptions {
+  args?: string[];
+  cwd?: string | URL;
+  env?: Record<string, string>;
+  clearEnv?: boolean;
+  stdin?: CommandStdio;
+  stdout?: CommandStdio;
+  stderr?: CommandStdio;
+}
+
+interface CommandStatus {
+  success: boolean;
+  code: number;
+  signal: string | null;
+}
+
+interface CommandOutput extends CommandStatus {
+  stdout: Uint8Array;
+  stderr: Uint8Array;
+}
+
+const READ_CHUNK_SIZE = 16 * 1024;
+
+function bytesToHex(bytes: Uint8Array): string {
+  let hex = "";
+  for (let i = 0; i < bytes.length; i++) {
+    hex += bytes[i].toString(16).padStart(2, "0");
+  }
+  return hex;
+}
+
+function hexToBytes(hex: string): Uint8Array {
+  const bytes = new Uint8Array(hex.length / 2);
+  for (let i = 0; i < bytes.length; i++) {
+    bytes[i] = parseInt(hex.substr(i * 2, 2), 16);
+  }
+  return bytes;
+}
+
+async function collect(stream: ReadableStream<Uint8Array>): Promise<Uint8Array> {
+  const chunks: Uint8Array[] = [];
+  let length = 0;
+  const reader = stream.getReader();
+  while (true) {
+    const { done, value } = await reader.read();
+    if (done) break;
+    chunks.push(value);
+    length += value.length;
+  }
+  const output = new Uint8Array(length);
+  let offset = 0;
+  for (const chunk of chunks) {
+    output.set(chunk, offset);
+    offset += chunk.length;
+  }
+  return output;
+}
+
+/**
+ * A handle to a spawned subprocess.
+ */
+class ChildProcess {
+  #rid: number;
+  #pid: number;
+  #stdin: WritableStream<Uint8Array> | null = null;
+  #stdout: ReadableStream<Uint8Array> | null = null;
+  #stderr: ReadableStream<Uint8Array> | null = null;
+  #status: Promise<CommandStatus>;
+  // The resource is released once the status and every piped output are done
+  #pending = 1;
+
+  constructor(rid: number, pid: number, options: CommandOptions) {
+    this.#rid = rid;
+    this.#pid = pid;
+
+    if (options.stdin === "piped") {
+      this.#stdin = new WritableStream<Uint8Array>({
+        write: async (chunk) => {
+          await __andromeda__.internal_command_write(rid, bytesToHex(chunk));
+        },
+        close: () => {
+          __andromeda__.internal_command_close_stdin(rid);
+        },
+        abort: () => {
+          __andromeda__.internal_command_close_stdin(rid);
+        },
+      });
+    }
+    if (options.stdout === "piped") {
+      this.#stdout = this.#readable("stdout");
+    }
+    if (options.stderr === "piped") {
+      this.#stderr = this.#readable("stderr");
+    }
+
+    this.#status = __andromeda__.internal_command_wait(rid).then(
+      (json: string) => {
+        this.#release();
+        return JSON.parse(json) as CommandStatus;
+      },
+    );
+  }
+
+  #readable(name: "stdout" | "stderr"): ReadableStream<Uint8Array> {
+    const rid = this.#rid;
+    this.#pending++;
+    let finished = false;
+    const finish = () => {
+      if (finished) return;
+      finished = true;
+      this.#release();
+    };
+    return new ReadableStream<Uint8Array>({
+      pull: async (controller) => {
+        try {
+          const hex = await __andromeda__.internal_command_read(
+            rid,
+            name,
+            READ_CHUNK_SIZE,
+          );
+          if (hex.length === 0) {
+            controller.close();
+            finish();
+          } else {
+            controller.enqueue(hexToBytes(hex));
+          }
+        } catch (error) {
+          controller.error(error);
+          finish();
+        }
+      },
+      cancel: () => finish(),
+    });
+  }
+
+  #release() {
+    if (--this.#pending === 0) {
+      __andromeda__.internal_command_close(this.#rid);
+    }
+  }
+
+  /** The process id of the child. */
+  get pid(): number {
+    return this.#pid;
+  }
+
+  /** The child's stdin, available when spawned with `stdin: "piped"`. */
+  get stdin(): WritableStream<Uint8Array> {
+    if (!this.#stdin) throw new TypeError("stdin is not piped");
+    return this.#stdin;
+  }
+
+  /** The child's stdout, available when spawned with `stdout: "piped"`. */
+  get stdout(): ReadableStream<Uint8Array> {
+    if (!this.#stdout) throw new TypeError("stdout is not piped");
+    return this.#stdout;
+  }
+
+  /** The child's stderr, available when spawned with `stderr: "piped"`. */
+  get stderr(): ReadableStream<Uint8Array> {
+    if (!this.#stderr) throw new TypeError("stderr is not piped");
+    return this.#stderr;
+  }
+
+  /** Resolves with the exit status once the child exits. */
+  get status(): Promise<CommandStatus> {
+    return this.#status;
+  }
+
+  /** Wait for the child to exit, collecting everything it wrote to its piped outputs. */
+  async output(): Promise<CommandOutput> {
+    const [status, stdout, stderr] = await Promise.all([
+      this.#status,
+      this.#stdout ? collect(this.#stdout) : new Uint8Array(),
+      this.#stderr ? collect(this.#stderr) : new Uint8Array(),
+    ]);
+    return { ...status, stdout, stderr };
+  }
+
+  /** Send a signal to the child, `SIGTERM` by default. */
+  kill(signal: string = "SIGTERM"): void {
+    __andromeda__.internal_command_kill(this.#rid, signal);
+  }
+}
+
+/**
+ * A description of a subprocess to run.
+ */
+class Command {
+  #command: string;
+  #options: CommandOptions;
+
+  constructor(command: string | URL, options: CommandOptions = {}) {
+    this.#command = command instanceof URL ? command.pathname : String(command);
+    this.#options = options;
+  }
+
+  #spawn(defaults: Required<Pick<CommandOptions, "stdin" | "stdout" | "stderr">>) {
+    const options = { ...defaults, ...this.#options };
+    const cwd = options.cwd instanceof URL ? options.cwd.pathname : options.cwd;
+    const { rid, pid } = JSON.parse(
+      __andromeda__.internal_command_spawn(JSON.stringify({
+        cmd: this.#command,
+        args: (options.args ?? []).map(String),
+        cwd,
+        env: options.env ?? {},
+        clearEnv: options.clearEnv ?? false,
+        stdin: options.stdin,
+        stdout: options.stdout,
+        stderr: options.stderr,
+      })),
+    );
+    return new ChildProcess(rid, pid, options);
+  }
+
+  /** Spawn the child, inheriting stdio unless configured otherwise. */
+  spawn(): ChildProcess {
+    return this.#spawn({ stdin: "inherit", stdout: "inherit", stderr: "inherit" });
+  }
+
+  /** Run the child to completion, collecting its stdout and stderr. */
+  output(): Promise<CommandOutput> {
+    return this.#spawn({ stdin: "null", stdout: "piped", stderr: "piped" })
+      .output();
+  }
+
+  /** Run the child to completion, inheriting its stdout and stderr. */
+  status(): Promise<CommandStatus> {
+    return this.#spawn({ stdin: "null", stdout: "inherit", stderr: "inherit" })
+      .status;
+  }
+}
+
+// @ts-ignore globalThis is not readonly
+globalThis.__andromeda_command = Command;
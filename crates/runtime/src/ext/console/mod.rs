@@ -6,7 +6,9 @@ use std::collections::HashMap;
 use std::io::{Write, stderr, stdout};
 use std::time::{SystemTime, UNIX_EPOCH};
 
-use andromeda_core::{AndromedaError, ErrorReporter, Extension, ExtensionOp, HostData, OpsStorage};
+use andromeda_core::{
+    AndromedaError, ErrorReporter, Extension, ExtensionOp, HostData, OpsStorage, read_line,
+};
 use nova_vm::{
     ecmascript::{
         builtins::ArgumentsList,
@@ -509,10 +511,9 @@ impl ConsoleExt {
         _args: ArgumentsList,
         gc: GcScope<'gc, '_>,
     ) -> JsResult<'gc, Value<'gc>> {
-        let mut input = String::new();
-        match std::io::stdin().read_line(&mut input) {
-            Ok(_) => {
-                Ok(Value::from_string(agent, input.trim_end().to_string(), gc.nogc()).unbind())
+        match read_line() {
+            Ok(input) => {
+                Ok(Value::from_string(agent, input.unwrap_or_default(), gc.nogc()).unbind())
             }
             Err(e) => {
                 let error =
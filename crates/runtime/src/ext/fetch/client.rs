@@ -0,0 +1,757 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! The HTTP client behind `fetch`.
+//!
+//! Connections are pooled per origin. HTTPS connections negotiate HTTP/2 or
+//! HTTP/1.1 with ALPN. A negotiated HTTP/2 connection is shared by every
+//! request to its origin, each running on its own stream, while an HTTP/1.1
+//! connection carries one request at a time and goes back to the pool once its
+//! response was read. Plain `http:` origins use HTTP/1.1, or HTTP/2 with prior
+//! knowledge (h2c) when the client has HTTP/1.1 disabled.
+//!
+//! Request bodies may be streamed in as they are produced, and response bodies
+//! are decoded and handed on chunk by chunk. A response body channel only holds
+//! a few chunks, so a reader that stops reading holds back the server.
+
+use std::{
+    collections::HashMap,
+    io,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use bytes::Bytes;
+use rustls_pki_types::ServerName;
+use tokio::{
+    io::{AsyncRead, AsyncWrite, AsyncWriteExt},
+    net::TcpStream,
+    sync::{mpsc, oneshot},
+};
+use tokio_rustls::TlsConnector;
+use url::{Host, Url};
+
+use super::decode::Decoder;
+use crate::ext::{
+    http1::{ChunkSink, MAX_HEAD_SIZE, MAX_HEADERS, Reader, invalid_data},
+    tls::client_connector,
+};
+
+/// Idle HTTP/1.1 connections kept per origin.
+const MAX_IDLE_PER_ORIGIN: usize = 8;
+/// How long an idle HTTP/1.1 connection stays in the pool.
+const IDLE_TIMEOUT: Duration = Duration::from_secs(30);
+/// Sent when a request does not ask for codings itself.
+const ACCEPT_ENCODING: &str = "gzip, deflate, br";
+/// Headers describing a single HTTP/1.1 connection, which HTTP/2 forbids.
+const CONNECTION_HEADERS: [&str; 6] = [
+    "connection",
+    "host",
+    "keep-alive",
+    "proxy-connection",
+    "transfer-encoding",
+    "upgrade",
+];
+
+trait Io: AsyncRead + AsyncWrite + Send + Unpin {}
+impl<T: AsyncRead + AsyncWrite + Send + Unpin> Io for T {}
+
+pub(crate) struct HttpRequest {
+    pub method: String,
+    pub url: Url,
+    pub headers: Vec<(String, String)>,
+    pub body: RequestBody,
+}
+
+pub(crate) enum RequestBody {
+    Full(Bytes),
+    /// Chunks written by the caller as the request is sent.
+    Stream(mpsc::Receiver<RequestChunk>),
+}
+
+impl RequestBody {
+    /// Whether the body can be sent again on another connection.
+    fn replayable(&self) -> bool {
+        matches!(self, RequestBody::Full(_))
+    }
+}
+
+pub(crate) enum RequestChunk {
+    Data(Vec<u8>),
+    /// The body is complete. Closing the channel without it aborts the
+    /// request.
+    End,
+}
+
+pub(crate) struct ResponseHead {
+    pub status: u16,
+    pub status_text: String,
+    pub headers: Vec<(String, String)>,
+    /// `"HTTP/1.0"`, `"HTTP/1.1"` or `"HTTP/2"`.
+    pub version: &'static str,
+}
+
+/// Where a response goes: its head once it arrived, then the chunks of its
+/// decoded body. The body channel closes once the body is complete.
+pub(crate) struct ResponseSink {
+    head: Option<oneshot::Sender<io::Result<ResponseHead>>>,
+    body: mpsc::Sender<io::Result<Vec<u8>>>,
+    decoder: Option<Decoder>,
+}
+
+impl ResponseSink {
+    pub(crate) fn new(
+        head: oneshot::Sender<io::Result<ResponseHead>>,
+        body: mpsc::Sender<io::Result<Vec<u8>>>,
+    ) -> Self {
+        ResponseSink {
+            head: Some(head),
+            body,
+            decoder: None,
+        }
+    }
+
+    fn head(&mut self, head: ResponseHead) {
+        self.decoder = Decoder::new(
+            head.headers
+                .iter()
+                .filter(|(name, _)| name.eq_ignore_ascii_case("content-encoding"))
+                .map(|(_, value)| value.as_str()),
+        );
+        if let Some(sender) = self.head.take() {
+            let _ = sender.send(Ok(head));
+        }
+    }
+
+    async fn end(&mut self) -> io::Result<()> {
+        if let Some(decoder) = self.decoder.take() {
+            let rest = decoder.finish()?;
+            self.data(rest).await?;
+        }
+        Ok(())
+    }
+
+    /// Report an error through the head, or the body once the head was sent.
+    async fn fail(mut self, error: io::Error) {
+        match self.head.take() {
+            Some(head) => {
+                let _ = head.send(Err(error));
+            }
+            None => {
+                let _ = self.body.send(Err(error)).await;
+            }
+        }
+    }
+}
+
+impl ChunkSink for ResponseSink {
+    /// Decode and hand on a chunk of the body. Fails once nobody reads the
+    /// body anymore.
+    async fn data(&mut self, chunk: Vec<u8>) -> io::Result<()> {
+        let chunk = match &mut self.decoder {
+            Some(decoder) => decoder.decode(chunk)?,
+            None => chunk,
+        };
+        if chunk.is_empty() {
+            return Ok(());
+        }
+        self.body
+            .send(Ok(chunk))
+            .await
+            .map_err(|_| io::Error::other("response body was cancelled"))
+    }
+}
+
+#[derive(Clone, PartialEq, Eq, Hash)]
+struct Origin {
+    secure: bool,
+    host: String,
+    port: u16,
+}
+
+impl Origin {
+    fn of(url: &Url) -> io::Result<Self> {
+        let secure = match url.scheme() {
+            "https" => true,
+            "http" => false,
+            scheme => return Err(invalid_input(format!("Unsupported scheme: {scheme}"))),
+        };
+        let host = match url.host() {
+            Some(Host::Domain(domain)) => domain.to_string(),
+            Some(Host::Ipv4(ip)) => ip.to_string(),
+            Some(Host::Ipv6(ip)) => ip.to_string(),
+            None => return Err(invalid_input(format!("Invalid URL: {url}"))),
+        };
+        let port = url
+            .port_or_known_default()
+            .unwrap_or(if secure { 443 } else { 80 });
+        Ok(Origin { secure, host, port })
+    }
+}
+
+/// Why a request failed.
+enum Failure {
+    /// The connection was closed before the request was answered, which
+    /// happens when a server drops a connection that sat idle in the pool.
+    Stale(io::Error),
+    Failed(io::Error),
+}
+
+impl Failure {
+    fn into_io(self) -> io::Error {
+        match self {
+            Failure::Stale(error) | Failure::Failed(error) => error,
+        }
+    }
+}
+
+enum Connection {
+    Http1(Http1Connection),
+    Http2(h2::client::SendRequest<Bytes>),
+}
+
+#[derive(Default)]
+struct Pool {
+    http1: HashMap<Origin, Vec<Http1Connection>>,
+    http2: HashMap<Origin, h2::client::SendRequest<Bytes>>,
+}
+
+/// A pool of connections together with the protocols it may use, created
+/// through `Andromeda.createHttpClient` or shared by plain `fetch` calls.
+pub(crate) struct HttpClient {
+    http1: bool,
+    tls: TlsConnector,
+    pool: Mutex<Pool>,
+}
+
+impl HttpClient {
+    pub(crate) fn new(http1: bool, http2: bool) -> Self {
+        let mut alpn_protocols = Vec::new();
+        if http2 {
+            alpn_protocols.push(b"h2".to_vec());
+        }
+        if http1 {
+            alpn_protocols.push(b"http/1.1".to_vec());
+        }
+        HttpClient {
+            http1,
+            tls: client_connector(alpn_protocols),
+            pool: Mutex::default(),
+        }
+    }
+
+    /// Send `request`, delivering the response to `sink`.
+    pub(crate) async fn send(&self, mut request: HttpRequest, mut sink: ResponseSink) {
+        if !request
+            .headers
+            .iter()
+            .any(|(name, _)| name.eq_ignore_ascii_case("accept-encoding"))
+        {
+            request
+                .headers
+                .push(("accept-encoding".to_string(), ACCEPT_ENCODING.to_string()));
+        }
+        if let Err(error) = self.try_send(&mut request, &mut sink).await {
+            sink.fail(error).await;
+        }
+    }
+
+    async fn try_send(&self, request: &mut HttpRequest, sink: &mut ResponseSink) -> io::Result<()> {
+        let origin = Origin::of(&request.url)?;
+        if let Some(connection) = self.pooled(&origin) {
+            match self.send_on(&origin, connection, request, sink).await {
+                // Nothing was answered or consumed yet, so retry once on a
+                // fresh connection
+                Err(Failure::Stale(_)) => {}
+                result => return result.map_err(Failure::into_io),
+            }
+        }
+        let connection = self.connect(&origin).await?;
+        self.send_on(&origin, connection, request, sink)
+            .await
+            .map_err(Failure::into_io)
+    }
+
+    async fn send_on(
+        &self,
+        origin: &Origin,
+        connection: Connection,
+        request: &mut HttpRequest,
+        sink: &mut ResponseSink,
+    ) -> Result<(), Failure> {
+        match connection {
+            Connection::Http2(send_request) => {
+                let result = send_http2(send_request, request, sink).await;
+                if let Err(Failure::Stale(_)) = &result {
+                    self.pool.lock().unwrap().http2.remove(origin);
+                }
+                result
+            }
+            Connection::Http1(mut connection) => {
+                if connection.send(request, sink).await? {
+                    self.release(origin, connection);
+                }
+                Ok(())
+            }
+        }
+    }
+
+    /// Take a connection to `origin` from the pool.
+    fn pooled(&self, origin: &Origin) -> Option<Connection> {
+        let mut pool = self.pool.lock().unwrap();
+        if let Some(send_request) = pool.http2.get(origin) {
+            return Some(Connection::Http2(send_request.clone()));
+        }
+        let idle = pool.http1.get_mut(origin)?;
+        while let Some(connection) = idle.pop() {
+            if connection.idle_since.elapsed() < IDLE_TIMEOUT {
+                return Some(Connection::Http1(connection));
+            }
+        }
+        None
+    }
+
+    fn release(&self, origin: &Origin, mut connection: Http1Connection) {
+        // Bytes past the end of the response mean the framing was off
+        if !connection.reader.buffer.is_empty() {
+            return;
+        }
+        connection.idle_since = Instant::now();
+        let mut pool = self.pool.lock().unwrap();
+        let idle = pool.http1.entry(origin.clone()).or_default();
+        idle.retain(|connection| connection.idle_since.elapsed() < IDLE_TIMEOUT);
+        if idle.len() < MAX_IDLE_PER_ORIGIN {
+            idle.push(connection);
+        }
+    }
+
+    async fn connect(&self, origin: &Origin) -> io::Result<Connection> {
+        let tcp = TcpStream::connect((origin.host.as_str(), origin.port)).await?;
+        let _ = tcp.set_nodelay(true);
+
+        let (io, http2): (Box<dyn Io>, bool) = if origin.secure {
+            let name = ServerName::try_from(origin.host.clone())
+                .map_err(|_| invalid_input(format!("Invalid DNS name: {}", origin.host)))?;
+            let stream = self.tls.connect(name, tcp).await?;
+            let http2 = stream.get_ref().1.alpn_protocol() == Some(b"h2");
+            (Box::new(stream), http2)
+        } else {
+            // Without ALPN to ask, HTTP/2 is only spoken when the client was
+            // told the server understands it
+            (Box::new(tcp), !self.http1)
+        };
+
+        if http2 {
+            let (send_request, connection) = h2::client::handshake(io).await.map_err(h2_error)?;
+            tokio::spawn(async move {
+                let _ = connection.await;
+            });
+            self.pool
+                .lock()
+                .unwrap()
+                .http2
+                .insert(origin.clone(), send_request.clone());
+            Ok(Connection::Http2(send_request))
+        } else if self.http1 {
+            Ok(Connection::Http1(Http1Connection {
+                reader: Reader {
+                    stream: io,
+                    buffer: Vec::new(),
+                },
+                idle_since: Instant::now(),
+            }))
+        } else {
+            Err(io::Error::other(format!(
+                "{}:{} does not support HTTP/2",
+                origin.host, origin.port
+            )))
+        }
+    }
+}
+
+async fn send_http2(
+    send_request: h2::client::SendRequest<Bytes>,
+    request: &mut HttpRequest,
+    sink: &mut ResponseSink,
+) -> Result<(), Failure> {
+    let mut send_request = send_request
+        .ready()
+        .await
+        .map_err(|error| Failure::Stale(h2_error(error)))?;
+
+    let mut uri = request.url.clone();
+    uri.set_fragment(None);
+    let mut builder = http::Request::builder()
+        .method(request.method.as_str())
+        .uri(uri.as_str());
+    for (name, value) in &request.headers {
+        let name = name.to_ascii_lowercase();
+        if CONNECTION_HEADERS.contains(&name.as_str())
+            || (name == "te" && !value.trim().eq_ignore_ascii_case("trailers"))
+        {
+            continue;
+        }
+        builder = builder.header(name, value.as_str());
+    }
+    let head = builder
+        .body(())
+        .map_err(|error| Failure::Failed(invalid_input(error.to_string())))?;
+
+    let replayable = request.body.replayable();
+    let refused = |error: h2::Error| {
+        if replayable {
+            stale_if_refused(error)
+        } else {
+            Failure::Failed(h2_error(error))
+        }
+    };
+    let failed = |error: h2::Error| Failure::Failed(h2_error(error));
+
+    let end_of_stream = matches!(&request.body, RequestBody::Full(body) if body.is_empty());
+    let (response, mut stream) = send_request
+        .send_request(head, end_of_stream)
+        .map_err(refused)?;
+    match &mut request.body {
+        RequestBody::Full(body) if !end_of_stream => {
+            send_all(&mut stream, body.clone(), true)
+                .await
+                .map_err(failed)?;
+        }
+        RequestBody::Full(_) => {}
+        RequestBody::Stream(chunks) => loop {
+            match chunks.recv().await {
+                Some(RequestChunk::Data(data)) if data.is_empty() => {}
+                Some(RequestChunk::Data(data)) => {
+                    send_all(&mut stream, Bytes::from(data), false)
+                        .await
+                        .map_err(failed)?;
+                }
+                Some(RequestChunk::End) => {
+                    stream.send_data(Bytes::new(), true).map_err(failed)?;
+                    break;
+                }
+                None => {
+                    stream.send_reset(h2::Reason::CANCEL);
+                    return Err(Failure::Failed(aborted()));
+                }
+            }
+        },
+    }
+
+    let response = response.await.map_err(refused)?;
+    let (parts, mut body) = response.into_parts();
+    sink.head(ResponseHead {
+        status: parts.status.as_u16(),
+        // HTTP/2 has no reason phrase
+        status_text: String::new(),
+        headers: parts
+            .headers
+            .iter()
+            .map(|(name, value)| {
+                (
+                    name.as_str().to_string(),
+                    String::from_utf8_lossy(value.as_bytes()).into_owned(),
+                )
+            })
+            .collect(),
+        version: "HTTP/2",
+    });
+
+    // Capacity is released once a chunk was handed on, so a slow reader
+    // holds back the server
+    while let Some(chunk) = body.data().await {
+        let chunk = chunk.map_err(failed)?;
+        let length = chunk.len();
+        sink.data(chunk.to_vec()).await.map_err(Failure::Failed)?;
+        let _ = body.flow_control().release_capacity(length);
+    }
+    sink.end().await.map_err(Failure::Failed)
+}
+
+/// Send `data` as the server's flow control window allows.
+async fn send_all(
+    stream: &mut h2::SendStream<Bytes>,
+    mut data: Bytes,
+    end_of_stream: bool,
+) -> Result<(), h2::Error> {
+    while !data.is_empty() {
+        stream.reserve_capacity(data.len());
+        let capacity = match std::future::poll_fn(|cx| stream.poll_capacity(cx)).await {
+            Some(capacity) => capacity?,
+            None => return Err(h2::Reason::CANCEL.into()),
+        };
+        if capacity == 0 {
+            continue;
+        }
+        let chunk = data.split_to(capacity.min(data.len()));
+        stream.send_data(chunk, end_of_stream && data.is_empty())?;
+    }
+    Ok(())
+}
+
+/// A stream the server refused was never processed and is safe to retry.
+fn stale_if_refused(error: h2::Error) -> Failure {
+    if error.reason() == Some(h2::Reason::REFUSED_STREAM) {
+        Failure::Stale(h2_error(error))
+    } else {
+        Failure::Failed(h2_error(error))
+    }
+}
+
+fn h2_error(error: h2::Error) -> io::Error {
+    if error.is_io() {
+        error
+            .into_io()
+            .unwrap_or_else(|| io::Error::other("HTTP/2 I/O error"))
+    } else {
+        io::Error::other(error)
+    }
+}
+
+fn invalid_input(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, message)
+}
+
+struct Http1Head {
+    status: u16,
+    reason: String,
+    minor_version: u8,
+    headers: Vec<(String, String)>,
+}
+
+impl Http1Head {
+    fn header_values<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a str> + 'a {
+        self.headers
+            .iter()
+            .filter(move |(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    fn has_token(&self, name: &str, token: &str) -> bool {
+        self.header_values(name)
+            .flat_map(|value| value.split(','))
+            .any(|item| item.trim().eq_ignore_ascii_case(token))
+    }
+
+    fn keep_alive(&self) -> bool {
+        if self.has_token("connection", "close") {
+            false
+        } else {
+            self.minor_version >= 1 || self.has_token("connection", "keep-alive")
+        }
+    }
+}
+
+/// An HTTP/1.1 connection together with the bytes read ahead of the current
+/// response.
+struct Http1Connection {
+    reader: Reader<Box<dyn Io>>,
+    idle_since: Instant,
+}
+
+impl Http1Connection {
+    /// Send `request` and read its response into `sink`, returning whether
+    /// the connection can carry another request.
+    async fn send(
+        &mut self,
+        request: &mut HttpRequest,
+        sink: &mut ResponseSink,
+    ) -> Result<bool, Failure> {
+        let url = &request.url;
+        let mut target = url.path().to_string();
+        if let Some(query) = url.query() {
+            target.push('?');
+            target.push_str(query);
+        }
+        let authority = match url.port() {
+            Some(port) => format!("{}:{port}", url.host_str().unwrap_or_default()),
+            None => url.host_str().unwrap_or_default().to_string(),
+        };
+
+        let mut out = Vec::with_capacity(256);
+        out.extend_from_slice(format!("{} {target} HTTP/1.1\r\n", request.method).as_bytes());
+        push_header(&mut out, "host", &authority);
+        for (name, value) in &request.headers {
+            if [
+                "host",
+                "connection",
+                "keep-alive",
+                "content-length",
+                "transfer-encoding",
+            ]
+            .iter()
+            .any(|framing| name.eq_ignore_ascii_case(framing))
+            {
+                continue;
+            }
+            push_header(&mut out, name, value);
+        }
+        match &request.body {
+            RequestBody::Full(body)
+                if !body.is_empty()
+                    || matches!(request.method.as_str(), "POST" | "PUT" | "PATCH") =>
+            {
+                push_header(&mut out, "content-length", &body.len().to_string());
+            }
+            RequestBody::Full(_) => {}
+            // The length of a streamed body is not known up front
+            RequestBody::Stream(_) => push_header(&mut out, "transfer-encoding", "chunked"),
+        }
+        out.extend_from_slice(b"\r\n");
+        if let RequestBody::Full(body) = &request.body {
+            out.extend_from_slice(body);
+        }
+
+        // A connection the server closed while idle fails here, before any of
+        // a streamed body was consumed
+        self.reader
+            .stream
+            .write_all(&out)
+            .await
+            .map_err(Failure::Stale)?;
+        if let RequestBody::Stream(chunks) = &mut request.body {
+            loop {
+                match chunks.recv().await {
+                    Some(RequestChunk::Data(data)) if data.is_empty() => {}
+                    Some(RequestChunk::Data(data)) => {
+                        let mut frame = Vec::with_capacity(data.len() + 16);
+                        frame.extend_from_slice(format!("{:x}\r\n", data.len()).as_bytes());
+                        frame.extend_from_slice(&data);
+                        frame.extend_from_slice(b"\r\n");
+                        self.reader
+                            .stream
+                            .write_all(&frame)
+                            .await
+                            .map_err(Failure::Failed)?;
+                    }
+                    Some(RequestChunk::End) => {
+                        self.reader
+                            .stream
+                            .write_all(b"0\r\n\r\n")
+                            .await
+                            .map_err(Failure::Failed)?;
+                        break;
+                    }
+                    None => return Err(Failure::Failed(aborted())),
+                }
+            }
+        }
+        self.reader.stream.flush().await.map_err(Failure::Failed)?;
+
+        // Informational responses other than 101 precede the final one
+        let mut retryable = request.body.replayable();
+        let head = loop {
+            let head = self.read_head(retryable).await?;
+            retryable = false;
+            if head.status >= 200 || head.status == 101 {
+                break head;
+            }
+        };
+
+        let bodyless = request.method.eq_ignore_ascii_case("HEAD")
+            || head.status == 101
+            || head.status == 204
+            || head.status == 304;
+        let chunked = head.has_token("transfer-encoding", "chunked");
+        let length = head
+            .header_values("content-length")
+            .next()
+            .and_then(|value| value.trim().parse::<u64>().ok());
+        let mut keep_alive = head.keep_alive() && head.status != 101;
+
+        sink.head(ResponseHead {
+            status: head.status,
+            status_text: head.reason,
+            headers: head.headers,
+            version: if head.minor_version == 0 {
+                "HTTP/1.0"
+            } else {
+                "HTTP/1.1"
+            },
+        });
+
+        let read = if bodyless {
+            Ok(())
+        } else if chunked {
+            self.reader.read_chunked(sink).await
+        } else if let Some(length) = length {
+            self.reader.read_exact_into(length, sink).await
+        } else {
+            // The body ends when the server closes the connection
+            keep_alive = false;
+            self.reader.read_to_end(sink).await
+        };
+        read.map_err(Failure::Failed)?;
+        sink.end().await.map_err(Failure::Failed)?;
+
+        Ok(keep_alive)
+    }
+
+    /// Read a response head. A connection closed before any of it arrived is
+    /// reported as stale when `retryable` is set.
+    async fn read_head(&mut self, retryable: bool) -> Result<Http1Head, Failure> {
+        loop {
+            if !self.reader.buffer.is_empty() {
+                let mut headers = [httparse::EMPTY_HEADER; MAX_HEADERS];
+                let mut response = httparse::Response::new(&mut headers);
+                match response.parse(&self.reader.buffer) {
+                    Ok(httparse::Status::Complete(length)) => {
+                        let head = Http1Head {
+                            status: response.code.unwrap_or_default(),
+                            reason: response.reason.unwrap_or_default().to_string(),
+                            minor_version: response.version.unwrap_or_default(),
+                            headers: response
+                                .headers
+                                .iter()
+                                .map(|header| {
+                                    (
+                                        header.name.to_string(),
+                                        String::from_utf8_lossy(header.value).into_owned(),
+                                    )
+                                })
+                                .collect(),
+                        };
+                        self.reader.buffer.drain(..length);
+                        return Ok(head);
+                    }
+                    Ok(httparse::Status::Partial) if self.reader.buffer.len() < MAX_HEAD_SIZE => {}
+                    Ok(httparse::Status::Partial) => {
+                        return Err(Failure::Failed(invalid_data("response head is too large")));
+                    }
+                    Err(error) => return Err(Failure::Failed(invalid_data(&error.to_string()))),
+                }
+            }
+
+            match self.reader.fill().await {
+                Ok(0) if retryable && self.reader.buffer.is_empty() => {
+                    return Err(Failure::Stale(io::ErrorKind::UnexpectedEof.into()));
+                }
+                Ok(0) => return Err(Failure::Failed(unexpected_eof())),
+                Ok(_) => {}
+                Err(error) if retryable && self.reader.buffer.is_empty() => {
+                    return Err(Failure::Stale(error));
+                }
+                Err(error) => return Err(Failure::Failed(error)),
+            }
+        }
+    }
+}
+
+fn push_header(out: &mut Vec<u8>, name: &str, value: &str) {
+    out.extend_from_slice(name.as_bytes());
+    out.extend_from_slice(b": ");
+    out.extend_from_slice(value.as_bytes());
+    out.extend_from_slice(b"\r\n");
+}
+
+fn aborted() -> io::Error {
+    io::Error::other("request body was aborted")
+}
+
+fn unexpected_eof() -> io::Error {
+    io::Error::new(
+        io::ErrorKind::UnexpectedEof,
+        "connection closed before the response was complete",
+    )
+}
@@ -0,0 +1,113 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! Decoding of `Content-Encoding` compressed response bodies as they stream
+//! in.
+
+use std::io::{self, Write};
+
+use flate2::write::{GzDecoder, ZlibDecoder};
+
+/// Output buffered inside the brotli decoder before it is handed on.
+const BROTLI_BUFFER_SIZE: usize = 4096;
+
+enum Coding {
+    Gzip(Box<GzDecoder<Vec<u8>>>),
+    Deflate(Box<ZlibDecoder<Vec<u8>>>),
+    Brotli(Box<brotli::DecompressorWriter<Vec<u8>>>),
+}
+
+impl Coding {
+    fn decode(&mut self, chunk: &[u8]) -> io::Result<Vec<u8>> {
+        match self {
+            Coding::Gzip(decoder) => {
+                decoder.write_all(chunk)?;
+                Ok(std::mem::take(decoder.get_mut()))
+            }
+            Coding::Deflate(decoder) => {
+                decoder.write_all(chunk)?;
+                Ok(std::mem::take(decoder.get_mut()))
+            }
+            Coding::Brotli(decoder) => {
+                decoder.write_all(chunk)?;
+                Ok(std::mem::take(decoder.get_mut()))
+            }
+        }
+    }
+
+    fn finish(self) -> io::Result<Vec<u8>> {
+        match self {
+            Coding::Gzip(decoder) => decoder.finish(),
+            Coding::Deflate(decoder) => decoder.finish(),
+            Coding::Brotli(mut decoder) => {
+                decoder.close()?;
+                Ok(std::mem::take(decoder.get_mut()))
+            }
+        }
+    }
+}
+
+/// Undoes the content codings of a response body, in the reverse of the order
+/// they were applied in.
+pub(crate) struct Decoder {
+    codings: Vec<Coding>,
+    /// Whether any of the body arrived. An empty body, such as the one of a
+    /// `HEAD` response, has nothing to decode.
+    started: bool,
+}
+
+impl Decoder {
+    /// A decoder for the `Content-Encoding` header values, or `None` when a
+    /// coding is not one of `gzip`, `deflate` and `br`. Such bodies are passed
+    /// through as they are.
+    pub(crate) fn new<'a>(content_encoding: impl Iterator<Item = &'a str>) -> Option<Self> {
+        let mut codings = Vec::new();
+        for coding in content_encoding.flat_map(|value| value.split(',')) {
+            let coding = match coding.trim().to_ascii_lowercase().as_str() {
+                "" | "identity" => continue,
+                "gzip" | "x-gzip" => Coding::Gzip(Box::new(GzDecoder::new(Vec::new()))),
+                "deflate" => Coding::Deflate(Box::new(ZlibDecoder::new(Vec::new()))),
+                "br" => Coding::Brotli(Box::new(brotli::DecompressorWriter::new(
+                    Vec::new(),
+                    BROTLI_BUFFER_SIZE,
+                ))),
+                _ => return None,
+            };
+            codings.push(coding);
+        }
+        codings.reverse();
+        Some(Decoder {
+            codings,
+            started: false,
+        })
+    }
+
+    /// Decode the next chunk of the body, returning the bytes decoded so far.
+    pub(crate) fn decode(&mut self, chunk: Vec<u8>) -> io::Result<Vec<u8>> {
+        self.started |= !chunk.is_empty();
+        let mut data = chunk;
+        for coding in &mut self.codings {
+            if data.is_empty() {
+                break;
+            }
+            data = coding.decode(&data)?;
+        }
+        Ok(data)
+    }
+
+    /// Decode what is left once the body is complete, failing if it was cut
+    /// short.
+    pub(crate) fn finish(self) -> io::Result<Vec<u8>> {
+        if !self.started {
+            return Ok(Vec::new());
+        }
+        let mut pending = Vec::new();
+        for mut coding in self.codings {
+            let mut data = coding.decode(&pending)?;
+            data.extend(coding.finish()?);
+            pending = data;
+        }
+        Ok(pending)
+    }
+}
@@ -63,6 +63,193 @@ function hasRequestHeader(request: any, name: string): boolean {
   return false;
 }
 
+/** Marks the resource ID of an `HttpClient`. */
+const HTTP_CLIENT_RID = Symbol("httpClientRid");
+
+interface CreateHttpClientOptions {
+  /** Whether HTTP/1.1 may be used, true by default. */
+  http1?: boolean;
+  /** Whether HTTP/2 may be used, true by default. */
+  http2?: boolean;
+}
+
+/**
+ * A pool of connections used by the `fetch` calls it is passed to as
+ * `client`. Without HTTP/1.1, plain `http:` URLs are fetched over HTTP/2 with
+ * prior knowledge (h2c).
+ */
+class HttpClient {
+  [HTTP_CLIENT_RID]: number;
+
+  constructor(options: CreateHttpClientOptions = {}) {
+    const http1 = options.http1 ?? true;
+    const http2 = options.http2 ?? true;
+    if (!http1 && !http2) {
+      throw new TypeError("Either `http1` or `http2` must be enabled");
+    }
+    this[HTTP_CLIENT_RID] = __andromeda__.internal_fetch_client_create(
+      String(http1),
+      String(http2),
+    );
+  }
+
+  /** Close the client's connections once their requests completed. */
+  close(): void {
+    __andromeda__.internal_fetch_client_close(this[HTTP_CLIENT_RID]);
+  }
+}
+
+function fetchBytesToHex(bytes: Uint8Array): string {
+  let hex = "";
+  for (let i = 0; i < bytes.length; i++) {
+    hex += bytes[i].toString(16).padStart(2, "0");
+  }
+  return hex;
+}
+
+function fetchHexToBytes(hex: string): Uint8Array {
+  const bytes = new Uint8Array(hex.length / 2);
+  for (let i = 0; i < bytes.length; i++) {
+    bytes[i] = parseInt(hex.substr(i * 2, 2), 16);
+  }
+  return bytes;
+}
+
+/** Collect a request body with a known source into bytes. */
+async function fetchBodyBytes(body: any): Promise<Uint8Array> {
+  if (body === null || body === undefined) {
+    return new Uint8Array(0);
+  }
+  if (typeof body === "string") {
+    return new TextEncoder().encode(body);
+  }
+  if (body instanceof Uint8Array) {
+    return body;
+  }
+  if (body instanceof ArrayBuffer) {
+    return new Uint8Array(body);
+  }
+  if (ArrayBuffer.isView(body)) {
+    return new Uint8Array(body.buffer, body.byteOffset, body.byteLength);
+  }
+  return fetchBodyBytes(body.source ?? null);
+}
+
+/**
+ * Start sending `request` through the native client and return the resource
+ * ID of the request. A body with a known source is sent whole, a body that is
+ * only a stream is written as it is read.
+ */
+async function fetchStartRequest(
+  fetchParams: any,
+  request: any,
+  headers: [string, string][],
+): Promise<number> {
+  const stream = request.body?.source === null &&
+      request.body.stream instanceof ReadableStream ?
+    request.body.stream :
+    null;
+  const body = stream === null ? await fetchBodyBytes(request.body) : null;
+  const rid = __andromeda__.internal_fetch(
+    request.httpClient?.[HTTP_CLIENT_RID] ?? -1,
+    request.method || "GET",
+    request.currentURL.href,
+    JSON.stringify(headers ?? []),
+    body === null ? "" : fetchBytesToHex(body),
+    String(stream !== null),
+  );
+  if (stream !== null) {
+    fetchWriteRequestBody(fetchParams, rid, stream);
+  }
+  return rid;
+}
+
+/** Write a request body stream to request `rid` as it is read. */
+async function fetchWriteRequestBody(
+  fetchParams: any,
+  rid: number,
+  stream: ReadableStream<Uint8Array>,
+) {
+  const reader = stream.getReader();
+  fetchParams.controller?.abortSteps?.push((reason: any) => {
+    reader.cancel(reason).catch(() => {});
+  });
+  try {
+    while (true) {
+      const { done, value } = await reader.read();
+      if (done) break;
+      if (fetchParams.controller?.state === "aborted") return;
+      if (!(value instanceof Uint8Array)) {
+        throw new TypeError("Request body chunks must be Uint8Array");
+      }
+      // Resolves once the chunk was taken, so a slow connection holds back
+      // the stream
+      await __andromeda__.internal_fetch_body_write(
+        rid,
+        fetchBytesToHex(value),
+      );
+    }
+    __andromeda__.internal_fetch_body_end(rid, "false");
+  } catch (error) {
+    __andromeda__.internal_fetch_body_end(rid, "true");
+    reader.cancel(error).catch(() => {});
+  }
+}
+
+/**
+ * The body of the response to request `rid`, read from the native client as
+ * it is pulled. The client already undid any content coding. Aborting the
+ * fetch errors the stream, `close` releases the request.
+ */
+function fetchResponseStream(
+  fetchParams: any,
+  rid: number,
+  close: () => void,
+): ReadableStream<Uint8Array> {
+  let done = false;
+  return new ReadableStream<Uint8Array>({
+    start(controller) {
+      fetchParams.controller?.abortSteps?.push((reason: any) => {
+        if (done) return;
+        done = true;
+        close();
+        try {
+          controller.error(reason);
+        } catch {
+          // The stream was already closed
+        }
+      });
+    },
+    async pull(controller) {
+      let chunk: string;
+      try {
+        chunk = await __andromeda__.internal_fetch_read(rid);
+      } catch (error) {
+        if (done) return;
+        done = true;
+        close();
+        controller.error(
+          new TypeError(error instanceof Error ? error.message : String(error)),
+        );
+        return;
+      }
+      // Aborted while the read was pending
+      if (done) return;
+      if (chunk.length === 0) {
+        done = true;
+        close();
+        controller.close();
+        return;
+      }
+      controller.enqueue(fetchHexToBytes(chunk));
+    },
+    cancel() {
+      done = true;
+      close();
+    },
+  });
+}
+
 const networkError = () => ({
   type: "error",
   status: 0,
@@ -83,6 +270,9 @@ const createDeferredPromise = () => {
   return { promise, resolve: res, reject: rej };
 };
 
+/**
+ * @see https://fetch.spec.whatwg.org/#fetch-controller
+ */
 class Fetch {
   // TODO: Event
   constructor() {
@@ -90,9 +280,64 @@ class Fetch {
     (this as any).connection = null;
     (this as any).dump = false;
     (this as any).state = "ongoing";
+    (this as any).serializedAbortReason = undefined;
+    // Steps the network layer registers to stop a request in flight
+    (this as any).abortSteps = [];
+  }
+
+  /**
+   * @see https://fetch.spec.whatwg.org/#fetch-controller-abort
+   */
+  abort(error?: any) {
+    if ((this as any).state !== "ongoing") return;
+    (this as any).state = "aborted";
+    const reason = error !== undefined ?
+      error :
+      new DOMException("The operation was aborted.", "AbortError");
+    (this as any).serializedAbortReason = reason;
+    for (const step of (this as any).abortSteps) {
+      step(reason);
+    }
+  }
+
+  /**
+   * @see https://fetch.spec.whatwg.org/#fetch-controller-terminate
+   */
+  terminate() {
+    if ((this as any).state !== "ongoing") return;
+    (this as any).state = "terminated";
+    for (const step of (this as any).abortSteps) {
+      step(new TypeError("Fetch was terminated"));
+    }
   }
 }
 
+/**
+ * @see https://fetch.spec.whatwg.org/#abort-fetch
+ */
+const abortFetch = (
+  p: any,
+  request: any,
+  responseObject: any,
+  error: any,
+) => {
+  // 1. Reject promise with error.
+  p.reject(error);
+
+  // 2. If request's body is non-null and is readable, then cancel request's
+  //    body with error.
+  const stream = request.body?.stream;
+  if (stream instanceof ReadableStream && !stream.locked) {
+    stream.cancel(error).catch(() => {});
+  }
+
+  // 3. If responseObject is null, then return.
+  // 4. Let response be responseObject's response.
+  // 5. If response's body is non-null and is readable, then error response's
+  //    body with error.
+  // NOTE: Aborting the controller already errored a body still being read.
+};
+
 /**
  * Implementation of the fetch API for Andromeda
  * Based on: https://developer.mozilla.org/ja/docs/Web/API/Window/fetch
@@ -152,7 +397,8 @@ const andromedaFetch = (input: RequestInfo, init = undefined) => {
     referrerPolicy = requestObject.referrerPolicy || "";
     integrity = requestObject.integrity || "";
     keepalive = requestObject.keepalive || false;
-    body = requestObject.body || null;
+    // The inner body, whose source tells a body known up front from a stream
+    body = requestObject[(globalThis as any).BODY_SYMBOL] || null;
     signal = requestObject.signal || null;
     destination = requestObject.destination || "";
 
@@ -197,6 +443,7 @@ const andromedaFetch = (input: RequestInfo, init = undefined) => {
       useCORSPreflightFlag: false,
       credentialsMode: credentials,
       CORSExposedHeaderNameList: [],
+      httpClient: (init as any)?.client ?? null,
     };
   } catch (e) {
     const errorToReject = e instanceof Error ?
@@ -207,15 +454,14 @@ const andromedaFetch = (input: RequestInfo, init = undefined) => {
   }
 
   // 4. If requestObject’s signal is aborted, then:
-  // if (request.signal.aborted) {
-  // 1. Abort the fetch() call with p, request, null, and
-  // requestObject’s signal’s abort reason.
-  //
-  // TODO: abortFetch
-  //
-  // 2. Return p.
-  // return p.promise;
-  // }
+  if (request.signal?.aborted) {
+    // 1. Abort the fetch() call with p, request, null, and
+    // requestObject’s signal’s abort reason.
+    abortFetch(p, request, null, request.signal.reason);
+
+    // 2. Return p.
+    return p.promise;
+  }
 
   // 5. Let globalObject be request’s client’s global object.
   // const globalObject = request.client.globalObject;
@@ -237,15 +483,19 @@ const andromedaFetch = (input: RequestInfo, init = undefined) => {
   let locallyAborted = false;
 
   // 10. Let controller be null.
-  let controller = null;
+  let controller: any = null;
 
-  // TODO: abort controller
   // 11. Add the following abort steps to requestObject’s signal:
-  //  1. Set locallyAborted to true.
-  //  2. Assert: controller is non-null.
-  //  3. Abort controller with requestObject’s signal’s abort reason.
-  //  4. Abort the fetch() call with p, request, responseObject,
-  //     and requestObject’s signal’s abort reason.
+  request.signal?.addEventListener("abort", () => {
+    //  1. Set locallyAborted to true.
+    locallyAborted = true;
+    //  2. Assert: controller is non-null.
+    //  3. Abort controller with requestObject’s signal’s abort reason.
+    controller?.abort(request.signal.reason);
+    //  4. Abort the fetch() call with p, request, responseObject,
+    //     and requestObject’s signal’s abort reason.
+    abortFetch(p, request, responseObject, request.signal.reason);
+  }, { once: true });
 
   // 12. Set controller to the result of calling fetch given request
   //     and processResponse given response being these steps:
@@ -264,6 +514,16 @@ const andromedaFetch = (input: RequestInfo, init = undefined) => {
         return;
       }
 
+      if (response?.aborted) {
+        abortFetch(
+          p,
+          request,
+          responseObject,
+          controller?.serializedAbortReason,
+        );
+        return;
+      }
+
       if (response?.type === "error") {
         p.reject(new TypeError("Network error"));
         return;
@@ -273,7 +533,10 @@ const andromedaFetch = (input: RequestInfo, init = undefined) => {
       // Convert body object to Uint8Array if needed
       let bodyData = null;
       if (response.body) {
-        if (response.body instanceof Uint8Array) {
+        if (
+          response.body instanceof ReadableStream ||
+          response.body instanceof Uint8Array
+        ) {
           bodyData = response.body;
         } else if (
           typeof response.body === "object" &&
@@ -305,6 +568,7 @@ const andromedaFetch = (input: RequestInfo, init = undefined) => {
       responseObject = new Response(bodyData, {
         status: response.status,
         statusText: response.statusText,
+        headers: response.headersList,
       } as any);
 
       // Add additional properties that might be needed
@@ -338,6 +602,9 @@ const andromedaFetch = (input: RequestInfo, init = undefined) => {
 };
 
 globalThis.fetch = andromedaFetch;
+globalThis.__andromeda_create_http_client = (
+  options?: CreateHttpClientOptions,
+) => new HttpClient(options);
 /**
  * @see https://fetch.spec.whatwg.org/#fetch-response-handover
  */
@@ -1546,13 +1813,8 @@ const httpNetworkFetch = async (
   }
 
   //  3. If connection is an HTTP/1.x connection, request's body is non-null, and request's body's source is null, then return a network error.
-  if (
-    connection.type === "http" &&
-    request.body !== null &&
-    request.body?.source === null
-  ) {
-    return networkError();
-  }
+  // NOTE: The native client sends such a body with chunked transfer coding
+  //       over HTTP/1.1, so it is not refused here.
 
   //  4. Set timingInfo's final network-request start time to the coarsened shared current time given fetchParams's cross-origin isolated capability.
   if (timingInfo) {
@@ -1582,126 +1844,47 @@ const httpNetworkFetch = async (
 
     let status = 200;
     let statusText = "OK";
-    let responseBody: Uint8Array | null = null;
+    let responseBody: ReadableStream<Uint8Array> | null = null;
     let responseHeaders: [string, string][] = [];
 
-    // Use TLS for HTTPS connections
-    if (request.currentURL.protocol === "https:") {
-      try {
-        const host = request.currentURL.hostname;
-        const port = request.currentURL.port || 443;
-
-        // Connect with TLS
-        const rid = await __andromeda__.internal_tls_connect(host, port);
-
-        // Format HTTP request
-        const method = request.method || "GET";
-        const path = request.currentURL.pathname + request.currentURL.search;
-
-        // Build HTTP request string
-        let httpRequest = `${method} ${path} HTTP/1.1\r\n`;
-        httpRequest += `Host: ${host}\r\n`;
-
-        // Add headers
-        if (headers && Array.isArray(headers)) {
-          for (const [name, value] of headers) {
-            httpRequest += `${name}: ${value}\r\n`;
-          }
-        }
-
-        // Add body if present
-        let bodyData = "";
-        if (request.body) {
-          if (request.body instanceof Uint8Array) {
-            bodyData = new TextDecoder().decode(request.body);
-          } else if (typeof request.body === "string") {
-            bodyData = request.body;
-          }
-
-          if (!hasRequestHeader(request, "Content-Length")) {
-            httpRequest += `Content-Length: ${bodyData.length}\r\n`;
-          }
-        }
-
-        httpRequest += `Connection: close\r\n`;
-        httpRequest += `\r\n`;
-        httpRequest += bodyData;
-
-        // Send request
-        await __andromeda__.internal_tls_write(rid, httpRequest);
-
-        // Read response
-        let responseHex = "";
-        const maxRetries = 100;
-        let retries = 0;
-
-        while (retries < maxRetries) {
-          try {
-            const chunk = await __andromeda__.internal_tls_read(rid, 4096);
-            if (!chunk || chunk.length === 0) break;
-            responseHex += chunk;
-          } catch (e) {
-            console.error("TLS read error:", e);
-            break;
-          }
-          retries++;
-        }
-
-        // Close connection
-        await __andromeda__.internal_tls_close(rid);
-
-        // Convert hex to bytes
-        function hexToUtf8(hex: string) {
-          if (!hex) return "";
-          const bytes = new Uint8Array(
-            hex.match(/.{1,2}/g)!.map((b) => parseInt(b, 16)),
-          );
-          return new TextDecoder().decode(bytes);
-        }
-
-        // Parse response
-        const responseText = hexToUtf8(responseHex);
-        const [headerPart, ...bodyParts] = responseText.split("\r\n\r\n");
-        const bodyText = bodyParts.join("\r\n\r\n");
-
-        // Parse status line
-        const lines = headerPart.split("\r\n");
-        const statusLine = lines[0];
-        const statusMatch = statusLine.match(/HTTP\/\d\.\d (\d+) (.+)/);
-
-        if (statusMatch) {
-          status = parseInt(statusMatch[1]);
-          statusText = statusMatch[2];
-        }
-
-        // Parse headers
-        for (let i = 1; i < lines.length; i++) {
-          const line = lines[i];
-          const colonIndex = line.indexOf(":");
-          if (colonIndex > 0) {
-            const name = line.substring(0, colonIndex).trim();
-            const value = line.substring(colonIndex + 1).trim();
-            responseHeaders.push([name, value]);
-          }
-        }
-
-        // Set response body
-        if (bodyText) {
-          responseBody = new TextEncoder().encode(bodyText);
-        }
-      } catch (error) {
+    // Send the request through the native client, which pools connections,
+    // speaks HTTP/2 where the server supports it and streams both bodies
+    let rid: number | null = null;
+    let closed = false;
+    const close = () => {
+      if (rid !== null && !closed) {
+        closed = true;
+        __andromeda__.internal_fetch_close(rid);
+      }
+    };
+    fetchParams.controller?.abortSteps?.push(close);
+    try {
+      rid = await fetchStartRequest(fetchParams, request, headers);
+      if (["aborted", "terminated"].includes(fetchParams.controller?.state)) {
+        close();
         return networkError();
       }
-    } else {
-      // For HTTP (non-TLS), return a simple mock response for now
-      responseBody = new TextEncoder().encode(
-        '{"message": "HTTP not implemented yet"}',
+      const result = JSON.parse(
+        await __andromeda__.internal_fetch_response(rid),
       );
-      responseHeaders = [
-        ["Content-Type", "application/json"],
-        ["Content-Length", String(responseBody.length)],
-        ["Date", new Date().toUTCString()],
-      ];
+      status = result.status;
+      statusText = result.statusText;
+      responseHeaders = result.headers;
+    } catch (error) {
+      close();
+      return networkError();
+    }
+
+    if (
+      request.method === "HEAD" ||
+      request.method === "CONNECT" ||
+      [101, 103, 204, 205, 304].includes(status)
+    ) {
+      // There is no body to hand out, wait for the connection to be done
+      // with the response before releasing the request
+      __andromeda__.internal_fetch_read(rid as number).then(close, close);
+    } else {
+      responseBody = fetchResponseStream(fetchParams, rid as number, close);
     }
 
     // Handle interim responses (100-199 range)
@@ -1827,33 +2010,13 @@ const httpNetworkFetch = async (
   }
 
   // 9. Let buffer be an empty byte sequence.
-  let buffer = new Uint8Array(0);
-
   // 10. Let stream be a new ReadableStream.
   // 11. Let pullAlgorithm be the following steps:
-  const pullAlgorithm = () => {
-    return new Promise<void>((resolve) => {
-      // Simplified implementation
-      setTimeout(() => {
-        if (fetchParams.controller?.state !== "aborted") {
-          resolve();
-        }
-      }, 0);
-    });
-  };
-
   // 12. Let cancelAlgorithm be an algorithm that aborts fetchParams's controller
-  const cancelAlgorithm = (reason: any) => {
-    if (fetchParams.controller?.abort) {
-      fetchParams.controller.abort(reason);
-    }
-  };
-
-  // 13. Set up stream with byte reading support (simplified)
+  // 13. Set up stream with byte reading support
   // 14. Set response's body to a new body whose stream is stream.
-  if (response && response.body) {
-    // Response body is already set from earlier processing
-  }
+  // NOTE: The body stream was set up by fetchResponseStream, which reads
+  //       chunks from the native client as they are pulled.
 
   // 15. If includeCredentials is true, parse and store Set-Cookie headers
   if (includeCredentials && response) {
@@ -1865,48 +2028,20 @@ const httpNetworkFetch = async (
   }
 
   // 16. Run these steps in parallel:
-  // (Simplified streaming implementation)
-  if (response) {
-    try {
-      // Handle response body streaming
-      if (response.body instanceof Uint8Array) {
-        const bytes = response.body;
-
-        // Extract Content-Encoding header
-        const contentEncodingHeader = response.headersList.find(
-          ([name]: [string, string]) =>
-            name.toLowerCase() === "content-encoding",
-        );
-        const codings = contentEncodingHeader ? [contentEncodingHeader[1]] : [];
-
-        let filteredCoding = "";
-        if (codings.length === 0) {
-          filteredCoding = "";
-        } else if (codings.length > 1) {
-          filteredCoding = "multiple";
-        } else {
-          filteredCoding = codings[0].toLowerCase();
-        }
-
-        // Set response body info (simplified)
-        if (!response.bodyInfo) {
-          response.bodyInfo = {
-            contentEncoding: filteredCoding,
-            encodedSize: bytes.length,
-            decodedSize: bytes.length,
-          };
-        }
-
-        // Append bytes to buffer
-        const newBuffer = new Uint8Array(buffer.length + bytes.length);
-        newBuffer.set(buffer);
-        newBuffer.set(bytes, buffer.length);
-        buffer = newBuffer;
-      }
-    } catch (error) {
-      // If error occurs during streaming, return network error
-      return networkError();
-    }
+  if (response && !response.bodyInfo) {
+    // Extract Content-Encoding header
+    const codings = response.headersList
+      .filter(([name]: [string, string]) =>
+        name.toLowerCase() === "content-encoding"
+      )
+      .flatMap(([, value]: [string, string]) => value.split(","))
+      .map((coding: string) => coding.trim().toLowerCase())
+      .filter((coding: string) => coding !== "");
+
+    // The native client decodes gzip, deflate and br while streaming
+    response.bodyInfo = {
+      contentEncoding: codings.length > 1 ? "multiple" : codings[0] ?? "",
+    };
   }
 
   // Handle abort cases
@@ -1954,6 +2089,11 @@ const httpRedirectFetch = async (fetchParams: any, response: any) => {
     return response;
   }
 
+  // The redirect response is not handed out, so release its connection
+  if (internalResponse.body instanceof ReadableStream) {
+    internalResponse.body.cancel().catch(() => {});
+  }
+
   // 6. If locationURL's scheme is not an HTTP(S) scheme, then return a network error.
   if (locationURL.protocol !== "http:" && locationURL.protocol !== "https:") {
     return networkError();
@@ -2,18 +2,118 @@
 // License, v. 2.0. If a copy of the MPL was not distributed with this
 // file, You can obtain one at https://mozilla.org/MPL/2.0/.
 
-use andromeda_core::Extension;
+mod client;
+mod decode;
+
+use std::{
+    cell::OnceCell,
+    io,
+    sync::{Arc, Mutex},
+};
+
+use andromeda_core::{
+    Extension, ExtensionOp, HostData, MacroTask, OpsStorage, ResourceTable, Rid, check_permission,
+};
+use bytes::Bytes;
+use nova_vm::{
+    ecmascript::{
+        builtins::{
+            ArgumentsList,
+            promise_objects::promise_abstract_operations::promise_capability_records::PromiseCapability,
+        },
+        execution::{Agent, JsResult, agent::ExceptionType},
+        types::{IntoValue, Value},
+    },
+    engine::{
+        Global,
+        context::{Bindable, GcScope},
+    },
+};
+use tokio::{
+    sync::{mpsc, oneshot},
+    task::AbortHandle,
+};
+
+use crate::RuntimeMacroTask;
+
+use super::command::decode_hex;
+use client::{HttpClient, HttpRequest, RequestBody, RequestChunk, ResponseHead, ResponseSink};
+
+/// Request and response body chunks buffered before writes stop resolving and
+/// the connection stops being read.
+const BODY_CHANNEL_CAPACITY: usize = 8;
+
+/// A request in flight, from `internal_fetch` until its response body was read
+/// or it was closed.
+#[derive(Clone)]
+struct FetchResource {
+    /// Set while a streamed request body is being written.
+    request_body: Arc<Mutex<Option<mpsc::Sender<RequestChunk>>>>,
+    head: Arc<Mutex<Option<oneshot::Receiver<io::Result<ResponseHead>>>>>,
+    body: Arc<tokio::sync::Mutex<mpsc::Receiver<io::Result<Vec<u8>>>>>,
+    /// Stops the task sending the request, closing its connection.
+    abort: AbortHandle,
+}
+
+struct FetchResources {
+    /// The client used by `fetch` calls without a `client` option, created on
+    /// first use.
+    default_client: OnceCell<Arc<HttpClient>>,
+    /// Clients created with `Andromeda.createHttpClient`.
+    clients: ResourceTable<Arc<HttpClient>>,
+    requests: ResourceTable<FetchResource>,
+}
 
 #[derive(Default)]
 pub struct FetchExt;
 
+#[hotpath::measure_all]
 impl FetchExt {
-    #[hotpath::measure]
     pub fn new_extension() -> Extension {
         Extension {
             name: "fetch",
-            ops: vec![],
-            storage: None,
+            ops: vec![
+                ExtensionOp::new("internal_fetch", Self::internal_fetch, 6, false),
+                ExtensionOp::new(
+                    "internal_fetch_body_write",
+                    Self::internal_fetch_body_write,
+                    2,
+                    false,
+                ),
+                ExtensionOp::new(
+                    "internal_fetch_body_end",
+                    Self::internal_fetch_body_end,
+                    2,
+                    false,
+                ),
+                ExtensionOp::new(
+                    "internal_fetch_response",
+                    Self::internal_fetch_response,
+                    1,
+                    false,
+                ),
+                ExtensionOp::new("internal_fetch_read", Self::internal_fetch_read, 1, false),
+                ExtensionOp::new("internal_fetch_close", Self::internal_fetch_close, 1, false),
+                ExtensionOp::new(
+                    "internal_fetch_client_create",
+                    Self::internal_fetch_client_create,
+                    2,
+                    false,
+                ),
+                ExtensionOp::new(
+                    "internal_fetch_client_close",
+                    Self::internal_fetch_client_close,
+                    1,
+                    false,
+                ),
+            ],
+            storage: Some(Box::new(|storage: &mut OpsStorage| {
+                storage.insert(FetchResources {
+                    default_client: OnceCell::new(),
+                    clients: ResourceTable::new(),
+                    requests: ResourceTable::new(),
+                });
+            })),
             files: vec![
                 include_str!("./body/inner_body.ts"),
                 include_str!("./body/extract.ts"),
@@ -33,4 +133,352 @@ impl FetchExt {
             ],
         }
     }
+
+    fn get_fetch_resources(agent: &Agent) -> std::cell::Ref<'_, FetchResources> {
+        let host_data = agent.get_host_data();
+        let host_data: &HostData<RuntimeMacroTask> = host_data.downcast_ref().unwrap();
+        std::cell::Ref::map(host_data.storage.borrow(), |storage| {
+            storage.get::<FetchResources>().unwrap()
+        })
+    }
+
+    fn get_request(agent: &Agent, rid: f64) -> Option<FetchResource> {
+        Self::get_fetch_resources(agent)
+            .requests
+            .get(Rid::from_index(rid as u32))
+    }
+
+    /// Start sending a request through the client `clientRid`, or the shared
+    /// client when it is negative, and return the id of the request.
+    /// `headers` is a JSON list of name/value pairs. The body is the hex
+    /// `body`, or with `streamBody` set to `"true"` is written through
+    /// `internal_fetch_body_write`.
+    fn internal_fetch<'gc>(
+        agent: &mut Agent,
+        _this: Value,
+        args: ArgumentsList,
+        mut gc: GcScope<'gc, '_>,
+    ) -> JsResult<'gc, Value<'gc>> {
+        let client_rid = args
+            .get(0)
+            .to_number(agent, gc.reborrow())
+            .unbind()?
+            .into_f64(agent);
+        let method_binding = args.get(1).to_string(agent, gc.reborrow()).unbind()?;
+        let url_binding = args.get(2).to_string(agent, gc.reborrow()).unbind()?;
+        let headers_binding = args.get(3).to_string(agent, gc.reborrow()).unbind()?;
+        let body_binding = args.get(4).to_string(agent, gc.reborrow()).unbind()?;
+        let stream_body_binding = args.get(5).to_string(agent, gc.reborrow()).unbind()?;
+
+        let url = url_binding
+            .as_str(agent)
+            .and_then(|url| url::Url::parse(url).ok())
+            .filter(|url| matches!(url.scheme(), "http" | "https") && url.host_str().is_some());
+        let headers = headers_binding
+            .as_str(agent)
+            .and_then(|headers| serde_json::from_str::<Vec<(String, String)>>(headers).ok());
+        let body = decode_hex(body_binding.as_str(agent).unwrap_or_default());
+        let stream_body = stream_body_binding.as_str(agent) == Some("true");
+        let (Some(url), Some(headers), Some(body)) = (url, headers, body) else {
+            return Err(agent
+                .throw_exception_with_static_message(
+                    ExceptionType::TypeError,
+                    "Invalid request",
+                    gc.nogc(),
+                )
+                .unbind());
+        };
+        let method = method_binding
+            .as_str(agent)
+            .unwrap_or("GET")
+            .to_ascii_uppercase();
+
+        let host = url.host_str().unwrap_or_default().to_string();
+        if let Err(error) = check_permission::<RuntimeMacroTask>(agent, |p| {
+            p.check_net(&host, url.port_or_known_default())
+        }) {
+            return Err(error
+                .into_js_error::<RuntimeMacroTask>(agent, gc.nogc())
+                .unbind());
+        }
+
+        let resources = Self::get_fetch_resources(agent);
+        let client = if client_rid < 0.0 {
+            Some(
+                resources
+                    .default_client
+                    .get_or_init(|| Arc::new(HttpClient::new(true, true)))
+                    .clone(),
+            )
+        } else {
+            resources.clients.get(Rid::from_index(client_rid as u32))
+        };
+        drop(resources);
+        let Some(client) = client else {
+            return Err(agent
+                .throw_exception_with_static_message(
+                    ExceptionType::TypeError,
+                    "HTTP client is closed",
+                    gc.nogc(),
+                )
+                .unbind());
+        };
+
+        let (request_body, body) = if stream_body {
+            let (sender, receiver) = mpsc::channel(BODY_CHANNEL_CAPACITY);
+            (Some(sender), RequestBody::Stream(receiver))
+        } else {
+            (None, RequestBody::Full(Bytes::from(body)))
+        };
+        let request = HttpRequest {
+            method,
+            url,
+            headers,
+            body,
+        };
+        let (head_sender, head) = oneshot::channel();
+        let (body_sender, response_body) = mpsc::channel(BODY_CHANNEL_CAPACITY);
+        let sink = ResponseSink::new(head_sender, body_sender);
+        let task = tokio::spawn(async move { client.send(request, sink).await });
+
+        let rid = Self::get_fetch_resources(agent)
+            .requests
+            .push(FetchResource {
+                request_body: Arc::new(Mutex::new(request_body)),
+                head: Arc::new(Mutex::new(Some(head))),
+                body: Arc::new(tokio::sync::Mutex::new(response_body)),
+                abort: task.abort_handle(),
+            });
+
+        Ok(Value::from_f64(agent, rid.index() as f64, gc.nogc()).unbind())
+    }
+
+    /// Queue a hex chunk of a streamed request body. Resolves once the chunk
+    /// was taken, so a slow connection holds back the writer.
+    fn internal_fetch_body_write<'gc>(
+        agent: &mut Agent,
+        _this: Value,
+        args: ArgumentsList,
+        mut gc: GcScope<'gc, '_>,
+    ) -> JsResult<'gc, Value<'gc>> {
+        let rid = args
+            .get(0)
+            .to_number(agent, gc.reborrow())
+            .unbind()?
+            .into_f64(agent);
+        let request = Self::get_request(agent, rid);
+        let chunk_binding = args.get(1).to_string(agent, gc.reborrow()).unbind()?;
+        let chunk = decode_hex(chunk_binding.as_str(agent).unwrap_or_default()).unwrap_or_default();
+        let sender = request.and_then(|request| request.request_body.lock().unwrap().clone());
+
+        let promise_capability = PromiseCapability::new(agent, gc.nogc());
+        let root_value = Global::new(agent, promise_capability.promise().into_value().unbind());
+
+        let host_data = agent.get_host_data();
+        let host_data: &HostData<RuntimeMacroTask> = host_data.downcast_ref().unwrap();
+        let macro_task_tx = host_data.macro_task_tx();
+
+        host_data.spawn_macro_task(async move {
+            let sent = match sender {
+                Some(sender) => sender.send(RequestChunk::Data(chunk)).await.is_ok(),
+                None => false,
+            };
+            let task = if sent {
+                RuntimeMacroTask::ResolvePromiseWithString(root_value, String::new())
+            } else {
+                RuntimeMacroTask::RejectPromise(
+                    root_value,
+                    "Connection closed before the request was sent".to_string(),
+                )
+            };
+            macro_task_tx.send(MacroTask::User(task)).unwrap();
+        });
+
+        Ok(Value::Promise(promise_capability.promise()).unbind())
+    }
+
+    /// Finish a streamed request body, or abort the request when `aborted`
+    /// is `"true"`.
+    fn internal_fetch_body_end<'gc>(
+        agent: &mut Agent,
+        _this: Value,
+        args: ArgumentsList,
+        mut gc: GcScope<'gc, '_>,
+    ) -> JsResult<'gc, Value<'gc>> {
+        let rid = args
+            .get(0)
+            .to_number(agent, gc.reborrow())
+            .unbind()?
+            .into_f64(agent);
+        let request = Self::get_request(agent, rid);
+        let aborted_binding = args.get(1).to_string(agent, gc.reborrow()).unbind()?;
+        let aborted = aborted_binding.as_str(agent) == Some("true");
+        let sender = request.and_then(|request| request.request_body.lock().unwrap().take());
+
+        // Dropping the sender without the end marker aborts the request
+        if let (Some(sender), false) = (sender, aborted) {
+            tokio::spawn(async move {
+                let _ = sender.send(RequestChunk::End).await;
+            });
+        }
+
+        Ok(Value::Undefined)
+    }
+
+    /// Resolves with the response head as JSON once it arrived.
+    fn internal_fetch_response<'gc>(
+        agent: &mut Agent,
+        _this: Value,
+        args: ArgumentsList,
+        mut gc: GcScope<'gc, '_>,
+    ) -> JsResult<'gc, Value<'gc>> {
+        let rid = args
+            .get(0)
+            .to_number(agent, gc.reborrow())
+            .unbind()?
+            .into_f64(agent);
+        let request = Self::get_request(agent, rid);
+        let head = request.and_then(|request| request.head.lock().unwrap().take());
+
+        let promise_capability = PromiseCapability::new(agent, gc.nogc());
+        let root_value = Global::new(agent, promise_capability.promise().into_value().unbind());
+
+        let host_data = agent.get_host_data();
+        let host_data: &HostData<RuntimeMacroTask> = host_data.downcast_ref().unwrap();
+        let macro_task_tx = host_data.macro_task_tx();
+
+        host_data.spawn_macro_task(async move {
+            let task = match head {
+                None => RuntimeMacroTask::RejectPromise(
+                    root_value,
+                    "Response is no longer available".to_string(),
+                ),
+                Some(head) => match head.await {
+                    Ok(Ok(head)) => {
+                        let json = serde_json::json!({
+                            "status": head.status,
+                            "statusText": head.status_text,
+                            "headers": head.headers,
+                            "version": head.version,
+                        });
+                        RuntimeMacroTask::ResolvePromiseWithString(root_value, json.to_string())
+                    }
+                    Ok(Err(e)) => RuntimeMacroTask::RejectPromise(root_value, e.to_string()),
+                    Err(_) => RuntimeMacroTask::RejectPromise(
+                        root_value,
+                        "Request was aborted".to_string(),
+                    ),
+                },
+            };
+            macro_task_tx.send(MacroTask::User(task)).unwrap();
+        });
+
+        Ok(Value::Promise(promise_capability.promise()).unbind())
+    }
+
+    /// Resolves with the next chunk of the decoded response body, or an empty
+    /// string once it is complete.
+    fn internal_fetch_read<'gc>(
+        agent: &mut Agent,
+        _this: Value,
+        args: ArgumentsList,
+        mut gc: GcScope<'gc, '_>,
+    ) -> JsResult<'gc, Value<'gc>> {
+        let rid = args
+            .get(0)
+            .to_number(agent, gc.reborrow())
+            .unbind()?
+            .into_f64(agent);
+        let request = Self::get_request(agent, rid);
+
+        let promise_capability = PromiseCapability::new(agent, gc.nogc());
+        let root_value = Global::new(agent, promise_capability.promise().into_value().unbind());
+
+        let host_data = agent.get_host_data();
+        let host_data: &HostData<RuntimeMacroTask> = host_data.downcast_ref().unwrap();
+        let macro_task_tx = host_data.macro_task_tx();
+
+        host_data.spawn_macro_task(async move {
+            let task = match request {
+                None => RuntimeMacroTask::RejectPromise(
+                    root_value,
+                    "Response body is no longer available".to_string(),
+                ),
+                Some(request) => match request.body.lock().await.recv().await {
+                    Some(Ok(chunk)) => RuntimeMacroTask::ResolvePromiseWithBytes(root_value, chunk),
+                    Some(Err(e)) => RuntimeMacroTask::RejectPromise(
+                        root_value,
+                        format!("Error reading response body: {e}"),
+                    ),
+                    None => RuntimeMacroTask::ResolvePromiseWithString(root_value, String::new()),
+                },
+            };
+            macro_task_tx.send(MacroTask::User(task)).unwrap();
+        });
+
+        Ok(Value::Promise(promise_capability.promise()).unbind())
+    }
+
+    /// Release a request. One still in flight is aborted and its connection
+    /// closed.
+    fn internal_fetch_close<'gc>(
+        agent: &mut Agent,
+        _this: Value,
+        args: ArgumentsList,
+        mut gc: GcScope<'gc, '_>,
+    ) -> JsResult<'gc, Value<'gc>> {
+        let rid = args
+            .get(0)
+            .to_number(agent, gc.reborrow())
+            .unbind()?
+            .into_f64(agent);
+        let request = Self::get_fetch_resources(agent)
+            .requests
+            .remove(Rid::from_index(rid as u32));
+        if let Some(request) = request {
+            request.abort.abort();
+        }
+
+        Ok(Value::Undefined)
+    }
+
+    /// Create a client with its own connection pool, allowed to speak HTTP/1.1
+    /// and HTTP/2 as set by `"true"` or `"false"`.
+    fn internal_fetch_client_create<'gc>(
+        agent: &mut Agent,
+        _this: Value,
+        args: ArgumentsList,
+        mut gc: GcScope<'gc, '_>,
+    ) -> JsResult<'gc, Value<'gc>> {
+        let http1_binding = args.get(0).to_string(agent, gc.reborrow()).unbind()?;
+        let http2_binding = args.get(1).to_string(agent, gc.reborrow()).unbind()?;
+        let http1 = http1_binding.as_str(agent) == Some("true");
+        let http2 = http2_binding.as_str(agent) == Some("true");
+
+        let rid = Self::get_fetch_resources(agent)
+            .clients
+            .push(Arc::new(HttpClient::new(http1, http2)));
+
+        Ok(Value::from_f64(agent, rid.index() as f64, gc.nogc()).unbind())
+    }
+
+    /// Close a client. Requests in flight complete, idle connections close.
+    fn internal_fetch_client_close<'gc>(
+        agent: &mut Agent,
+        _this: Value,
+        args: ArgumentsList,
+        mut gc: GcScope<'gc, '_>,
+    ) -> JsResult<'gc, Value<'gc>> {
+        let rid = args
+            .get(0)
+            .to_number(agent, gc.reborrow())
+            .unbind()?
+            .into_f64(agent);
+
+        Self::get_fetch_resources(agent)
+            .clients
+            .remove(Rid::from_index(rid as u32));
+
+        Ok(Value::Undefined)
+    }
 }
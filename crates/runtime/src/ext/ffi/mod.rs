@@ -6,6 +6,7 @@ mod error;
 mod ir;
 use std::collections::HashMap;
 use std::os::raw::c_void;
+use std::path::Path;
 use std::ptr;
 use std::sync::atomic::{AtomicU32, Ordering};
 use std::sync::{Arc, Mutex};
@@ -21,7 +22,7 @@ use crate::ext::ffi::ir::{
     ffi_parse_pointer_arg, ffi_parse_u8_arg, ffi_parse_u16_arg, ffi_parse_u32_arg,
     ffi_parse_u64_arg, ffi_parse_usize_arg, parse_foreign_function,
 };
-use andromeda_core::{Extension, ExtensionOp, HostData, OpsStorage};
+use andromeda_core::{Extension, ExtensionOp, HostData, OpsStorage, check_permission};
 use libffi::middle;
 use nova_vm::{
     ecmascript::{
@@ -268,6 +269,14 @@ impl FfiExt {
             }
         };
 
+        if let Err(error) = check_permission::<crate::RuntimeMacroTask>(agent, |p| {
+            p.check_ffi(Path::new(&filename))
+        }) {
+            return Err(error
+                .into_js_error::<crate::RuntimeMacroTask>(agent, gc.nogc())
+                .unbind());
+        }
+
         let symbols_obj = args.get(1);
 
         let library = match unsafe { Library::new(&filename) } {
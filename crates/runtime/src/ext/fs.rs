@@ -26,8 +26,8 @@ use nova_vm::{
 };
 
 use andromeda_core::{
-    AndromedaError, ErrorReporter, Extension, ExtensionOp, HostData, MacroTask, OpsStorage,
-    ResourceTable,
+    AndromedaError, EmbeddedFs, ErrorReporter, Extension, ExtensionOp, HostData, MacroTask,
+    OpsStorage, ResourceTable, check_permission,
 };
 
 use crate::RuntimeMacroTask;
@@ -194,8 +194,16 @@ impl FsExt {
                 );
             }
         };
+        if let Err(error) =
+            check_permission::<RuntimeMacroTask>(agent, |p| p.check_read(&resolved_path))
+        {
+            return Err(error
+                .into_js_error::<RuntimeMacroTask>(agent, gc.nogc())
+                .unbind());
+        }
 
-        match std::fs::read_to_string(&resolved_path) {
+        // Files embedded in a compiled executable are read first
+        match EmbeddedFs::read_to_string(path) {
             Ok(content) => Ok(Value::from_string(agent, content, gc.nogc()).unbind()),
             Err(e) => {
                 let error = AndromedaError::fs_error(e, "read_text_file", path);
@@ -231,6 +239,13 @@ impl FsExt {
                 );
             }
         };
+        if let Err(error) =
+            check_permission::<RuntimeMacroTask>(agent, |p| p.check_write(&resolved_path))
+        {
+            return Err(error
+                .into_js_error::<RuntimeMacroTask>(agent, gc.nogc())
+                .unbind());
+        }
 
         match std::fs::write(
             &resolved_path,
@@ -268,6 +283,13 @@ impl FsExt {
                 );
             }
         };
+        if let Err(error) =
+            check_permission::<RuntimeMacroTask>(agent, |p| p.check_write(&resolved_path))
+        {
+            return Err(error
+                .into_js_error::<RuntimeMacroTask>(agent, gc.nogc())
+                .unbind());
+        }
 
         let file = match File::create(&resolved_path) {
             Ok(file) => file,
@@ -314,6 +336,13 @@ impl FsExt {
                 );
             }
         };
+        if let Err(error) =
+            check_permission::<RuntimeMacroTask>(agent, |p| p.check_read(&resolved_from))
+        {
+            return Err(error
+                .into_js_error::<RuntimeMacroTask>(agent, gc.nogc())
+                .unbind());
+        }
 
         let resolved_to = match resolve_path(to_path) {
             Ok(p) => p,
@@ -325,6 +354,13 @@ impl FsExt {
                 );
             }
         };
+        if let Err(error) =
+            check_permission::<RuntimeMacroTask>(agent, |p| p.check_write(&resolved_to))
+        {
+            return Err(error
+                .into_js_error::<RuntimeMacroTask>(agent, gc.nogc())
+                .unbind());
+        }
 
         match std::fs::copy(&resolved_from, &resolved_to) {
             Ok(_) => Ok(Value::from_string(agent, "Success".to_string(), gc.nogc()).unbind()),
@@ -363,6 +399,13 @@ impl FsExt {
                 );
             }
         };
+        if let Err(error) =
+            check_permission::<RuntimeMacroTask>(agent, |p| p.check_write(&resolved_path))
+        {
+            return Err(error
+                .into_js_error::<RuntimeMacroTask>(agent, gc.nogc())
+                .unbind());
+        }
 
         match std::fs::create_dir(&resolved_path) {
             Ok(_) => Ok(Value::from_string(agent, "Success".to_string(), gc.nogc()).unbind()),
@@ -393,6 +436,13 @@ impl FsExt {
                 );
             }
         };
+        if let Err(error) =
+            check_permission::<RuntimeMacroTask>(agent, |p| p.check_write(&resolved_path))
+        {
+            return Err(error
+                .into_js_error::<RuntimeMacroTask>(agent, gc.nogc())
+                .unbind());
+        }
 
         match std::fs::create_dir_all(&resolved_path) {
             Ok(_) => Ok(Value::from_string(agent, "Success".to_string(), gc.nogc()).unbind()),
@@ -424,8 +474,16 @@ impl FsExt {
                 );
             }
         };
+        if let Err(error) =
+            check_permission::<RuntimeMacroTask>(agent, |p| p.check_read(&resolved_path))
+        {
+            return Err(error
+                .into_js_error::<RuntimeMacroTask>(agent, gc.nogc())
+                .unbind());
+        }
 
-        match std::fs::read(&resolved_path) {
+        // Files embedded in a compiled executable are read first
+        match EmbeddedFs::read(path) {
             Ok(content) => {
                 // For now, return the content as a hex encoded string
                 // In a full implementation, you'd want to return an actual Uint8Array
@@ -477,6 +535,13 @@ impl FsExt {
                 );
             }
         };
+        if let Err(error) =
+            check_permission::<RuntimeMacroTask>(agent, |p| p.check_write(&resolved_path))
+        {
+            return Err(error
+                .into_js_error::<RuntimeMacroTask>(agent, gc.nogc())
+                .unbind());
+        }
 
         // For now, just write the string as bytes
         // TODO: handle Uint8Array directly
@@ -512,6 +577,13 @@ impl FsExt {
                 );
             }
         };
+        if let Err(error) =
+            check_permission::<RuntimeMacroTask>(agent, |p| p.check_read(&resolved_path))
+        {
+            return Err(error
+                .into_js_error::<RuntimeMacroTask>(agent, gc.nogc())
+                .unbind());
+        }
 
         match std::fs::metadata(&resolved_path) {
             Ok(metadata) => {
@@ -546,6 +618,13 @@ impl FsExt {
                 );
             }
         };
+        if let Err(error) =
+            check_permission::<RuntimeMacroTask>(agent, |p| p.check_read(&resolved_path))
+        {
+            return Err(error
+                .into_js_error::<RuntimeMacroTask>(agent, gc.nogc())
+                .unbind());
+        }
 
         match std::fs::symlink_metadata(&resolved_path) {
             Ok(metadata) => {
@@ -580,6 +659,13 @@ impl FsExt {
                 );
             }
         };
+        if let Err(error) =
+            check_permission::<RuntimeMacroTask>(agent, |p| p.check_read(&resolved_path))
+        {
+            return Err(error
+                .into_js_error::<RuntimeMacroTask>(agent, gc.nogc())
+                .unbind());
+        }
 
         match std::fs::read_dir(&resolved_path) {
             Ok(entries) => {
@@ -640,6 +726,13 @@ impl FsExt {
                 );
             }
         };
+        if let Err(error) =
+            check_permission::<RuntimeMacroTask>(agent, |p| p.check_write(&resolved_path))
+        {
+            return Err(error
+                .into_js_error::<RuntimeMacroTask>(agent, gc.nogc())
+                .unbind());
+        }
 
         let result = if resolved_path.is_dir() {
             std::fs::remove_dir(&resolved_path)
@@ -677,6 +770,13 @@ impl FsExt {
                 );
             }
         };
+        if let Err(error) =
+            check_permission::<RuntimeMacroTask>(agent, |p| p.check_write(&resolved_path))
+        {
+            return Err(error
+                .into_js_error::<RuntimeMacroTask>(agent, gc.nogc())
+                .unbind());
+        }
 
         let result = if resolved_path.is_dir() {
             std::fs::remove_dir_all(&resolved_path)
@@ -717,6 +817,13 @@ impl FsExt {
                 );
             }
         };
+        if let Err(error) =
+            check_permission::<RuntimeMacroTask>(agent, |p| p.check_write(&resolved_from))
+        {
+            return Err(error
+                .into_js_error::<RuntimeMacroTask>(agent, gc.nogc())
+                .unbind());
+        }
 
         let resolved_to = match resolve_path(to_path) {
             Ok(p) => p,
@@ -728,6 +835,13 @@ impl FsExt {
                 );
             }
         };
+        if let Err(error) =
+            check_permission::<RuntimeMacroTask>(agent, |p| p.check_write(&resolved_to))
+        {
+            return Err(error
+                .into_js_error::<RuntimeMacroTask>(agent, gc.nogc())
+                .unbind());
+        }
 
         match std::fs::rename(&resolved_from, &resolved_to) {
             Ok(_) => Ok(Value::from_string(agent, "Success".to_string(), gc.nogc()).unbind()),
@@ -764,8 +878,15 @@ impl FsExt {
                 return Ok(Value::from_string(agent, "false".to_string(), gc.nogc()).unbind());
             }
         };
+        if let Err(error) =
+            check_permission::<RuntimeMacroTask>(agent, |p| p.check_read(&resolved_path))
+        {
+            return Err(error
+                .into_js_error::<RuntimeMacroTask>(agent, gc.nogc())
+                .unbind());
+        }
 
-        let exists = resolved_path.exists();
+        let exists = EmbeddedFs::exists(path);
         Ok(Value::from_string(agent, exists.to_string(), gc.nogc()).unbind())
     }
     /// Truncate a file to a specific length.
@@ -813,6 +934,13 @@ impl FsExt {
                 );
             }
         };
+        if let Err(error) =
+            check_permission::<RuntimeMacroTask>(agent, |p| p.check_write(&resolved_path))
+        {
+            return Err(error
+                .into_js_error::<RuntimeMacroTask>(agent, gc.nogc())
+                .unbind());
+        }
 
         let file = std::fs::OpenOptions::new().write(true).open(&resolved_path);
         match file {
@@ -884,6 +1012,13 @@ impl FsExt {
                     );
                 }
             };
+            if let Err(error) =
+                check_permission::<RuntimeMacroTask>(agent, |p| p.check_write(&resolved_path))
+            {
+                return Err(error
+                    .into_js_error::<RuntimeMacroTask>(agent, gc.nogc())
+                    .unbind());
+            }
 
             let permissions = std::fs::Permissions::from_mode(mode);
             match std::fs::set_permissions(&resolved_path, permissions) {
@@ -935,6 +1070,13 @@ impl FsExt {
                 );
             }
         };
+        if let Err(error) =
+            check_permission::<RuntimeMacroTask>(agent, |p| p.check_read(&resolved_target))
+        {
+            return Err(error
+                .into_js_error::<RuntimeMacroTask>(agent, gc.nogc())
+                .unbind());
+        }
 
         let resolved_link = match resolve_path(link_path) {
             Ok(p) => p,
@@ -946,6 +1088,13 @@ impl FsExt {
                 );
             }
         };
+        if let Err(error) =
+            check_permission::<RuntimeMacroTask>(agent, |p| p.check_write(&resolved_link))
+        {
+            return Err(error
+                .into_js_error::<RuntimeMacroTask>(agent, gc.nogc())
+                .unbind());
+        }
 
         #[cfg(unix)]
         {
@@ -1021,6 +1170,13 @@ impl FsExt {
                 );
             }
         };
+        if let Err(error) =
+            check_permission::<RuntimeMacroTask>(agent, |p| p.check_read(&resolved_path))
+        {
+            return Err(error
+                .into_js_error::<RuntimeMacroTask>(agent, gc.nogc())
+                .unbind());
+        }
 
         match std::fs::read_link(&resolved_path) {
             Ok(target) => {
@@ -1055,6 +1211,13 @@ impl FsExt {
                 );
             }
         };
+        if let Err(error) =
+            check_permission::<RuntimeMacroTask>(agent, |p| p.check_read(&resolved_path))
+        {
+            return Err(error
+                .into_js_error::<RuntimeMacroTask>(agent, gc.nogc())
+                .unbind());
+        }
 
         match std::fs::canonicalize(&resolved_path) {
             Ok(real_path) => {
@@ -1089,6 +1252,13 @@ impl FsExt {
                 );
             }
         };
+        if let Err(error) =
+            check_permission::<RuntimeMacroTask>(agent, |p| p.check_read(&resolved_path))
+        {
+            return Err(error
+                .into_js_error::<RuntimeMacroTask>(agent, gc.nogc())
+                .unbind());
+        }
 
         let file = match File::open(&resolved_path) {
             Ok(file) => file,
@@ -1181,6 +1351,13 @@ impl FsExt {
                 return Ok(Value::Promise(promise_capability.promise()).unbind());
             }
         };
+        if let Err(error) =
+            check_permission::<RuntimeMacroTask>(agent, |p| p.check_read(&resolved_path))
+        {
+            return Err(error
+                .into_js_error::<RuntimeMacroTask>(agent, gc.nogc())
+                .unbind());
+        }
 
         let path_string = resolved_path.to_string_lossy().to_string();
 
@@ -1190,8 +1367,17 @@ impl FsExt {
         let host_data: &HostData<RuntimeMacroTask> = host_data.downcast_ref().unwrap();
         let macro_task_tx = host_data.macro_task_tx();
 
+        // Files embedded in a compiled executable are read first
+        let embedded = EmbeddedFs::get(path).map(|content| {
+            String::from_utf8(content.to_vec())
+                .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))
+        });
+
         host_data.spawn_macro_task(async move {
-            let result = tokio::fs::read_to_string(&path_string).await;
+            let result = match embedded {
+                Some(result) => result,
+                None => tokio::fs::read_to_string(&path_string).await,
+            };
             match result {
                 Ok(content) => {
                     macro_task_tx
@@ -1260,6 +1446,13 @@ impl FsExt {
                 return Ok(Value::Promise(promise_capability.promise()).unbind());
             }
         };
+        if let Err(error) =
+            check_permission::<RuntimeMacroTask>(agent, |p| p.check_write(&resolved_path))
+        {
+            return Err(error
+                .into_js_error::<RuntimeMacroTask>(agent, gc.nogc())
+                .unbind());
+        }
 
         let path_string = resolved_path.to_string_lossy().to_string();
 
@@ -1308,6 +1501,13 @@ impl FsExt {
             .as_str(agent)
             .expect("String is not valid UTF-8")
             .to_string();
+        if let Err(error) =
+            check_permission::<RuntimeMacroTask>(agent, |p| p.check_read(Path::new(&path_string)))
+        {
+            return Err(error
+                .into_js_error::<RuntimeMacroTask>(agent, gc.nogc())
+                .unbind());
+        }
 
         let promise_capability = PromiseCapability::new(agent, gc.nogc());
         let root_value = Global::new(agent, promise_capability.promise().into_value().unbind());
@@ -1315,8 +1515,14 @@ impl FsExt {
         let host_data: &HostData<RuntimeMacroTask> = host_data.downcast_ref().unwrap();
         let macro_task_tx = host_data.macro_task_tx();
 
+        // Files embedded in a compiled executable are read first
+        let embedded = EmbeddedFs::get(&path_string).map(<[u8]>::to_vec);
+
         host_data.spawn_macro_task(async move {
-            let result = tokio::fs::read(&path_string).await;
+            let result = match embedded {
+                Some(content) => Ok(content),
+                None => tokio::fs::read(&path_string).await,
+            };
             match result {
                 Ok(content) => {
                     macro_task_tx
@@ -1361,6 +1567,13 @@ impl FsExt {
             .as_str(agent)
             .expect("String is not valid UTF-8")
             .to_string();
+        if let Err(error) =
+            check_permission::<RuntimeMacroTask>(agent, |p| p.check_write(Path::new(&path_string)))
+        {
+            return Err(error
+                .into_js_error::<RuntimeMacroTask>(agent, gc.nogc())
+                .unbind());
+        }
         let content_string = content_binding
             .as_str(agent)
             .expect("String is not valid UTF-8")
@@ -1419,10 +1632,24 @@ impl FsExt {
             .as_str(agent)
             .expect("String is not valid UTF-8")
             .to_string();
+        if let Err(error) =
+            check_permission::<RuntimeMacroTask>(agent, |p| p.check_read(Path::new(&from_string)))
+        {
+            return Err(error
+                .into_js_error::<RuntimeMacroTask>(agent, gc.nogc())
+                .unbind());
+        }
         let to_string = to_binding
             .as_str(agent)
             .expect("String is not valid UTF-8")
             .to_string();
+        if let Err(error) =
+            check_permission::<RuntimeMacroTask>(agent, |p| p.check_write(Path::new(&to_string)))
+        {
+            return Err(error
+                .into_js_error::<RuntimeMacroTask>(agent, gc.nogc())
+                .unbind());
+        }
 
         let promise_capability = PromiseCapability::new(agent, gc.nogc());
         let root_value = Global::new(agent, promise_capability.promise().into_value().unbind());
@@ -1473,6 +1700,13 @@ impl FsExt {
             .as_str(agent)
             .expect("String is not valid UTF-8")
             .to_string();
+        if let Err(error) =
+            check_permission::<RuntimeMacroTask>(agent, |p| p.check_write(Path::new(&path_string)))
+        {
+            return Err(error
+                .into_js_error::<RuntimeMacroTask>(agent, gc.nogc())
+                .unbind());
+        }
 
         let promise_capability = PromiseCapability::new(agent, gc.nogc());
         let root_value = Global::new(agent, promise_capability.promise().into_value().unbind());
@@ -1525,6 +1759,13 @@ impl FsExt {
             .as_str(agent)
             .expect("String is not valid UTF-8")
             .to_string();
+        if let Err(error) =
+            check_permission::<RuntimeMacroTask>(agent, |p| p.check_write(Path::new(&path_string)))
+        {
+            return Err(error
+                .into_js_error::<RuntimeMacroTask>(agent, gc.nogc())
+                .unbind());
+        }
 
         let promise_capability = PromiseCapability::new(agent, gc.nogc());
         let root_value = Global::new(agent, promise_capability.promise().into_value().unbind());
@@ -1571,6 +1812,13 @@ impl FsExt {
             .as_str(agent)
             .expect("String is not valid UTF-8")
             .to_string();
+        if let Err(error) =
+            check_permission::<RuntimeMacroTask>(agent, |p| p.check_write(Path::new(&path_string)))
+        {
+            return Err(error
+                .into_js_error::<RuntimeMacroTask>(agent, gc.nogc())
+                .unbind());
+        }
 
         let promise_capability = PromiseCapability::new(agent, gc.nogc());
         let root_value = Global::new(agent, promise_capability.promise().into_value().unbind());
@@ -1614,6 +1862,13 @@ impl FsExt {
             .as_str(agent)
             .expect("String is not valid UTF-8")
             .to_string();
+        if let Err(error) =
+            check_permission::<RuntimeMacroTask>(agent, |p| p.check_write(Path::new(&path_string)))
+        {
+            return Err(error
+                .into_js_error::<RuntimeMacroTask>(agent, gc.nogc())
+                .unbind());
+        }
 
         let promise_capability = PromiseCapability::new(agent, gc.nogc());
         let root_value = Global::new(agent, promise_capability.promise().into_value().unbind());
@@ -1657,6 +1912,13 @@ impl FsExt {
             .as_str(agent)
             .expect("String is not valid UTF-8")
             .to_string();
+        if let Err(error) =
+            check_permission::<RuntimeMacroTask>(agent, |p| p.check_read(Path::new(&path_string)))
+        {
+            return Err(error
+                .into_js_error::<RuntimeMacroTask>(agent, gc.nogc())
+                .unbind());
+        }
 
         let promise_capability = PromiseCapability::new(agent, gc.nogc());
         let root_value = Global::new(agent, promise_capability.promise().into_value().unbind());
@@ -1691,10 +1953,24 @@ impl FsExt {
             .as_str(agent)
             .expect("String is not valid UTF-8")
             .to_string();
+        if let Err(error) =
+            check_permission::<RuntimeMacroTask>(agent, |p| p.check_write(Path::new(&from_string)))
+        {
+            return Err(error
+                .into_js_error::<RuntimeMacroTask>(agent, gc.nogc())
+                .unbind());
+        }
         let to_string = to_binding
             .as_str(agent)
             .expect("String is not valid UTF-8")
             .to_string();
+        if let Err(error) =
+            check_permission::<RuntimeMacroTask>(agent, |p| p.check_write(Path::new(&to_string)))
+        {
+            return Err(error
+                .into_js_error::<RuntimeMacroTask>(agent, gc.nogc())
+                .unbind());
+        }
 
         let promise_capability = PromiseCapability::new(agent, gc.nogc());
         let root_value = Global::new(agent, promise_capability.promise().into_value().unbind());
@@ -1738,6 +2014,13 @@ impl FsExt {
             .as_str(agent)
             .expect("String is not valid UTF-8")
             .to_string();
+        if let Err(error) =
+            check_permission::<RuntimeMacroTask>(agent, |p| p.check_write(Path::new(&path_string)))
+        {
+            return Err(error
+                .into_js_error::<RuntimeMacroTask>(agent, gc.nogc())
+                .unbind());
+        }
 
         let promise_capability = PromiseCapability::new(agent, gc.nogc());
         let root_value = Global::new(agent, promise_capability.promise().into_value().unbind());
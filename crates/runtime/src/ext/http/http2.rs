@@ -0,0 +1,322 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! HTTP/2 connection handling for `Andromeda.serve`.
+//!
+//! Connections that negotiated `h2` with ALPN, or that open with the HTTP/2
+//! preface in plain text (h2c with prior knowledge), are served here. Every
+//! stream becomes an [`IncomingRequest`], so handlers see the same requests as
+//! over HTTP/1.1 while they are multiplexed over one connection. Flow control
+//! capacity for a request body is only released once the handler took a chunk,
+//! and response chunks wait for the client's window, so both sides apply
+//! backpressure as they do over HTTP/1.1.
+
+use std::{
+    io,
+    net::SocketAddr,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use bytes::Bytes;
+use h2::{
+    Reason, RecvStream, SendStream,
+    server::{self, SendResponse},
+};
+use tokio::{
+    io::{AsyncRead, AsyncWrite, ReadBuf},
+    sync::{mpsc, oneshot},
+};
+
+use super::server::{
+    BODY_CHANNEL_CAPACITY, BodyChunk, HEAD_TIMEOUT, IncomingRequest, OutgoingResponse,
+    ResponseBody, http_date,
+};
+
+/// The bytes every HTTP/2 client sends first.
+pub(super) const PREFACE: &[u8] = b"PRI * HTTP/2.0\r\n\r\nSM\r\n\r\n";
+
+/// Response headers describing a single HTTP/1.1 connection, which HTTP/2
+/// forbids.
+const CONNECTION_HEADERS: [&str; 5] = [
+    "connection",
+    "keep-alive",
+    "proxy-connection",
+    "transfer-encoding",
+    "upgrade",
+];
+
+/// Serve the streams of an HTTP/2 connection until the client is done or the
+/// handler side is gone.
+pub(super) async fn serve_connection<S>(
+    stream: S,
+    remote_addr: SocketAddr,
+    requests: mpsc::Sender<IncomingRequest>,
+) where
+    S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    let Ok(Ok(mut connection)) =
+        tokio::time::timeout(HEAD_TIMEOUT, server::handshake(stream)).await
+    else {
+        return;
+    };
+
+    // The connection must keep being polled to make progress on the streams,
+    // including after a graceful shutdown while they finish
+    let mut shutting_down = false;
+    loop {
+        let accepted = tokio::select! {
+            accepted = connection.accept() => accepted,
+            _ = requests.closed(), if !shutting_down => {
+                connection.graceful_shutdown();
+                shutting_down = true;
+                continue;
+            }
+        };
+        match accepted {
+            Some(Ok((request, respond))) => {
+                tokio::spawn(serve_stream(
+                    request,
+                    respond,
+                    remote_addr,
+                    requests.clone(),
+                ));
+            }
+            Some(Err(_)) | None => break,
+        }
+    }
+}
+
+async fn serve_stream(
+    request: http::Request<RecvStream>,
+    mut respond: SendResponse<Bytes>,
+    remote_addr: SocketAddr,
+    requests: mpsc::Sender<IncomingRequest>,
+) {
+    let (parts, mut recv) = request.into_parts();
+    let method = parts.method.as_str().to_string();
+    let target = parts
+        .uri
+        .path_and_query()
+        .map(|target| target.as_str())
+        .unwrap_or("/")
+        .to_string();
+
+    let mut headers = Vec::with_capacity(parts.headers.len() + 1);
+    // The :authority pseudo-header takes the place of Host
+    if let Some(authority) = parts.uri.authority()
+        && !parts.headers.contains_key(http::header::HOST)
+    {
+        headers.push(("host".to_string(), authority.to_string()));
+    }
+    for (name, value) in &parts.headers {
+        let Ok(value) = value.to_str() else {
+            respond.send_reset(Reason::PROTOCOL_ERROR);
+            return;
+        };
+        headers.push((name.as_str().to_string(), value.to_string()));
+    }
+
+    let body = if recv.is_end_stream() {
+        None
+    } else {
+        let (sender, receiver) = mpsc::channel(BODY_CHANNEL_CAPACITY);
+        tokio::spawn(async move {
+            while let Some(chunk) = recv.data().await {
+                match chunk {
+                    Ok(chunk) => {
+                        let length = chunk.len();
+                        if sender.send(Ok(chunk.to_vec())).await.is_err() {
+                            break;
+                        }
+                        let _ = recv.flow_control().release_capacity(length);
+                    }
+                    Err(error) => {
+                        let _ = sender.send(Err(io::Error::other(error))).await;
+                        break;
+                    }
+                }
+            }
+        });
+        Some(receiver)
+    };
+
+    let (response_sender, response) = oneshot::channel();
+    let request = IncomingRequest {
+        method: method.clone(),
+        target,
+        headers,
+        remote_addr,
+        body,
+        response: response_sender,
+    };
+    if requests.send(request).await.is_err() {
+        respond.send_reset(Reason::REFUSED_STREAM);
+        return;
+    }
+
+    let Ok(response) = response.await else {
+        // The handler went away without answering
+        let head = http::Response::builder()
+            .status(500)
+            .body(())
+            .expect("a status and no headers form a valid response");
+        let _ = respond.send_response(head, true);
+        return;
+    };
+    if respond_with(&mut respond, response, &method).await.is_err() {
+        respond.send_reset(Reason::INTERNAL_ERROR);
+    }
+}
+
+async fn respond_with(
+    respond: &mut SendResponse<Bytes>,
+    response: OutgoingResponse,
+    method: &str,
+) -> Result<(), h2::Error> {
+    // HTTP/2 has no informational responses to upgrade a connection with,
+    // dropping the upgrade lets a pending WebSocket fail
+    if response.status < 200 {
+        respond.send_reset(Reason::HTTP_1_1_REQUIRED);
+        return Ok(());
+    }
+
+    let write_body =
+        !method.eq_ignore_ascii_case("HEAD") && response.status != 204 && response.status != 304;
+    let declared_length = response
+        .headers
+        .iter()
+        .find(|(name, _)| name.eq_ignore_ascii_case("content-length"))
+        .and_then(|(_, value)| value.trim().parse::<u64>().ok());
+
+    let mut builder = http::Response::builder().status(response.status);
+    let mut has_date = false;
+    for (name, value) in &response.headers {
+        let name = name.to_ascii_lowercase();
+        if CONNECTION_HEADERS.contains(&name.as_str()) || name == "content-length" {
+            continue;
+        }
+        has_date |= name == "date";
+        builder = builder.header(name, value.as_str());
+    }
+    if !has_date {
+        builder = builder.header("date", http_date());
+    }
+    let length = match &response.body {
+        ResponseBody::Full(bytes) => Some(bytes.len() as u64),
+        ResponseBody::Stream(_) => declared_length,
+    };
+    if let Some(length) = length
+        && response.status != 204
+        && response.status != 304
+    {
+        builder = builder.header("content-length", length.to_string());
+    }
+    let head = builder
+        .body(())
+        .map_err(|_| h2::Error::from(Reason::INTERNAL_ERROR))?;
+
+    match response.body {
+        ResponseBody::Full(bytes) => {
+            let end_of_stream = !write_body || bytes.is_empty();
+            let mut stream = respond.send_response(head, end_of_stream)?;
+            if !end_of_stream {
+                send_all(&mut stream, Bytes::from(bytes), true).await?;
+            }
+        }
+        ResponseBody::Stream(mut chunks) => {
+            let mut stream = respond.send_response(head, !write_body)?;
+            let mut written = 0u64;
+            loop {
+                match chunks.recv().await {
+                    Some(BodyChunk::Data(data)) if data.is_empty() || !write_body => {}
+                    Some(BodyChunk::Data(data)) => {
+                        written += data.len() as u64;
+                        if declared_length.is_some_and(|length| written > length) {
+                            return Err(Reason::INTERNAL_ERROR.into());
+                        }
+                        send_all(&mut stream, Bytes::from(data), false).await?;
+                    }
+                    Some(BodyChunk::End) => break,
+                    None => return Err(Reason::INTERNAL_ERROR.into()),
+                }
+            }
+            if write_body {
+                if declared_length.is_some_and(|length| written < length) {
+                    return Err(Reason::INTERNAL_ERROR.into());
+                }
+                stream.send_data(Bytes::new(), true)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Send `data` as the client's flow control window allows.
+async fn send_all(
+    stream: &mut SendStream<Bytes>,
+    mut data: Bytes,
+    end_of_stream: bool,
+) -> Result<(), h2::Error> {
+    while !data.is_empty() {
+        stream.reserve_capacity(data.len());
+        let capacity = match std::future::poll_fn(|cx| stream.poll_capacity(cx)).await {
+            Some(capacity) => capacity?,
+            None => return Err(Reason::CANCEL.into()),
+        };
+        if capacity == 0 {
+            continue;
+        }
+        let chunk = data.split_to(capacity.min(data.len()));
+        stream.send_data(chunk, end_of_stream && data.is_empty())?;
+    }
+    Ok(())
+}
+
+/// A stream that first replays the bytes read while looking for the preface.
+pub(super) struct Rewind<S> {
+    buffered: Vec<u8>,
+    stream: S,
+}
+
+impl<S> Rewind<S> {
+    pub(super) fn new(buffered: Vec<u8>, stream: S) -> Self {
+        Rewind { buffered, stream }
+    }
+}
+
+impl<S: AsyncRead + Unpin> AsyncRead for Rewind<S> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        if this.buffered.is_empty() {
+            return Pin::new(&mut this.stream).poll_read(cx, buf);
+        }
+        let length = this.buffered.len().min(buf.remaining());
+        buf.put_slice(&this.buffered[..length]);
+        this.buffered.drain(..length);
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl<S: AsyncWrite + Unpin> AsyncWrite for Rewind<S> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.get_mut().stream).poll_write(cx, buf)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().stream).poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().stream).poll_shutdown(cx)
+    }
+}
@@ -2,19 +2,586 @@
 // License, v. 2.0. If a copy of the MPL was not distributed with this
 // file, You can obtain one at https://mozilla.org/MPL/2.0/.
 
-use andromeda_core::Extension;
+mod http2;
+mod server;
+
+use std::{io, sync::Arc};
+
+use andromeda_core::{
+    Extension, ExtensionOp, HostData, MacroTask, ResourceTable, Rid, SyncResourceTable,
+    check_permission,
+};
+use nova_vm::{
+    ecmascript::{
+        builtins::{
+            ArgumentsList,
+            promise_objects::promise_abstract_operations::promise_capability_records::PromiseCapability,
+        },
+        execution::{Agent, JsResult, agent::ExceptionType},
+        types::{IntoValue, Value},
+    },
+    engine::{
+        Global,
+        context::{Bindable, GcScope},
+    },
+};
+use tokio::sync::{Mutex, mpsc, oneshot, watch};
+use tokio_rustls::TlsAcceptor;
+
+use crate::RuntimeMacroTask;
+
+use super::command::decode_hex;
+use super::tls::{TlsServerOptions, server_config};
+use super::websocket::{Upgraded, WebSocketResources, accept_key, is_valid_key};
+
+use server::{BodyChunk, IncomingRequest, OutgoingResponse, ResponseBody};
+
+/// Parsed requests waiting for the handler before connections stop reading.
+const REQUEST_QUEUE_CAPACITY: usize = 128;
+/// Response chunks buffered before `http_response_write` stops resolving.
+const RESPONSE_CHANNEL_CAPACITY: usize = 8;
+
+#[derive(Clone)]
+struct HttpServerResource {
+    requests: Arc<Mutex<mpsc::Receiver<IncomingRequest>>>,
+    shutdown: Arc<watch::Sender<bool>>,
+}
+
+#[derive(Clone)]
+struct HttpRequestResource {
+    body: Option<Arc<Mutex<mpsc::Receiver<io::Result<Vec<u8>>>>>>,
+    response: Arc<std::sync::Mutex<Option<oneshot::Sender<OutgoingResponse>>>>,
+    response_body: Arc<std::sync::Mutex<Option<mpsc::Sender<BodyChunk>>>>,
+    /// Set by `http_upgrade_websocket`, handed to the server with a 101 response
+    upgrade: Arc<std::sync::Mutex<Option<oneshot::Sender<Upgraded>>>>,
+}
+
+/// Extension storage for HTTP servers and their in-flight requests
+struct HttpExtResources {
+    servers: ResourceTable<HttpServerResource>,
+    /// Shared with the tasks that receive requests off the connections
+    requests: SyncResourceTable<HttpRequestResource>,
+}
 
 #[derive(Default)]
 pub struct ServeExt;
 
+#[hotpath::measure_all]
 impl ServeExt {
-    #[hotpath::measure]
     pub fn new_extension() -> Extension {
         Extension {
             name: "http",
-            ops: vec![],
-            storage: None,
+            ops: vec![
+                ExtensionOp::new("http_serve", Self::internal_http_serve, 4, false),
+                ExtensionOp::new(
+                    "http_next_request",
+                    Self::internal_http_next_request,
+                    1,
+                    false,
+                ),
+                ExtensionOp::new(
+                    "http_request_read",
+                    Self::internal_http_request_read,
+                    1,
+                    false,
+                ),
+                ExtensionOp::new("http_respond", Self::internal_http_respond, 5, false),
+                ExtensionOp::new(
+                    "http_response_write",
+                    Self::internal_http_response_write,
+                    2,
+                    false,
+                ),
+                ExtensionOp::new(
+                    "http_response_end",
+                    Self::internal_http_response_end,
+                    2,
+                    false,
+                ),
+                ExtensionOp::new(
+                    "http_upgrade_websocket",
+                    Self::internal_http_upgrade_websocket,
+                    3,
+                    false,
+                ),
+                ExtensionOp::new(
+                    "http_server_close",
+                    Self::internal_http_server_close,
+                    1,
+                    false,
+                ),
+            ],
+            storage: Some(Box::new(|storage| {
+                storage.insert(HttpExtResources {
+                    servers: ResourceTable::new(),
+                    requests: SyncResourceTable::new(),
+                });
+            })),
             files: vec![include_str!("./mod.ts")],
         }
     }
+
+    fn get_http_resources(agent: &Agent) -> std::cell::Ref<'_, HttpExtResources> {
+        let host_data = agent.get_host_data();
+        let host_data: &HostData<RuntimeMacroTask> = host_data.downcast_ref().unwrap();
+        std::cell::Ref::map(host_data.storage.borrow(), |storage| {
+            storage.get::<HttpExtResources>().unwrap()
+        })
+    }
+
+    fn parse_rid(value: &str) -> Option<Rid> {
+        value.parse().ok().map(Rid::from_index)
+    }
+
+    /// Start listening and accepting connections in the background, over TLS
+    /// when the fourth argument holds JSON encoded `TlsServerOptions`.
+    /// Returns `{"success":true,"resourceId":n,"hostname":"..","port":n}`.
+    pub fn internal_http_serve<'gc>(
+        agent: &mut Agent,
+        _this: Value,
+        args: ArgumentsList,
+        mut gc: GcScope<'gc, '_>,
+    ) -> JsResult<'gc, Value<'gc>> {
+        let hostname_binding = args.get(0).to_string(agent, gc.reborrow()).unbind()?;
+        let port_binding = args.get(1).to_string(agent, gc.reborrow()).unbind()?;
+        let reuse_port_binding = args.get(2).to_string(agent, gc.reborrow()).unbind()?;
+        let tls_binding = args.get(3).to_string(agent, gc.reborrow()).unbind()?;
+
+        let hostname = hostname_binding
+            .as_str(agent)
+            .expect("String is not valid UTF-8")
+            .to_string();
+        let reuse_port = reuse_port_binding.as_str(agent) == Some("true");
+        let port: u16 = match port_binding
+            .as_str(agent)
+            .expect("String is not valid UTF-8")
+            .parse()
+        {
+            Ok(port) => port,
+            Err(_) => {
+                return Err(agent
+                    .throw_exception(
+                        ExceptionType::TypeError,
+                        "Invalid port number".to_string(),
+                        gc.nogc(),
+                    )
+                    .unbind());
+            }
+        };
+
+        if let Err(error) =
+            check_permission::<RuntimeMacroTask>(agent, |p| p.check_net(&hostname, Some(port)))
+        {
+            return Err(error
+                .into_js_error::<RuntimeMacroTask>(agent, gc.nogc())
+                .unbind());
+        }
+
+        let tls = match tls_binding.as_str(agent).unwrap_or_default() {
+            "" => Ok(None),
+            options => serde_json::from_str::<TlsServerOptions>(options)
+                .map_err(|e| format!("Invalid TLS options: {e}"))
+                .and_then(|options| server_config(&options))
+                .map(|config| Some(TlsAcceptor::from(config))),
+        };
+        let tls = match tls {
+            Ok(tls) => tls,
+            Err(error) => {
+                return Err(agent
+                    .throw_exception(ExceptionType::TypeError, error, gc.nogc())
+                    .unbind());
+            }
+        };
+
+        let bound = tokio::task::block_in_place(|| {
+            tokio::runtime::Handle::current().block_on(server::bind(&hostname, port, reuse_port))
+        });
+        let (listener, local_addr) = match bound.and_then(|listener| {
+            listener
+                .local_addr()
+                .map(|local_addr| (listener, local_addr))
+        }) {
+            Ok(bound) => bound,
+            Err(e) => {
+                let result = serde_json::json!({ "success": false, "error": e.to_string() });
+                return Ok(Value::from_string(agent, result.to_string(), gc.nogc()).unbind());
+            }
+        };
+
+        let (request_tx, request_rx) = mpsc::channel(REQUEST_QUEUE_CAPACITY);
+        let (shutdown_tx, shutdown_rx) = watch::channel(false);
+        tokio::spawn(server::accept_loop(listener, tls, request_tx, shutdown_rx));
+
+        let rid = Self::get_http_resources(agent)
+            .servers
+            .push(HttpServerResource {
+                requests: Arc::new(Mutex::new(request_rx)),
+                shutdown: Arc::new(shutdown_tx),
+            });
+
+        let result = serde_json::json!({
+            "success": true,
+            "resourceId": rid.index(),
+            "hostname": local_addr.ip().to_string(),
+            "port": local_addr.port(),
+        });
+        Ok(Value::from_string(agent, result.to_string(), gc.nogc()).unbind())
+    }
+
+    /// Wait for the next request on a server. Resolves with the request as
+    /// JSON, or `"null"` once the server has been closed.
+    pub fn internal_http_next_request<'gc>(
+        agent: &mut Agent,
+        _this: Value,
+        args: ArgumentsList,
+        mut gc: GcScope<'gc, '_>,
+    ) -> JsResult<'gc, Value<'gc>> {
+        let rid_binding = args.get(0).to_string(agent, gc.reborrow()).unbind()?;
+        let server = Self::parse_rid(rid_binding.as_str(agent).unwrap_or_default())
+            .and_then(|rid| Self::get_http_resources(agent).servers.get(rid));
+
+        let promise_capability = PromiseCapability::new(agent, gc.nogc());
+        let root_value = Global::new(agent, promise_capability.promise().into_value().unbind());
+        let requests = Self::get_http_resources(agent).requests.clone();
+
+        let host_data = agent.get_host_data();
+        let host_data: &HostData<RuntimeMacroTask> = host_data.downcast_ref().unwrap();
+        let macro_task_tx = host_data.macro_task_tx();
+
+        host_data.spawn_macro_task(async move {
+            let request = match server {
+                Some(server) => {
+                    let mut shutdown = server.shutdown.subscribe();
+                    tokio::select! {
+                        request = async { server.requests.lock().await.recv().await } => request,
+                        _ = shutdown.wait_for(|closed| *closed) => None,
+                    }
+                }
+                None => None,
+            };
+
+            let result = match request {
+                Some(request) => {
+                    let has_body = request.body.is_some();
+                    let rid = requests.push(HttpRequestResource {
+                        body: request.body.map(|body| Arc::new(Mutex::new(body))),
+                        response: Arc::new(std::sync::Mutex::new(Some(request.response))),
+                        response_body: Arc::new(std::sync::Mutex::new(None)),
+                        upgrade: Arc::new(std::sync::Mutex::new(None)),
+                    });
+                    serde_json::json!({
+                        "resourceId": rid.index(),
+                        "method": request.method,
+                        "url": request.target,
+                        "headers": request.headers,
+                        "hasBody": has_body,
+                        "remoteAddr": {
+                            "hostname": request.remote_addr.ip().to_string(),
+                            "port": request.remote_addr.port(),
+                        },
+                    })
+                    .to_string()
+                }
+                None => "null".to_string(),
+            };
+
+            macro_task_tx
+                .send(MacroTask::User(RuntimeMacroTask::ResolvePromiseWithString(
+                    root_value, result,
+                )))
+                .unwrap();
+        });
+
+        Ok(Value::Promise(promise_capability.promise()).unbind())
+    }
+
+    /// Read the next chunk of a request body. Resolves with the chunk as hex,
+    /// or an empty string once the body is complete.
+    pub fn internal_http_request_read<'gc>(
+        agent: &mut Agent,
+        _this: Value,
+        args: ArgumentsList,
+        mut gc: GcScope<'gc, '_>,
+    ) -> JsResult<'gc, Value<'gc>> {
+        let rid_binding = args.get(0).to_string(agent, gc.reborrow()).unbind()?;
+        let request = Self::parse_rid(rid_binding.as_str(agent).unwrap_or_default())
+            .and_then(|rid| Self::get_http_resources(agent).requests.get(rid));
+
+        let promise_capability = PromiseCapability::new(agent, gc.nogc());
+        let root_value = Global::new(agent, promise_capability.promise().into_value().unbind());
+
+        let host_data = agent.get_host_data();
+        let host_data: &HostData<RuntimeMacroTask> = host_data.downcast_ref().unwrap();
+        let macro_task_tx = host_data.macro_task_tx();
+
+        host_data.spawn_macro_task(async move {
+            let task = match request {
+                None => RuntimeMacroTask::RejectPromise(
+                    root_value,
+                    "Request body is no longer available".to_string(),
+                ),
+                Some(HttpRequestResource { body: None, .. }) => {
+                    RuntimeMacroTask::ResolvePromiseWithString(root_value, String::new())
+                }
+                Some(HttpRequestResource {
+                    body: Some(body), ..
+                }) => match body.lock().await.recv().await {
+                    Some(Ok(chunk)) => RuntimeMacroTask::ResolvePromiseWithBytes(root_value, chunk),
+                    Some(Err(e)) => RuntimeMacroTask::RejectPromise(
+                        root_value,
+                        format!("Error reading request body: {e}"),
+                    ),
+                    None => RuntimeMacroTask::ResolvePromiseWithString(root_value, String::new()),
+                },
+            };
+            macro_task_tx.send(MacroTask::User(task)).unwrap();
+        });
+
+        Ok(Value::Promise(promise_capability.promise()).unbind())
+    }
+
+    /// Send the response head. With `streaming` set the body follows through
+    /// `http_response_write`, otherwise the hex `body` is the whole body.
+    pub fn internal_http_respond<'gc>(
+        agent: &mut Agent,
+        _this: Value,
+        args: ArgumentsList,
+        mut gc: GcScope<'gc, '_>,
+    ) -> JsResult<'gc, Value<'gc>> {
+        let rid_binding = args.get(0).to_string(agent, gc.reborrow()).unbind()?;
+        let status_binding = args.get(1).to_string(agent, gc.reborrow()).unbind()?;
+        let headers_binding = args.get(2).to_string(agent, gc.reborrow()).unbind()?;
+        let body_binding = args.get(3).to_string(agent, gc.reborrow()).unbind()?;
+        let streaming_binding = args.get(4).to_string(agent, gc.reborrow()).unbind()?;
+
+        let rid = Self::parse_rid(rid_binding.as_str(agent).unwrap_or_default());
+        let status = status_binding
+            .as_str(agent)
+            .and_then(|status| status.parse::<u16>().ok())
+            .filter(|status| (100..=999).contains(status));
+        let headers = serde_json::from_str::<Vec<(String, String)>>(
+            headers_binding.as_str(agent).unwrap_or_default(),
+        );
+        let streaming = streaming_binding.as_str(agent) == Some("true");
+        let body = decode_hex(body_binding.as_str(agent).unwrap_or_default());
+
+        let (Some(status), Ok(headers), Some(body)) = (status, headers, body) else {
+            return Err(agent
+                .throw_exception(
+                    ExceptionType::TypeError,
+                    "Invalid response status, headers or body".to_string(),
+                    gc.nogc(),
+                )
+                .unbind());
+        };
+        if !headers
+            .iter()
+            .all(|(name, value)| is_valid_header(name, value))
+        {
+            return Err(agent
+                .throw_exception(
+                    ExceptionType::TypeError,
+                    "Invalid response header".to_string(),
+                    gc.nogc(),
+                )
+                .unbind());
+        }
+
+        let request = rid.and_then(|rid| Self::get_http_resources(agent).requests.get(rid));
+        let response = request
+            .as_ref()
+            .and_then(|request| request.response.lock().unwrap().take());
+        let (Some(rid), Some(request), Some(response)) = (rid, request, response) else {
+            return Err(agent
+                .throw_exception(
+                    ExceptionType::Error,
+                    "Response was already sent".to_string(),
+                    gc.nogc(),
+                )
+                .unbind());
+        };
+
+        let upgrade = if status == 101 {
+            request.upgrade.lock().unwrap().take()
+        } else {
+            None
+        };
+        let body = if streaming {
+            let (body_tx, body_rx) = mpsc::channel(RESPONSE_CHANNEL_CAPACITY);
+            *request.response_body.lock().unwrap() = Some(body_tx);
+            ResponseBody::Stream(body_rx)
+        } else {
+            Self::get_http_resources(agent).requests.remove(rid);
+            ResponseBody::Full(body)
+        };
+
+        // The connection is gone when nobody is waiting for the response
+        let _ = response.send(OutgoingResponse {
+            status,
+            headers,
+            body,
+            upgrade,
+        });
+
+        Ok(Value::Undefined)
+    }
+
+    /// Queue a hex chunk of a streaming response body. Resolves once the
+    /// connection has room for it, and rejects if the client went away.
+    pub fn internal_http_response_write<'gc>(
+        agent: &mut Agent,
+        _this: Value,
+        args: ArgumentsList,
+        mut gc: GcScope<'gc, '_>,
+    ) -> JsResult<'gc, Value<'gc>> {
+        let rid_binding = args.get(0).to_string(agent, gc.reborrow()).unbind()?;
+        let chunk_binding = args.get(1).to_string(agent, gc.reborrow()).unbind()?;
+
+        let sender = Self::parse_rid(rid_binding.as_str(agent).unwrap_or_default())
+            .and_then(|rid| Self::get_http_resources(agent).requests.get(rid))
+            .and_then(|request| request.response_body.lock().unwrap().clone());
+        let chunk = decode_hex(chunk_binding.as_str(agent).unwrap_or_default()).unwrap_or_default();
+
+        let promise_capability = PromiseCapability::new(agent, gc.nogc());
+        let root_value = Global::new(agent, promise_capability.promise().into_value().unbind());
+
+        let host_data = agent.get_host_data();
+        let host_data: &HostData<RuntimeMacroTask> = host_data.downcast_ref().unwrap();
+        let macro_task_tx = host_data.macro_task_tx();
+
+        host_data.spawn_macro_task(async move {
+            let sent = match sender {
+                Some(sender) => sender.send(BodyChunk::Data(chunk)).await.is_ok(),
+                None => false,
+            };
+            let task = if sent {
+                RuntimeMacroTask::ResolvePromiseWithString(root_value, String::new())
+            } else {
+                RuntimeMacroTask::RejectPromise(
+                    root_value,
+                    "Connection closed before the response was complete".to_string(),
+                )
+            };
+            macro_task_tx.send(MacroTask::User(task)).unwrap();
+        });
+
+        Ok(Value::Promise(promise_capability.promise()).unbind())
+    }
+
+    /// Finish a streaming response, or abort it when `aborted` is `"true"`.
+    pub fn internal_http_response_end<'gc>(
+        agent: &mut Agent,
+        _this: Value,
+        args: ArgumentsList,
+        mut gc: GcScope<'gc, '_>,
+    ) -> JsResult<'gc, Value<'gc>> {
+        let rid_binding = args.get(0).to_string(agent, gc.reborrow()).unbind()?;
+        let aborted_binding = args.get(1).to_string(agent, gc.reborrow()).unbind()?;
+        let aborted = aborted_binding.as_str(agent) == Some("true");
+
+        let request = Self::parse_rid(rid_binding.as_str(agent).unwrap_or_default())
+            .and_then(|rid| Self::get_http_resources(agent).requests.remove(rid));
+        let sender = request.and_then(|request| request.response_body.lock().unwrap().take());
+
+        // Dropping the sender without the end marker aborts the response
+        if let (Some(sender), false) = (sender, aborted) {
+            tokio::spawn(async move {
+                let _ = sender.send(BodyChunk::End).await;
+            });
+        }
+
+        Ok(Value::Undefined)
+    }
+
+    /// Prepare to upgrade a request to a WebSocket, given its
+    /// `Sec-WebSocket-Key` and the selected subprotocol. Returns
+    /// `{"resourceId":n,"accept":".."}`, the socket opens once the handler
+    /// responds with a 101 carrying the accept value.
+    pub fn internal_http_upgrade_websocket<'gc>(
+        agent: &mut Agent,
+        _this: Value,
+        args: ArgumentsList,
+        mut gc: GcScope<'gc, '_>,
+    ) -> JsResult<'gc, Value<'gc>> {
+        let rid_binding = args.get(0).to_string(agent, gc.reborrow()).unbind()?;
+        let key_binding = args.get(1).to_string(agent, gc.reborrow()).unbind()?;
+        let protocol_binding = args.get(2).to_string(agent, gc.reborrow()).unbind()?;
+
+        let key = key_binding
+            .as_str(agent)
+            .unwrap_or_default()
+            .trim()
+            .to_string();
+        let protocol = protocol_binding
+            .as_str(agent)
+            .unwrap_or_default()
+            .to_string();
+        if !is_valid_key(&key) {
+            return Err(agent
+                .throw_exception(
+                    ExceptionType::TypeError,
+                    "Invalid Sec-WebSocket-Key header".to_string(),
+                    gc.nogc(),
+                )
+                .unbind());
+        }
+
+        let request = Self::parse_rid(rid_binding.as_str(agent).unwrap_or_default())
+            .and_then(|rid| Self::get_http_resources(agent).requests.get(rid))
+            .filter(|request| request.response.lock().unwrap().is_some());
+        let Some(request) = request else {
+            return Err(agent
+                .throw_exception(
+                    ExceptionType::Error,
+                    "Response was already sent".to_string(),
+                    gc.nogc(),
+                )
+                .unbind());
+        };
+
+        let (upgrade_tx, upgrade_rx) = oneshot::channel();
+        *request.upgrade.lock().unwrap() = Some(upgrade_tx);
+
+        let host_data = agent.get_host_data();
+        let host_data: &HostData<RuntimeMacroTask> = host_data.downcast_ref().unwrap();
+        let storage = host_data.storage.borrow();
+        let sockets: &WebSocketResources = storage.get().unwrap();
+        let socket_rid = sockets.accept(upgrade_rx, protocol);
+        drop(storage);
+
+        let result = serde_json::json!({
+            "resourceId": socket_rid.index(),
+            "accept": accept_key(&key),
+        });
+        Ok(Value::from_string(agent, result.to_string(), gc.nogc()).unbind())
+    }
+
+    /// Stop accepting connections. Pending `http_next_request` calls resolve
+    /// with `"null"`.
+    pub fn internal_http_server_close<'gc>(
+        agent: &mut Agent,
+        _this: Value,
+        args: ArgumentsList,
+        mut gc: GcScope<'gc, '_>,
+    ) -> JsResult<'gc, Value<'gc>> {
+        let rid_binding = args.get(0).to_string(agent, gc.reborrow()).unbind()?;
+        let server = Self::parse_rid(rid_binding.as_str(agent).unwrap_or_default())
+            .and_then(|rid| Self::get_http_resources(agent).servers.remove(rid));
+
+        if let Some(server) = server {
+            server.shutdown.send_replace(true);
+        }
+
+        Ok(Value::Undefined)
+    }
+}
+
+/// Header names must be tokens and values must not be able to end the header
+/// line, so handler-supplied headers cannot inject anything into the response.
+fn is_valid_header(name: &str, value: &str) -> bool {
+    !name.is_empty()
+        && name
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || b"!#$%&'*+-.^_`|~".contains(&b))
+        && !value.bytes().any(|b| b == b'\r' || b == b'\n' || b == 0)
 }
@@ -11,128 +11,246 @@ interface ServeOptions {
   hostname?: string;
   signal?: AbortSignal;
   reusePort?: boolean;
+  /** PEM encoded private key, serving HTTPS together with `cert`. */
   key?: string;
+  /** PEM encoded certificate chain, serving HTTPS together with `key`. */
   cert?: string;
+  /** Certificates selected by the server name the client asks for (SNI). */
+  sni?: { hostname: string; key: string; cert: string; }[];
   onError?: (error: unknown) => Response | Promise<Response>;
   onListen?: (params: { hostname: string; port: number; }) => void;
   handler?: ServeHandler;
 }
 
-function parseHttpRequest(data: string): {
+/** A request as received by the native server. */
+interface IncomingRequest {
+  resourceId: number;
   method: string;
-  path: string;
-  headers: Headers;
-  body: string;
-} {
-  const lines = data.split("\r\n");
-  if (lines.length === 0) {
-    return { method: "GET", path: "/", headers: new Headers(), body: "" };
-  }
+  url: string;
+  headers: [string, string][];
+  hasBody: boolean;
+  remoteAddr: { hostname: string; port: number; };
+}
 
-  const requestLine = lines[0].split(" ");
-  const method = requestLine[0] || "GET";
-  const path = requestLine[1] || "/";
+interface UpgradeWebSocketOptions {
+  /** The subprotocol to select, one the client offered. */
+  protocol?: string;
+}
 
-  const headers = new Headers();
-  let i = 1;
-  for (; i < lines.length; i++) {
-    if (lines[i] === "") break;
-    const colonIndex = lines[i].indexOf(":");
-    if (colonIndex > 0) {
-      const key = lines[i].substring(0, colonIndex).trim();
-      const value = lines[i].substring(colonIndex + 1).trim();
-      headers.append(key, value);
-    }
-  }
+const HTTP_BODY_SYMBOL = (globalThis as any).BODY_SYMBOL;
 
-  const body = lines.slice(i + 1).join("\r\n");
-  // TODO: Handle chunked transfer encoding
-  // TODO: Handle multipart form data
-  // TODO: Validate Content-Length header
+/** The native request behind each Request handed to a handler. */
+const httpRequestRids = new WeakMap<Request, number>();
 
-  return { method, path, headers, body };
+function bytesToHex(bytes: Uint8Array): string {
+  let hex = "";
+  for (let i = 0; i < bytes.length; i++) {
+    hex += bytes[i].toString(16).padStart(2, "0");
+  }
+  return hex;
 }
 
-function createHttpResponse(
-  statusCode: number,
-  statusText: string,
-  headers: Headers,
-  body: string,
-): string {
-  const lines = [`HTTP/1.1 ${statusCode} ${statusText}`];
-
-  // Iterate all headers using forEach
-  headers.forEach((value, name) => {
-    lines.push(`${name}: ${value}`);
+function hexToBytes(hex: string): Uint8Array {
+  const bytes = new Uint8Array(hex.length / 2);
+  for (let i = 0; i < bytes.length; i++) {
+    bytes[i] = parseInt(hex.substr(i * 2, 2), 16);
+  }
+  return bytes;
+}
+
+/** Stream a request body out of the native server as the handler reads it. */
+function requestBody(rid: number): ReadableStream<Uint8Array> {
+  return new ReadableStream<Uint8Array>({
+    async pull(controller) {
+      try {
+        const hex = await __andromeda__.http_request_read(rid);
+        if (hex.length === 0) {
+          controller.close();
+        } else {
+          controller.enqueue(hexToBytes(hex));
+        }
+      } catch (error) {
+        controller.error(error);
+      }
+    },
   });
+}
 
-  if (!headers.has("Content-Length")) {
-    lines.push(`Content-Length: ${body.length}`);
+function toRequest(
+  incoming: IncomingRequest,
+  scheme: string,
+  origin: string,
+): Request {
+  const headers = new Headers();
+  for (const [name, value] of incoming.headers) {
+    headers.append(name, value);
   }
 
-  if (!headers.has("Connection")) {
-    lines.push("Connection: close");
+  // Origin-form targets are resolved against the Host header, absolute-form
+  // targets (sent to proxies) are used as they are
+  let url: string;
+  if (incoming.url.startsWith("/")) {
+    const host = headers.get("host");
+    url = (host ? `${scheme}://${host}` : origin) + incoming.url;
+  } else if (/^https?:\/\//i.test(incoming.url)) {
+    url = incoming.url;
+  } else {
+    url = origin + "/";
   }
 
-  lines.push("", body);
-  return lines.join("\r\n");
+  const hasBody = incoming.hasBody && incoming.method !== "GET" &&
+    incoming.method !== "HEAD";
+  const request = new Request(url, {
+    method: incoming.method,
+    headers,
+    body: hasBody ? requestBody(incoming.resourceId) : null,
+    duplex: "half",
+  });
+  httpRequestRids.set(request, incoming.resourceId);
+  return request;
+}
+
+function headerTokens(headers: Headers, name: string): string[] {
+  return (headers.get(name) ?? "").split(",").map((token) => token.trim());
 }
 
-async function handleConnection(
-  connectionId: number,
-  handler: ServeHandler,
-  options: ServeOptions,
-): Promise<void> {
-  try {
-    const READ_BUFFER_SIZE = "4096";
+/**
+ * Upgrade a request received by `Andromeda.serve` to a WebSocket. The
+ * handler must return the `response`, the socket opens once it was sent.
+ */
+function upgradeWebSocket(
+  request: Request,
+  options: UpgradeWebSocketOptions = {},
+): { socket: WebSocket; response: Response; } {
+  const rid = httpRequestRids.get(request);
+  if (rid === undefined) {
+    throw new TypeError("Request was not received by Andromeda.serve");
+  }
 
-    const readResult = await __andromeda__.tcp_read_async(
-      connectionId,
-      READ_BUFFER_SIZE,
-    );
-    if (!readResult || readResult === "0") {
-      __andromeda__.tcp_close(connectionId);
-      return;
-    }
+  const headers = request.headers;
+  const lowercase = (tokens: string[]) => tokens.map((t) => t.toLowerCase());
+  if (
+    request.method !== "GET" ||
+    !lowercase(headerTokens(headers, "upgrade")).includes("websocket") ||
+    !lowercase(headerTokens(headers, "connection")).includes("upgrade")
+  ) {
+    throw new TypeError("Request is not a WebSocket upgrade");
+  }
+  if (headers.get("sec-websocket-version")?.trim() !== "13") {
+    throw new TypeError("Unsupported WebSocket version, expected 13");
+  }
+  const protocol = options.protocol ?? "";
+  if (
+    protocol !== "" &&
+    !headerTokens(headers, "sec-websocket-protocol").includes(protocol)
+  ) {
+    throw new TypeError(`The client did not offer the subprotocol ${protocol}`);
+  }
 
-    const {
-      method,
-      path,
-      headers,
-      body: requestBody,
-    } = parseHttpRequest(readResult);
+  const upgrade = JSON.parse(
+    __andromeda__.http_upgrade_websocket(
+      rid,
+      headers.get("sec-websocket-key") ?? "",
+      protocol,
+    ),
+  );
+  const responseHeaders: [string, string][] = [
+    ["upgrade", "websocket"],
+    ["connection", "Upgrade"],
+    ["sec-websocket-accept", upgrade.accept],
+  ];
+  if (protocol !== "") {
+    responseHeaders.push(["sec-websocket-protocol", protocol]);
+  }
+  // 101 is outside the range the Response constructor accepts
+  const response = new Response(null, { headers: responseHeaders });
+  (Response as any).getResponse(response).status = 101;
 
-    const url = `http://${options.hostname}:${options.port}${path}`;
+  const socket = globalThis.__andromeda_websocket_accept(
+    upgrade.resourceId,
+    request.url,
+  );
+  return { socket, response };
+}
 
-    const request = new Request(url, {
-      method: method,
-      headers: headers,
-      body: requestBody || null,
-    });
+/** Write a response back, streaming its body when it isn't known up front. */
+async function respond(rid: number, response: Response): Promise<void> {
+  const headers: [string, string][] = [];
+  response.headers.forEach((value, name) => {
+    headers.push([name, value]);
+  });
+  const status = String(response.status);
+  const headersJson = JSON.stringify(headers);
 
-    const response = await handler(request);
+  // Bodies created from a string or bytes are sent in one piece with a
+  // Content-Length, streams are sent chunk by chunk
+  const inner = (response as any)[HTTP_BODY_SYMBOL];
+  if (!inner || (inner.source !== null && !response.bodyUsed)) {
+    const source = inner ? inner.source : null;
+    const bytes = typeof source === "string" ?
+      new TextEncoder().encode(source) :
+      (source ?? new Uint8Array(0));
+    __andromeda__.http_respond(
+      rid,
+      status,
+      headersJson,
+      bytesToHex(bytes),
+      "false",
+    );
+    return;
+  }
 
-    let responseBody = "";
-    if (response.body) {
-      responseBody = await response.text();
+  const reader = (response.body as ReadableStream<Uint8Array>).getReader();
+  __andromeda__.http_respond(rid, status, headersJson, "", "true");
+  try {
+    while (true) {
+      const { done, value } = await reader.read();
+      if (done) break;
+      const chunk = typeof value === "string" ?
+        new TextEncoder().encode(value) :
+        value;
+      await __andromeda__.http_response_write(rid, bytesToHex(chunk));
     }
+    __andromeda__.http_response_end(rid, "false");
+  } catch (error) {
+    __andromeda__.http_response_end(rid, "true");
+    reader.cancel(error).catch(() => {});
+  }
+}
 
-    const statusCode = response.status || 200;
-    const statusText = response.statusText || "OK";
-    const responseHeaders = response.headers;
+function defaultOnError(error: unknown): Response {
+  console.error("Error in request handler:", error);
+  return new Response("Internal Server Error", { status: 500 });
+}
 
-    const httpResponse = createHttpResponse(
-      statusCode,
-      statusText,
-      responseHeaders,
-      responseBody,
-    );
-    await __andromeda__.tcp_write_async(connectionId, httpResponse);
-    __andromeda__.tcp_close(connectionId);
+async function handleRequest(
+  incoming: IncomingRequest,
+  handler: ServeHandler,
+  onError: (error: unknown) => Response | Promise<Response>,
+  scheme: string,
+  origin: string,
+): Promise<void> {
+  const rid = incoming.resourceId;
+  let response: Response;
+  try {
+    response = await handler(toRequest(incoming, scheme, origin));
+    if (!(response instanceof Response)) {
+      throw new TypeError("Handler must return a Response");
+    }
   } catch (error) {
     try {
-      __andromeda__.tcp_close(connectionId);
-    } catch (e) {}
+      response = await onError(error);
+    } catch (innerError) {
+      response = defaultOnError(innerError);
+    }
+  }
+
+  try {
+    await respond(rid, response);
+  } catch (error) {
+    // The response could not be sent, end it so the connection moves on
+    __andromeda__.http_response_end(rid, "true");
+    console.error("Failed to send response:", error);
   }
 }
 
@@ -159,47 +277,55 @@ async function serve(
     handler = options.handler;
   }
 
-  const hostname = options.hostname ?? DEFAULT_HOSTNAME;
-  const port = options.port ?? DEFAULT_PORT;
-  console.info(`HTTP server running on http://${hostname}:${port}/`);
+  const secure = options.key !== undefined || options.cert !== undefined ||
+    (options.sni?.length ?? 0) > 0;
+  // HTTP/2 is preferred, clients without it fall back to HTTP/1.1
+  const tls = secure ?
+    globalThis.__andromeda_tls_server_options(options, ["h2", "http/1.1"]) :
+    "";
+  const scheme = secure ? "https" : "http";
+
+  if (options.signal?.aborted) {
+    return;
+  }
 
-  const listenResult = __andromeda__.tcp_listen(hostname, port);
+  const listenResult = __andromeda__.http_serve(
+    options.hostname ?? DEFAULT_HOSTNAME,
+    String(options.port ?? DEFAULT_PORT),
+    String(options.reusePort ?? false),
+    tls,
+  );
   const listenData = JSON.parse(listenResult);
   if (!listenData.success) {
-    console.error("Failed to create listener:", listenData.error);
-    return;
+    throw new Error(`Failed to start HTTP server: ${listenData.error}`);
   }
-  const listenerId = listenData.resourceId;
+  const serverId: number = listenData.resourceId;
+  const hostname: string = listenData.hostname;
+  const port: number = listenData.port;
+  const origin = `${scheme}://${
+    hostname.includes(":") ? `[${hostname}]` : hostname
+  }:${port}`;
 
-  while (true) {
-    try {
-      const acceptPromise = __andromeda__.tcp_accept_async(String(listenerId));
-      const acceptResult = await acceptPromise;
-      const result = JSON.parse(acceptResult);
-      if (!result.success) {
-        continue;
-      }
+  options.signal?.addEventListener("abort", () => {
+    __andromeda__.http_server_close(serverId);
+  }, { once: true });
 
-      // TODO: Handle connections concurrently instead of sequentially
-      handleConnection(result.resourceId, handler, {
-        ...options,
-        hostname,
-        port,
-      });
-    } catch (error) {
-      break;
-    }
+  if (options.onListen) {
+    options.onListen({ hostname, port });
+  } else {
+    console.info(`HTTP server running on ${origin}/`);
   }
-}
 
-// TODO: Add support for server configuration options:
-// - TLS/HTTPS support
-// - Connection timeout
-// - Request size limits
-// - Keep-Alive support
-// - HTTP/2 support
-// - WebSocket upgrade
-// - Signal/AbortController for graceful shutdown
-// - onListen callback
+  const onError = options.onError ?? defaultOnError;
+  while (true) {
+    const next = await __andromeda__.http_next_request(serverId);
+    const incoming: IncomingRequest | null = JSON.parse(next);
+    if (incoming === null) break;
+    // Requests are handled concurrently, the server orders the responses
+    // of pipelined requests on the same connection
+    handleRequest(incoming, handler, onError, scheme, origin);
+  }
+}
 
 globalThis.__andromeda_http_serve = serve;
+globalThis.__andromeda_upgrade_websocket = upgradeWebSocket;
@@ -0,0 +1,726 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! HTTP/1.1 connection handling for `Andromeda.serve`.
+//!
+//! Every accepted connection runs in its own task. Requests are read one at a
+//! time, so pipelined requests are answered in the order they arrived, and the
+//! connection is kept alive between requests unless either side asks to close
+//! it. Request and response bodies are streamed through bounded channels, which
+//! makes a slow handler or a slow client apply backpressure to the other side.
+//! A `101` response to a WebSocket upgrade hands the connection over to the
+//! WebSocket extension instead. Connections speaking HTTP/2, negotiated with
+//! ALPN or announced by the preface, are handed to the `http2` module.
+
+use std::{io, net::SocketAddr, time::Duration};
+
+use tokio::{
+    io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt},
+    net::{TcpListener, TcpSocket, TcpStream},
+    sync::{mpsc, oneshot, watch},
+};
+use tokio_rustls::TlsAcceptor;
+
+use super::http2::{self, PREFACE, Rewind};
+use crate::ext::{
+    http1::{ChunkSink, MAX_HEAD_SIZE, MAX_HEADERS, Reader},
+    tls::handshake,
+    websocket::Upgraded,
+};
+
+/// Request body bytes discarded after the handler stopped reading the body.
+/// Bigger leftovers close the connection instead of being drained.
+const MAX_DRAIN_SIZE: u64 = 1024 * 1024;
+/// Body chunks buffered between the connection and the handler.
+pub(super) const BODY_CHANNEL_CAPACITY: usize = 8;
+/// How long an idle keep-alive connection waits for the next request.
+const KEEP_ALIVE_TIMEOUT: Duration = Duration::from_secs(5);
+/// How long a client may take to send a complete request head.
+pub(super) const HEAD_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// A parsed request handed to the JavaScript handler.
+pub struct IncomingRequest {
+    pub method: String,
+    pub target: String,
+    pub headers: Vec<(String, String)>,
+    pub remote_addr: SocketAddr,
+    /// Body chunks, `None` for requests without a body.
+    pub body: Option<mpsc::Receiver<io::Result<Vec<u8>>>>,
+    pub response: oneshot::Sender<OutgoingResponse>,
+}
+
+/// The status, headers and body the handler answered with.
+pub struct OutgoingResponse {
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: ResponseBody,
+    /// Receives the connection when a 101 response upgrades it to WebSocket
+    pub upgrade: Option<oneshot::Sender<Upgraded>>,
+}
+
+pub enum ResponseBody {
+    Full(Vec<u8>),
+    Stream(mpsc::Receiver<BodyChunk>),
+}
+
+pub enum BodyChunk {
+    Data(Vec<u8>),
+    /// The body is complete. Closing the channel without it aborts the
+    /// response.
+    End,
+}
+
+/// Errors that end a connection, answered with a status when possible.
+enum HttpError {
+    BadRequest,
+    HeadersTooLarge,
+    NotImplemented,
+    VersionNotSupported,
+    Io(io::Error),
+}
+
+impl HttpError {
+    fn status(&self) -> Option<u16> {
+        match self {
+            HttpError::BadRequest => Some(400),
+            HttpError::HeadersTooLarge => Some(431),
+            HttpError::NotImplemented => Some(501),
+            HttpError::VersionNotSupported => Some(505),
+            HttpError::Io(_) => None,
+        }
+    }
+}
+
+impl From<io::Error> for HttpError {
+    fn from(error: io::Error) -> Self {
+        HttpError::Io(error)
+    }
+}
+
+/// What happens to a connection once a response has been written.
+enum Outcome {
+    KeepAlive,
+    Close,
+    Upgrade(oneshot::Sender<Upgraded>),
+}
+
+enum BodyKind {
+    Empty,
+    Length(u64),
+    Chunked,
+}
+
+struct RequestHead {
+    method: String,
+    target: String,
+    minor_version: u8,
+    headers: Vec<(String, String)>,
+}
+
+impl RequestHead {
+    fn header_values<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a str> + 'a {
+        self.headers
+            .iter()
+            .filter(move |(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    fn has_token(&self, name: &str, token: &str) -> bool {
+        self.header_values(name)
+            .flat_map(|value| value.split(','))
+            .any(|item| item.trim().eq_ignore_ascii_case(token))
+    }
+
+    fn keep_alive(&self) -> bool {
+        if self.has_token("connection", "close") {
+            false
+        } else {
+            self.minor_version >= 1 || self.has_token("connection", "keep-alive")
+        }
+    }
+
+    fn body_kind(&self) -> Result<BodyKind, HttpError> {
+        let transfer_encoding: Vec<&str> = self
+            .header_values("transfer-encoding")
+            .flat_map(|value| value.split(','))
+            .map(str::trim)
+            .filter(|coding| !coding.is_empty())
+            .collect();
+
+        if !transfer_encoding.is_empty() {
+            // A request carrying both framings is a request smuggling vector
+            if self.header_values("content-length").next().is_some() || self.minor_version == 0 {
+                return Err(HttpError::BadRequest);
+            }
+            return match transfer_encoding.as_slice() {
+                [coding] if coding.eq_ignore_ascii_case("chunked") => Ok(BodyKind::Chunked),
+                [.., last] if last.eq_ignore_ascii_case("chunked") => {
+                    Err(HttpError::NotImplemented)
+                }
+                _ => Err(HttpError::BadRequest),
+            };
+        }
+
+        let mut length = None;
+        for value in self
+            .header_values("content-length")
+            .flat_map(|value| value.split(','))
+        {
+            let value = value.trim();
+            if value.is_empty() || !value.bytes().all(|b| b.is_ascii_digit()) {
+                return Err(HttpError::BadRequest);
+            }
+            let parsed: u64 = value.parse().map_err(|_| HttpError::BadRequest)?;
+            match length {
+                Some(existing) if existing != parsed => return Err(HttpError::BadRequest),
+                _ => length = Some(parsed),
+            }
+        }
+
+        Ok(match length {
+            None | Some(0) => BodyKind::Empty,
+            Some(length) => BodyKind::Length(length),
+        })
+    }
+}
+
+impl<R: AsyncRead + Unpin> Reader<R> {
+    /// Read the next request head, or `None` once the client is done.
+    async fn read_head(&mut self, first: bool) -> Result<Option<RequestHead>, HttpError> {
+        loop {
+            // Clients may send stray line breaks between pipelined requests
+            let leading = self
+                .buffer
+                .iter()
+                .take_while(|b| **b == b'\r' || **b == b'\n')
+                .count();
+            self.buffer.drain(..leading);
+
+            if !self.buffer.is_empty() {
+                let mut headers = [httparse::EMPTY_HEADER; MAX_HEADERS];
+                let mut request = httparse::Request::new(&mut headers);
+                match request.parse(&self.buffer) {
+                    Ok(httparse::Status::Complete(length)) => {
+                        let head = RequestHead {
+                            method: request.method.unwrap_or_default().to_string(),
+                            target: request.path.unwrap_or_default().to_string(),
+                            minor_version: request.version.unwrap_or_default(),
+                            headers: request
+                                .headers
+                                .iter()
+                                .map(|header| {
+                                    Ok((
+                                        header.name.to_ascii_lowercase(),
+                                        std::str::from_utf8(header.value)
+                                            .map_err(|_| HttpError::BadRequest)?
+                                            .to_string(),
+                                    ))
+                                })
+                                .collect::<Result<_, HttpError>>()?,
+                        };
+                        self.buffer.drain(..length);
+                        return Ok(Some(head));
+                    }
+                    Ok(httparse::Status::Partial) if self.buffer.len() >= MAX_HEAD_SIZE => {
+                        return Err(HttpError::HeadersTooLarge);
+                    }
+                    Ok(httparse::Status::Partial) => {}
+                    Err(httparse::Error::TooManyHeaders) => {
+                        return Err(HttpError::HeadersTooLarge);
+                    }
+                    Err(httparse::Error::Version) => return Err(HttpError::VersionNotSupported),
+                    Err(_) => return Err(HttpError::BadRequest),
+                }
+            }
+
+            let timeout = if self.buffer.is_empty() && !first {
+                KEEP_ALIVE_TIMEOUT
+            } else {
+                HEAD_TIMEOUT
+            };
+            match tokio::time::timeout(timeout, self.fill()).await {
+                Ok(Ok(0)) if self.buffer.is_empty() => return Ok(None),
+                Ok(Ok(0)) => return Err(HttpError::BadRequest),
+                Ok(Ok(_)) => {}
+                Ok(Err(error)) => return Err(HttpError::Io(error)),
+                Err(_) => return Ok(None),
+            }
+        }
+    }
+
+    async fn read_body(&mut self, kind: BodyKind, mut body: BodySink) -> io::Result<()> {
+        let result = match kind {
+            BodyKind::Empty => Ok(()),
+            BodyKind::Length(length) => self.read_exact_into(length, &mut body).await,
+            BodyKind::Chunked => self.read_chunked(&mut body).await,
+        };
+        if let Err(error) = &result {
+            body.fail(error).await;
+        }
+        result
+    }
+}
+
+/// Forwards body chunks to the handler, draining them once it stops reading.
+struct BodySink {
+    sender: Option<mpsc::Sender<io::Result<Vec<u8>>>>,
+    drained: u64,
+}
+
+impl ChunkSink for BodySink {
+    async fn data(&mut self, chunk: Vec<u8>) -> io::Result<()> {
+        if let Some(sender) = &self.sender {
+            match sender.send(Ok(chunk)).await {
+                Ok(()) => return Ok(()),
+                Err(mpsc::error::SendError(Ok(chunk))) => {
+                    self.sender = None;
+                    self.drained += chunk.len() as u64;
+                }
+                Err(_) => self.sender = None,
+            }
+        } else {
+            self.drained += chunk.len() as u64;
+        }
+        if self.drained > MAX_DRAIN_SIZE {
+            return Err(io::Error::other("request body was not read by the handler"));
+        }
+        Ok(())
+    }
+}
+
+impl BodySink {
+    async fn fail(&mut self, error: &io::Error) {
+        if let Some(sender) = self.sender.take() {
+            let _ = sender.send(Err(io::Error::other(error.to_string()))).await;
+        }
+    }
+}
+
+/// Bind a listening socket, sharing the port with other listeners when
+/// `reuse_port` is set.
+pub async fn bind(hostname: &str, port: u16, reuse_port: bool) -> io::Result<TcpListener> {
+    let addr = tokio::net::lookup_host((hostname, port))
+        .await?
+        .next()
+        .ok_or_else(|| io::Error::other(format!("Could not resolve {hostname}")))?;
+    let socket = if addr.is_ipv4() {
+        TcpSocket::new_v4()?
+    } else {
+        TcpSocket::new_v6()?
+    };
+    #[cfg(not(windows))]
+    socket.set_reuseaddr(true)?;
+    #[cfg(all(unix, not(target_os = "solaris"), not(target_os = "illumos")))]
+    socket.set_reuseport(reuse_port)?;
+    #[cfg(not(all(unix, not(target_os = "solaris"), not(target_os = "illumos"))))]
+    let _ = reuse_port;
+    socket.bind(addr)?;
+    socket.listen(1024)
+}
+
+/// Accept connections until the server is shut down or the handler side is
+/// gone. With an acceptor, connections complete a TLS handshake first.
+pub async fn accept_loop(
+    listener: TcpListener,
+    tls: Option<TlsAcceptor>,
+    requests: mpsc::Sender<IncomingRequest>,
+    mut shutdown: watch::Receiver<bool>,
+) {
+    loop {
+        tokio::select! {
+            _ = shutdown.wait_for(|closed| *closed) => break,
+            _ = requests.closed() => break,
+            accepted = listener.accept() => match accepted {
+                Ok((stream, remote_addr)) => {
+                    let _ = stream.set_nodelay(true);
+                    let requests = requests.clone();
+                    match tls.clone() {
+                        Some(acceptor) => {
+                            tokio::spawn(async move {
+                                let Ok(stream) = handshake(&acceptor, stream).await else {
+                                    return;
+                                };
+                                if stream.get_ref().1.alpn_protocol() == Some(b"h2") {
+                                    http2::serve_connection(stream, remote_addr, requests).await;
+                                } else {
+                                    serve_connection(stream, Vec::new(), remote_addr, requests)
+                                        .await;
+                                }
+                            });
+                        }
+                        None => {
+                            tokio::spawn(serve_plain(stream, remote_addr, requests));
+                        }
+                    }
+                }
+                // Running out of file descriptors is transient, back off briefly
+                Err(_) => tokio::time::sleep(Duration::from_millis(10)).await,
+            },
+        }
+    }
+}
+
+/// Serve a plain text connection, telling HTTP/2 with prior knowledge apart
+/// from HTTP/1.1 by whether it opens with the HTTP/2 preface.
+async fn serve_plain(
+    mut stream: TcpStream,
+    remote_addr: SocketAddr,
+    requests: mpsc::Sender<IncomingRequest>,
+) {
+    let mut buffered = Vec::new();
+    let http2 = loop {
+        let compared = buffered.len().min(PREFACE.len());
+        if buffered[..compared] != PREFACE[..compared] {
+            break false;
+        }
+        if compared == PREFACE.len() {
+            break true;
+        }
+        match tokio::time::timeout(HEAD_TIMEOUT, stream.read_buf(&mut buffered)).await {
+            Ok(Ok(read)) if read > 0 => {}
+            _ => return,
+        }
+    };
+
+    if http2 {
+        http2::serve_connection(Rewind::new(buffered, stream), remote_addr, requests).await;
+    } else {
+        serve_connection(stream, buffered, remote_addr, requests).await;
+    }
+}
+
+/// Serve HTTP/1.1 on a connection, `buffered` holding bytes already read from
+/// it.
+async fn serve_connection<S: AsyncRead + AsyncWrite + Send + 'static>(
+    stream: S,
+    buffered: Vec<u8>,
+    remote_addr: SocketAddr,
+    requests: mpsc::Sender<IncomingRequest>,
+) {
+    let (read, mut writer) = tokio::io::split(stream);
+    let mut reader = Reader {
+        stream: read,
+        buffer: buffered,
+    };
+
+    let mut first = true;
+    loop {
+        let head = tokio::select! {
+            head = reader.read_head(first) => head,
+            _ = requests.closed() => break,
+        };
+        first = false;
+
+        let parsed = match head {
+            Ok(Some(head)) => head.body_kind().map(|kind| (head, kind)),
+            Ok(None) => break,
+            Err(error) => Err(error),
+        };
+        let (head, kind) = match parsed {
+            Ok(parsed) => parsed,
+            Err(error) => {
+                if let Some(status) = error.status() {
+                    let _ = write_error(&mut writer, status).await;
+                }
+                break;
+            }
+        };
+        let keep_alive = head.keep_alive();
+
+        let (body_sender, body) = match kind {
+            BodyKind::Empty => (None, None),
+            _ => {
+                let (sender, receiver) = mpsc::channel(BODY_CHANNEL_CAPACITY);
+                (Some(sender), Some(receiver))
+            }
+        };
+        if body.is_some()
+            && head.minor_version >= 1
+            && head.has_token("expect", "100-continue")
+            && writer
+                .write_all(b"HTTP/1.1 100 Continue\r\n\r\n")
+                .await
+                .is_err()
+        {
+            break;
+        }
+
+        let (response_sender, response) = oneshot::channel();
+        let request = IncomingRequest {
+            method: head.method.clone(),
+            target: head.target.clone(),
+            headers: head.headers.clone(),
+            remote_addr,
+            body,
+            response: response_sender,
+        };
+        if requests.send(request).await.is_err() {
+            break;
+        }
+
+        let sink = BodySink {
+            sender: body_sender,
+            drained: 0,
+        };
+        let (body_result, response_result) = tokio::join!(
+            reader.read_body(kind, sink),
+            write_response(&mut writer, response, &head, keep_alive),
+        );
+        match (body_result, response_result) {
+            (Ok(()), Ok(Outcome::KeepAlive)) => continue,
+            (Ok(()), Ok(Outcome::Upgrade(upgrade))) => {
+                // The WebSocket owns the connection from here on
+                let _ = upgrade.send(Upgraded {
+                    read: Box::new(reader.stream),
+                    write: Box::new(writer),
+                    buffered: reader.buffer,
+                });
+                return;
+            }
+            _ => break,
+        }
+    }
+
+    let _ = writer.shutdown().await;
+}
+
+/// Write the handler's response, returning whether the connection can be
+/// reused for another request or was upgraded.
+async fn write_response<W: AsyncWrite + Unpin>(
+    writer: &mut W,
+    response: oneshot::Receiver<OutgoingResponse>,
+    head: &RequestHead,
+    keep_alive: bool,
+) -> io::Result<Outcome> {
+    let Ok(mut response) = response.await else {
+        // The handler went away without answering
+        write_error(writer, 500).await?;
+        return Ok(Outcome::Close);
+    };
+
+    if response.status == 101
+        && head.minor_version >= 1
+        && let Some(upgrade) = response.upgrade.take()
+    {
+        write_upgrade(writer, &response.headers).await?;
+        return Ok(Outcome::Upgrade(upgrade));
+    }
+
+    let is_head = head.method.eq_ignore_ascii_case("HEAD");
+    let bodyless = response.status < 200 || response.status == 204 || response.status == 304;
+    let mut keep_alive = keep_alive
+        && !response.headers.iter().any(|(name, value)| {
+            name.eq_ignore_ascii_case("connection")
+                && value
+                    .split(',')
+                    .any(|token| token.trim().eq_ignore_ascii_case("close"))
+        });
+
+    let declared_length = response
+        .headers
+        .iter()
+        .find(|(name, _)| name.eq_ignore_ascii_case("content-length"))
+        .and_then(|(_, value)| value.trim().parse::<u64>().ok());
+
+    let mut out = Vec::with_capacity(256);
+    out.extend_from_slice(
+        format!(
+            "HTTP/1.1 {} {}\r\n",
+            response.status,
+            reason_phrase(response.status)
+        )
+        .as_bytes(),
+    );
+    for (name, value) in &response.headers {
+        if [
+            "connection",
+            "content-length",
+            "transfer-encoding",
+            "keep-alive",
+        ]
+        .iter()
+        .any(|framing| name.eq_ignore_ascii_case(framing))
+        {
+            continue;
+        }
+        push_header(&mut out, name, value);
+    }
+    if !response
+        .headers
+        .iter()
+        .any(|(name, _)| name.eq_ignore_ascii_case("date"))
+    {
+        push_header(&mut out, "date", &http_date());
+    }
+
+    let chunked = match &response.body {
+        _ if bodyless => false,
+        ResponseBody::Full(bytes) => {
+            push_header(&mut out, "content-length", &bytes.len().to_string());
+            false
+        }
+        ResponseBody::Stream(_) => match declared_length {
+            Some(length) => {
+                push_header(&mut out, "content-length", &length.to_string());
+                false
+            }
+            None if head.minor_version >= 1 => {
+                push_header(&mut out, "transfer-encoding", "chunked");
+                true
+            }
+            // HTTP/1.0 clients learn the body ended when the connection closes
+            None => {
+                keep_alive = false;
+                false
+            }
+        },
+    };
+
+    if !keep_alive {
+        push_header(&mut out, "connection", "close");
+    } else if head.minor_version == 0 {
+        push_header(&mut out, "connection", "keep-alive");
+    }
+    out.extend_from_slice(b"\r\n");
+
+    let write_body = !is_head && !bodyless;
+    match response.body {
+        ResponseBody::Full(bytes) => {
+            if write_body {
+                out.extend_from_slice(&bytes);
+            }
+            writer.write_all(&out).await?;
+        }
+        ResponseBody::Stream(mut chunks) => {
+            writer.write_all(&out).await?;
+            let mut written = 0u64;
+            loop {
+                match chunks.recv().await {
+                    Some(BodyChunk::Data(data)) if data.is_empty() => {}
+                    Some(BodyChunk::Data(data)) => {
+                        if !write_body {
+                            continue;
+                        }
+                        written += data.len() as u64;
+                        if declared_length.is_some_and(|length| written > length) {
+                            return Err(io::Error::other(
+                                "response body is longer than its content-length",
+                            ));
+                        }
+                        if chunked {
+                            let mut frame = Vec::with_capacity(data.len() + 16);
+                            frame.extend_from_slice(format!("{:x}\r\n", data.len()).as_bytes());
+                            frame.extend_from_slice(&data);
+                            frame.extend_from_slice(b"\r\n");
+                            writer.write_all(&frame).await?;
+                        } else {
+                            writer.write_all(&data).await?;
+                        }
+                    }
+                    Some(BodyChunk::End) => break,
+                    None => return Err(io::Error::other("response body was aborted")),
+                }
+            }
+            if write_body && declared_length.is_some_and(|length| written < length) {
+                return Err(io::Error::other(
+                    "response body is shorter than its content-length",
+                ));
+            }
+            if chunked && write_body {
+                writer.write_all(b"0\r\n\r\n").await?;
+            }
+        }
+    }
+    writer.flush().await?;
+
+    Ok(if keep_alive {
+        Outcome::KeepAlive
+    } else {
+        Outcome::Close
+    })
+}
+
+/// Write a `101 Switching Protocols` head. Unlike other responses it keeps
+/// the handler's `Connection` and `Upgrade` headers and has no body framing.
+async fn write_upgrade<W: AsyncWrite + Unpin>(
+    writer: &mut W,
+    headers: &[(String, String)],
+) -> io::Result<()> {
+    let mut out = Vec::with_capacity(256);
+    out.extend_from_slice(b"HTTP/1.1 101 Switching Protocols\r\n");
+    for (name, value) in headers {
+        if ["content-length", "transfer-encoding", "keep-alive"]
+            .iter()
+            .any(|framing| name.eq_ignore_ascii_case(framing))
+        {
+            continue;
+        }
+        push_header(&mut out, name, value);
+    }
+    out.extend_from_slice(b"\r\n");
+    writer.write_all(&out).await?;
+    writer.flush().await
+}
+
+async fn write_error<W: AsyncWrite + Unpin>(writer: &mut W, status: u16) -> io::Result<()> {
+    let response = format!(
+        "HTTP/1.1 {status} {}\r\ncontent-length: 0\r\nconnection: close\r\n\r\n",
+        reason_phrase(status)
+    );
+    writer.write_all(response.as_bytes()).await?;
+    writer.flush().await
+}
+
+/// The current time as an HTTP date, for the `Date` header.
+pub(super) fn http_date() -> String {
+    chrono::Utc::now()
+        .format("%a, %d %b %Y %H:%M:%S GMT")
+        .to_string()
+}
+
+fn push_header(out: &mut Vec<u8>, name: &str, value: &str) {
+    out.extend_from_slice(name.as_bytes());
+    out.extend_from_slice(b": ");
+    out.extend_from_slice(value.as_bytes());
+    out.extend_from_slice(b"\r\n");
+}
+
+fn reason_phrase(status: u16) -> &'static str {
+    match status {
+        100 => "Continue",
+        101 => "Switching Protocols",
+        200 => "OK",
+        201 => "Created",
+        202 => "Accepted",
+        204 => "No Content",
+        206 => "Partial Content",
+        301 => "Moved Permanently",
+        302 => "Found",
+        303 => "See Other",
+        304 => "Not Modified",
+        307 => "Temporary Redirect",
+        308 => "Permanent Redirect",
+        400 => "Bad Request",
+        401 => "Unauthorized",
+        403 => "Forbidden",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        408 => "Request Timeout",
+        409 => "Conflict",
+        410 => "Gone",
+        411 => "Length Required",
+        413 => "Content Too Large",
+        415 => "Unsupported Media Type",
+        422 => "Unprocessable Content",
+        429 => "Too Many Requests",
+        431 => "Request Header Fields Too Large",
+        500 => "Internal Server Error",
+        501 => "Not Implemented",
+        502 => "Bad Gateway",
+        503 => "Service Unavailable",
+        504 => "Gateway Timeout",
+        505 => "HTTP Version Not Supported",
+        _ => "",
+    }
+}
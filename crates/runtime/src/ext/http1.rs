@@ -0,0 +1,142 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! HTTP/1.1 message framing shared by the `fetch` client and
+//! `Andromeda.serve`.
+//!
+//! Each side parses its own message heads, while bodies delimited by a length,
+//! by chunked transfer coding or by the end of the connection are read here
+//! and handed to a [`ChunkSink`] as they arrive.
+
+use std::io;
+
+use tokio::io::{AsyncRead, AsyncReadExt};
+
+/// Largest start line plus headers accepted, in bytes.
+pub(crate) const MAX_HEAD_SIZE: usize = 64 * 1024;
+/// Largest number of headers accepted.
+pub(crate) const MAX_HEADERS: usize = 100;
+/// Largest chunk-size or trailer line accepted in a chunked body, in bytes.
+const MAX_LINE_SIZE: usize = 8 * 1024;
+/// Largest trailer section accepted after a chunked body, in bytes.
+const MAX_TRAILERS_SIZE: usize = 64 * 1024;
+const READ_BUFFER_SIZE: usize = 16 * 1024;
+
+/// Receives the bytes of a message body as they are read.
+pub(crate) trait ChunkSink {
+    async fn data(&mut self, chunk: Vec<u8>) -> io::Result<()>;
+}
+
+/// The read half of a connection together with the bytes read ahead of the
+/// current message.
+pub(crate) struct Reader<R> {
+    pub(crate) stream: R,
+    pub(crate) buffer: Vec<u8>,
+}
+
+impl<R: AsyncRead + Unpin> Reader<R> {
+    /// Read more bytes into the buffer, returning how many were read.
+    pub(crate) async fn fill(&mut self) -> io::Result<usize> {
+        let start = self.buffer.len();
+        self.buffer.resize(start + READ_BUFFER_SIZE, 0);
+        let result = self.stream.read(&mut self.buffer[start..]).await;
+        self.buffer.truncate(start + *result.as_ref().unwrap_or(&0));
+        result
+    }
+
+    /// Read a CRLF-terminated line of a chunked body without its terminator.
+    async fn read_line(&mut self) -> io::Result<Vec<u8>> {
+        loop {
+            if let Some(end) = self.buffer.windows(2).position(|w| w == b"\r\n") {
+                let line = self.buffer[..end].to_vec();
+                self.buffer.drain(..end + 2);
+                return Ok(line);
+            }
+            if self.buffer.len() > MAX_LINE_SIZE {
+                return Err(invalid_data("chunk line is too long"));
+            }
+            if self.fill().await? == 0 {
+                return Err(unexpected_eof());
+            }
+        }
+    }
+
+    /// Read exactly `length` body bytes into `sink`.
+    pub(crate) async fn read_exact_into(
+        &mut self,
+        mut length: u64,
+        sink: &mut impl ChunkSink,
+    ) -> io::Result<()> {
+        while length > 0 {
+            if self.buffer.is_empty() && self.fill().await? == 0 {
+                return Err(unexpected_eof());
+            }
+            let take = self
+                .buffer
+                .len()
+                .min(length.try_into().unwrap_or(usize::MAX));
+            let chunk: Vec<u8> = self.buffer.drain(..take).collect();
+            length -= take as u64;
+            sink.data(chunk).await?;
+        }
+        Ok(())
+    }
+
+    /// Read a body with chunked transfer coding into `sink`.
+    pub(crate) async fn read_chunked(&mut self, sink: &mut impl ChunkSink) -> io::Result<()> {
+        loop {
+            let line = self.read_line().await?;
+            let size = line.split(|b| *b == b';').next().unwrap_or_default();
+            let size = std::str::from_utf8(size)
+                .ok()
+                .map(str::trim)
+                .filter(|size| !size.is_empty() && size.bytes().all(|b| b.is_ascii_hexdigit()))
+                .and_then(|size| u64::from_str_radix(size, 16).ok())
+                .ok_or_else(|| invalid_data("invalid chunk size"))?;
+
+            if size == 0 {
+                // Trailers are read and discarded
+                let mut trailers_size = 0;
+                loop {
+                    let line = self.read_line().await?;
+                    if line.is_empty() {
+                        return Ok(());
+                    }
+                    trailers_size += line.len() + 2;
+                    if trailers_size > MAX_TRAILERS_SIZE {
+                        return Err(invalid_data("chunked trailers are too large"));
+                    }
+                }
+            }
+
+            self.read_exact_into(size, sink).await?;
+            if !self.read_line().await?.is_empty() {
+                return Err(invalid_data("chunk is longer than its size"));
+            }
+        }
+    }
+
+    /// Read a body that ends when the peer closes the connection into `sink`.
+    pub(crate) async fn read_to_end(&mut self, sink: &mut impl ChunkSink) -> io::Result<()> {
+        loop {
+            if !self.buffer.is_empty() {
+                sink.data(std::mem::take(&mut self.buffer)).await?;
+            }
+            if self.fill().await? == 0 {
+                return Ok(());
+            }
+        }
+    }
+}
+
+pub(crate) fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
+
+fn unexpected_eof() -> io::Error {
+    io::Error::new(
+        io::ErrorKind::UnexpectedEof,
+        "connection closed before the body was complete",
+    )
+}
@@ -7,6 +7,7 @@ mod broadcast_channel;
 mod cache_storage;
 #[cfg(feature = "canvas")]
 mod canvas;
+mod command;
 mod console;
 pub mod cron;
 #[cfg(feature = "crypto")]
@@ -16,14 +17,18 @@ mod ffi;
 mod file;
 #[cfg(not(feature = "virtualfs"))]
 mod fs;
+#[cfg(feature = "serve")]
 mod http;
+mod http1;
 #[cfg(feature = "storage")]
 mod local_storage;
 mod net;
+mod permissions;
 mod process;
 #[cfg(feature = "storage")]
 mod sqlite;
 mod streams;
+mod testing;
 mod time;
 pub mod tls;
 mod url;
@@ -32,12 +37,15 @@ mod virtualfs;
 mod web;
 mod web_locks;
 mod webidl;
+mod websocket;
+mod worker;
 
 pub use broadcast_channel::*;
 #[cfg(feature = "storage")]
 pub use cache_storage::*;
 #[cfg(feature = "canvas")]
 pub use canvas::*;
+pub use command::*;
 pub use console::*;
 pub use cron::*;
 #[cfg(feature = "crypto")]
@@ -47,14 +55,17 @@ pub use ffi::*;
 pub use file::*;
 #[cfg(not(feature = "virtualfs"))]
 pub use fs::*;
+#[cfg(feature = "serve")]
 pub use http::*;
 #[cfg(feature = "storage")]
 pub use local_storage::*;
 pub use net::*;
+pub use permissions::*;
 pub use process::*;
 #[cfg(feature = "storage")]
 pub use sqlite::*;
 pub use streams::*;
+pub use testing::*;
 pub use time::*;
 pub use tls::*;
 pub use url::*;
@@ -63,3 +74,5 @@ pub use virtualfs::*;
 pub use web::*;
 pub use web_locks::*;
 pub use webidl::*;
+pub use websocket::*;
+pub use worker::*;
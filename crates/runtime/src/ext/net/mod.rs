@@ -38,6 +38,7 @@ use nova_vm::{
 
 use andromeda_core::{
     Extension, ExtensionOp, HostData, MacroTask, ResourceTable, Rid, SyncResourceTable,
+    check_permission,
 };
 
 use crate::RuntimeMacroTask;
@@ -279,6 +280,14 @@ impl NetExt {
             }
         };
 
+        if let Err(error) =
+            check_permission::<RuntimeMacroTask>(agent, |p| p.check_net(&host, Some(port)))
+        {
+            return Err(error
+                .into_js_error::<RuntimeMacroTask>(agent, gc.nogc())
+                .unbind());
+        }
+
         let promise_capability = PromiseCapability::new(agent, gc.nogc());
         let root_value = Global::new(agent, promise_capability.promise().into_value().unbind());
         let host_data = agent.get_host_data();
@@ -355,6 +364,14 @@ impl NetExt {
             }
         };
 
+        if let Err(error) =
+            check_permission::<RuntimeMacroTask>(agent, |p| p.check_net(&host, Some(port)))
+        {
+            return Err(error
+                .into_js_error::<RuntimeMacroTask>(agent, gc.nogc())
+                .unbind());
+        }
+
         // Use block_in_place for synchronous TCP listen operation
         let addr = NetAddr::new(host, port);
         let result = tokio::task::block_in_place(|| {
@@ -793,6 +810,14 @@ impl NetExt {
             }
         };
 
+        if let Err(error) =
+            check_permission::<RuntimeMacroTask>(agent, |p| p.check_net(&host, Some(port)))
+        {
+            return Err(error
+                .into_js_error::<RuntimeMacroTask>(agent, gc.nogc())
+                .unbind());
+        }
+
         let addr = NetAddr::new(host, port);
         let result = tokio::task::block_in_place(|| {
             tokio::runtime::Handle::current().block_on(async {
@@ -875,6 +900,14 @@ impl NetExt {
             }
         };
 
+        if let Err(error) =
+            check_permission::<RuntimeMacroTask>(agent, |p| p.check_net_addr(&target_addr))
+        {
+            return Err(error
+                .into_js_error::<RuntimeMacroTask>(agent, gc.nogc())
+                .unbind());
+        }
+
         let promise_capability = PromiseCapability::new(agent, gc.nogc());
         let root_value = Global::new(agent, promise_capability.promise().into_value().unbind());
 
@@ -1079,6 +1112,14 @@ impl NetExt {
             .expect("String is not valid UTF-8")
             .to_string();
 
+        if let Err(error) =
+            check_permission::<RuntimeMacroTask>(agent, |p| p.check_net(&hostname, None))
+        {
+            return Err(error
+                .into_js_error::<RuntimeMacroTask>(agent, gc.nogc())
+                .unbind());
+        }
+
         let promise_capability = PromiseCapability::new(agent, gc.nogc());
         let root_value = Global::new(agent, promise_capability.promise().into_value().unbind());
         let host_data = agent.get_host_data();
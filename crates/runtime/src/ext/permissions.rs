@@ -0,0 +1,164 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use andromeda_core::{
+    Extension, ExtensionOp, HostData, PermissionKind, PermissionState, Permissions,
+};
+use nova_vm::{
+    ecmascript::{
+        builtins::ArgumentsList,
+        execution::{Agent, JsResult, agent::ExceptionType},
+        types::{Object, Value},
+    },
+    engine::{
+        Global,
+        context::{Bindable, GcScope},
+    },
+};
+
+use crate::RuntimeMacroTask;
+
+/// Permissions extension for Andromeda.
+/// This extension backs the `Andromeda.permissions` API.
+#[derive(Default)]
+pub struct PermissionsExt;
+
+#[hotpath::measure_all]
+impl PermissionsExt {
+    pub fn new_extension() -> Extension {
+        Extension {
+            name: "permissions",
+            ops: vec![
+                ExtensionOp::new(
+                    "internal_permissions_query",
+                    Self::internal_permissions_query,
+                    2,
+                    false,
+                ),
+                ExtensionOp::new(
+                    "internal_permissions_request",
+                    Self::internal_permissions_request,
+                    2,
+                    false,
+                ),
+                ExtensionOp::new(
+                    "internal_permissions_revoke",
+                    Self::internal_permissions_revoke,
+                    2,
+                    false,
+                ),
+                ExtensionOp::new(
+                    "internal_permissions_set_error_prototype",
+                    Self::internal_permissions_set_error_prototype,
+                    1,
+                    false,
+                ),
+            ],
+            storage: None,
+            files: vec![],
+        }
+    }
+
+    /// Query the state of a permission without prompting.
+    fn internal_permissions_query<'gc>(
+        agent: &mut Agent,
+        _this: Value,
+        args: ArgumentsList,
+        gc: GcScope<'gc, '_>,
+    ) -> JsResult<'gc, Value<'gc>> {
+        Self::apply(agent, args, gc, |permissions, kind, target| {
+            permissions.query(kind, target)
+        })
+    }
+
+    /// Request a permission, prompting on the terminal if needed.
+    fn internal_permissions_request<'gc>(
+        agent: &mut Agent,
+        _this: Value,
+        args: ArgumentsList,
+        gc: GcScope<'gc, '_>,
+    ) -> JsResult<'gc, Value<'gc>> {
+        Self::apply(agent, args, gc, |permissions, kind, target| {
+            permissions.request(kind, target)
+        })
+    }
+
+    /// Revoke a permission for the rest of the session.
+    fn internal_permissions_revoke<'gc>(
+        agent: &mut Agent,
+        _this: Value,
+        args: ArgumentsList,
+        gc: GcScope<'gc, '_>,
+    ) -> JsResult<'gc, Value<'gc>> {
+        Self::apply(agent, args, gc, |permissions, kind, target| {
+            permissions.revoke(kind, target)
+        })
+    }
+
+    /// Register the prototype given to the errors thrown by denied permission checks.
+    fn internal_permissions_set_error_prototype<'gc>(
+        agent: &mut Agent,
+        _this: Value,
+        args: ArgumentsList,
+        gc: GcScope<'gc, '_>,
+    ) -> JsResult<'gc, Value<'gc>> {
+        let Ok(prototype) = Object::try_from(args.get(0)) else {
+            return Err(agent
+                .throw_exception_with_static_message(
+                    ExceptionType::TypeError,
+                    "Error prototype must be an object",
+                    gc.nogc(),
+                )
+                .unbind());
+        };
+        let prototype = Global::new(agent, prototype.unbind());
+        let host_data: &HostData<RuntimeMacroTask> = agent.get_host_data().downcast_ref().unwrap();
+        if let Some(previous) = host_data
+            .permission_denied_prototype
+            .replace(Some(prototype))
+        {
+            previous.take(agent);
+        }
+        Ok(Value::Undefined)
+    }
+
+    /// Parse the `(name, target?)` arguments and run the given operation on the
+    /// permissions of the runtime, returning the resulting state name.
+    fn apply<'gc>(
+        agent: &mut Agent,
+        args: ArgumentsList,
+        mut gc: GcScope<'gc, '_>,
+        operation: impl FnOnce(&mut Permissions, PermissionKind, Option<&str>) -> PermissionState,
+    ) -> JsResult<'gc, Value<'gc>> {
+        let name = args.get(0).to_string(agent, gc.reborrow()).unbind()?;
+        let target = match args.get(1) {
+            Value::Undefined | Value::Null => None,
+            value => Some(
+                value
+                    .to_string(agent, gc.reborrow())
+                    .unbind()?
+                    .as_str(agent)
+                    .expect("String is not valid UTF-8")
+                    .to_string(),
+            ),
+        };
+
+        let name = name.as_str(agent).expect("String is not valid UTF-8");
+        let Some(kind) = PermissionKind::from_name(name) else {
+            let message = format!("Unknown permission name: \"{name}\"");
+            return Err(agent
+                .throw_exception(ExceptionType::TypeError, message, gc.nogc())
+                .unbind());
+        };
+
+        let host_data: &HostData<RuntimeMacroTask> = agent.get_host_data().downcast_ref().unwrap();
+        let state = operation(
+            &mut host_data.permissions.borrow_mut(),
+            kind,
+            target.as_deref(),
+        );
+
+        Ok(Value::from_string(agent, state.name().to_string(), gc.nogc()).unbind())
+    }
+}
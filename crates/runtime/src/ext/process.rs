@@ -2,7 +2,10 @@
 // License, v. 2.0. If a copy of the MPL was not distributed with this
 // file, You can obtain one at https://mozilla.org/MPL/2.0/.
 
-use andromeda_core::{AndromedaError, ErrorReporter, Extension, ExtensionOp, HostData, OpsStorage};
+use andromeda_core::{
+    AndromedaError, ErrorReporter, Extension, ExtensionOp, HostData, OpsStorage, ProgramArgs,
+    check_permission,
+};
 use nova_vm::{
     ecmascript::{
         builtins::{ArgumentsList, Array},
@@ -78,7 +81,12 @@ impl ProcessExt {
         _: ArgumentsList,
         gc: GcScope<'gc, '_>,
     ) -> JsResult<'gc, Value<'gc>> {
-        let args = env::args().skip(1).collect::<Vec<String>>();
+        let host_data = agent.get_host_data();
+        let host_data: &HostData<RuntimeMacroTask> = host_data.downcast_ref().unwrap();
+        let args = match host_data.storage.borrow().get::<ProgramArgs>() {
+            Some(ProgramArgs(args)) => args.clone(),
+            None => env::args().skip(1).collect::<Vec<String>>(),
+        };
         let args = args.iter().map(|s| s.as_str()).collect::<Vec<&str>>();
         let args = args
             .iter()
@@ -101,6 +109,11 @@ impl ProcessExt {
         let key = args.get(0);
         let key = key.to_string(agent, gc.reborrow()).unbind()?;
         let key_str = key.as_str(agent).expect("String is not valid UTF-8");
+        if let Err(error) = check_permission::<RuntimeMacroTask>(agent, |p| p.check_env(key_str)) {
+            return Err(error
+                .into_js_error::<RuntimeMacroTask>(agent, gc.nogc())
+                .unbind());
+        }
 
         match env::var(key_str) {
             Ok(value) => {
@@ -140,9 +153,16 @@ impl ProcessExt {
         let value = args.get(1);
         let value = value.to_string(agent, gc.reborrow()).unbind().unbind()?;
 
+        let key_str = key.as_str(agent).expect("String is not valid UTF-8");
+        if let Err(error) = check_permission::<RuntimeMacroTask>(agent, |p| p.check_env(key_str)) {
+            return Err(error
+                .into_js_error::<RuntimeMacroTask>(agent, gc.nogc())
+                .unbind());
+        }
+
         unsafe {
             env::set_var(
-                key.as_str(agent).expect("String is not valid UTF-8"),
+                key_str,
                 value.as_str(agent).expect("String is not valid UTF-8"),
             );
         }
@@ -159,8 +179,15 @@ impl ProcessExt {
         let key = args.get(0);
         let key = key.to_string(agent, gc.reborrow()).unbind()?;
 
+        let key_str = key.as_str(agent).expect("String is not valid UTF-8");
+        if let Err(error) = check_permission::<RuntimeMacroTask>(agent, |p| p.check_env(key_str)) {
+            return Err(error
+                .into_js_error::<RuntimeMacroTask>(agent, gc.nogc())
+                .unbind());
+        }
+
         unsafe {
-            env::remove_var(key.as_str(agent).expect("String is not valid UTF-8"));
+            env::remove_var(key_str);
         }
 
         Ok(Value::Undefined)
@@ -171,6 +198,12 @@ impl ProcessExt {
         _: ArgumentsList,
         gc: GcScope<'gc, '_>,
     ) -> JsResult<'gc, Value<'gc>> {
+        if let Err(error) = check_permission::<RuntimeMacroTask>(agent, |p| p.check_env_all()) {
+            return Err(error
+                .into_js_error::<RuntimeMacroTask>(agent, gc.nogc())
+                .unbind());
+        }
+
         let keys = env::vars()
             .map(|(k, _)| k)
             .map(|s| {
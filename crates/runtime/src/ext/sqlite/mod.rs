@@ -3,10 +3,11 @@
 // file, You can obtain one at https://mozilla.org/MPL/2.0/.
 
 use std::collections::HashMap;
+use std::path::Path;
 use std::sync::atomic::{AtomicU32, Ordering};
 use std::sync::{Arc, Mutex};
 
-use andromeda_core::{Extension, ExtensionOp, HostData};
+use andromeda_core::{Extension, ExtensionOp, HostData, check_permission};
 use nova_vm::{
     ecmascript::{
         builtins::{ArgumentsList, Array},
@@ -301,6 +302,18 @@ impl SqliteExt {
             }
         };
 
+        // In-memory databases don't touch the file system
+        if filename != ":memory:"
+            && let Err(error) = check_permission::<crate::RuntimeMacroTask>(agent, |p| {
+                let path = Path::new(&filename);
+                p.check_read(path).and_then(|_| p.check_write(path))
+            })
+        {
+            return Err(error
+                .into_js_error::<crate::RuntimeMacroTask>(agent, gc.nogc())
+                .unbind());
+        }
+
         let flags = OpenFlags::SQLITE_OPEN_READ_WRITE | OpenFlags::SQLITE_OPEN_CREATE;
 
         let connection = match Connection::open_with_flags(&filename, flags) {
@@ -1235,6 +1248,18 @@ impl SqliteExt {
             }
         };
 
+        // In-memory databases don't touch the file system
+        if filename != ":memory:"
+            && let Err(error) = check_permission::<crate::RuntimeMacroTask>(agent, |p| {
+                let path = Path::new(&filename);
+                p.check_read(path).and_then(|_| p.check_write(path))
+            })
+        {
+            return Err(error
+                .into_js_error::<crate::RuntimeMacroTask>(agent, gc.nogc())
+                .unbind());
+        }
+
         // Parse options (3rd argument)
         let read_only = false;
         if args.len() > 2
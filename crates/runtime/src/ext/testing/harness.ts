@@ -0,0 +1,19 @@
+// deno-lint-ignore-file no-explicit-any
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+// Entry point of `andromeda test`, loaded as a builtin of test runtimes only.
+// Imports the module under test, then runs the tests it registered.
+
+(() => {
+  const internals = (globalThis as any).__andromeda_test_internals;
+  const options = JSON.parse(__andromeda__.internal_test_options());
+
+  import(options.main)
+    .then(() => internals.run(options))
+    .catch((error: any) => {
+      internals.report({ type: "error", error: internals.formatError(error) });
+    })
+    .finally(() => internals.report({ type: "end" }));
+})();
@@ -0,0 +1,166 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use std::sync::atomic::Ordering;
+
+use andromeda_core::{Extension, ExtensionOp, HostData};
+use nova_vm::{
+    ecmascript::{
+        builtins::ArgumentsList,
+        execution::{Agent, JsResult, agent::ExceptionType},
+        types::Value,
+    },
+    engine::context::{Bindable, GcScope},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::RuntimeMacroTask;
+
+/// Options of a test run, read by the test harness.
+#[derive(Debug, Clone, Serialize)]
+pub struct TestRunOptions {
+    /// Absolute path of the module under test.
+    pub main: String,
+    /// Only run the tests whose name contains this string, or matches it when
+    /// written as `/regex/`.
+    pub filter: Option<String>,
+    /// Default timeout of every test, in milliseconds.
+    pub timeout: Option<u64>,
+}
+
+/// Outcome of a test or a test step.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TestOutcome {
+    Ok,
+    Failed,
+    Ignored,
+}
+
+/// Progress of a test run, reported by the test harness as tests run.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum TestEvent {
+    /// The module was loaded and `total` of its tests are about to run.
+    Plan {
+        total: usize,
+        filtered: usize,
+        only: bool,
+    },
+    /// A test, or a step when `depth` is above zero, started.
+    Wait { name: String, depth: usize },
+    /// A test or a step finished after `duration` milliseconds.
+    Result {
+        name: String,
+        depth: usize,
+        outcome: TestOutcome,
+        duration: f64,
+        error: Option<String>,
+    },
+    /// The module under test failed to load.
+    Error { error: String },
+    /// Every test ran.
+    End,
+}
+
+/// State of a test run, only present in the storage of a test runtime.
+pub struct TestContext {
+    pub options: TestRunOptions,
+    pub reporter: Box<dyn FnMut(TestEvent)>,
+}
+
+/// Test extension for Andromeda.
+/// This extension provides `Andromeda.test()` and the ops `andromeda test` uses
+/// to run the registered tests.
+#[derive(Default)]
+pub struct TestExt;
+
+#[hotpath::measure_all]
+impl TestExt {
+    pub fn new_extension() -> Extension {
+        Extension {
+            name: "test",
+            ops: vec![
+                ExtensionOp::new(
+                    "internal_test_options",
+                    Self::internal_test_options,
+                    0,
+                    false,
+                ),
+                ExtensionOp::new("internal_test_report", Self::internal_test_report, 1, false),
+            ],
+            storage: None,
+            files: vec![include_str!("./mod.ts")],
+        }
+    }
+
+    /// Builtin script running the tests of the module under test, to be added to
+    /// the builtins of a runtime whose storage holds a [TestContext].
+    pub fn harness() -> &'static str {
+        include_str!("./harness.ts")
+    }
+
+    /// The [TestRunOptions] of this run as JSON.
+    fn internal_test_options<'gc>(
+        agent: &mut Agent,
+        _this: Value,
+        _args: ArgumentsList,
+        gc: GcScope<'gc, '_>,
+    ) -> JsResult<'gc, Value<'gc>> {
+        let host_data = agent.get_host_data();
+        let host_data: &HostData<RuntimeMacroTask> = host_data.downcast_ref().unwrap();
+        let options = host_data
+            .storage
+            .borrow()
+            .get::<TestContext>()
+            .map(|context| serde_json::to_string(&context.options).unwrap());
+
+        match options {
+            Some(options) => Ok(Value::from_string(agent, options, gc.nogc()).unbind()),
+            None => Err(agent
+                .throw_exception_with_static_message(
+                    ExceptionType::Error,
+                    "Tests can only be run by `andromeda test`",
+                    gc.nogc(),
+                )
+                .unbind()),
+        }
+    }
+
+    /// Hand a [TestEvent] to the reporter, stopping the event loop once every test ran.
+    fn internal_test_report<'gc>(
+        agent: &mut Agent,
+        _this: Value,
+        args: ArgumentsList,
+        mut gc: GcScope<'gc, '_>,
+    ) -> JsResult<'gc, Value<'gc>> {
+        let event_binding = args.get(0).to_string(agent, gc.reborrow()).unbind()?;
+        let event = event_binding
+            .as_str(agent)
+            .expect("String is not valid UTF-8")
+            .to_string();
+        let event: TestEvent = match serde_json::from_str(&event) {
+            Ok(event) => event,
+            Err(e) => {
+                let message = format!("Invalid test event: {e}");
+                return Err(agent
+                    .throw_exception(ExceptionType::TypeError, message, gc.nogc())
+                    .unbind());
+            }
+        };
+
+        let host_data = agent.get_host_data();
+        let host_data: &HostData<RuntimeMacroTask> = host_data.downcast_ref().unwrap();
+        // Timers and other pending work left behind by the tests must not keep
+        // the run alive
+        if matches!(event, TestEvent::End) {
+            host_data.terminated.store(true, Ordering::Release);
+        }
+        if let Some(context) = host_data.storage.borrow_mut().get_mut::<TestContext>() {
+            (context.reporter)(event);
+        }
+
+        Ok(Value::Undefined)
+    }
+}
@@ -0,0 +1,305 @@
+// deno-lint-ignore-file no-explicit-any
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+type TestFunction = (t: TestContext) => void | Promise<void>;
+
+interface TestDefinition {
+  name: string;
+  fn: TestFunction;
+  ignore?: boolean;
+  only?: boolean;
+  /** Fail the test if it takes longer than this many milliseconds. */
+  timeout?: number;
+}
+
+interface TestStepDefinition {
+  name: string;
+  fn: TestFunction;
+  ignore?: boolean;
+  timeout?: number;
+}
+
+interface TestRunOptions {
+  main: string;
+  filter: string | null;
+  timeout: number | null;
+}
+
+type TestOutcome = "ok" | "failed" | "ignored";
+
+/** Tests registered by `Andromeda.test()`, only run by `andromeda test`. */
+const registeredTests: TestDefinition[] = [];
+
+function report(event: Record<string, unknown>) {
+  __andromeda__.internal_test_report(JSON.stringify(event));
+}
+
+function formatTestError(error: any): string {
+  if (error instanceof Error) {
+    const message = `${error.name}: ${error.message}`;
+    if (typeof error.stack === "string" && error.stack.length > 0) {
+      return error.stack.startsWith(message)
+        ? error.stack
+        : `${message}\n${error.stack}`;
+    }
+    return message;
+  }
+  return String(error);
+}
+
+/**
+ * Normalize the overloads accepted by `Andromeda.test()` and `t.step()`:
+ * `(name, fn)`, `(name, options, fn)`, `(fn)`, `(options, fn)` and `(definition)`.
+ */
+function normalizeDefinition<T extends { name: string; fn: TestFunction }>(
+  kind: string,
+  nameOrFnOrOptions: any,
+  optionsOrFn?: any,
+  maybeFn?: any,
+): T {
+  let definition: any;
+  if (typeof nameOrFnOrOptions === "string") {
+    if (typeof optionsOrFn === "function") {
+      definition = { name: nameOrFnOrOptions, fn: optionsOrFn };
+    } else {
+      definition = { ...optionsOrFn, name: nameOrFnOrOptions, fn: maybeFn };
+    }
+  } else if (typeof nameOrFnOrOptions === "function") {
+    definition = { name: nameOrFnOrOptions.name, fn: nameOrFnOrOptions };
+  } else if (nameOrFnOrOptions && typeof nameOrFnOrOptions === "object") {
+    definition = typeof optionsOrFn === "function"
+      ? { ...nameOrFnOrOptions, fn: optionsOrFn }
+      : { ...nameOrFnOrOptions };
+    if (!definition.name && typeof definition.fn === "function") {
+      definition.name = definition.fn.name;
+    }
+  }
+  if (!definition || typeof definition.fn !== "function") {
+    throw new TypeError(`The ${kind} function is missing`);
+  }
+  if (!definition.name) {
+    throw new TypeError(`The ${kind} name can't be empty`);
+  }
+  if (
+    definition.timeout !== undefined &&
+    !(typeof definition.timeout === "number" && definition.timeout > 0)
+  ) {
+    throw new TypeError(`The ${kind} timeout must be a positive number`);
+  }
+  return definition;
+}
+
+/**
+ * Register a test, run by `andromeda test` and ignored otherwise.
+ */
+function test(
+  nameOrFnOrOptions: string | TestFunction | TestDefinition | Omit<TestDefinition, "fn">,
+  optionsOrFn?: Omit<TestDefinition, "name" | "fn"> | TestFunction,
+  maybeFn?: TestFunction,
+): void {
+  registeredTests.push(
+    normalizeDefinition<TestDefinition>("test", nameOrFnOrOptions, optionsOrFn, maybeFn),
+  );
+}
+
+/** Register a test that is the only one run, along with other `only` tests. */
+test.only = function (
+  nameOrFnOrOptions: any,
+  optionsOrFn?: any,
+  maybeFn?: any,
+): void {
+  const definition = normalizeDefinition<TestDefinition>(
+    "test",
+    nameOrFnOrOptions,
+    optionsOrFn,
+    maybeFn,
+  );
+  registeredTests.push({ ...definition, only: true });
+};
+
+/** Register a test that is reported but not run. */
+test.ignore = function (
+  nameOrFnOrOptions: any,
+  optionsOrFn?: any,
+  maybeFn?: any,
+): void {
+  const definition = normalizeDefinition<TestDefinition>(
+    "test",
+    nameOrFnOrOptions,
+    optionsOrFn,
+    maybeFn,
+  );
+  registeredTests.push({ ...definition, ignore: true });
+};
+
+/**
+ * Call `fn`, failing if it didn't settle within `timeout` milliseconds.
+ */
+async function runWithTimeout(
+  fn: () => void | Promise<void>,
+  timeout: number | null | undefined,
+): Promise<void> {
+  if (!timeout) {
+    await fn();
+    return;
+  }
+  let timer: number | undefined;
+  const expired = new Promise<never>((_, reject) => {
+    timer = setTimeout(() => {
+      reject(new Error(`Test timed out after ${timeout}ms`));
+    }, timeout);
+  });
+  try {
+    await Promise.race([fn(), expired]);
+  } finally {
+    clearTimeout(timer);
+  }
+}
+
+/**
+ * The context passed to a test function, used to run sub-tests.
+ */
+class TestContext {
+  #depth: number;
+  #timeout: number | null;
+  #running = 0;
+  #failedSteps = 0;
+  readonly name: string;
+  readonly origin: string;
+
+  constructor(name: string, origin: string, depth: number, timeout: number | null) {
+    this.name = name;
+    this.origin = origin;
+    this.#depth = depth;
+    this.#timeout = timeout;
+  }
+
+  /**
+   * Run a sub-test. Resolves with whether it passed, failing the parent test
+   * if it didn't.
+   */
+  async step(
+    nameOrFnOrOptions: string | TestFunction | TestStepDefinition,
+    optionsOrFn?: Omit<TestStepDefinition, "name" | "fn"> | TestFunction,
+    maybeFn?: TestFunction,
+  ): Promise<boolean> {
+    const definition = normalizeDefinition<TestStepDefinition>(
+      "step",
+      nameOrFnOrOptions,
+      optionsOrFn,
+      maybeFn,
+    );
+    this.#running++;
+    try {
+      const outcome = await runTest(
+        definition,
+        this.origin,
+        this.#depth + 1,
+        definition.timeout ?? this.#timeout,
+      );
+      if (outcome === "failed") this.#failedSteps++;
+      return outcome !== "failed";
+    } finally {
+      this.#running--;
+    }
+  }
+
+  /** Check the steps were all awaited and passed once the test function returned. */
+  finish() {
+    if (this.#running > 0) {
+      throw new Error(
+        "Test finished before all of its steps completed, make sure to await t.step()",
+      );
+    }
+    if (this.#failedSteps > 0) {
+      const steps = this.#failedSteps === 1 ? "step" : "steps";
+      throw new Error(`${this.#failedSteps} test ${steps} failed`);
+    }
+  }
+}
+
+async function runTest(
+  definition: TestDefinition | TestStepDefinition,
+  origin: string,
+  depth: number,
+  timeout: number | null,
+): Promise<TestOutcome> {
+  report({ type: "wait", name: definition.name, depth });
+  if (definition.ignore) {
+    report({
+      type: "result",
+      name: definition.name,
+      depth,
+      outcome: "ignored",
+      duration: 0,
+    });
+    return "ignored";
+  }
+
+  const context = new TestContext(definition.name, origin, depth, timeout);
+  const start = performance.now();
+  let error: string | undefined;
+  try {
+    await runWithTimeout(() => definition.fn(context), timeout);
+    context.finish();
+  } catch (e) {
+    error = formatTestError(e);
+  }
+  const outcome: TestOutcome = error === undefined ? "ok" : "failed";
+  report({
+    type: "result",
+    name: definition.name,
+    depth,
+    outcome,
+    duration: performance.now() - start,
+    error,
+  });
+  return outcome;
+}
+
+function matchesFilter(name: string, filter: string | null): boolean {
+  if (!filter) return true;
+  if (filter.length > 2 && filter.startsWith("/") && filter.endsWith("/")) {
+    return new RegExp(filter.slice(1, -1)).test(name);
+  }
+  return name.includes(filter);
+}
+
+/**
+ * Run the tests registered by the module under test, reporting each result.
+ */
+async function runRegisteredTests(options: TestRunOptions): Promise<void> {
+  const matching = registeredTests.filter((definition) =>
+    matchesFilter(definition.name, options.filter)
+  );
+  const only = matching.some((definition) => definition.only);
+  const selected = only
+    ? matching.filter((definition) => definition.only)
+    : matching;
+
+  report({
+    type: "plan",
+    total: selected.length,
+    filtered: registeredTests.length - selected.length,
+    only,
+  });
+  for (const definition of selected) {
+    await runTest(
+      definition,
+      options.main,
+      0,
+      definition.timeout ?? options.timeout,
+    );
+  }
+}
+
+// @ts-ignore globalThis is not readonly
+globalThis.__andromeda_test = test;
+// @ts-ignore globalThis is not readonly
+globalThis.__andromeda_test_internals = {
+  run: runRegisteredTests,
+  report,
+  formatError: formatTestError,
+};
@@ -2,10 +2,15 @@
 // License, v. 2.0. If a copy of the MPL was not distributed with this
 // file, You can obtain one at https://mozilla.org/MPL/2.0/.
 
+mod server;
+
 use std::sync::Arc;
 
 use andromeda_core::Rid;
-use andromeda_core::{Extension, ExtensionOp, HostData, MacroTask, OpsStorage, ResourceTable};
+use andromeda_core::{
+    Extension, ExtensionOp, HostData, MacroTask, OpsStorage, ResourceTable, SyncResourceTable,
+    check_permission,
+};
 use nova_vm::{
     ecmascript::{
         builtins::ArgumentsList,
@@ -24,18 +29,47 @@ use std::sync::Arc as StdArc;
 use tokio::io::{AsyncReadExt, AsyncWriteExt};
 use tokio::net::TcpStream;
 use tokio::sync::Mutex as TokioMutex;
-use tokio_rustls::TlsConnector;
+use tokio::sync::{mpsc, watch};
 use tokio_rustls::client::TlsStream;
+use tokio_rustls::{TlsAcceptor, TlsConnector};
 
 use crate::RuntimeMacroTask;
 
+#[cfg(feature = "serve")]
+pub(crate) use server::handshake;
+pub(crate) use server::{TlsServerOptions, server_config};
+
+/// Completed handshakes queued before a listener stops accepting.
+const ACCEPT_QUEUE_CAPACITY: usize = 128;
+
+/// A connector verifying servers against the bundled web PKI roots and
+/// offering `alpn_protocols`. Shared with `wss:` WebSockets and `fetch`.
+pub(crate) fn client_connector(alpn_protocols: Vec<Vec<u8>>) -> TlsConnector {
+    let root_store =
+        rustls::RootCertStore::from_iter(webpki_roots::TLS_SERVER_ROOTS.iter().cloned());
+    let mut config = rustls::ClientConfig::builder()
+        .with_root_certificates(root_store)
+        .with_no_client_auth();
+    config.alpn_protocols = alpn_protocols;
+    TlsConnector::from(Arc::new(config))
+}
+
 #[derive(Clone)]
 pub(crate) enum TlsResource {
     Client(StdArc<TokioMutex<TlsStream<TcpStream>>>),
+    Server(StdArc<TokioMutex<tokio_rustls::server::TlsStream<TcpStream>>>),
+}
+
+#[derive(Clone)]
+pub(crate) struct TlsListenerResource {
+    accepted: StdArc<TokioMutex<mpsc::Receiver<server::AcceptedTls>>>,
+    shutdown: StdArc<watch::Sender<bool>>,
 }
 
 pub(crate) struct TlsResources {
-    pub(crate) streams: ResourceTable<TlsResource>,
+    /// Shared with the tasks that complete server handshakes
+    pub(crate) streams: SyncResourceTable<TlsResource>,
+    pub(crate) listeners: ResourceTable<TlsListenerResource>,
 }
 
 #[derive(Default)]
@@ -51,19 +85,34 @@ impl TlsExt {
                 ExtensionOp::new("internal_tls_close", Self::internal_tls_close, 1, false),
                 ExtensionOp::new("internal_tls_read", Self::internal_tls_read, 2, false),
                 ExtensionOp::new("internal_tls_write", Self::internal_tls_write, 2, false),
+                ExtensionOp::new(
+                    "internal_tls_write_bytes",
+                    Self::internal_tls_write_bytes,
+                    2,
+                    false,
+                ),
                 ExtensionOp::new(
                     "internal_tls_get_peer_certificate",
                     Self::internal_tls_get_peer_certificate,
                     1,
                     false,
                 ),
+                ExtensionOp::new("internal_tls_listen", Self::internal_tls_listen, 3, false),
+                ExtensionOp::new("internal_tls_accept", Self::internal_tls_accept, 1, false),
+                ExtensionOp::new(
+                    "internal_tls_listener_close",
+                    Self::internal_tls_listener_close,
+                    1,
+                    false,
+                ),
             ],
             storage: Some(Box::new(|storage: &mut OpsStorage| {
                 storage.insert(TlsResources {
-                    streams: ResourceTable::new(),
+                    streams: SyncResourceTable::new(),
+                    listeners: ResourceTable::new(),
                 });
             })),
-            files: vec![],
+            files: vec![include_str!("./mod.ts")],
         }
     }
 
@@ -85,6 +134,14 @@ impl TlsExt {
             .expect("String is not valid UTF-8")
             .to_string();
 
+        if let Err(error) =
+            check_permission::<RuntimeMacroTask>(agent, |p| p.check_net(&host, port.parse().ok()))
+        {
+            return Err(error
+                .into_js_error::<RuntimeMacroTask>(agent, gc.nogc())
+                .unbind());
+        }
+
         let promise_capability = nova_vm::ecmascript::builtins::promise_objects::promise_abstract_operations::promise_capability_records::PromiseCapability::new(agent, gc.nogc());
         let root_value = Global::new(agent, promise_capability.promise().into_value().unbind());
 
@@ -113,16 +170,9 @@ impl TlsExt {
             let connect_res = TcpStream::connect(&addr).await;
             match connect_res {
                 Ok(tcp_stream) => {
-                    let root_store = rustls::RootCertStore::from_iter(
-                        webpki_roots::TLS_SERVER_ROOTS.iter().cloned(),
-                    );
-
-                    let config = rustls::ClientConfig::builder()
-                        .with_root_certificates(root_store)
-                        .with_no_client_auth();
-
-                    let connector = TlsConnector::from(Arc::new(config));
-                    let tls_res = connector.connect(domain, tcp_stream).await;
+                    let tls_res = client_connector(Vec::new())
+                        .connect(domain, tcp_stream)
+                        .await;
                     match tls_res {
                         Ok(tls_stream) => {
                             macro_task_tx
@@ -246,17 +296,17 @@ impl TlsExt {
 
         let storage = host_data.storage.borrow();
         let resources: &TlsResources = storage.get().unwrap();
-        let stream_arc_opt = resources.streams.get_mut(rid).map(|r| {
-            let TlsResource::Client(ref tls_stream_arc) = *r;
-            tls_stream_arc.clone()
-        });
+        let resource = resources.streams.get(rid);
 
-        match stream_arc_opt {
-            Some(stream_arc) => {
+        match resource {
+            Some(resource) => {
                 host_data.spawn_macro_task(async move {
                     let mut buffer = vec![0u8; len];
-                    let mut guard = stream_arc.lock().await;
-                    match guard.read(&mut buffer).await {
+                    let result = match resource {
+                        TlsResource::Client(stream) => stream.lock().await.read(&mut buffer).await,
+                        TlsResource::Server(stream) => stream.lock().await.read(&mut buffer).await,
+                    };
+                    match result {
                         Ok(n) => {
                             buffer.truncate(n);
                             macro_task_tx
@@ -330,18 +380,14 @@ impl TlsExt {
 
         let storage = host_data.storage.borrow();
         let resources: &TlsResources = storage.get().unwrap();
-        let stream_arc_opt = resources.streams.get_mut(rid).map(|r| {
-            let TlsResource::Client(ref tls_stream_arc) = *r;
-            tls_stream_arc.clone()
-        });
+        let resource = resources.streams.get(rid);
         drop(storage);
 
-        match stream_arc_opt {
-            Some(stream_arc) => {
+        match resource {
+            Some(resource) => {
                 let macro_task_tx = macro_task_tx.clone();
                 host_data.spawn_macro_task(async move {
-                    let mut guard = stream_arc.lock().await;
-                    match guard.write_all(data_str.as_bytes()).await {
+                    match Self::write_all(resource, data_str.as_bytes()).await {
                         Ok(_) => {
                             macro_task_tx
                                 .send(MacroTask::User(RuntimeMacroTask::ResolvePromiseWithString(
@@ -408,18 +454,29 @@ impl TlsExt {
 
         let storage = host_data.storage.borrow();
         let resources: &TlsResources = storage.get().unwrap();
-        let stream_arc_opt = resources.streams.get_mut(rid).map(|r| {
-            let TlsResource::Client(ref tls_stream_arc) = *r;
-            tls_stream_arc.clone()
-        });
+        let resource = resources.streams.get(rid);
         drop(storage);
 
-        match stream_arc_opt {
-            Some(stream_arc) => {
+        match resource {
+            Some(resource) => {
                 let macro_task_tx = macro_task_tx.clone();
                 host_data.spawn_macro_task(async move {
-                    let guard = stream_arc.lock().await;
-                    let peer_certs = guard.get_ref().1.peer_certificates();
+                    let peer_certs = match resource {
+                        TlsResource::Client(stream) => stream
+                            .lock()
+                            .await
+                            .get_ref()
+                            .1
+                            .peer_certificates()
+                            .map(|certs| certs.to_vec()),
+                        TlsResource::Server(stream) => stream
+                            .lock()
+                            .await
+                            .get_ref()
+                            .1
+                            .peer_certificates()
+                            .map(|certs| certs.to_vec()),
+                    };
                     if let Some(certs) = peer_certs
                         && !certs.is_empty()
                     {
@@ -456,4 +513,266 @@ impl TlsExt {
 
         Ok(Value::Promise(promise_capability.promise()).unbind())
     }
+
+    async fn write_all(resource: TlsResource, data: &[u8]) -> std::io::Result<()> {
+        match resource {
+            TlsResource::Client(stream) => stream.lock().await.write_all(data).await,
+            TlsResource::Server(stream) => stream.lock().await.write_all(data).await,
+        }
+    }
+
+    /// Write hex encoded bytes to a TLS stream.
+    fn internal_tls_write_bytes<'gc>(
+        agent: &mut Agent,
+        _this: Value,
+        args: ArgumentsList,
+        mut gc: GcScope<'gc, '_>,
+    ) -> JsResult<'gc, Value<'gc>> {
+        let rid_binding = args.get(0).to_string(agent, gc.reborrow()).unbind()?;
+        let data_binding = args.get(1).to_string(agent, gc.reborrow()).unbind()?;
+
+        let rid = rid_binding
+            .as_str(agent)
+            .and_then(|rid| rid.parse().ok())
+            .map(Rid::from_index);
+        let data = data_binding
+            .as_str(agent)
+            .and_then(super::command::decode_hex);
+        let (Some(rid), Some(data)) = (rid, data) else {
+            return Err(agent
+                .throw_exception_with_static_message(
+                    ExceptionType::Error,
+                    "Invalid RID or data",
+                    gc.nogc(),
+                )
+                .unbind());
+        };
+
+        let promise_capability = nova_vm::ecmascript::builtins::promise_objects::promise_abstract_operations::promise_capability_records::PromiseCapability::new(agent, gc.nogc());
+        let root_value = Global::new(agent, promise_capability.promise().into_value().unbind());
+
+        let host_data = agent.get_host_data();
+        let host_data: &HostData<RuntimeMacroTask> = host_data.downcast_ref().unwrap();
+        let macro_task_tx = host_data.macro_task_tx();
+
+        let storage = host_data.storage.borrow();
+        let resources: &TlsResources = storage.get().unwrap();
+        let resource = resources.streams.get(rid);
+        drop(storage);
+
+        host_data.spawn_macro_task(async move {
+            let task = match resource {
+                Some(resource) => match Self::write_all(resource, &data).await {
+                    Ok(()) => RuntimeMacroTask::ResolvePromiseWithString(
+                        root_value,
+                        data.len().to_string(),
+                    ),
+                    Err(e) => {
+                        RuntimeMacroTask::RejectPromise(root_value, format!("TLS write error: {e}"))
+                    }
+                },
+                None => RuntimeMacroTask::RejectPromise(
+                    root_value,
+                    "Resource not found or invalid type".to_string(),
+                ),
+            };
+            macro_task_tx.send(MacroTask::User(task)).unwrap();
+        });
+
+        Ok(Value::Promise(promise_capability.promise()).unbind())
+    }
+
+    /// Listen for TLS connections. The options are a JSON encoded
+    /// [`TlsServerOptions`]. Returns `{"success":true,"resourceId":n,...}`.
+    fn internal_tls_listen<'gc>(
+        agent: &mut Agent,
+        _this: Value,
+        args: ArgumentsList,
+        mut gc: GcScope<'gc, '_>,
+    ) -> JsResult<'gc, Value<'gc>> {
+        let hostname_binding = args.get(0).to_string(agent, gc.reborrow()).unbind()?;
+        let port_binding = args.get(1).to_string(agent, gc.reborrow()).unbind()?;
+        let options_binding = args.get(2).to_string(agent, gc.reborrow()).unbind()?;
+
+        let hostname = hostname_binding
+            .as_str(agent)
+            .expect("String is not valid UTF-8")
+            .to_string();
+        let Some(port) = port_binding
+            .as_str(agent)
+            .and_then(|port| port.parse::<u16>().ok())
+        else {
+            return Err(agent
+                .throw_exception_with_static_message(
+                    ExceptionType::TypeError,
+                    "Invalid port number",
+                    gc.nogc(),
+                )
+                .unbind());
+        };
+
+        if let Err(error) =
+            check_permission::<RuntimeMacroTask>(agent, |p| p.check_net(&hostname, Some(port)))
+        {
+            return Err(error
+                .into_js_error::<RuntimeMacroTask>(agent, gc.nogc())
+                .unbind());
+        }
+
+        let config = serde_json::from_str::<TlsServerOptions>(
+            options_binding.as_str(agent).unwrap_or_default(),
+        )
+        .map_err(|e| format!("Invalid TLS options: {e}"))
+        .and_then(|options| server_config(&options));
+        let config = match config {
+            Ok(config) => config,
+            Err(error) => {
+                return Err(agent
+                    .throw_exception(ExceptionType::TypeError, error, gc.nogc())
+                    .unbind());
+            }
+        };
+
+        let bound = tokio::task::block_in_place(|| {
+            tokio::runtime::Handle::current()
+                .block_on(tokio::net::TcpListener::bind((hostname.as_str(), port)))
+        });
+        let (listener, local_addr) = match bound.and_then(|listener| {
+            listener
+                .local_addr()
+                .map(|local_addr| (listener, local_addr))
+        }) {
+            Ok(bound) => bound,
+            Err(e) => {
+                let result = serde_json::json!({ "success": false, "error": e.to_string() });
+                return Ok(Value::from_string(agent, result.to_string(), gc.nogc()).unbind());
+            }
+        };
+
+        let (accepted_tx, accepted_rx) = mpsc::channel(ACCEPT_QUEUE_CAPACITY);
+        let (shutdown_tx, shutdown_rx) = watch::channel(false);
+        tokio::spawn(server::accept_loop(
+            listener,
+            TlsAcceptor::from(config),
+            accepted_tx,
+            shutdown_rx,
+        ));
+
+        let host_data = agent.get_host_data();
+        let host_data: &HostData<RuntimeMacroTask> = host_data.downcast_ref().unwrap();
+        let storage = host_data.storage.borrow();
+        let resources: &TlsResources = storage.get().unwrap();
+        let rid = resources.listeners.push(TlsListenerResource {
+            accepted: StdArc::new(TokioMutex::new(accepted_rx)),
+            shutdown: StdArc::new(shutdown_tx),
+        });
+        drop(storage);
+
+        let result = serde_json::json!({
+            "success": true,
+            "resourceId": rid.index(),
+            "hostname": local_addr.ip().to_string(),
+            "port": local_addr.port(),
+        });
+        Ok(Value::from_string(agent, result.to_string(), gc.nogc()).unbind())
+    }
+
+    /// Wait for the next connection that completed its handshake. Resolves
+    /// with the connection as JSON, or `"null"` once the listener is closed.
+    fn internal_tls_accept<'gc>(
+        agent: &mut Agent,
+        _this: Value,
+        args: ArgumentsList,
+        mut gc: GcScope<'gc, '_>,
+    ) -> JsResult<'gc, Value<'gc>> {
+        let rid_binding = args.get(0).to_string(agent, gc.reborrow()).unbind()?;
+        let rid = rid_binding
+            .as_str(agent)
+            .and_then(|rid| rid.parse().ok())
+            .map(Rid::from_index);
+
+        let promise_capability = nova_vm::ecmascript::builtins::promise_objects::promise_abstract_operations::promise_capability_records::PromiseCapability::new(agent, gc.nogc());
+        let root_value = Global::new(agent, promise_capability.promise().into_value().unbind());
+
+        let host_data = agent.get_host_data();
+        let host_data: &HostData<RuntimeMacroTask> = host_data.downcast_ref().unwrap();
+        let macro_task_tx = host_data.macro_task_tx();
+
+        let storage = host_data.storage.borrow();
+        let resources: &TlsResources = storage.get().unwrap();
+        let listener = rid.and_then(|rid| resources.listeners.get(rid));
+        let streams = resources.streams.clone();
+        drop(storage);
+
+        host_data.spawn_macro_task(async move {
+            let accepted = match listener {
+                Some(listener) => {
+                    let mut shutdown = listener.shutdown.subscribe();
+                    tokio::select! {
+                        accepted = async { listener.accepted.lock().await.recv().await } => accepted,
+                        _ = shutdown.wait_for(|closed| *closed) => None,
+                    }
+                }
+                None => None,
+            };
+
+            let result = match accepted {
+                Some(accepted) => {
+                    let connection = accepted.stream.get_ref().1;
+                    let alpn_protocol = connection
+                        .alpn_protocol()
+                        .map(|protocol| String::from_utf8_lossy(protocol).into_owned());
+                    let server_name = connection.server_name().map(str::to_string);
+                    let rid = streams.push(TlsResource::Server(StdArc::new(TokioMutex::new(
+                        accepted.stream,
+                    ))));
+                    serde_json::json!({
+                        "resourceId": rid.index(),
+                        "remoteAddr": {
+                            "hostname": accepted.remote_addr.ip().to_string(),
+                            "port": accepted.remote_addr.port(),
+                        },
+                        "alpnProtocol": alpn_protocol,
+                        "serverName": server_name,
+                    })
+                    .to_string()
+                }
+                None => "null".to_string(),
+            };
+
+            macro_task_tx
+                .send(MacroTask::User(RuntimeMacroTask::ResolvePromiseWithString(
+                    root_value, result,
+                )))
+                .unwrap();
+        });
+
+        Ok(Value::Promise(promise_capability.promise()).unbind())
+    }
+
+    /// Stop a TLS listener. Pending `internal_tls_accept` calls resolve with
+    /// `"null"`.
+    fn internal_tls_listener_close<'gc>(
+        agent: &mut Agent,
+        _this: Value,
+        args: ArgumentsList,
+        mut gc: GcScope<'gc, '_>,
+    ) -> JsResult<'gc, Value<'gc>> {
+        let rid_binding = args.get(0).to_string(agent, gc.reborrow()).unbind()?;
+        let rid = rid_binding
+            .as_str(agent)
+            .and_then(|rid| rid.parse().ok())
+            .map(Rid::from_index);
+
+        let host_data = agent.get_host_data();
+        let host_data: &HostData<RuntimeMacroTask> = host_data.downcast_ref().unwrap();
+        let storage = host_data.storage.borrow();
+        let resources: &TlsResources = storage.get().unwrap();
+        if let Some(listener) = rid.and_then(|rid| resources.listeners.remove(rid)) {
+            listener.shutdown.send_replace(true);
+        }
+        drop(storage);
+
+        Ok(Value::Undefined)
+    }
 }
@@ -0,0 +1,228 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+interface TlsCertificateOptions {
+  /** The server name this certificate is selected for, `*.` wildcards allowed. */
+  hostname?: string;
+  /** PEM encoded certificate chain. */
+  cert: string;
+  /** PEM encoded private key. */
+  key: string;
+}
+
+interface ListenTlsOptions {
+  port?: number;
+  hostname?: string;
+  /** PEM encoded certificate chain used when no SNI certificate matches. */
+  cert?: string;
+  /** PEM encoded private key for `cert`. */
+  key?: string;
+  /** Certificates selected by the server name the client asks for (SNI). */
+  sni?: TlsCertificateOptions[];
+  /** ALPN protocols to advertise, most preferred first. */
+  alpnProtocols?: string[];
+}
+
+interface NetAddr {
+  hostname: string;
+  port: number;
+}
+
+const TLS_READ_SIZE = 16 * 1024;
+
+function tlsBytesToHex(bytes: Uint8Array): string {
+  let hex = "";
+  for (let i = 0; i < bytes.length; i++) {
+    hex += bytes[i].toString(16).padStart(2, "0");
+  }
+  return hex;
+}
+
+function tlsHexToBytes(hex: string): Uint8Array {
+  const bytes = new Uint8Array(hex.length / 2);
+  for (let i = 0; i < bytes.length; i++) {
+    bytes[i] = parseInt(hex.substr(i * 2, 2), 16);
+  }
+  return bytes;
+}
+
+/**
+ * Collect the certificates of `key`/`cert` and `sni` into the options the
+ * native TLS server expects. Shared with `Andromeda.serve`.
+ */
+function tlsServerOptions(
+  options: {
+    key?: string;
+    cert?: string;
+    sni?: TlsCertificateOptions[];
+  },
+  alpnProtocols: string[],
+): string {
+  const certificates: TlsCertificateOptions[] = [];
+  if (options.key !== undefined || options.cert !== undefined) {
+    if (options.key === undefined || options.cert === undefined) {
+      throw new TypeError("Both `key` and `cert` must be provided");
+    }
+    certificates.push({ cert: options.cert, key: options.key });
+  }
+  for (const entry of options.sni ?? []) {
+    if (!entry.hostname) {
+      throw new TypeError("SNI certificates require a `hostname`");
+    }
+    certificates.push({
+      hostname: entry.hostname,
+      cert: entry.cert,
+      key: entry.key,
+    });
+  }
+  if (certificates.length === 0) {
+    throw new TypeError("TLS requires `key` and `cert` or `sni` certificates");
+  }
+  return JSON.stringify({ certificates, alpnProtocols });
+}
+
+/** A server-side TLS connection. */
+class TlsConn {
+  #rid: number;
+  #closed = false;
+  readonly remoteAddr: NetAddr;
+  /** The ALPN protocol agreed with the client, if any. */
+  readonly alpnProtocol: string | null;
+  /** The server name the client asked for with SNI, if any. */
+  readonly serverName: string | null;
+
+  constructor(info: {
+    resourceId: number;
+    remoteAddr: NetAddr;
+    alpnProtocol: string | null;
+    serverName: string | null;
+  }) {
+    this.#rid = info.resourceId;
+    this.remoteAddr = info.remoteAddr;
+    this.alpnProtocol = info.alpnProtocol;
+    this.serverName = info.serverName;
+  }
+
+  get rid(): number {
+    return this.#rid;
+  }
+
+  /** Read up to `size` bytes, resolving with `null` once the peer is done. */
+  async read(size: number = TLS_READ_SIZE): Promise<Uint8Array | null> {
+    const hex = await __andromeda__.internal_tls_read(
+      String(this.#rid),
+      size,
+    );
+    return hex.length === 0 ? null : tlsHexToBytes(hex);
+  }
+
+  /** Write all of `data`, resolving with the number of bytes written. */
+  async write(data: Uint8Array | string): Promise<number> {
+    const bytes = typeof data === "string" ?
+      new TextEncoder().encode(data) :
+      data;
+    const written = await __andromeda__.internal_tls_write_bytes(
+      String(this.#rid),
+      tlsBytesToHex(bytes),
+    );
+    return Number(written);
+  }
+
+  close(): void {
+    if (this.#closed) return;
+    this.#closed = true;
+    __andromeda__.internal_tls_close(String(this.#rid));
+  }
+
+  get readable(): ReadableStream<Uint8Array> {
+    return new ReadableStream<Uint8Array>({
+      pull: async (controller) => {
+        try {
+          const chunk = await this.read();
+          if (chunk === null) {
+            controller.close();
+          } else {
+            controller.enqueue(chunk);
+          }
+        } catch (error) {
+          controller.error(error);
+        }
+      },
+    });
+  }
+
+  get writable(): WritableStream<Uint8Array> {
+    return new WritableStream<Uint8Array>({
+      write: async (chunk) => {
+        await this.write(chunk);
+      },
+      close: () => this.close(),
+      abort: () => this.close(),
+    });
+  }
+}
+
+/** A listener accepting TLS connections. */
+class TlsListener {
+  #rid: number;
+  #closed = false;
+  readonly addr: NetAddr;
+
+  constructor(rid: number, addr: NetAddr) {
+    this.#rid = rid;
+    this.addr = addr;
+  }
+
+  get rid(): number {
+    return this.#rid;
+  }
+
+  /**
+   * Wait for the next connection that completed its handshake. Rejects once
+   * the listener is closed.
+   */
+  async accept(): Promise<TlsConn> {
+    const next = await __andromeda__.internal_tls_accept(String(this.#rid));
+    const info = JSON.parse(next);
+    if (info === null) {
+      throw new Error("Listener has been closed");
+    }
+    return new TlsConn(info);
+  }
+
+  close(): void {
+    if (this.#closed) return;
+    this.#closed = true;
+    __andromeda__.internal_tls_listener_close(String(this.#rid));
+  }
+
+  async *[Symbol.asyncIterator](): AsyncGenerator<TlsConn> {
+    while (true) {
+      const next = await __andromeda__.internal_tls_accept(String(this.#rid));
+      const info = JSON.parse(next);
+      if (info === null) return;
+      yield new TlsConn(info);
+    }
+  }
+}
+
+function listenTls(options: ListenTlsOptions): TlsListener {
+  const result = JSON.parse(
+    __andromeda__.internal_tls_listen(
+      options.hostname ?? "0.0.0.0",
+      String(options.port ?? 443),
+      tlsServerOptions(options, options.alpnProtocols ?? []),
+    ),
+  );
+  if (!result.success) {
+    throw new Error(`Failed to listen: ${result.error}`);
+  }
+  return new TlsListener(result.resourceId, {
+    hostname: result.hostname,
+    port: result.port,
+  });
+}
+
+globalThis.__andromeda_listen_tls = listenTls;
+globalThis.__andromeda_tls_server_options = tlsServerOptions;
@@ -0,0 +1,184 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! Server-side TLS for `Andromeda.listenTls` and HTTPS in `Andromeda.serve`.
+//!
+//! Certificates and keys are given as PEM. When several certificates are
+//! configured, the one matching the server name the client sent (SNI) is used,
+//! falling back to the certificate configured without a hostname.
+
+use std::{collections::HashMap, io, net::SocketAddr, sync::Arc, time::Duration};
+
+use rustls::{
+    ServerConfig,
+    crypto::CryptoProvider,
+    server::{ClientHello, ResolvesServerCert},
+    sign::CertifiedKey,
+};
+use serde::Deserialize;
+use tokio::{
+    net::{TcpListener, TcpStream},
+    sync::{mpsc, watch},
+};
+use tokio_rustls::{TlsAcceptor, server::TlsStream};
+
+/// How long a client may take to complete the TLS handshake.
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// A PEM certificate chain and its private key, optionally bound to a
+/// hostname for SNI.
+#[derive(Deserialize)]
+pub(crate) struct TlsCertificate {
+    #[serde(default)]
+    pub hostname: Option<String>,
+    pub cert: String,
+    pub key: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct TlsServerOptions {
+    pub certificates: Vec<TlsCertificate>,
+    #[serde(default)]
+    pub alpn_protocols: Vec<String>,
+}
+
+/// A completed handshake waiting to be accepted by JavaScript.
+pub(crate) struct AcceptedTls {
+    pub stream: TlsStream<TcpStream>,
+    pub remote_addr: SocketAddr,
+}
+
+/// Selects a certificate by the server name the client asked for.
+#[derive(Debug)]
+struct SniResolver {
+    names: HashMap<String, Arc<CertifiedKey>>,
+    default: Arc<CertifiedKey>,
+}
+
+impl SniResolver {
+    fn lookup(&self, name: &str) -> Option<Arc<CertifiedKey>> {
+        let name = name.to_ascii_lowercase();
+        if let Some(key) = self.names.get(&name) {
+            return Some(key.clone());
+        }
+        // `*.example.com` covers exactly one label in front of `example.com`
+        let (_, parent) = name.split_once('.')?;
+        self.names.get(&format!("*.{parent}")).cloned()
+    }
+}
+
+impl ResolvesServerCert for SniResolver {
+    fn resolve(&self, client_hello: ClientHello<'_>) -> Option<Arc<CertifiedKey>> {
+        client_hello
+            .server_name()
+            .and_then(|name| self.lookup(name))
+            .or_else(|| Some(self.default.clone()))
+    }
+}
+
+fn certified_key(
+    provider: &CryptoProvider,
+    certificate: &TlsCertificate,
+) -> Result<CertifiedKey, String> {
+    let subject = certificate
+        .hostname
+        .as_deref()
+        .unwrap_or("the default certificate");
+
+    let chain = rustls_pemfile::certs(&mut certificate.cert.as_bytes())
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("Invalid certificate for {subject}: {e}"))?;
+    if chain.is_empty() {
+        return Err(format!("No PEM certificate found for {subject}"));
+    }
+
+    let key = rustls_pemfile::private_key(&mut certificate.key.as_bytes())
+        .map_err(|e| format!("Invalid private key for {subject}: {e}"))?
+        .ok_or_else(|| format!("No PEM private key found for {subject}"))?;
+    let key = provider
+        .key_provider
+        .load_private_key(key)
+        .map_err(|e| format!("Unsupported private key for {subject}: {e}"))?;
+
+    Ok(CertifiedKey::new(chain, key))
+}
+
+/// Build a server configuration from PEM certificates and the ALPN protocols
+/// to advertise, most preferred first.
+pub(crate) fn server_config(options: &TlsServerOptions) -> Result<Arc<ServerConfig>, String> {
+    let provider = CryptoProvider::get_default()
+        .cloned()
+        .unwrap_or_else(|| Arc::new(rustls::crypto::aws_lc_rs::default_provider()));
+
+    let mut names = HashMap::new();
+    let mut default = None;
+    let mut first = None;
+    for certificate in &options.certificates {
+        let key = Arc::new(certified_key(&provider, certificate)?);
+        first.get_or_insert_with(|| key.clone());
+        match &certificate.hostname {
+            Some(hostname) => {
+                names.insert(hostname.to_ascii_lowercase(), key);
+            }
+            None => {
+                default.get_or_insert(key);
+            }
+        }
+    }
+    let default = default
+        .or(first)
+        .ok_or_else(|| "TLS requires a certificate and a private key".to_string())?;
+
+    let mut config = ServerConfig::builder_with_provider(provider)
+        .with_safe_default_protocol_versions()
+        .map_err(|e| e.to_string())?
+        .with_no_client_auth()
+        .with_cert_resolver(Arc::new(SniResolver { names, default }));
+    config.alpn_protocols = options
+        .alpn_protocols
+        .iter()
+        .map(|protocol| protocol.as_bytes().to_vec())
+        .collect();
+
+    Ok(Arc::new(config))
+}
+
+/// Complete the server side of a handshake, giving up on stalled clients.
+pub(crate) async fn handshake(
+    acceptor: &TlsAcceptor,
+    stream: TcpStream,
+) -> io::Result<TlsStream<TcpStream>> {
+    tokio::time::timeout(HANDSHAKE_TIMEOUT, acceptor.accept(stream))
+        .await
+        .map_err(|_| io::Error::new(io::ErrorKind::TimedOut, "TLS handshake timed out"))?
+}
+
+/// Accept connections and hand over those that complete the handshake, until
+/// the listener is closed. Failed handshakes are dropped without surfacing.
+pub(crate) async fn accept_loop(
+    listener: TcpListener,
+    acceptor: TlsAcceptor,
+    accepted: mpsc::Sender<AcceptedTls>,
+    mut shutdown: watch::Receiver<bool>,
+) {
+    loop {
+        tokio::select! {
+            _ = shutdown.wait_for(|closed| *closed) => break,
+            _ = accepted.closed() => break,
+            result = listener.accept() => match result {
+                Ok((stream, remote_addr)) => {
+                    let acceptor = acceptor.clone();
+                    let accepted = accepted.clone();
+                    tokio::spawn(async move {
+                        if let Ok(stream) = handshake(&acceptor, stream).await {
+                            let _ = accepted.send(AcceptedTls { stream, remote_addr }).await;
+                        }
+                    });
+                }
+                Err(_) => tokio::time::sleep(Duration::from_millis(10)).await,
+            },
+        }
+    }
+}
@@ -414,3 +414,10 @@ function structuredClone<T = any>(
 
 // @ts-ignore globalThis is not readonly
 globalThis.structuredClone = structuredClone;
+
+// Exposed for values crossing thread boundaries, e.g. worker messages
+// @ts-ignore globalThis is not readonly
+globalThis.__andromeda_structured_clone = {
+  serialize: structuredSerialize,
+  deserialize: structuredDeserialize,
+};
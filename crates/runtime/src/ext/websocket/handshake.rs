@@ -0,0 +1,260 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! The opening handshake of a WebSocket connection, see RFC 6455 section 4.
+
+use std::time::Duration;
+
+use rustls_pki_types::ServerName;
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpStream,
+};
+use url::{Host, Url};
+
+use super::protocol::{BoxedRead, BoxedWrite, Connected, Upgraded};
+use crate::ext::tls::client_connector;
+
+/// Appended to the client's key before hashing it into the accept value.
+const ACCEPT_GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+/// Largest response status line plus headers accepted, in bytes.
+const MAX_HEAD_SIZE: usize = 64 * 1024;
+const MAX_HEADERS: usize = 100;
+/// How long a server may take to answer the opening handshake.
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// The `Sec-WebSocket-Accept` value proving the server read `key`.
+pub(crate) fn accept_key(key: &str) -> String {
+    let digest = sha1(format!("{key}{ACCEPT_GUID}").as_bytes());
+    base64_simd::STANDARD.encode_to_string(digest)
+}
+
+/// A `Sec-WebSocket-Key` must be 16 base64 encoded bytes.
+#[cfg(feature = "serve")]
+pub(crate) fn is_valid_key(key: &str) -> bool {
+    base64_simd::STANDARD
+        .decode_to_vec(key.trim())
+        .is_ok_and(|key| key.len() == 16)
+}
+
+/// Open a `ws:` or `wss:` connection and run the client side of the opening
+/// handshake, offering `protocols`.
+pub(crate) async fn connect(url: Url, protocols: Vec<String>) -> Result<Connected, String> {
+    let secure = url.scheme() == "wss";
+    let host = match url.host() {
+        Some(Host::Domain(domain)) => domain.to_string(),
+        Some(Host::Ipv4(ip)) => ip.to_string(),
+        Some(Host::Ipv6(ip)) => ip.to_string(),
+        None => return Err(format!("Invalid WebSocket URL: {url}")),
+    };
+    let port = url
+        .port_or_known_default()
+        .unwrap_or(if secure { 443 } else { 80 });
+
+    let tcp = TcpStream::connect((host.as_str(), port))
+        .await
+        .map_err(|e| format!("Failed to connect to {host}:{port}: {e}"))?;
+    let _ = tcp.set_nodelay(true);
+
+    let (read, mut write): (BoxedRead, BoxedWrite) = if secure {
+        let name =
+            ServerName::try_from(host.clone()).map_err(|_| format!("Invalid DNS name: {host}"))?;
+        let stream = client_connector(vec![b"http/1.1".to_vec()])
+            .connect(name, tcp)
+            .await
+            .map_err(|e| format!("TLS handshake failed: {e}"))?;
+        let (read, write) = tokio::io::split(stream);
+        (Box::new(read), Box::new(write))
+    } else {
+        let (read, write) = tcp.into_split();
+        (Box::new(read), Box::new(write))
+    };
+
+    let key = base64_simd::STANDARD.encode_to_string(uuid::Uuid::new_v4().as_bytes());
+    let mut target = url.path().to_string();
+    if let Some(query) = url.query() {
+        target.push('?');
+        target.push_str(query);
+    }
+    let authority = match url.port() {
+        Some(port) => format!("{}:{port}", url.host_str().unwrap_or_default()),
+        None => url.host_str().unwrap_or_default().to_string(),
+    };
+
+    let mut request = format!(
+        "GET {target} HTTP/1.1\r\n\
+         Host: {authority}\r\n\
+         Upgrade: websocket\r\n\
+         Connection: Upgrade\r\n\
+         Sec-WebSocket-Key: {key}\r\n\
+         Sec-WebSocket-Version: 13\r\n\
+         User-Agent: Andromeda\r\n"
+    );
+    if !protocols.is_empty() {
+        request.push_str(&format!(
+            "Sec-WebSocket-Protocol: {}\r\n",
+            protocols.join(", ")
+        ));
+    }
+    request.push_str("\r\n");
+
+    let exchange = async {
+        write.write_all(request.as_bytes()).await?;
+        write.flush().await?;
+        read_response(read).await
+    };
+    let (read, status, headers, buffered) = tokio::time::timeout(HANDSHAKE_TIMEOUT, exchange)
+        .await
+        .map_err(|_| "WebSocket handshake timed out".to_string())?
+        .map_err(|e| format!("WebSocket handshake failed: {e}"))?;
+
+    let header = |name: &str| {
+        headers
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    };
+    let has_token = |name: &str, token: &str| {
+        header(name).is_some_and(|value| {
+            value
+                .split(',')
+                .any(|item| item.trim().eq_ignore_ascii_case(token))
+        })
+    };
+
+    if status != 101 {
+        return Err(format!(
+            "Expected a 101 response to the WebSocket handshake, got {status}"
+        ));
+    }
+    if !has_token("upgrade", "websocket") || !has_token("connection", "upgrade") {
+        return Err("Server did not upgrade the connection to WebSocket".to_string());
+    }
+    if header("sec-websocket-accept").map(str::trim) != Some(accept_key(&key).as_str()) {
+        return Err("Server sent an invalid Sec-WebSocket-Accept".to_string());
+    }
+    // No extension was offered, so the server must not use one
+    if header("sec-websocket-extensions").is_some_and(|value| !value.trim().is_empty()) {
+        return Err("Server negotiated an extension that was not offered".to_string());
+    }
+    let protocol = header("sec-websocket-protocol")
+        .map(str::trim)
+        .unwrap_or_default()
+        .to_string();
+    if !protocol.is_empty() && !protocols.contains(&protocol) {
+        return Err(format!(
+            "Server selected the subprotocol {protocol}, which was not offered"
+        ));
+    }
+
+    Ok(Connected {
+        stream: Upgraded {
+            read,
+            write,
+            buffered,
+        },
+        protocol,
+        extensions: String::new(),
+    })
+}
+
+type ResponseHead = (BoxedRead, u16, Vec<(String, String)>, Vec<u8>);
+
+/// Read the response head, returning the status, headers and any bytes the
+/// server already sent after it.
+async fn read_response(mut read: BoxedRead) -> std::io::Result<ResponseHead> {
+    let mut buffer = Vec::new();
+    let mut chunk = [0u8; 4096];
+    loop {
+        if let Some((length, status, headers)) = parse_response(&buffer)? {
+            let buffered = buffer.split_off(length);
+            return Ok((read, status, headers, buffered));
+        }
+        if buffer.len() > MAX_HEAD_SIZE {
+            return Err(std::io::Error::other("response head is too large"));
+        }
+        let read_bytes = read.read(&mut chunk).await?;
+        if read_bytes == 0 {
+            return Err(std::io::ErrorKind::UnexpectedEof.into());
+        }
+        buffer.extend_from_slice(&chunk[..read_bytes]);
+    }
+}
+
+type ParsedHead = (usize, u16, Vec<(String, String)>);
+
+fn parse_response(buffer: &[u8]) -> std::io::Result<Option<ParsedHead>> {
+    let mut headers = [httparse::EMPTY_HEADER; MAX_HEADERS];
+    let mut response = httparse::Response::new(&mut headers);
+    match response.parse(buffer) {
+        Ok(httparse::Status::Complete(length)) => {
+            let headers = response
+                .headers
+                .iter()
+                .map(|header| {
+                    (
+                        header.name.to_string(),
+                        String::from_utf8_lossy(header.value).into_owned(),
+                    )
+                })
+                .collect();
+            Ok(Some((length, response.code.unwrap_or_default(), headers)))
+        }
+        Ok(httparse::Status::Partial) => Ok(None),
+        Err(e) => Err(std::io::Error::new(std::io::ErrorKind::InvalidData, e)),
+    }
+}
+
+/// SHA-1, which the handshake needs regardless of the `crypto` feature.
+fn sha1(data: &[u8]) -> [u8; 20] {
+    let mut state: [u32; 5] = [0x67452301, 0xEFCDAB89, 0x98BADCFE, 0x10325476, 0xC3D2E1F0];
+
+    let mut message = data.to_vec();
+    message.push(0x80);
+    while message.len() % 64 != 56 {
+        message.push(0);
+    }
+    message.extend_from_slice(&((data.len() as u64) * 8).to_be_bytes());
+
+    for block in message.chunks_exact(64) {
+        let mut words = [0u32; 80];
+        for (word, bytes) in words.iter_mut().zip(block.chunks_exact(4)) {
+            *word = u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+        }
+        for i in 16..80 {
+            words[i] = (words[i - 3] ^ words[i - 8] ^ words[i - 14] ^ words[i - 16]).rotate_left(1);
+        }
+
+        let [mut a, mut b, mut c, mut d, mut e] = state;
+        for (i, word) in words.iter().enumerate() {
+            let (f, k) = match i {
+                0..=19 => ((b & c) | (!b & d), 0x5A827999),
+                20..=39 => (b ^ c ^ d, 0x6ED9EBA1),
+                40..=59 => ((b & c) | (b & d) | (c & d), 0x8F1BBCDC),
+                _ => (b ^ c ^ d, 0xCA62C1D6),
+            };
+            let temp = a
+                .rotate_left(5)
+                .wrapping_add(f)
+                .wrapping_add(e)
+                .wrapping_add(k)
+                .wrapping_add(*word);
+            e = d;
+            d = c;
+            c = b.rotate_left(30);
+            b = a;
+            a = temp;
+        }
+
+        for (value, add) in state.iter_mut().zip([a, b, c, d, e]) {
+            *value = value.wrapping_add(add);
+        }
+    }
+
+    let mut digest = [0u8; 20];
+    for (bytes, value) in digest.chunks_exact_mut(4).zip(state) {
+        bytes.copy_from_slice(&value.to_be_bytes());
+    }
+    digest
+}
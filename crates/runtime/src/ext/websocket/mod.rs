@@ -0,0 +1,319 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+mod handshake;
+mod protocol;
+
+use std::sync::Arc;
+
+use andromeda_core::{
+    Extension, ExtensionOp, HostData, MacroTask, OpsStorage, Rid, SyncResourceTable,
+    check_permission,
+};
+use nova_vm::{
+    ecmascript::{
+        builtins::{
+            ArgumentsList,
+            promise_objects::promise_abstract_operations::promise_capability_records::PromiseCapability,
+        },
+        execution::{Agent, JsResult, agent::ExceptionType},
+        types::{IntoValue, Value},
+    },
+    engine::{
+        Global,
+        context::{Bindable, GcScope},
+    },
+};
+use tokio::sync::{Mutex as TokioMutex, mpsc, oneshot};
+
+use crate::RuntimeMacroTask;
+
+use super::command::decode_hex;
+use protocol::{Connected, Event, Outgoing, Role};
+
+#[cfg(feature = "serve")]
+pub(crate) use handshake::{accept_key, is_valid_key};
+#[cfg(feature = "serve")]
+pub(crate) use protocol::Upgraded;
+
+/// Events queued before a socket stops reading from its connection.
+const EVENT_QUEUE_CAPACITY: usize = 16;
+
+#[derive(Clone)]
+pub(crate) struct WebSocketResource {
+    events: Arc<TokioMutex<mpsc::Receiver<Event>>>,
+    outgoing: mpsc::UnboundedSender<Outgoing>,
+}
+
+pub(crate) struct WebSocketResources {
+    /// Shared with the tasks that deliver events, which drop closed sockets
+    sockets: SyncResourceTable<WebSocketResource>,
+}
+
+impl WebSocketResources {
+    /// Register a socket that opens once `connect` completes the handshake.
+    fn open<F>(&self, role: Role, connect: F) -> Rid
+    where
+        F: Future<Output = Result<Connected, String>> + Send + 'static,
+    {
+        let (events_tx, events_rx) = mpsc::channel(EVENT_QUEUE_CAPACITY);
+        let (outgoing_tx, outgoing_rx) = mpsc::unbounded_channel();
+        tokio::spawn(protocol::run(
+            role,
+            connect,
+            events_tx,
+            outgoing_rx,
+            outgoing_tx.clone(),
+        ));
+        self.sockets.push(WebSocketResource {
+            events: Arc::new(TokioMutex::new(events_rx)),
+            outgoing: outgoing_tx,
+        })
+    }
+
+    /// Register the server side of a socket, which opens once the HTTP server
+    /// has sent the 101 response and hands over the connection.
+    #[cfg(feature = "serve")]
+    pub(crate) fn accept(&self, upgraded: oneshot::Receiver<Upgraded>, protocol: String) -> Rid {
+        self.open(Role::Server, async move {
+            let stream = upgraded
+                .await
+                .map_err(|_| "The WebSocket upgrade response was not sent".to_string())?;
+            Ok(Connected {
+                stream,
+                protocol,
+                extensions: String::new(),
+            })
+        })
+    }
+}
+
+/// WebSocket extension for Andromeda.
+/// This extension provides the `WebSocket` and `WebSocketStream` classes, and
+/// the sockets behind `Andromeda.upgradeWebSocket`.
+#[derive(Default)]
+pub struct WebSocketExt;
+
+#[hotpath::measure_all]
+impl WebSocketExt {
+    pub fn new_extension() -> Extension {
+        Extension {
+            name: "websocket",
+            ops: vec![
+                ExtensionOp::new("internal_ws_connect", Self::internal_ws_connect, 2, false),
+                ExtensionOp::new("internal_ws_next", Self::internal_ws_next, 1, false),
+                ExtensionOp::new("internal_ws_send", Self::internal_ws_send, 3, false),
+                ExtensionOp::new("internal_ws_close", Self::internal_ws_close, 3, false),
+            ],
+            storage: Some(Box::new(|storage: &mut OpsStorage| {
+                storage.insert(WebSocketResources {
+                    sockets: SyncResourceTable::new(),
+                });
+            })),
+            files: vec![include_str!("./mod.ts")],
+        }
+    }
+
+    /// Connect to a `ws:` or `wss:` URL, offering the comma separated
+    /// subprotocols. Returns the socket's resource ID right away, the
+    /// handshake result arrives as its first event.
+    fn internal_ws_connect<'gc>(
+        agent: &mut Agent,
+        _this: Value,
+        args: ArgumentsList,
+        mut gc: GcScope<'gc, '_>,
+    ) -> JsResult<'gc, Value<'gc>> {
+        let url_binding = args.get(0).to_string(agent, gc.reborrow()).unbind()?;
+        let protocols_binding = args.get(1).to_string(agent, gc.reborrow()).unbind()?;
+
+        let url = url_binding
+            .as_str(agent)
+            .and_then(|url| url::Url::parse(url).ok())
+            .filter(|url| matches!(url.scheme(), "ws" | "wss") && url.host_str().is_some());
+        let Some(url) = url else {
+            return Err(agent
+                .throw_exception_with_static_message(
+                    ExceptionType::SyntaxError,
+                    "Invalid WebSocket URL",
+                    gc.nogc(),
+                )
+                .unbind());
+        };
+        let protocols: Vec<String> = protocols_binding
+            .as_str(agent)
+            .unwrap_or_default()
+            .split(',')
+            .map(str::trim)
+            .filter(|protocol| !protocol.is_empty())
+            .map(str::to_string)
+            .collect();
+
+        let host = url.host_str().unwrap_or_default().to_string();
+        if let Err(error) = check_permission::<RuntimeMacroTask>(agent, |p| {
+            p.check_net(&host, url.port_or_known_default())
+        }) {
+            return Err(error
+                .into_js_error::<RuntimeMacroTask>(agent, gc.nogc())
+                .unbind());
+        }
+
+        let host_data = agent.get_host_data();
+        let host_data: &HostData<RuntimeMacroTask> = host_data.downcast_ref().unwrap();
+        let storage = host_data.storage.borrow();
+        let resources: &WebSocketResources = storage.get().unwrap();
+        let rid = resources.open(Role::Client, handshake::connect(url, protocols));
+        drop(storage);
+
+        Ok(Value::from_f64(agent, rid.index() as f64, gc.nogc()).unbind())
+    }
+
+    /// Wait for the next event of a socket. Resolves with the event as JSON,
+    /// or `"null"` once the socket is gone.
+    fn internal_ws_next<'gc>(
+        agent: &mut Agent,
+        _this: Value,
+        args: ArgumentsList,
+        mut gc: GcScope<'gc, '_>,
+    ) -> JsResult<'gc, Value<'gc>> {
+        let rid = Self::rid_arg(agent, &args, gc.reborrow())?;
+
+        let promise_capability = PromiseCapability::new(agent, gc.nogc());
+        let root_value = Global::new(agent, promise_capability.promise().into_value().unbind());
+
+        let host_data = agent.get_host_data();
+        let host_data: &HostData<RuntimeMacroTask> = host_data.downcast_ref().unwrap();
+        let macro_task_tx = host_data.macro_task_tx();
+        let storage = host_data.storage.borrow();
+        let resources: &WebSocketResources = storage.get().unwrap();
+        let sockets = resources.sockets.clone();
+        drop(storage);
+
+        host_data.spawn_macro_task(async move {
+            let event = match sockets.get(rid) {
+                Some(socket) => socket.events.lock().await.recv().await,
+                None => None,
+            };
+            let result = match event {
+                Some(event) => {
+                    // Nothing follows the close event
+                    if matches!(event, Event::Close { .. }) {
+                        sockets.remove(rid);
+                    }
+                    event.to_json()
+                }
+                None => {
+                    sockets.remove(rid);
+                    "null".to_string()
+                }
+            };
+            macro_task_tx
+                .send(MacroTask::User(RuntimeMacroTask::ResolvePromiseWithString(
+                    root_value, result,
+                )))
+                .unwrap();
+        });
+
+        Ok(Value::Promise(promise_capability.promise()).unbind())
+    }
+
+    /// Queue a message, `data` being text or, with `binary` set to `"true"`,
+    /// hex encoded bytes. Resolves once the message was written, or dropped
+    /// because the socket closed first.
+    fn internal_ws_send<'gc>(
+        agent: &mut Agent,
+        _this: Value,
+        args: ArgumentsList,
+        mut gc: GcScope<'gc, '_>,
+    ) -> JsResult<'gc, Value<'gc>> {
+        let rid = Self::rid_arg(agent, &args, gc.reborrow())?;
+        let data_binding = args.get(1).to_string(agent, gc.reborrow()).unbind()?;
+        let binary_binding = args.get(2).to_string(agent, gc.reborrow()).unbind()?;
+
+        let data = data_binding.as_str(agent).unwrap_or_default();
+        let binary = binary_binding.as_str(agent) == Some("true");
+        let payload = if binary {
+            decode_hex(data)
+        } else {
+            Some(data.as_bytes().to_vec())
+        };
+        let Some(payload) = payload else {
+            return Err(agent
+                .throw_exception_with_static_message(
+                    ExceptionType::TypeError,
+                    "Invalid binary message",
+                    gc.nogc(),
+                )
+                .unbind());
+        };
+
+        let promise_capability = PromiseCapability::new(agent, gc.nogc());
+        let root_value = Global::new(agent, promise_capability.promise().into_value().unbind());
+
+        // Queued synchronously, so messages keep the order they were sent in
+        let (written_tx, written_rx) = oneshot::channel();
+        if let Some(socket) = Self::socket(agent, rid) {
+            let _ = socket.outgoing.send(Outgoing::Message {
+                text: !binary,
+                payload,
+                written: written_tx,
+            });
+        }
+
+        let host_data = agent.get_host_data();
+        let host_data: &HostData<RuntimeMacroTask> = host_data.downcast_ref().unwrap();
+        let macro_task_tx = host_data.macro_task_tx();
+        host_data.spawn_macro_task(async move {
+            let _ = written_rx.await;
+            macro_task_tx
+                .send(MacroTask::User(RuntimeMacroTask::ResolvePromiseWithString(
+                    root_value,
+                    String::new(),
+                )))
+                .unwrap();
+        });
+
+        Ok(Value::Promise(promise_capability.promise()).unbind())
+    }
+
+    /// Start the closing handshake, or abort a socket that is still
+    /// connecting. An empty `code` sends a close frame without a status.
+    fn internal_ws_close<'gc>(
+        agent: &mut Agent,
+        _this: Value,
+        args: ArgumentsList,
+        mut gc: GcScope<'gc, '_>,
+    ) -> JsResult<'gc, Value<'gc>> {
+        let rid = Self::rid_arg(agent, &args, gc.reborrow())?;
+        let code_binding = args.get(1).to_string(agent, gc.reborrow()).unbind()?;
+        let reason_binding = args.get(2).to_string(agent, gc.reborrow()).unbind()?;
+
+        let code = code_binding
+            .as_str(agent)
+            .and_then(|code| code.parse::<u16>().ok());
+        let reason = reason_binding.as_str(agent).unwrap_or_default().to_string();
+
+        if let Some(socket) = Self::socket(agent, rid) {
+            let _ = socket.outgoing.send(Outgoing::Close { code, reason });
+        }
+
+        Ok(Value::Undefined)
+    }
+
+    fn rid_arg<'gc>(
+        agent: &mut Agent,
+        args: &ArgumentsList,
+        gc: GcScope<'gc, '_>,
+    ) -> JsResult<'gc, Rid> {
+        let rid = args.get(0).to_number(agent, gc).unbind()?.into_f64(agent);
+        Ok(Rid::from_index(rid as u32))
+    }
+
+    fn socket(agent: &Agent, rid: Rid) -> Option<WebSocketResource> {
+        let host_data = agent.get_host_data();
+        let host_data: &HostData<RuntimeMacroTask> = host_data.downcast_ref().unwrap();
+        let storage = host_data.storage.borrow();
+        let resources: &WebSocketResources = storage.get().unwrap();
+        resources.sockets.get(rid)
+    }
+}
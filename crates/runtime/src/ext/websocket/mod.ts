@@ -0,0 +1,579 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+// deno-lint-ignore-file no-explicit-any
+
+type BinaryType = "blob" | "arraybuffer";
+
+/** An event of a socket, as delivered by `internal_ws_next`. */
+type WebSocketEventData =
+  | { type: "open"; protocol: string; extensions: string; }
+  | { type: "text"; data: string; }
+  | { type: "binary"; data: string; }
+  | { type: "error"; message: string; }
+  | { type: "close"; code: number; reason: string; wasClean: boolean; };
+
+const WS_CONNECTING = 0;
+const WS_OPEN = 1;
+const WS_CLOSING = 2;
+const WS_CLOSED = 3;
+
+const WS_TOKEN = /^[!#$%&'*+\-.^_`|~0-9A-Za-z]+$/;
+
+/** Set by `Andromeda.upgradeWebSocket` to wrap an accepted socket. */
+let wsAcceptedRid: number | null = null;
+
+function wsBytesToHex(bytes: Uint8Array): string {
+  let hex = "";
+  for (let i = 0; i < bytes.length; i++) {
+    hex += bytes[i].toString(16).padStart(2, "0");
+  }
+  return hex;
+}
+
+function wsHexToBytes(hex: string): Uint8Array {
+  const bytes = new Uint8Array(hex.length / 2);
+  for (let i = 0; i < bytes.length; i++) {
+    bytes[i] = parseInt(hex.substr(i * 2, 2), 16);
+  }
+  return bytes;
+}
+
+/** Resolve a WebSocket URL, accepting `http:` and `https:` as well. */
+function webSocketUrl(url: string | URL): URL {
+  let parsed: URL;
+  try {
+    parsed = new URL(String(url));
+  } catch {
+    throw new DOMException(`Invalid URL: ${url}`, "SyntaxError");
+  }
+  if (parsed.protocol === "http:" || parsed.protocol === "https:") {
+    parsed = new URL(parsed.href.replace(/^http/, "ws"));
+  }
+  if (parsed.protocol !== "ws:" && parsed.protocol !== "wss:") {
+    throw new DOMException(
+      `The URL's scheme must be "ws" or "wss", got "${parsed.protocol}"`,
+      "SyntaxError",
+    );
+  }
+  if (parsed.hash !== "" || parsed.href.endsWith("#")) {
+    throw new DOMException("The URL must not contain a fragment", "SyntaxError");
+  }
+  return parsed;
+}
+
+function webSocketProtocols(protocols: string | string[]): string[] {
+  const list = typeof protocols === "string" ? [protocols] : [...protocols];
+  const seen = new Set<string>();
+  for (const protocol of list) {
+    if (!WS_TOKEN.test(protocol)) {
+      throw new DOMException(`Invalid subprotocol: ${protocol}`, "SyntaxError");
+    }
+    if (seen.has(protocol)) {
+      throw new DOMException(`Duplicate subprotocol: ${protocol}`, "SyntaxError");
+    }
+    seen.add(protocol);
+  }
+  return list;
+}
+
+/** Validate the arguments of `close()`, returning the code to send. */
+function webSocketCloseCode(code?: number, reason?: string): string {
+  if (code !== undefined) {
+    code = Math.trunc(Number(code));
+    if (code !== 1000 && !(code >= 3000 && code <= 4999)) {
+      throw new DOMException(
+        `The close code must be 1000 or in the range 3000 to 4999, got ${code}`,
+        "InvalidAccessError",
+      );
+    }
+  }
+  if (reason !== undefined && new TextEncoder().encode(reason).length > 123) {
+    throw new DOMException(
+      "The close reason must not be longer than 123 bytes",
+      "SyntaxError",
+    );
+  }
+  // A reason can only be sent together with a code
+  if (code === undefined) {
+    return reason ? "1000" : "";
+  }
+  return String(code);
+}
+
+/**
+ * A WebSocket client, or the server side of a connection accepted with
+ * `Andromeda.upgradeWebSocket`.
+ *
+ * @see https://websockets.spec.whatwg.org/#the-websocket-interface
+ */
+class WebSocket extends EventTarget {
+  static readonly CONNECTING = WS_CONNECTING;
+  static readonly OPEN = WS_OPEN;
+  static readonly CLOSING = WS_CLOSING;
+  static readonly CLOSED = WS_CLOSED;
+
+  #rid: number;
+  #url: string;
+  #origin: string;
+  #readyState = WS_CONNECTING;
+  #protocol = "";
+  #extensions = "";
+  #bufferedAmount = 0;
+  #binaryType: BinaryType = "blob";
+  /** Blob messages being read, which later messages have to wait for. */
+  #pending: Promise<void> | null = null;
+  #handlers: Record<string, ((event: any) => any) | null> = {
+    open: null,
+    message: null,
+    error: null,
+    close: null,
+  };
+
+  constructor(url: string | URL, protocols: string | string[] = []) {
+    super();
+    const acceptedRid = wsAcceptedRid;
+    wsAcceptedRid = null;
+    const parsed = webSocketUrl(url);
+    this.#url = parsed.href;
+    this.#origin = `${parsed.protocol}//${parsed.host}`;
+
+    if (acceptedRid !== null) {
+      this.#rid = acceptedRid;
+    } else {
+      const list = webSocketProtocols(protocols);
+      this.#rid = __andromeda__.internal_ws_connect(this.#url, list.join(","));
+    }
+    this.#poll();
+  }
+
+  async #poll() {
+    while (true) {
+      const event: WebSocketEventData | null = JSON.parse(
+        await __andromeda__.internal_ws_next(this.#rid),
+      );
+      if (event === null) {
+        if (this.#readyState !== WS_CLOSED) {
+          this.#readyState = WS_CLOSED;
+          this.dispatchEvent(new CloseEvent("close", { code: 1006 }));
+        }
+        return;
+      }
+      this.#handle(event);
+      if (event.type === "close") return;
+    }
+  }
+
+  #handle(event: WebSocketEventData) {
+    switch (event.type) {
+      case "open":
+        this.#protocol = event.protocol;
+        this.#extensions = event.extensions;
+        // close() was called while connecting, the close handshake follows
+        if (this.#readyState !== WS_CONNECTING) return;
+        this.#readyState = WS_OPEN;
+        this.dispatchEvent(new Event("open"));
+        break;
+      case "text":
+      case "binary": {
+        if (this.#readyState !== WS_OPEN) return;
+        let data: string | Blob | ArrayBuffer = event.data;
+        if (event.type === "binary") {
+          const bytes = wsHexToBytes(event.data);
+          data = this.#binaryType === "blob" ? new Blob([bytes]) : bytes.buffer;
+        }
+        this.dispatchEvent(
+          new MessageEvent("message", { data, origin: this.#origin }),
+        );
+        break;
+      }
+      case "error":
+        this.dispatchEvent(new ErrorEvent("error", { message: event.message }));
+        break;
+      case "close":
+        this.#readyState = WS_CLOSED;
+        this.dispatchEvent(
+          new CloseEvent("close", {
+            wasClean: event.wasClean,
+            code: event.code,
+            reason: event.reason,
+          }),
+        );
+        break;
+    }
+  }
+
+  get url(): string {
+    return this.#url;
+  }
+
+  get readyState(): number {
+    return this.#readyState;
+  }
+
+  /** Bytes passed to `send()` that have not been written yet. */
+  get bufferedAmount(): number {
+    return this.#bufferedAmount;
+  }
+
+  /** The subprotocol the server selected. */
+  get protocol(): string {
+    return this.#protocol;
+  }
+
+  get extensions(): string {
+    return this.#extensions;
+  }
+
+  get binaryType(): BinaryType {
+    return this.#binaryType;
+  }
+
+  set binaryType(value: BinaryType) {
+    if (value === "blob" || value === "arraybuffer") {
+      this.#binaryType = value;
+    }
+  }
+
+  /**
+   * Queue a message. Strings are sent as text, buffers and blobs as binary
+   * messages.
+   */
+  send(data: string | ArrayBufferLike | ArrayBufferView | Blob): void {
+    if (this.#readyState === WS_CONNECTING) {
+      throw new DOMException("WebSocket is not open", "InvalidStateError");
+    }
+
+    if (data instanceof Blob) {
+      this.#transmit(
+        data.size,
+        data.arrayBuffer().then((buffer) =>
+          wsBytesToHex(new Uint8Array(buffer))
+        ),
+        true,
+      );
+    } else if (ArrayBuffer.isView(data)) {
+      const bytes = new Uint8Array(
+        data.buffer,
+        data.byteOffset,
+        data.byteLength,
+      );
+      this.#transmit(bytes.length, wsBytesToHex(bytes), true);
+    } else if (
+      data instanceof ArrayBuffer ||
+      (typeof SharedArrayBuffer !== "undefined" &&
+        data instanceof SharedArrayBuffer)
+    ) {
+      const bytes = new Uint8Array(data);
+      this.#transmit(bytes.length, wsBytesToHex(bytes), true);
+    } else {
+      const text = String(data);
+      this.#transmit(new TextEncoder().encode(text).length, text, false);
+    }
+  }
+
+  #transmit(size: number, data: string | Promise<string>, binary: boolean) {
+    this.#bufferedAmount += size;
+    // Messages sent after closing only count towards bufferedAmount
+    if (this.#readyState !== WS_OPEN) return;
+
+    const issue = (payload: string) => {
+      __andromeda__
+        .internal_ws_send(this.#rid, payload, binary ? "true" : "false")
+        .then(() => {
+          this.#bufferedAmount -= size;
+        });
+    };
+    if (this.#pending === null && typeof data === "string") {
+      issue(data);
+    } else {
+      this.#enqueue(() => Promise.resolve(data).then(issue));
+    }
+  }
+
+  /** Run `step` once the blobs sent before it have been read. */
+  #enqueue(step: () => void | Promise<void>) {
+    const pending = (this.#pending ?? Promise.resolve()).then(step).catch(
+      (error) => {
+        this.dispatchEvent(new ErrorEvent("error", { error }));
+      },
+    );
+    this.#pending = pending;
+    pending.then(() => {
+      if (this.#pending === pending) this.#pending = null;
+    });
+  }
+
+  /**
+   * Start the closing handshake. Closing a socket that is still connecting
+   * aborts the connection.
+   */
+  close(code?: number, reason?: string): void {
+    const closeCode = webSocketCloseCode(code, reason);
+    if (this.#readyState === WS_CLOSING || this.#readyState === WS_CLOSED) {
+      return;
+    }
+    this.#readyState = WS_CLOSING;
+
+    const issue = () => {
+      __andromeda__.internal_ws_close(this.#rid, closeCode, reason ?? "");
+    };
+    if (this.#pending === null) {
+      issue();
+    } else {
+      this.#enqueue(issue);
+    }
+  }
+
+  #setHandler(type: string, handler: ((event: any) => any) | null) {
+    const previous = this.#handlers[type];
+    if (previous) this.removeEventListener(type, previous);
+    this.#handlers[type] = typeof handler === "function" ? handler : null;
+    if (this.#handlers[type]) this.addEventListener(type, this.#handlers[type]!);
+  }
+
+  get onopen(): ((event: Event) => any) | null {
+    return this.#handlers.open;
+  }
+
+  set onopen(handler: ((event: Event) => any) | null) {
+    this.#setHandler("open", handler);
+  }
+
+  get onmessage(): ((event: MessageEvent) => any) | null {
+    return this.#handlers.message;
+  }
+
+  set onmessage(handler: ((event: MessageEvent) => any) | null) {
+    this.#setHandler("message", handler);
+  }
+
+  get onerror(): ((event: Event) => any) | null {
+    return this.#handlers.error;
+  }
+
+  set onerror(handler: ((event: Event) => any) | null) {
+    this.#setHandler("error", handler);
+  }
+
+  get onclose(): ((event: CloseEvent) => any) | null {
+    return this.#handlers.close;
+  }
+
+  set onclose(handler: ((event: CloseEvent) => any) | null) {
+    this.#setHandler("close", handler);
+  }
+}
+
+for (const [name, value] of Object.entries({
+  CONNECTING: WS_CONNECTING,
+  OPEN: WS_OPEN,
+  CLOSING: WS_CLOSING,
+  CLOSED: WS_CLOSED,
+})) {
+  Object.defineProperty(WebSocket.prototype, name, {
+    value,
+    enumerable: true,
+  });
+}
+
+interface WebSocketStreamOptions {
+  protocols?: string[];
+  signal?: AbortSignal;
+}
+
+interface WebSocketOpenInfo {
+  readable: ReadableStream<string | Uint8Array>;
+  writable: WritableStream<string | BufferSource>;
+  protocol: string;
+  extensions: string;
+}
+
+interface WebSocketCloseInfo {
+  closeCode?: number;
+  reason?: string;
+}
+
+/**
+ * A WebSocket exposing its messages as streams. Reading applies
+ * backpressure: the connection is not read while the readable is not pulled.
+ *
+ * @see https://github.com/whatwg/websockets/pull/48
+ */
+class WebSocketStream {
+  #rid: number;
+  #url: string;
+  #opened: Promise<WebSocketOpenInfo>;
+  #closed: Promise<WebSocketCloseInfo>;
+  #closing = false;
+
+  constructor(url: string | URL, options: WebSocketStreamOptions = {}) {
+    const parsed = webSocketUrl(url);
+    const protocols = webSocketProtocols(options.protocols ?? []);
+    this.#url = parsed.href;
+
+    let resolveOpened!: (info: WebSocketOpenInfo) => void;
+    let rejectOpened!: (reason: unknown) => void;
+    let resolveClosed!: (info: WebSocketCloseInfo) => void;
+    let rejectClosed!: (reason: unknown) => void;
+    this.#opened = new Promise((resolve, reject) => {
+      resolveOpened = resolve;
+      rejectOpened = reject;
+    });
+    this.#closed = new Promise((resolve, reject) => {
+      resolveClosed = resolve;
+      rejectClosed = reject;
+    });
+    // Callers only interested in one of them should not see the other reject
+    this.#opened.catch(() => {});
+    this.#closed.catch(() => {});
+
+    if (options.signal?.aborted) {
+      this.#rid = -1;
+      rejectOpened(options.signal.reason);
+      rejectClosed(options.signal.reason);
+      return;
+    }
+    this.#rid = __andromeda__.internal_ws_connect(
+      this.#url,
+      protocols.join(","),
+    );
+
+    let opened = false;
+    let abortReason: unknown = undefined;
+    options.signal?.addEventListener("abort", () => {
+      if (opened) return;
+      abortReason = options.signal!.reason;
+      this.#abort();
+    }, { once: true });
+
+    const next = async (): Promise<WebSocketEventData | null> =>
+      JSON.parse(await __andromeda__.internal_ws_next(this.#rid));
+    let error: string | null = null;
+    const settle = (event: WebSocketEventData | null) => {
+      if (event?.type === "close" && event.wasClean) {
+        resolveClosed({ closeCode: event.code, reason: event.reason });
+      } else {
+        const failure = abortReason ?? new DOMException(
+          error ?? "WebSocket connection closed abnormally",
+          "NetworkError",
+        );
+        rejectOpened(failure);
+        rejectClosed(failure);
+      }
+    };
+
+    (async () => {
+      let event = await next();
+      while (event?.type === "error") {
+        error = event.message;
+        event = await next();
+      }
+      if (event?.type !== "open") {
+        settle(event);
+        return;
+      }
+      opened = true;
+
+      const readable = new ReadableStream<string | Uint8Array>({
+        pull: async (controller) => {
+          while (true) {
+            const event = await next();
+            if (event?.type === "text") {
+              controller.enqueue(event.data);
+            } else if (event?.type === "binary") {
+              controller.enqueue(wsHexToBytes(event.data));
+            } else if (event?.type === "error") {
+              error = event.message;
+              continue;
+            } else {
+              settle(event);
+              if (event?.type === "close" && event.wasClean) {
+                controller.close();
+              } else {
+                controller.error(
+                  new DOMException(
+                    error ?? "WebSocket connection closed abnormally",
+                    "NetworkError",
+                  ),
+                );
+              }
+            }
+            return;
+          }
+        },
+        cancel: () => this.close(),
+      });
+
+      const writable = new WritableStream<string | BufferSource>({
+        write: async (chunk) => {
+          if (typeof chunk === "string") {
+            await __andromeda__.internal_ws_send(this.#rid, chunk, "false");
+            return;
+          }
+          const bytes = ArrayBuffer.isView(chunk) ?
+            new Uint8Array(chunk.buffer, chunk.byteOffset, chunk.byteLength) :
+            new Uint8Array(chunk);
+          await __andromeda__.internal_ws_send(
+            this.#rid,
+            wsBytesToHex(bytes),
+            "true",
+          );
+        },
+        close: () => this.close(),
+        abort: () => this.close(),
+      });
+
+      resolveOpened({
+        readable,
+        writable,
+        protocol: event.protocol,
+        extensions: event.extensions,
+      });
+    })();
+  }
+
+  get url(): string {
+    return this.#url;
+  }
+
+  /** Resolves once the opening handshake completed. */
+  get opened(): Promise<WebSocketOpenInfo> {
+    return this.#opened;
+  }
+
+  /** Resolves once the connection was closed cleanly, rejects otherwise. */
+  get closed(): Promise<WebSocketCloseInfo> {
+    return this.#closed;
+  }
+
+  close(closeInfo: WebSocketCloseInfo = {}): void {
+    const closeCode = webSocketCloseCode(closeInfo.closeCode, closeInfo.reason);
+    if (this.#closing || this.#rid < 0) return;
+    this.#closing = true;
+    __andromeda__.internal_ws_close(
+      this.#rid,
+      closeCode,
+      closeInfo.reason ?? "",
+    );
+  }
+
+  #abort() {
+    if (this.#closing) return;
+    this.#closing = true;
+    __andromeda__.internal_ws_close(this.#rid, "", "");
+  }
+}
+
+/** Wrap a socket accepted by the HTTP server, used by `upgradeWebSocket`. */
+function acceptWebSocket(rid: number, url: string): WebSocket {
+  wsAcceptedRid = rid;
+  return new WebSocket(url);
+}
+
+// @ts-ignore globalThis is not readonly
+globalThis.WebSocket = WebSocket;
+// @ts-ignore globalThis is not readonly
+globalThis.WebSocketStream = WebSocketStream;
+globalThis.__andromeda_websocket_accept = acceptWebSocket;
@@ -0,0 +1,491 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! The WebSocket framing protocol (RFC 6455) on an established connection.
+//!
+//! Every socket runs a reader and a writer task. The reader reassembles
+//! fragmented messages, answers pings and follows the closing handshake,
+//! queueing events for JavaScript through a bounded channel, so a script that
+//! stops receiving stops the reads. The writer sends the frames queued by
+//! JavaScript in order.
+
+use std::{fmt::Write as _, io, time::Duration};
+
+use tokio::{
+    io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufReader},
+    sync::{mpsc, oneshot, watch},
+};
+
+const OP_CONTINUATION: u8 = 0x0;
+const OP_TEXT: u8 = 0x1;
+const OP_BINARY: u8 = 0x2;
+const OP_CLOSE: u8 = 0x8;
+const OP_PING: u8 = 0x9;
+const OP_PONG: u8 = 0xA;
+
+/// Largest message accepted, after reassembling its fragments, in bytes.
+const MAX_MESSAGE_SIZE: usize = 64 * 1024 * 1024;
+/// Outgoing messages bigger than this are sent as several fragments.
+const MAX_FRAME_SIZE: usize = 1024 * 1024;
+const READ_BUFFER_SIZE: usize = 16 * 1024;
+/// How long the peer may take to answer our close frame before the
+/// connection is dropped.
+const CLOSE_TIMEOUT: Duration = Duration::from_secs(5);
+
+pub(crate) type BoxedRead = Box<dyn AsyncRead + Send + Unpin>;
+pub(crate) type BoxedWrite = Box<dyn AsyncWrite + Send + Unpin>;
+
+/// A connection that switched to the WebSocket protocol, together with the
+/// bytes read past the end of the handshake.
+pub(crate) struct Upgraded {
+    pub read: BoxedRead,
+    pub write: BoxedWrite,
+    pub buffered: Vec<u8>,
+}
+
+/// A completed opening handshake.
+pub(crate) struct Connected {
+    pub stream: Upgraded,
+    pub protocol: String,
+    pub extensions: String,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Role {
+    Client,
+    Server,
+}
+
+/// What JavaScript receives from a socket, in order. `Close` is always last.
+pub(crate) enum Event {
+    Open {
+        protocol: String,
+        extensions: String,
+    },
+    Text(String),
+    Binary(Vec<u8>),
+    Error(String),
+    Close {
+        code: u16,
+        reason: String,
+        was_clean: bool,
+    },
+}
+
+impl Event {
+    /// Abnormal closure, reported when no close frame was received.
+    fn abnormal() -> Self {
+        Event::Close {
+            code: 1006,
+            reason: String::new(),
+            was_clean: false,
+        }
+    }
+
+    /// The event as JSON, with binary data hex encoded.
+    pub(crate) fn to_json(&self) -> String {
+        let value = match self {
+            Event::Open {
+                protocol,
+                extensions,
+            } => serde_json::json!({
+                "type": "open",
+                "protocol": protocol,
+                "extensions": extensions,
+            }),
+            Event::Text(data) => serde_json::json!({ "type": "text", "data": data }),
+            Event::Binary(data) => {
+                let mut hex = String::with_capacity(data.len() * 2);
+                for byte in data {
+                    write!(&mut hex, "{byte:02x}").unwrap();
+                }
+                serde_json::json!({ "type": "binary", "data": hex })
+            }
+            Event::Error(message) => serde_json::json!({ "type": "error", "message": message }),
+            Event::Close {
+                code,
+                reason,
+                was_clean,
+            } => serde_json::json!({
+                "type": "close",
+                "code": code,
+                "reason": reason,
+                "wasClean": was_clean,
+            }),
+        };
+        value.to_string()
+    }
+}
+
+/// Frames queued for the writer task.
+pub(crate) enum Outgoing {
+    Message {
+        text: bool,
+        payload: Vec<u8>,
+        /// Notified once the message has been handed to the connection
+        written: oneshot::Sender<()>,
+    },
+    Pong(Vec<u8>),
+    /// Start or answer the closing handshake. Later frames are dropped.
+    Close {
+        code: Option<u16>,
+        reason: String,
+    },
+}
+
+/// Reasons a connection is dropped instead of closed cleanly.
+enum Failure {
+    Io(io::Error),
+    /// The peer broke the protocol. The close frame we send carries the code.
+    Protocol(u16, &'static str),
+}
+
+impl From<io::Error> for Failure {
+    fn from(error: io::Error) -> Self {
+        Failure::Io(error)
+    }
+}
+
+struct Frame {
+    fin: bool,
+    opcode: u8,
+    payload: Vec<u8>,
+}
+
+/// Drive a socket until it is closed: run the opening handshake, then the
+/// reader and writer. Closing the socket before it is open aborts `connect`.
+pub(crate) async fn run<F>(
+    role: Role,
+    connect: F,
+    events: mpsc::Sender<Event>,
+    mut outgoing: mpsc::UnboundedReceiver<Outgoing>,
+    reply: mpsc::UnboundedSender<Outgoing>,
+) where
+    F: Future<Output = Result<Connected, String>>,
+{
+    let connected = tokio::select! {
+        connected = connect => connected,
+        // Only a close can be queued for a socket that is still connecting
+        _ = outgoing.recv() => Err("WebSocket was closed before the connection was established".to_string()),
+    };
+    let connected = match connected {
+        Ok(connected) => connected,
+        Err(message) => {
+            let _ = events.send(Event::Error(message)).await;
+            let _ = events.send(Event::abnormal()).await;
+            return;
+        }
+    };
+
+    let open = Event::Open {
+        protocol: connected.protocol,
+        extensions: connected.extensions,
+    };
+    if events.send(open).await.is_err() {
+        return;
+    }
+
+    let Upgraded {
+        read,
+        write,
+        buffered,
+    } = connected.stream;
+    let (closing_tx, closing_rx) = watch::channel(false);
+    tokio::spawn(write_loop(write, role, outgoing, closing_tx));
+    let reader = BufReader::with_capacity(
+        READ_BUFFER_SIZE,
+        AsyncReadExt::chain(io::Cursor::new(buffered), read),
+    );
+    read_loop(reader, role, events, reply, closing_rx).await;
+}
+
+async fn read_loop<R: AsyncRead + Unpin>(
+    mut reader: R,
+    role: Role,
+    events: mpsc::Sender<Event>,
+    outgoing: mpsc::UnboundedSender<Outgoing>,
+    closing: watch::Receiver<bool>,
+) {
+    // Started once, so frames arriving after our close frame can't extend it
+    let timeout = close_timeout(closing.clone());
+    tokio::pin!(timeout);
+
+    let mut partial: Option<(u8, Vec<u8>)> = None;
+    let result = loop {
+        let frame = tokio::select! {
+            frame = read_frame(&mut reader, role) => frame,
+            _ = &mut timeout => break Ok(Event::abnormal()),
+        };
+        let frame = match frame {
+            Ok(frame) => frame,
+            Err(failure) => break Err(failure),
+        };
+
+        match frame.opcode {
+            OP_PING => {
+                let _ = outgoing.send(Outgoing::Pong(frame.payload));
+            }
+            OP_PONG => {}
+            OP_CLOSE => break close_frame(&frame.payload),
+            _ => match reassemble(&mut partial, frame) {
+                Ok(Some(event)) => {
+                    if events.send(event).await.is_err() {
+                        return;
+                    }
+                }
+                Ok(None) => {}
+                Err(failure) => break Err(failure),
+            },
+        }
+    };
+
+    let close = match result {
+        Ok(close) => {
+            // Answer a close frame the peer started with, echoing its code
+            if let Event::Close {
+                code,
+                was_clean: true,
+                ..
+            } = &close
+                && !*closing.borrow()
+            {
+                let _ = outgoing.send(Outgoing::Close {
+                    code: (*code != 1005).then_some(*code),
+                    reason: String::new(),
+                });
+            }
+            close
+        }
+        Err(Failure::Protocol(code, message)) => {
+            let _ = outgoing.send(Outgoing::Close {
+                code: Some(code),
+                reason: String::new(),
+            });
+            let _ = events.send(Event::Error(message.to_string())).await;
+            Event::abnormal()
+        }
+        // A peer that just goes away is not an error, only an unclean close
+        Err(Failure::Io(error)) if error.kind() == io::ErrorKind::UnexpectedEof => {
+            Event::abnormal()
+        }
+        Err(Failure::Io(error)) => {
+            let _ = events.send(Event::Error(error.to_string())).await;
+            Event::abnormal()
+        }
+    };
+    let _ = events.send(close).await;
+}
+
+/// Resolves once our close frame went unanswered for [`CLOSE_TIMEOUT`].
+async fn close_timeout(mut closing: watch::Receiver<bool>) {
+    let _ = closing.wait_for(|closing| *closing).await;
+    tokio::time::sleep(CLOSE_TIMEOUT).await;
+}
+
+async fn read_frame<R: AsyncRead + Unpin>(reader: &mut R, role: Role) -> Result<Frame, Failure> {
+    let mut head = [0u8; 2];
+    reader.read_exact(&mut head).await?;
+    let fin = head[0] & 0x80 != 0;
+    let opcode = head[0] & 0x0F;
+    let masked = head[1] & 0x80 != 0;
+
+    // No extension was negotiated, so no reserved bit may be set
+    if head[0] & 0x70 != 0 {
+        return Err(Failure::Protocol(1002, "Reserved bits must not be set"));
+    }
+    // Clients mask every frame they send and servers never do
+    if masked != (role == Role::Server) {
+        return Err(Failure::Protocol(1002, "Frame masking is invalid"));
+    }
+
+    let length = match head[1] & 0x7F {
+        126 => reader.read_u16().await? as u64,
+        127 => reader.read_u64().await?,
+        length => length as u64,
+    };
+    if opcode & 0x08 != 0 && (!fin || length > 125) {
+        return Err(Failure::Protocol(
+            1002,
+            "Control frames must not be fragmented",
+        ));
+    }
+    if length > MAX_MESSAGE_SIZE as u64 {
+        return Err(Failure::Protocol(1009, "Message is too big"));
+    }
+
+    let mut mask = [0u8; 4];
+    if masked {
+        reader.read_exact(&mut mask).await?;
+    }
+    let mut payload = vec![0u8; length as usize];
+    reader.read_exact(&mut payload).await?;
+    if masked {
+        for (i, byte) in payload.iter_mut().enumerate() {
+            *byte ^= mask[i % 4];
+        }
+    }
+
+    Ok(Frame {
+        fin,
+        opcode,
+        payload,
+    })
+}
+
+/// Collect the frames of a data message, returning it once complete.
+fn reassemble(partial: &mut Option<(u8, Vec<u8>)>, frame: Frame) -> Result<Option<Event>, Failure> {
+    let (opcode, payload) = match (frame.opcode, partial.take()) {
+        (OP_TEXT | OP_BINARY, None) => (frame.opcode, frame.payload),
+        (OP_TEXT | OP_BINARY, Some(_)) => {
+            return Err(Failure::Protocol(1002, "Expected a continuation frame"));
+        }
+        (OP_CONTINUATION, Some((opcode, mut payload))) => {
+            if payload.len() + frame.payload.len() > MAX_MESSAGE_SIZE {
+                return Err(Failure::Protocol(1009, "Message is too big"));
+            }
+            payload.extend_from_slice(&frame.payload);
+            (opcode, payload)
+        }
+        (OP_CONTINUATION, None) => {
+            return Err(Failure::Protocol(1002, "Unexpected continuation frame"));
+        }
+        _ => return Err(Failure::Protocol(1002, "Unknown opcode")),
+    };
+
+    if !frame.fin {
+        *partial = Some((opcode, payload));
+        return Ok(None);
+    }
+    if opcode == OP_BINARY {
+        return Ok(Some(Event::Binary(payload)));
+    }
+    String::from_utf8(payload)
+        .map(|text| Some(Event::Text(text)))
+        .map_err(|_| Failure::Protocol(1007, "Text message is not valid UTF-8"))
+}
+
+/// Parse the payload of a close frame into the clean close it reports.
+fn close_frame(payload: &[u8]) -> Result<Event, Failure> {
+    let (code, reason) = match payload {
+        [] => (1005, String::new()),
+        [_] => return Err(Failure::Protocol(1002, "Close frame is truncated")),
+        [high, low, reason @ ..] => {
+            let code = u16::from_be_bytes([*high, *low]);
+            if !is_valid_close_code(code) {
+                return Err(Failure::Protocol(1002, "Invalid close code"));
+            }
+            let reason = std::str::from_utf8(reason)
+                .map_err(|_| Failure::Protocol(1007, "Close reason is not valid UTF-8"))?;
+            (code, reason.to_string())
+        }
+    };
+    Ok(Event::Close {
+        code,
+        reason,
+        was_clean: true,
+    })
+}
+
+/// Codes a peer may send, see RFC 6455 section 7.4.
+fn is_valid_close_code(code: u16) -> bool {
+    matches!(code, 1000..=1003 | 1007..=1014 | 3000..=4999)
+}
+
+async fn write_loop(
+    mut writer: BoxedWrite,
+    role: Role,
+    mut outgoing: mpsc::UnboundedReceiver<Outgoing>,
+    closing: watch::Sender<bool>,
+) {
+    while let Some(message) = outgoing.recv().await {
+        let result = match message {
+            Outgoing::Message {
+                text,
+                payload,
+                written,
+            } => {
+                let result = write_message(&mut writer, role, text, &payload).await;
+                let _ = written.send(());
+                result
+            }
+            Outgoing::Pong(payload) => {
+                writer
+                    .write_all(&encode_frame(role, true, OP_PONG, &payload))
+                    .await
+            }
+            Outgoing::Close { code, reason } => {
+                let mut payload = Vec::new();
+                if let Some(code) = code {
+                    payload.extend_from_slice(&code.to_be_bytes());
+                    payload.extend_from_slice(reason.as_bytes());
+                }
+                let _ = writer
+                    .write_all(&encode_frame(role, true, OP_CLOSE, &payload))
+                    .await;
+                let _ = writer.flush().await;
+                closing.send_replace(true);
+                break;
+            }
+        };
+        if result.is_err() || writer.flush().await.is_err() {
+            break;
+        }
+    }
+    let _ = writer.shutdown().await;
+}
+
+async fn write_message(
+    writer: &mut BoxedWrite,
+    role: Role,
+    text: bool,
+    payload: &[u8],
+) -> io::Result<()> {
+    let opcode = if text { OP_TEXT } else { OP_BINARY };
+    if payload.is_empty() {
+        return writer
+            .write_all(&encode_frame(role, true, opcode, payload))
+            .await;
+    }
+    let count = payload.len().div_ceil(MAX_FRAME_SIZE);
+    for (i, fragment) in payload.chunks(MAX_FRAME_SIZE).enumerate() {
+        let opcode = if i == 0 { opcode } else { OP_CONTINUATION };
+        writer
+            .write_all(&encode_frame(role, i + 1 == count, opcode, fragment))
+            .await?;
+    }
+    Ok(())
+}
+
+fn encode_frame(role: Role, fin: bool, opcode: u8, payload: &[u8]) -> Vec<u8> {
+    let mut frame = Vec::with_capacity(payload.len() + 14);
+    frame.push(if fin { 0x80 } else { 0 } | opcode);
+
+    let mask_bit = if role == Role::Client { 0x80 } else { 0 };
+    match payload.len() {
+        length if length < 126 => frame.push(mask_bit | length as u8),
+        length if length <= u16::MAX as usize => {
+            frame.push(mask_bit | 126);
+            frame.extend_from_slice(&(length as u16).to_be_bytes());
+        }
+        length => {
+            frame.push(mask_bit | 127);
+            frame.extend_from_slice(&(length as u64).to_be_bytes());
+        }
+    }
+
+    if role == Role::Client {
+        let mut mask = [0u8; 4];
+        mask.copy_from_slice(&uuid::Uuid::new_v4().as_bytes()[..4]);
+        frame.extend_from_slice(&mask);
+        frame.extend(
+            payload
+                .iter()
+                .enumerate()
+                .map(|(i, byte)| byte ^ mask[i % 4]),
+        );
+    } else {
+        frame.extend_from_slice(payload);
+    }
+    frame
+}
@@ -0,0 +1,465 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use std::{
+    path::PathBuf,
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+};
+
+use andromeda_core::{
+    Extension, ExtensionOp, HostData, MacroTask, OpsStorage, Permissions, ResourceTable, Rid,
+    Runtime, RuntimeConfig, check_permission,
+};
+use nova_vm::{
+    ecmascript::{
+        builtins::{
+            ArgumentsList,
+            promise_objects::promise_abstract_operations::promise_capability_records::PromiseCapability,
+        },
+        execution::{Agent, JsResult, agent::ExceptionType},
+        types::{IntoValue, Value},
+    },
+    engine::{
+        Global,
+        context::{Bindable, GcScope},
+    },
+};
+use tokio::sync::{Mutex as TokioMutex, mpsc, watch};
+
+use crate::{
+    RuntimeMacroTask, recommended_builtins, recommended_eventloop_handler, recommended_extensions,
+};
+
+/// Parent side of a worker: a channel to send messages into the worker and one
+/// to receive the messages and errors it posts back.
+#[derive(Clone)]
+struct WorkerHandle {
+    to_worker: mpsc::UnboundedSender<String>,
+    from_worker: Arc<TokioMutex<mpsc::UnboundedReceiver<String>>>,
+    terminated: Arc<AtomicBool>,
+    terminate_tx: Arc<watch::Sender<bool>>,
+}
+
+struct WorkerResources {
+    workers: ResourceTable<WorkerHandle>,
+}
+
+/// Worker side of the channels, only present in the storage of a worker runtime.
+struct WorkerScope {
+    name: String,
+    main: String,
+    to_parent: mpsc::UnboundedSender<String>,
+    from_parent: Arc<TokioMutex<mpsc::UnboundedReceiver<String>>>,
+}
+
+/// Worker extension for Andromeda.
+/// This extension provides the `Worker` class, running modules on their own
+/// thread with a separate agent and event loop.
+#[derive(Default)]
+pub struct WorkerExt;
+
+#[hotpath::measure_all]
+impl WorkerExt {
+    pub fn new_extension() -> Extension {
+        Extension {
+            name: "worker",
+            ops: vec![
+                ExtensionOp::new(
+                    "internal_worker_create",
+                    Self::internal_worker_create,
+                    2,
+                    false,
+                ),
+                ExtensionOp::new("internal_worker_post", Self::internal_worker_post, 2, false),
+                ExtensionOp::new("internal_worker_recv", Self::internal_worker_recv, 1, false),
+                ExtensionOp::new(
+                    "internal_worker_terminate",
+                    Self::internal_worker_terminate,
+                    1,
+                    false,
+                ),
+                ExtensionOp::new(
+                    "internal_worker_scope_name",
+                    Self::internal_worker_scope_name,
+                    0,
+                    false,
+                ),
+                ExtensionOp::new(
+                    "internal_worker_scope_main",
+                    Self::internal_worker_scope_main,
+                    0,
+                    false,
+                ),
+                ExtensionOp::new(
+                    "internal_worker_scope_post",
+                    Self::internal_worker_scope_post,
+                    1,
+                    false,
+                ),
+                ExtensionOp::new(
+                    "internal_worker_scope_recv",
+                    Self::internal_worker_scope_recv,
+                    0,
+                    false,
+                ),
+                ExtensionOp::new(
+                    "internal_worker_scope_close",
+                    Self::internal_worker_scope_close,
+                    0,
+                    false,
+                ),
+            ],
+            storage: Some(Box::new(|storage: &mut OpsStorage| {
+                storage.insert(WorkerResources {
+                    workers: ResourceTable::new(),
+                });
+            })),
+            files: vec![include_str!("./mod.ts")],
+        }
+    }
+
+    /// Start a worker running the module at the given path or `file:` URL, returning its rid.
+    fn internal_worker_create<'gc>(
+        agent: &mut Agent,
+        _this: Value,
+        args: ArgumentsList,
+        mut gc: GcScope<'gc, '_>,
+    ) -> JsResult<'gc, Value<'gc>> {
+        let specifier_binding = args.get(0).to_string(agent, gc.reborrow()).unbind()?;
+        let name_binding = args.get(1).to_string(agent, gc.reborrow()).unbind()?;
+        let specifier = specifier_binding
+            .as_str(agent)
+            .expect("String is not valid UTF-8")
+            .to_string();
+        let name = name_binding
+            .as_str(agent)
+            .expect("String is not valid UTF-8")
+            .to_string();
+
+        let path = match url::Url::parse(&specifier) {
+            Ok(url) if url.scheme() == "file" => url.to_file_path().ok(),
+            Ok(url) if url.scheme().len() > 1 => None,
+            _ => Some(PathBuf::from(&specifier)),
+        };
+        let Some(path) = path.filter(|path| path.is_file()) else {
+            let message = format!("Worker module not found: {specifier}");
+            return Err(agent
+                .throw_exception(ExceptionType::TypeError, message, gc.nogc())
+                .unbind());
+        };
+
+        if let Err(error) = check_permission::<RuntimeMacroTask>(agent, |p| p.check_read(&path)) {
+            return Err(error
+                .into_js_error::<RuntimeMacroTask>(agent, gc.nogc())
+                .unbind());
+        }
+
+        let host_data = agent.get_host_data();
+        let host_data: &HostData<RuntimeMacroTask> = host_data.downcast_ref().unwrap();
+        // Workers inherit the permissions their parent has at the time they are created
+        let permissions = host_data.permissions.borrow().clone();
+
+        let (to_worker, from_parent) = mpsc::unbounded_channel();
+        let (to_parent, from_worker) = mpsc::unbounded_channel();
+        let terminated = Arc::new(AtomicBool::new(false));
+
+        let scope = WorkerScope {
+            name: name.clone(),
+            main: std::path::absolute(&path)
+                .unwrap_or(path)
+                .to_string_lossy()
+                .to_string(),
+            to_parent,
+            from_parent: Arc::new(TokioMutex::new(from_parent)),
+        };
+        let thread = std::thread::Builder::new()
+            .name(format!("worker {name}"))
+            .spawn({
+                let terminated = terminated.clone();
+                move || run_worker(permissions, scope, terminated)
+            });
+        if let Err(e) = thread {
+            let message = format!("Failed to start worker: {e}");
+            return Err(agent
+                .throw_exception(ExceptionType::Error, message, gc.nogc())
+                .unbind());
+        }
+
+        let storage = host_data.storage.borrow();
+        let resources: &WorkerResources = storage.get().unwrap();
+        let rid = resources.workers.push(WorkerHandle {
+            to_worker,
+            from_worker: Arc::new(TokioMutex::new(from_worker)),
+            terminated,
+            terminate_tx: Arc::new(watch::channel(false).0),
+        });
+        drop(storage);
+
+        Ok(Value::from_f64(agent, rid.index() as f64, gc.nogc()).unbind())
+    }
+
+    /// Send a serialized message to a worker.
+    fn internal_worker_post<'gc>(
+        agent: &mut Agent,
+        _this: Value,
+        args: ArgumentsList,
+        mut gc: GcScope<'gc, '_>,
+    ) -> JsResult<'gc, Value<'gc>> {
+        let rid = Self::rid_arg(agent, &args, gc.reborrow())?;
+        let message_binding = args.get(1).to_string(agent, gc.reborrow()).unbind()?;
+        let message = message_binding
+            .as_str(agent)
+            .expect("String is not valid UTF-8")
+            .to_string();
+
+        // Messages sent to a worker that already exited are dropped, like on the web
+        if let Some(worker) = Self::worker(agent, rid) {
+            let _ = worker.to_worker.send(message);
+        }
+
+        Ok(Value::Undefined)
+    }
+
+    /// Receive the next message posted by a worker.
+    /// Resolves with an empty string once the worker exited or was terminated.
+    fn internal_worker_recv<'gc>(
+        agent: &mut Agent,
+        _this: Value,
+        args: ArgumentsList,
+        mut gc: GcScope<'gc, '_>,
+    ) -> JsResult<'gc, Value<'gc>> {
+        let rid = Self::rid_arg(agent, &args, gc.reborrow())?;
+
+        let promise_capability = PromiseCapability::new(agent, gc.nogc());
+        let root_value = Global::new(agent, promise_capability.promise().into_value().unbind());
+
+        let worker = Self::worker(agent, rid);
+        let host_data = agent.get_host_data();
+        let host_data: &HostData<RuntimeMacroTask> = host_data.downcast_ref().unwrap();
+        let macro_task_tx = host_data.macro_task_tx();
+
+        host_data.spawn_macro_task(async move {
+            let message = match worker {
+                Some(worker) => {
+                    let mut terminate_rx = worker.terminate_tx.subscribe();
+                    tokio::select! {
+                        message = async { worker.from_worker.lock().await.recv().await } => message,
+                        _ = terminate_rx.wait_for(|terminated| *terminated) => None,
+                    }
+                }
+                None => None,
+            };
+            macro_task_tx
+                .send(MacroTask::User(RuntimeMacroTask::ResolvePromiseWithString(
+                    root_value,
+                    message.unwrap_or_default(),
+                )))
+                .unwrap();
+        });
+
+        Ok(Value::Promise(promise_capability.promise()).unbind())
+    }
+
+    /// Stop a worker, dropping any message it has not handled yet.
+    fn internal_worker_terminate<'gc>(
+        agent: &mut Agent,
+        _this: Value,
+        args: ArgumentsList,
+        mut gc: GcScope<'gc, '_>,
+    ) -> JsResult<'gc, Value<'gc>> {
+        let rid = Self::rid_arg(agent, &args, gc.reborrow())?;
+
+        let host_data = agent.get_host_data();
+        let host_data: &HostData<RuntimeMacroTask> = host_data.downcast_ref().unwrap();
+        let storage = host_data.storage.borrow();
+        let resources: &WorkerResources = storage.get().unwrap();
+        if let Some(worker) = resources.workers.remove(rid) {
+            // The worker's event loop stops at its next iteration, which dropping
+            // `to_worker` triggers by waking up its pending receive
+            worker.terminated.store(true, Ordering::Release);
+            worker.terminate_tx.send_replace(true);
+        }
+
+        Ok(Value::Undefined)
+    }
+
+    /// The name given to this worker by its parent.
+    fn internal_worker_scope_name<'gc>(
+        agent: &mut Agent,
+        _this: Value,
+        _args: ArgumentsList,
+        gc: GcScope<'gc, '_>,
+    ) -> JsResult<'gc, Value<'gc>> {
+        let name = Self::scope_field(agent, |scope| scope.name.clone());
+        Ok(Value::from_string(agent, name, gc.nogc()).unbind())
+    }
+
+    /// The path of the module this worker runs.
+    fn internal_worker_scope_main<'gc>(
+        agent: &mut Agent,
+        _this: Value,
+        _args: ArgumentsList,
+        gc: GcScope<'gc, '_>,
+    ) -> JsResult<'gc, Value<'gc>> {
+        let main = Self::scope_field(agent, |scope| scope.main.clone());
+        Ok(Value::from_string(agent, main, gc.nogc()).unbind())
+    }
+
+    /// Post a serialized message from the worker to its parent.
+    fn internal_worker_scope_post<'gc>(
+        agent: &mut Agent,
+        _this: Value,
+        args: ArgumentsList,
+        mut gc: GcScope<'gc, '_>,
+    ) -> JsResult<'gc, Value<'gc>> {
+        let message_binding = args.get(0).to_string(agent, gc.reborrow()).unbind()?;
+        let message = message_binding
+            .as_str(agent)
+            .expect("String is not valid UTF-8")
+            .to_string();
+
+        let host_data = agent.get_host_data();
+        let host_data: &HostData<RuntimeMacroTask> = host_data.downcast_ref().unwrap();
+        let storage = host_data.storage.borrow();
+        if let Some(scope) = storage.get::<WorkerScope>() {
+            let _ = scope.to_parent.send(message);
+        }
+
+        Ok(Value::Undefined)
+    }
+
+    /// Receive the next message sent by the parent.
+    /// Resolves with an empty string once the parent dropped the worker.
+    fn internal_worker_scope_recv<'gc>(
+        agent: &mut Agent,
+        _this: Value,
+        _args: ArgumentsList,
+        gc: GcScope<'gc, '_>,
+    ) -> JsResult<'gc, Value<'gc>> {
+        let promise_capability = PromiseCapability::new(agent, gc.nogc());
+        let root_value = Global::new(agent, promise_capability.promise().into_value().unbind());
+
+        let host_data = agent.get_host_data();
+        let host_data: &HostData<RuntimeMacroTask> = host_data.downcast_ref().unwrap();
+        let macro_task_tx = host_data.macro_task_tx();
+        let from_parent = host_data
+            .storage
+            .borrow()
+            .get::<WorkerScope>()
+            .map(|scope| scope.from_parent.clone());
+
+        host_data.spawn_macro_task(async move {
+            let message = match from_parent {
+                Some(from_parent) => from_parent.lock().await.recv().await,
+                None => None,
+            };
+            macro_task_tx
+                .send(MacroTask::User(RuntimeMacroTask::ResolvePromiseWithString(
+                    root_value,
+                    message.unwrap_or_default(),
+                )))
+                .unwrap();
+        });
+
+        Ok(Value::Promise(promise_capability.promise()).unbind())
+    }
+
+    /// Stop the worker's event loop from inside the worker.
+    fn internal_worker_scope_close<'gc>(
+        agent: &mut Agent,
+        _this: Value,
+        _args: ArgumentsList,
+        _gc: GcScope<'gc, '_>,
+    ) -> JsResult<'gc, Value<'gc>> {
+        let host_data = agent.get_host_data();
+        let host_data: &HostData<RuntimeMacroTask> = host_data.downcast_ref().unwrap();
+        host_data.terminated.store(true, Ordering::Release);
+
+        Ok(Value::Undefined)
+    }
+
+    fn rid_arg<'gc>(
+        agent: &mut Agent,
+        args: &ArgumentsList,
+        gc: GcScope<'gc, '_>,
+    ) -> JsResult<'gc, Rid> {
+        let rid = args.get(0).to_number(agent, gc).unbind()?.into_f64(agent);
+        Ok(Rid::from_index(rid as u32))
+    }
+
+    fn scope_field(agent: &Agent, field: impl FnOnce(&WorkerScope) -> String) -> String {
+        let host_data = agent.get_host_data();
+        let host_data: &HostData<RuntimeMacroTask> = host_data.downcast_ref().unwrap();
+        let storage = host_data.storage.borrow();
+        storage.get::<WorkerScope>().map(field).unwrap_or_default()
+    }
+
+    fn worker(agent: &Agent, rid: Rid) -> Option<WorkerHandle> {
+        let host_data = agent.get_host_data();
+        let host_data: &HostData<RuntimeMacroTask> = host_data.downcast_ref().unwrap();
+        let storage = host_data.storage.borrow();
+        let resources: &WorkerResources = storage.get().unwrap();
+        resources.workers.get(rid)
+    }
+}
+
+/// Body of a worker thread: run a new runtime with its own Tokio runtime.
+/// The worker's module is imported by `worker_global.ts`, which also reports
+/// uncaught errors to the parent.
+fn run_worker(permissions: Permissions, scope: WorkerScope, terminated: Arc<AtomicBool>) {
+    let rt = match tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+    {
+        Ok(rt) => rt,
+        Err(e) => {
+            let message = serde_json::json!({
+                "type": "error",
+                "message": format!("Failed to initialize worker runtime: {e}"),
+                "filename": scope.main,
+            });
+            let _ = scope.to_parent.send(message.to_string());
+            return;
+        }
+    };
+
+    // Like the main runtime, the agent runs on a blocking thread so the Tokio
+    // tasks it spawns are driven by this one
+    let nova_thread = rt.spawn_blocking(move || {
+        let (macro_task_tx, macro_task_rx) = std::sync::mpsc::channel();
+        let mut host_data = HostData::with_permissions(macro_task_tx, permissions);
+        host_data.terminated = terminated;
+        host_data.storage.borrow_mut().insert(scope);
+
+        let mut builtins = recommended_builtins();
+        builtins.push(include_str!("./worker_global.ts"));
+
+        let runtime = Runtime::new(
+            RuntimeConfig {
+                no_strict: false,
+                files: vec![],
+                verbose: false,
+                extensions: recommended_extensions(),
+                builtins,
+                eventloop_handler: recommended_eventloop_handler,
+                macro_task_rx,
+                import_map: None,
+                coverage: None,
+                module_graph: None,
+                module_cache: None,
+                args: None,
+                transpile_cache: None,
+            },
+            host_data,
+        );
+        // Dropping the output drops the scope, which closes the channel for
+        // the parent to see the worker is done. Without files, running it
+        // cannot fail.
+        let _ = runtime.run();
+    });
+    let _ = rt.block_on(nova_thread);
+}
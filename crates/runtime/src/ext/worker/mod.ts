@@ -0,0 +1,148 @@
+// deno-lint-ignore-file no-explicit-any
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+// Based on the HTML Living Standard - Workers
+// https://html.spec.whatwg.org/multipage/workers.html#dedicated-workers-and-the-worker-interface
+
+interface WorkerOptions {
+  type?: "classic" | "module";
+  name?: string;
+}
+
+interface StructuredSerializeOptions {
+  transfer?: any[];
+}
+
+const structuredCloneInternals = (globalThis as any).__andromeda_structured_clone;
+
+/**
+ * Serialize a message into the envelope understood by the other side of a worker.
+ * Transferred values are copied, as the two agents don't share memory.
+ */
+function serializeWorkerMessage(message: any): string {
+  return JSON.stringify({
+    type: "message",
+    data: structuredCloneInternals.serialize(message),
+  });
+}
+
+/**
+ * The Worker interface runs a module on a separate thread with its own event loop.
+ */
+class Worker extends EventTarget {
+  #rid: number;
+  #name: string;
+  #terminated = false;
+  #handlers: Record<string, ((event: any) => any) | null> = {
+    message: null,
+    messageerror: null,
+    error: null,
+  };
+
+  constructor(specifier: string | URL, options: WorkerOptions = {}) {
+    super();
+    if (options.type !== "module") {
+      throw new TypeError(
+        'Only module workers are supported, use `new Worker(url, { type: "module" })`',
+      );
+    }
+    this.#name = options.name ?? "";
+    const url = specifier instanceof URL ? specifier.href : String(specifier);
+    this.#rid = __andromeda__.internal_worker_create(url, this.#name);
+    this.#poll();
+  }
+
+  async #poll() {
+    while (!this.#terminated) {
+      const envelope = await __andromeda__.internal_worker_recv(this.#rid);
+      if (envelope === "") break;
+      this.#handle(JSON.parse(envelope));
+    }
+    this.terminate();
+  }
+
+  #handle(envelope: any) {
+    if (this.#terminated) return;
+    if (envelope.type === "message") {
+      let data;
+      try {
+        data = structuredCloneInternals.deserialize(envelope.data);
+      } catch (error) {
+        this.dispatchEvent(new MessageEvent("messageerror", { data: error }));
+        return;
+      }
+      this.dispatchEvent(new MessageEvent("message", { data }));
+    } else if (envelope.type === "error") {
+      const event = new ErrorEvent("error", {
+        cancelable: true,
+        message: envelope.message,
+        filename: envelope.filename ?? "",
+        lineno: envelope.lineno ?? 0,
+        colno: envelope.colno ?? 0,
+      });
+      this.dispatchEvent(event);
+      // Unhandled worker errors are fatal for the parent, like uncaught exceptions
+      if (!event.defaultPrevented) {
+        const name = this.#name ? ` "${this.#name}"` : "";
+        console.error(`Uncaught (in worker${name}) ${envelope.message}`);
+        __andromeda__.internal_exit(1);
+      }
+    }
+  }
+
+  /**
+   * Send a message to the worker, cloned with the structured clone algorithm.
+   */
+  postMessage(
+    message: any,
+    _transferOrOptions?: any[] | StructuredSerializeOptions,
+  ): void {
+    if (this.#terminated) return;
+    __andromeda__.internal_worker_post(this.#rid, serializeWorkerMessage(message));
+  }
+
+  /**
+   * Stop the worker immediately, discarding messages it has not handled yet.
+   */
+  terminate(): void {
+    if (this.#terminated) return;
+    this.#terminated = true;
+    __andromeda__.internal_worker_terminate(this.#rid);
+  }
+
+  #setHandler(type: string, handler: ((event: any) => any) | null) {
+    const previous = this.#handlers[type];
+    if (previous) this.removeEventListener(type, previous);
+    this.#handlers[type] = typeof handler === "function" ? handler : null;
+    if (this.#handlers[type]) this.addEventListener(type, this.#handlers[type]!);
+  }
+
+  get onmessage(): ((event: MessageEvent) => any) | null {
+    return this.#handlers.message;
+  }
+
+  set onmessage(handler: ((event: MessageEvent) => any) | null) {
+    this.#setHandler("message", handler);
+  }
+
+  get onmessageerror(): ((event: MessageEvent) => any) | null {
+    return this.#handlers.messageerror;
+  }
+
+  set onmessageerror(handler: ((event: MessageEvent) => any) | null) {
+    this.#setHandler("messageerror", handler);
+  }
+
+  get onerror(): ((event: ErrorEvent) => any) | null {
+    return this.#handlers.error;
+  }
+
+  set onerror(handler: ((event: ErrorEvent) => any) | null) {
+    this.#setHandler("error", handler);
+  }
+}
+
+// @ts-ignore globalThis is not readonly
+globalThis.Worker = Worker;
@@ -0,0 +1,142 @@
+// deno-lint-ignore-file no-explicit-any
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+// Global scope of a dedicated worker, loaded as a builtin of worker runtimes only.
+// https://html.spec.whatwg.org/multipage/workers.html#dedicated-workers-and-the-dedicatedworkerglobalscope-interface
+
+(() => {
+  const structuredCloneInternals = (globalThis as any).__andromeda_structured_clone;
+  const main: string = __andromeda__.internal_worker_scope_main();
+  const handlers: Record<string, ((event: any) => any) | null> = {
+    message: null,
+    messageerror: null,
+    error: null,
+  };
+  const listeners = new Map<string, Set<(event: any) => any>>();
+  let closed = false;
+
+  // Listeners are called directly rather than through an EventTarget so their
+  // exceptions can be reported to the parent
+  function dispatch(event: Event) {
+    const handler = handlers[event.type];
+    const callbacks = [
+      ...(handler ? [handler] : []),
+      ...(listeners.get(event.type) ?? []),
+    ];
+    for (const callback of callbacks) {
+      callback.call(globalThis, event);
+    }
+  }
+
+  function reportError(error: any) {
+    const message = error instanceof Error
+      ? `${error.name}: ${error.message}`
+      : String(error);
+    const event = new ErrorEvent("error", {
+      cancelable: true,
+      message,
+      filename: main,
+      error,
+    });
+    try {
+      dispatch(event);
+    } catch {
+      // An error thrown by an error handler is reported as the original one
+    }
+    if (event.defaultPrevented) return;
+
+    __andromeda__.internal_worker_scope_post(
+      JSON.stringify({ type: "error", message, filename: main }),
+    );
+    close();
+  }
+
+  function handle(envelope: any) {
+    let data;
+    try {
+      data = structuredCloneInternals.deserialize(envelope.data);
+    } catch (error) {
+      dispatch(new MessageEvent("messageerror", { data: error }));
+      return;
+    }
+    dispatch(new MessageEvent("message", { data }));
+  }
+
+  async function poll() {
+    while (!closed) {
+      const envelope = await __andromeda__.internal_worker_scope_recv();
+      if (envelope === "") break;
+      try {
+        handle(JSON.parse(envelope));
+      } catch (error) {
+        reportError(error);
+      }
+    }
+  }
+
+  function postMessage(message: any, _transferOrOptions?: any): void {
+    if (closed) return;
+    __andromeda__.internal_worker_scope_post(JSON.stringify({
+      type: "message",
+      data: structuredCloneInternals.serialize(message),
+    }));
+  }
+
+  function close(): void {
+    if (closed) return;
+    closed = true;
+    __andromeda__.internal_worker_scope_close();
+  }
+
+  function addEventListener(type: string, listener: (event: any) => any) {
+    if (typeof listener !== "function") return;
+    if (!listeners.has(type)) listeners.set(type, new Set());
+    listeners.get(type)!.add(listener);
+  }
+
+  function removeEventListener(type: string, listener: (event: any) => any) {
+    listeners.get(type)?.delete(listener);
+  }
+
+  function dispatchEvent(event: Event): boolean {
+    dispatch(event);
+    return !event.defaultPrevented;
+  }
+
+  const properties: PropertyDescriptorMap = {
+    self: { value: globalThis, writable: true, configurable: true },
+    name: {
+      value: __andromeda__.internal_worker_scope_name(),
+      writable: true,
+      configurable: true,
+    },
+    postMessage: { value: postMessage, writable: true, configurable: true },
+    close: { value: close, writable: true, configurable: true },
+    addEventListener: {
+      value: addEventListener,
+      writable: true,
+      configurable: true,
+    },
+    removeEventListener: {
+      value: removeEventListener,
+      writable: true,
+      configurable: true,
+    },
+    dispatchEvent: { value: dispatchEvent, writable: true, configurable: true },
+  };
+  for (const type of Object.keys(handlers)) {
+    properties[`on${type}`] = {
+      get: () => handlers[type],
+      set: (handler: any) => {
+        handlers[type] = typeof handler === "function" ? handler : null;
+      },
+      configurable: true,
+    };
+  }
+  Object.defineProperties(globalThis, properties);
+
+  poll();
+  import(main).catch(reportError);
+})();
@@ -13,8 +13,9 @@ use nova_vm::{
 };
 
 use crate::{
-    BroadcastChannelExt, ConsoleExt, CronExt, FetchExt, FfiExt, FileExt, NetExt, ProcessExt,
-    RuntimeMacroTask, StreamsExt, TimeExt, TlsExt, URLExt, WebExt, WebIDLExt, WebLocksExt,
+    BroadcastChannelExt, CommandExt, ConsoleExt, CronExt, FetchExt, FfiExt, FileExt, NetExt,
+    PermissionsExt, ProcessExt, RuntimeMacroTask, StreamsExt, TestExt, TimeExt, TlsExt, URLExt,
+    WebExt, WebIDLExt, WebLocksExt, WebSocketExt, WorkerExt,
 };
 
 #[cfg(not(feature = "virtualfs"))]
@@ -34,6 +35,7 @@ pub fn recommended_extensions() -> Vec<Extension> {
         TimeExt::new_extension(),
         CronExt::new_extension(),
         ProcessExt::new_extension(),
+        PermissionsExt::new_extension(),
         URLExt::new_extension(),
         WebExt::new_extension(),
         WebLocksExt::new_extension(),
@@ -42,8 +44,12 @@ pub fn recommended_extensions() -> Vec<Extension> {
         FetchExt::new_extension(),
         NetExt::new_extension(),
         StreamsExt::new_extension(),
+        CommandExt::new_extension(),
         TlsExt::new_extension(),
+        WebSocketExt::new_extension(),
         FfiExt::new_extension(),
+        WorkerExt::new_extension(),
+        TestExt::new_extension(),
         #[cfg(feature = "serve")]
         crate::ServeExt::new_extension(),
         #[cfg(feature = "canvas")]
@@ -0,0 +1,7 @@
+import { format } from "./format.ts";
+
+export function renderChart(values: number[]): string {
+  return values
+    .map((value, index) => format(`#${index}`, value) + " " + "*".repeat(value))
+    .join("\n");
+}
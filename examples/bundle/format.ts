@@ -0,0 +1,9 @@
+const separator = ": ";
+
+export function format(name: string, count: number): string {
+  return `${name}${separator}${count}`;
+}
+
+export function unused(): string {
+  return "never bundled";
+}
@@ -0,0 +1,13 @@
+// Bundle this example with:
+//   andromeda bundle examples/bundle/main.ts dist/main.js
+// `unused` from format.ts is tree-shaken away, and `chart.ts` is split into its own chunk.
+import { format } from "./format.ts";
+
+console.log(format("Andromeda", 3));
+
+if (Andromeda.args.includes("--chart")) {
+  const { renderChart } = await import("./chart.ts");
+  console.log(renderChart([1, 4, 2]));
+}
+
+export { format };
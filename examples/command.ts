@@ -0,0 +1,34 @@
+// Run with `andromeda run --allow-run=echo,cat,sleep examples/command.ts`
+
+const decoder = new TextDecoder();
+const encoder = new TextEncoder();
+
+// Run a program to completion and collect its output
+const output = await new Andromeda.Command("echo", {
+  args: ["Hello from a subprocess"],
+}).output();
+console.log("exit code:", output.code);
+console.log("stdout:", decoder.decode(output.stdout).trim());
+
+// Stream data through a child process
+const cat = new Andromeda.Command("cat", {
+  stdin: "piped",
+  stdout: "piped",
+}).spawn();
+
+const writer = cat.stdin.getWriter();
+await writer.write(encoder.encode("line one\n"));
+await writer.write(encoder.encode("line two\n"));
+await writer.close();
+
+for await (const chunk of cat.stdout) {
+  console.log("cat:", decoder.decode(chunk).trimEnd());
+}
+console.log("cat status:", await cat.status);
+
+// Terminate a long running process
+const sleep = new Andromeda.Command("sleep", { args: ["30"] }).spawn();
+console.log("spawned sleep with pid", sleep.pid);
+sleep.kill("SIGTERM");
+const status = await sleep.status;
+console.log("sleep terminated by", status.signal, "with code", status.code);
@@ -0,0 +1,7 @@
+const { isEven } = require("./parity.cjs");
+
+exports.count = 0;
+exports.isEven = isEven;
+exports.increment = function increment() {
+  exports.count++;
+};
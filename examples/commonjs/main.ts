@@ -0,0 +1,8 @@
+// CommonJS modules are imported like ES modules: the default export is
+// `module.exports` and its properties are also available as named exports
+import counter, { increment } from "./counter.cjs";
+
+increment();
+increment();
+console.log(`count: ${counter.count}`);
+console.log(`even: ${counter.isEven(counter.count)}`);
@@ -0,0 +1,6 @@
+// Requires the module requiring it, which gets its partially filled exports
+const counter = require("./counter.cjs");
+
+exports.isEven = function isEven(n) {
+  return n % 2 === 0 && typeof counter.increment === "function";
+};
@@ -0,0 +1,8 @@
+// JSON, text and binary files are imported with the `type` import attribute
+import config from "./config.json" with { type: "json" };
+import greeting from "./greeting.txt" with { type: "text" };
+import bytes from "./greeting.txt" with { type: "bytes" };
+
+console.log(`${config.name}: ${config.features.join(", ")}`);
+console.log(greeting.trim());
+console.log(`${bytes.length} bytes, starting with ${bytes[0]}`);
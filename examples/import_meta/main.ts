@@ -0,0 +1,9 @@
+// `import.meta` describes the current module
+console.log(`url: ${import.meta.url}`);
+console.log(`filename: ${import.meta.filename}`);
+console.log(`dirname: ${import.meta.dirname}`);
+console.log(`resolved: ${import.meta.resolve("./other.ts")}`);
+
+if (import.meta.main) {
+  console.log("Run directly rather than imported");
+}
@@ -27,7 +27,9 @@ async function runTests(): Promise<void> {
 
     // Test 4: JSON module
     console.log("\n📄 Test 4: JSON module");
-    const jsonModule = await import("./config.json");
+    const jsonModule = await import("./config.json", {
+      with: { type: "json" },
+    });
     console.log("   ✅ Package name:", jsonModule.default.name);
     console.log("   ✅ Features:", jsonModule.default.features);
 
@@ -0,0 +1,6 @@
+// Packages are looked up in node_modules, then in the npm registry cache
+import greeter from "npm:greeter@^1";
+import { shout } from "npm:greeter@^1/shout";
+
+console.log(greeter.greet("Andromeda"));
+console.log(shout("Andromeda"));
@@ -0,0 +1,25 @@
+// Run with `andromeda run examples/permissions.ts` to see the denied
+// operations, with `--allow-env=HOME --allow-read=./examples` to grant them,
+// or with `--prompt` to be asked on the terminal.
+
+const env = await Andromeda.permissions.request({
+  name: "env",
+  variable: "HOME",
+});
+if (env.state === "granted") {
+  console.log("HOME:", Andromeda.env.get("HOME"));
+} else {
+  console.log("Environment access declined, skipping HOME lookup");
+}
+
+try {
+  const content = Andromeda.readTextFileSync("./examples/permissions.ts");
+  console.log(`Read ${content.length} characters`);
+} catch (error) {
+  if (error instanceof Andromeda.errors.PermissionDenied) {
+    console.log("Read access denied:", error.message);
+  }
+}
+
+const revoked = await Andromeda.permissions.revoke({ name: "env" });
+console.log("env permission after revoke:", revoked.state);
@@ -0,0 +1,31 @@
+// HTTP/2 with prior knowledge (h2c)
+// The server answers HTTP/1.1 and HTTP/2 on the same port. The client only
+// speaks HTTP/2, so its requests are multiplexed over a single connection.
+//
+//   curl --http2-prior-knowledge http://127.0.0.1:8082/
+
+const controller = new AbortController();
+
+Andromeda.serve({
+  port: 8082,
+  signal: controller.signal,
+  onListen: async ({ hostname, port }) => {
+    console.log(`🚀 Server on http://${hostname}:${port}/`);
+
+    const client = Andromeda.createHttpClient({ http1: false, http2: true });
+    const responses = await Promise.all(
+      [1, 2, 3].map((n) =>
+        fetch(`http://${hostname}:${port}/${n}`, { client } as RequestInit)
+      ),
+    );
+    for (const response of responses) {
+      console.log(response.status, await response.text());
+    }
+    client.close();
+    controller.abort();
+  },
+  handler: (req) => {
+    const url = new URL(req.url);
+    return new Response(`Hello from ${url.pathname}`);
+  },
+});
@@ -0,0 +1,27 @@
+// Andromeda.serve over HTTPS
+// Serves localhost with a default certificate and example.test through SNI.
+// The certificates in ./tls are self-signed, for local testing only:
+//
+//   curl --cacert examples/serve/tls/localhost.pem https://localhost:8443/
+//   curl --cacert examples/serve/tls/example.test.pem \
+//     --resolve example.test:8443:127.0.0.1 https://example.test:8443/
+
+const dir = "examples/serve/tls";
+
+Andromeda.serve({
+  port: 8443,
+  cert: Andromeda.readTextFileSync(`${dir}/localhost.pem`),
+  key: Andromeda.readTextFileSync(`${dir}/localhost-key.pem`),
+  sni: [{
+    hostname: "example.test",
+    cert: Andromeda.readTextFileSync(`${dir}/example.test.pem`),
+    key: Andromeda.readTextFileSync(`${dir}/example.test-key.pem`),
+  }],
+  onListen: ({ hostname, port }) => {
+    console.log(`🔒 HTTPS server on https://${hostname}:${port}/`);
+  },
+  handler: (req) => {
+    const url = new URL(req.url);
+    return new Response(`Hello from ${url.hostname} over ${url.protocol}\n`);
+  },
+});
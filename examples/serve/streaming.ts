@@ -0,0 +1,46 @@
+// Andromeda.serve with streaming bodies
+// Demonstrates echoing request bodies, streaming responses and shutting down
+
+const controller = new AbortController();
+
+Andromeda.serve({
+  port: 8000,
+  signal: controller.signal,
+  onListen: ({ hostname, port }) => {
+    console.log(`🚀 Streaming server on http://${hostname}:${port}/`);
+  },
+  handler: (req) => {
+    const url = new URL(req.url);
+
+    // curl -T large-file.bin http://localhost:8000/echo
+    if (url.pathname === "/echo" && req.body) {
+      return new Response(req.body, {
+        headers: { "Content-Type": "application/octet-stream" },
+      });
+    }
+
+    // curl -N http://localhost:8000/ticks
+    if (url.pathname === "/ticks") {
+      let count = 0;
+      const body = new ReadableStream<Uint8Array>({
+        async pull(streamController) {
+          await Andromeda.sleep(500);
+          count++;
+          streamController.enqueue(new TextEncoder().encode(`tick ${count}\n`));
+          if (count === 5) streamController.close();
+        },
+      });
+      return new Response(body, {
+        headers: { "Content-Type": "text/plain" },
+      });
+    }
+
+    // curl http://localhost:8000/shutdown
+    if (url.pathname === "/shutdown") {
+      controller.abort();
+      return new Response("Shutting down\n");
+    }
+
+    return new Response("Try /echo, /ticks or /shutdown\n");
+  },
+});
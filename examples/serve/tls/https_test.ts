@@ -0,0 +1,113 @@
+// Run with `andromeda test examples/serve/tls` from the repository root.
+// Requires `curl` and the self-signed certificates next to this file.
+
+const dir = "examples/serve/tls";
+const localhostCert = Andromeda.readTextFileSync(`${dir}/localhost.pem`);
+const localhostKey = Andromeda.readTextFileSync(`${dir}/localhost-key.pem`);
+const exampleCert = Andromeda.readTextFileSync(`${dir}/example.test.pem`);
+const exampleKey = Andromeda.readTextFileSync(`${dir}/example.test-key.pem`);
+
+function assertEquals(actual: unknown, expected: unknown) {
+  if (actual !== expected) {
+    throw new Error(`Expected ${expected}, got ${actual}`);
+  }
+}
+
+async function curl(args: string[]): Promise<string> {
+  const output = await new Andromeda.Command("curl", {
+    args: ["--silent", "--show-error", ...args],
+  }).output();
+  const text = new TextDecoder().decode(output.stdout);
+  if (output.code !== 0) {
+    throw new Error(
+      `curl exited with ${output.code}: ${
+        new TextDecoder().decode(output.stderr)
+      }`,
+    );
+  }
+  return text;
+}
+
+function listening(): [Promise<number>, (port: number) => void] {
+  let resolve!: (port: number) => void;
+  const promise = new Promise<number>((r) => (resolve = r));
+  return [promise, resolve];
+}
+
+Andromeda.test("serve HTTPS with SNI", async () => {
+  const controller = new AbortController();
+  const [ready, onPort] = listening();
+  const server = Andromeda.serve({
+    port: 0,
+    signal: controller.signal,
+    cert: localhostCert,
+    key: localhostKey,
+    sni: [{ hostname: "example.test", cert: exampleCert, key: exampleKey }],
+    onListen: ({ port }) => onPort(port),
+    handler: (req) => new Response(new URL(req.url).href),
+  });
+  const port = await ready;
+
+  try {
+    // The default certificate is used for names without an SNI entry
+    assertEquals(
+      await curl([
+        "--cacert",
+        `${dir}/localhost.pem`,
+        `https://localhost:${port}/default`,
+      ]),
+      `https://localhost:${port}/default`,
+    );
+    // Verifying against the example.test CA only succeeds if SNI selected it
+    assertEquals(
+      await curl([
+        "--cacert",
+        `${dir}/example.test.pem`,
+        "--resolve",
+        `example.test:${port}:127.0.0.1`,
+        `https://example.test:${port}/sni`,
+      ]),
+      `https://example.test:${port}/sni`,
+    );
+  } finally {
+    controller.abort();
+    await server;
+  }
+});
+
+Andromeda.test("listenTls negotiates ALPN", async () => {
+  const listener = Andromeda.listenTls({
+    hostname: "127.0.0.1",
+    port: 0,
+    cert: localhostCert,
+    key: localhostKey,
+    alpnProtocols: ["http/1.1"],
+  });
+  const port = listener.addr.port;
+
+  const request = curl([
+    "--cacert",
+    `${dir}/localhost.pem`,
+    `https://localhost:${port}/`,
+  ]);
+  const conn = await listener.accept();
+  try {
+    assertEquals(conn.alpnProtocol, "http/1.1");
+    assertEquals(conn.serverName, "localhost");
+    // Read until the end of the request head before answering
+    let head = "";
+    const decoder = new TextDecoder();
+    while (!head.includes("\r\n\r\n")) {
+      const chunk = await conn.read();
+      if (chunk === null) break;
+      head += decoder.decode(chunk);
+    }
+    await conn.write(
+      "HTTP/1.1 200 OK\r\ncontent-length: 2\r\nconnection: close\r\n\r\nok",
+    );
+  } finally {
+    conn.close();
+    listener.close();
+  }
+  assertEquals(await request, "ok");
+});
@@ -0,0 +1,52 @@
+// WebSocket echo server and client
+// The server upgrades requests to /ws, the client connects to it, sends a
+// text and a binary message and closes once both came back.
+
+const controller = new AbortController();
+
+Andromeda.serve({
+  port: 8081,
+  signal: controller.signal,
+  onListen: ({ hostname, port }) => {
+    console.log(`🔌 WebSocket server on ws://${hostname}:${port}/ws`);
+    connect(port);
+  },
+  handler: (req) => {
+    if (new URL(req.url).pathname !== "/ws") {
+      return new Response("Expected a WebSocket upgrade on /ws", {
+        status: 426,
+      });
+    }
+    const { socket, response } = Andromeda.upgradeWebSocket(req, {
+      protocol: "echo",
+    });
+    socket.onmessage = (event) => socket.send(event.data);
+    socket.onclose = (event) => {
+      console.log(`server: closed with ${event.code} ${event.reason}`);
+    };
+    return response;
+  },
+});
+
+function connect(port: number) {
+  const socket = new WebSocket(`ws://127.0.0.1:${port}/ws`, ["echo"]);
+  socket.binaryType = "arraybuffer";
+  let received = 0;
+
+  socket.onopen = () => {
+    console.log(`client: connected, protocol ${socket.protocol}`);
+    socket.send("Hello, WebSocket!");
+    socket.send(new Uint8Array([1, 2, 3]));
+  };
+  socket.onmessage = (event) => {
+    const data = event.data instanceof ArrayBuffer ?
+      `bytes ${new Uint8Array(event.data).join(",")}` :
+      event.data;
+    console.log(`client: received ${data}`);
+    if (++received === 2) socket.close(1000, "done");
+  };
+  socket.onclose = (event) => {
+    console.log(`client: closed cleanly: ${event.wasClean}`);
+    controller.abort();
+  };
+}
@@ -0,0 +1,37 @@
+// Run with `andromeda test examples/test`
+
+function assertEquals(actual: unknown, expected: unknown) {
+  if (actual !== expected) {
+    throw new Error(`Expected ${expected}, got ${actual}`);
+  }
+}
+
+Andromeda.test("addition", () => {
+  assertEquals(1 + 2, 3);
+});
+
+Andromeda.test("async test", async () => {
+  await Andromeda.sleep(10);
+  assertEquals(await Promise.resolve(42), 42);
+});
+
+Andromeda.test("steps", async (t) => {
+  let total = 0;
+  await t.step("add one", () => {
+    total += 1;
+  });
+  await t.step("add two", async (t) => {
+    await t.step("nested", () => {
+      total += 2;
+    });
+  });
+  assertEquals(total, 3);
+});
+
+Andromeda.test("with a timeout", { timeout: 1000 }, async () => {
+  await Andromeda.sleep(5);
+});
+
+Andromeda.test.ignore("not implemented yet", () => {
+  throw new Error("unreachable");
+});
@@ -0,0 +1,20 @@
+// Run with `andromeda run --allow-read examples/worker/main.ts`
+
+const worker = new Worker(new URL("./primes.ts", import.meta.url), {
+  type: "module",
+  name: "primes",
+});
+
+worker.onmessage = (event) => {
+  const { limit, count, elapsed } = event.data;
+  console.log(`Found ${count} primes below ${limit} in ${elapsed}ms`);
+  worker.terminate();
+};
+
+worker.onerror = (event) => {
+  console.error("Worker failed:", event.message);
+  event.preventDefault();
+};
+
+worker.postMessage({ limit: 100_000 });
+console.log("The main thread stays responsive while the worker counts");
@@ -0,0 +1,23 @@
+// Worker module used by examples/worker/main.ts
+
+function countPrimes(limit: number): number {
+  const sieve = new Uint8Array(limit);
+  let count = 0;
+  for (let i = 2; i < limit; i++) {
+    if (sieve[i]) continue;
+    count++;
+    for (let j = i * i; j < limit; j += i) sieve[j] = 1;
+  }
+  return count;
+}
+
+self.onmessage = (event: MessageEvent) => {
+  const { limit } = event.data;
+  const start = performance.now();
+  const count = countPrimes(limit);
+  postMessage({
+    limit,
+    count,
+    elapsed: Math.round(performance.now() - start),
+  });
+};
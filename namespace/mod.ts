@@ -46,6 +46,66 @@ type Signal =
 // Internal signal listener storage
 const signalListeners: Map<Signal, Set<() => void>> = new Map();
 
+/**
+ * Error thrown when an operation is not allowed by the granted permissions.
+ */
+class PermissionDenied extends Error {}
+
+Object.defineProperty(PermissionDenied.prototype, "name", {
+  value: "PermissionDenied",
+  writable: true,
+  enumerable: false,
+  configurable: true,
+});
+
+// Errors of denied permission checks raised by the runtime share this prototype
+__andromeda__.internal_permissions_set_error_prototype(PermissionDenied.prototype);
+
+/**
+ * Describes a permission and, optionally, the target it applies to.
+ */
+type PermissionDescriptor =
+  | { name: "read" | "write" | "ffi"; path?: string | URL }
+  | { name: "net"; host?: string }
+  | { name: "env"; variable?: string }
+  | { name: "run"; command?: string };
+
+/**
+ * State of a permission, `prompt` means the user will be asked on the next request.
+ */
+type PermissionState = "granted" | "prompt" | "denied";
+
+/**
+ * The result of a permission query, request or revocation.
+ */
+class PermissionStatus {
+  readonly state: PermissionState;
+
+  constructor(state: PermissionState) {
+    this.state = state;
+  }
+}
+
+// Extract the target of a permission descriptor, `undefined` means every target
+function permissionTarget(desc: PermissionDescriptor): string | undefined {
+  switch (desc.name) {
+    case "read":
+    case "write":
+    case "ffi":
+      return desc.path instanceof URL ? desc.path.pathname : desc.path;
+    case "net":
+      return desc.host;
+    case "env":
+      return desc.variable;
+    case "run":
+      return desc.command;
+    default:
+      throw new TypeError(
+        `Unknown permission name: "${(desc as { name: string }).name}"`,
+      );
+  }
+}
+
 /**
  * Andromeda namespace for the Andromeda runtime.
  */
@@ -55,6 +115,85 @@ const Andromeda = {
    */
   args: __andromeda__.internal_get_cli_args(),
 
+  /**
+   * Error classes thrown by the runtime.
+   *
+   * @example
+   * ```ts
+   * try {
+   *   Andromeda.readTextFileSync("/etc/hosts");
+   * } catch (error) {
+   *   if (error instanceof Andromeda.errors.PermissionDenied) {
+   *     console.log("Run again with --allow-read");
+   *   }
+   * }
+   * ```
+   */
+  errors: {
+    PermissionDenied,
+  },
+
+  /**
+   * permissions namespace for querying, requesting and revoking permissions at runtime.
+   */
+  permissions: {
+    /**
+     * The `query` function returns the current state of a permission without prompting.
+     *
+     * @example
+     * ```ts
+     * const status = await Andromeda.permissions.query({ name: "read", path: "./data" });
+     * console.log(status.state); // "granted", "prompt" or "denied"
+     * ```
+     */
+    query(desc: PermissionDescriptor): Promise<PermissionStatus> {
+      const target = permissionTarget(desc);
+      return Promise.resolve(
+        new PermissionStatus(
+          __andromeda__.internal_permissions_query(desc.name, target),
+        ),
+      );
+    },
+
+    /**
+     * The `request` function asks the user for a permission when it is in the
+     * `prompt` state. The answer is remembered for the rest of the session.
+     *
+     * @example
+     * ```ts
+     * const status = await Andromeda.permissions.request({ name: "net", host: "example.com" });
+     * if (status.state !== "granted") {
+     *   console.log("Running offline");
+     * }
+     * ```
+     */
+    request(desc: PermissionDescriptor): Promise<PermissionStatus> {
+      const target = permissionTarget(desc);
+      return Promise.resolve(
+        new PermissionStatus(
+          __andromeda__.internal_permissions_request(desc.name, target),
+        ),
+      );
+    },
+
+    /**
+     * The `revoke` function removes a granted permission for the rest of the session.
+     *
+     * @example
+     * ```ts
+     * await Andromeda.permissions.revoke({ name: "env" });
+     * ```
+     */
+    revoke(desc: PermissionDescriptor): Promise<PermissionStatus> {
+      const target = permissionTarget(desc);
+      return Promise.resolve(
+        new PermissionStatus(
+          __andromeda__.internal_permissions_revoke(desc.name, target),
+        ),
+      );
+    },
+  },
+
   // File operations
   /**
    * The readTextFileSync function reads a text file from the filesystem.
@@ -677,6 +816,57 @@ const Andromeda = {
     }
   },
 
+  /**
+   * Command describes a subprocess to run. Requires `--allow-run`.
+   *
+   * @example
+   * ```ts
+   * const { code, stdout } = await new Andromeda.Command("git", {
+   *   args: ["status", "--short"],
+   * }).output();
+   * console.log(code, new TextDecoder().decode(stdout));
+   * ```
+   */
+  // @ts-ignore - internal use
+  Command: globalThis.__andromeda_command,
+
+  /**
+   * Listen for TLS connections. Requires `--allow-net`.
+   *
+   * @example
+   * ```ts
+   * const listener = Andromeda.listenTls({
+   *   port: 8443,
+   *   cert: Andromeda.readTextFileSync("./cert.pem"),
+   *   key: Andromeda.readTextFileSync("./key.pem"),
+   *   alpnProtocols: ["echo"],
+   * });
+   * for await (const conn of listener) {
+   *   conn.readable.pipeTo(conn.writable);
+   * }
+   * ```
+   */
+  // @ts-ignore - internal use
+  listenTls: globalThis.__andromeda_listen_tls,
+
+  /**
+   * Register a test, run by `andromeda test`.
+   *
+   * @example
+   * ```ts
+   * Andromeda.test("addition", () => {
+   *   if (1 + 1 !== 2) throw new Error("math is broken");
+   * });
+   *
+   * Andromeda.test("steps", async (t) => {
+   *   await t.step("first", () => {});
+   *   await t.step({ name: "slow", timeout: 1000, fn: () => Andromeda.sleep(10) });
+   * });
+   * ```
+   */
+  // @ts-ignore - internal use
+  test: globalThis.__andromeda_test,
+
   /**
    * Creates an HTTP server that listens for requests.
    *
@@ -709,6 +899,47 @@ const Andromeda = {
     }
     return httpServe;
   },
+
+  /**
+   * Creates an HTTP client with its own connection pool, for use as the
+   * `client` option of `fetch`. HTTPS servers are asked for HTTP/2 with ALPN.
+   * With `http1: false`, plain `http:` URLs use HTTP/2 with prior knowledge.
+   *
+   * @example
+   * ```ts
+   * const client = Andromeda.createHttpClient({ http1: false, http2: true });
+   * const response = await fetch("http://localhost:8080/", { client });
+   * client.close();
+   * ```
+   */
+  createHttpClient(options?: { http1?: boolean; http2?: boolean; }) {
+    // @ts-ignore - internal use
+    return globalThis.__andromeda_create_http_client(options);
+  },
+
+  /**
+   * Upgrades a request received by `Andromeda.serve` to a WebSocket. Return
+   * the `response` from the handler, the socket opens once it was sent.
+   *
+   * @example
+   * ```ts
+   * Andromeda.serve((req) => {
+   *   const { socket, response } = Andromeda.upgradeWebSocket(req);
+   *   socket.onmessage = (event) => socket.send(event.data);
+   *   return response;
+   * });
+   * ```
+   */
+  get upgradeWebSocket() {
+    // @ts-ignore - internal use
+    const upgrade = globalThis.__andromeda_upgrade_websocket;
+    if (typeof upgrade !== "function") {
+      throw new Error(
+        "HTTP extension is not available. Make sure the 'serve' feature is enabled.",
+      );
+    }
+    return upgrade;
+  },
 };
 
 /**
@@ -201,6 +201,7 @@ impl WptTestExecutor {
         // Spawn the child process
         let mut child = Command::new(&binary_path)
             .arg("run")
+            .arg("--allow-all")
             .arg(&temp_path)
             .stdout(Stdio::piped())
             .stderr(Stdio::piped())
@@ -51,12 +51,95 @@ declare function assertThrows(fn: () => void, message: string): void;
 /**
  * The Andromeda namespace for the Andromeda runtime.
  */
+/**
+ * Describes a permission and, optionally, the target it applies to.
+ * Omitting the target refers to every target of the permission.
+ */
+type PermissionDescriptor =
+  | { name: "read" | "write" | "ffi"; path?: string | URL; }
+  | { name: "net"; host?: string; }
+  | { name: "env"; variable?: string; }
+  | { name: "run"; command?: string; };
+
+/**
+ * State of a permission, `prompt` means the user will be asked on the next request.
+ */
+type PermissionState = "granted" | "prompt" | "denied";
+
+/**
+ * The result of a permission query, request or revocation.
+ */
+interface PermissionStatus {
+  readonly state: PermissionState;
+}
+
 declare namespace Andromeda {
   /**
    * The `args` property contains the command-line arguments.
    */
   const args: string[];
 
+  /**
+   * permissions namespace for querying, requesting and revoking permissions at runtime.
+   */
+  namespace permissions {
+    /**
+     * query returns the current state of a permission without prompting.
+     *
+     * @example
+     * ```ts
+     * const status = await Andromeda.permissions.query({ name: "read", path: "./data" });
+     * console.log(status.state);
+     * ```
+     */
+    function query(desc: PermissionDescriptor): Promise<PermissionStatus>;
+
+    /**
+     * request asks the user for a permission when it is in the `prompt` state.
+     * The answer is remembered for the rest of the session.
+     *
+     * @example
+     * ```ts
+     * const status = await Andromeda.permissions.request({ name: "env", variable: "HOME" });
+     * ```
+     */
+    function request(desc: PermissionDescriptor): Promise<PermissionStatus>;
+
+    /**
+     * revoke removes a granted permission for the rest of the session.
+     *
+     * @example
+     * ```ts
+     * await Andromeda.permissions.revoke({ name: "net" });
+     * ```
+     */
+    function revoke(desc: PermissionDescriptor): Promise<PermissionStatus>;
+  }
+
+  /**
+   * errors namespace for the error classes thrown by the runtime.
+   */
+  namespace errors {
+    /**
+     * Thrown when an operation is not allowed by the granted permissions,
+     * e.g. reading a file without `--allow-read`.
+     *
+     * @example
+     * ```ts
+     * try {
+     *   Andromeda.env.get("HOME");
+     * } catch (error) {
+     *   if (error instanceof Andromeda.errors.PermissionDenied) {
+     *     console.log("Run again with --allow-env");
+     *   }
+     * }
+     * ```
+     */
+    class PermissionDenied extends Error {
+      constructor(message?: string);
+    }
+  }
+
   // Text file operations
   /**
    * readTextFileSync reads a text file from the file system.
@@ -571,6 +654,213 @@ declare namespace Andromeda {
     options: { backoffSchedule?: number[]; signal?: AbortSignal; },
     handler: () => Promise<void> | void,
   ): Promise<void>;
+
+  /**
+   * How a standard stream of a subprocess is connected.
+   */
+  type CommandStdio = "piped" | "inherit" | "null";
+
+  /**
+   * CommandOptions configures a subprocess created with {@linkcode Command}.
+   */
+  interface CommandOptions {
+    /** Arguments passed to the program. */
+    args?: string[];
+    /** Working directory of the subprocess. */
+    cwd?: string | URL;
+    /** Environment variables to set for the subprocess. */
+    env?: Record<string, string>;
+    /** Start from an empty environment instead of inheriting the current one. */
+    clearEnv?: boolean;
+    stdin?: CommandStdio;
+    stdout?: CommandStdio;
+    stderr?: CommandStdio;
+  }
+
+  /**
+   * CommandStatus is the exit status of a subprocess.
+   */
+  interface CommandStatus {
+    success: boolean;
+    /** The exit code, `128 + signal number` when terminated by a signal. */
+    code: number;
+    /** The name of the signal that terminated the subprocess, if any. */
+    signal: string | null;
+  }
+
+  /**
+   * CommandOutput is the exit status and collected output of a subprocess.
+   */
+  interface CommandOutput extends CommandStatus {
+    stdout: Uint8Array;
+    stderr: Uint8Array;
+  }
+
+  /**
+   * ChildProcess is a handle to a running subprocess.
+   */
+  class ChildProcess {
+    /** The process id of the child. */
+    readonly pid: number;
+    /** The child's stdin, available when spawned with `stdin: "piped"`. */
+    readonly stdin: WritableStream<Uint8Array>;
+    /** The child's stdout, available when spawned with `stdout: "piped"`. */
+    readonly stdout: ReadableStream<Uint8Array>;
+    /** The child's stderr, available when spawned with `stderr: "piped"`. */
+    readonly stderr: ReadableStream<Uint8Array>;
+    /** Resolves with the exit status once the child exits. */
+    readonly status: Promise<CommandStatus>;
+    /** Wait for the child to exit, collecting its piped outputs. */
+    output(): Promise<CommandOutput>;
+    /** Send a signal to the child, `SIGTERM` by default. */
+    kill(signal?: string): void;
+  }
+
+  /**
+   * Command describes a subprocess to run. Requires `--allow-run`.
+   *
+   * @example
+   * ```ts
+   * const { code, stdout } = await new Andromeda.Command("git", {
+   *   args: ["status", "--short"],
+   * }).output();
+   * console.log(code, new TextDecoder().decode(stdout));
+   * ```
+   *
+   * @example Streaming
+   * ```ts
+   * const child = new Andromeda.Command("cat", {
+   *   stdin: "piped",
+   *   stdout: "piped",
+   * }).spawn();
+   * const writer = child.stdin.getWriter();
+   * await writer.write(new TextEncoder().encode("hello"));
+   * await writer.close();
+   * const { stdout } = await child.output();
+   * ```
+   */
+  class Command {
+    constructor(command: string | URL, options?: CommandOptions);
+    /** Spawn the subprocess, inheriting stdio unless configured otherwise. */
+    spawn(): ChildProcess;
+    /** Run the subprocess to completion, collecting its stdout and stderr. */
+    output(): Promise<CommandOutput>;
+    /** Run the subprocess to completion, inheriting its stdout and stderr. */
+    status(): Promise<CommandStatus>;
+  }
+
+  /**
+   * TestContext is passed to test functions to run sub-tests.
+   */
+  interface TestContext {
+    /** The name of the running test or step. */
+    readonly name: string;
+    /** The path of the module the test was registered by. */
+    readonly origin: string;
+    /**
+     * Run a sub-test, resolving with whether it passed.
+     * A failing step fails its parent test.
+     */
+    step(definition: TestStepDefinition): Promise<boolean>;
+    step(name: string, fn: (t: TestContext) => void | Promise<void>): Promise<boolean>;
+    step(
+      name: string,
+      options: Omit<TestStepDefinition, "name" | "fn">,
+      fn: (t: TestContext) => void | Promise<void>,
+    ): Promise<boolean>;
+    step(fn: (t: TestContext) => void | Promise<void>): Promise<boolean>;
+  }
+
+  /**
+   * TestStepDefinition describes a sub-test run with {@linkcode TestContext.step}.
+   */
+  interface TestStepDefinition {
+    name: string;
+    fn: (t: TestContext) => void | Promise<void>;
+    /** Report the step without running it. */
+    ignore?: boolean;
+    /** Fail the step if it takes longer than this many milliseconds. */
+    timeout?: number;
+  }
+
+  /**
+   * TestDefinition describes a test registered with {@linkcode test}.
+   */
+  interface TestDefinition extends TestStepDefinition {
+    /** Only run this test and the other tests marked `only`, failing the run. */
+    only?: boolean;
+  }
+
+  interface TestRegistrar {
+    (definition: TestDefinition): void;
+    (name: string, fn: (t: TestContext) => void | Promise<void>): void;
+    (
+      name: string,
+      options: Omit<TestDefinition, "name" | "fn">,
+      fn: (t: TestContext) => void | Promise<void>,
+    ): void;
+    (fn: (t: TestContext) => void | Promise<void>): void;
+  }
+
+  /**
+   * Register a test, run by `andromeda test` and ignored by `andromeda run`.
+   *
+   * @example
+   * ```ts
+   * Andromeda.test("addition", () => {
+   *   if (1 + 1 !== 2) throw new Error("math is broken");
+   * });
+   *
+   * Andromeda.test("fetches in time", { timeout: 5000 }, async (t) => {
+   *   await t.step("first step", () => {});
+   * });
+   * ```
+   */
+  const test: TestRegistrar & {
+    /** Register a test that runs along with the other `only` tests, and no others. */
+    only: TestRegistrar;
+    /** Register a test that is reported but not run. */
+    ignore: TestRegistrar;
+  };
+
+  /**
+   * CreateHttpClientOptions selects the protocols an {@linkcode HttpClient}
+   * may speak.
+   */
+  interface CreateHttpClientOptions {
+    /** Whether HTTP/1.1 may be used, true by default. */
+    http1?: boolean;
+    /** Whether HTTP/2 may be used, true by default. */
+    http2?: boolean;
+  }
+
+  /**
+   * HttpClient is a pool of connections used by the `fetch` calls it is
+   * passed to as `client`.
+   */
+  interface HttpClient {
+    /** Close the client's connections once their requests completed. */
+    close(): void;
+  }
+
+  /**
+   * Create an HTTP client with its own connection pool. HTTPS servers are
+   * asked for HTTP/2 with ALPN. With `http1: false`, plain `http:` URLs are
+   * fetched over HTTP/2 with prior knowledge (h2c).
+   *
+   * @example
+   * ```ts
+   * const client = Andromeda.createHttpClient({ http1: false, http2: true });
+   * const response = await fetch("http://localhost:8080/", { client });
+   * client.close();
+   * ```
+   */
+  function createHttpClient(options?: CreateHttpClientOptions): HttpClient;
+}
+
+interface RequestInit {
+  /** The client whose connections `fetch` uses, see `Andromeda.createHttpClient`. */
+  client?: Andromeda.HttpClient;
 }
 /**
  * The `prompt` function prompts the user for input.
@@ -588,6 +878,32 @@ declare function prompt(message: string): string;
  */
 declare function confirm(message: string): boolean;
 
+/**
+ * Metadata of the current module.
+ *
+ * @example
+ * ```ts
+ * if (import.meta.main) {
+ *   console.log(Andromeda.readTextFileSync(`${import.meta.dirname}/hello.txt`));
+ * }
+ * ```
+ */
+interface ImportMeta {
+  /** URL of the module, a `file://` URL for local modules */
+  url: string;
+  /** Whether the module is the entry point of the program */
+  main: boolean;
+  /** Absolute path of the module, for local modules only */
+  filename?: string;
+  /** Absolute path of the directory of the module, for local modules only */
+  dirname?: string;
+  /**
+   * Resolve a specifier relative to the module, through the import map, into
+   * a URL. The resolved module does not have to exist.
+   */
+  resolve(specifier: string): string;
+}
+
 // Extension to Navigator interface for Web Locks API
 interface Navigator {
   /** The LockManager for Web Locks API */
@@ -639,6 +955,143 @@ declare function structuredClone<T = any>(
   options?: StructuredSerializeOptions,
 ): T;
 
+/**
+ * Options for the {@linkcode Worker} constructor.
+ */
+interface WorkerOptions {
+  /** Only module workers are supported. */
+  type: "module";
+  /** A name for the worker, available as `self.name` inside it. */
+  name?: string;
+}
+
+/**
+ * Runs a module on a separate thread with its own event loop.
+ * Messages are copied with the structured clone algorithm, and the worker
+ * inherits the permissions of its parent.
+ *
+ * @example
+ * ```ts
+ * const worker = new Worker(new URL("./worker.ts", import.meta.url), {
+ *   type: "module",
+ * });
+ * worker.onmessage = (event) => {
+ *   console.log("result:", event.data);
+ *   worker.terminate();
+ * };
+ * worker.postMessage({ numbers: [1, 2, 3] });
+ * ```
+ */
+declare class Worker extends EventTarget {
+  constructor(specifier: string | URL, options: WorkerOptions);
+  onmessage: ((event: MessageEvent) => any) | null;
+  onmessageerror: ((event: MessageEvent) => any) | null;
+  /** Called with uncaught worker errors, call `preventDefault()` to keep the parent running. */
+  onerror: ((event: ErrorEvent) => any) | null;
+  /** Send a message to the worker. Transferred values are copied. */
+  postMessage(message: any, transfer?: any[] | StructuredSerializeOptions): void;
+  /** Stop the worker immediately. */
+  terminate(): void;
+}
+
+/**
+ * Inside a worker, send a message to the parent.
+ */
+declare function postMessage(
+  message: any,
+  transfer?: any[] | StructuredSerializeOptions,
+): void;
+
+/**
+ * Inside a worker, stop the worker once the current task completes.
+ */
+declare function close(): void;
+
+/**
+ * A WebSocket client, also returned by `Andromeda.upgradeWebSocket` for the
+ * server side of a connection. Requires `--allow-net`.
+ *
+ * @example
+ * ```ts
+ * const socket = new WebSocket("wss://echo.websocket.org");
+ * socket.onopen = () => socket.send("hello");
+ * socket.onmessage = (event) => {
+ *   console.log(event.data);
+ *   socket.close(1000, "done");
+ * };
+ * ```
+ */
+declare class WebSocket extends EventTarget {
+  constructor(url: string | URL, protocols?: string | string[]);
+  static readonly CONNECTING: 0;
+  static readonly OPEN: 1;
+  static readonly CLOSING: 2;
+  static readonly CLOSED: 3;
+  readonly CONNECTING: 0;
+  readonly OPEN: 1;
+  readonly CLOSING: 2;
+  readonly CLOSED: 3;
+  readonly url: string;
+  readonly readyState: number;
+  /** Bytes passed to `send()` that have not been written yet. */
+  readonly bufferedAmount: number;
+  /** The subprotocol the server selected. */
+  readonly protocol: string;
+  readonly extensions: string;
+  /** How binary messages are delivered, `"blob"` by default. */
+  binaryType: "blob" | "arraybuffer";
+  onopen: ((event: Event) => any) | null;
+  onmessage: ((event: MessageEvent) => any) | null;
+  onerror: ((event: Event) => any) | null;
+  onclose: ((event: CloseEvent) => any) | null;
+  /** Send text, or binary data from a buffer or blob. */
+  send(data: string | ArrayBufferLike | ArrayBufferView | Blob): void;
+  /** Close with a code of 1000 or 3000 to 4999 and an optional reason. */
+  close(code?: number, reason?: string): void;
+}
+
+interface WebSocketStreamOptions {
+  protocols?: string[];
+  /** Aborts the connection while it is being established. */
+  signal?: AbortSignal;
+}
+
+interface WebSocketOpenInfo {
+  readable: ReadableStream<string | Uint8Array>;
+  writable: WritableStream<string | BufferSource>;
+  protocol: string;
+  extensions: string;
+}
+
+interface WebSocketCloseInfo {
+  closeCode?: number;
+  reason?: string;
+}
+
+/**
+ * A WebSocket exposing its messages as streams, reading only as fast as the
+ * readable is consumed.
+ *
+ * @example
+ * ```ts
+ * const wss = new WebSocketStream("wss://echo.websocket.org");
+ * const { readable, writable } = await wss.opened;
+ * const writer = writable.getWriter();
+ * await writer.write("hello");
+ * for await (const message of readable) {
+ *   console.log(message);
+ *   wss.close();
+ * }
+ * ```
+ */
+declare class WebSocketStream {
+  constructor(url: string | URL, options?: WebSocketStreamOptions);
+  readonly url: string;
+  readonly opened: Promise<WebSocketOpenInfo>;
+  readonly closed: Promise<WebSocketCloseInfo>;
+  close(closeInfo?: WebSocketCloseInfo): void;
+}
+
 /**
  * An offscreen Canvas implementation.
  */
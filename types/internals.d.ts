@@ -1939,7 +1939,35 @@ declare namespace __andromeda__ {
   export function internal_tls_close(rid: Rid): Promise<string>;
   export function internal_tls_read(rid: Rid, len: number): Promise<string>;
   export function internal_tls_write(rid: Rid, data: string): Promise<string>;
+  /**
+   * Write hex encoded bytes to a TLS stream.
+   * @returns - A promise that resolves to the number of bytes written.
+   */
+  export function internal_tls_write_bytes(
+    rid: Rid,
+    hex: string,
+  ): Promise<string>;
   export function internal_tls_get_peer_certificate(rid: Rid): Promise<string>;
+  /**
+   * Listen for TLS connections.
+   * @param options - JSON encoded certificates and ALPN protocols.
+   * @returns - A JSON string with the listener resource ID and bound address.
+   */
+  export function internal_tls_listen(
+    hostname: string,
+    port: string,
+    options: string,
+  ): string;
+  /**
+   * Accept the next TLS connection that completed its handshake.
+   * @returns - A promise that resolves to the connection as a JSON string, or
+   * "null" once the listener is closed.
+   */
+  export function internal_tls_accept(rid: Rid): Promise<string>;
+  /**
+   * Stop a TLS listener from accepting connections.
+   */
+  export function internal_tls_listener_close(rid: Rid): void;
   export function ffi_dlopen(filename: string, symbols: unknown): number;
   export function ffi_dlopen_get_symbol(
     libId: number,
@@ -2018,4 +2046,210 @@ declare namespace __andromeda__ {
     name: string,
     lockId: string,
   ): Promise<string>;
+
+  /**
+   * Start the native HTTP/1.1 and HTTP/2 server behind `Andromeda.serve`.
+   * @param hostname - The hostname or IP address to listen on.
+   * @param port - The port to listen on, "0" picks a free port.
+   * @param reusePort - "true" to share the port with other listeners.
+   * @param tls - JSON encoded TLS server options, or "" to serve plain HTTP.
+   * @returns - A JSON string with the server resource ID and bound address.
+   */
+  export function http_serve(
+    hostname: string,
+    port: string,
+    reusePort: string,
+    tls: string,
+  ): string;
+
+  /**
+   * Wait for the next request on an HTTP server.
+   * @param serverId - The server resource ID.
+   * @returns - A promise that resolves to the request as a JSON string, or
+   * "null" once the server is closed.
+   */
+  export function http_next_request(serverId: number): Promise<string>;
+
+  /**
+   * Read the next chunk of a request body.
+   * @param requestId - The request resource ID.
+   * @returns - A promise that resolves to the chunk as hex, or an empty
+   * string at the end of the body.
+   */
+  export function http_request_read(requestId: number): Promise<string>;
+
+  /**
+   * Send the status and headers of a response.
+   * @param requestId - The request resource ID.
+   * @param status - The response status code.
+   * @param headers - The response headers as a JSON array of pairs.
+   * @param body - The whole body as hex when not streaming.
+   * @param streaming - "true" to send the body with `http_response_write`.
+   */
+  export function http_respond(
+    requestId: number,
+    status: string,
+    headers: string,
+    body: string,
+    streaming: string,
+  ): void;
+
+  /**
+   * Write a chunk of a streaming response body.
+   * @param requestId - The request resource ID.
+   * @param chunk - The chunk as hex.
+   * @returns - A promise that resolves once the connection accepted the chunk.
+   */
+  export function http_response_write(
+    requestId: number,
+    chunk: string,
+  ): Promise<string>;
+
+  /**
+   * Finish a streaming response.
+   * @param requestId - The request resource ID.
+   * @param aborted - "true" to abort the response instead of completing it.
+   */
+  export function http_response_end(requestId: number, aborted: string): void;
+
+  /**
+   * Stop an HTTP server from accepting connections.
+   * @param serverId - The server resource ID.
+   */
+  export function http_server_close(serverId: number): void;
+
+  /**
+   * Start sending a request with the native HTTP client.
+   * @param clientId - The client resource ID, or -1 for the shared client.
+   * @param method - The request method.
+   * @param url - An http: or https: URL.
+   * @param headers - A JSON string of [name, value] pairs.
+   * @param body - The body as hex.
+   * @param streamBody - "true" to write the body with
+   * internal_fetch_body_write instead.
+   * @returns - The request resource ID.
+   */
+  export function internal_fetch(
+    clientId: number,
+    method: string,
+    url: string,
+    headers: string,
+    body: string,
+    streamBody: string,
+  ): number;
+
+  /**
+   * Write a chunk of a streamed request body.
+   * @param requestId - The request resource ID.
+   * @param chunk - The chunk as hex.
+   * @returns - A promise that resolves once the chunk was taken.
+   */
+  export function internal_fetch_body_write(
+    requestId: number,
+    chunk: string,
+  ): Promise<string>;
+
+  /**
+   * Finish a streamed request body.
+   * @param requestId - The request resource ID.
+   * @param aborted - "true" to abort the request instead.
+   */
+  export function internal_fetch_body_end(
+    requestId: number,
+    aborted: string,
+  ): void;
+
+  /**
+   * Wait for the response to a request.
+   * @param requestId - The request resource ID.
+   * @returns - A promise that resolves to a JSON string with the status,
+   * status text, headers and HTTP version of the response.
+   */
+  export function internal_fetch_response(requestId: number): Promise<string>;
+
+  /**
+   * Read the next chunk of a decoded response body.
+   * @param requestId - The request resource ID.
+   * @returns - A promise that resolves to the chunk as hex, or an empty
+   * string once the body is complete.
+   */
+  export function internal_fetch_read(requestId: number): Promise<string>;
+
+  /**
+   * Release a request, aborting it if it is still in flight.
+   * @param requestId - The request resource ID.
+   */
+  export function internal_fetch_close(requestId: number): void;
+
+  /**
+   * Create an HTTP client with its own connection pool.
+   * @param http1 - "true" to allow HTTP/1.1.
+   * @param http2 - "true" to allow HTTP/2.
+   * @returns - The client resource ID.
+   */
+  export function internal_fetch_client_create(
+    http1: string,
+    http2: string,
+  ): number;
+
+  /**
+   * Close an HTTP client and its idle connections.
+   * @param clientId - The client resource ID.
+   */
+  export function internal_fetch_client_close(clientId: number): void;
+
+  /**
+   * Prepare to upgrade a request to a WebSocket.
+   * @param requestId - The request resource ID.
+   * @param key - The request's Sec-WebSocket-Key header.
+   * @param protocol - The selected subprotocol, or "".
+   * @returns - A JSON string with the socket resource ID and the
+   * Sec-WebSocket-Accept value for the 101 response.
+   */
+  export function http_upgrade_websocket(
+    requestId: number,
+    key: string,
+    protocol: string,
+  ): string;
+
+  /**
+   * Connect a WebSocket.
+   * @param url - A ws: or wss: URL.
+   * @param protocols - The subprotocols to offer, comma separated.
+   * @returns - The socket resource ID.
+   */
+  export function internal_ws_connect(url: string, protocols: string): number;
+
+  /**
+   * Wait for the next event of a WebSocket.
+   * @param socketId - The socket resource ID.
+   * @returns - A promise that resolves to the event as a JSON string, or
+   * "null" once the socket is gone.
+   */
+  export function internal_ws_next(socketId: number): Promise<string>;
+
+  /**
+   * Send a WebSocket message.
+   * @param socketId - The socket resource ID.
+   * @param data - The text, or the bytes as hex for binary messages.
+   * @param binary - "true" to send a binary message.
+   * @returns - A promise that resolves once the message was written.
+   */
+  export function internal_ws_send(
+    socketId: number,
+    data: string,
+    binary: string,
+  ): Promise<string>;
+
+  /**
+   * Start the closing handshake of a WebSocket, or abort its connection.
+   * @param socketId - The socket resource ID.
+   * @param code - The close code, or "" to send none.
+   * @param reason - The close reason.
+   */
+  export function internal_ws_close(
+    socketId: number,
+    code: string,
+    reason: string,
+  ): void;
 }